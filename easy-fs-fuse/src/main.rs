@@ -1,11 +1,17 @@
 use clap::{App, Arg};
-use easy_fs::{BlockDevice, EasyFileSystem};
-use std::fs::{read_dir, File, OpenOptions};
-use std::io::{Read, Seek, SeekFrom, Write};
+use easy_fs::{BlockDevice, EasyFileSystem, FsckReport, Inode};
+use std::collections::HashMap;
+use std::fs::{read_dir, read_link, symlink_metadata, File, OpenOptions};
+use std::io::{Error, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::Path;
 use std::sync::Arc;
 use std::sync::Mutex;
 
 const BLOCK_SZ: usize = 512;
+/// Blocks left for the swap area after the file system, SWAP_PAGES pages
+/// of the kernel.
+const SWAP_BLOCKS: u64 = 0x4000 * 8;
 
 struct BlockFile(Mutex<File>);
 
@@ -49,9 +55,25 @@ fn easy_fs_pack() -> std::io::Result<()> {
                 .takes_value(true)
                 .help("Executable target dir(with backslash)"),
         )
+        .arg(
+            Arg::with_name("root")
+                .short("r")
+                .long("root")
+                .takes_value(true)
+                .help("Host dir whose tree goes into the root as well, with permissions and times"),
+        )
+        .arg(
+            Arg::with_name("check")
+                .short("c")
+                .long("check")
+                .help("Check and repair fs.img in the target dir rather than packing it"),
+        )
         .get_matches();
-    let src_path = matches.value_of("source").unwrap();
     let target_path = matches.value_of("target").unwrap();
+    if matches.is_present("check") {
+        return easy_fs_check(target_path);
+    }
+    let src_path = matches.value_of("source").unwrap();
     println!("src_path = {}\ntarget_path = {}", src_path, target_path);
     let block_file = Arc::new(BlockFile(Mutex::new({
         let f = OpenOptions::new()
@@ -59,10 +81,10 @@ fn easy_fs_pack() -> std::io::Result<()> {
             .write(true)
             .create(true)
             .open(format!("{}{}", target_path, "fs.img"))?;
-        f.set_len(32 * 2048 * 512).unwrap();
+        f.set_len((32 * 2048 + SWAP_BLOCKS) * 512).unwrap();
         f
     })));
-    // 32MiB, at most 4095 files
+    // 32MiB, at most 4095 files, followed by the swap area of the kernel
     let efs = EasyFileSystem::create(block_file, 32 * 2048, 1);
     let root_inode = Arc::new(EasyFileSystem::root_inode(&efs));
     let apps: Vec<_> = read_dir(src_path)
@@ -83,11 +105,125 @@ fn easy_fs_pack() -> std::io::Result<()> {
         let inode = root_inode.create(app.as_str()).unwrap();
         // write data to easy-fs
         inode.write_at(0, all_data.as_slice());
+        // which anyone may run
+        inode.chmod(0o755);
     }
     // list apps
     // for app in root_inode.ls() {
     //     println!("{}", app);
     // }
+    if let Some(root_path) = matches.value_of("root") {
+        println!("root_path = {}", root_path);
+        mirror(Path::new(root_path), &root_inode, &mut HashMap::new())?;
+    }
+    let report = efs.lock().check(false);
+    verify(report)
+}
+
+/// Copy the tree under `host_dir` into `dir`: directories, files,
+/// symbolic links and FIFOs, with their permission bits and modification
+/// times. Files with several names on the host keep them, through `links`,
+/// by host device and inode. Everything belongs to root, as the owners of
+/// the host mean nothing inside.
+fn mirror(
+    host_dir: &Path,
+    dir: &Arc<Inode>,
+    links: &mut HashMap<(u64, u64), Arc<Inode>>,
+) -> std::io::Result<()> {
+    let mut entries: Vec<_> = read_dir(host_dir)?.collect::<Result<_, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let host_path = entry.path();
+        let name = entry
+            .file_name()
+            .into_string()
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "name not in UTF-8"))?;
+        let unfit = || {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("cannot make {}", host_path.display()),
+            )
+        };
+        let metadata = symlink_metadata(&host_path)?;
+        let file_type = metadata.file_type();
+        let inode = if file_type.is_dir() {
+            let inode = match dir.find(&name) {
+                Some(inode) if inode.is_dir() => inode,
+                Some(_) => return Err(unfit()),
+                None => dir.mkdir(&name).ok_or_else(unfit)?,
+            };
+            mirror(&host_path, &inode, links)?;
+            inode
+        } else if file_type.is_symlink() {
+            let target = read_link(&host_path)?;
+            let target = target.to_str().ok_or_else(unfit)?;
+            dir.symlink(&name, target).ok_or_else(unfit)?
+        } else if file_type.is_file() {
+            let key = (metadata.dev(), metadata.ino());
+            if let Some(inode) = links.get(&key) {
+                if !dir.link(&name, inode) {
+                    return Err(unfit());
+                }
+                continue;
+            }
+            let inode = dir.create(&name).ok_or_else(unfit)?;
+            let mut data = Vec::new();
+            File::open(&host_path)?.read_to_end(&mut data)?;
+            inode.write_at(0, &data);
+            if metadata.nlink() > 1 {
+                links.insert(key, inode.clone());
+            }
+            inode
+        } else if file_type.is_fifo() {
+            dir.mkfifo(&name).ok_or_else(unfit)?
+        } else {
+            println!("{}: skipped, not a file", host_path.display());
+            continue;
+        };
+        if !file_type.is_symlink() {
+            inode.chmod(metadata.mode());
+        }
+        inode.set_mtime(metadata.mtime().max(0) as u64);
+    }
+    Ok(())
+}
+
+/// Fail unless fsck found the image as it should be.
+fn verify(report: FsckReport) -> std::io::Result<()> {
+    for problem in report.problems.iter() {
+        println!("{}", problem);
+    }
+    if !report.problems.is_empty() {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "the image is inconsistent",
+        ));
+    }
+    println!(
+        "{} inodes and {} blocks in use",
+        report.inodes, report.blocks
+    );
+    Ok(())
+}
+
+fn easy_fs_check(target_path: &str) -> std::io::Result<()> {
+    let block_file = Arc::new(BlockFile(Mutex::new(
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(format!("{}{}", target_path, "fs.img"))?,
+    )));
+    let efs = EasyFileSystem::open(block_file);
+    let report = efs.lock().check(true);
+    for problem in report.problems.iter() {
+        println!("{}, repaired", problem);
+    }
+    println!(
+        "{} inodes and {} blocks in use, {} problems",
+        report.inodes,
+        report.blocks,
+        report.problems.len()
+    );
     Ok(())
 }
 
@@ -110,6 +246,27 @@ fn efs_test() -> std::io::Result<()> {
     for name in root_inode.ls() {
         println!("{}", name);
     }
+    let dir = root_inode.mkdir("dir").unwrap();
+    assert!(dir.is_dir());
+    assert_eq!(dir.ls(), [".", ".."]);
+    dir.mkdir("subdir").unwrap();
+    assert!(!root_inode.rmdir("dir"));
+    assert!(dir.rmdir("subdir"));
+    assert!(root_inode.rmdir("dir"));
+    assert!(root_inode.find("dir").is_none());
+    let fileb = root_inode.find("fileb").unwrap();
+    assert!(root_inode.link("filec", &fileb));
+    assert!(root_inode.unlink("fileb"));
+    assert!(root_inode.rename("filec", &root_inode, "fileb"));
+    assert!(root_inode.find("filec").is_none());
+    assert!(root_inode.find("fileb").is_some());
+    let link = root_inode.symlink("link", "/fileb").unwrap();
+    assert!(link.is_symlink() && !link.is_dir());
+    let mut target = [0u8; 16];
+    let len = link.read_at(0, &mut target);
+    assert_eq!(&target[..len], b"/fileb");
+    assert!(root_inode.symlink("link", "/filea").is_none());
+    assert!(root_inode.unlink("link"));
     let filea = root_inode.find("filea").unwrap();
     let greet_str = "Hello, world!";
     filea.write_at(0, greet_str.as_bytes());
@@ -153,3 +310,90 @@ fn efs_test() -> std::io::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn fsck_test() -> std::io::Result<()> {
+    let block_file = Arc::new(BlockFile(Mutex::new({
+        let f = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open("target/fsck.img")?;
+        f.set_len(4096 * 512).unwrap();
+        f
+    })));
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    root_inode.mkdir("dir").unwrap();
+    let file = root_inode.create("file").unwrap();
+    file.write_at(0, &[1u8; 40 * BLOCK_SZ]);
+    assert!(efs.lock().check(false).problems.is_empty());
+    // an unlinked file still open when the system goes down
+    assert!(root_inode.unlink("file"));
+    let efs = EasyFileSystem::open(block_file);
+    let report = efs.lock().check(true);
+    assert_eq!(report.problems[0], "inode 2 is in no directory");
+    // its 40 blocks and the index block
+    assert_eq!(report.problems.len(), 1 + 41);
+    let report = efs.lock().check(false);
+    assert!(report.problems.is_empty());
+    // the root and "dir", with their entries
+    assert_eq!((report.inodes, report.blocks), (2, 2));
+    // which must not free the inode again
+    std::mem::forget(file);
+    Ok(())
+}
+
+#[test]
+fn mirror_test() -> std::io::Result<()> {
+    use std::fs::{create_dir_all, hard_link, metadata, remove_dir_all, set_permissions, write};
+    use std::os::unix::fs::{symlink, PermissionsExt};
+    let host_root = Path::new("target/mirror");
+    let _ = remove_dir_all(host_root);
+    create_dir_all(host_root.join("etc/sub"))?;
+    write(host_root.join("etc/passwd"), b"root:x:0:0")?;
+    set_permissions(
+        host_root.join("etc/passwd"),
+        PermissionsExt::from_mode(0o600),
+    )?;
+    hard_link(
+        host_root.join("etc/passwd"),
+        host_root.join("etc/sub/passwd"),
+    )?;
+    write(host_root.join("etc/sub/big"), vec![3u8; 100 * BLOCK_SZ])?;
+    symlink("/etc/passwd", host_root.join("passwd"))?;
+    let block_file = Arc::new(BlockFile(Mutex::new({
+        let f = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open("target/mirror.img")?;
+        f.set_len(4096 * 512).unwrap();
+        f
+    })));
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = Arc::new(EasyFileSystem::root_inode(&efs));
+    mirror(host_root, &root_inode, &mut HashMap::new())?;
+    verify(efs.lock().check(false))?;
+
+    let efs = EasyFileSystem::open(block_file);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let etc = root_inode.find("etc").unwrap();
+    let passwd = etc.find("passwd").unwrap();
+    assert_eq!(passwd.permissions(), (0o600, 0, 0));
+    assert_eq!(passwd.nlink(), 2);
+    let host_mtime = metadata(host_root.join("etc/passwd"))?.mtime();
+    assert_eq!(passwd.mtime(), host_mtime as u64);
+    let sub = etc.find("sub").unwrap();
+    assert!(sub.is_dir());
+    assert_eq!(sub.find("passwd").unwrap().inode_id(), passwd.inode_id());
+    assert_eq!(sub.find("big").unwrap().size(), 100 * BLOCK_SZ);
+    let link = root_inode.find("passwd").unwrap();
+    assert!(link.is_symlink());
+    let mut target = [0u8; 16];
+    let len = link.read_at(0, &mut target);
+    assert_eq!(&target[..len], b"/etc/passwd");
+    // the root, etc, sub, passwd, big and the link
+    assert_eq!(efs.lock().check(false).inodes, 6);
+    Ok(())
+}
@@ -3,9 +3,14 @@ use alloc::collections::VecDeque;
 use alloc::sync::Arc;
 use alloc::vec;
 use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
 use lazy_static::*;
 use spin::Mutex;
 
+/// Block caches alive, and made since the start, to tell leaks by.
+static LIVE_CACHES: AtomicUsize = AtomicUsize::new(0);
+static MADE_CACHES: AtomicUsize = AtomicUsize::new(0);
+
 pub struct BlockCache {
     cache: Vec<u8>,
     block_id: usize,
@@ -19,6 +24,8 @@ impl BlockCache {
         // for alignment and move effciency
         let mut cache = vec![0u8; BLOCK_SZ];
         block_device.read_block(block_id, &mut cache);
+        LIVE_CACHES.fetch_add(1, Ordering::Relaxed);
+        MADE_CACHES.fetch_add(1, Ordering::Relaxed);
         Self {
             cache,
             block_id,
@@ -70,14 +77,21 @@ impl BlockCache {
 
 impl Drop for BlockCache {
     fn drop(&mut self) {
-        self.sync()
+        self.sync();
+        LIVE_CACHES.fetch_sub(1, Ordering::Relaxed);
     }
 }
 
 const BLOCK_CACHE_SIZE: usize = 16;
 
+/// The address of `block_device`, which tells devices apart.
+fn device_id(block_device: &Arc<dyn BlockDevice>) -> usize {
+    Arc::as_ptr(block_device) as *const u8 as usize
+}
+
 pub struct BlockCacheManager {
-    queue: VecDeque<(usize, Arc<Mutex<BlockCache>>)>,
+    /// caches by block id and device
+    queue: VecDeque<(usize, usize, Arc<Mutex<BlockCache>>)>,
 }
 
 impl BlockCacheManager {
@@ -92,8 +106,13 @@ impl BlockCacheManager {
         block_id: usize,
         block_device: Arc<dyn BlockDevice>,
     ) -> Arc<Mutex<BlockCache>> {
-        if let Some(pair) = self.queue.iter().find(|pair| pair.0 == block_id) {
-            Arc::clone(&pair.1)
+        let device = device_id(&block_device);
+        if let Some(entry) = self
+            .queue
+            .iter()
+            .find(|entry| entry.0 == block_id && entry.1 == device)
+        {
+            Arc::clone(&entry.2)
         } else {
             // substitute
             if self.queue.len() == BLOCK_CACHE_SIZE {
@@ -102,7 +121,7 @@ impl BlockCacheManager {
                     .queue
                     .iter()
                     .enumerate()
-                    .find(|(_, pair)| Arc::strong_count(&pair.1) == 1)
+                    .find(|(_, entry)| Arc::strong_count(&entry.2) == 1)
                 {
                     self.queue.drain(idx..=idx);
                 } else {
@@ -114,7 +133,8 @@ impl BlockCacheManager {
                 block_id,
                 Arc::clone(&block_device),
             )));
-            self.queue.push_back((block_id, Arc::clone(&block_cache)));
+            self.queue
+                .push_back((block_id, device, Arc::clone(&block_cache)));
             block_cache
         }
     }
@@ -134,9 +154,45 @@ pub fn get_block_cache(
         .get_block_cache(block_id, block_device)
 }
 
+/// Copy `block_id` of `block_device` into `buf` if it is cached, without
+/// reading it in if it is not.
+pub fn read_cached_block(
+    block_id: usize,
+    block_device: &Arc<dyn BlockDevice>,
+    buf: &mut [u8],
+) -> bool {
+    let device = device_id(block_device);
+    let cache = BLOCK_CACHE_MANAGER
+        .lock()
+        .queue
+        .iter()
+        .find(|entry| entry.0 == block_id && entry.1 == device)
+        .map(|entry| Arc::clone(&entry.2));
+    match cache {
+        Some(cache) => {
+            buf.copy_from_slice(&cache.lock().cache);
+            true
+        }
+        None => false,
+    }
+}
+
 pub fn block_cache_sync_all() {
     let manager = BLOCK_CACHE_MANAGER.lock();
-    for (_, cache) in manager.queue.iter() {
+    for (_, _, cache) in manager.queue.iter() {
         cache.lock().sync();
     }
 }
+
+/// Number of cached blocks, or None if the cache is in use.
+pub fn cached_blocks() -> Option<usize> {
+    BLOCK_CACHE_MANAGER
+        .try_lock()
+        .map(|manager| manager.queue.len())
+}
+
+/// Block caches alive, the cache's and those still held after it let them
+/// go, and those made since the start.
+pub fn block_cache_counts() -> (usize, usize) {
+    (LIVE_CACHES.load(Ordering::Relaxed), MADE_CACHES.load(Ordering::Relaxed))
+}
@@ -1,7 +1,30 @@
 use core::any::Any;
+use core::task::Waker;
 
 pub trait BlockDevice: Send + Sync + Any {
     fn read_block(&self, block_id: usize, buf: &mut [u8]);
     fn write_block(&self, block_id: usize, buf: &[u8]);
     fn handle_irq(&self);
+    /// Start reading `block_id` into `buf`, `waker` to be woken once it is
+    /// read, and return the request; None if the block is to be read with
+    /// `read_block`, as devices without requests of their own have it.
+    ///
+    /// # Safety
+    ///
+    /// `buf` must stay where it is until `finish_read` tells the request
+    /// is done.
+    unsafe fn start_read(
+        &self,
+        _block_id: usize,
+        _buf: &mut [u8],
+        _waker: &Waker,
+    ) -> Option<usize> {
+        None
+    }
+    /// Whether the request `id` is done, and if so whether the block was
+    /// read, the request being forgotten then; `waker` is woken once it is
+    /// done otherwise.
+    fn finish_read(&self, _id: usize, _waker: &Waker) -> Option<bool> {
+        None
+    }
 }
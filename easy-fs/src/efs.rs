@@ -1,17 +1,24 @@
 use super::{
     block_cache_sync_all, get_block_cache, Bitmap, BlockDevice, DiskInode, DiskInodeType, Inode,
-    SuperBlock,
+    Journal, SuperBlock, EFS_VERSION, JOURNAL_BLOCKS,
 };
 use crate::BLOCK_SZ;
+use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
 use spin::Mutex;
 
 pub struct EasyFileSystem {
+    /// the journal, in front of the disk
     pub block_device: Arc<dyn BlockDevice>,
+    journal: Arc<Journal>,
     pub inode_bitmap: Bitmap,
     pub data_bitmap: Bitmap,
     inode_area_start_block: u32,
-    data_area_start_block: u32,
+    pub(crate) data_area_start_block: u32,
+    pub(crate) data_area_blocks: u32,
+    /// `Inode`s in memory for each inode, which keep it after its last link
+    /// is gone
+    inode_refs: BTreeMap<u32, usize>,
 }
 
 type DataBlock = [u8; BLOCK_SZ];
@@ -28,30 +35,32 @@ impl EasyFileSystem {
         let inode_area_blocks =
             ((inode_num * core::mem::size_of::<DiskInode>() + BLOCK_SZ - 1) / BLOCK_SZ) as u32;
         let inode_total_blocks = inode_bitmap_blocks + inode_area_blocks;
-        let data_total_blocks = total_blocks - 1 - inode_total_blocks;
+        let data_total_blocks = total_blocks - 1 - inode_total_blocks - JOURNAL_BLOCKS;
         let data_bitmap_blocks = (data_total_blocks + 4096) / 4097;
         let data_area_blocks = data_total_blocks - data_bitmap_blocks;
         let data_bitmap = Bitmap::new(
             (1 + inode_bitmap_blocks + inode_area_blocks) as usize,
             data_bitmap_blocks as usize,
         );
+        // clear all blocks
+        for i in 0..total_blocks {
+            block_device.write_block(i as usize, &[0; BLOCK_SZ]);
+        }
+        let journal = Arc::new(Journal::new(
+            block_device,
+            (total_blocks - JOURNAL_BLOCKS) as usize,
+        ));
+        let block_device: Arc<dyn BlockDevice> = journal.clone();
         let mut efs = Self {
             block_device: Arc::clone(&block_device),
+            journal,
             inode_bitmap,
             data_bitmap,
             inode_area_start_block: 1 + inode_bitmap_blocks,
             data_area_start_block: 1 + inode_total_blocks + data_bitmap_blocks,
+            data_area_blocks,
+            inode_refs: BTreeMap::new(),
         };
-        // clear all blocks
-        for i in 0..total_blocks {
-            get_block_cache(i as usize, Arc::clone(&block_device))
-                .lock()
-                .modify(0, |data_block: &mut DataBlock| {
-                    for byte in data_block.iter_mut() {
-                        *byte = 0;
-                    }
-                });
-        }
         // initialize SuperBlock
         get_block_cache(0, Arc::clone(&block_device)).lock().modify(
             0,
@@ -62,6 +71,7 @@ impl EasyFileSystem {
                     inode_area_blocks,
                     data_bitmap_blocks,
                     data_area_blocks,
+                    JOURNAL_BLOCKS,
                 );
             },
         );
@@ -74,20 +84,37 @@ impl EasyFileSystem {
             .modify(root_inode_offset, |disk_inode: &mut DiskInode| {
                 disk_inode.initialize(DiskInodeType::Directory);
             });
-        block_cache_sync_all();
-        Arc::new(Mutex::new(efs))
+        let efs = Arc::new(Mutex::new(efs));
+        // the root is its own parent
+        let root_inode = Self::root_inode(&efs);
+        root_inode.init_dir(0, &mut efs.lock());
+        drop(root_inode);
+        efs.lock().commit();
+        efs
     }
 
+    /// Open the file system, writing in place the last operation which a
+    /// crash left in the journal.
     pub fn open(block_device: Arc<dyn BlockDevice>) -> Arc<Mutex<Self>> {
         // read SuperBlock
         get_block_cache(0, Arc::clone(&block_device))
             .lock()
             .read(0, |super_block: &SuperBlock| {
                 assert!(super_block.is_valid(), "Error loading EFS!");
+                assert_eq!(
+                    super_block.version, EFS_VERSION,
+                    "Outdated EFS layout, rebuild the image with easy-fs-fuse!"
+                );
                 let inode_total_blocks =
                     super_block.inode_bitmap_blocks + super_block.inode_area_blocks;
-                let efs = Self {
+                let journal = Arc::new(Journal::new(
                     block_device,
+                    (super_block.total_blocks - super_block.journal_blocks) as usize,
+                ));
+                journal.recover();
+                let efs = Self {
+                    block_device: journal.clone(),
+                    journal,
                     inode_bitmap: Bitmap::new(1, super_block.inode_bitmap_blocks as usize),
                     data_bitmap: Bitmap::new(
                         (1 + inode_total_blocks) as usize,
@@ -95,17 +122,61 @@ impl EasyFileSystem {
                     ),
                     inode_area_start_block: 1 + super_block.inode_bitmap_blocks,
                     data_area_start_block: 1 + inode_total_blocks + super_block.data_bitmap_blocks,
+                    data_area_blocks: super_block.data_area_blocks,
+                    inode_refs: BTreeMap::new(),
                 };
                 Arc::new(Mutex::new(efs))
             })
     }
 
+    /// Write the blocks changed by an operation back, all or none of them.
+    pub fn commit(&self) {
+        block_cache_sync_all();
+        self.journal.commit();
+    }
+
     pub fn root_inode(efs: &Arc<Mutex<Self>>) -> Inode {
         let block_device = Arc::clone(&efs.lock().block_device);
         // acquire efs lock temporarily
         let (block_id, block_offset) = efs.lock().get_disk_inode_pos(0);
+        efs.lock().get_inode(0);
         // release efs lock
-        Inode::new(block_id, block_offset, Arc::clone(efs), block_device)
+        Inode::new(0, block_id, block_offset, Arc::clone(efs), block_device)
+    }
+
+    /// An `Inode` of `inode_id` was made.
+    pub fn get_inode(&mut self, inode_id: u32) {
+        *self.inode_refs.entry(inode_id).or_insert(0) += 1;
+    }
+
+    /// An `Inode` of `inode_id` was dropped. Return whether it was the last.
+    pub fn put_inode(&mut self, inode_id: u32) -> bool {
+        let refs = self.inode_refs.get_mut(&inode_id).unwrap();
+        *refs -= 1;
+        if *refs > 0 {
+            return false;
+        }
+        self.inode_refs.remove(&inode_id);
+        true
+    }
+
+    pub fn is_referenced(&self, inode_id: u32) -> bool {
+        self.inode_refs.contains_key(&inode_id)
+    }
+
+    /// Give back the inode `inode_id` and its data blocks.
+    pub fn free_inode(&mut self, inode_id: u32) {
+        let (block_id, block_offset) = self.get_disk_inode_pos(inode_id);
+        let data_blocks_dealloc =
+            get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+                .lock()
+                .modify(block_offset, |disk_inode: &mut DiskInode| {
+                    disk_inode.clear_size(&self.block_device)
+                });
+        for data_block in data_blocks_dealloc.into_iter() {
+            self.dealloc_data(data_block);
+        }
+        self.dealloc_inode(inode_id);
     }
 
     pub fn get_disk_inode_pos(&self, inode_id: u32) -> (u32, usize) {
@@ -126,19 +197,24 @@ impl EasyFileSystem {
         self.inode_bitmap.alloc(&self.block_device).unwrap() as u32
     }
 
-    /// Return a block ID not ID in the data area.
-    pub fn alloc_data(&mut self) -> u32 {
-        self.data_bitmap.alloc(&self.block_device).unwrap() as u32 + self.data_area_start_block
+    pub fn dealloc_inode(&mut self, inode_id: u32) {
+        self.inode_bitmap
+            .dealloc(&self.block_device, inode_id as usize)
     }
 
-    pub fn dealloc_data(&mut self, block_id: u32) {
+    /// Return a block ID not ID in the data area, of a zeroed block. Blocks
+    /// are zeroed as they are allocated rather than freed, which would put
+    /// all blocks of a file removed in the journal.
+    pub fn alloc_data(&mut self) -> u32 {
+        let block_id =
+            self.data_bitmap.alloc(&self.block_device).unwrap() as u32 + self.data_area_start_block;
         get_block_cache(block_id as usize, Arc::clone(&self.block_device))
             .lock()
-            .modify(0, |data_block: &mut DataBlock| {
-                data_block.iter_mut().for_each(|p| {
-                    *p = 0;
-                })
-            });
+            .modify(0, |data_block: &mut DataBlock| data_block.fill(0));
+        block_id
+    }
+
+    pub fn dealloc_data(&mut self, block_id: u32) {
         self.data_bitmap.dealloc(
             &self.block_device,
             (block_id - self.data_area_start_block) as usize,
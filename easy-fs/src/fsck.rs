@@ -0,0 +1,272 @@
+//! A checker of easy-fs, which cross-checks the directories, the inodes and
+//! the bitmaps, and can repair what it finds wrong.
+//!
+//! It is meant for a file system nobody uses yet, before any `Inode` of it
+//! is made: an inode kept by an `Inode` after its last link is gone looks
+//! the same as one lost in a crash.
+
+use super::{get_block_cache, DirEntry, DiskInode, EasyFileSystem, DIRENT_SZ};
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// What `EasyFileSystem::check` found.
+#[derive(Default)]
+pub struct FsckReport {
+    /// inodes in the directory tree
+    pub inodes: usize,
+    /// data blocks of these inodes, the index blocks included
+    pub blocks: usize,
+    /// one line for each problem
+    pub problems: Vec<String>,
+}
+
+impl EasyFileSystem {
+    fn read_disk_inode<V>(&self, inode_id: u32, f: impl FnOnce(&DiskInode) -> V) -> V {
+        let (block_id, block_offset) = self.get_disk_inode_pos(inode_id);
+        get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+            .lock()
+            .read(block_offset, f)
+    }
+
+    fn modify_disk_inode<V>(&self, inode_id: u32, f: impl FnOnce(&mut DiskInode) -> V) -> V {
+        let (block_id, block_offset) = self.get_disk_inode_pos(inode_id);
+        get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+            .lock()
+            .modify(block_offset, f)
+    }
+
+    fn set_entry(&self, dir: u32, index: usize, dirent: &DirEntry) {
+        self.modify_disk_inode(dir, |dir_inode| {
+            dir_inode.overwrite_at(index * DIRENT_SZ, dirent.as_bytes(), &self.block_device);
+        });
+    }
+
+    /// Check the file system from the root down, and repair it as well if
+    /// `repair`:
+    /// - entries naming free inodes, with bad names, or naming a directory
+    ///   a second time are removed, and wrong `.` and `..` are rewritten;
+    /// - a file with blocks outside the data area or of another file is
+    ///   cut to nothing, and a wrong count of blocks is set right;
+    /// - link counts are set to the number of entries;
+    /// - inodes in no directory and blocks of no file are freed, and blocks
+    ///   in use are marked so.
+    ///
+    /// Each repair is committed on its own.
+    pub fn check(&mut self, repair: bool) -> FsckReport {
+        let mut report = FsckReport::default();
+        let inode_count = self.inode_bitmap.maximum();
+        let mut links = vec![0u32; inode_count];
+        let mut reached = vec![false; inode_count];
+        let mut used = vec![false; self.data_bitmap.maximum()];
+        // the root, which no entry names
+        if !self.inode_bitmap.is_set(&self.block_device, 0) {
+            report.problems.push(String::from("the root inode is free"));
+            if repair {
+                self.inode_bitmap.set(&self.block_device, 0);
+                self.commit();
+            }
+        }
+        links[0] = 1;
+        reached[0] = true;
+        let mut dirs = Vec::new();
+        if self.check_blocks(0, &mut used, &mut report, repair) {
+            dirs.push((0u32, 0u32));
+        }
+        while let Some((dir, parent)) = dirs.pop() {
+            let count = self.read_disk_inode(dir, |dir_inode| dir_inode.size as usize / DIRENT_SZ);
+            for index in 0..count {
+                let mut dirent = DirEntry::empty();
+                self.read_disk_inode(dir, |dir_inode| {
+                    dir_inode.read_at(index * DIRENT_SZ, dirent.as_bytes_mut(), &self.block_device)
+                });
+                if dirent.is_empty() {
+                    continue;
+                }
+                let inode_id = dirent.inode_number();
+                let problem = if !dirent.name_is_valid() {
+                    format!("entry {} of directory {} has a bad name", index, dir)
+                } else {
+                    let name = dirent.name();
+                    if name == "." || name == ".." {
+                        let expected = if name == "." { dir } else { parent };
+                        if inode_id != expected {
+                            report.problems.push(format!(
+                                "\"{}\" of directory {} is {} rather than {}",
+                                name, dir, inode_id, expected
+                            ));
+                            if repair {
+                                self.set_entry(dir, index, &DirEntry::new(name, expected));
+                                self.commit();
+                            }
+                        }
+                        continue;
+                    }
+                    if inode_id as usize >= inode_count
+                        || !self
+                            .inode_bitmap
+                            .is_set(&self.block_device, inode_id as usize)
+                    {
+                        format!(
+                            "\"{}\" in directory {} is the free inode {}",
+                            name, dir, inode_id
+                        )
+                    } else if reached[inode_id as usize]
+                        && self.read_disk_inode(inode_id, |disk_inode| disk_inode.is_dir())
+                    {
+                        format!(
+                            "\"{}\" in directory {} is directory {} once more",
+                            name, dir, inode_id
+                        )
+                    } else {
+                        links[inode_id as usize] += 1;
+                        if !reached[inode_id as usize] {
+                            reached[inode_id as usize] = true;
+                            let is_dir =
+                                self.read_disk_inode(inode_id, |disk_inode| disk_inode.is_dir());
+                            if self.check_blocks(inode_id, &mut used, &mut report, repair) && is_dir
+                            {
+                                dirs.push((inode_id, dir));
+                            }
+                        }
+                        continue;
+                    }
+                };
+                report.problems.push(problem);
+                if repair {
+                    self.set_entry(dir, index, &DirEntry::empty());
+                    self.commit();
+                }
+            }
+        }
+        for inode_id in 0..inode_count {
+            if reached[inode_id] {
+                report.inodes += 1;
+                let nlink = self.read_disk_inode(inode_id as u32, |disk_inode| disk_inode.nlink);
+                if nlink != links[inode_id] {
+                    report.problems.push(format!(
+                        "inode {} has {} links rather than {}",
+                        inode_id, nlink, links[inode_id]
+                    ));
+                    if repair {
+                        self.modify_disk_inode(inode_id as u32, |disk_inode| {
+                            disk_inode.nlink = links[inode_id]
+                        });
+                        self.commit();
+                    }
+                }
+            } else if self.inode_bitmap.is_set(&self.block_device, inode_id) {
+                // its blocks are freed below, as they are in no file
+                report
+                    .problems
+                    .push(format!("inode {} is in no directory", inode_id));
+                if repair {
+                    self.dealloc_inode(inode_id as u32);
+                    self.commit();
+                }
+            }
+        }
+        for (bit, used) in used.into_iter().enumerate() {
+            let allocated = self.data_bitmap.is_set(&self.block_device, bit);
+            let block_id = self.data_area_start_block as usize + bit;
+            if used {
+                report.blocks += 1;
+            }
+            if allocated && !used {
+                report
+                    .problems
+                    .push(format!("block {} is in no file", block_id));
+                if repair {
+                    self.data_bitmap.dealloc(&self.block_device, bit);
+                    self.commit();
+                }
+            } else if used && !allocated {
+                report
+                    .problems
+                    .push(format!("block {} is in use but free", block_id));
+                if repair {
+                    self.data_bitmap.set(&self.block_device, bit);
+                    self.commit();
+                }
+            }
+        }
+        report
+    }
+
+    /// Mark the blocks of `inode_id` in `used`. A file with blocks outside
+    /// the data area or used already is cut to nothing if `repair`, and the
+    /// blocks it alone had are then in no file. Return whether the file is
+    /// fine now.
+    fn check_blocks(
+        &self,
+        inode_id: u32,
+        used: &mut [bool],
+        report: &mut FsckReport,
+        repair: bool,
+    ) -> bool {
+        let start = self.data_area_start_block;
+        let end = start + self.data_area_blocks;
+        let valid = |block_id: u32| block_id >= start && block_id < end;
+        let blocks = self.read_disk_inode(inode_id, |disk_inode| {
+            if disk_inode.size_is_valid() {
+                Some(disk_inode.blocks(&self.block_device, valid))
+            } else {
+                None
+            }
+        });
+        let mut marked = Vec::new();
+        let problem = match blocks {
+            None => Some(format!("inode {} is too large", inode_id)),
+            Some(blocks) => blocks.into_iter().find_map(|block_id| {
+                if !valid(block_id) {
+                    return Some(format!(
+                        "inode {} has the block {} outside the data area",
+                        inode_id, block_id
+                    ));
+                }
+                let bit = (block_id - start) as usize;
+                if used[bit] {
+                    return Some(format!(
+                        "inode {} has the block {} of another file",
+                        inode_id, block_id
+                    ));
+                }
+                used[bit] = true;
+                marked.push(bit);
+                None
+            }),
+        };
+        let problem = match problem {
+            Some(problem) => problem,
+            None => {
+                let counted = self.read_disk_inode(inode_id, |disk_inode| disk_inode.blocks);
+                if counted as usize != marked.len() {
+                    report.problems.push(format!(
+                        "inode {} counts {} blocks but has {}",
+                        inode_id,
+                        counted,
+                        marked.len()
+                    ));
+                    if repair {
+                        let blocks = marked.len() as u32;
+                        self.modify_disk_inode(inode_id, |disk_inode| disk_inode.blocks = blocks);
+                        self.commit();
+                    }
+                }
+                return true;
+            }
+        };
+        report.problems.push(problem);
+        if !repair {
+            return false;
+        }
+        for bit in marked {
+            used[bit] = false;
+        }
+        self.modify_disk_inode(inode_id, |disk_inode| disk_inode.forget_blocks());
+        self.commit();
+        true
+    }
+}
@@ -0,0 +1,170 @@
+//! A write-ahead journal, so that each operation of easy-fs reaches the
+//! disk whole or not at all.
+//!
+//! `Journal` stands between the block cache and the disk. Blocks written
+//! back are held in memory until the operation commits: they are copied to
+//! the journal area first, then the header is written, which commits them,
+//! and only then are they written in place. After a crash, `recover` writes
+//! a committed transaction in place once more, and drops one which was not.
+
+use super::{BlockDevice, JournalHeader, BLOCK_SZ, JOURNAL_TARGETS};
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::task::Waker;
+use spin::Mutex;
+
+/// A block for the header, aligned for it.
+#[repr(C, align(4))]
+struct HeaderBlock([u8; BLOCK_SZ]);
+
+impl HeaderBlock {
+    fn new() -> Self {
+        Self([0; BLOCK_SZ])
+    }
+    fn header(&mut self) -> &mut JournalHeader {
+        unsafe { &mut *(self.0.as_mut_ptr() as *mut JournalHeader) }
+    }
+}
+
+pub struct Journal {
+    block_device: Arc<dyn BlockDevice>,
+    /// the header, followed by the copies
+    start_block: usize,
+    /// blocks written since the last commit
+    pending: Mutex<BTreeMap<usize, Vec<u8>>>,
+}
+
+/// FNV-1a of the blocks and where they go.
+fn checksum<'a>(blocks: impl Iterator<Item = (usize, &'a [u8])>) -> u32 {
+    let mut hash = 0x811c_9dc5u32;
+    for (block_id, data) in blocks {
+        for byte in (block_id as u32).to_le_bytes().iter().chain(data) {
+            hash = (hash ^ *byte as u32).wrapping_mul(0x0100_0193);
+        }
+    }
+    hash
+}
+
+impl Journal {
+    pub fn new(block_device: Arc<dyn BlockDevice>, start_block: usize) -> Self {
+        Self {
+            block_device,
+            start_block,
+            pending: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Write the transaction left in the journal by a crash in place, if it
+    /// was committed. Return the number of blocks written.
+    pub fn recover(&self) -> usize {
+        let mut block = HeaderBlock::new();
+        self.block_device.read_block(self.start_block, &mut block.0);
+        let header = block.header();
+        if !header.is_valid() || header.count == 0 {
+            return 0;
+        }
+        let count = header.count as usize;
+        let mut copies = vec![0u8; count * BLOCK_SZ];
+        for (i, copy) in copies.chunks_mut(BLOCK_SZ).enumerate() {
+            self.block_device.read_block(self.start_block + 1 + i, copy);
+        }
+        let targets = header.targets[..count]
+            .iter()
+            .map(|block_id| *block_id as usize);
+        let committed = checksum(targets.clone().zip(copies.chunks(BLOCK_SZ))) == header.checksum;
+        if committed {
+            for (block_id, copy) in targets.zip(copies.chunks(BLOCK_SZ)) {
+                self.block_device.write_block(block_id, copy);
+            }
+        }
+        self.clear();
+        if committed {
+            count
+        } else {
+            0
+        }
+    }
+
+    /// Put the blocks written since the last commit on the disk, all or
+    /// none of them. The block cache is written back first by the caller.
+    pub fn commit(&self) {
+        // the blocks stay readable from `pending` until they are in place
+        let blocks: Vec<(usize, Vec<u8>)> = self
+            .pending
+            .lock()
+            .iter()
+            .map(|(block_id, data)| (*block_id, data.clone()))
+            .collect();
+        if blocks.is_empty() {
+            return;
+        }
+        assert!(
+            blocks.len() <= JOURNAL_TARGETS,
+            "Transaction too large for the journal!"
+        );
+        for (i, (_, data)) in blocks.iter().enumerate() {
+            self.block_device
+                .write_block(self.start_block + 1 + i, data);
+        }
+        let mut block = HeaderBlock::new();
+        let header = block.header();
+        for (target, (block_id, _)) in header.targets.iter_mut().zip(blocks.iter()) {
+            *target = *block_id as u32;
+        }
+        let sum = checksum(
+            blocks
+                .iter()
+                .map(|(block_id, data)| (*block_id, data.as_slice())),
+        );
+        header.initialize(blocks.len() as u32, sum);
+        self.block_device.write_block(self.start_block, &block.0);
+        for (block_id, data) in blocks.iter() {
+            self.block_device.write_block(*block_id, data);
+        }
+        self.clear();
+        let mut pending = self.pending.lock();
+        for (block_id, _) in blocks.iter() {
+            pending.remove(block_id);
+        }
+    }
+
+    /// Mark the journal empty.
+    fn clear(&self) {
+        let mut block = HeaderBlock::new();
+        block.header().initialize(0, 0);
+        self.block_device.write_block(self.start_block, &block.0);
+    }
+}
+
+impl BlockDevice for Journal {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        if let Some(data) = self.pending.lock().get(&block_id) {
+            buf.copy_from_slice(data);
+            return;
+        }
+        self.block_device.read_block(block_id, buf);
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        self.pending.lock().insert(block_id, buf.to_vec());
+    }
+
+    fn handle_irq(&self) {
+        self.block_device.handle_irq();
+    }
+
+    /// A block held here is newer than the one on the disk, and is read
+    /// with `read_block`.
+    unsafe fn start_read(&self, block_id: usize, buf: &mut [u8], waker: &Waker) -> Option<usize> {
+        if self.pending.lock().contains_key(&block_id) {
+            return None;
+        }
+        self.block_device.start_read(block_id, buf, waker)
+    }
+
+    fn finish_read(&self, id: usize, waker: &Waker) -> Option<bool> {
+        self.block_device.finish_read(id, waker)
+    }
+}
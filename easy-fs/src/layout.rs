@@ -4,33 +4,52 @@ use alloc::vec::Vec;
 use core::fmt::{Debug, Formatter, Result};
 
 const EFS_MAGIC: u32 = 0x3b800001;
-const INODE_DIRECT_COUNT: usize = 28;
-const NAME_LENGTH_LIMIT: usize = 27;
-const INODE_INDIRECT1_COUNT: usize = BLOCK_SZ / 4;
-const INODE_INDIRECT2_COUNT: usize = INODE_INDIRECT1_COUNT * INODE_INDIRECT1_COUNT;
-const DIRECT_BOUND: usize = INODE_DIRECT_COUNT;
-const INDIRECT1_BOUND: usize = DIRECT_BOUND + INODE_INDIRECT1_COUNT;
-#[allow(unused)]
-const INDIRECT2_BOUND: usize = INDIRECT1_BOUND + INODE_INDIRECT2_COUNT;
+/// Bumped whenever the layout changes: 2 brought directories, 3 link
+/// counts, 4 the journal, 5 owners and permissions, 6 64-bit sizes, four
+/// levels of index blocks and holes, 7 modification times.
+pub const EFS_VERSION: u32 = 7;
+/// Two fewer than before the modification time, which keeps an inode at
+/// 128 bytes.
+const INODE_DIRECT_COUNT: usize = 18;
+pub const NAME_LENGTH_LIMIT: usize = 27;
+const JOURNAL_MAGIC: u32 = 0x6a726e6c;
+/// Blocks a transaction may change.
+pub const JOURNAL_TARGETS: usize = BLOCK_SZ / 4 - 3;
+/// The header and a copy of each block.
+pub const JOURNAL_BLOCKS: u32 = 1 + JOURNAL_TARGETS as u32;
+/// Block ids an index block holds.
+const INDEX_ENTRIES: usize = BLOCK_SZ / 4;
+const INDIRECT_LEVELS: usize = 4;
+/// The blocks the direct slots and the four trees reach.
+const MAX_FILE_BLOCKS: usize = INODE_DIRECT_COUNT
+    + INDEX_ENTRIES
+    + INDEX_ENTRIES * INDEX_ENTRIES
+    + INDEX_ENTRIES * INDEX_ENTRIES * INDEX_ENTRIES
+    + INDEX_ENTRIES * INDEX_ENTRIES * INDEX_ENTRIES * INDEX_ENTRIES;
 
 #[repr(C)]
 pub struct SuperBlock {
     magic: u32,
+    pub version: u32,
     pub total_blocks: u32,
     pub inode_bitmap_blocks: u32,
     pub inode_area_blocks: u32,
     pub data_bitmap_blocks: u32,
     pub data_area_blocks: u32,
+    /// the journal, at the end of the file system
+    pub journal_blocks: u32,
 }
 
 impl Debug for SuperBlock {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         f.debug_struct("SuperBlock")
+            .field("version", &self.version)
             .field("total_blocks", &self.total_blocks)
             .field("inode_bitmap_blocks", &self.inode_bitmap_blocks)
             .field("inode_area_blocks", &self.inode_area_blocks)
             .field("data_bitmap_blocks", &self.data_bitmap_blocks)
             .field("data_area_blocks", &self.data_area_blocks)
+            .field("journal_blocks", &self.journal_blocks)
             .finish()
     }
 }
@@ -43,14 +62,17 @@ impl SuperBlock {
         inode_area_blocks: u32,
         data_bitmap_blocks: u32,
         data_area_blocks: u32,
+        journal_blocks: u32,
     ) {
         *self = Self {
             magic: EFS_MAGIC,
+            version: EFS_VERSION,
             total_blocks,
             inode_bitmap_blocks,
             inode_area_blocks,
             data_bitmap_blocks,
             data_area_blocks,
+            journal_blocks,
         }
     }
     pub fn is_valid(&self) -> bool {
@@ -58,32 +80,115 @@ impl SuperBlock {
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, Copy, PartialEq)]
 pub enum DiskInodeType {
     File,
     Directory,
+    /// whose content is the path it points to
+    Symlink,
+    /// a named pipe, with no content on disk
+    Fifo,
+    /// where an AF_UNIX socket is bound, with no content on disk either
+    Socket,
 }
 
-type IndirectBlock = [u32; BLOCK_SZ / 4];
+/// The first block of the journal, which says where the blocks after it
+/// go once `count` is not 0.
+#[repr(C)]
+pub struct JournalHeader {
+    magic: u32,
+    pub count: u32,
+    /// of the copies and where they go
+    pub checksum: u32,
+    pub targets: [u32; JOURNAL_TARGETS],
+}
+
+impl JournalHeader {
+    pub fn initialize(&mut self, count: u32, checksum: u32) {
+        self.magic = JOURNAL_MAGIC;
+        self.count = count;
+        self.checksum = checksum;
+    }
+    pub fn is_valid(&self) -> bool {
+        self.magic == JOURNAL_MAGIC && self.count as usize <= JOURNAL_TARGETS
+    }
+}
+
+type IndexBlock = [u32; INDEX_ENTRIES];
 type DataBlock = [u8; BLOCK_SZ];
 
+/// Beyond the direct blocks, a file's blocks are found through trees of
+/// index blocks, one for each level: the first tree is a single index block,
+/// the fourth reaches 128^4 blocks, which makes files of up to 128 GiB.
+///
+/// Block 0 is the super block, so a 0 in a direct slot or an index block
+/// means the block was never written: a hole, which reads as zeros.
 #[repr(C)]
 pub struct DiskInode {
-    pub size: u32,
+    pub size: u64,
     pub direct: [u32; INODE_DIRECT_COUNT],
-    pub indirect1: u32,
-    pub indirect2: u32,
+    /// the root index block of each level
+    pub indirect: [u32; INDIRECT_LEVELS],
+    /// data and index blocks allocated
+    pub blocks: u32,
+    /// the number of directory entries naming this inode
+    pub nlink: u32,
+    /// permission bits, as in the low 12 bits of st_mode
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
     type_: DiskInodeType,
+    /// seconds since the Unix epoch, as the packer found it on the host;
+    /// 0 for what is made with no clock to ask
+    pub mtime: u64,
+}
+
+/// The tree holding the `inner_id`th block, which is not a direct one, and
+/// the index of the block within it.
+fn tree_of(inner_id: usize) -> Option<(usize, usize)> {
+    let mut index = inner_id - INODE_DIRECT_COUNT;
+    let mut span = INDEX_ENTRIES;
+    for level in 1..=INDIRECT_LEVELS {
+        if index < span {
+            return Some((level, index));
+        }
+        index -= span;
+        span *= INDEX_ENTRIES;
+    }
+    None
+}
+
+/// The entry of the index block at `depth` above the data leading to the
+/// `index`th block of a tree.
+fn entry_of(index: usize, depth: usize) -> usize {
+    index / INDEX_ENTRIES.pow(depth as u32) % INDEX_ENTRIES
+}
+
+fn read_index(block_id: u32, block_device: &Arc<dyn BlockDevice>) -> IndexBlock {
+    get_block_cache(block_id as usize, Arc::clone(block_device))
+        .lock()
+        .read(0, |index_block: &IndexBlock| *index_block)
 }
 
 impl DiskInode {
-    /// indirect1 and indirect2 block are allocated only when they are needed.
+    /// Index blocks are allocated only when they are needed.
     pub fn initialize(&mut self, type_: DiskInodeType) {
         self.size = 0;
         self.direct.iter_mut().for_each(|v| *v = 0);
-        self.indirect1 = 0;
-        self.indirect2 = 0;
+        self.indirect.iter_mut().for_each(|v| *v = 0);
+        self.blocks = 0;
+        self.nlink = 1;
+        self.mode = match type_ {
+            DiskInodeType::File => 0o644,
+            DiskInodeType::Directory => 0o755,
+            DiskInodeType::Symlink => 0o777,
+            DiskInodeType::Fifo => 0o644,
+            DiskInodeType::Socket => 0o755,
+        };
+        self.uid = 0;
+        self.gid = 0;
         self.type_ = type_;
+        self.mtime = 0;
     }
     pub fn is_dir(&self) -> bool {
         self.type_ == DiskInodeType::Directory
@@ -92,203 +197,141 @@ impl DiskInode {
     pub fn is_file(&self) -> bool {
         self.type_ == DiskInodeType::File
     }
-    /// Return block number correspond to size.
-    pub fn data_blocks(&self) -> u32 {
-        Self::_data_blocks(self.size)
+    pub fn is_symlink(&self) -> bool {
+        self.type_ == DiskInodeType::Symlink
     }
-    fn _data_blocks(size: u32) -> u32 {
-        (size + BLOCK_SZ as u32 - 1) / BLOCK_SZ as u32
+    pub fn inode_type(&self) -> DiskInodeType {
+        self.type_
     }
-    /// Return number of blocks needed include indirect1/2.
-    pub fn total_blocks(size: u32) -> u32 {
-        let data_blocks = Self::_data_blocks(size) as usize;
-        let mut total = data_blocks as usize;
-        // indirect1
-        if data_blocks > INODE_DIRECT_COUNT {
-            total += 1;
-        }
-        // indirect2
-        if data_blocks > INDIRECT1_BOUND {
-            total += 1;
-            // sub indirect1
-            total +=
-                (data_blocks - INDIRECT1_BOUND + INODE_INDIRECT1_COUNT - 1) / INODE_INDIRECT1_COUNT;
-        }
-        total as u32
-    }
-    pub fn blocks_num_needed(&self, new_size: u32) -> u32 {
-        assert!(new_size >= self.size);
-        Self::total_blocks(new_size) - Self::total_blocks(self.size)
+    /// Return block number correspond to size.
+    pub fn data_blocks(&self) -> u64 {
+        (self.size + BLOCK_SZ as u64 - 1) / BLOCK_SZ as u64
     }
-    pub fn get_block_id(&self, inner_id: u32, block_device: &Arc<dyn BlockDevice>) -> u32 {
-        let inner_id = inner_id as usize;
+    /// The block holding the `inner_id`th block of the file, 0 in a hole.
+    pub fn get_block_id(&self, inner_id: usize, block_device: &Arc<dyn BlockDevice>) -> u32 {
         if inner_id < INODE_DIRECT_COUNT {
-            self.direct[inner_id]
-        } else if inner_id < INDIRECT1_BOUND {
-            get_block_cache(self.indirect1 as usize, Arc::clone(block_device))
-                .lock()
-                .read(0, |indirect_block: &IndirectBlock| {
-                    indirect_block[inner_id - INODE_DIRECT_COUNT]
-                })
-        } else {
-            let last = inner_id - INDIRECT1_BOUND;
-            let indirect1 = get_block_cache(self.indirect2 as usize, Arc::clone(block_device))
+            return self.direct[inner_id];
+        }
+        let (level, index) = match tree_of(inner_id) {
+            Some(tree) => tree,
+            None => return 0,
+        };
+        let mut block_id = self.indirect[level - 1];
+        for depth in (0..level).rev() {
+            if block_id == 0 {
+                return 0;
+            }
+            block_id = get_block_cache(block_id as usize, Arc::clone(block_device))
                 .lock()
-                .read(0, |indirect2: &IndirectBlock| {
-                    indirect2[last / INODE_INDIRECT1_COUNT]
+                .read(0, |index_block: &IndexBlock| {
+                    index_block[entry_of(index, depth)]
                 });
-            get_block_cache(indirect1 as usize, Arc::clone(block_device))
-                .lock()
-                .read(0, |indirect1: &IndirectBlock| {
-                    indirect1[last % INODE_INDIRECT1_COUNT]
-                })
         }
+        block_id
     }
-    pub fn increase_size(
+    /// The block holding the `inner_id`th block of the file, taken from
+    /// `alloc`, which hands out zeroed blocks, if it is a hole, along with
+    /// the index blocks on the way to it.
+    fn map_block(
         &mut self,
-        new_size: u32,
-        new_blocks: Vec<u32>,
+        inner_id: usize,
+        alloc: &mut dyn FnMut() -> u32,
         block_device: &Arc<dyn BlockDevice>,
-    ) {
-        let mut current_blocks = self.data_blocks();
-        self.size = new_size;
-        let mut total_blocks = self.data_blocks();
-        let mut new_blocks = new_blocks.into_iter();
-        // fill direct
-        while current_blocks < total_blocks.min(INODE_DIRECT_COUNT as u32) {
-            self.direct[current_blocks as usize] = new_blocks.next().unwrap();
-            current_blocks += 1;
-        }
-        // alloc indirect1
-        if total_blocks > INODE_DIRECT_COUNT as u32 {
-            if current_blocks == INODE_DIRECT_COUNT as u32 {
-                self.indirect1 = new_blocks.next().unwrap();
+    ) -> u32 {
+        let mut allocated = 0;
+        let mut alloc_counted = || {
+            allocated += 1;
+            alloc()
+        };
+        let block_id = if inner_id < INODE_DIRECT_COUNT {
+            if self.direct[inner_id] == 0 {
+                self.direct[inner_id] = alloc_counted();
             }
-            current_blocks -= INODE_DIRECT_COUNT as u32;
-            total_blocks -= INODE_DIRECT_COUNT as u32;
+            self.direct[inner_id]
         } else {
-            return;
-        }
-        // fill indirect1
-        get_block_cache(self.indirect1 as usize, Arc::clone(block_device))
-            .lock()
-            .modify(0, |indirect1: &mut IndirectBlock| {
-                while current_blocks < total_blocks.min(INODE_INDIRECT1_COUNT as u32) {
-                    indirect1[current_blocks as usize] = new_blocks.next().unwrap();
-                    current_blocks += 1;
-                }
-            });
-        // alloc indirect2
-        if total_blocks > INODE_INDIRECT1_COUNT as u32 {
-            if current_blocks == INODE_INDIRECT1_COUNT as u32 {
-                self.indirect2 = new_blocks.next().unwrap();
+            let (level, index) = tree_of(inner_id).expect("File too large!");
+            if self.indirect[level - 1] == 0 {
+                self.indirect[level - 1] = alloc_counted();
             }
-            current_blocks -= INODE_INDIRECT1_COUNT as u32;
-            total_blocks -= INODE_INDIRECT1_COUNT as u32;
-        } else {
-            return;
-        }
-        // fill indirect2 from (a0, b0) -> (a1, b1)
-        let mut a0 = current_blocks as usize / INODE_INDIRECT1_COUNT;
-        let mut b0 = current_blocks as usize % INODE_INDIRECT1_COUNT;
-        let a1 = total_blocks as usize / INODE_INDIRECT1_COUNT;
-        let b1 = total_blocks as usize % INODE_INDIRECT1_COUNT;
-        // alloc low-level indirect1
-        get_block_cache(self.indirect2 as usize, Arc::clone(block_device))
-            .lock()
-            .modify(0, |indirect2: &mut IndirectBlock| {
-                while (a0 < a1) || (a0 == a1 && b0 < b1) {
-                    if b0 == 0 {
-                        indirect2[a0] = new_blocks.next().unwrap();
-                    }
-                    // fill current
-                    get_block_cache(indirect2[a0] as usize, Arc::clone(block_device))
+            let mut block_id = self.indirect[level - 1];
+            for depth in (0..level).rev() {
+                let entry = entry_of(index, depth);
+                let cache = get_block_cache(block_id as usize, Arc::clone(block_device));
+                let next = cache
+                    .lock()
+                    .read(0, |index_block: &IndexBlock| index_block[entry]);
+                block_id = if next == 0 {
+                    let next = alloc_counted();
+                    cache
                         .lock()
-                        .modify(0, |indirect1: &mut IndirectBlock| {
-                            indirect1[b0] = new_blocks.next().unwrap();
-                        });
-                    // move to next
-                    b0 += 1;
-                    if b0 == INODE_INDIRECT1_COUNT {
-                        b0 = 0;
-                        a0 += 1;
-                    }
-                }
-            });
+                        .modify(0, |index_block: &mut IndexBlock| index_block[entry] = next);
+                    next
+                } else {
+                    next
+                };
+            }
+            block_id
+        };
+        self.blocks += allocated;
+        block_id
     }
 
+    /// Whether the size is one the index blocks can hold.
+    pub fn size_is_valid(&self) -> bool {
+        self.data_blocks() <= MAX_FILE_BLOCKS as u64
+    }
     /// Clear size to zero and return blocks that should be deallocated.
-    ///
-    /// We will clear the block contents to zero later.
     pub fn clear_size(&mut self, block_device: &Arc<dyn BlockDevice>) -> Vec<u32> {
-        let mut v: Vec<u32> = Vec::new();
-        let mut data_blocks = self.data_blocks() as usize;
+        let v = self.blocks(block_device, |_| true);
+        self.forget_blocks();
+        v
+    }
+    /// Make the file empty, leaving its blocks to the caller.
+    pub fn forget_blocks(&mut self) {
         self.size = 0;
-        let mut current_blocks = 0usize;
-        // direct
-        while current_blocks < data_blocks.min(INODE_DIRECT_COUNT) {
-            v.push(self.direct[current_blocks]);
-            self.direct[current_blocks] = 0;
-            current_blocks += 1;
-        }
-        // indirect1 block
-        if data_blocks > INODE_DIRECT_COUNT {
-            v.push(self.indirect1);
-            data_blocks -= INODE_DIRECT_COUNT;
-            current_blocks = 0;
-        } else {
-            return v;
-        }
-        // indirect1
-        get_block_cache(self.indirect1 as usize, Arc::clone(block_device))
-            .lock()
-            .modify(0, |indirect1: &mut IndirectBlock| {
-                while current_blocks < data_blocks.min(INODE_INDIRECT1_COUNT) {
-                    v.push(indirect1[current_blocks]);
-                    //indirect1[current_blocks] = 0;
-                    current_blocks += 1;
+        self.direct.iter_mut().for_each(|v| *v = 0);
+        self.indirect.iter_mut().for_each(|v| *v = 0);
+        self.blocks = 0;
+    }
+    /// The data blocks and the index blocks of the file. Index blocks
+    /// `valid` does not accept are listed but not read.
+    ///
+    /// The index blocks are only read, so that the journal does not have
+    /// to hold them when the file is cleared.
+    pub fn blocks(
+        &self,
+        block_device: &Arc<dyn BlockDevice>,
+        valid: impl Fn(u32) -> bool,
+    ) -> Vec<u32> {
+        /// Push `block_id` and the blocks below it, `depth` levels of them.
+        fn walk(
+            block_id: u32,
+            depth: usize,
+            block_device: &Arc<dyn BlockDevice>,
+            valid: &dyn Fn(u32) -> bool,
+            v: &mut Vec<u32>,
+        ) {
+            v.push(block_id);
+            if depth == 0 || !valid(block_id) {
+                return;
+            }
+            for entry in read_index(block_id, block_device).iter() {
+                if *entry != 0 {
+                    walk(*entry, depth - 1, block_device, valid, v);
                 }
-            });
-        self.indirect1 = 0;
-        // indirect2 block
-        if data_blocks > INODE_INDIRECT1_COUNT {
-            v.push(self.indirect2);
-            data_blocks -= INODE_INDIRECT1_COUNT;
-        } else {
-            return v;
+            }
+        }
+        let mut v: Vec<u32> = self
+            .direct
+            .iter()
+            .copied()
+            .filter(|block_id| *block_id != 0)
+            .collect();
+        for (level, root) in (1..=INDIRECT_LEVELS).zip(self.indirect.iter()) {
+            if *root != 0 {
+                walk(*root, level, block_device, &valid, &mut v);
+            }
         }
-        // indirect2
-        assert!(data_blocks <= INODE_INDIRECT2_COUNT);
-        let a1 = data_blocks / INODE_INDIRECT1_COUNT;
-        let b1 = data_blocks % INODE_INDIRECT1_COUNT;
-        get_block_cache(self.indirect2 as usize, Arc::clone(block_device))
-            .lock()
-            .modify(0, |indirect2: &mut IndirectBlock| {
-                // full indirect1 blocks
-                for entry in indirect2.iter_mut().take(a1) {
-                    v.push(*entry);
-                    get_block_cache(*entry as usize, Arc::clone(block_device))
-                        .lock()
-                        .modify(0, |indirect1: &mut IndirectBlock| {
-                            for entry in indirect1.iter() {
-                                v.push(*entry);
-                            }
-                        });
-                }
-                // last indirect1 block
-                if b1 > 0 {
-                    v.push(indirect2[a1]);
-                    get_block_cache(indirect2[a1] as usize, Arc::clone(block_device))
-                        .lock()
-                        .modify(0, |indirect1: &mut IndirectBlock| {
-                            for entry in indirect1.iter().take(b1) {
-                                v.push(*entry);
-                            }
-                        });
-                    //indirect2[a1] = 0;
-                }
-            });
-        self.indirect2 = 0;
         v
     }
     pub fn read_at(
@@ -311,15 +354,15 @@ impl DiskInode {
             // read and update read size
             let block_read_size = end_current_block - start;
             let dst = &mut buf[read_size..read_size + block_read_size];
-            get_block_cache(
-                self.get_block_id(start_block as u32, block_device) as usize,
-                Arc::clone(block_device),
-            )
-            .lock()
-            .read(0, |data_block: &DataBlock| {
-                let src = &data_block[start % BLOCK_SZ..start % BLOCK_SZ + block_read_size];
-                dst.copy_from_slice(src);
-            });
+            match self.get_block_id(start_block, block_device) {
+                0 => dst.fill(0),
+                block_id => get_block_cache(block_id as usize, Arc::clone(block_device))
+                    .lock()
+                    .read(0, |data_block: &DataBlock| {
+                        let src = &data_block[start % BLOCK_SZ..start % BLOCK_SZ + block_read_size];
+                        dst.copy_from_slice(src);
+                    }),
+            }
             read_size += block_read_size;
             // move to next block
             if end_current_block == end {
@@ -330,16 +373,20 @@ impl DiskInode {
         }
         read_size
     }
-    /// File size must be adjusted before.
+    /// Write `buf` at `offset`, with the blocks of holes it lands on taken
+    /// from `alloc`, and grow the size to its end.
     pub fn write_at(
         &mut self,
         offset: usize,
         buf: &[u8],
+        alloc: &mut dyn FnMut() -> u32,
         block_device: &Arc<dyn BlockDevice>,
     ) -> usize {
         let mut start = offset;
-        let end = (offset + buf.len()).min(self.size as usize);
-        assert!(start <= end);
+        let end = offset + buf.len();
+        if start >= end {
+            return 0;
+        }
         let mut start_block = start / BLOCK_SZ;
         let mut write_size = 0usize;
         loop {
@@ -348,16 +395,15 @@ impl DiskInode {
             end_current_block = end_current_block.min(end);
             // write and update write size
             let block_write_size = end_current_block - start;
-            get_block_cache(
-                self.get_block_id(start_block as u32, block_device) as usize,
-                Arc::clone(block_device),
-            )
-            .lock()
-            .modify(0, |data_block: &mut DataBlock| {
-                let src = &buf[write_size..write_size + block_write_size];
-                let dst = &mut data_block[start % BLOCK_SZ..start % BLOCK_SZ + block_write_size];
-                dst.copy_from_slice(src);
-            });
+            let block_id = self.map_block(start_block, alloc, block_device);
+            get_block_cache(block_id as usize, Arc::clone(block_device))
+                .lock()
+                .modify(0, |data_block: &mut DataBlock| {
+                    let src = &buf[write_size..write_size + block_write_size];
+                    let dst =
+                        &mut data_block[start % BLOCK_SZ..start % BLOCK_SZ + block_write_size];
+                    dst.copy_from_slice(src);
+                });
             write_size += block_write_size;
             // move to next block
             if end_current_block == end {
@@ -366,8 +412,23 @@ impl DiskInode {
             start_block += 1;
             start = end_current_block;
         }
+        self.size = self.size.max(end as u64);
         write_size
     }
+    /// Write `buf` over what the file holds at `offset`, which is no hole.
+    pub fn overwrite_at(
+        &mut self,
+        offset: usize,
+        buf: &[u8],
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> usize {
+        self.write_at(
+            offset,
+            buf,
+            &mut || panic!("Overwriting a hole!"),
+            block_device,
+        )
+    }
 }
 
 #[repr(C)]
@@ -406,4 +467,15 @@ impl DirEntry {
     pub fn inode_number(&self) -> u32 {
         self.inode_number
     }
+    /// Whether the name ends and is UTF-8, as `name` expects.
+    pub fn name_is_valid(&self) -> bool {
+        match self.name.iter().position(|b| *b == 0) {
+            Some(len) => core::str::from_utf8(&self.name[..len]).is_ok(),
+            None => false,
+        }
+    }
+    /// A slot left by a removed entry.
+    pub fn is_empty(&self) -> bool {
+        self.name[0] == 0
+    }
 }
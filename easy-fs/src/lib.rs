@@ -6,13 +6,21 @@ mod bitmap;
 mod block_cache;
 mod block_dev;
 mod efs;
+mod fsck;
+mod journal;
 mod layout;
 mod vfs;
 
 pub const BLOCK_SZ: usize = 512;
 use bitmap::Bitmap;
-use block_cache::{block_cache_sync_all, get_block_cache};
+use block_cache::read_cached_block;
+pub use block_cache::{
+    block_cache_counts, block_cache_sync_all, cached_blocks, get_block_cache, BlockCache,
+};
 pub use block_dev::BlockDevice;
 pub use efs::EasyFileSystem;
+pub use fsck::FsckReport;
+use journal::Journal;
+pub use layout::DiskInodeType;
 use layout::*;
 pub use vfs::Inode;
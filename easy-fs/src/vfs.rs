@@ -1,6 +1,6 @@
 use super::{
-    block_cache_sync_all, get_block_cache, BlockDevice, DirEntry, DiskInode, DiskInodeType,
-    EasyFileSystem, DIRENT_SZ,
+    get_block_cache, read_cached_block, BlockDevice, DirEntry, DiskInode, DiskInodeType,
+    EasyFileSystem, BLOCK_SZ, DIRENT_SZ, NAME_LENGTH_LIMIT,
 };
 use alloc::string::String;
 use alloc::sync::Arc;
@@ -8,21 +8,34 @@ use alloc::vec::Vec;
 use spin::{Mutex, MutexGuard};
 
 pub struct Inode {
+    inode_id: u32,
     block_id: usize,
     block_offset: usize,
     fs: Arc<Mutex<EasyFileSystem>>,
     block_device: Arc<dyn BlockDevice>,
 }
 
+/// The most a transaction writes of a file, which leaves room in the
+/// journal for the bitmaps, the inode and the index blocks.
+const WRITE_CHUNK: usize = 64 * BLOCK_SZ;
+
+/// Whether `name` may be given to a new entry.
+fn valid_name(name: &str) -> bool {
+    !name.is_empty() && name.len() <= NAME_LENGTH_LIMIT && name != "." && name != ".."
+}
+
 impl Inode {
-    /// We should not acquire efs lock here.
+    /// We should not acquire efs lock here. The caller counts the new
+    /// `Inode` with `EasyFileSystem::get_inode`.
     pub fn new(
+        inode_id: u32,
         block_id: u32,
         block_offset: usize,
         fs: Arc<Mutex<EasyFileSystem>>,
         block_device: Arc<dyn BlockDevice>,
     ) -> Self {
         Self {
+            inode_id,
             block_id: block_id as usize,
             block_offset,
             fs,
@@ -30,6 +43,12 @@ impl Inode {
         }
     }
 
+    /// Where the disk inode is, which tells apart the files of a file
+    /// system, each opened as an `Inode` of its own.
+    pub fn position(&self) -> (usize, usize) {
+        (self.block_id, self.block_offset)
+    }
+
     fn read_disk_inode<V>(&self, f: impl FnOnce(&DiskInode) -> V) -> V {
         get_block_cache(self.block_id, Arc::clone(&self.block_device))
             .lock()
@@ -42,7 +61,8 @@ impl Inode {
             .modify(self.block_offset, f)
     }
 
-    fn find_inode_id(&self, name: &str, disk_inode: &DiskInode) -> Option<u32> {
+    /// The index and inode number of the entry `name`.
+    fn find_entry(&self, name: &str, disk_inode: &DiskInode) -> Option<(usize, u32)> {
         // assert it is a directory
         assert!(disk_inode.is_dir());
         let file_count = (disk_inode.size as usize) / DIRENT_SZ;
@@ -52,52 +72,158 @@ impl Inode {
                 disk_inode.read_at(DIRENT_SZ * i, dirent.as_bytes_mut(), &self.block_device,),
                 DIRENT_SZ,
             );
-            if dirent.name() == name {
-                return Some(dirent.inode_number() as u32);
+            if !dirent.is_empty() && dirent.name() == name {
+                return Some((i, dirent.inode_number() as u32));
             }
         }
         None
     }
 
-    pub fn find(&self, name: &str) -> Option<Arc<Inode>> {
-        let fs = self.fs.lock();
-        self.read_disk_inode(|disk_inode| {
-            self.find_inode_id(name, disk_inode).map(|inode_id| {
-                let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
-                Arc::new(Self::new(
-                    block_id,
-                    block_offset,
-                    self.fs.clone(),
-                    self.block_device.clone(),
-                ))
+    fn find_inode_id(&self, name: &str, disk_inode: &DiskInode) -> Option<u32> {
+        self.find_entry(name, disk_inode)
+            .map(|(_, inode_id)| inode_id)
+    }
+
+    /// The number of entries of a directory, `.` and `..` included.
+    fn entry_count(&self, disk_inode: &DiskInode) -> usize {
+        let file_count = (disk_inode.size as usize) / DIRENT_SZ;
+        let mut dirent = DirEntry::empty();
+        (0..file_count)
+            .filter(|i| {
+                disk_inode.read_at(DIRENT_SZ * i, dirent.as_bytes_mut(), &self.block_device);
+                !dirent.is_empty()
             })
-        })
+            .count()
+    }
+
+    fn lookup_entry(&self, name: &str) -> Option<(usize, u32)> {
+        self.read_disk_inode(|disk_inode| self.find_entry(name, disk_inode))
+    }
+
+    /// Overwrite the `index`th entry of this directory.
+    fn set_entry(&self, index: usize, dirent: &DirEntry) {
+        self.modify_disk_inode(|dir_inode| {
+            dir_inode.overwrite_at(index * DIRENT_SZ, dirent.as_bytes(), &self.block_device);
+        });
     }
 
-    fn increase_size(
+    /// Add the entry `name` to this directory, in the slot of a removed one
+    /// if there is any.
+    fn add_entry(&self, name: &str, inode_id: u32, fs: &mut MutexGuard<EasyFileSystem>) {
+        self.modify_disk_inode(|dir_inode| {
+            let file_count = (dir_inode.size as usize) / DIRENT_SZ;
+            let mut dirent = DirEntry::empty();
+            let slot = (0..file_count)
+                .find(|i| {
+                    dir_inode.read_at(DIRENT_SZ * i, dirent.as_bytes_mut(), &self.block_device);
+                    dirent.is_empty()
+                })
+                .unwrap_or(file_count);
+            let dirent = DirEntry::new(name, inode_id);
+            self.write_disk_inode(dir_inode, slot * DIRENT_SZ, dirent.as_bytes(), fs);
+        });
+    }
+
+    fn read_inode<V>(
+        &self,
+        inode_id: u32,
+        fs: &EasyFileSystem,
+        f: impl FnOnce(&DiskInode) -> V,
+    ) -> V {
+        let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+        get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+            .lock()
+            .read(block_offset, f)
+    }
+
+    fn modify_inode<V>(
+        &self,
+        inode_id: u32,
+        fs: &EasyFileSystem,
+        f: impl FnOnce(&mut DiskInode) -> V,
+    ) -> V {
+        let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+        get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+            .lock()
+            .modify(block_offset, f)
+    }
+
+    /// One entry naming `inode_id` went away. Free it if it was the last
+    /// one and no `Inode` is left either, otherwise the last `Inode` does.
+    fn drop_link(&self, inode_id: u32, fs: &mut MutexGuard<EasyFileSystem>) {
+        let nlink = self.modify_inode(inode_id, fs, |disk_inode| {
+            disk_inode.nlink -= 1;
+            disk_inode.nlink
+        });
+        if nlink == 0 && !fs.is_referenced(inode_id) {
+            fs.free_inode(inode_id);
+        }
+    }
+
+    fn inode_at(&self, inode_id: u32, fs: &mut EasyFileSystem) -> Arc<Inode> {
+        let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+        fs.get_inode(inode_id);
+        Arc::new(Self::new(
+            inode_id,
+            block_id,
+            block_offset,
+            self.fs.clone(),
+            self.block_device.clone(),
+        ))
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.read_disk_inode(|disk_inode| disk_inode.is_dir())
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.read_disk_inode(|disk_inode| disk_inode.is_symlink())
+    }
+
+    pub fn inode_type(&self) -> DiskInodeType {
+        self.read_disk_inode(|disk_inode| disk_inode.inode_type())
+    }
+
+    pub fn find(&self, name: &str) -> Option<Arc<Inode>> {
+        let mut fs = self.fs.lock();
+        self.lookup_entry(name)
+            .map(|(_, inode_id)| self.inode_at(inode_id, &mut fs))
+    }
+
+    /// Write `buf` at `offset` of `disk_inode`, with the blocks of holes
+    /// taken from `fs`.
+    fn write_disk_inode(
         &self,
-        new_size: u32,
         disk_inode: &mut DiskInode,
+        offset: usize,
+        buf: &[u8],
         fs: &mut MutexGuard<EasyFileSystem>,
-    ) {
-        if new_size < disk_inode.size {
-            return;
-        }
-        let blocks_needed = disk_inode.blocks_num_needed(new_size);
-        let mut v: Vec<u32> = Vec::new();
-        for _ in 0..blocks_needed {
-            v.push(fs.alloc_data());
-        }
-        disk_inode.increase_size(new_size, v, &self.block_device);
+    ) -> usize {
+        disk_inode.write_at(offset, buf, &mut || fs.alloc_data(), &self.block_device)
     }
 
-    pub fn create(&self, name: &str) -> Option<Arc<Inode>> {
+    /// Fill a new directory with `.` and `..`.
+    pub fn init_dir(&self, parent_id: u32, fs: &mut MutexGuard<EasyFileSystem>) {
+        self.modify_disk_inode(|disk_inode| {
+            let dot = DirEntry::new(".", self.inode_id);
+            let dotdot = DirEntry::new("..", parent_id);
+            self.write_disk_inode(disk_inode, 0, dot.as_bytes(), fs);
+            self.write_disk_inode(disk_inode, DIRENT_SZ, dotdot.as_bytes(), fs);
+        });
+    }
+
+    /// Create the entry `name` of a new inode holding `content`, in one
+    /// transaction.
+    fn create_inode(&self, name: &str, type_: DiskInodeType, content: &[u8]) -> Option<Arc<Inode>> {
+        if !valid_name(name) {
+            return None;
+        }
         let mut fs = self.fs.lock();
-        let op = |root_inode: &mut DiskInode| {
+        let op = |dir_inode: &mut DiskInode| {
             // assert it is a directory
-            assert!(root_inode.is_dir());
+            assert!(dir_inode.is_dir());
             // has the file been created?
-            self.find_inode_id(name, root_inode)
+            self.find_inode_id(name, dir_inode)
         };
         if self.modify_disk_inode(op).is_some() {
             return None;
@@ -106,39 +232,144 @@ impl Inode {
         // alloc a inode with an indirect block
         let new_inode_id = fs.alloc_inode();
         // initialize inode
+        let is_dir = type_ == DiskInodeType::Directory;
         let (new_inode_block_id, new_inode_block_offset) = fs.get_disk_inode_pos(new_inode_id);
         get_block_cache(new_inode_block_id as usize, Arc::clone(&self.block_device))
             .lock()
             .modify(new_inode_block_offset, |new_inode: &mut DiskInode| {
-                new_inode.initialize(DiskInodeType::File);
+                new_inode.initialize(type_);
             });
-        self.modify_disk_inode(|root_inode| {
-            // append file in the dirent
-            let file_count = (root_inode.size as usize) / DIRENT_SZ;
-            let new_size = (file_count + 1) * DIRENT_SZ;
-            // increase size
-            self.increase_size(new_size as u32, root_inode, &mut fs);
-            // write dirent
-            let dirent = DirEntry::new(name, new_inode_id);
-            root_inode.write_at(
-                file_count * DIRENT_SZ,
-                dirent.as_bytes(),
-                &self.block_device,
-            );
-        });
-
-        let (block_id, block_offset) = fs.get_disk_inode_pos(new_inode_id);
-        block_cache_sync_all();
+        let new_inode = self.inode_at(new_inode_id, &mut fs);
+        if is_dir {
+            new_inode.init_dir(self.inode_id, &mut fs);
+        }
+        if !content.is_empty() {
+            new_inode.modify_disk_inode(|disk_inode| {
+                new_inode.write_disk_inode(disk_inode, 0, content, &mut fs);
+            });
+        }
+        self.add_entry(name, new_inode_id, &mut fs);
+        fs.commit();
         // return inode
-        Some(Arc::new(Self::new(
-            block_id,
-            block_offset,
-            self.fs.clone(),
-            self.block_device.clone(),
-        )))
+        Some(new_inode)
         // release efs lock automatically by compiler
     }
 
+    pub fn create(&self, name: &str) -> Option<Arc<Inode>> {
+        self.create_inode(name, DiskInodeType::File, &[])
+    }
+
+    pub fn mkdir(&self, name: &str) -> Option<Arc<Inode>> {
+        self.create_inode(name, DiskInodeType::Directory, &[])
+    }
+
+    /// Create the symbolic link `name` to `target`, which must fit in a
+    /// transaction.
+    pub fn symlink(&self, name: &str, target: &str) -> Option<Arc<Inode>> {
+        if target.is_empty() || target.len() > WRITE_CHUNK {
+            return None;
+        }
+        self.create_inode(name, DiskInodeType::Symlink, target.as_bytes())
+    }
+
+    /// Create the named pipe `name`.
+    pub fn mkfifo(&self, name: &str) -> Option<Arc<Inode>> {
+        self.create_inode(name, DiskInodeType::Fifo, &[])
+    }
+
+    /// Create the socket `name`, for an AF_UNIX socket to be bound to.
+    pub fn mksock(&self, name: &str) -> Option<Arc<Inode>> {
+        self.create_inode(name, DiskInodeType::Socket, &[])
+    }
+
+    /// Remove the empty directory `name`.
+    pub fn rmdir(&self, name: &str) -> bool {
+        self.remove(name, true)
+    }
+
+    /// Remove the entry `name` of a file.
+    pub fn unlink(&self, name: &str) -> bool {
+        self.remove(name, false)
+    }
+
+    fn remove(&self, name: &str, dir: bool) -> bool {
+        if !valid_name(name) {
+            return false;
+        }
+        let mut fs = self.fs.lock();
+        let (index, inode_id) = match self.lookup_entry(name) {
+            Some(entry) => entry,
+            None => return false,
+        };
+        let removable = self.read_inode(inode_id, &fs, |disk_inode| {
+            disk_inode.is_dir() == dir && (!dir || self.entry_count(disk_inode) == 2)
+        });
+        if !removable {
+            return false;
+        }
+        self.set_entry(index, &DirEntry::empty());
+        self.drop_link(inode_id, &mut fs);
+        fs.commit();
+        true
+    }
+
+    /// Name the file `inode` `name` in this directory as well.
+    pub fn link(&self, name: &str, inode: &Inode) -> bool {
+        if !valid_name(name) || !Arc::ptr_eq(&self.fs, &inode.fs) || inode.is_dir() {
+            return false;
+        }
+        let mut fs = self.fs.lock();
+        if self.lookup_entry(name).is_some() {
+            return false;
+        }
+        self.add_entry(name, inode.inode_id, &mut fs);
+        inode.modify_disk_inode(|disk_inode| disk_inode.nlink += 1);
+        fs.commit();
+        true
+    }
+
+    /// Move the entry `old_name` to `new_name` in `new_dir`, replacing the
+    /// file there if any. Within a directory, the entry is rewritten in one
+    /// go.
+    pub fn rename(&self, old_name: &str, new_dir: &Inode, new_name: &str) -> bool {
+        if !valid_name(old_name) || !valid_name(new_name) || !Arc::ptr_eq(&self.fs, &new_dir.fs) {
+            return false;
+        }
+        let mut fs = self.fs.lock();
+        let (old_index, inode_id) = match self.lookup_entry(old_name) {
+            Some(entry) => entry,
+            None => return false,
+        };
+        let is_dir = self.read_inode(inode_id, &fs, |disk_inode| disk_inode.is_dir());
+        match new_dir.lookup_entry(new_name) {
+            // two names of the same file
+            Some((_, target_id)) if target_id == inode_id => return true,
+            Some((new_index, target_id)) => {
+                if is_dir || self.read_inode(target_id, &fs, |disk_inode| disk_inode.is_dir()) {
+                    return false;
+                }
+                new_dir.set_entry(new_index, &DirEntry::new(new_name, inode_id));
+                self.set_entry(old_index, &DirEntry::empty());
+                self.drop_link(target_id, &mut fs);
+            }
+            None if self.inode_id == new_dir.inode_id => {
+                self.set_entry(old_index, &DirEntry::new(new_name, inode_id));
+            }
+            None => {
+                new_dir.add_entry(new_name, inode_id, &mut fs);
+                self.set_entry(old_index, &DirEntry::empty());
+            }
+        }
+        if is_dir && self.inode_id != new_dir.inode_id {
+            let dotdot = DirEntry::new("..", new_dir.inode_id);
+            self.modify_inode(inode_id, &fs, |disk_inode| {
+                disk_inode.overwrite_at(DIRENT_SZ, dotdot.as_bytes(), &self.block_device);
+            });
+        }
+        fs.commit();
+        true
+    }
+
     pub fn ls(&self) -> Vec<String> {
         let _fs = self.fs.lock();
         self.read_disk_inode(|disk_inode| {
@@ -150,37 +381,178 @@ impl Inode {
                     disk_inode.read_at(i * DIRENT_SZ, dirent.as_bytes_mut(), &self.block_device,),
                     DIRENT_SZ,
                 );
-                v.push(String::from(dirent.name()));
+                if !dirent.is_empty() {
+                    v.push(String::from(dirent.name()));
+                }
             }
             v
         })
     }
 
+    /// The first entry in slot `index` or after it: the slot, the name, the
+    /// inode number and its type. An entry stays in its slot until removed,
+    /// so a reader going from slot to slot meets each entry there all along
+    /// once, whatever comes and goes meanwhile.
+    pub fn read_dir(&self, index: usize) -> Option<(usize, String, u32, DiskInodeType)> {
+        let fs = self.fs.lock();
+        let (slot, dirent) = self.read_disk_inode(|disk_inode| {
+            let file_count = (disk_inode.size as usize) / DIRENT_SZ;
+            let mut dirent = DirEntry::empty();
+            (index..file_count)
+                .find(|i| {
+                    disk_inode.read_at(DIRENT_SZ * i, dirent.as_bytes_mut(), &self.block_device);
+                    !dirent.is_empty()
+                })
+                .map(|slot| (slot, dirent))
+        })?;
+        let inode_id = dirent.inode_number();
+        let type_ = self.read_inode(inode_id, &fs, |disk_inode| disk_inode.inode_type());
+        Some((slot, String::from(dirent.name()), inode_id, type_))
+    }
+
     pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
         let _fs = self.fs.lock();
         self.read_disk_inode(|disk_inode| disk_inode.read_at(offset, buf, &self.block_device))
     }
 
+    /// Read from `offset` on into `buf` as `read_at` does, but for the
+    /// blocks which only the disk holds: those are left for the caller to
+    /// read from `block_device`, and listed by their place in `buf`, in
+    /// blocks, and on the disk. `offset` is at the start of a block, and
+    /// blocks are read whole, the end of the last one included, which
+    /// easy-fs leaves zeroed.
+    pub fn read_cached_blocks(&self, offset: usize, buf: &mut [u8]) -> Vec<(usize, usize)> {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| {
+            let end = (offset + buf.len()).min(disk_inode.size as usize);
+            let mut uncached = Vec::new();
+            for (i, block) in buf.chunks_mut(BLOCK_SZ).enumerate() {
+                let start = offset + i * BLOCK_SZ;
+                if start >= end {
+                    break;
+                }
+                match disk_inode.get_block_id(start / BLOCK_SZ, &self.block_device) as usize {
+                    0 => block.fill(0),
+                    block_id => {
+                        if !read_cached_block(block_id, &self.block_device, block) {
+                            uncached.push((i, block_id));
+                        }
+                    }
+                }
+            }
+            uncached
+        })
+    }
+
+    /// The device the blocks of the file are read from, in front of the
+    /// disk.
+    pub fn block_device(&self) -> Arc<dyn BlockDevice> {
+        Arc::clone(&self.block_device)
+    }
+
     pub fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
         let mut fs = self.fs.lock();
-        let size = self.modify_disk_inode(|disk_inode| {
-            self.increase_size((offset + buf.len()) as u32, disk_inode, &mut fs);
-            disk_inode.write_at(offset, buf, &self.block_device)
+        self.write_chunks(offset, buf, &mut fs)
+    }
+
+    /// Write `buf` at `offset` in pieces the journal can hold, each of which
+    /// is committed on its own.
+    fn write_chunks(
+        &self,
+        offset: usize,
+        buf: &[u8],
+        fs: &mut MutexGuard<EasyFileSystem>,
+    ) -> usize {
+        let mut written = 0;
+        for chunk in buf.chunks(WRITE_CHUNK) {
+            let offset = offset + written;
+            written += self.modify_disk_inode(|disk_inode| {
+                self.write_disk_inode(disk_inode, offset, chunk, fs)
+            });
+            fs.commit();
+        }
+        written
+    }
+
+    pub fn inode_id(&self) -> u32 {
+        self.inode_id
+    }
+
+    pub fn nlink(&self) -> u32 {
+        self.read_disk_inode(|disk_inode| disk_inode.nlink)
+    }
+
+    /// The permission bits, the owner and the group.
+    pub fn permissions(&self) -> (u32, u32, u32) {
+        self.read_disk_inode(|disk_inode| (disk_inode.mode, disk_inode.uid, disk_inode.gid))
+    }
+
+    /// Set the permission bits to the low 12 bits of `mode`.
+    pub fn chmod(&self, mode: u32) {
+        let fs = self.fs.lock();
+        self.modify_disk_inode(|disk_inode| disk_inode.mode = mode & 0o7777);
+        fs.commit();
+    }
+
+    pub fn chown(&self, uid: u32, gid: u32) {
+        let fs = self.fs.lock();
+        self.modify_disk_inode(|disk_inode| {
+            disk_inode.uid = uid;
+            disk_inode.gid = gid;
         });
-        block_cache_sync_all();
-        size
+        fs.commit();
+    }
+
+    /// The modification time, in seconds since the Unix epoch.
+    pub fn mtime(&self) -> u64 {
+        self.read_disk_inode(|disk_inode| disk_inode.mtime)
+    }
+
+    pub fn set_mtime(&self, mtime: u64) {
+        let fs = self.fs.lock();
+        self.modify_disk_inode(|disk_inode| disk_inode.mtime = mtime);
+        fs.commit();
+    }
+
+    /// Blocks taken by the data and the index blocks.
+    pub fn blocks(&self) -> u32 {
+        self.read_disk_inode(|disk_inode| disk_inode.blocks)
+    }
+
+    pub fn size(&self) -> usize {
+        self.read_disk_inode(|disk_inode| disk_inode.size as usize)
+    }
+
+    /// Write `buf` at the end, which is found under the same lock as the
+    /// write, and return where that was.
+    pub fn append(&self, buf: &[u8]) -> usize {
+        let mut fs = self.fs.lock();
+        let offset = self.read_disk_inode(|disk_inode| disk_inode.size as usize);
+        self.write_chunks(offset, buf, &mut fs);
+        offset
     }
 
     pub fn clear(&self) {
         let mut fs = self.fs.lock();
         self.modify_disk_inode(|disk_inode| {
-            let size = disk_inode.size;
+            let blocks = disk_inode.blocks;
             let data_blocks_dealloc = disk_inode.clear_size(&self.block_device);
-            assert!(data_blocks_dealloc.len() == DiskInode::total_blocks(size) as usize);
+            assert!(data_blocks_dealloc.len() == blocks as usize);
             for data_block in data_blocks_dealloc.into_iter() {
                 fs.dealloc_data(data_block);
             }
         });
-        block_cache_sync_all();
+        fs.commit();
+    }
+}
+
+impl Drop for Inode {
+    /// The last `Inode` of an unlinked inode frees it.
+    fn drop(&mut self) {
+        let mut fs = self.fs.lock();
+        if fs.put_inode(self.inode_id) && self.read_disk_inode(|disk_inode| disk_inode.nlink == 0) {
+            fs.free_inode(self.inode_id);
+            fs.commit();
+        }
     }
 }
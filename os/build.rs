@@ -1,6 +1,45 @@
+use std::{env, fs, path::PathBuf};
+
 static TARGET_PATH: &str = "../user/target/riscv64gc-unknown-none-elf/release/";
 
 fn main() {
     println!("cargo:rerun-if-changed=../user/src/");
     println!("cargo:rerun-if-changed={}", TARGET_PATH);
+    symbols();
+    initramfs();
+}
+
+/// Put the symbol table at KERNEL_SYMBOLS, from an earlier link of the
+/// kernel, where src/backtrace.rs takes it from, or an empty one. It is
+/// only written if it changed, lest the kernel be built again for nothing.
+fn symbols() {
+    println!("cargo:rerun-if-env-changed=KERNEL_SYMBOLS");
+    let table = match env::var("KERNEL_SYMBOLS") {
+        Ok(path) => {
+            println!("cargo:rerun-if-changed={}", path);
+            fs::read_to_string(path).unwrap_or_default()
+        }
+        Err(_) => String::new(),
+    };
+    let out = PathBuf::from(env::var("OUT_DIR").unwrap()).join("symbols.txt");
+    if fs::read_to_string(&out).ok().as_deref() != Some(table.as_str()) {
+        fs::write(&out, table).unwrap();
+    }
+}
+
+/// Put the cpio archive at INITRAMFS where src/fs/initramfs.rs builds it
+/// in from, or nothing; written only if it changed, as the symbols are.
+fn initramfs() {
+    println!("cargo:rerun-if-env-changed=INITRAMFS");
+    let archive = match env::var("INITRAMFS") {
+        Ok(path) if !path.is_empty() => {
+            println!("cargo:rerun-if-changed={}", path);
+            fs::read(&path).unwrap_or_else(|error| panic!("INITRAMFS={}: {}", path, error))
+        }
+        _ => Vec::new(),
+    };
+    let out = PathBuf::from(env::var("OUT_DIR").unwrap()).join("initramfs.cpio");
+    if fs::read(&out).ok().as_deref() != Some(archive.as_slice()) {
+        fs::write(&out, archive).unwrap();
+    }
 }
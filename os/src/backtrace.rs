@@ -0,0 +1,115 @@
+//! Backtraces of the kernel, walking the frame pointers the kernel is built
+//! to keep and naming the functions from a symbol table built into it.
+//!
+//! `make kernel` links the kernel twice: the symbols of the first link,
+//! as `rust-nm` lists them, are built into the second, whose code is the
+//! same and lies at the same addresses. A kernel built by cargo alone has
+//! an empty table, and its backtraces give addresses only.
+//!
+//! The table has the addresses the kernel is linked at, which KASLR moves
+//! the kernel away from, so addresses are looked up less that offset.
+
+use crate::config::KERNEL_STACK_SIZE;
+use crate::kaslr::link_address;
+use crate::task::current_kstack_top;
+use core::arch::asm;
+
+/// Lines of `address name`, the address in hex, in order of address.
+static SYMBOLS: &str = include_str!(concat!(env!("OUT_DIR"), "/symbols.txt"));
+
+/// Frames shown at most, lest a broken chain go on for ever.
+const MAX_FRAMES: usize = 32;
+
+extern "C" {
+    fn boot_stack_lower_bound();
+    fn boot_stack_top();
+    fn stext();
+    fn etext();
+}
+
+/// The function `addr` is in and how far into it, if there is a symbol
+/// table and `addr` is in the kernel code.
+pub fn symbolize(addr: usize) -> Option<(&'static str, usize)> {
+    if !(stext as usize..etext as usize).contains(&addr) {
+        return None;
+    }
+    let addr = link_address(addr);
+    SYMBOLS
+        .lines()
+        .filter_map(|line| {
+            let (start, name) = line.split_once(' ')?;
+            Some((usize::from_str_radix(start, 16).ok()?, name))
+        })
+        .take_while(|(start, _)| *start <= addr)
+        .last()
+        .map(|(start, name)| (strip_hash(name), addr - start))
+}
+
+/// `name` without the hash rustc ends it with, like `::h0123456789abcdef`.
+fn strip_hash(name: &str) -> &str {
+    match name.rsplit_once("::h") {
+        Some((path, hash)) if hash.len() == 16 && hash.bytes().all(|b| b.is_ascii_hexdigit()) => {
+            path
+        }
+        _ => name,
+    }
+}
+
+/// Whether the table is of this kernel, the function it names at the
+/// address of rust_main being rust_main.
+fn symbols_match() -> bool {
+    matches!(symbolize(crate::rust_main as usize), Some((name, 0)) if name.ends_with("rust_main"))
+}
+
+/// The bounds of the kernel stack `sp` is on: the boot stack, which the
+/// idle loop runs on too, or that of the current task.
+pub fn stack_bounds(sp: usize) -> Option<(usize, usize)> {
+    let boot = (boot_stack_lower_bound as usize, boot_stack_top as usize);
+    if (boot.0..boot.1).contains(&sp) {
+        return Some(boot);
+    }
+    let top = current_kstack_top()?;
+    let task = (top - KERNEL_STACK_SIZE, top);
+    (task.0..task.1).contains(&sp).then_some(task)
+}
+
+/// Print the calls which led here, the innermost first, as far as the
+/// frame pointers go on the stack it runs on.
+pub fn print_backtrace() {
+    let (mut fp, sp): (usize, usize);
+    unsafe {
+        asm!("mv {}, s0", out(reg) fp);
+        asm!("mv {}, sp", out(reg) sp);
+    }
+    let (bottom, top) = match stack_bounds(sp) {
+        Some(bounds) => bounds,
+        None => {
+            println!("backtrace: not on a kernel stack known");
+            return;
+        }
+    };
+    let symbolic = symbols_match();
+    if !symbolic {
+        println!("backtrace: no symbols of this kernel, addresses only");
+    }
+    println!("---START BACKTRACE---");
+    for i in 0..MAX_FRAMES {
+        // the return address and the caller's frame pointer are the two
+        // words below where the frame pointer points
+        if fp < bottom + 16 || fp > top || fp % 8 != 0 {
+            break;
+        }
+        let (ra, caller_fp) =
+            unsafe { (*((fp - 8) as *const usize), *((fp - 16) as *const usize)) };
+        if ra == 0 {
+            break;
+        }
+        // the call is the instruction before the one returned to
+        match symbolize(ra - 1).filter(|_| symbolic) {
+            Some((name, offset)) => println!("#{:<2} {:#x} {}+{:#x}", i, ra, name, offset + 1),
+            None => println!("#{:<2} {:#x}", i, ra),
+        }
+        fp = caller_fp;
+    }
+    println!("---END   BACKTRACE---");
+}
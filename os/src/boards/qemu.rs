@@ -1,54 +1,174 @@
+//! The virt machine of QEMU, the board built for unless a board feature
+//! picks another.
+
 pub const CLOCK_FREQ: usize = 12500000;
+pub const MEMORY_START: usize = 0x80000000;
+pub const MEMORY_END: usize = 0x88000000;
 
 pub const MMIO: &[(usize, usize)] = &[
     (0x0010_0000, 0x00_2000), // VIRT_TEST/RTC  in virt machine
     (0x2000000, 0x10000),     // core local interrupter (CLINT)
     (0xc000000, 0x210000),    // VIRT_PLIC in virt machine
     (0x10000000, 0x9000),     // VIRT_UART0 with GPU  in virt machine
+    (0x3000000, 0x10000),     // VIRT_PCIE_PIO, I/O windows of PCI devices
+    (0x30000000, 0x100000),   // VIRT_PCIE_ECAM, bus 0 of it only
 ];
 
 pub type BlockDeviceImpl = crate::drivers::block::VirtIOBlock;
-pub type CharDeviceImpl = crate::drivers::chardev::NS16550a<VIRT_UART>;
+pub type CharDeviceImpl = crate::drivers::chardev::NS16550a<{ phys_to_virt(VIRT_UART) }>;
+
+/// The virtio-mmio slots of the disks, the root disk, the first device the
+/// Makefile gives QEMU, then the second disk, given after the others.
+pub const DISKS: &[usize] = &[0x1000_8000, 0x1000_3000];
+/// The virtio-mmio slots of the other devices, in the order of the
+/// Makefile counting down from the last slot.
+pub const VIRTIO_GPU: Option<usize> = Some(0x1000_7000);
+pub const VIRTIO_MOUSE: Option<usize> = Some(0x1000_6000);
+pub const VIRTIO_KEYBOARD: Option<usize> = Some(0x1000_5000);
+pub const VIRTIO_NET: Option<usize> = Some(0x1000_4000);
+/// The configuration space of bus 0 and the I/O windows of PCI devices.
+pub const PCIE: Option<(usize, usize)> = Some((VIRT_PCIE_ECAM, VIRT_PCIE_PIO));
 
+/// The sifive_test device, which ends QEMU with a status.
+pub const VIRT_TEST: usize = 0x10_0000;
 pub const VIRT_PLIC: usize = 0xC00_0000;
 pub const VIRT_UART: usize = 0x1000_0000;
+pub const VIRT_PCIE_PIO: usize = 0x300_0000;
+pub const VIRT_PCIE_ECAM: usize = 0x3000_0000;
 #[allow(unused)]
 pub const VIRTGPU_XRES: u32 = 1280;
 #[allow(unused)]
 pub const VIRTGPU_YRES: u32 = 800;
 
-use crate::drivers::block::BLOCK_DEVICE;
+use crate::drivers::block::{BLOCK_DEVICE, BLOCK_DEVICE1};
 use crate::drivers::chardev::{CharDevice, UART};
 use crate::drivers::plic::{IntrTargetPriority, PLIC};
-use crate::drivers::{KEYBOARD_DEVICE, MOUSE_DEVICE};
+use crate::drivers::{KEYBOARD_DEVICE, MOUSE_DEVICE, NET_DEVICE};
+use crate::mm::phys_to_virt;
+use crate::power::register_device;
+use crate::trace::{trace, TraceEvent};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// The hart taking the interrupts, the one the kernel booted on.
+static BOOT_HART: AtomicUsize = AtomicUsize::new(0);
+
+/// The interrupt sources enabled in the PLIC, by source id.
+pub const IRQS: [(usize, &str); 6] = [
+    (3, "virtio-blk1"),
+    (4, "virtio-net"),
+    (5, "virtio-keyboard"),
+    (6, "virtio-mouse"),
+    (8, "virtio-blk"),
+    (10, "uart"),
+];
+/// Interrupts taken from each of IRQS.
+static IRQ_COUNTS: [AtomicUsize; 6] = [
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+];
 
-pub fn device_init() {
+pub fn device_init(hart_id: usize) {
     use riscv::register::sie;
-    let mut plic = unsafe { PLIC::new(VIRT_PLIC) };
-    let hart_id: usize = 0;
+    BOOT_HART.store(hart_id, Ordering::Relaxed);
+    let mut plic = unsafe { PLIC::new(phys_to_virt(VIRT_PLIC)) };
     let supervisor = IntrTargetPriority::Supervisor;
     let machine = IntrTargetPriority::Machine;
     plic.set_threshold(hart_id, supervisor, 0);
     plic.set_threshold(hart_id, machine, 1);
-    //irq nums: 5 keyboard, 6 mouse, 8 block, 10 uart
-    for intr_src_id in [5usize, 6, 8, 10] {
+    for (intr_src_id, _) in IRQS {
         plic.enable(hart_id, supervisor, intr_src_id);
         plic.set_priority(intr_src_id, 1);
     }
+    // typing wakes a suspended system, the disks, the network and the
+    // mouse do not
+    for (intr_src_id, name) in IRQS {
+        match intr_src_id {
+            5 => register_device(name, intr_src_id, true, None),
+            10 => register_device(name, intr_src_id, true, Some(UART.clone())),
+            _ => register_device(name, intr_src_id, false, None),
+        }
+    }
     unsafe {
         sie::set_sext();
     }
 }
 
 pub fn irq_handler() {
-    let mut plic = unsafe { PLIC::new(VIRT_PLIC) };
-    let intr_src_id = plic.claim(0, IntrTargetPriority::Supervisor);
-    match intr_src_id {
-        5 => KEYBOARD_DEVICE.handle_irq(),
-        6 => MOUSE_DEVICE.handle_irq(),
-        8 => BLOCK_DEVICE.handle_irq(),
+    if let Some(source) = irq_claim() {
+        irq_dispatch(source);
+        irq_complete(source);
+    }
+}
+
+/// The source of the interrupt pending with the highest priority, taken
+/// from the PLIC until completed, if any is.
+pub fn irq_claim() -> Option<usize> {
+    let mut plic = unsafe { PLIC::new(phys_to_virt(VIRT_PLIC)) };
+    let hart_id = BOOT_HART.load(Ordering::Relaxed);
+    match plic.claim(hart_id, IntrTargetPriority::Supervisor) {
+        0 => None,
+        source => Some(source as usize),
+    }
+}
+
+/// Handle the interrupt claimed from `source`.
+pub fn irq_dispatch(source: usize) {
+    trace(TraceEvent::IrqEnter { source });
+    match source {
+        3 => BLOCK_DEVICE1.as_ref().unwrap().handle_irq(),
+        4 => NET_DEVICE.as_ref().unwrap().handle_irq(),
+        5 => KEYBOARD_DEVICE.as_ref().unwrap().handle_irq(),
+        6 => MOUSE_DEVICE.as_ref().unwrap().handle_irq(),
+        8 => BLOCK_DEVICE.as_ref().unwrap().handle_irq(),
         10 => UART.handle_irq(),
-        _ => panic!("unsupported IRQ {}", intr_src_id),
+        _ => panic!("unsupported IRQ {}", source),
+    }
+    if let Some(index) = IRQS.iter().position(|(id, _)| *id == source) {
+        IRQ_COUNTS[index].fetch_add(1, Ordering::Relaxed);
+    }
+    trace(TraceEvent::IrqExit);
+}
+
+/// Mask or unmask `source` in the PLIC.
+pub fn irq_set_enabled(source: usize, enabled: bool) {
+    let mut plic = unsafe { PLIC::new(phys_to_virt(VIRT_PLIC)) };
+    let hart_id = BOOT_HART.load(Ordering::Relaxed);
+    if enabled {
+        plic.enable(hart_id, IntrTargetPriority::Supervisor, source);
+    } else {
+        plic.disable(hart_id, IntrTargetPriority::Supervisor, source);
     }
-    plic.complete(0, IntrTargetPriority::Supervisor, intr_src_id);
+}
+
+/// Let `source` interrupt again.
+pub fn irq_complete(source: usize) {
+    let mut plic = unsafe { PLIC::new(phys_to_virt(VIRT_PLIC)) };
+    let hart_id = BOOT_HART.load(Ordering::Relaxed);
+    plic.complete(hart_id, IntrTargetPriority::Supervisor, source as u32);
+}
+
+/// Each source of IRQS with the interrupts taken from it.
+pub fn irq_counts() -> impl Iterator<Item = (usize, &'static str, usize)> {
+    IRQS.iter()
+        .zip(IRQ_COUNTS.iter())
+        .map(|((id, name), count)| (*id, *name, count.load(Ordering::Relaxed)))
+}
+
+/// Leave QEMU through its test device, with status `code`: 0 for success,
+/// otherwise failure.
+#[allow(unused)]
+pub fn qemu_exit(code: u32) -> ! {
+    const FINISHER_PASS: u32 = 0x5555;
+    const FINISHER_FAIL: u32 = 0x3333;
+    let value = match code {
+        0 => FINISHER_PASS,
+        _ => code << 16 | FINISHER_FAIL,
+    };
+    unsafe { (phys_to_virt(VIRT_TEST) as *mut u32).write_volatile(value) };
+    // without the device, at least stop
+    crate::sbi::shutdown(code != 0)
 }
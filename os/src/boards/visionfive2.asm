@@ -0,0 +1,20 @@
+    # the header of a RISC-V Linux Image, for the booti of U-Boot, which
+    # jumps to its start with the hart in a0 and the device tree in a1
+    .section .text.head
+    .option push
+    .option norvc
+    j _start
+    .option pop
+    .word 0
+    # text_offset, from the start of the memory
+    .dword 0x200000
+    .dword image_size
+    # flags: little endian
+    .dword 0
+    # version 0.2
+    .word 2
+    .word 0
+    .dword 0
+    .ascii "RISCV\0\0\0"
+    .ascii "RSC\x05"
+    .word 0
@@ -0,0 +1,135 @@
+//! The StarFive VisionFive 2, a JH7110 with four U74 harts and a S7
+//! monitor hart 0 without S mode, booted by OpenSBI and U-Boot, which loads
+//! the kernel at 0x4020_0000 and the file system image at RAMDISK:
+//!
+//! ```text
+//! load mmc 1:3 0x40200000 os.bin
+//! load mmc 1:3 0xb0000000 fs.img
+//! booti 0x40200000 - ${fdtcontroladdr}
+//! ```
+//!
+//! There is no virtio, no PCI the kernel knows of, and no driver for the
+//! SD card, so / is that image in memory and goes with the next boot.
+
+core::arch::global_asm!(include_str!("visionfive2.asm"));
+
+pub const CLOCK_FREQ: usize = 4_000_000;
+/// The 2 GiB of the smallest board, less the ram disk at its top.
+pub const MEMORY_START: usize = 0x4000_0000;
+pub const MEMORY_END: usize = RAMDISK;
+
+pub const MMIO: &[(usize, usize)] = &[
+    (0x0200_0000, 0x1_0000),  // core local interrupter (CLINT)
+    (0x0c00_0000, 0x21_0000), // PLIC
+    (0x1000_0000, 0x1_0000),  // UART0
+    (RAMDISK, RAMDISK_SIZE),  // the file system image U-Boot loaded
+];
+
+/// Where U-Boot is told to load the file system image, and its size at
+/// most, which is as large as the image of the Makefile with its swap.
+pub const RAMDISK: usize = 0xb000_0000;
+pub const RAMDISK_SIZE: usize = 0x1000_0000;
+
+pub type BlockDeviceImpl = crate::drivers::block::RamDisk<RAMDISK_SIZE>;
+/// A DesignWare 8250, its registers 4 bytes apart.
+pub type CharDeviceImpl = crate::drivers::chardev::NS16550a<{ phys_to_virt(UART0) }, 2>;
+
+pub const DISKS: &[usize] = &[RAMDISK];
+pub const VIRTIO_GPU: Option<usize> = None;
+pub const VIRTIO_MOUSE: Option<usize> = None;
+pub const VIRTIO_KEYBOARD: Option<usize> = None;
+pub const VIRTIO_NET: Option<usize> = None;
+pub const PCIE: Option<(usize, usize)> = None;
+
+pub const PLIC_BASE: usize = 0xc00_0000;
+pub const UART0: usize = 0x1000_0000;
+
+use crate::drivers::chardev::{CharDevice, UART};
+use crate::drivers::plic::{IntrTargetPriority, PLIC};
+use crate::mm::phys_to_virt;
+use crate::power::register_device;
+use crate::trace::{trace, TraceEvent};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// The hart taking the interrupts, the one OpenSBI booted the kernel on,
+/// one of 1 to 4.
+static BOOT_HART: AtomicUsize = AtomicUsize::new(1);
+
+/// The interrupt sources enabled in the PLIC, by source id.
+pub const IRQS: [(usize, &str); 1] = [(32, "uart")];
+/// Interrupts taken from each of IRQS.
+static IRQ_COUNTS: [AtomicUsize; 1] = [AtomicUsize::new(0)];
+
+fn plic() -> PLIC {
+    unsafe { PLIC::new_with_machine_only_hart0(phys_to_virt(PLIC_BASE)) }
+}
+
+pub fn device_init(hart_id: usize) {
+    use riscv::register::sie;
+    BOOT_HART.store(hart_id, Ordering::Relaxed);
+    let mut plic = plic();
+    let supervisor = IntrTargetPriority::Supervisor;
+    plic.set_threshold(hart_id, supervisor, 0);
+    for (intr_src_id, _) in IRQS {
+        plic.enable(hart_id, supervisor, intr_src_id);
+        plic.set_priority(intr_src_id, 1);
+    }
+    register_device("uart", 32, true, Some(UART.clone()));
+    unsafe {
+        sie::set_sext();
+    }
+}
+
+pub fn irq_handler() {
+    if let Some(source) = irq_claim() {
+        irq_dispatch(source);
+        irq_complete(source);
+    }
+}
+
+/// The source of the interrupt pending with the highest priority, taken
+/// from the PLIC until completed, if any is.
+pub fn irq_claim() -> Option<usize> {
+    let hart_id = BOOT_HART.load(Ordering::Relaxed);
+    match plic().claim(hart_id, IntrTargetPriority::Supervisor) {
+        0 => None,
+        source => Some(source as usize),
+    }
+}
+
+/// Handle the interrupt claimed from `source`.
+pub fn irq_dispatch(source: usize) {
+    trace(TraceEvent::IrqEnter { source });
+    match source {
+        32 => UART.handle_irq(),
+        _ => panic!("unsupported IRQ {}", source),
+    }
+    if let Some(index) = IRQS.iter().position(|(id, _)| *id == source) {
+        IRQ_COUNTS[index].fetch_add(1, Ordering::Relaxed);
+    }
+    trace(TraceEvent::IrqExit);
+}
+
+/// Mask or unmask `source` in the PLIC.
+pub fn irq_set_enabled(source: usize, enabled: bool) {
+    let mut plic = plic();
+    let hart_id = BOOT_HART.load(Ordering::Relaxed);
+    if enabled {
+        plic.enable(hart_id, IntrTargetPriority::Supervisor, source);
+    } else {
+        plic.disable(hart_id, IntrTargetPriority::Supervisor, source);
+    }
+}
+
+/// Let `source` interrupt again.
+pub fn irq_complete(source: usize) {
+    let hart_id = BOOT_HART.load(Ordering::Relaxed);
+    plic().complete(hart_id, IntrTargetPriority::Supervisor, source as u32);
+}
+
+/// Each source of IRQS with the interrupts taken from it.
+pub fn irq_counts() -> impl Iterator<Item = (usize, &'static str, usize)> {
+    IRQS.iter()
+        .zip(IRQ_COUNTS.iter())
+        .map(|((id, name), count)| (*id, *name, count.load(Ordering::Relaxed)))
+}
@@ -0,0 +1,196 @@
+//! The kernel command line: the bootargs of the /chosen node of the device
+//! tree the SBI hands over, which QEMU fills in from -append, or else the
+//! `CMDLINE` environment variable at compile time.
+//!
+//! It is a list of `name=value` options separated by spaces, the last of a
+//! name counting, which the subsystems read as they start:
+//!
+//! - `loglevel=`: the filter of the kernel log, like `warn,net=debug`, as
+//!   `LOG` at compile time sets it;
+//! - `sched=`: `rr`, tasks taking turns at each tick, or `fifo`, a task
+//!   running until it blocks or yields;
+//! - `root=`: the disk easy-fs is mounted from as /, like `/dev/vdb`, or
+//!   `blk1` for the second disk, or `initramfs` for the initramfs even if
+//!   there is a disk;
+//! - `smp=`: the harts to run, of which there is one;
+//! - `nokaslr`: the kernel left at the address it is linked at by a kernel
+//!   built with KASLR, for GDB to find its symbols.
+//!
+//! The command line is copied out of the device tree first thing, before
+//! the frames it lies in are handed out, as is where the initrd is which
+//! QEMU's -initrd or U-Boot loaded.
+
+use crate::config::{MEMORY_END, MEMORY_START, PHYS_VIRT_OFFSET};
+use core::ptr::{addr_of, addr_of_mut};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Bytes of the command line kept, the rest being cut off.
+const CMDLINE_MAX: usize = 1024;
+/// Device trees larger than this are not looked into.
+const FDT_MAX_SIZE: usize = 0x10_0000;
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+
+/// Written once at boot, before anything reads it.
+static mut CMDLINE: [u8; CMDLINE_MAX] = [0; CMDLINE_MAX];
+static CMDLINE_LEN: AtomicUsize = AtomicUsize::new(0);
+/// The physical range of the initrd, empty without one.
+static INITRD_START: AtomicUsize = AtomicUsize::new(0);
+static INITRD_END: AtomicUsize = AtomicUsize::new(0);
+
+fn be32(fdt: &[u8], offset: usize) -> Option<u32> {
+    let bytes = fdt.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// The NUL-terminated string at `offset` of `fdt`, without the NUL.
+fn c_str(fdt: &[u8], offset: usize) -> Option<&[u8]> {
+    let rest = fdt.get(offset..)?;
+    let len = rest.iter().position(|byte| *byte == 0)?;
+    Some(&rest[..len])
+}
+
+/// The property `prop` of the node `node` under the root of the flattened
+/// device tree `fdt`, like /chosen.
+fn fdt_prop<'a>(fdt: &'a [u8], node: &[u8], prop: &[u8]) -> Option<&'a [u8]> {
+    if be32(fdt, 0)? != FDT_MAGIC {
+        return None;
+    }
+    let structs = be32(fdt, 8)? as usize;
+    let strings = be32(fdt, 12)? as usize;
+    let mut offset = structs;
+    // depth 1 being the root node, and whether the node of depth 2 we are
+    // in is `node`
+    let (mut depth, mut in_node) = (0, false);
+    loop {
+        let token = be32(fdt, offset)?;
+        offset += 4;
+        match token {
+            FDT_BEGIN_NODE => {
+                let name = c_str(fdt, offset)?;
+                offset += (name.len() + 4) & !3;
+                depth += 1;
+                if depth == 2 {
+                    in_node = name == node;
+                }
+            }
+            FDT_END_NODE => {
+                if depth == 2 && in_node {
+                    return None;
+                }
+                depth -= 1;
+            }
+            FDT_PROP => {
+                let len = be32(fdt, offset)? as usize;
+                let name = c_str(fdt, strings + be32(fdt, offset + 4)? as usize)?;
+                let value = fdt.get(offset + 8..offset + 8 + len)?;
+                offset += 8 + ((len + 3) & !3);
+                if depth == 2 && in_node && name == prop {
+                    return Some(value);
+                }
+            }
+            FDT_NOP => {}
+            // FDT_END, or a tree broken
+            _ => return None,
+        }
+    }
+}
+
+/// The property `prop` of /chosen of `fdt`.
+fn fdt_chosen<'a>(fdt: &'a [u8], prop: &[u8]) -> Option<&'a [u8]> {
+    fdt_prop(fdt, b"chosen", prop)
+}
+
+/// The device tree at `dtb_pa`, if it is in memory and not too large.
+fn fdt(dtb_pa: usize) -> Option<&'static [u8]> {
+    if !(MEMORY_START..MEMORY_END).contains(&dtb_pa) {
+        return None;
+    }
+    // the boot page table maps the memory at PHYS_VIRT_OFFSET too
+    let header =
+        unsafe { core::slice::from_raw_parts((dtb_pa + PHYS_VIRT_OFFSET) as *const u8, 8) };
+    let size = be32(header, 4).unwrap_or(0) as usize;
+    if size > FDT_MAX_SIZE.min(MEMORY_END - dtb_pa) {
+        return None;
+    }
+    Some(unsafe { core::slice::from_raw_parts((dtb_pa + PHYS_VIRT_OFFSET) as *const u8, size) })
+}
+
+/// A property of one or two cells, as the addresses of the initrd are.
+fn fdt_cells(value: &[u8]) -> Option<usize> {
+    match value.len() {
+        4 => Some(be32(value, 0)? as usize),
+        8 => Some((be32(value, 0)? as usize) << 32 | be32(value, 4)? as usize),
+        _ => None,
+    }
+}
+
+/// The random bytes the boot firmware put in /chosen of the device tree at
+/// `dtb_pa` for the kernel to seed from, as rng-seed or kaslr-seed.
+pub fn fdt_seed(dtb_pa: usize) -> Option<&'static [u8]> {
+    let fdt = fdt(dtb_pa)?;
+    fdt_chosen(fdt, b"rng-seed").or_else(|| fdt_chosen(fdt, b"kaslr-seed"))
+}
+
+/// The frequency the time CSR counts at, the timebase-frequency of /cpus
+/// of the device tree at `dtb_pa`.
+pub fn fdt_timebase(dtb_pa: usize) -> Option<usize> {
+    fdt_cells(fdt_prop(fdt(dtb_pa)?, b"cpus", b"timebase-frequency")?)
+        .filter(|freq| *freq != 0)
+}
+
+/// Keep the command line of the device tree at `dtb_pa`, or the one built
+/// in; run before the frames are handed out.
+pub fn init(dtb_pa: usize) {
+    let from_fdt = fdt(dtb_pa)
+        .and_then(|fdt| fdt_chosen(fdt, b"bootargs"))
+        // the NUL ends it
+        .and_then(|value| value.split(|byte| *byte == 0).next())
+        .filter(|args| !args.is_empty());
+    let args = from_fdt.unwrap_or_else(|| option_env!("CMDLINE").unwrap_or("").as_bytes());
+    let len = args.len().min(CMDLINE_MAX);
+    unsafe {
+        (*addr_of_mut!(CMDLINE))[..len].copy_from_slice(&args[..len]);
+    }
+    CMDLINE_LEN.store(len, Ordering::Relaxed);
+    let initrd = fdt(dtb_pa).and_then(|fdt| {
+        let start = fdt_cells(fdt_chosen(fdt, b"linux,initrd-start")?)?;
+        let end = fdt_cells(fdt_chosen(fdt, b"linux,initrd-end")?)?;
+        (MEMORY_START <= start && start < end && end <= MEMORY_END).then_some((start, end))
+    });
+    let (start, end) = initrd.unwrap_or((0, 0));
+    INITRD_START.store(start, Ordering::Relaxed);
+    INITRD_END.store(end, Ordering::Relaxed);
+}
+
+/// The physical range of the initrd, if the boot loader loaded one.
+pub fn initrd() -> Option<(usize, usize)> {
+    let start = INITRD_START.load(Ordering::Relaxed);
+    let end = INITRD_END.load(Ordering::Relaxed);
+    (start < end).then_some((start, end))
+}
+
+/// The whole command line, as far as it is text.
+pub fn cmdline() -> &'static str {
+    let bytes = unsafe { &(*addr_of!(CMDLINE))[..CMDLINE_LEN.load(Ordering::Relaxed)] };
+    match core::str::from_utf8(bytes) {
+        Ok(cmdline) => cmdline,
+        Err(error) => core::str::from_utf8(&bytes[..error.valid_up_to()]).unwrap(),
+    }
+}
+
+/// The value of the option `name`, "" for one without a value.
+pub fn option(name: &str) -> Option<&'static str> {
+    cmdline()
+        .split_ascii_whitespace()
+        .filter_map(|option| match option.split_once('=') {
+            Some((key, value)) if key == name => Some(value),
+            None if option == name => Some(""),
+            _ => None,
+        })
+        .last()
+}
@@ -3,11 +3,69 @@
 pub const USER_STACK_SIZE: usize = 4096 * 2;
 pub const KERNEL_STACK_SIZE: usize = 4096 * 2;
 pub const KERNEL_HEAP_SIZE: usize = 0x100_0000;
-pub const MEMORY_END: usize = 0x88000000;
+/// The kernel space maps all of the physical memory linearly from here on,
+/// the start of the upper half of Sv39. See also entry.asm.
+pub const PHYS_VIRT_OFFSET: usize = 0xffff_ffc0_0000_0000;
+/// Kernel buffers made of scattered frames, like kernel stacks, are mapped
+/// in [VMALLOC_START, VMALLOC_END), well above the direct map.
+pub const VMALLOC_START: usize = 0xffff_ffe0_0000_0000;
+pub const VMALLOC_END: usize = 0xffff_fff0_0000_0000;
+/// A kernel built with KASLR moves its image to a random megapage of
+/// [KASLR_START, KASLR_END), above the vmalloc area.
+pub const KASLR_START: usize = 0xffff_fff0_0000_0000;
+pub const KASLR_END: usize = 0xffff_fff8_0000_0000;
 pub const PAGE_SIZE: usize = 0x1000;
 pub const PAGE_SIZE_BITS: usize = 0xc;
+/// Size of an Sv39 megapage.
+pub const HUGE_PAGE_SIZE: usize = 0x20_0000;
+/// Harts the kernel keeps per-hart state for.
+pub const MAX_HARTS: usize = 8;
+
+pub const DEFAULT_RLIMIT_NOFILE: usize = 128;
+pub const DEFAULT_RLIMIT_STACK: usize = 0x80_0000;
+pub const DEFAULT_RLIMIT_MEMLOCK: usize = 0x80_0000;
+pub const DEFAULT_RLIMIT_AS: usize = 0x400_0000;
+/// No core dumps unless a process raises RLIMIT_CORE.
+pub const DEFAULT_RLIMIT_CORE: usize = 0;
+
+/// PIE executables and ELF interpreters are loaded at a random page within
+/// ASLR_PAGES pages above these bases.
+pub const ELF_ET_DYN_BASE: usize = 0x4000_0000;
+pub const ELF_INTERP_BASE: usize = 0x6000_0000;
+pub const ASLR_PAGES: usize = 0x1000;
+/// mmap picks addresses in [MMAP_BASE, MMAP_END), the latter being the end
+/// of the lower half of Sv39.
+pub const MMAP_BASE: usize = 0x1_0000_0000;
+pub const MMAP_END: usize = 0x40_0000_0000;
+/// User stacks start here, or above the image if it is higher, leaving the
+/// heap room to grow past the end of the image. This is above the physical
+/// memory, part of which the kernel image is mapped at identically.
+pub const USER_STACK_BASE: usize = 0x9000_0000;
+/// Each thread has a slot of USER_STACK_SLOT bytes for its stack, which
+/// starts with USER_STACK_SIZE bytes at the top and grows down on faults up
+/// to RLIMIT_STACK, keeping at least USER_STACK_GUARD_GAP bytes unmapped
+/// below it to catch runaway recursion.
+pub const USER_STACK_GUARD_GAP: usize = 16 * PAGE_SIZE;
+pub const USER_STACK_SLOT: usize = DEFAULT_RLIMIT_STACK + USER_STACK_GUARD_GAP;
+
+/// The swap area follows the file system on the block device, and holds
+/// SWAP_PAGES pages.
+pub const SWAP_START_BLOCK: usize = 32 * 2048;
+pub const SWAP_PAGES: usize = 0x4000;
+/// Reclaim starts when fewer than FREE_FRAMES_LOW frames are free, and goes
+/// on until FREE_FRAMES_HIGH are.
+pub const FREE_FRAMES_LOW: usize = 0x400;
+pub const FREE_FRAMES_HIGH: usize = 0x800;
+/// The flusher writes dirty pages of files back this often.
+pub const DIRTY_WRITEBACK_MS: usize = 5000;
+/// The watchdog reports a hart stuck in the kernel without scheduling, or
+/// taking no timer interrupts, for this long.
+pub const WATCHDOG_MS: usize = 10_000;
 
 pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
 pub const TRAP_CONTEXT_BASE: usize = TRAMPOLINE - PAGE_SIZE;
+/// Signal handlers return to the page of rt_sigreturn mapped here, far
+/// below the trap contexts of the threads.
+pub const SIGRETURN_TRAMPOLINE: usize = TRAMPOLINE - 0x4000_0000;
 
-pub use crate::board::{CLOCK_FREQ, MMIO};
+pub use crate::board::{CLOCK_FREQ, MEMORY_END, MEMORY_START, MMIO};
@@ -1,13 +1,26 @@
+//! The console: the UART once its driver is up, the console of the SBI
+//! before, so that a panic early in the boot is seen.
+
 use crate::drivers::chardev::CharDevice;
 use crate::drivers::chardev::UART;
+use crate::logging;
+use crate::sbi::console_putchar;
 use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether the UART is up and written to.
+static UART_READY: AtomicBool = AtomicBool::new(false);
 
 struct Stdout;
 
 impl Write for Stdout {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        for c in s.chars() {
-            UART.write(c as u8);
+        if UART_READY.load(Ordering::Acquire) {
+            for c in s.chars() {
+                UART.write(c as u8);
+            }
+        } else {
+            s.bytes().for_each(console_putchar);
         }
         Ok(())
     }
@@ -17,6 +30,19 @@ pub fn print(args: fmt::Arguments) {
     Stdout.write_fmt(args).unwrap();
 }
 
+/// Whether the console is the UART already.
+pub fn ready() -> bool {
+    UART_READY.load(Ordering::Acquire)
+}
+
+/// Bring up the UART and write to it from now on, starting with the lines
+/// the log held for it.
+pub fn init() {
+    UART.init();
+    UART_READY.store(true, Ordering::Release);
+    logging::flush_early();
+}
+
 #[macro_export]
 macro_rules! print {
     ($fmt: literal $(, $($arg: tt)+)?) => {
@@ -0,0 +1,94 @@
+//! Reading blocks as a future: the requests of all the blocks are started
+//! at once, and the future is woken from the interrupt of the disk as they
+//! are done.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use easy_fs::{BlockDevice, BLOCK_SZ};
+
+/// A block to read into its place in the buffer.
+struct BlockRead {
+    /// in the buffer, in blocks
+    index: usize,
+    block_id: usize,
+    /// from `start_read`, until the block is read
+    request: Option<usize>,
+    done: bool,
+}
+
+/// Reads of blocks of `device` into `buf`, ready once all are done.
+pub struct ReadBlocks {
+    device: Arc<dyn BlockDevice>,
+    buf: &'static mut [u8],
+    blocks: Vec<BlockRead>,
+}
+
+impl ReadBlocks {
+    /// Read each `(index, block_id)` of `blocks` into the `index`th block of
+    /// `buf`. The future is to be polled until it is ready: the disk may
+    /// write to `buf` until then.
+    pub fn new(
+        device: Arc<dyn BlockDevice>,
+        buf: &'static mut [u8],
+        blocks: Vec<(usize, usize)>,
+    ) -> Self {
+        let blocks = blocks
+            .into_iter()
+            .map(|(index, block_id)| BlockRead {
+                index,
+                block_id,
+                request: None,
+                done: false,
+            })
+            .collect();
+        Self {
+            device,
+            buf,
+            blocks,
+        }
+    }
+}
+
+impl Future for ReadBlocks {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+        let mut pending = false;
+        for block in this.blocks.iter_mut().filter(|block| !block.done) {
+            let buf = &mut this.buf[block.index * BLOCK_SZ..(block.index + 1) * BLOCK_SZ];
+            match block.request {
+                None => match unsafe { this.device.start_read(block.block_id, buf, cx.waker()) } {
+                    Some(request) => {
+                        block.request = Some(request);
+                        pending = true;
+                    }
+                    // no requests to the device, or it holds the block
+                    None => {
+                        this.device.read_block(block.block_id, buf);
+                        block.done = true;
+                    }
+                },
+                Some(request) => match this.device.finish_read(request, cx.waker()) {
+                    None => pending = true,
+                    Some(ok) => {
+                        // a failed request is tried again the usual way,
+                        // which gives up on the disk if it keeps failing
+                        if !ok {
+                            this.device.read_block(block.block_id, buf);
+                        }
+                        block.done = true;
+                    }
+                },
+            }
+        }
+        if pending {
+            Poll::Pending
+        } else {
+            Poll::Ready(())
+        }
+    }
+}
@@ -1,19 +1,58 @@
+mod async_read;
+// the one the board has is used
+#[allow(unused)]
+mod ramdisk;
+#[allow(unused)]
 mod virtio_blk;
 
+pub use async_read::ReadBlocks;
+pub use ramdisk::RamDisk;
 pub use virtio_blk::VirtIOBlock;
 
-use crate::board::BlockDeviceImpl;
+use crate::board::{BlockDeviceImpl, DISKS};
 use alloc::sync::Arc;
 use easy_fs::BlockDevice;
 use lazy_static::*;
 
 lazy_static! {
-    pub static ref BLOCK_DEVICE: Arc<dyn BlockDevice> = Arc::new(BlockDeviceImpl::new());
+    /// The first disk, if the board has one and it is there, as QEMU may
+    /// be run without it.
+    pub static ref BLOCK_DEVICE: Option<Arc<dyn BlockDevice>> = DISKS
+        .first()
+        .and_then(|base| BlockDeviceImpl::new(*base))
+        .map(|device| Arc::new(device) as Arc<dyn BlockDevice>);
+    /// The second disk, e.g. a FAT32 image, if the board has one.
+    pub static ref BLOCK_DEVICE1: Option<Arc<dyn BlockDevice>> = DISKS
+        .get(1)
+        .and_then(|base| BlockDeviceImpl::new(*base))
+        .map(|device| Arc::new(device) as Arc<dyn BlockDevice>);
+}
+
+/// The names of the disks, in the order QEMU was given them.
+const DISK_NAMES: [&str; 2] = ["/dev/vda", "/dev/vdb"];
+
+/// The name of a disk there is, given as its path, like `/dev/vdb`, or as
+/// `blk` and its index, like `blk1` for the second disk.
+pub fn disk_name(name: &str) -> Option<&'static str> {
+    let name = match name.strip_prefix("blk") {
+        Some(index) => *DISK_NAMES.get(index.parse::<usize>().ok()?)?,
+        None => *DISK_NAMES.iter().find(|disk| **disk == name)?,
+    };
+    block_device(name).map(|_| name)
+}
+
+/// The disk named `name`, as the source of a mount.
+pub fn block_device(name: &str) -> Option<Arc<dyn BlockDevice>> {
+    match name {
+        "/dev/vda" => BLOCK_DEVICE.clone(),
+        "/dev/vdb" => BLOCK_DEVICE1.clone(),
+        _ => None,
+    }
 }
 
 #[allow(unused)]
 pub fn block_device_test() {
-    let block_device = BLOCK_DEVICE.clone();
+    let block_device = BLOCK_DEVICE.clone().unwrap();
     let mut write_buffer = [0u8; 512];
     let mut read_buffer = [0u8; 512];
     for i in 0..512 {
@@ -0,0 +1,40 @@
+use super::BlockDevice;
+use crate::mm::phys_to_virt;
+use easy_fs::BLOCK_SZ;
+
+/// A disk image of SIZE bytes the boot loader left in memory at `base`, out
+/// of the frames the kernel hands out. It is gone at the next boot, writes
+/// and all.
+pub struct RamDisk<const SIZE: usize> {
+    base: usize,
+}
+
+impl<const SIZE: usize> RamDisk<SIZE> {
+    /// The image at the physical address `base`.
+    pub fn new(base: usize) -> Option<Self> {
+        Some(Self {
+            base: phys_to_virt(base),
+        })
+    }
+
+    fn block(&self, block_id: usize) -> *mut u8 {
+        assert!(
+            (block_id + 1) * BLOCK_SZ <= SIZE,
+            "block {} past the end of the ram disk",
+            block_id
+        );
+        (self.base + block_id * BLOCK_SZ) as *mut u8
+    }
+}
+
+impl<const SIZE: usize> BlockDevice for RamDisk<SIZE> {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        let block = unsafe { core::slice::from_raw_parts(self.block(block_id), BLOCK_SZ) };
+        buf.copy_from_slice(block);
+    }
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        let block = unsafe { core::slice::from_raw_parts_mut(self.block(block_id), BLOCK_SZ) };
+        block.copy_from_slice(buf);
+    }
+    fn handle_irq(&self) {}
+}
@@ -1,21 +1,142 @@
 use super::BlockDevice;
 use crate::drivers::bus::virtio::VirtioHal;
+use crate::mm::phys_to_virt;
 use crate::sync::{Condvar, UPIntrFreeCell};
 use crate::task::schedule;
+#[cfg(feature = "fault_inject")]
+use crate::fs::wait_ready;
+#[cfg(feature = "fault_inject")]
+use crate::timer::{ktime_now, NSEC_PER_MSEC};
 use crate::DEV_NON_BLOCKING_ACCESS;
+use alloc::boxed::Box;
 use alloc::collections::BTreeMap;
+use core::task::Waker;
 use virtio_drivers::{BlkResp, RespStatus, VirtIOBlk, VirtIOHeader};
 
-#[allow(unused)]
-const VIRTIO0: usize = 0x10008000;
+/// Tries of a request before the disk is given up on.
+const BLOCK_TRIES: usize = 3;
+
+/// Whether a request may go to the disk, false if a fault injected fails
+/// it; a delay is waited out first, busily before tasks run.
+#[cfg(feature = "fault_inject")]
+fn injected_ok() -> bool {
+    use crate::fault::{inject, Fault, FaultPoint};
+    match inject(FaultPoint::Block) {
+        None => true,
+        Some(Fault::Fail) => false,
+        Some(Fault::Delay(ms)) => {
+            let deadline = ktime_now() + ms as u64 * NSEC_PER_MSEC;
+            if *DEV_NON_BLOCKING_ACCESS.exclusive_access() {
+                wait_ready(Some(deadline), || None::<()>);
+            }
+            while ktime_now() < deadline {}
+            true
+        }
+    }
+}
+
+/// A read started by `start_read`, until `finish_read` takes how it went.
+struct Request {
+    /// where the device tells how it went
+    resp: Box<BlkResp>,
+    waker: Waker,
+    done: bool,
+}
+
+/// The reads started by `start_read`. Their ids are not the tokens of the
+/// queue, which are reused as soon as the device is done with them.
+struct Requests {
+    next_id: usize,
+    /// the id of the read of each token in the queue
+    tokens: BTreeMap<u16, usize>,
+    by_id: BTreeMap<usize, Request>,
+}
 
 pub struct VirtIOBlock {
     virtio_blk: UPIntrFreeCell<VirtIOBlk<'static, VirtioHal>>,
     condvars: BTreeMap<u16, Condvar>,
+    requests: UPIntrFreeCell<Requests>,
 }
 
 impl BlockDevice for VirtIOBlock {
     fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        for tries in 1..=BLOCK_TRIES {
+            if self.try_read(block_id, buf) {
+                return;
+            }
+            warn!("reading block {} failed, try {} of {}", block_id, tries, BLOCK_TRIES);
+        }
+        panic!("Error when reading VirtIOBlk");
+    }
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        for tries in 1..=BLOCK_TRIES {
+            if self.try_write(block_id, buf) {
+                return;
+            }
+            warn!("writing block {} failed, try {} of {}", block_id, tries, BLOCK_TRIES);
+        }
+        panic!("Error when writing VirtIOBlk");
+    }
+    fn handle_irq(&self) {
+        self.virtio_blk.exclusive_session(|blk| {
+            while let Ok(token) = blk.pop_used() {
+                let mut requests = self.requests.exclusive_access();
+                match requests.tokens.remove(&token) {
+                    Some(id) => {
+                        let request = requests.by_id.get_mut(&id).unwrap();
+                        request.done = true;
+                        request.waker.wake_by_ref();
+                    }
+                    None => self.condvars.get(&token).unwrap().signal(),
+                }
+            }
+        });
+    }
+    /// Reads wait for the interrupt once tasks run; with fault injection
+    /// built in, they go through `read_block` for the faults to be
+    /// injected there.
+    unsafe fn start_read(&self, block_id: usize, buf: &mut [u8], waker: &Waker) -> Option<usize> {
+        if cfg!(feature = "fault_inject") || !*DEV_NON_BLOCKING_ACCESS.exclusive_access() {
+            return None;
+        }
+        let mut resp = Box::new(BlkResp::default());
+        self.virtio_blk.exclusive_session(|blk| {
+            // a full queue leaves the block to read_block
+            let token = blk.read_block_nb(block_id, buf, &mut resp).ok()?;
+            let mut requests = self.requests.exclusive_access();
+            let id = requests.next_id;
+            requests.next_id += 1;
+            requests.tokens.insert(token, id);
+            requests.by_id.insert(
+                id,
+                Request {
+                    resp,
+                    waker: waker.clone(),
+                    done: false,
+                },
+            );
+            Some(id)
+        })
+    }
+    fn finish_read(&self, id: usize, waker: &Waker) -> Option<bool> {
+        let mut requests = self.requests.exclusive_access();
+        let request = requests.by_id.get_mut(&id).unwrap();
+        if !request.done {
+            request.waker = waker.clone();
+            return None;
+        }
+        let request = requests.by_id.remove(&id).unwrap();
+        Some(request.resp.status() == RespStatus::Ok)
+    }
+}
+
+impl VirtIOBlock {
+    /// Read `block_id` once; false if the disk failed to.
+    fn try_read(&self, block_id: usize, buf: &mut [u8]) -> bool {
+        #[cfg(feature = "fault_inject")]
+        if !injected_ok() {
+            return false;
+        }
         let nb = *DEV_NON_BLOCKING_ACCESS.exclusive_access();
         if nb {
             let mut resp = BlkResp::default();
@@ -24,19 +145,21 @@ impl BlockDevice for VirtIOBlock {
                 self.condvars.get(&token).unwrap().wait_no_sched()
             });
             schedule(task_cx_ptr);
-            assert_eq!(
-                resp.status(),
-                RespStatus::Ok,
-                "Error when reading VirtIOBlk"
-            );
+            resp.status() == RespStatus::Ok
         } else {
             self.virtio_blk
                 .exclusive_access()
                 .read_block(block_id, buf)
-                .expect("Error when reading VirtIOBlk");
+                .is_ok()
         }
     }
-    fn write_block(&self, block_id: usize, buf: &[u8]) {
+
+    /// Write `block_id` once; false if the disk failed to.
+    fn try_write(&self, block_id: usize, buf: &[u8]) -> bool {
+        #[cfg(feature = "fault_inject")]
+        if !injected_ok() {
+            return false;
+        }
         let nb = *DEV_NON_BLOCKING_ACCESS.exclusive_access();
         if nb {
             let mut resp = BlkResp::default();
@@ -45,32 +168,21 @@ impl BlockDevice for VirtIOBlock {
                 self.condvars.get(&token).unwrap().wait_no_sched()
             });
             schedule(task_cx_ptr);
-            assert_eq!(
-                resp.status(),
-                RespStatus::Ok,
-                "Error when writing VirtIOBlk"
-            );
+            resp.status() == RespStatus::Ok
         } else {
             self.virtio_blk
                 .exclusive_access()
                 .write_block(block_id, buf)
-                .expect("Error when writing VirtIOBlk");
+                .is_ok()
         }
     }
-    fn handle_irq(&self) {
-        self.virtio_blk.exclusive_session(|blk| {
-            while let Ok(token) = blk.pop_used() {
-                self.condvars.get(&token).unwrap().signal();
-            }
-        });
-    }
-}
 
-impl VirtIOBlock {
-    pub fn new() -> Self {
+    /// The disk at the virtio-mmio slot `base`, if there is one.
+    pub fn new(base: usize) -> Option<Self> {
         let virtio_blk = unsafe {
             UPIntrFreeCell::new(
-                VirtIOBlk::<VirtioHal>::new(&mut *(VIRTIO0 as *mut VirtIOHeader)).unwrap(),
+                VirtIOBlk::<VirtioHal>::new(&mut *(phys_to_virt(base) as *mut VirtIOHeader))
+                    .ok()?,
             )
         };
         let mut condvars = BTreeMap::new();
@@ -79,9 +191,16 @@ impl VirtIOBlock {
             let condvar = Condvar::new();
             condvars.insert(i, condvar);
         }
-        Self {
+        Some(Self {
             virtio_blk,
             condvars,
-        }
+            requests: unsafe {
+                UPIntrFreeCell::new(Requests {
+                    next_id: 0,
+                    tokens: BTreeMap::new(),
+                    by_id: BTreeMap::new(),
+                })
+            },
+        })
     }
 }
@@ -0,0 +1,46 @@
+//! The PCI bus of the board, as far as finding a device on bus 0 through
+//! the ECAM and giving it an I/O window goes. QEMU puts devices added with
+//! `-device` there, like the pci-serial port of the GDB stub; the windows
+//! are the board's PCIE.
+
+use crate::board::PCIE;
+use crate::mm::phys_to_virt;
+use core::ptr::{read_volatile, write_volatile};
+
+const PCI_VENDOR_ID: usize = 0x00;
+const PCI_COMMAND: usize = 0x04;
+const PCI_BAR0: usize = 0x10;
+const PCI_COMMAND_IO: u32 = 1 << 0;
+const PCI_BAR_IO: u32 = 1 << 0;
+
+/// The configuration space of function `func` of device `dev` on bus 0 of
+/// the ECAM at `ecam`.
+fn config(ecam: usize, dev: usize, func: usize) -> usize {
+    phys_to_virt(ecam) + (dev << 15 | func << 12)
+}
+
+fn read_config(ecam: usize, dev: usize, func: usize, offset: usize) -> u32 {
+    unsafe { read_volatile((config(ecam, dev, func) + offset) as *const u32) }
+}
+
+fn write_config(ecam: usize, dev: usize, func: usize, offset: usize, value: u32) {
+    unsafe { write_volatile((config(ecam, dev, func) + offset) as *mut u32, value) }
+}
+
+/// Find the device of `vendor` and `device` on bus 0 whose BAR0 is an I/O
+/// window, place the window at `port` and turn it on; where its registers
+/// are then. None on a board without PCI.
+pub fn enable_io_device(vendor: u16, device: u16, port: usize) -> Option<usize> {
+    let (ecam, pio) = PCIE?;
+    let id = (device as u32) << 16 | vendor as u32;
+    let (dev, func) = (0..32)
+        .flat_map(|dev| (0..8).map(move |func| (dev, func)))
+        .find(|&(dev, func)| read_config(ecam, dev, func, PCI_VENDOR_ID) == id)?;
+    if read_config(ecam, dev, func, PCI_BAR0) & PCI_BAR_IO == 0 {
+        return None;
+    }
+    write_config(ecam, dev, func, PCI_BAR0, port as u32 | PCI_BAR_IO);
+    let command = read_config(ecam, dev, func, PCI_COMMAND);
+    write_config(ecam, dev, func, PCI_COMMAND, command | PCI_COMMAND_IO);
+    Some(phys_to_virt(pio + port))
+}
@@ -1,6 +1,6 @@
 use crate::mm::{
-    frame_alloc_more, frame_dealloc, kernel_token, FrameTracker, PageTable, PhysAddr, PhysPageNum,
-    StepByOne, VirtAddr,
+    frame_alloc_more, frame_dealloc, phys_to_virt, virt_to_phys, FrameTracker, PhysAddr,
+    PhysPageNum, StepByOne,
 };
 use crate::sync::UPIntrFreeCell;
 use alloc::vec::Vec;
@@ -17,7 +17,7 @@ pub struct VirtioHal;
 impl Hal for VirtioHal {
     fn dma_alloc(pages: usize) -> usize {
         let trakcers = frame_alloc_more(pages);
-        let ppn_base = trakcers.as_ref().unwrap().last().unwrap().ppn;
+        let ppn_base = trakcers.as_ref().unwrap().first().unwrap().ppn;
         QUEUE_FRAMES
             .exclusive_access()
             .append(&mut trakcers.unwrap());
@@ -36,13 +36,10 @@ impl Hal for VirtioHal {
     }
 
     fn phys_to_virt(addr: usize) -> usize {
-        addr
+        phys_to_virt(addr)
     }
 
     fn virt_to_phys(vaddr: usize) -> usize {
-        PageTable::from_token(kernel_token())
-            .translate_va(VirtAddr::from(vaddr))
-            .unwrap()
-            .0
+        virt_to_phys(vaddr)
     }
 }
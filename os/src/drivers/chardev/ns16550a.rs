@@ -2,11 +2,13 @@
 ///! Ref: ns16550a datasheet: https://datasheetspdf.com/pdf-file/605590/NationalSemiconductor/NS16550A/1
 ///! Ref: ns16450 datasheet: https://datasheetspdf.com/pdf-file/1311818/NationalSemiconductor/NS16450/1
 use super::CharDevice;
+use crate::fs::{control_signal, signal_foreground};
+use crate::power::PmOps;
 use crate::sync::{Condvar, UPIntrFreeCell};
 use crate::task::schedule;
 use alloc::collections::VecDeque;
+use alloc::vec::Vec;
 use bitflags::*;
-use volatile::{ReadOnly, Volatile, WriteOnly};
 
 bitflags! {
     /// InterruptEnableRegister
@@ -19,6 +21,8 @@ bitflags! {
     pub struct LSR: u8 {
         const DATA_AVAILABLE = 1 << 0;
         const THR_EMPTY = 1 << 5;
+        /// Both the holding and the shift register are empty.
+        const TX_IDLE = 1 << 6;
     }
 
     /// Model Control Register
@@ -30,92 +34,87 @@ bitflags! {
     }
 }
 
-#[repr(C)]
-#[allow(dead_code)]
-struct ReadWithoutDLAB {
-    /// receiver buffer register
-    pub rbr: ReadOnly<u8>,
-    /// interrupt enable register
-    pub ier: Volatile<IER>,
-    /// interrupt identification register
-    pub iir: ReadOnly<u8>,
-    /// line control register
-    pub lcr: Volatile<u8>,
-    /// model control register
-    pub mcr: Volatile<MCR>,
-    /// line status register
-    pub lsr: ReadOnly<LSR>,
-    /// ignore MSR
-    _padding1: ReadOnly<u8>,
-    /// ignore SCR
-    _padding2: ReadOnly<u8>,
-}
-
-#[repr(C)]
-#[allow(dead_code)]
-struct WriteWithoutDLAB {
-    /// transmitter holding register
-    pub thr: WriteOnly<u8>,
-    /// interrupt enable register
-    pub ier: Volatile<IER>,
-    /// ignore FCR
-    _padding0: ReadOnly<u8>,
-    /// line control register
-    pub lcr: Volatile<u8>,
-    /// modem control register
-    pub mcr: Volatile<MCR>,
-    /// line status register
-    pub lsr: ReadOnly<LSR>,
-    /// ignore other registers
-    _padding1: ReadOnly<u16>,
-}
+/// Receiver buffer register when read, transmitter holding register when
+/// written.
+const RBR_THR: usize = 0;
+/// Interrupt enable register.
+const IER_REG: usize = 1;
+/// Modem control register.
+const MCR_REG: usize = 4;
+/// Line status register.
+const LSR_REG: usize = 5;
 
 pub struct NS16550aRaw {
     base_addr: usize,
+    /// Register `n` is at `n << reg_shift`, read and written as a byte if
+    /// that is 0, otherwise as 32 bits, like the DesignWare UARTs have it.
+    reg_shift: usize,
 }
 
 impl NS16550aRaw {
-    fn read_end(&mut self) -> &mut ReadWithoutDLAB {
-        unsafe { &mut *(self.base_addr as *mut ReadWithoutDLAB) }
+    fn read_reg(&self, reg: usize) -> u8 {
+        let addr = self.base_addr + (reg << self.reg_shift);
+        unsafe {
+            match self.reg_shift {
+                0 => (addr as *const u8).read_volatile(),
+                _ => (addr as *const u32).read_volatile() as u8,
+            }
+        }
     }
 
-    fn write_end(&mut self) -> &mut WriteWithoutDLAB {
-        unsafe { &mut *(self.base_addr as *mut WriteWithoutDLAB) }
+    fn write_reg(&mut self, reg: usize, value: u8) {
+        let addr = self.base_addr + (reg << self.reg_shift);
+        unsafe {
+            match self.reg_shift {
+                0 => (addr as *mut u8).write_volatile(value),
+                _ => (addr as *mut u32).write_volatile(value as u32),
+            }
+        }
     }
 
     pub fn new(base_addr: usize) -> Self {
-        Self { base_addr }
+        Self::with_reg_shift(base_addr, 0)
+    }
+
+    /// The UART at `base_addr` with its registers `1 << reg_shift` bytes
+    /// apart.
+    pub fn with_reg_shift(base_addr: usize, reg_shift: usize) -> Self {
+        Self {
+            base_addr,
+            reg_shift,
+        }
     }
 
     pub fn init(&mut self) {
-        let read_end = self.read_end();
         let mut mcr = MCR::empty();
         mcr |= MCR::DATA_TERMINAL_READY;
         mcr |= MCR::REQUEST_TO_SEND;
         mcr |= MCR::AUX_OUTPUT2;
-        read_end.mcr.write(mcr);
+        self.write_reg(MCR_REG, mcr.bits());
         let ier = IER::RX_AVAILABLE;
-        read_end.ier.write(ier);
+        self.write_reg(IER_REG, ier.bits());
+    }
+
+    fn lsr(&self) -> LSR {
+        LSR::from_bits_truncate(self.read_reg(LSR_REG))
     }
 
     pub fn read(&mut self) -> Option<u8> {
-        let read_end = self.read_end();
-        let lsr = read_end.lsr.read();
-        if lsr.contains(LSR::DATA_AVAILABLE) {
-            Some(read_end.rbr.read())
+        if self.lsr().contains(LSR::DATA_AVAILABLE) {
+            Some(self.read_reg(RBR_THR))
         } else {
             None
         }
     }
 
     pub fn write(&mut self, ch: u8) {
-        let write_end = self.write_end();
-        loop {
-            if write_end.lsr.read().contains(LSR::THR_EMPTY) {
-                write_end.thr.write(ch);
-                break;
-            }
-        }
+        while !self.lsr().contains(LSR::THR_EMPTY) {}
+        self.write_reg(RBR_THR, ch);
+    }
+
+    /// Wait for all that was written to be sent.
+    pub fn flush(&mut self) {
+        while !self.lsr().contains(LSR::TX_IDLE) {}
     }
 }
 
@@ -124,15 +123,16 @@ struct NS16550aInner {
     read_buffer: VecDeque<u8>,
 }
 
-pub struct NS16550a<const BASE_ADDR: usize> {
+/// The UART at BASE_ADDR, with its registers `1 << REG_SHIFT` bytes apart.
+pub struct NS16550a<const BASE_ADDR: usize, const REG_SHIFT: usize = 0> {
     inner: UPIntrFreeCell<NS16550aInner>,
     condvar: Condvar,
 }
 
-impl<const BASE_ADDR: usize> NS16550a<BASE_ADDR> {
+impl<const BASE_ADDR: usize, const REG_SHIFT: usize> NS16550a<BASE_ADDR, REG_SHIFT> {
     pub fn new() -> Self {
         let inner = NS16550aInner {
-            ns16550a: NS16550aRaw::new(BASE_ADDR),
+            ns16550a: NS16550aRaw::with_reg_shift(BASE_ADDR, REG_SHIFT),
             read_buffer: VecDeque::new(),
         };
         //inner.ns16550a.init();
@@ -146,9 +146,41 @@ impl<const BASE_ADDR: usize> NS16550a<BASE_ADDR> {
         self.inner
             .exclusive_session(|inner| inner.read_buffer.is_empty())
     }
+
+    /// Take in `input` as if it came over the line, as the interrupt does
+    /// and a replay of it.
+    pub fn receive(&self, input: &[u8]) {
+        let mut signals = Vec::new();
+        self.inner.exclusive_session(|inner| {
+            for &ch in input {
+                // the line discipline may take it for a signal
+                match control_signal(ch) {
+                    Some(signo) => signals.push(signo),
+                    None => inner.read_buffer.push_back(ch),
+                }
+            }
+        });
+        for signo in signals {
+            signal_foreground(signo);
+        }
+        if !input.is_empty() {
+            self.condvar.signal();
+        }
+    }
+
+    /// Block until there is something to read, or a byte taken for a
+    /// signal comes.
+    pub fn wait_input(&self) {
+        let inner = self.inner.exclusive_access();
+        if inner.read_buffer.is_empty() {
+            let task_cx_ptr = self.condvar.wait_no_sched();
+            drop(inner);
+            schedule(task_cx_ptr);
+        }
+    }
 }
 
-impl<const BASE_ADDR: usize> CharDevice for NS16550a<BASE_ADDR> {
+impl<const BASE_ADDR: usize, const REG_SHIFT: usize> CharDevice for NS16550a<BASE_ADDR, REG_SHIFT> {
     fn init(&self) {
         let mut inner = self.inner.exclusive_access();
         inner.ns16550a.init();
@@ -172,15 +204,24 @@ impl<const BASE_ADDR: usize> CharDevice for NS16550a<BASE_ADDR> {
         inner.ns16550a.write(ch);
     }
     fn handle_irq(&self) {
-        let mut count = 0;
+        let mut input = Vec::new();
         self.inner.exclusive_session(|inner| {
             while let Some(ch) = inner.ns16550a.read() {
-                count += 1;
-                inner.read_buffer.push_back(ch);
+                input.push(ch);
             }
         });
-        if count > 0 {
-            self.condvar.signal();
-        }
+        crate::replay::record_input(&input);
+        self.receive(&input);
+    }
+}
+
+impl<const BASE_ADDR: usize, const REG_SHIFT: usize> PmOps for NS16550a<BASE_ADDR, REG_SHIFT> {
+    /// Let the last lines out, the receiver left to wake the system.
+    fn suspend(&self) {
+        self.inner.exclusive_access().ns16550a.flush();
+    }
+
+    fn resume(&self) {
+        self.init();
     }
 }
@@ -1,19 +1,25 @@
+use crate::board::VIRTIO_GPU;
 use crate::drivers::bus::virtio::VirtioHal;
+use crate::mm::phys_to_virt;
 use crate::sync::UPIntrFreeCell;
 use alloc::{sync::Arc, vec::Vec};
 use core::any::Any;
 use embedded_graphics::pixelcolor::Rgb888;
 use tinybmp::Bmp;
 use virtio_drivers::{VirtIOGpu, VirtIOHeader};
-const VIRTIO7: usize = 0x10007000;
 pub trait GpuDevice: Send + Sync + Any {
     fn update_cursor(&self);
     fn get_framebuffer(&self) -> &mut [u8];
     fn flush(&self);
+    /// Width and height in pixels of 4 bytes, blue first.
+    fn resolution(&self) -> (u32, u32);
 }
 
 lazy_static::lazy_static!(
-    pub static ref GPU_DEVICE: Arc<dyn GpuDevice> = Arc::new(VirtIOGpuWrapper::new());
+    /// The GPU, if the board has one.
+    pub static ref GPU_DEVICE: Option<Arc<dyn GpuDevice>> = VIRTIO_GPU
+        .and_then(VirtIOGpuWrapper::new)
+        .map(|device| Arc::new(device) as Arc<dyn GpuDevice>);
 );
 
 pub struct VirtIOGpuWrapper {
@@ -22,10 +28,12 @@ pub struct VirtIOGpuWrapper {
 }
 static BMP_DATA: &[u8] = include_bytes!("../../assert/mouse.bmp");
 impl VirtIOGpuWrapper {
-    pub fn new() -> Self {
+    /// The GPU at the virtio-mmio slot `base`, if there is one.
+    pub fn new(base: usize) -> Option<Self> {
         unsafe {
             let mut virtio =
-                VirtIOGpu::<VirtioHal>::new(&mut *(VIRTIO7 as *mut VirtIOHeader)).unwrap();
+                VirtIOGpu::<VirtioHal>::new(&mut *(phys_to_virt(base) as *mut VirtIOHeader))
+                    .ok()?;
 
             let fbuffer = virtio.setup_framebuffer().unwrap();
             let len = fbuffer.len();
@@ -46,10 +54,10 @@ impl VirtIOGpuWrapper {
             }
             virtio.setup_cursor(b.as_slice(), 50, 50, 50, 50).unwrap();
 
-            Self {
+            Some(Self {
                 gpu: UPIntrFreeCell::new(virtio),
                 fb,
-            }
+            })
         }
     }
 }
@@ -65,4 +73,7 @@ impl GpuDevice for VirtIOGpuWrapper {
         }
     }
     fn update_cursor(&self) {}
+    fn resolution(&self) -> (u32, u32) {
+        self.gpu.exclusive_access().resolution()
+    }
 }
@@ -1,4 +1,6 @@
+use crate::board::{VIRTIO_KEYBOARD, VIRTIO_MOUSE};
 use crate::drivers::bus::virtio::VirtioHal;
+use crate::mm::phys_to_virt;
 use crate::sync::{Condvar, UPIntrFreeCell};
 use crate::task::schedule;
 use alloc::collections::VecDeque;
@@ -6,9 +8,6 @@ use alloc::sync::Arc;
 use core::any::Any;
 use virtio_drivers::{VirtIOHeader, VirtIOInput};
 
-const VIRTIO5: usize = 0x10005000;
-const VIRTIO6: usize = 0x10006000;
-
 struct VirtIOInputInner {
     virtio_input: VirtIOInput<'static, VirtioHal>,
     events: VecDeque<u64>,
@@ -26,22 +25,29 @@ pub trait InputDevice: Send + Sync + Any {
 }
 
 lazy_static::lazy_static!(
-    pub static ref KEYBOARD_DEVICE: Arc<dyn InputDevice> = Arc::new(VirtIOInputWrapper::new(VIRTIO5));
-    pub static ref MOUSE_DEVICE: Arc<dyn InputDevice> = Arc::new(VirtIOInputWrapper::new(VIRTIO6));
+    /// The keyboard and the mouse, if the board has them.
+    pub static ref KEYBOARD_DEVICE: Option<Arc<dyn InputDevice>> = VIRTIO_KEYBOARD
+        .and_then(VirtIOInputWrapper::new)
+        .map(|device| Arc::new(device) as Arc<dyn InputDevice>);
+    pub static ref MOUSE_DEVICE: Option<Arc<dyn InputDevice>> = VIRTIO_MOUSE
+        .and_then(VirtIOInputWrapper::new)
+        .map(|device| Arc::new(device) as Arc<dyn InputDevice>);
 );
 
 impl VirtIOInputWrapper {
-    pub fn new(addr: usize) -> Self {
+    /// The input device at the virtio-mmio slot `addr`, if there is one.
+    pub fn new(addr: usize) -> Option<Self> {
         let inner = VirtIOInputInner {
             virtio_input: unsafe {
-                VirtIOInput::<VirtioHal>::new(&mut *(addr as *mut VirtIOHeader)).unwrap()
+                VirtIOInput::<VirtioHal>::new(&mut *(phys_to_virt(addr) as *mut VirtIOHeader))
+                    .ok()?
             },
             events: VecDeque::new(),
         };
-        Self {
+        Some(Self {
             inner: unsafe { UPIntrFreeCell::new(inner) },
             condvar: Condvar::new(),
-        }
+        })
     }
 }
 
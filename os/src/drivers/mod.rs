@@ -6,8 +6,7 @@ pub mod input;
 pub mod net;
 pub mod plic;
 
-pub use block::BLOCK_DEVICE;
-pub use bus::*;
+pub use block::{block_device, disk_name, ReadBlocks, BLOCK_DEVICE};
 pub use chardev::UART;
 pub use gpu::*;
 pub use input::*;
@@ -0,0 +1,280 @@
+//! Packet buffers, which the card and the stack pass each other instead of
+//! copying frames: the card receives right into one and the stack reads
+//! the frame where it is, and the stack builds a frame in one back to
+//! front, pushing each header before the last, for the card to send from.
+//!
+//! A buffer is a slot of `MBUF_SIZE` bytes of a dedicated pool of frames,
+//! which grows as buffers are wanted up to `POOL_MAX` of them, and the
+//! bytes it holds are a range of the slot, with headroom before and
+//! tailroom after. Clones share the slot, each with a range of its own,
+//! as a socket keeps the data of a segment of the frame; the slot goes
+//! back to the pool with the last of them. The bytes of a shared slot are
+//! read-only, only a buffer without clones has bytes written.
+//!
+//! A buffer also tells what is known of the checksum of its TCP or UDP
+//! packet: that the card checked it on receive, or that it is left for the
+//! card to complete on transmit, the way virtio-net has it.
+
+use crate::config::PAGE_SIZE;
+use crate::mm::{frame_alloc, phys_to_virt, FrameTracker, PhysAddr};
+use crate::sync::UPIntrFreeCell;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::ops::{Deref, DerefMut};
+use lazy_static::*;
+
+/// A virtio-net header and an Ethernet frame of 1514 bytes fit.
+pub const MBUF_SIZE: usize = 2048;
+/// Room left before the data of a new buffer, for the headers of the
+/// card, Ethernet, IPv4 and TCP.
+pub const MBUF_HEADROOM: usize = 128;
+/// The most buffers there are at once, 1 MiB of them.
+const POOL_MAX: usize = 512;
+
+struct Pool {
+    pages: Vec<FrameTracker>,
+    /// Slots of `pages` no buffer has.
+    free: Vec<usize>,
+}
+
+lazy_static! {
+    static ref POOL: UPIntrFreeCell<Pool> = unsafe {
+        UPIntrFreeCell::new(Pool {
+            pages: Vec::new(),
+            free: Vec::new(),
+        })
+    };
+}
+
+/// A slot of the pool, given back when dropped.
+struct Slot {
+    index: usize,
+    pa: usize,
+}
+
+impl Slot {
+    fn alloc() -> Option<Self> {
+        let per_page = PAGE_SIZE / MBUF_SIZE;
+        let mut pool = POOL.exclusive_access();
+        if pool.free.is_empty() {
+            if pool.pages.len() * per_page >= POOL_MAX {
+                return None;
+            }
+            let first = pool.pages.len() * per_page;
+            pool.pages.push(frame_alloc()?);
+            pool.free.extend((first..first + per_page).rev());
+        }
+        let index = pool.free.pop().unwrap();
+        let pa = PhysAddr::from(pool.pages[index / per_page].ppn).0 + index % per_page * MBUF_SIZE;
+        Some(Self { index, pa })
+    }
+
+    fn bytes(&self) -> *mut u8 {
+        phys_to_virt(self.pa) as *mut u8
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        POOL.exclusive_access().free.push(self.index);
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Checksum {
+    /// Nothing: it is for software to check, or it is complete.
+    None,
+    /// The card checked it on receive.
+    Verified,
+    /// The checksum field, `offset` bytes into the header at `start` of
+    /// the slot, holds the sum of the pseudo-header, and the rest is to be
+    /// added from `start` on.
+    Partial { start: usize, offset: usize },
+}
+
+/// Bytes of a packet in a slot of the pool.
+#[derive(Clone)]
+pub struct Mbuf {
+    slot: Arc<Slot>,
+    /// The range of the slot holding the bytes.
+    head: usize,
+    tail: usize,
+    csum: Checksum,
+}
+
+impl Mbuf {
+    /// An empty buffer, its data to start `headroom` bytes into the slot;
+    /// none if the pool is used up.
+    pub fn with_headroom(headroom: usize) -> Option<Self> {
+        assert!(headroom <= MBUF_SIZE);
+        Some(Self {
+            slot: Arc::new(Slot::alloc()?),
+            head: headroom,
+            tail: headroom,
+            csum: Checksum::None,
+        })
+    }
+
+    /// An empty buffer with `MBUF_HEADROOM`.
+    pub fn alloc() -> Option<Self> {
+        Self::with_headroom(MBUF_HEADROOM)
+    }
+
+    /// A buffer holding a copy of `data`, which has to fit after
+    /// `MBUF_HEADROOM`.
+    pub fn from_slice(data: &[u8]) -> Option<Self> {
+        if data.len() > MBUF_SIZE - MBUF_HEADROOM {
+            return None;
+        }
+        let mut mbuf = Self::alloc()?;
+        mbuf.put(data.len()).copy_from_slice(data);
+        Some(mbuf)
+    }
+
+    /// A buffer of its own with a copy of the bytes, as many of them as
+    /// fit after `headroom`, and of what is known of the checksum.
+    pub fn copy_with_headroom(&self, headroom: usize) -> Option<Self> {
+        let len = self.len().min(MBUF_SIZE - headroom);
+        let mut mbuf = Self::with_headroom(headroom)?;
+        mbuf.put(len).copy_from_slice(&self[..len]);
+        mbuf.csum = match self.csum {
+            Checksum::Partial { start, offset } => Checksum::Partial {
+                start: start - self.head + headroom,
+                offset,
+            },
+            csum => csum,
+        };
+        Some(mbuf)
+    }
+
+    /// The physical address of the bytes, for a device to reach them.
+    pub fn pa(&self) -> usize {
+        self.slot.pa + self.head
+    }
+
+    pub fn headroom(&self) -> usize {
+        self.head
+    }
+
+    pub fn tailroom(&self) -> usize {
+        MBUF_SIZE - self.tail
+    }
+
+    /// Whether a clone shares the slot, whose bytes are then read-only.
+    pub fn is_shared(&self) -> bool {
+        Arc::strong_count(&self.slot) > 1
+    }
+
+    fn assert_unique(&self) {
+        assert!(!self.is_shared(), "writing a shared packet buffer");
+    }
+
+    /// Take `len` bytes of the headroom in front of the data, for a
+    /// header to be written in.
+    pub fn push(&mut self, len: usize) -> &mut [u8] {
+        self.assert_unique();
+        assert!(len <= self.head, "no headroom in a packet buffer");
+        self.head -= len;
+        &mut self[..len]
+    }
+
+    /// Take `len` bytes of the tailroom after the data, to be written in.
+    pub fn put(&mut self, len: usize) -> &mut [u8] {
+        self.assert_unique();
+        assert!(len <= self.tailroom(), "no tailroom in a packet buffer");
+        self.tail += len;
+        let start = self.len() - len;
+        &mut self[start..]
+    }
+
+    /// Leave out the first `len` bytes, a header which was read.
+    pub fn pull(&mut self, len: usize) {
+        self.head += len.min(self.len());
+    }
+
+    /// Keep no more than the first `len` bytes.
+    pub fn truncate(&mut self, len: usize) {
+        self.tail = self.head + len.min(self.len());
+    }
+
+    /// Take in that the card checked the checksum.
+    pub fn set_checksum_verified(&mut self) {
+        self.csum = Checksum::Verified;
+    }
+
+    /// Whether the card checked the checksum, for the stack not to.
+    pub fn checksum_verified(&self) -> bool {
+        self.csum == Checksum::Verified
+    }
+
+    /// Leave the checksum of the header at the start of the bytes to be
+    /// completed, its field `offset` bytes in holding the sum of the
+    /// pseudo-header.
+    pub fn set_checksum_partial(&mut self, offset: usize) {
+        self.csum = Checksum::Partial {
+            start: self.head,
+            offset,
+        };
+    }
+
+    /// Where the checksum left to complete starts from, from the start of
+    /// the bytes, and the offset of its field from there.
+    pub fn checksum_partial(&self) -> Option<(usize, usize)> {
+        match self.csum {
+            Checksum::Partial { start, offset } => Some((start - self.head, offset)),
+            _ => None,
+        }
+    }
+
+    /// Complete in software the checksum left to complete, for a card
+    /// which does not.
+    pub fn finish_checksum(&mut self) {
+        if let Some((start, offset)) = self.checksum_partial() {
+            let mut sum: u32 = 0;
+            for chunk in self[start..].chunks(2) {
+                let word = if chunk.len() == 2 {
+                    u16::from_be_bytes([chunk[0], chunk[1]])
+                } else {
+                    (chunk[0] as u16) << 8
+                };
+                sum += word as u32;
+            }
+            while sum >> 16 != 0 {
+                sum = (sum & 0xffff) + (sum >> 16);
+            }
+            let field = start + offset;
+            self[field..field + 2].copy_from_slice(&(!(sum as u16)).to_be_bytes());
+            self.csum = Checksum::None;
+        }
+    }
+
+    /// A clone holding `part`, which is a slice of the bytes of `self`.
+    pub fn share(&self, part: &[u8]) -> Self {
+        let start = (part.as_ptr() as usize)
+            .checked_sub(self.as_ptr() as usize)
+            .filter(|start| start + part.len() <= self.len())
+            .expect("not a part of the packet buffer");
+        let mut mbuf = self.clone();
+        mbuf.pull(start);
+        mbuf.truncate(part.len());
+        mbuf
+    }
+}
+
+impl Deref for Mbuf {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        unsafe {
+            core::slice::from_raw_parts(self.slot.bytes().add(self.head), self.tail - self.head)
+        }
+    }
+}
+
+impl DerefMut for Mbuf {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        self.assert_unique();
+        unsafe {
+            core::slice::from_raw_parts_mut(self.slot.bytes().add(self.head), self.tail - self.head)
+        }
+    }
+}
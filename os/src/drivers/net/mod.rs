@@ -1,46 +1,43 @@
-use core::any::Any;
+mod mbuf;
+mod virtio_net;
+
+pub use mbuf::Mbuf;
+pub use virtio_net::VirtIONet;
 
-use crate::drivers::virtio::VirtioHal;
-use crate::sync::UPIntrFreeCell;
+use crate::board::VIRTIO_NET;
 use alloc::sync::Arc;
+use core::any::Any;
 use lazy_static::*;
-use virtio_drivers::{VirtIOHeader, VirtIONet};
-
-const VIRTIO8: usize = 0x10004000;
 
 lazy_static! {
-    pub static ref NET_DEVICE: Arc<dyn NetDevice> = Arc::new(VirtIONetWrapper::new());
-}
-
-pub trait NetDevice: Send + Sync + Any {
-    fn transmit(&self, data: &[u8]);
-    fn receive(&self, data: &mut [u8]) -> usize;
+    /// The card, if the board has one.
+    pub static ref NET_DEVICE: Option<Arc<dyn NetDevice>> = VIRTIO_NET
+        .and_then(VirtIONet::new)
+        .map(|device| Arc::new(device) as Arc<dyn NetDevice>);
 }
 
-pub struct VirtIONetWrapper(UPIntrFreeCell<VirtIONet<'static, VirtioHal>>);
-
-impl NetDevice for VirtIONetWrapper {
-    fn transmit(&self, data: &[u8]) {
-        self.0
-            .exclusive_access()
-            .send(data)
-            .expect("can't send data")
-    }
-
-    fn receive(&self, data: &mut [u8]) -> usize {
-        self.0
-            .exclusive_access()
-            .recv(data)
-            .expect("can't receive data")
-    }
+/// The work on packets a card takes off the stack.
+#[derive(Copy, Clone, Default)]
+pub struct Offloads {
+    /// It completes the checksums of TCP and UDP packets sent.
+    pub tx_csum: bool,
+    /// It checks those of packets received, which it then marks.
+    pub rx_csum: bool,
 }
 
-impl VirtIONetWrapper {
-    pub fn new() -> Self {
-        unsafe {
-            let virtio = VirtIONet::<VirtioHal>::new(&mut *(VIRTIO8 as *mut VirtIOHeader))
-                .expect("can't create net device by virtio");
-            VirtIONetWrapper(UPIntrFreeCell::new(virtio))
-        }
-    }
+pub trait NetDevice: Send + Sync + Any {
+    /// Send the frame in `packet`, waiting for a transmit buffer if all are
+    /// in flight.
+    fn transmit(&self, packet: Mbuf);
+    /// A frame received, waiting for one.
+    fn receive(&self) -> Mbuf;
+    /// Whether a packet is there for receive, which waits for one.
+    fn can_receive(&self) -> bool;
+    /// Have `waker` called from the interrupt whenever frames arrive.
+    fn on_receive(&self, waker: fn());
+    /// The MAC address of the card.
+    fn mac(&self) -> [u8; 6];
+    /// What the card was agreed to do.
+    fn offloads(&self) -> Offloads;
+    fn handle_irq(&self);
 }
@@ -0,0 +1,440 @@
+//! The virtio-net device over virtio-mmio, legacy (version 1, which QEMU
+//! gives by default) or modern (version 2).
+//!
+//! Queue 0 receives and queue 1 transmits. The descriptors point at packet
+//! buffers, held by the queue while the device has them, so a buffer is
+//! known by the id of its descriptor. Every receive descriptor is handed to
+//! the device from the start; on the interrupt its buffer, the frame in it,
+//! goes up to the stack and a new one takes its place. A frame to send has
+//! the virtio-net header pushed in its headroom, and is sent from where it
+//! is with a descriptor of the free list, which the interrupt refills as
+//! the device is done with them.
+//!
+//! The checksums of TCP and UDP are left to the device both ways when it
+//! offers to: a frame sent with one to complete says where in its header,
+//! and a frame received is marked as checked if the header says so. TSO
+//! is not asked for, a packet buffer holding no more than a frame.
+
+use super::mbuf::{Mbuf, MBUF_SIZE};
+use super::{NetDevice, Offloads};
+use crate::config::PAGE_SIZE;
+use crate::mm::{frame_alloc_contiguous, phys_to_virt, FrameTracker, PhysAddr};
+use crate::sync::{Condvar, UPIntrFreeCell};
+use crate::task::schedule;
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::ptr::{read_volatile, write_volatile};
+use core::sync::atomic::{fence, Ordering};
+
+const MAGIC: u32 = 0x74726976;
+const DEVICE_NET: u32 = 1;
+
+// virtio-mmio registers
+const REG_MAGIC: usize = 0x000;
+const REG_VERSION: usize = 0x004;
+const REG_DEVICE_ID: usize = 0x008;
+const REG_DEVICE_FEATURES: usize = 0x010;
+const REG_DEVICE_FEATURES_SEL: usize = 0x014;
+const REG_DRIVER_FEATURES: usize = 0x020;
+const REG_DRIVER_FEATURES_SEL: usize = 0x024;
+const REG_GUEST_PAGE_SIZE: usize = 0x028;
+const REG_QUEUE_SEL: usize = 0x030;
+const REG_QUEUE_NUM_MAX: usize = 0x034;
+const REG_QUEUE_NUM: usize = 0x038;
+const REG_QUEUE_ALIGN: usize = 0x03c;
+const REG_QUEUE_PFN: usize = 0x040;
+const REG_QUEUE_READY: usize = 0x044;
+const REG_QUEUE_NOTIFY: usize = 0x050;
+const REG_INTERRUPT_STATUS: usize = 0x060;
+const REG_INTERRUPT_ACK: usize = 0x064;
+const REG_STATUS: usize = 0x070;
+const REG_QUEUE_DESC: usize = 0x080;
+const REG_QUEUE_DRIVER: usize = 0x090;
+const REG_QUEUE_DEVICE: usize = 0x0a0;
+const REG_CONFIG: usize = 0x100;
+
+// device status
+const STATUS_ACKNOWLEDGE: u32 = 1;
+const STATUS_DRIVER: u32 = 2;
+const STATUS_DRIVER_OK: u32 = 4;
+const STATUS_FEATURES_OK: u32 = 8;
+
+// features
+const NET_F_CSUM: u64 = 1 << 0;
+const NET_F_GUEST_CSUM: u64 = 1 << 1;
+const NET_F_MAC: u64 = 1 << 5;
+const NET_F_STATUS: u64 = 1 << 16;
+const F_VERSION_1: u64 = 1 << 32;
+
+const QUEUE_RECEIVE: u32 = 0;
+const QUEUE_TRANSMIT: u32 = 1;
+/// Descriptors, and so buffers, of each queue.
+const QUEUE_SIZE: usize = 16;
+/// Frames kept for receive, newer ones being dropped beyond.
+const RX_BACKLOG: usize = 64;
+/// The address of QEMU's card, for a device which does not tell its own.
+const DEFAULT_MAC: [u8; 6] = [0x52, 0x54, 0x00, 0x12, 0x34, 0x56];
+
+const DESC_F_WRITE: u16 = 2;
+
+// flags of the virtio-net header
+const HDR_F_NEEDS_CSUM: u8 = 1;
+const HDR_F_DATA_VALID: u8 = 2;
+
+#[repr(C)]
+struct Descriptor {
+    addr: u64,
+    len: u32,
+    flags: u16,
+    next: u16,
+}
+
+/// A split virtqueue in the layout of legacy devices, which modern ones
+/// take as well: the descriptors and the available ring in the first page,
+/// the used ring in the second.
+struct VirtQueue {
+    ring: Vec<FrameTracker>,
+    /// The buffer of each descriptor the device has.
+    buffers: Vec<Option<Mbuf>>,
+    avail_idx: u16,
+    last_used: u16,
+}
+
+impl VirtQueue {
+    /// A queue whose descriptors are for the device to write if
+    /// `device_writes`, else to read.
+    fn new(device_writes: bool) -> Option<Self> {
+        let ring = frame_alloc_contiguous(1)?;
+        let queue = Self {
+            ring,
+            buffers: (0..QUEUE_SIZE).map(|_| None).collect(),
+            avail_idx: 0,
+            last_used: 0,
+        };
+        for id in 0..QUEUE_SIZE {
+            let desc = Descriptor {
+                addr: 0,
+                len: 0,
+                flags: if device_writes { DESC_F_WRITE } else { 0 },
+                next: 0,
+            };
+            unsafe { write_volatile(queue.desc(id), desc) };
+        }
+        Some(queue)
+    }
+
+    fn desc_pa(&self) -> usize {
+        PhysAddr::from(self.ring[0].ppn).0
+    }
+
+    fn avail_pa(&self) -> usize {
+        self.desc_pa() + core::mem::size_of::<Descriptor>() * QUEUE_SIZE
+    }
+
+    fn used_pa(&self) -> usize {
+        PhysAddr::from(self.ring[1].ppn).0
+    }
+
+    fn desc(&self, id: usize) -> *mut Descriptor {
+        (phys_to_virt(self.desc_pa()) as *mut Descriptor).wrapping_add(id)
+    }
+
+    /// Offer `mbuf` to the device with descriptor `id`: its bytes to read,
+    /// or, an empty one, its tailroom to write in.
+    fn push(&mut self, id: usize, mbuf: Mbuf, device_writes: bool) {
+        let len = if device_writes {
+            mbuf.tailroom()
+        } else {
+            mbuf.len()
+        };
+        let avail = phys_to_virt(self.avail_pa()) as *mut u16;
+        unsafe {
+            (*self.desc(id)).addr = mbuf.pa() as u64;
+            (*self.desc(id)).len = len as u32;
+            write_volatile(
+                avail.add(2 + self.avail_idx as usize % QUEUE_SIZE),
+                id as u16,
+            );
+            fence(Ordering::SeqCst);
+            self.avail_idx = self.avail_idx.wrapping_add(1);
+            write_volatile(avail.add(1), self.avail_idx);
+            fence(Ordering::SeqCst);
+        }
+        self.buffers[id] = Some(mbuf);
+    }
+
+    /// The descriptor of a buffer the device is done with, and the bytes
+    /// it wrote in it.
+    fn pop_used(&mut self) -> Option<(usize, usize)> {
+        let used = phys_to_virt(self.used_pa()) as *const u16;
+        let used_idx = unsafe { read_volatile(used.add(1)) };
+        if used_idx == self.last_used {
+            return None;
+        }
+        fence(Ordering::SeqCst);
+        let elem =
+            unsafe { (used.add(2) as *const u32).add(2 * (self.last_used as usize % QUEUE_SIZE)) };
+        let (id, len) = unsafe { (read_volatile(elem), read_volatile(elem.add(1))) };
+        self.last_used = self.last_used.wrapping_add(1);
+        Some((id as usize, len as usize))
+    }
+}
+
+struct VirtIONetInner {
+    rx: VirtQueue,
+    tx: VirtQueue,
+    /// Ids of the transmit buffers the device is not holding.
+    tx_free: Vec<usize>,
+    received: VecDeque<Mbuf>,
+    /// Called as frames arrive.
+    waker: Option<fn()>,
+}
+
+pub struct VirtIONet {
+    base: usize,
+    /// The length of the virtio-net header before each frame.
+    header_len: usize,
+    mac: [u8; 6],
+    offloads: Offloads,
+    inner: UPIntrFreeCell<VirtIONetInner>,
+    /// Waiting for a frame to be received.
+    rx_condvar: Condvar,
+    /// Waiting for a transmit buffer to be free.
+    tx_condvar: Condvar,
+}
+
+impl VirtIONet {
+    fn read(&self, reg: usize) -> u32 {
+        unsafe { read_volatile((self.base + reg) as *const u32) }
+    }
+
+    fn write(&self, reg: usize, value: u32) {
+        unsafe { write_volatile((self.base + reg) as *mut u32, value) }
+    }
+
+    /// Give queue `index` its rings.
+    fn setup_queue(&self, index: u32, queue: &VirtQueue, legacy: bool) -> Option<()> {
+        self.write(REG_QUEUE_SEL, index);
+        if (self.read(REG_QUEUE_NUM_MAX) as usize) < QUEUE_SIZE {
+            return None;
+        }
+        self.write(REG_QUEUE_NUM, QUEUE_SIZE as u32);
+        if legacy {
+            self.write(REG_QUEUE_ALIGN, PAGE_SIZE as u32);
+            self.write(REG_QUEUE_PFN, (queue.desc_pa() / PAGE_SIZE) as u32);
+        } else {
+            for (reg, pa) in [
+                (REG_QUEUE_DESC, queue.desc_pa()),
+                (REG_QUEUE_DRIVER, queue.avail_pa()),
+                (REG_QUEUE_DEVICE, queue.used_pa()),
+            ] {
+                self.write(reg, pa as u32);
+                self.write(reg + 4, (pa >> 32) as u32);
+            }
+            self.write(REG_QUEUE_READY, 1);
+        }
+        Some(())
+    }
+
+    /// The network card at the virtio-mmio slot `base`, if there is one.
+    pub fn new(base: usize) -> Option<Self> {
+        let rx = VirtQueue::new(true)?;
+        let tx = VirtQueue::new(false)?;
+        let mut net = Self {
+            base: phys_to_virt(base),
+            header_len: 10,
+            mac: DEFAULT_MAC,
+            offloads: Offloads::default(),
+            inner: unsafe {
+                UPIntrFreeCell::new(VirtIONetInner {
+                    rx,
+                    tx,
+                    tx_free: (0..QUEUE_SIZE).collect(),
+                    received: VecDeque::new(),
+                    waker: None,
+                })
+            },
+            rx_condvar: Condvar::new(),
+            tx_condvar: Condvar::new(),
+        };
+        let version = net.read(REG_VERSION);
+        if net.read(REG_MAGIC) != MAGIC
+            || !(1..=2).contains(&version)
+            || net.read(REG_DEVICE_ID) != DEVICE_NET
+        {
+            return None;
+        }
+        let legacy = version == 1;
+        net.write(REG_STATUS, 0);
+        net.write(REG_STATUS, STATUS_ACKNOWLEDGE);
+        net.write(REG_STATUS, STATUS_ACKNOWLEDGE | STATUS_DRIVER);
+
+        // feature negotiation: the MAC address and the link status, the
+        // checksums both ways, and a modern device's own layout
+        net.write(REG_DEVICE_FEATURES_SEL, 0);
+        let mut features = net.read(REG_DEVICE_FEATURES) as u64;
+        net.write(REG_DEVICE_FEATURES_SEL, 1);
+        features |= (net.read(REG_DEVICE_FEATURES) as u64) << 32;
+        let mut wanted = NET_F_MAC | NET_F_STATUS | NET_F_CSUM | NET_F_GUEST_CSUM;
+        if !legacy {
+            wanted |= F_VERSION_1;
+        }
+        let features = features & wanted;
+        net.write(REG_DRIVER_FEATURES_SEL, 0);
+        net.write(REG_DRIVER_FEATURES, features as u32);
+        net.write(REG_DRIVER_FEATURES_SEL, 1);
+        net.write(REG_DRIVER_FEATURES, (features >> 32) as u32);
+        let mut status = STATUS_ACKNOWLEDGE | STATUS_DRIVER;
+        if legacy {
+            net.write(REG_GUEST_PAGE_SIZE, PAGE_SIZE as u32);
+        } else {
+            status |= STATUS_FEATURES_OK;
+            net.write(REG_STATUS, status);
+            if net.read(REG_STATUS) & STATUS_FEATURES_OK == 0 {
+                return None;
+            }
+        }
+        // with VIRTIO_F_VERSION_1 the header always has num_buffers
+        if features & F_VERSION_1 != 0 {
+            net.header_len = 12;
+        }
+        net.offloads = Offloads {
+            tx_csum: features & NET_F_CSUM != 0,
+            rx_csum: features & NET_F_GUEST_CSUM != 0,
+        };
+        if features & NET_F_MAC != 0 {
+            for (i, byte) in net.mac.iter_mut().enumerate() {
+                *byte = unsafe { read_volatile((net.base + REG_CONFIG + i) as *const u8) };
+            }
+        }
+
+        let mut inner = net.inner.exclusive_access();
+        net.setup_queue(QUEUE_RECEIVE, &inner.rx, legacy)?;
+        net.setup_queue(QUEUE_TRANSMIT, &inner.tx, legacy)?;
+        for id in 0..QUEUE_SIZE {
+            inner.rx.push(id, Mbuf::with_headroom(0)?, true);
+        }
+        drop(inner);
+        net.write(REG_STATUS, status | STATUS_DRIVER_OK);
+        net.write(REG_QUEUE_NOTIFY, QUEUE_RECEIVE);
+        Some(net)
+    }
+}
+
+impl NetDevice for VirtIONet {
+    fn transmit(&self, mut packet: Mbuf) {
+        // a frame built elsewhere may leave no room for our header, or be
+        // shared, and then goes in a buffer of its own
+        if packet.is_shared() || packet.headroom() < self.header_len {
+            packet = match packet.copy_with_headroom(self.header_len) {
+                Some(copy) => copy,
+                None => return,
+            };
+        }
+        let csum = packet.checksum_partial();
+        // no segmentation, so gso_type and gso_size stay zero
+        let header = packet.push(self.header_len);
+        header.fill(0);
+        if let Some((start, offset)) = csum {
+            header[0] = HDR_F_NEEDS_CSUM;
+            header[6..8].copy_from_slice(&(start as u16).to_le_bytes());
+            header[8..10].copy_from_slice(&(offset as u16).to_le_bytes());
+        }
+        loop {
+            let mut inner = self.inner.exclusive_access();
+            if let Some(id) = inner.tx_free.pop() {
+                inner.tx.push(id, packet, false);
+                drop(inner);
+                self.write(REG_QUEUE_NOTIFY, QUEUE_TRANSMIT);
+                return;
+            }
+            let task_cx_ptr = self.tx_condvar.wait_no_sched();
+            drop(inner);
+            schedule(task_cx_ptr);
+        }
+    }
+
+    fn receive(&self) -> Mbuf {
+        loop {
+            let mut inner = self.inner.exclusive_access();
+            if let Some(frame) = inner.received.pop_front() {
+                return frame;
+            }
+            let task_cx_ptr = self.rx_condvar.wait_no_sched();
+            drop(inner);
+            schedule(task_cx_ptr);
+        }
+    }
+
+    fn can_receive(&self) -> bool {
+        !self.inner.exclusive_access().received.is_empty()
+    }
+
+    fn on_receive(&self, waker: fn()) {
+        self.inner.exclusive_access().waker = Some(waker);
+    }
+
+    fn mac(&self) -> [u8; 6] {
+        self.mac
+    }
+
+    fn offloads(&self) -> Offloads {
+        self.offloads
+    }
+
+    fn handle_irq(&self) {
+        let status = self.read(REG_INTERRUPT_STATUS);
+        self.write(REG_INTERRUPT_ACK, status);
+        let (replenished, received, sent, waker) = self.inner.exclusive_session(|inner| {
+            let (mut replenished, mut received) = (0, 0);
+            while let Some((id, len)) = inner.rx.pop_used() {
+                let mut frame = inner.rx.buffers[id].take().unwrap();
+                // the frame goes up if a new buffer takes its place, else
+                // it is dropped and its buffer offered again
+                let fresh = if inner.received.len() < RX_BACKLOG {
+                    Mbuf::with_headroom(0)
+                } else {
+                    None
+                };
+                match fresh {
+                    Some(fresh) => {
+                        frame.put(len.clamp(self.header_len, MBUF_SIZE));
+                        // a checksum left to complete came from the host,
+                        // which is trusted as one it checked
+                        let flags = HDR_F_NEEDS_CSUM | HDR_F_DATA_VALID;
+                        if self.offloads.rx_csum && frame[0] & flags != 0 {
+                            frame.set_checksum_verified();
+                        }
+                        frame.pull(self.header_len);
+                        inner.received.push_back(frame);
+                        received += 1;
+                        inner.rx.push(id, fresh, true);
+                    }
+                    None => inner.rx.push(id, frame, true),
+                }
+                // replenished at once, for the device never to run short
+                replenished += 1;
+            }
+            let mut sent = 0;
+            while let Some((id, _)) = inner.tx.pop_used() {
+                // the frame is sent, and its buffer free
+                inner.tx.buffers[id] = None;
+                inner.tx_free.push(id);
+                sent += 1;
+            }
+            (replenished, received, sent, inner.waker)
+        });
+        if replenished > 0 {
+            self.write(REG_QUEUE_NOTIFY, QUEUE_RECEIVE);
+        }
+        for _ in 0..received {
+            self.rx_condvar.signal();
+        }
+        if let Some(waker) = waker.filter(|_| received > 0) {
+            waker();
+        }
+        for _ in 0..sent {
+            self.tx_condvar.signal();
+        }
+    }
+}
@@ -1,6 +1,9 @@
 #[allow(clippy::upper_case_acronyms)]
 pub struct PLIC {
     base_addr: usize,
+    /// Contexts hart 0 lacks before those of hart 1, 1 where it has no S
+    /// mode, like the monitor core of the JH7110.
+    missing_contexts: usize,
 }
 
 #[derive(Copy, Clone)]
@@ -20,9 +23,10 @@ impl PLIC {
         assert!(intr_source_id > 0 && intr_source_id <= 132);
         (self.base_addr + intr_source_id * 4) as *mut u32
     }
-    fn hart_id_with_priority(hart_id: usize, target_priority: IntrTargetPriority) -> usize {
+    fn hart_id_with_priority(&self, hart_id: usize, target_priority: IntrTargetPriority) -> usize {
         let priority_num = IntrTargetPriority::supported_number();
-        hart_id * priority_num + target_priority as usize
+        let missing = if hart_id > 0 { self.missing_contexts } else { 0 };
+        hart_id * priority_num + target_priority as usize - missing
     }
     fn enable_ptr(
         &self,
@@ -30,7 +34,7 @@ impl PLIC {
         target_priority: IntrTargetPriority,
         intr_source_id: usize,
     ) -> (*mut u32, usize) {
-        let id = Self::hart_id_with_priority(hart_id, target_priority);
+        let id = self.hart_id_with_priority(hart_id, target_priority);
         let (reg_id, reg_shift) = (intr_source_id / 32, intr_source_id % 32);
         (
             (self.base_addr + 0x2000 + 0x80 * id + 0x4 * reg_id) as *mut u32,
@@ -42,7 +46,7 @@ impl PLIC {
         hart_id: usize,
         target_priority: IntrTargetPriority,
     ) -> *mut u32 {
-        let id = Self::hart_id_with_priority(hart_id, target_priority);
+        let id = self.hart_id_with_priority(hart_id, target_priority);
         (self.base_addr + 0x20_0000 + 0x1000 * id) as *mut u32
     }
     fn claim_comp_ptr_of_hart_with_priority(
@@ -50,11 +54,22 @@ impl PLIC {
         hart_id: usize,
         target_priority: IntrTargetPriority,
     ) -> *mut u32 {
-        let id = Self::hart_id_with_priority(hart_id, target_priority);
+        let id = self.hart_id_with_priority(hart_id, target_priority);
         (self.base_addr + 0x20_0004 + 0x1000 * id) as *mut u32
     }
     pub unsafe fn new(base_addr: usize) -> Self {
-        Self { base_addr }
+        Self {
+            base_addr,
+            missing_contexts: 0,
+        }
+    }
+    /// The PLIC at `base_addr` of a board whose hart 0 has an M mode only.
+    #[allow(unused)]
+    pub unsafe fn new_with_machine_only_hart0(base_addr: usize) -> Self {
+        Self {
+            base_addr,
+            missing_contexts: 1,
+        }
     }
     pub fn set_priority(&mut self, intr_source_id: usize, priority: u32) {
         assert!(priority < 8);
@@ -1,8 +1,31 @@
     .section .text.entry
     .globl _start
 _start:
-    la sp, boot_stack_top
-    call rust_main
+    # turn on paging with boot_page_table before anything else, so that the
+    # direct map of physical memory is there from the start
+    lla t0, boot_page_table
+    srli t0, t0, 12
+    li t1, 8 << 60
+    or t0, t0, t1
+    csrw satp, t0
+    sfence.vma
+    lla sp, boot_stack_top
+    # move the kernel if it is built with KASLR, keeping the hart id and
+    # the device tree; lla rather than la, which is through the GOT the
+    # relocations change
+    mv s0, a0
+    mv s1, a1
+    mv a0, a1
+    call kaslr_relocate
+    mv a2, a0
+    mv a0, s0
+    mv a1, s1
+    li s0, 0
+    lla sp, boot_stack_top
+    add sp, sp, a2
+    lla t0, rust_main
+    add t0, t0, a2
+    jr t0
 
     .section .bss.stack
     .globl boot_stack_lower_bound
@@ -10,3 +33,32 @@ boot_stack_lower_bound:
     .space 4096 * 16
     .globl boot_stack_top
 boot_stack_top:
+
+    # gigapages until the kernel space is activated, kept out of .bss which
+    # is cleared by rust_main; the first 4 GiB, where the memory of every
+    # board starts, at 0x4000_0000 or 0x8000_0000
+    .section .data
+    .align 12
+    .globl boot_page_table
+boot_page_table:
+    # 0x0000_0000 -> 0x0000_0000, where the kernel is loaded, and runs
+    # without KASLR
+    .quad (0x00000 << 10) | 0xcf
+    .quad (0x40000 << 10) | 0xcf
+    .quad (0x80000 << 10) | 0xcf
+    .quad (0xc0000 << 10) | 0xcf
+    .zero 8 * 252
+    # 0xffff_ffc0_0000_0000 -> 0x0000_0000, the direct map of devices and
+    # memory
+    .quad (0x00000 << 10) | 0xcf
+    .quad (0x40000 << 10) | 0xcf
+    .quad (0x80000 << 10) | 0xcf
+    .quad (0xc0000 << 10) | 0xcf
+    .zero 8 * 252
+
+    # the megapages of the kernel image at the address KASLR picked, one
+    # gigapage of the boot page table pointing here
+    .align 12
+    .globl boot_kaslr_table
+boot_kaslr_table:
+    .zero 4096
@@ -0,0 +1,111 @@
+//! Faults injected on purpose, in kernels built with the `fault_inject`
+//! feature, for the paths handling failures to be run at will rather than
+//! when the machine happens to fail.
+//!
+//! Each point of injection counts the events passing it, and once armed
+//! with an interval n, the nth event from then on faults, and every nth
+//! after it:
+//!
+//! - Alloc, allocations of the frames of user pages fail, which the kernel
+//!   handles; those of page tables, of the page cache and of areas mapped
+//!   at once are left alone, as their failure would only panic;
+//! - Block, reads and writes of a disk fail, which the driver tries again,
+//!   or are delayed by the milliseconds asked for;
+//! - Packet, frames sent or received are dropped;
+//! - Heap, allocations of the buffers of the sizes users ask for fail,
+//!   which the syscalls report as ENOMEM; other heap allocations are left
+//!   alone, as the kernel panics on those.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(Clone, Copy)]
+pub enum FaultPoint {
+    Alloc = 0,
+    Block = 1,
+    Packet = 2,
+    Heap = 3,
+}
+
+impl FaultPoint {
+    pub fn from_usize(point: usize) -> Option<Self> {
+        match point {
+            0 => Some(Self::Alloc),
+            1 => Some(Self::Block),
+            2 => Some(Self::Packet),
+            3 => Some(Self::Heap),
+            _ => None,
+        }
+    }
+}
+
+/// What an event which faults is to do.
+pub enum Fault {
+    Fail,
+    /// wait so many milliseconds, then go on
+    Delay(usize),
+}
+
+/// Atomics rather than a lock, for frames to be allocated with any held.
+struct Injection {
+    /// 0 when not armed
+    interval: AtomicUsize,
+    delay_ms: AtomicUsize,
+    /// events since it was armed
+    events: AtomicUsize,
+    /// faults since it was armed
+    injected: AtomicUsize,
+}
+
+impl Injection {
+    const fn new() -> Self {
+        Self {
+            interval: AtomicUsize::new(0),
+            delay_ms: AtomicUsize::new(0),
+            events: AtomicUsize::new(0),
+            injected: AtomicUsize::new(0),
+        }
+    }
+}
+
+static INJECTIONS: [Injection; 4] = [
+    Injection::new(),
+    Injection::new(),
+    Injection::new(),
+    Injection::new(),
+];
+
+/// Count an event at `point`; the fault it is to suffer, if any.
+pub fn inject(point: FaultPoint) -> Option<Fault> {
+    let injection = &INJECTIONS[point as usize];
+    let interval = injection.interval.load(Ordering::Relaxed);
+    if interval == 0 {
+        return None;
+    }
+    let events = injection.events.fetch_add(1, Ordering::Relaxed) + 1;
+    if events % interval != 0 {
+        return None;
+    }
+    injection.injected.fetch_add(1, Ordering::Relaxed);
+    match injection.delay_ms.load(Ordering::Relaxed) {
+        0 => Some(Fault::Fail),
+        ms => Some(Fault::Delay(ms)),
+    }
+}
+
+/// Whether an event at `point` is to fail, for points which only fail.
+pub fn should_fail(point: FaultPoint) -> bool {
+    inject(point).is_some()
+}
+
+/// Fault every `interval`th event at `point` from now on, delaying it by
+/// `delay_ms` rather than failing it if that is not 0, or none if
+/// `interval` is 0; how many faults it injected since it was last armed.
+pub fn arm(point: FaultPoint, interval: usize, delay_ms: usize) -> usize {
+    let injection = &INJECTIONS[point as usize];
+    injection.interval.store(0, Ordering::Relaxed);
+    injection.events.store(0, Ordering::Relaxed);
+    injection.delay_ms.store(delay_ms, Ordering::Relaxed);
+    let injected = injection.injected.swap(0, Ordering::Relaxed);
+    injection.interval.store(interval, Ordering::Relaxed);
+    injected
+}
@@ -0,0 +1,509 @@
+//! devfs, the devices as files, mounted on /dev.
+//!
+//! Each device is an inode of its own kind, reading and writing through
+//! its driver. Character devices other than the framebuffer have no
+//! offsets, so they ignore the ones they are given.
+
+use super::stat::{Stat, S_IFCHR, S_IFDIR};
+use super::tty::{tty_ioctl, wait_input};
+use super::vfs::{FileSystem, Inode};
+use crate::drivers::chardev::{CharDevice, UART};
+use crate::drivers::{GpuDevice, InputDevice, GPU_DEVICE, KEYBOARD_DEVICE, MOUSE_DEVICE};
+#[cfg(feature = "kcov")]
+use crate::mm::SharedMemory;
+use crate::mm::{virt_to_phys, UserPtr};
+use crate::random::{add_entropy, fill_random};
+use crate::syscall::EFAULT;
+use crate::task::current_user_token;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::any::Any;
+use lazy_static::*;
+
+const FBIOGET_VSCREENINFO: usize = 0x4600;
+const FBIOGET_FSCREENINFO: usize = 0x4602;
+/// Show what was drawn, which virtio-gpu needs to be told.
+const FBIOPAN_DISPLAY: usize = 0x4606;
+
+const FB_TYPE_PACKED_PIXELS: u32 = 0;
+const FB_VISUAL_TRUECOLOR: u32 = 2;
+
+/// Device number as stat shows it.
+const fn makedev(major: u64, minor: u64) -> u64 {
+    (major << 8) | minor
+}
+
+fn device_stat(ino: u64, rdev: u64) -> Stat {
+    let mut stat = Stat::new(S_IFCHR | 0o666);
+    stat.ino = ino;
+    stat.rdev = rdev;
+    stat
+}
+
+/// A directory of devices, which does not change.
+struct DevDir {
+    ino: u64,
+    entries: Vec<(&'static str, Arc<dyn Inode>)>,
+}
+
+impl Inode for DevDir {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn is_dir(&self) -> bool {
+        true
+    }
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> usize {
+        0
+    }
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> usize {
+        0
+    }
+    fn size(&self) -> usize {
+        0
+    }
+    fn stat(&self) -> Stat {
+        let mut stat = Stat::new(S_IFDIR | 0o755);
+        stat.ino = self.ino;
+        stat.nlink = 2;
+        stat
+    }
+    fn find(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        self.entries
+            .iter()
+            .find(|(entry, _)| *entry == name)
+            .map(|(_, inode)| inode.clone())
+    }
+    fn ls(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .collect()
+    }
+}
+
+/// /dev/null, empty and swallowing everything.
+struct Null;
+
+impl Inode for Null {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn is_dir(&self) -> bool {
+        false
+    }
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> usize {
+        0
+    }
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> usize {
+        buf.len()
+    }
+    fn size(&self) -> usize {
+        0
+    }
+    fn stat(&self) -> Stat {
+        device_stat(2, makedev(1, 3))
+    }
+}
+
+/// /dev/zero, zeros for reading and for private mappings.
+struct Zero;
+
+impl Inode for Zero {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn is_dir(&self) -> bool {
+        false
+    }
+    fn read_at(&self, _offset: usize, buf: &mut [u8]) -> usize {
+        buf.fill(0);
+        buf.len()
+    }
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> usize {
+        buf.len()
+    }
+    fn size(&self) -> usize {
+        0
+    }
+    fn stat(&self) -> Stat {
+        device_stat(3, makedev(1, 5))
+    }
+}
+
+/// /dev/urandom, the entropy pool. Writes are mixed into it.
+struct Urandom;
+
+impl Inode for Urandom {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn is_dir(&self) -> bool {
+        false
+    }
+    fn read_at(&self, _offset: usize, buf: &mut [u8]) -> usize {
+        fill_random(buf);
+        buf.len()
+    }
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> usize {
+        for chunk in buf.chunks(8) {
+            let mut bytes = [0u8; 8];
+            bytes[..chunk.len()].copy_from_slice(chunk);
+            add_entropy(usize::from_le_bytes(bytes));
+        }
+        buf.len()
+    }
+    fn size(&self) -> usize {
+        0
+    }
+    fn stat(&self) -> Stat {
+        device_stat(4, makedev(1, 9))
+    }
+}
+
+/// /dev/console, the UART. Reads wait for the first byte only.
+struct Console;
+
+impl Inode for Console {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn is_dir(&self) -> bool {
+        false
+    }
+    fn read_at(&self, _offset: usize, buf: &mut [u8]) -> usize {
+        if buf.is_empty() || !wait_input() {
+            return 0;
+        }
+        buf[0] = UART.read();
+        let mut len = 1;
+        while len < buf.len() && !UART.read_buffer_is_empty() {
+            buf[len] = UART.read();
+            len += 1;
+        }
+        len
+    }
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> usize {
+        for byte in buf {
+            UART.write(*byte);
+        }
+        buf.len()
+    }
+    fn size(&self) -> usize {
+        0
+    }
+    fn stat(&self) -> Stat {
+        device_stat(5, makedev(5, 1))
+    }
+    fn read_ready(&self) -> bool {
+        !UART.read_buffer_is_empty()
+    }
+    fn ioctl(&self, cmd: usize, arg: usize) -> isize {
+        tty_ioctl(cmd, arg)
+    }
+}
+
+/// /dev/kcov, the coverage of the syscalls of a thread, for fuzzers; the
+/// area mapped is that of the process mapping it.
+#[cfg(feature = "kcov")]
+struct Kcov;
+
+#[cfg(feature = "kcov")]
+impl Inode for Kcov {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn is_dir(&self) -> bool {
+        false
+    }
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> usize {
+        0
+    }
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> usize {
+        0
+    }
+    fn size(&self) -> usize {
+        0
+    }
+    fn stat(&self) -> Stat {
+        device_stat(10, makedev(10, 60))
+    }
+    fn ioctl(&self, cmd: usize, arg: usize) -> isize {
+        crate::kcov::kcov_ioctl(cmd, arg)
+    }
+    fn shared_memory(&self) -> Option<Arc<SharedMemory>> {
+        crate::kcov::kcov_memory()
+    }
+}
+
+/// /dev/input/event*, the events of an input device, 8 bytes each: type,
+/// code and value from the high bits down, as sys_event_get returns them.
+struct Event {
+    ino: u64,
+    index: u64,
+    device: Arc<dyn InputDevice>,
+}
+
+impl Inode for Event {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn is_dir(&self) -> bool {
+        false
+    }
+    fn read_at(&self, _offset: usize, buf: &mut [u8]) -> usize {
+        let mut len = 0;
+        for chunk in buf.chunks_exact_mut(8) {
+            // waiting for the first one only
+            if len > 0 && self.device.is_empty() {
+                break;
+            }
+            chunk.copy_from_slice(&self.device.read_event().to_le_bytes());
+            len += 8;
+        }
+        len
+    }
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> usize {
+        0
+    }
+    fn size(&self) -> usize {
+        0
+    }
+    fn stat(&self) -> Stat {
+        device_stat(self.ino, makedev(13, 64 + self.index))
+    }
+    fn read_ready(&self) -> bool {
+        !self.device.is_empty()
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+struct FbBitfield {
+    offset: u32,
+    length: u32,
+    msb_right: u32,
+}
+
+/// `struct fb_var_screeninfo` of Linux, as far as it applies.
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+struct FbVarScreenInfo {
+    xres: u32,
+    yres: u32,
+    xres_virtual: u32,
+    yres_virtual: u32,
+    xoffset: u32,
+    yoffset: u32,
+    bits_per_pixel: u32,
+    grayscale: u32,
+    red: FbBitfield,
+    green: FbBitfield,
+    blue: FbBitfield,
+    transp: FbBitfield,
+    nonstd: u32,
+    activate: u32,
+    height: u32,
+    width: u32,
+    accel_flags: u32,
+    pixclock: u32,
+    left_margin: u32,
+    right_margin: u32,
+    upper_margin: u32,
+    lower_margin: u32,
+    hsync_len: u32,
+    vsync_len: u32,
+    sync: u32,
+    vmode: u32,
+    rotate: u32,
+    colorspace: u32,
+    reserved: [u32; 4],
+}
+
+/// `struct fb_fix_screeninfo` of Linux.
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+struct FbFixScreenInfo {
+    id: [u8; 16],
+    smem_start: usize,
+    smem_len: u32,
+    type_: u32,
+    type_aux: u32,
+    visual: u32,
+    xpanstep: u16,
+    ypanstep: u16,
+    ywrapstep: u16,
+    line_length: u32,
+    mmio_start: usize,
+    mmio_len: u32,
+    accel: u32,
+    capabilities: u16,
+    reserved: [u16; 2],
+}
+
+/// /dev/fb0, the framebuffer of the GPU, which mmap maps directly.
+struct Framebuffer {
+    gpu: Arc<dyn GpuDevice>,
+}
+
+impl Framebuffer {
+    fn var_screeninfo(&self) -> FbVarScreenInfo {
+        let (width, height) = self.gpu.resolution();
+        let channel = |offset| FbBitfield {
+            offset,
+            length: 8,
+            msb_right: 0,
+        };
+        FbVarScreenInfo {
+            xres: width,
+            yres: height,
+            xres_virtual: width,
+            yres_virtual: height,
+            bits_per_pixel: 32,
+            red: channel(16),
+            green: channel(8),
+            blue: channel(0),
+            transp: channel(24),
+            ..Default::default()
+        }
+    }
+    fn fix_screeninfo(&self) -> FbFixScreenInfo {
+        let (width, _) = self.gpu.resolution();
+        let fb = self.gpu.get_framebuffer();
+        let mut id = [0u8; 16];
+        id[..10].copy_from_slice(b"virtio-gpu");
+        FbFixScreenInfo {
+            id,
+            smem_start: virt_to_phys(fb.as_ptr() as usize),
+            smem_len: fb.len() as u32,
+            type_: FB_TYPE_PACKED_PIXELS,
+            visual: FB_VISUAL_TRUECOLOR,
+            line_length: width * 4,
+            ..Default::default()
+        }
+    }
+}
+
+impl Inode for Framebuffer {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn is_dir(&self) -> bool {
+        false
+    }
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let fb = self.gpu.get_framebuffer();
+        if offset >= fb.len() {
+            return 0;
+        }
+        let len = buf.len().min(fb.len() - offset);
+        buf[..len].copy_from_slice(&fb[offset..offset + len]);
+        len
+    }
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        let fb = self.gpu.get_framebuffer();
+        if offset >= fb.len() {
+            return 0;
+        }
+        let len = buf.len().min(fb.len() - offset);
+        fb[offset..offset + len].copy_from_slice(&buf[..len]);
+        len
+    }
+    fn size(&self) -> usize {
+        self.gpu.get_framebuffer().len()
+    }
+    fn stat(&self) -> Stat {
+        let mut stat = device_stat(6, makedev(29, 0));
+        stat.size = self.size() as i64;
+        stat
+    }
+    fn ioctl(&self, cmd: usize, arg: usize) -> isize {
+        let token = current_user_token();
+        let written = match cmd {
+            FBIOGET_VSCREENINFO => {
+                UserPtr::new(token, arg as *mut FbVarScreenInfo).write(self.var_screeninfo())
+            }
+            FBIOGET_FSCREENINFO => {
+                UserPtr::new(token, arg as *mut FbFixScreenInfo).write(self.fix_screeninfo())
+            }
+            FBIOPAN_DISPLAY => {
+                self.gpu.flush();
+                Some(())
+            }
+            _ => return -1,
+        };
+        match written {
+            Some(()) => 0,
+            None => EFAULT,
+        }
+    }
+    fn phys_range(&self) -> Option<(usize, usize)> {
+        let fb = self.gpu.get_framebuffer();
+        Some((virt_to_phys(fb.as_ptr() as usize), fb.len()))
+    }
+}
+
+pub struct DevFs {
+    root: Arc<DevDir>,
+}
+
+impl FileSystem for DevFs {
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+}
+
+lazy_static! {
+    static ref DEV_FS: Arc<DevFs> = {
+        // the devices the board has
+        let mut input = DevDir {
+            ino: 7,
+            entries: Vec::new(),
+        };
+        if let Some(device) = KEYBOARD_DEVICE.clone() {
+            let event = Event {
+                ino: 8,
+                index: 0,
+                device,
+            };
+            input.entries.push(("event0", Arc::new(event)));
+        }
+        if let Some(device) = MOUSE_DEVICE.clone() {
+            let event = Event {
+                ino: 9,
+                index: 1,
+                device,
+            };
+            input.entries.push(("event1", Arc::new(event)));
+        }
+        let mut root = DevDir {
+            ino: 1,
+            entries: vec![("console", Arc::new(Console) as Arc<dyn Inode>)],
+        };
+        if let Some(gpu) = GPU_DEVICE.clone() {
+            root.entries.push(("fb0", Arc::new(Framebuffer { gpu })));
+        }
+        root.entries.extend([
+            ("input", Arc::new(input) as Arc<dyn Inode>),
+            ("null", Arc::new(Null)),
+            ("urandom", Arc::new(Urandom)),
+            ("zero", Arc::new(Zero)),
+        ]);
+        #[cfg(feature = "kcov")]
+        root.entries.push(("kcov", Arc::new(Kcov)));
+        Arc::new(DevFs {
+            root: Arc::new(root),
+        })
+    };
+}
+
+/// There is one devfs, whatever the source.
+fn mount(_source: &str) -> Option<Arc<dyn FileSystem>> {
+    Some(DEV_FS.clone())
+}
+
+pub fn init() {
+    super::register_filesystem("devfs", mount);
+}
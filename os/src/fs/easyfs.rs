@@ -0,0 +1,333 @@
+//! easy-fs on the block device, as the root file system if there is a disk.
+//! It is checked and repaired as it is opened.
+//!
+//! The contents of regular files go through the page cache; a symbolic
+//! link keeps its target as its contents, a FIFO nothing. There is one
+//! `EfsInode` for each inode in use, so that all of its users share the
+//! cache.
+
+use super::page_cache::{PageCache, PageIo};
+use super::stat::{Stat, S_IFDIR, S_IFIFO, S_IFLNK, S_IFREG, S_IFSOCK};
+use super::vfs::{DirEntry, FileSystem, Inode};
+use crate::cmdline;
+use crate::drivers::{block_device, disk_name, ReadBlocks};
+use crate::mm::FrameTracker;
+use crate::objects::{Live, OBJ_INODE};
+use crate::sync::UPIntrFreeCell;
+use crate::task::BoxFuture;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::any::Any;
+use easy_fs::{DiskInodeType, EasyFileSystem};
+use lazy_static::*;
+
+/// The disk of the root file system unless `root=` names another.
+const DEFAULT_ROOT: &str = "/dev/vda";
+
+pub struct EasyFs {
+    root: Arc<EfsInode>,
+}
+
+pub struct EfsInode {
+    inode: Arc<easy_fs::Inode>,
+    /// None for all but regular files, which easy-fs reads and writes
+    /// itself
+    cache: Option<PageCache>,
+    type_: DiskInodeType,
+    _live: Live<OBJ_INODE>,
+}
+
+lazy_static! {
+    /// The `EfsInode`s in use, by inode number.
+    static ref INODES: UPIntrFreeCell<BTreeMap<u32, Weak<EfsInode>>> =
+        unsafe { UPIntrFreeCell::new(BTreeMap::new()) };
+}
+
+/// The `EfsInode` of `inode`, shared with the other users of the inode.
+fn efs_inode(inode: Arc<easy_fs::Inode>) -> Arc<EfsInode> {
+    let inode_id = inode.inode_id();
+    if let Some(efs_inode) = INODES
+        .exclusive_access()
+        .get(&inode_id)
+        .and_then(|efs_inode| efs_inode.upgrade())
+    {
+        return efs_inode;
+    }
+    // easy-fs reads the disk, so outside the table
+    let type_ = inode.inode_type();
+    let cache = if type_ == DiskInodeType::File {
+        Some(PageCache::new(inode.size()))
+    } else {
+        None
+    };
+    let efs_inode = Arc::new(EfsInode {
+        inode,
+        cache,
+        type_,
+        _live: Live::default(),
+    });
+    INODES
+        .exclusive_access()
+        .insert(inode_id, Arc::downgrade(&efs_inode));
+    efs_inode
+}
+
+/// The file type bits of the mode of `type_`.
+fn file_type(type_: DiskInodeType) -> u32 {
+    match type_ {
+        DiskInodeType::File => S_IFREG,
+        DiskInodeType::Directory => S_IFDIR,
+        DiskInodeType::Symlink => S_IFLNK,
+        DiskInodeType::Fifo => S_IFIFO,
+        DiskInodeType::Socket => S_IFSOCK,
+    }
+}
+
+impl PageIo for easy_fs::Inode {
+    fn read_page(&self, offset: usize, buf: &mut [u8]) -> usize {
+        self.read_at(offset, buf)
+    }
+    fn read_page_async(&self, offset: usize, frame: FrameTracker) -> BoxFuture {
+        let bytes = frame.ppn.get_bytes_array();
+        let uncached = self.read_cached_blocks(offset, bytes);
+        let read = ReadBlocks::new(self.block_device(), bytes, uncached);
+        Box::pin(async move {
+            read.await;
+            // the disk is done with the frame
+            drop(frame);
+        })
+    }
+    fn write_page(&self, offset: usize, buf: &[u8]) {
+        self.write_at(offset, buf);
+    }
+}
+
+impl EfsInode {
+    /// Write the dirty pages back to the disk.
+    fn write_back(&self) {
+        if let Some(cache) = &self.cache {
+            cache.write_back(self.inode.as_ref());
+        }
+    }
+}
+
+impl Drop for EfsInode {
+    /// Nobody reads the pages of an unlinked inode any more, so they are
+    /// only written back while it has a name.
+    fn drop(&mut self) {
+        let mut inodes = INODES.exclusive_access();
+        let inode_id = self.inode.inode_id();
+        if inodes
+            .get(&inode_id)
+            .map_or(false, |efs_inode| efs_inode.strong_count() == 0)
+        {
+            inodes.remove(&inode_id);
+        }
+        drop(inodes);
+        if self.inode.nlink() > 0 {
+            self.write_back();
+        }
+    }
+}
+
+/// Write the dirty pages of all inodes in use back.
+fn sync_all() {
+    let inodes: Vec<_> = INODES
+        .exclusive_access()
+        .values()
+        .filter_map(|efs_inode| efs_inode.upgrade())
+        .collect();
+    for efs_inode in inodes {
+        efs_inode.write_back();
+    }
+}
+
+impl FileSystem for EasyFs {
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+    fn sync(&self) {
+        sync_all()
+    }
+}
+
+impl Inode for EfsInode {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn is_dir(&self) -> bool {
+        self.type_ == DiskInodeType::Directory
+    }
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        match &self.cache {
+            Some(cache) => cache.read_at(offset, buf, self.inode.as_ref()),
+            None => self.inode.read_at(offset, buf),
+        }
+    }
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        match &self.cache {
+            Some(cache) => cache.write_at(offset, buf, self.inode.as_ref()),
+            None => self.inode.write_at(offset, buf),
+        }
+    }
+    fn size(&self) -> usize {
+        match &self.cache {
+            Some(cache) => cache.size(),
+            None => self.inode.size(),
+        }
+    }
+    fn append(&self, buf: &[u8]) -> usize {
+        match &self.cache {
+            Some(cache) => cache.append(buf, self.inode.as_ref()),
+            None => self.inode.append(buf),
+        }
+    }
+    fn sync(&self) {
+        self.write_back()
+    }
+    fn stat(&self) -> Stat {
+        let (mode, uid, gid) = self.inode.permissions();
+        let mut stat = Stat::new(file_type(self.type_) | mode);
+        stat.ino = self.inode.inode_id() as u64;
+        stat.uid = uid;
+        stat.gid = gid;
+        stat.nlink = self.inode.nlink();
+        stat.size = self.size() as i64;
+        stat.blksize = easy_fs::BLOCK_SZ as i32;
+        stat.blocks = self.inode.blocks() as i64;
+        // the only time easy-fs keeps
+        let mtime = self.inode.mtime() as i64;
+        stat.atime = mtime;
+        stat.mtime = mtime;
+        stat.ctime = mtime;
+        stat
+    }
+    fn find(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        self.inode
+            .find(name)
+            .map(|inode| efs_inode(inode) as Arc<dyn Inode>)
+    }
+    fn create(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        self.inode
+            .create(name)
+            .map(|inode| efs_inode(inode) as Arc<dyn Inode>)
+    }
+    fn mkdir(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        self.inode
+            .mkdir(name)
+            .map(|inode| efs_inode(inode) as Arc<dyn Inode>)
+    }
+    fn symlink(&self, name: &str, target: &str) -> Option<Arc<dyn Inode>> {
+        self.inode
+            .symlink(name, target)
+            .map(|inode| efs_inode(inode) as Arc<dyn Inode>)
+    }
+    fn mkfifo(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        self.inode
+            .mkfifo(name)
+            .map(|inode| efs_inode(inode) as Arc<dyn Inode>)
+    }
+    fn mksock(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        self.inode
+            .mksock(name)
+            .map(|inode| efs_inode(inode) as Arc<dyn Inode>)
+    }
+    fn readlink(&self) -> Option<String> {
+        if self.type_ != DiskInodeType::Symlink {
+            return None;
+        }
+        let mut target = vec![0u8; self.inode.size()];
+        let len = self.inode.read_at(0, &mut target);
+        target.truncate(len);
+        String::from_utf8(target).ok()
+    }
+    fn rmdir(&self, name: &str) -> bool {
+        self.inode.rmdir(name)
+    }
+    fn unlink(&self, name: &str) -> bool {
+        self.inode.unlink(name)
+    }
+    fn link(&self, name: &str, inode: &Arc<dyn Inode>) -> bool {
+        match inode.as_any().downcast_ref::<EfsInode>() {
+            Some(inode) => self.inode.link(name, &inode.inode),
+            None => false,
+        }
+    }
+    fn rename(&self, old_name: &str, new_dir: &Arc<dyn Inode>, new_name: &str) -> bool {
+        match new_dir.as_any().downcast_ref::<EfsInode>() {
+            Some(new_dir) => self.inode.rename(old_name, &new_dir.inode, new_name),
+            None => false,
+        }
+    }
+    fn ls(&self) -> Vec<String> {
+        self.inode.ls()
+    }
+    /// Offsets are the slots of the entries.
+    fn read_dir(&self, offset: usize) -> Option<(DirEntry, usize)> {
+        let (slot, name, inode_id, type_) = self.inode.read_dir(offset)?;
+        Some((
+            DirEntry::new(inode_id as u64, file_type(type_), name),
+            slot + 1,
+        ))
+    }
+    fn chmod(&self, mode: u32) -> bool {
+        self.inode.chmod(mode);
+        true
+    }
+    fn chown(&self, uid: u32, gid: u32) -> bool {
+        self.inode.chown(uid, gid);
+        true
+    }
+    fn clear(&self) {
+        if let Some(cache) = &self.cache {
+            cache.clear();
+        }
+        self.inode.clear()
+    }
+    fn position(&self) -> Option<(usize, usize)> {
+        Some(self.inode.position())
+    }
+    fn cached_page(&self, index: usize) -> Option<FrameTracker> {
+        self.cache.as_ref()?.page(index, self.inode.as_ref())
+    }
+}
+
+lazy_static! {
+    /// The name the disk of the root file system is mounted by.
+    static ref ROOT_DEVICE: &'static str = match cmdline::option("root") {
+        // the disk is there to be mounted all the same
+        None | Some("initramfs") => DEFAULT_ROOT,
+        Some(name) => disk_name(name).unwrap_or_else(|| {
+            warn!("root={} is no disk, {} is the root", name, DEFAULT_ROOT);
+            DEFAULT_ROOT
+        }),
+    };
+    /// None without the disk.
+    pub static ref ROOT_FS: Option<Arc<EasyFs>> = block_device(*ROOT_DEVICE).map(|device| {
+        let efs = EasyFileSystem::open(device);
+        let report = efs.lock().check(true);
+        for problem in report.problems.iter() {
+            warn!("fsck: {}, repaired", problem);
+        }
+        Arc::new(EasyFs {
+            root: efs_inode(Arc::new(EasyFileSystem::root_inode(&efs))),
+        })
+    });
+}
+
+/// Mounting the root device again shows the same file system once more,
+/// lest two of them allocate blocks behind each other's back.
+fn mount(source: &str) -> Option<Arc<dyn FileSystem>> {
+    if source != *ROOT_DEVICE {
+        return None;
+    }
+    ROOT_FS.clone().map(|fs| fs as Arc<dyn FileSystem>)
+}
+
+pub fn init() {
+    super::register_filesystem("easyfs", mount);
+}
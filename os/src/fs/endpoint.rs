@@ -0,0 +1,143 @@
+//! Endpoints for synchronous calls between threads, as in a microkernel:
+//! a caller sends a small message, and maybe a file, and waits for the
+//! reply of whichever server thread receives it, which replies and waits
+//! for the next call at once.
+//!
+//! Each hand-over is also one of the processor: the thread woken runs
+//! next rather than after those ready before it, so that a call and its
+//! reply cost two switches and no wait in the ready queue.
+
+use super::{File, Stat};
+use crate::mm::UserBuffer;
+use crate::sync::UPIntrFreeCell;
+use crate::task::{block_current_and_run_next, current_task, wakeup_task_next, TaskControlBlock};
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+
+/// The most bytes of a message.
+pub const IPC_MSG_SIZE: usize = 64;
+
+/// What a call or a reply carries.
+pub struct IpcMessage {
+    pub data: Vec<u8>,
+    /// passed along as with SCM_RIGHTS
+    pub file: Option<Arc<dyn File + Send + Sync>>,
+}
+
+/// A call, from the time it is made until it is replied to.
+struct Call {
+    caller: Arc<TaskControlBlock>,
+    request: UPIntrFreeCell<Option<IpcMessage>>,
+    reply: UPIntrFreeCell<Option<IpcMessage>>,
+}
+
+pub struct Endpoint {
+    inner: UPIntrFreeCell<EndpointInner>,
+}
+
+struct EndpointInner {
+    /// calls no server received yet
+    calls: VecDeque<Arc<Call>>,
+    /// servers waiting for a call
+    servers: VecDeque<Arc<TaskControlBlock>>,
+    /// the call each server received last and has not replied to, by the
+    /// address of its task
+    serving: BTreeMap<usize, Arc<Call>>,
+}
+
+fn task_key(task: &Arc<TaskControlBlock>) -> usize {
+    Arc::as_ptr(task) as usize
+}
+
+impl Endpoint {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            inner: unsafe {
+                UPIntrFreeCell::new(EndpointInner {
+                    calls: VecDeque::new(),
+                    servers: VecDeque::new(),
+                    serving: BTreeMap::new(),
+                })
+            },
+        })
+    }
+
+    /// Send `request` to a server, handing the processor to one waiting,
+    /// and wait for the reply.
+    pub fn call(&self, request: IpcMessage) -> IpcMessage {
+        let call = Arc::new(Call {
+            caller: current_task().unwrap(),
+            request: unsafe { UPIntrFreeCell::new(Some(request)) },
+            reply: unsafe { UPIntrFreeCell::new(None) },
+        });
+        let mut inner = self.inner.exclusive_access();
+        inner.calls.push_back(call.clone());
+        let server = inner.servers.pop_front();
+        drop(inner);
+        if let Some(server) = server {
+            wakeup_task_next(server);
+        }
+        // only the reply wakes us
+        block_current_and_run_next();
+        let reply = call.reply.exclusive_access().take();
+        reply.unwrap()
+    }
+
+    /// Reply with `reply` to the call the current thread received last,
+    /// the caller to run next; false if it has none to reply to.
+    pub fn reply(&self, reply: IpcMessage) -> bool {
+        let key = task_key(&current_task().unwrap());
+        let call = match self.inner.exclusive_access().serving.remove(&key) {
+            Some(call) => call,
+            None => return false,
+        };
+        *call.reply.exclusive_access() = Some(reply);
+        wakeup_task_next(call.caller.clone());
+        true
+    }
+
+    /// Wait for a call and take its request, to be replied to with `reply`.
+    pub fn receive(&self) -> IpcMessage {
+        let task = current_task().unwrap();
+        loop {
+            let mut inner = self.inner.exclusive_access();
+            if let Some(call) = inner.calls.pop_front() {
+                inner.serving.insert(task_key(&task), call.clone());
+                drop(inner);
+                let request = call.request.exclusive_access().take();
+                return request.unwrap();
+            }
+            inner.servers.push_back(task.clone());
+            drop(inner);
+            // another server may take the call we are woken for
+            block_current_and_run_next();
+        }
+    }
+}
+
+impl File for Endpoint {
+    fn readable(&self) -> bool {
+        true
+    }
+    fn writable(&self) -> bool {
+        true
+    }
+    fn read(&self, _buf: UserBuffer) -> usize {
+        0
+    }
+    fn write(&self, _buf: UserBuffer) -> usize {
+        0
+    }
+    /// Whether a call waits for a server.
+    fn read_ready(&self) -> bool {
+        !self.inner.exclusive_access().calls.is_empty()
+    }
+    fn stat(&self) -> Stat {
+        Stat::new(0o600)
+    }
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
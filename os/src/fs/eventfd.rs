@@ -0,0 +1,127 @@
+//! eventfd, a counter behind a file descriptor for threads and processes
+//! to wake each other by: a write adds to it, and a read takes it all, or
+//! one with EFD_SEMAPHORE, waiting while it is 0.
+
+use super::{File, OpenFlags, Stat};
+use crate::mm::UserBuffer;
+use crate::sync::UPIntrFreeCell;
+use crate::task::{signal_pending, suspend_current_and_run_next};
+use alloc::sync::Arc;
+
+/// The most the counter may hold.
+const MAX_COUNT: u64 = u64::MAX - 1;
+
+pub struct EventFd {
+    /// reads take 1, not all
+    semaphore: bool,
+    inner: UPIntrFreeCell<EventFdInner>,
+}
+
+struct EventFdInner {
+    count: u64,
+    /// O_NONBLOCK, if set
+    status: OpenFlags,
+}
+
+impl EventFd {
+    pub fn new(count: u64, semaphore: bool, status: OpenFlags) -> Arc<Self> {
+        Arc::new(Self {
+            semaphore,
+            inner: unsafe {
+                UPIntrFreeCell::new(EventFdInner {
+                    count,
+                    status: status & OpenFlags::NONBLOCK,
+                })
+            },
+        })
+    }
+}
+
+/// The 8 bytes of a value in `buf`, None if it is shorter.
+fn read_value(buf: &UserBuffer) -> Option<u64> {
+    let mut bytes = [0u8; 8];
+    let mut len = 0;
+    for slice in buf.buffers.iter() {
+        let part = slice.len().min(8 - len);
+        bytes[len..len + part].copy_from_slice(&slice[..part]);
+        len += part;
+    }
+    (len == 8).then(|| u64::from_ne_bytes(bytes))
+}
+
+/// Put `value` in the first 8 bytes of `buf`, which has room for it.
+pub(super) fn write_value(buf: &mut UserBuffer, value: u64) {
+    let bytes = value.to_ne_bytes();
+    let mut len = 0;
+    for slice in buf.buffers.iter_mut() {
+        let part = slice.len().min(8 - len);
+        slice[..part].copy_from_slice(&bytes[len..len + part]);
+        len += part;
+    }
+}
+
+impl File for EventFd {
+    fn readable(&self) -> bool {
+        true
+    }
+    fn writable(&self) -> bool {
+        true
+    }
+    /// Take the count, 0 if `buf` has no room for it.
+    fn read(&self, mut buf: UserBuffer) -> usize {
+        if buf.len() < 8 {
+            return 0;
+        }
+        let value = loop {
+            let mut inner = self.inner.exclusive_access();
+            if inner.count > 0 {
+                let value = if self.semaphore { 1 } else { inner.count };
+                inner.count -= value;
+                break value;
+            }
+            // sys_read has turned away those who may not wait
+            drop(inner);
+            if signal_pending() {
+                return 0;
+            }
+            suspend_current_and_run_next();
+        };
+        write_value(&mut buf, value);
+        8
+    }
+    /// Add to the count, waiting for room for it unless O_NONBLOCK; 0 for
+    /// less than 8 bytes, u64::MAX, or no room with O_NONBLOCK.
+    fn write(&self, buf: UserBuffer) -> usize {
+        let value = match read_value(&buf) {
+            Some(value) if value != u64::MAX => value,
+            _ => return 0,
+        };
+        loop {
+            let mut inner = self.inner.exclusive_access();
+            if inner.count <= MAX_COUNT - value {
+                inner.count += value;
+                return 8;
+            }
+            if inner.status.contains(OpenFlags::NONBLOCK) || signal_pending() {
+                return 0;
+            }
+            drop(inner);
+            suspend_current_and_run_next();
+        }
+    }
+    fn status(&self) -> OpenFlags {
+        self.inner.exclusive_access().status
+    }
+    fn set_status(&self, status: OpenFlags) {
+        self.inner.exclusive_access().status = status & OpenFlags::NONBLOCK;
+    }
+    fn read_ready(&self) -> bool {
+        self.inner.exclusive_access().count > 0
+    }
+    fn write_ready(&self) -> bool {
+        self.inner.exclusive_access().count < MAX_COUNT
+    }
+    fn stat(&self) -> Stat {
+        Stat::new(0o600)
+    }
+}
@@ -0,0 +1,342 @@
+//! The on-disk structures of FAT32: the boot sector, FAT entries and the
+//! 32-byte directory entries, short and long.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use easy_fs::BLOCK_SZ;
+
+/// FAT entries are 28 bits; the upper 4 are reserved.
+pub const FAT_ENTRY_MASK: u32 = 0x0fff_ffff;
+/// Entries from here on end a cluster chain.
+pub const FAT_EOC: u32 = 0x0fff_fff8;
+/// What an allocated cluster at the end of its chain is marked with.
+pub const FAT_EOC_MARK: u32 = 0x0fff_ffff;
+
+pub const ATTR_READ_ONLY: u8 = 0x01;
+pub const ATTR_VOLUME_ID: u8 = 0x08;
+pub const ATTR_DIRECTORY: u8 = 0x10;
+pub const ATTR_ARCHIVE: u8 = 0x20;
+/// Read-only, hidden, system and volume ID at once mark a long name entry.
+pub const ATTR_LONG_NAME: u8 = 0x0f;
+
+pub const DIRENT_SZ: usize = 32;
+/// The first byte of a free entry, and of the free entries after the last.
+pub const SLOT_FREE: u8 = 0xe5;
+pub const SLOT_END: u8 = 0x00;
+/// The entry holding the last part of a long name has this in its order.
+const LAST_LONG_ENTRY: u8 = 0x40;
+/// UTF-16 units of a long name in each entry, and where they are.
+const LONG_NAME_UNITS: usize = 13;
+const LONG_NAME_OFFSETS: [usize; LONG_NAME_UNITS] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+pub const LONG_NAME_MAX: usize = 255;
+/// The short name is all lower case in its base or extension.
+const NT_LOWER_BASE: u8 = 0x08;
+const NT_LOWER_EXT: u8 = 0x10;
+/// 1980-01-01, the earliest FAT date, until there is a real-time clock.
+const FAT_EPOCH_DATE: u16 = (1 << 5) | 1;
+
+fn le16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn le32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+/// Where things are on a FAT32 volume, in sectors of BLOCK_SZ bytes.
+pub struct Geometry {
+    pub sectors_per_cluster: usize,
+    /// the first FAT, the others following it
+    pub fat_start: usize,
+    pub fat_sectors: usize,
+    pub fats: usize,
+    /// cluster 2, the first one
+    pub data_start: usize,
+    /// number of clusters, numbered from 2
+    pub clusters: u32,
+    pub root_cluster: u32,
+    pub fs_info: usize,
+}
+
+impl Geometry {
+    /// Parse the boot sector, or None if it is not one of FAT32 with
+    /// sectors of BLOCK_SZ bytes.
+    pub fn parse(boot: &[u8; BLOCK_SZ]) -> Option<Self> {
+        let bytes_per_sector = le16(boot, 11) as usize;
+        let sectors_per_cluster = boot[13] as usize;
+        let reserved = le16(boot, 14) as usize;
+        let fats = boot[16] as usize;
+        let root_entries = le16(boot, 17);
+        let fat_size_16 = le16(boot, 22);
+        let total_sectors = le32(boot, 32) as usize;
+        let fat_sectors = le32(boot, 36) as usize;
+        if boot[510..512] != [0x55, 0xaa]
+            || bytes_per_sector != BLOCK_SZ
+            || !sectors_per_cluster.is_power_of_two()
+            || fats == 0
+            || root_entries != 0
+            || fat_size_16 != 0
+            || fat_sectors == 0
+        {
+            return None;
+        }
+        let data_start = reserved + fats * fat_sectors;
+        let clusters = (total_sectors.checked_sub(data_start)? / sectors_per_cluster) as u32;
+        // all the clusters have an entry in the FAT
+        if (clusters as usize + 2) * 4 > fat_sectors * BLOCK_SZ {
+            return None;
+        }
+        Some(Self {
+            sectors_per_cluster,
+            fat_start: reserved,
+            fat_sectors,
+            fats,
+            data_start,
+            clusters,
+            root_cluster: le32(boot, 44),
+            fs_info: le16(boot, 48) as usize,
+        })
+    }
+
+    pub fn cluster_size(&self) -> usize {
+        self.sectors_per_cluster * BLOCK_SZ
+    }
+
+    /// The first sector of `cluster`.
+    pub fn cluster_sector(&self, cluster: u32) -> usize {
+        self.data_start + (cluster as usize - 2) * self.sectors_per_cluster
+    }
+
+    /// Whether `cluster` may be in a chain.
+    pub fn is_valid(&self, cluster: u32) -> bool {
+        (2..self.clusters + 2).contains(&cluster)
+    }
+}
+
+/// The FSInfo sector keeps a free cluster count, which is only a hint.
+pub const FSI_FREE_COUNT: usize = 488;
+pub const FSI_UNKNOWN: u32 = 0xffff_ffff;
+
+/// A short directory entry, the one with the attributes, the first cluster
+/// and the size of a file.
+#[derive(Clone, Copy)]
+pub struct ShortEntry {
+    pub name: [u8; 11],
+    pub attr: u8,
+    pub nt_res: u8,
+    pub first_cluster: u32,
+    pub size: u32,
+}
+
+impl ShortEntry {
+    pub fn new(name: [u8; 11], attr: u8, first_cluster: u32) -> Self {
+        Self {
+            name,
+            attr,
+            nt_res: 0,
+            first_cluster,
+            size: 0,
+        }
+    }
+
+    pub fn parse(raw: &[u8; DIRENT_SZ]) -> Self {
+        Self {
+            name: raw[..11].try_into().unwrap(),
+            attr: raw[11],
+            nt_res: raw[12],
+            first_cluster: (le16(raw, 20) as u32) << 16 | le16(raw, 26) as u32,
+            size: le32(raw, 28),
+        }
+    }
+
+    pub fn to_bytes(self) -> [u8; DIRENT_SZ] {
+        let mut raw = [0u8; DIRENT_SZ];
+        raw[..11].copy_from_slice(&self.name);
+        raw[11] = self.attr;
+        raw[12] = self.nt_res;
+        for date in [16, 18, 24] {
+            raw[date..date + 2].copy_from_slice(&FAT_EPOCH_DATE.to_le_bytes());
+        }
+        Self::set_cluster_and_size(&mut raw, self.first_cluster, self.size);
+        raw
+    }
+
+    /// Change the first cluster and the size of the entry `raw`, leaving
+    /// its times alone.
+    pub fn set_cluster_and_size(raw: &mut [u8; DIRENT_SZ], first_cluster: u32, size: u32) {
+        raw[20..22].copy_from_slice(&((first_cluster >> 16) as u16).to_le_bytes());
+        raw[26..28].copy_from_slice(&(first_cluster as u16).to_le_bytes());
+        raw[28..32].copy_from_slice(&size.to_le_bytes());
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.attr & ATTR_DIRECTORY != 0
+    }
+
+    /// "NAME.EXT", in lower case where the entry says so.
+    pub fn display_name(&self) -> String {
+        let mut name = self.name;
+        // a name starting with 0xe5 is stored with 0x05
+        if name[0] == 0x05 {
+            name[0] = SLOT_FREE;
+        }
+        let part = |bytes: &[u8], lower: bool| -> String {
+            bytes
+                .iter()
+                .take_while(|b| **b != b' ')
+                .map(|b| if lower { b.to_ascii_lowercase() } else { *b } as char)
+                .collect()
+        };
+        let mut display = part(&name[..8], self.nt_res & NT_LOWER_BASE != 0);
+        let ext = part(&name[8..], self.nt_res & NT_LOWER_EXT != 0);
+        if !ext.is_empty() {
+            display.push('.');
+            display.push_str(&ext);
+        }
+        display
+    }
+
+    /// The checksum of the short name the long name entries carry.
+    pub fn checksum(&self) -> u8 {
+        self.name
+            .iter()
+            .fold(0u8, |sum, b| (sum >> 1 | sum << 7).wrapping_add(*b))
+    }
+}
+
+fn valid_short_char(c: u8) -> bool {
+    c.is_ascii_uppercase() || c.is_ascii_digit() || b"!#$%&'()-@^_`{}~".contains(&c)
+}
+
+/// `name` as a short name if it is a valid one as it is, in upper case and
+/// without a long name needed.
+pub fn exact_short_name(name: &str) -> Option<[u8; 11]> {
+    let (base, ext) = match name.rsplit_once('.') {
+        Some((base, ext)) => (base, ext),
+        None => (name, ""),
+    };
+    if base.is_empty()
+        || base.len() > 8
+        || ext.len() > 3
+        || !base.bytes().chain(ext.bytes()).all(valid_short_char)
+    {
+        return None;
+    }
+    let mut short = [b' '; 11];
+    short[..base.len()].copy_from_slice(base.as_bytes());
+    short[8..8 + ext.len()].copy_from_slice(ext.as_bytes());
+    Some(short)
+}
+
+/// The `n`th short name made up for the long `name`, like "LONGNA~1.TXT".
+pub fn numbered_short_name(name: &str, n: usize) -> [u8; 11] {
+    let squash = |part: &str| -> Vec<u8> {
+        part.bytes()
+            .filter(|b| *b != b' ' && *b != b'.')
+            .map(|b| {
+                let b = b.to_ascii_uppercase();
+                if valid_short_char(b) {
+                    b
+                } else {
+                    b'_'
+                }
+            })
+            .collect()
+    };
+    let (base, ext) = match name.rsplit_once('.') {
+        Some((base, ext)) if !base.is_empty() => (squash(base), squash(ext)),
+        _ => (squash(name), Vec::new()),
+    };
+    let tail = alloc::format!("~{}", n);
+    let base_len = base.len().min(8 - tail.len());
+    let mut short = [b' '; 11];
+    short[..base_len].copy_from_slice(&base[..base_len]);
+    short[base_len..base_len + tail.len()].copy_from_slice(tail.as_bytes());
+    let ext_len = ext.len().min(3);
+    short[8..8 + ext_len].copy_from_slice(&ext[..ext_len]);
+    short
+}
+
+/// The long name entries for `name`, in the order they go on the disk,
+/// which is the last part first.
+pub fn long_name_entries(name: &str, checksum: u8) -> Vec<[u8; DIRENT_SZ]> {
+    let units: Vec<u16> = name.encode_utf16().collect();
+    let count = (units.len() + LONG_NAME_UNITS - 1) / LONG_NAME_UNITS;
+    (0..count)
+        .rev()
+        .map(|i| {
+            let mut raw = [0u8; DIRENT_SZ];
+            raw[0] = (i + 1) as u8;
+            if i == count - 1 {
+                raw[0] |= LAST_LONG_ENTRY;
+            }
+            raw[11] = ATTR_LONG_NAME;
+            raw[13] = checksum;
+            for (j, offset) in LONG_NAME_OFFSETS.iter().enumerate() {
+                let index = i * LONG_NAME_UNITS + j;
+                // the name ends with a 0 if it does not fill the entry, and
+                // the rest is padded with 0xffff
+                let unit = match units.get(index) {
+                    Some(unit) => *unit,
+                    None if index == units.len() => 0,
+                    None => 0xffff,
+                };
+                raw[*offset..*offset + 2].copy_from_slice(&unit.to_le_bytes());
+            }
+            raw
+        })
+        .collect()
+}
+
+/// The parts of a long name, gathered from its entries as a directory is
+/// read, in the order of the disk.
+#[derive(Default)]
+pub struct LongName {
+    /// the part of each entry, by its order
+    parts: Vec<(u8, [u16; LONG_NAME_UNITS])>,
+    checksum: u8,
+    /// offset of the first entry in the directory
+    pub start: usize,
+}
+
+impl LongName {
+    /// Take in the long name entry `raw` at `offset`.
+    pub fn push(&mut self, raw: &[u8; DIRENT_SZ], offset: usize) {
+        if raw[0] & LAST_LONG_ENTRY != 0 {
+            self.parts.clear();
+            self.checksum = raw[13];
+            self.start = offset;
+        }
+        let mut units = [0u16; LONG_NAME_UNITS];
+        for (unit, offset) in units.iter_mut().zip(LONG_NAME_OFFSETS.iter()) {
+            *unit = le16(raw, *offset);
+        }
+        self.parts.push((raw[0] & !LAST_LONG_ENTRY, units));
+    }
+
+    pub fn clear(&mut self) {
+        self.parts.clear();
+    }
+
+    /// The name the entries make up for the short entry with `checksum`,
+    /// if they are all there.
+    pub fn take(&mut self, checksum: u8) -> Option<String> {
+        let parts = core::mem::take(&mut self.parts);
+        let complete = !parts.is_empty()
+            && self.checksum == checksum
+            && parts
+                .iter()
+                .enumerate()
+                .all(|(i, (order, _))| *order as usize == parts.len() - i);
+        if !complete {
+            return None;
+        }
+        let units: Vec<u16> = parts
+            .iter()
+            .rev()
+            .flat_map(|(_, units)| units.iter().copied())
+            .take_while(|unit| *unit != 0)
+            .collect();
+        Some(String::from_utf16_lossy(&units))
+    }
+}
@@ -0,0 +1,783 @@
+//! FAT32, to exchange files with the host on the second disk, mounted with
+//! e.g. `mount("/dev/vdb", "/mnt", "vfat")`.
+//!
+//! Long names are read and written, and short names are made up for them.
+//! Files and directories are read and written through the block cache of
+//! easy-fs, sector by sector. There are no hard links, owners or times:
+//! everything written is dated 1980-01-01.
+//!
+//! A file is known by its directory entry: the first cluster of the
+//! directory and the offset of the short entry in it. There is one
+//! `FatInode` for each entry in use, so that its users agree on the size
+//! and the first cluster, which change as the file is written. Operations
+//! on a volume take its lock, which sleeps, as the disk may make them wait.
+
+mod layout;
+
+use super::stat::{Stat, S_IFDIR, S_IFREG};
+use super::vfs::{DirEntry, FileSystem, Inode};
+use crate::drivers::block_device;
+use crate::objects::{Live, OBJ_INODE};
+use crate::sync::{Mutex, MutexBlocking, UPIntrFreeCell};
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::any::Any;
+use easy_fs::{block_cache_sync_all, get_block_cache, BlockDevice, BLOCK_SZ};
+use layout::*;
+use lazy_static::*;
+
+/// Inode number of the root directory, which has no entry.
+const ROOT_INO: u64 = 1;
+/// The signature at the start of the FSInfo sector.
+const FSI_LEAD_SIG: u32 = 0x4161_5252;
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && !name.contains('/')
+        && name.encode_utf16().count() <= LONG_NAME_MAX
+}
+
+/// A directory entry as read.
+struct Entry {
+    name: String,
+    short: ShortEntry,
+    /// offset of the first of its long name entries, or of the short entry
+    start: usize,
+    /// offset of the short entry
+    offset: usize,
+}
+
+pub struct FatVolume {
+    device: Arc<dyn BlockDevice>,
+    geo: Geometry,
+    lock: MutexBlocking,
+    /// where to start looking for a free cluster
+    next_free: UPIntrFreeCell<u32>,
+    /// the `FatInode`s in use, by directory and offset of the entry
+    inodes: UPIntrFreeCell<BTreeMap<(u32, usize), Weak<FatInode>>>,
+}
+
+impl FatVolume {
+    fn locked<T>(&self, f: impl FnOnce() -> T) -> T {
+        self.lock.lock();
+        let ret = f();
+        self.lock.unlock();
+        ret
+    }
+
+    fn read_sector<V>(&self, sector: usize, f: impl FnOnce(&[u8; BLOCK_SZ]) -> V) -> V {
+        get_block_cache(sector, Arc::clone(&self.device))
+            .lock()
+            .read(0, f)
+    }
+
+    fn modify_sector<V>(&self, sector: usize, f: impl FnOnce(&mut [u8; BLOCK_SZ]) -> V) -> V {
+        get_block_cache(sector, Arc::clone(&self.device))
+            .lock()
+            .modify(0, f)
+    }
+
+    /// The sector of the entry of `cluster` in the first FAT, and its
+    /// offset there.
+    fn fat_position(&self, cluster: u32) -> (usize, usize) {
+        let offset = cluster as usize * 4;
+        (self.geo.fat_start + offset / BLOCK_SZ, offset % BLOCK_SZ)
+    }
+
+    fn fat_entry(&self, cluster: u32) -> u32 {
+        let (sector, offset) = self.fat_position(cluster);
+        self.read_sector(sector, |data| {
+            u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) & FAT_ENTRY_MASK
+        })
+    }
+
+    /// Set the entry of `cluster` in every FAT.
+    fn set_fat_entry(&self, cluster: u32, value: u32) {
+        let (sector, offset) = self.fat_position(cluster);
+        for fat in 0..self.geo.fats {
+            self.modify_sector(sector + fat * self.geo.fat_sectors, |data| {
+                let old = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+                let new = (old & !FAT_ENTRY_MASK) | value;
+                data[offset..offset + 4].copy_from_slice(&new.to_le_bytes());
+            });
+        }
+    }
+
+    fn next_cluster(&self, cluster: u32) -> Option<u32> {
+        let next = self.fat_entry(cluster);
+        (next < FAT_EOC && self.geo.is_valid(next)).then_some(next)
+    }
+
+    /// Cluster `n` of the chain starting at `first`.
+    fn nth_cluster(&self, first: u32, n: usize) -> Option<u32> {
+        if !self.geo.is_valid(first) {
+            return None;
+        }
+        let mut cluster = first;
+        for _ in 0..n {
+            cluster = self.next_cluster(cluster)?;
+        }
+        Some(cluster)
+    }
+
+    /// The last cluster of the chain starting at `first`, and the length of
+    /// the chain.
+    fn last_cluster(&self, first: u32) -> (u32, usize) {
+        let (mut cluster, mut len) = (first, 1);
+        while let Some(next) = self.next_cluster(cluster) {
+            cluster = next;
+            len += 1;
+        }
+        (cluster, len)
+    }
+
+    /// Allocate a zeroed cluster, at the end of the chain ending at `last`
+    /// if any.
+    fn alloc_cluster(&self, last: Option<u32>) -> Option<u32> {
+        let clusters = self.geo.clusters;
+        let start = *self.next_free.exclusive_access() - 2;
+        let cluster = (0..clusters)
+            .map(|i| 2 + (start + i) % clusters)
+            .find(|cluster| self.fat_entry(*cluster) == 0)?;
+        self.set_fat_entry(cluster, FAT_EOC_MARK);
+        if let Some(last) = last {
+            self.set_fat_entry(last, cluster);
+        }
+        *self.next_free.exclusive_access() = 2 + (cluster - 1) % clusters;
+        let sector = self.geo.cluster_sector(cluster);
+        for i in 0..self.geo.sectors_per_cluster {
+            self.modify_sector(sector + i, |data| data.fill(0));
+        }
+        Some(cluster)
+    }
+
+    /// Make the chain starting at `*first`, 0 if there is none yet, at
+    /// least `count` clusters long.
+    fn grow_chain(&self, first: &mut u32, count: usize) -> bool {
+        if count == 0 {
+            return true;
+        }
+        if *first == 0 {
+            *first = match self.alloc_cluster(None) {
+                Some(cluster) => cluster,
+                None => return false,
+            };
+        }
+        let (mut last, mut len) = self.last_cluster(*first);
+        while len < count {
+            last = match self.alloc_cluster(Some(last)) {
+                Some(cluster) => cluster,
+                None => return false,
+            };
+            len += 1;
+        }
+        true
+    }
+
+    fn free_chain(&self, first: u32) {
+        let mut cluster = first;
+        while self.geo.is_valid(cluster) {
+            let next = self.fat_entry(cluster);
+            self.set_fat_entry(cluster, 0);
+            if next >= FAT_EOC {
+                break;
+            }
+            cluster = next;
+        }
+    }
+
+    /// Go over the sectors of `[offset, offset + len)` of the chain starting
+    /// at `first`, calling `f` with each sector, the offset in it, the offset
+    /// from `offset` and the length. Return how far the chain went.
+    fn chain_io(
+        &self,
+        first: u32,
+        offset: usize,
+        len: usize,
+        mut f: impl FnMut(usize, usize, usize, usize),
+    ) -> usize {
+        let cluster_size = self.geo.cluster_size();
+        let mut cluster = match self.nth_cluster(first, offset / cluster_size) {
+            Some(cluster) => cluster,
+            None => return 0,
+        };
+        let end = offset + len;
+        let mut pos = offset;
+        while pos < end {
+            let in_sector = pos % BLOCK_SZ;
+            let len = (BLOCK_SZ - in_sector).min(end - pos);
+            let sector = self.geo.cluster_sector(cluster) + pos % cluster_size / BLOCK_SZ;
+            f(sector, in_sector, pos - offset, len);
+            pos += len;
+            if pos % cluster_size == 0 && pos < end {
+                cluster = match self.next_cluster(cluster) {
+                    Some(cluster) => cluster,
+                    None => break,
+                };
+            }
+        }
+        pos - offset
+    }
+
+    fn read_chain(&self, first: u32, offset: usize, buf: &mut [u8]) -> usize {
+        self.chain_io(first, offset, buf.len(), |sector, in_sector, pos, len| {
+            self.read_sector(sector, |data| {
+                buf[pos..pos + len].copy_from_slice(&data[in_sector..in_sector + len])
+            })
+        })
+    }
+
+    fn write_chain(&self, first: u32, offset: usize, buf: &[u8]) -> usize {
+        self.chain_io(first, offset, buf.len(), |sector, in_sector, pos, len| {
+            self.modify_sector(sector, |data| {
+                data[in_sector..in_sector + len].copy_from_slice(&buf[pos..pos + len])
+            })
+        })
+    }
+
+    fn read_slot(&self, dir: u32, offset: usize) -> Option<[u8; DIRENT_SZ]> {
+        let mut raw = [0u8; DIRENT_SZ];
+        (self.read_chain(dir, offset, &mut raw) == DIRENT_SZ).then_some(raw)
+    }
+
+    fn write_slot(&self, dir: u32, offset: usize, raw: &[u8; DIRENT_SZ]) {
+        self.write_chain(dir, offset, raw);
+    }
+
+    /// The entries of the directory at `dir`, without "." and "..".
+    fn entries(&self, dir: u32) -> Vec<Entry> {
+        let mut entries = Vec::new();
+        let mut long_name = LongName::default();
+        let mut offset = 0;
+        while let Some(raw) = self.read_slot(dir, offset) {
+            match raw[0] {
+                SLOT_END => break,
+                SLOT_FREE => long_name.clear(),
+                _ if raw[11] & 0x3f == ATTR_LONG_NAME => long_name.push(&raw, offset),
+                _ => {
+                    let short = ShortEntry::parse(&raw);
+                    let long = long_name.take(short.checksum());
+                    if short.attr & ATTR_VOLUME_ID == 0 && raw[0] != b'.' {
+                        let (name, start) = match long {
+                            Some(name) => (name, long_name.start),
+                            None => (short.display_name(), offset),
+                        };
+                        entries.push(Entry {
+                            name,
+                            short,
+                            start,
+                            offset,
+                        });
+                    }
+                }
+            }
+            offset += DIRENT_SZ;
+        }
+        entries
+    }
+
+    /// The entry `name` of the directory at `dir`, whatever its case.
+    fn lookup(&self, dir: u32, name: &str) -> Option<Entry> {
+        self.entries(dir)
+            .into_iter()
+            .find(|entry| entry.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Find `count` free entries in a row in the directory at `dir`, growing
+    /// it if need be, and return the offset of the first.
+    fn alloc_slots(&self, dir: u32, count: usize) -> Option<usize> {
+        let (mut start, mut run, mut offset) = (0, 0, 0);
+        loop {
+            let raw = match self.read_slot(dir, offset) {
+                Some(raw) => raw,
+                None => {
+                    let (last, _) = self.last_cluster(dir);
+                    self.alloc_cluster(Some(last))?;
+                    continue;
+                }
+            };
+            if raw[0] == SLOT_FREE || raw[0] == SLOT_END {
+                if run == 0 {
+                    start = offset;
+                }
+                run += 1;
+                if run == count {
+                    return Some(start);
+                }
+            } else {
+                run = 0;
+            }
+            offset += DIRENT_SZ;
+        }
+    }
+
+    /// Add the entry `name` to the directory at `dir`, with the short entry
+    /// `raw` but for its name. Return the offset of the short entry.
+    fn add_entry(&self, dir: u32, name: &str, mut raw: [u8; DIRENT_SZ]) -> Option<usize> {
+        let mut slots = Vec::new();
+        // lower case is kept in the long name
+        raw[12] = 0;
+        match exact_short_name(name) {
+            Some(short_name) => raw[..11].copy_from_slice(&short_name),
+            None => {
+                let taken: Vec<[u8; 11]> = self
+                    .entries(dir)
+                    .iter()
+                    .map(|entry| entry.short.name)
+                    .collect();
+                let short_name = (1..1_000_000)
+                    .map(|n| numbered_short_name(name, n))
+                    .find(|short_name| !taken.contains(short_name))?;
+                raw[..11].copy_from_slice(&short_name);
+                slots = long_name_entries(name, ShortEntry::parse(&raw).checksum());
+            }
+        }
+        slots.push(raw);
+        let start = self.alloc_slots(dir, slots.len())?;
+        for (i, slot) in slots.iter().enumerate() {
+            self.write_slot(dir, start + i * DIRENT_SZ, slot);
+        }
+        Some(start + (slots.len() - 1) * DIRENT_SZ)
+    }
+
+    /// Free the slots of `entry` of the directory at `dir`.
+    fn remove_slots(&self, dir: u32, entry: &Entry) {
+        for offset in (entry.start..=entry.offset).step_by(DIRENT_SZ) {
+            if let Some(mut raw) = self.read_slot(dir, offset) {
+                raw[0] = SLOT_FREE;
+                self.write_slot(dir, offset, &raw);
+            }
+        }
+    }
+
+    /// Remove `entry` of the directory at `dir` and free its clusters. An
+    /// inode of it still in use becomes an empty file of no directory.
+    fn remove_entry(&self, dir: u32, entry: &Entry) {
+        self.remove_slots(dir, entry);
+        self.free_chain(entry.short.first_cluster);
+        let inode = self
+            .inodes
+            .exclusive_access()
+            .remove(&(dir, entry.offset))
+            .and_then(|inode| inode.upgrade());
+        if let Some(inode) = inode {
+            let mut inner = inode.inner.exclusive_access();
+            inner.location = None;
+            inner.first_cluster = 0;
+            inner.size = 0;
+        }
+    }
+
+    /// The inode of the entry at `offset` of the directory at `dir`, shared
+    /// with its other users.
+    fn inode(self: &Arc<Self>, dir: u32, offset: usize, short: &ShortEntry) -> Arc<FatInode> {
+        let key = (dir, offset);
+        let mut inodes = self.inodes.exclusive_access();
+        if let Some(inode) = inodes.get(&key).and_then(|inode| inode.upgrade()) {
+            return inode;
+        }
+        let inode = FatInode::new(
+            Arc::clone(self),
+            short.is_dir(),
+            short.attr & ATTR_READ_ONLY != 0,
+            Some(key),
+            short.first_cluster,
+            short.size as usize,
+        );
+        inodes.insert(key, Arc::downgrade(&inode));
+        inode
+    }
+}
+
+pub struct FatInode {
+    fs: Arc<FatVolume>,
+    is_dir: bool,
+    read_only: bool,
+    _live: Live<OBJ_INODE>,
+    inner: UPIntrFreeCell<FatInodeInner>,
+}
+
+struct FatInodeInner {
+    /// the directory of the entry and the offset of its short entry, None
+    /// for the root and once the entry is removed
+    location: Option<(u32, usize)>,
+    /// 0 for a file without clusters
+    first_cluster: u32,
+    size: usize,
+}
+
+impl FatInode {
+    fn new(
+        fs: Arc<FatVolume>,
+        is_dir: bool,
+        read_only: bool,
+        location: Option<(u32, usize)>,
+        first_cluster: u32,
+        size: usize,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            fs,
+            is_dir,
+            read_only,
+            _live: Live::default(),
+            inner: unsafe {
+                UPIntrFreeCell::new(FatInodeInner {
+                    location,
+                    first_cluster,
+                    size,
+                })
+            },
+        })
+    }
+
+    fn first_cluster(&self) -> u32 {
+        self.inner.exclusive_access().first_cluster
+    }
+
+    /// Write the first cluster and the size back to the entry.
+    fn update_entry(&self) {
+        let inner = self.inner.exclusive_access();
+        let (location, first_cluster, size) = (inner.location, inner.first_cluster, inner.size);
+        drop(inner);
+        if let Some((dir, offset)) = location {
+            if let Some(mut raw) = self.fs.read_slot(dir, offset) {
+                ShortEntry::set_cluster_and_size(&mut raw, first_cluster, size as u32);
+                self.fs.write_slot(dir, offset, &raw);
+            }
+        }
+    }
+
+    /// The cluster ".." of a subdirectory points to, 0 for the root.
+    fn parent_cluster(&self) -> u32 {
+        let dir = self.first_cluster();
+        if dir == self.fs.geo.root_cluster {
+            0
+        } else {
+            dir
+        }
+    }
+}
+
+impl Inode for FatInode {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        if self.is_dir {
+            return 0;
+        }
+        self.fs.locked(|| {
+            let inner = self.inner.exclusive_access();
+            let (first_cluster, size) = (inner.first_cluster, inner.size);
+            drop(inner);
+            if offset >= size {
+                return 0;
+            }
+            let len = buf.len().min(size - offset);
+            self.fs.read_chain(first_cluster, offset, &mut buf[..len])
+        })
+    }
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        let end = offset + buf.len();
+        if self.is_dir || end > u32::MAX as usize {
+            return 0;
+        }
+        self.fs.locked(|| {
+            let mut first_cluster = self.first_cluster();
+            let cluster_size = self.fs.geo.cluster_size();
+            let grown = self
+                .fs
+                .grow_chain(&mut first_cluster, (end + cluster_size - 1) / cluster_size);
+            let written = if grown {
+                self.fs.write_chain(first_cluster, offset, buf)
+            } else {
+                0
+            };
+            let mut inner = self.inner.exclusive_access();
+            inner.first_cluster = first_cluster;
+            if written > 0 {
+                inner.size = inner.size.max(offset + written);
+            }
+            drop(inner);
+            self.update_entry();
+            written
+        })
+    }
+    fn size(&self) -> usize {
+        self.inner.exclusive_access().size
+    }
+    fn stat(&self) -> Stat {
+        let inner = self.inner.exclusive_access();
+        let mut stat = if self.is_dir {
+            let mut stat = Stat::new(S_IFDIR | 0o755);
+            stat.nlink = 2;
+            stat
+        } else if self.read_only {
+            Stat::new(S_IFREG | 0o444)
+        } else {
+            Stat::new(S_IFREG | 0o644)
+        };
+        stat.ino = match inner.location {
+            Some((dir, offset)) => (dir as u64) << 32 | (offset / DIRENT_SZ) as u64,
+            None => ROOT_INO,
+        };
+        let cluster_size = self.fs.geo.cluster_size();
+        stat.size = inner.size as i64;
+        stat.blksize = cluster_size as i32;
+        stat.blocks = ((inner.size + cluster_size - 1) / cluster_size * cluster_size / 512) as i64;
+        stat
+    }
+    fn find(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        if !self.is_dir {
+            return None;
+        }
+        self.fs.locked(|| {
+            let dir = self.first_cluster();
+            let entry = self.fs.lookup(dir, name)?;
+            Some(self.fs.inode(dir, entry.offset, &entry.short) as Arc<dyn Inode>)
+        })
+    }
+    fn create(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        if !self.is_dir || !valid_name(name) {
+            return None;
+        }
+        self.fs.locked(|| {
+            let dir = self.first_cluster();
+            if self.fs.lookup(dir, name).is_some() {
+                return None;
+            }
+            let short = ShortEntry::new([b' '; 11], ATTR_ARCHIVE, 0);
+            let offset = self.fs.add_entry(dir, name, short.to_bytes())?;
+            Some(self.fs.inode(dir, offset, &short) as Arc<dyn Inode>)
+        })
+    }
+    fn mkdir(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        if !self.is_dir || !valid_name(name) {
+            return None;
+        }
+        self.fs.locked(|| {
+            let dir = self.first_cluster();
+            if self.fs.lookup(dir, name).is_some() {
+                return None;
+            }
+            let cluster = self.fs.alloc_cluster(None)?;
+            let dot = ShortEntry::new(*b".          ", ATTR_DIRECTORY, cluster);
+            let dotdot = ShortEntry::new(*b"..         ", ATTR_DIRECTORY, self.parent_cluster());
+            self.fs.write_slot(cluster, 0, &dot.to_bytes());
+            self.fs.write_slot(cluster, DIRENT_SZ, &dotdot.to_bytes());
+            let short = ShortEntry::new([b' '; 11], ATTR_DIRECTORY, cluster);
+            match self.fs.add_entry(dir, name, short.to_bytes()) {
+                Some(offset) => Some(self.fs.inode(dir, offset, &short) as Arc<dyn Inode>),
+                None => {
+                    self.fs.free_chain(cluster);
+                    None
+                }
+            }
+        })
+    }
+    fn rmdir(&self, name: &str) -> bool {
+        self.fs.locked(|| {
+            let dir = self.first_cluster();
+            match self.fs.lookup(dir, name) {
+                Some(entry)
+                    if entry.short.is_dir()
+                        && self.fs.entries(entry.short.first_cluster).is_empty() =>
+                {
+                    self.fs.remove_entry(dir, &entry);
+                    true
+                }
+                _ => false,
+            }
+        })
+    }
+    fn unlink(&self, name: &str) -> bool {
+        self.fs.locked(|| {
+            let dir = self.first_cluster();
+            match self.fs.lookup(dir, name) {
+                Some(entry) if !entry.short.is_dir() => {
+                    self.fs.remove_entry(dir, &entry);
+                    true
+                }
+                _ => false,
+            }
+        })
+    }
+    /// Replace a file at `new_name`, as easy-fs does.
+    fn rename(&self, old_name: &str, new_dir: &Arc<dyn Inode>, new_name: &str) -> bool {
+        let new_dir = match new_dir.as_any().downcast_ref::<FatInode>() {
+            Some(new_dir) if Arc::ptr_eq(&new_dir.fs, &self.fs) && new_dir.is_dir => new_dir,
+            _ => return false,
+        };
+        if !valid_name(new_name) {
+            return false;
+        }
+        self.fs.locked(|| {
+            let (dir, new_dir_cluster) = (self.first_cluster(), new_dir.first_cluster());
+            let entry = match self.fs.lookup(dir, old_name) {
+                Some(entry) => entry,
+                None => return false,
+            };
+            if let Some(target) = self.fs.lookup(new_dir_cluster, new_name) {
+                // unless only the case of the name changes
+                if new_dir_cluster != dir || target.offset != entry.offset {
+                    if entry.short.is_dir() || target.short.is_dir() {
+                        return false;
+                    }
+                    self.fs.remove_entry(new_dir_cluster, &target);
+                }
+            }
+            let raw = self.fs.read_slot(dir, entry.offset).unwrap();
+            let offset = match self.fs.add_entry(new_dir_cluster, new_name, raw) {
+                Some(offset) => offset,
+                None => return false,
+            };
+            self.fs.remove_slots(dir, &entry);
+            let mut inodes = self.fs.inodes.exclusive_access();
+            if let Some(inode) = inodes
+                .remove(&(dir, entry.offset))
+                .and_then(|inode| inode.upgrade())
+            {
+                inode.inner.exclusive_access().location = Some((new_dir_cluster, offset));
+                inodes.insert((new_dir_cluster, offset), Arc::downgrade(&inode));
+            }
+            drop(inodes);
+            if entry.short.is_dir() && dir != new_dir_cluster {
+                let moved = entry.short.first_cluster;
+                if let Some(mut raw) = self.fs.read_slot(moved, DIRENT_SZ) {
+                    ShortEntry::set_cluster_and_size(&mut raw, new_dir.parent_cluster(), 0);
+                    self.fs.write_slot(moved, DIRENT_SZ, &raw);
+                }
+            }
+            true
+        })
+    }
+    fn ls(&self) -> Vec<String> {
+        if !self.is_dir {
+            return Vec::new();
+        }
+        self.fs.locked(|| {
+            self.fs
+                .entries(self.first_cluster())
+                .into_iter()
+                .map(|entry| entry.name)
+                .collect()
+        })
+    }
+    /// Offsets are those of the short entries, in entries; the inode
+    /// numbers are made of them as in `stat`.
+    fn read_dir(&self, offset: usize) -> Option<(DirEntry, usize)> {
+        if !self.is_dir {
+            return None;
+        }
+        let dir = self.first_cluster();
+        let entry = self.fs.locked(|| {
+            self.fs
+                .entries(dir)
+                .into_iter()
+                .find(|entry| entry.offset / DIRENT_SZ >= offset)
+        })?;
+        let index = entry.offset / DIRENT_SZ;
+        let mode = if entry.short.is_dir() {
+            S_IFDIR
+        } else {
+            S_IFREG
+        };
+        let ino = (dir as u64) << 32 | index as u64;
+        Some((DirEntry::new(ino, mode, entry.name), index + 1))
+    }
+    fn clear(&self) {
+        if self.is_dir {
+            return;
+        }
+        self.fs.locked(|| {
+            let mut inner = self.inner.exclusive_access();
+            let first_cluster = core::mem::take(&mut inner.first_cluster);
+            inner.size = 0;
+            drop(inner);
+            self.fs.free_chain(first_cluster);
+            self.update_entry();
+        })
+    }
+    fn sync(&self) {
+        block_cache_sync_all();
+    }
+}
+
+pub struct FatFs {
+    root: Arc<FatInode>,
+}
+
+impl FileSystem for FatFs {
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+    fn sync(&self) {
+        block_cache_sync_all();
+    }
+}
+
+lazy_static! {
+    /// The volumes mounted, by device, so that mounting one again shows the
+    /// same inodes.
+    static ref VOLUMES: UPIntrFreeCell<BTreeMap<String, Weak<FatVolume>>> =
+        unsafe { UPIntrFreeCell::new(BTreeMap::new()) };
+}
+
+fn open_volume(device: Arc<dyn BlockDevice>) -> Option<Arc<FatVolume>> {
+    let geo = get_block_cache(0, Arc::clone(&device))
+        .lock()
+        .read(0, Geometry::parse)?;
+    if !geo.is_valid(geo.root_cluster) {
+        return None;
+    }
+    let volume = Arc::new(FatVolume {
+        device,
+        next_free: unsafe { UPIntrFreeCell::new(2) },
+        inodes: unsafe { UPIntrFreeCell::new(BTreeMap::new()) },
+        lock: MutexBlocking::new(),
+        geo,
+    });
+    // the free cluster count goes out of date as clusters are allocated
+    if volume.geo.fs_info != 0 {
+        volume.modify_sector(volume.geo.fs_info, |data| {
+            if data[..4] == FSI_LEAD_SIG.to_le_bytes() {
+                data[FSI_FREE_COUNT..FSI_FREE_COUNT + 4]
+                    .copy_from_slice(&FSI_UNKNOWN.to_le_bytes());
+            }
+        });
+    }
+    Some(volume)
+}
+
+fn mount(source: &str) -> Option<Arc<dyn FileSystem>> {
+    let volume = VOLUMES
+        .exclusive_access()
+        .get(source)
+        .and_then(|volume| volume.upgrade());
+    let volume = match volume {
+        Some(volume) => volume,
+        None => {
+            // reading the boot sector may sleep
+            let volume = open_volume(block_device(source)?)?;
+            VOLUMES
+                .exclusive_access()
+                .insert(String::from(source), Arc::downgrade(&volume));
+            volume
+        }
+    };
+    let root_cluster = volume.geo.root_cluster;
+    Some(Arc::new(FatFs {
+        root: FatInode::new(volume, true, false, None, root_cluster, 0),
+    }))
+}
+
+pub fn init() {
+    super::register_filesystem("vfat", mount);
+}
@@ -0,0 +1,76 @@
+//! Named pipes: a FIFO in a file system is opened by its path, and all who
+//! open it share one pipe, so that processes which are not related can
+//! meet there.
+//!
+//! Opening a FIFO for reading waits for a writer, and for writing waits
+//! for a reader, unless both or O_NONBLOCK are asked for. With O_NONBLOCK
+//! a reader goes on alone, while a writer is refused. The pipe lives as
+//! long as any end of it is open; what was written and not read goes with
+//! the last end.
+
+use super::pipe::{Pipe, PipeRingBuffer};
+use super::{File, Inode, OpenFlags};
+use crate::sync::UPIntrFreeCell;
+use crate::task::{signal_pending, suspend_current_and_run_next};
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use lazy_static::*;
+
+lazy_static! {
+    /// The pipes of the FIFOs open, by the address of the inode.
+    static ref FIFOS: UPIntrFreeCell<BTreeMap<usize, Weak<UPIntrFreeCell<PipeRingBuffer>>>> =
+        unsafe { UPIntrFreeCell::new(BTreeMap::new()) };
+}
+
+/// The pipe of the FIFO `inode`, a new one if no end of it is open.
+fn fifo_buffer(inode: &Arc<dyn Inode>) -> Arc<UPIntrFreeCell<PipeRingBuffer>> {
+    let key = Arc::as_ptr(inode) as *const u8 as usize;
+    let mut fifos = FIFOS.exclusive_access();
+    fifos.retain(|_, buffer| buffer.strong_count() > 0);
+    if let Some(buffer) = fifos.get(&key).and_then(Weak::upgrade) {
+        return buffer;
+    }
+    let buffer = Arc::new(unsafe { UPIntrFreeCell::new(PipeRingBuffer::new()) });
+    fifos.insert(key, Arc::downgrade(&buffer));
+    buffer
+}
+
+/// Open an end of the FIFO `inode`, waiting for the other side as `flags`
+/// say; None for a writer with O_NONBLOCK and no reader, or if a signal
+/// ends the wait.
+pub fn open_fifo(
+    inode: Arc<dyn Inode>,
+    readable: bool,
+    writable: bool,
+    flags: OpenFlags,
+) -> Option<Arc<Pipe>> {
+    let buffer = fifo_buffer(&inode);
+    let nonblock = flags.contains(OpenFlags::NONBLOCK);
+    if writable && !readable && nonblock && buffer.exclusive_access().all_read_ends_closed() {
+        return None;
+    }
+    let pipe = Arc::new(Pipe::new(buffer.clone(), readable, writable, Some(inode)));
+    pipe.set_status(flags);
+    if nonblock || readable == writable {
+        return Some(pipe);
+    }
+    // the others there now, or ever opened
+    let others = |ring_buffer: &PipeRingBuffer| {
+        if readable {
+            (ring_buffer.writers, ring_buffer.writers_opened)
+        } else {
+            (ring_buffer.readers, ring_buffer.readers_opened)
+        }
+    };
+    let (_, opened) = others(&buffer.exclusive_access());
+    loop {
+        let (open_now, opened_now) = others(&buffer.exclusive_access());
+        if open_now > 0 || opened_now != opened {
+            return Some(pipe);
+        }
+        if signal_pending() {
+            return None;
+        }
+        suspend_current_and_run_next();
+    }
+}
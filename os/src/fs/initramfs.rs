@@ -0,0 +1,146 @@
+//! The initramfs: a cpio archive of the newc format, unpacked into a tmpfs
+//! which is the root file system if there is no disk, or `root=initramfs`
+//! is on the command line.
+//!
+//! The archive is the initrd the boot loader hands over, like QEMU's
+//! -initrd, or else the one built into the kernel from the `INITRAMFS`
+//! environment variable at compile time. Its directories, regular files and
+//! FIFOs are unpacked with their modes and owners; symbolic links and
+//! device nodes, which tmpfs has none of, are left out.
+
+use super::stat::{S_IFDIR, S_IFIFO, S_IFMT, S_IFREG};
+use super::tmpfs::new_tmpfs;
+use super::vfs::{FileSystem, Inode};
+use crate::cmdline;
+use crate::mm::phys_to_virt;
+use alloc::sync::Arc;
+
+/// The archive built in, empty without `INITRAMFS`.
+static BUILT_IN: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/initramfs.cpio"));
+
+const HEADER_SIZE: usize = 110;
+const TRAILER: &str = "TRAILER!!!";
+
+/// A member of the archive.
+struct Entry<'a> {
+    name: &'a str,
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    data: &'a [u8],
+}
+
+/// Field `index` of the header at the start of `header`, 8 hex digits.
+fn field(header: &[u8], index: usize) -> Option<u32> {
+    let digits = header.get(6 + index * 8..6 + (index + 1) * 8)?;
+    u32::from_str_radix(core::str::from_utf8(digits).ok()?, 16).ok()
+}
+
+/// The members of `archive` up to the trailer, or what there is of them
+/// before it breaks off.
+struct Members<'a> {
+    archive: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Iterator for Members<'a> {
+    type Item = Entry<'a>;
+
+    fn next(&mut self) -> Option<Entry<'a>> {
+        let header = self.archive.get(self.offset..self.offset + HEADER_SIZE)?;
+        // without or with checksums
+        if &header[..6] != b"070701" && &header[..6] != b"070702" {
+            warn!("initramfs: no cpio header at {:#x}", self.offset);
+            return None;
+        }
+        let (mode, uid, gid) = (field(header, 1)?, field(header, 2)?, field(header, 3)?);
+        let (file_size, name_size) = (field(header, 6)? as usize, field(header, 11)? as usize);
+        let name_start = self.offset + HEADER_SIZE;
+        let name = self.archive.get(name_start..name_start + name_size)?;
+        // the NUL ends it
+        let name = core::str::from_utf8(name.split(|byte| *byte == 0).next()?).ok()?;
+        let data_start = (name_start + name_size + 3) & !3;
+        let data = self.archive.get(data_start..data_start + file_size)?;
+        self.offset = (data_start + file_size + 3) & !3;
+        if name == TRAILER {
+            return None;
+        }
+        Some(Entry {
+            name,
+            mode,
+            uid,
+            gid,
+            data,
+        })
+    }
+}
+
+/// The directory `path` leads to from `root`, made as needed.
+fn make_dirs(root: &Arc<dyn Inode>, path: &str) -> Option<Arc<dyn Inode>> {
+    let mut dir = root.clone();
+    for name in path.split('/').filter(|name| !name.is_empty()) {
+        dir = match dir.find(name) {
+            Some(inode) => inode,
+            None => dir.mkdir(name)?,
+        };
+    }
+    Some(dir)
+}
+
+/// Unpack `entry` under `root`.
+fn unpack_entry(root: &Arc<dyn Inode>, entry: &Entry) -> Option<()> {
+    let path = entry.name.trim_start_matches("./").trim_start_matches('/');
+    if path.is_empty() || path == "." {
+        return Some(());
+    }
+    let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+    let dir = make_dirs(root, parent)?;
+    let inode = match entry.mode & S_IFMT {
+        S_IFDIR => match dir.find(name) {
+            Some(inode) => inode,
+            None => dir.mkdir(name)?,
+        },
+        S_IFREG => {
+            let inode = dir.create(name)?;
+            if inode.write_at(0, entry.data) != entry.data.len() {
+                return None;
+            }
+            inode
+        }
+        S_IFIFO => dir.mkfifo(name)?,
+        _ => {
+            warn!("initramfs: {} left out, tmpfs having no such files", path);
+            return Some(());
+        }
+    };
+    inode.chmod(entry.mode & 0o7777);
+    inode.chown(entry.uid, entry.gid);
+    Some(())
+}
+
+/// The archive to unpack: the initrd, or else the one built in.
+fn archive() -> Option<&'static [u8]> {
+    if let Some((start, end)) = cmdline::initrd() {
+        let initrd =
+            unsafe { core::slice::from_raw_parts(phys_to_virt(start) as *const u8, end - start) };
+        return Some(initrd);
+    }
+    (!BUILT_IN.is_empty()).then_some(BUILT_IN)
+}
+
+/// A tmpfs with the initramfs unpacked into it, None without one.
+pub fn unpack() -> Option<Arc<dyn FileSystem>> {
+    let archive = archive()?;
+    let fs = new_tmpfs();
+    let root = fs.root_inode();
+    let mut files = 0;
+    let members = Members { archive, offset: 0 };
+    for entry in members {
+        match unpack_entry(&root, &entry) {
+            Some(()) => files += 1,
+            None => warn!("initramfs: {} could not be unpacked", entry.name),
+        }
+    }
+    info!("initramfs: {} files from a {} KiB archive", files, archive.len() / 1024);
+    Some(fs)
+}
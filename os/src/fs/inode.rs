@@ -1,58 +1,85 @@
-use super::File;
-use crate::drivers::BLOCK_DEVICE;
+use super::fifo::open_fifo;
+use super::inotify::{notify, IN_MODIFY};
+use super::lock::release_file_locks;
+use super::stat::{S_IFMT, S_IFREG};
+use super::{
+    create, lookup, lookup_nofollow, may_access, File, Inode, SeekFrom, MAY_READ, MAY_WRITE,
+    S_IFIFO, S_IFSOCK,
+};
 use crate::mm::UserBuffer;
-use crate::sync::UPIntrFreeCell;
+use crate::objects::{Live, OBJ_FILE};
+use crate::sync::{Mutex, MutexBlocking, UPIntrFreeCell};
+use crate::task::preempt_point;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use bitflags::*;
-use easy_fs::{EasyFileSystem, Inode};
-use lazy_static::*;
 
 pub struct OSInode {
     readable: bool,
     writable: bool,
+    _live: Live<OBJ_FILE>,
+    /// held by a read, a write or a seek of a regular file or a directory
+    /// for the offset to move by whole calls, as f_pos_lock on Linux;
+    /// devices, whose reads may wait for ever, go without it
+    pos_lock: Option<MutexBlocking>,
     inner: UPIntrFreeCell<OSInodeInner>,
 }
 
 pub struct OSInodeInner {
     offset: usize,
-    inode: Arc<Inode>,
+    /// O_APPEND, for every write to go to the end, and O_NONBLOCK, which
+    /// does not make a difference for files on disk
+    status: OpenFlags,
+    inode: Arc<dyn Inode>,
 }
 
 impl OSInode {
-    pub fn new(readable: bool, writable: bool, inode: Arc<Inode>) -> Self {
+    pub fn new(readable: bool, writable: bool, status: OpenFlags, inode: Arc<dyn Inode>) -> Self {
+        let atomic_pos = inode.is_dir() || inode.stat().mode & S_IFMT == S_IFREG;
         Self {
             readable,
             writable,
-            inner: unsafe { UPIntrFreeCell::new(OSInodeInner { offset: 0, inode }) },
+            _live: Live::default(),
+            pos_lock: atomic_pos.then(MutexBlocking::new),
+            inner: unsafe {
+                UPIntrFreeCell::new(OSInodeInner {
+                    offset: 0,
+                    status: status & OpenFlags::STATUS,
+                    inode,
+                })
+            },
         }
     }
-    pub fn read_all(&self) -> Vec<u8> {
-        let mut inner = self.inner.exclusive_access();
-        let mut buffer = [0u8; 512];
-        let mut v: Vec<u8> = Vec::new();
-        loop {
-            let len = inner.inode.read_at(inner.offset, &mut buffer);
-            if len == 0 {
-                break;
-            }
-            inner.offset += len;
-            v.extend_from_slice(&buffer[..len]);
-        }
-        v
+    /// The inode and the offset, to read or write with the inner part
+    /// released, since the thread is parked while the disk reads. Other
+    /// threads may use the file meanwhile, but only wait to move the offset
+    /// if called from within `positioned`.
+    fn position(&self) -> (Arc<dyn Inode>, usize) {
+        let inner = self.inner.exclusive_access();
+        (Arc::clone(&inner.inode), inner.offset)
+    }
+    /// Run `f` holding the position lock, if the file has one.
+    fn positioned<T>(&self, f: impl FnOnce() -> T) -> T {
+        let lock = match &self.pos_lock {
+            Some(lock) => lock,
+            None => return f(),
+        };
+        lock.lock();
+        let ret = f();
+        lock.unlock();
+        ret
     }
 }
 
-lazy_static! {
-    pub static ref ROOT_INODE: Arc<Inode> = {
-        let efs = EasyFileSystem::open(BLOCK_DEVICE.clone());
-        Arc::new(EasyFileSystem::root_inode(&efs))
-    };
+impl Drop for OSInode {
+    fn drop(&mut self) {
+        release_file_locks(self as *const Self as usize);
+    }
 }
 
 pub fn list_apps() {
     println!("/**** APPS ****");
-    for app in ROOT_INODE.ls() {
+    for app in lookup("/").unwrap().ls() {
         println!("{}", app);
     }
     println!("**************/")
@@ -63,8 +90,18 @@ bitflags! {
         const RDONLY = 0;
         const WRONLY = 1 << 0;
         const RDWR = 1 << 1;
+        /// with CREATE, fail if there is one already
+        const EXCL = 1 << 7;
         const CREATE = 1 << 9;
         const TRUNC = 1 << 10;
+        const APPEND = 1 << 11;
+        const NONBLOCK = 1 << 12;
+        /// fail on a symbolic link as the last component
+        const NOFOLLOW = 1 << 17;
+        /// for the new fd, not the file
+        const CLOEXEC = 1 << 19;
+        /// the flags fcntl may change after open
+        const STATUS = Self::APPEND.bits | Self::NONBLOCK.bits;
     }
 }
 
@@ -72,7 +109,11 @@ impl OpenFlags {
     /// Do not check validity for simplicity
     /// Return (readable, writable)
     pub fn read_write(&self) -> (bool, bool) {
-        if self.is_empty() {
+        // neither says anything about the access mode
+        if self
+            .difference(Self::NONBLOCK | Self::NOFOLLOW | Self::CLOEXEC)
+            .is_empty()
+        {
             (true, false)
         } else if self.contains(Self::WRONLY) {
             (false, true)
@@ -82,30 +123,60 @@ impl OpenFlags {
     }
 }
 
-pub fn open_file(name: &str, flags: OpenFlags) -> Option<Arc<OSInode>> {
+pub fn open_file(path: &str, flags: OpenFlags) -> Option<Arc<dyn File + Send + Sync>> {
     let (readable, writable) = flags.read_write();
-    if flags.contains(OpenFlags::CREATE) {
-        if let Some(inode) = ROOT_INODE.find(name) {
-            // clear size
-            inode.clear();
-            Some(Arc::new(OSInode::new(readable, writable, inode)))
-        } else {
-            // create file
-            ROOT_INODE
-                .create(name)
-                .map(|inode| Arc::new(OSInode::new(readable, writable, inode)))
-        }
+    let found = if flags.contains(OpenFlags::NOFOLLOW) {
+        lookup_nofollow(path)
     } else {
-        ROOT_INODE.find(name).map(|inode| {
-            if flags.contains(OpenFlags::TRUNC) {
+        lookup(path)
+    };
+    let inode = match found {
+        Some(_) if flags.contains(OpenFlags::CREATE | OpenFlags::EXCL) => return None,
+        Some(inode) => {
+            let mut want = 0;
+            if readable {
+                want |= MAY_READ;
+            }
+            if writable || flags.intersects(OpenFlags::CREATE | OpenFlags::TRUNC) {
+                want |= MAY_WRITE;
+            }
+            if inode.readlink().is_some() {
+                // only with O_NOFOLLOW, which wants no symbolic link
+                return None;
+            } else if !may_access(&inode, want) {
+                return None;
+            } else if inode.stat().mode & S_IFMT == S_IFSOCK {
+                // a socket is connected to, not opened
+                return None;
+            } else if inode.stat().mode & S_IFMT == S_IFIFO {
+                // a pipe, which has nothing to truncate
+                return open_fifo(inode, readable, writable, flags)
+                    .map(|pipe| pipe as Arc<dyn File + Send + Sync>);
+            } else if inode.is_dir() {
+                // directories change only through the file system
+                if writable || flags.intersects(OpenFlags::CREATE | OpenFlags::TRUNC) {
+                    return None;
+                }
+            } else if writable && inode.stat().mode & 0o222 == 0 {
+                // files nobody may write, like those of procfs
+                return None;
+            } else if flags.intersects(OpenFlags::CREATE | OpenFlags::TRUNC) {
+                // clear size
                 inode.clear();
+                notify(&inode, IN_MODIFY, "");
             }
-            Arc::new(OSInode::new(readable, writable, inode))
-        })
-    }
+            inode
+        }
+        None if flags.contains(OpenFlags::CREATE) => create(path)?,
+        None => return None,
+    };
+    Some(Arc::new(OSInode::new(readable, writable, flags, inode)))
 }
 
 impl File for OSInode {
+    fn inode(&self) -> Option<Arc<dyn Inode>> {
+        Some(Arc::clone(&self.inner.exclusive_access().inode))
+    }
     fn readable(&self) -> bool {
         self.readable
     }
@@ -113,27 +184,103 @@ impl File for OSInode {
         self.writable
     }
     fn read(&self, mut buf: UserBuffer) -> usize {
-        let mut inner = self.inner.exclusive_access();
+        self.positioned(move || {
+            let mut total_read_size = 0usize;
+            for slice in buf.buffers.iter_mut() {
+                let (inode, offset) = self.position();
+                // the thread is parked here while the disk reads
+                let read_size = inode.read_at(offset, *slice);
+                self.inner.exclusive_access().offset = offset + read_size;
+                total_read_size += read_size;
+                // the end of the file, or all a device has for now
+                if read_size < slice.len() {
+                    break;
+                }
+                // one page at a time
+                preempt_point();
+            }
+            total_read_size
+        })
+    }
+    fn write(&self, buf: UserBuffer) -> usize {
+        self.positioned(move || {
+            if self.status().contains(OpenFlags::APPEND) {
+                // in one piece, lest other writers come in between
+                let data: Vec<u8> = buf
+                    .buffers
+                    .iter()
+                    .flat_map(|slice| slice.iter())
+                    .copied()
+                    .collect();
+                let (inode, _) = self.position();
+                let offset = inode.append(&data);
+                self.inner.exclusive_access().offset = offset + data.len();
+                notify(&inode, IN_MODIFY, "");
+                return data.len();
+            }
+            let mut total_write_size = 0usize;
+            for slice in buf.buffers.iter() {
+                let (inode, offset) = self.position();
+                let write_size = inode.write_at(offset, *slice);
+                self.inner.exclusive_access().offset = offset + write_size;
+                total_write_size += write_size;
+                // the file system is full
+                if write_size < slice.len() {
+                    break;
+                }
+                preempt_point();
+            }
+            if total_write_size > 0 {
+                let (inode, _) = self.position();
+                notify(&inode, IN_MODIFY, "");
+            }
+            total_write_size
+        })
+    }
+    fn status(&self) -> OpenFlags {
+        self.inner.exclusive_access().status
+    }
+    fn set_status(&self, status: OpenFlags) {
+        self.inner.exclusive_access().status = status & OpenFlags::STATUS;
+    }
+    fn read_ready(&self) -> bool {
+        self.inner.exclusive_access().inode.read_ready()
+    }
+    fn seek(&self, pos: SeekFrom) -> Option<usize> {
+        self.positioned(|| {
+            let mut inner = self.inner.exclusive_access();
+            let offset = match pos {
+                SeekFrom::Start(offset) => offset,
+                SeekFrom::Current(delta) => inner.offset.checked_add_signed(delta)?,
+                SeekFrom::End(delta) => inner.inode.size().checked_add_signed(delta)?,
+            };
+            inner.offset = offset;
+            Some(offset)
+        })
+    }
+    fn read_at(&self, offset: usize, mut buf: UserBuffer) -> Option<usize> {
+        let inode = Arc::clone(&self.inner.exclusive_access().inode);
         let mut total_read_size = 0usize;
         for slice in buf.buffers.iter_mut() {
-            let read_size = inner.inode.read_at(inner.offset, *slice);
-            if read_size == 0 {
+            let read_size = inode.read_at(offset + total_read_size, *slice);
+            total_read_size += read_size;
+            if read_size < slice.len() {
                 break;
             }
-            inner.offset += read_size;
-            total_read_size += read_size;
+            preempt_point();
         }
-        total_read_size
+        Some(total_read_size)
     }
-    fn write(&self, buf: UserBuffer) -> usize {
-        let mut inner = self.inner.exclusive_access();
+    fn write_at(&self, offset: usize, buf: UserBuffer) -> Option<usize> {
+        let inode = Arc::clone(&self.inner.exclusive_access().inode);
         let mut total_write_size = 0usize;
         for slice in buf.buffers.iter() {
-            let write_size = inner.inode.write_at(inner.offset, *slice);
+            let write_size = inode.write_at(offset + total_write_size, *slice);
             assert_eq!(write_size, slice.len());
-            inner.offset += write_size;
             total_write_size += write_size;
+            preempt_point();
         }
-        total_write_size
+        notify(&inode, IN_MODIFY, "");
+        Some(total_write_size)
     }
 }
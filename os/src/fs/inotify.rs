@@ -0,0 +1,321 @@
+//! inotify, which tells a process what happens to the files and
+//! directories it watches, as events read from a file descriptor.
+//!
+//! A watch holds the inode it watches and tells it apart by that, since a
+//! file system hands out one `Inode` for a file while it is in use. The
+//! vfs reports changes of directories and attributes, open files their
+//! writes. A file or directory whose last name goes away loses its
+//! watches, with IN_DELETE_SELF and IN_IGNORED.
+
+use super::{File, Inode, OpenFlags, Stat};
+use crate::mm::UserBuffer;
+use crate::sync::UPIntrFreeCell;
+use crate::task::{signal_pending, suspend_current_and_run_next};
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::any::Any;
+use core::sync::atomic::{AtomicU32, Ordering};
+use lazy_static::*;
+
+pub const IN_MODIFY: u32 = 0x2;
+pub const IN_ATTRIB: u32 = 0x4;
+pub const IN_MOVED_FROM: u32 = 0x40;
+pub const IN_MOVED_TO: u32 = 0x80;
+pub const IN_CREATE: u32 = 0x100;
+pub const IN_DELETE: u32 = 0x200;
+pub const IN_DELETE_SELF: u32 = 0x400;
+/// The events a watch may ask for.
+pub const IN_ALL_EVENTS: u32 =
+    IN_MODIFY | IN_ATTRIB | IN_MOVED_FROM | IN_MOVED_TO | IN_CREATE | IN_DELETE | IN_DELETE_SELF;
+/// Events were dropped as the queue was full.
+pub const IN_Q_OVERFLOW: u32 = 0x4000;
+/// The watch is gone; the last event of it.
+pub const IN_IGNORED: u32 = 0x8000;
+/// The entry the event is about is a directory.
+pub const IN_ISDIR: u32 = 0x4000_0000;
+
+/// Events an inotify queues before dropping them.
+const MAX_EVENTS: usize = 256;
+/// The fixed part of `struct inotify_event`, to which names are padded.
+const EVENT_SZ: usize = 16;
+
+/// Pairs of IN_MOVED_FROM and IN_MOVED_TO of a rename.
+static NEXT_COOKIE: AtomicU32 = AtomicU32::new(1);
+
+struct Watch {
+    wd: i32,
+    inode: Arc<dyn Inode>,
+    mask: u32,
+    inotify: Weak<Inotify>,
+}
+
+lazy_static! {
+    static ref WATCHES: UPIntrFreeCell<Vec<Watch>> = unsafe { UPIntrFreeCell::new(Vec::new()) };
+}
+
+/// Whether `a` and `b` are the same inode, as handed out by its file system.
+pub fn same_inode(a: &Arc<dyn Inode>, b: &Arc<dyn Inode>) -> bool {
+    Arc::as_ptr(a) as *const u8 == Arc::as_ptr(b) as *const u8
+}
+
+#[derive(PartialEq)]
+struct Event {
+    wd: i32,
+    mask: u32,
+    cookie: u32,
+    name: String,
+}
+
+impl Event {
+    /// The length of `name` in the record, a NUL and padding included.
+    fn name_len(&self) -> usize {
+        if self.name.is_empty() {
+            0
+        } else {
+            (self.name.len() + EVENT_SZ) / EVENT_SZ * EVENT_SZ
+        }
+    }
+
+    fn len(&self) -> usize {
+        EVENT_SZ + self.name_len()
+    }
+}
+
+pub struct Inotify {
+    /// for the watches to point back
+    this: Weak<Inotify>,
+    inner: UPIntrFreeCell<InotifyInner>,
+}
+
+struct InotifyInner {
+    next_wd: i32,
+    events: VecDeque<Event>,
+    /// O_NONBLOCK, if set
+    status: OpenFlags,
+}
+
+impl Inotify {
+    pub fn new(status: OpenFlags) -> Arc<Self> {
+        Arc::new_cyclic(|this| Self {
+            this: this.clone(),
+            inner: unsafe {
+                UPIntrFreeCell::new(InotifyInner {
+                    next_wd: 1,
+                    events: VecDeque::new(),
+                    status: status & OpenFlags::NONBLOCK,
+                })
+            },
+        })
+    }
+
+    /// Watch `inode` for the events in `mask`, in place of those watched
+    /// for already, and return the watch descriptor.
+    pub fn add_watch(&self, inode: Arc<dyn Inode>, mask: u32) -> i32 {
+        let mask = mask & IN_ALL_EVENTS;
+        let mut watches = WATCHES.exclusive_access();
+        if let Some(watch) = watches.iter_mut().find(|watch| {
+            Weak::ptr_eq(&watch.inotify, &self.this) && same_inode(&watch.inode, &inode)
+        }) {
+            watch.mask = mask;
+            return watch.wd;
+        }
+        let mut inner = self.inner.exclusive_access();
+        let wd = inner.next_wd;
+        inner.next_wd += 1;
+        watches.push(Watch {
+            wd,
+            inode,
+            mask,
+            inotify: self.this.clone(),
+        });
+        wd
+    }
+
+    /// Remove the watch `wd`, which queues IN_IGNORED.
+    pub fn rm_watch(&self, wd: i32) -> bool {
+        let mut watches = WATCHES.exclusive_access();
+        let index = match watches
+            .iter()
+            .position(|watch| Weak::ptr_eq(&watch.inotify, &self.this) && watch.wd == wd)
+        {
+            Some(index) => index,
+            None => return false,
+        };
+        let watch = watches.swap_remove(index);
+        drop(watches);
+        self.push(wd, IN_IGNORED, 0, "");
+        // the inode may be the last of a file, which the file system frees
+        drop(watch);
+        true
+    }
+
+    fn push(&self, wd: i32, mask: u32, cookie: u32, name: &str) {
+        let event = Event {
+            wd,
+            mask,
+            cookie,
+            name: String::from(name),
+        };
+        let mut inner = self.inner.exclusive_access();
+        // the same as the last one unread, like a write after a write
+        if inner.events.back() == Some(&event) {
+            return;
+        }
+        if inner.events.len() >= MAX_EVENTS {
+            let overflow = Event {
+                wd: -1,
+                mask: IN_Q_OVERFLOW,
+                cookie: 0,
+                name: String::new(),
+            };
+            if inner.events.back() != Some(&overflow) {
+                inner.events.push_back(overflow);
+            }
+            return;
+        }
+        inner.events.push_back(event);
+    }
+}
+
+impl Drop for Inotify {
+    fn drop(&mut self) {
+        let gone: Vec<Watch> = {
+            let mut watches = WATCHES.exclusive_access();
+            let (gone, kept) = watches
+                .drain(..)
+                .partition(|watch| Weak::ptr_eq(&watch.inotify, &self.this));
+            *watches = kept;
+            gone
+        };
+        // outside the table, for the file systems to free what they may
+        drop(gone);
+    }
+}
+
+impl File for Inotify {
+    fn readable(&self) -> bool {
+        true
+    }
+    fn writable(&self) -> bool {
+        false
+    }
+    /// Wait for an event, then copy as many whole events as fit; 0 if not
+    /// even the first does.
+    fn read(&self, mut buf: UserBuffer) -> usize {
+        let mut inner = loop {
+            let inner = self.inner.exclusive_access();
+            if !inner.events.is_empty() || inner.status.contains(OpenFlags::NONBLOCK) {
+                break inner;
+            }
+            drop(inner);
+            if signal_pending() {
+                return 0;
+            }
+            suspend_current_and_run_next();
+        };
+        let mut records = Vec::new();
+        while let Some(event) = inner.events.front() {
+            if records.len() + event.len() > buf.len() {
+                break;
+            }
+            records.extend_from_slice(&event.wd.to_ne_bytes());
+            records.extend_from_slice(&event.mask.to_ne_bytes());
+            records.extend_from_slice(&event.cookie.to_ne_bytes());
+            records.extend_from_slice(&(event.name_len() as u32).to_ne_bytes());
+            records.extend_from_slice(event.name.as_bytes());
+            records.resize(records.len() + event.name_len() - event.name.len(), 0);
+            inner.events.pop_front();
+        }
+        drop(inner);
+        let mut copied = 0;
+        for slice in buf.buffers.iter_mut() {
+            let len = slice.len().min(records.len() - copied);
+            slice[..len].copy_from_slice(&records[copied..copied + len]);
+            copied += len;
+        }
+        copied
+    }
+    fn write(&self, _buf: UserBuffer) -> usize {
+        0
+    }
+    fn status(&self) -> OpenFlags {
+        self.inner.exclusive_access().status
+    }
+    fn set_status(&self, status: OpenFlags) {
+        self.inner.exclusive_access().status = status & OpenFlags::NONBLOCK;
+    }
+    fn read_ready(&self) -> bool {
+        !self.inner.exclusive_access().events.is_empty()
+    }
+    fn stat(&self) -> Stat {
+        Stat::new(0o600)
+    }
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+/// Queue `mask` for the watches of `inode` asking for it, about the entry
+/// `name` if `inode` is a directory.
+fn notify_cookie(inode: &Arc<dyn Inode>, mask: u32, cookie: u32, name: &str) {
+    let watchers: Vec<(Arc<Inotify>, i32)> = {
+        let watches = WATCHES.exclusive_access();
+        if watches.is_empty() {
+            return;
+        }
+        watches
+            .iter()
+            .filter(|watch| watch.mask & mask != 0 && same_inode(&watch.inode, inode))
+            .filter_map(|watch| Some((watch.inotify.upgrade()?, watch.wd)))
+            .collect()
+    };
+    for (inotify, wd) in watchers {
+        inotify.push(wd, mask, cookie, name);
+    }
+}
+
+/// Tell the watchers of `inode` about `mask`, about the entry `name` if
+/// `inode` is a directory.
+pub fn notify(inode: &Arc<dyn Inode>, mask: u32, name: &str) {
+    notify_cookie(inode, mask, 0, name);
+}
+
+/// Tell the watchers of the two directories that `old_name` of
+/// `old_parent` is now `new_name` of `new_parent`.
+pub fn notify_rename(
+    old_parent: &Arc<dyn Inode>,
+    old_name: &str,
+    new_parent: &Arc<dyn Inode>,
+    new_name: &str,
+    is_dir: bool,
+) {
+    let cookie = NEXT_COOKIE.fetch_add(1, Ordering::Relaxed);
+    let isdir = if is_dir { IN_ISDIR } else { 0 };
+    notify_cookie(old_parent, IN_MOVED_FROM | isdir, cookie, old_name);
+    notify_cookie(new_parent, IN_MOVED_TO | isdir, cookie, new_name);
+}
+
+/// `inode` has lost its last name: tell its watchers and end the watches.
+pub fn notify_deleted(inode: &Arc<dyn Inode>) {
+    let gone: Vec<Watch> = {
+        let mut watches = WATCHES.exclusive_access();
+        if watches.is_empty() {
+            return;
+        }
+        let (gone, kept) = watches
+            .drain(..)
+            .partition(|watch| same_inode(&watch.inode, inode));
+        *watches = kept;
+        gone
+    };
+    for watch in gone.iter() {
+        if let Some(inotify) = watch.inotify.upgrade() {
+            if watch.mask & IN_DELETE_SELF != 0 {
+                inotify.push(watch.wd, IN_DELETE_SELF, 0, "");
+            }
+            inotify.push(watch.wd, IN_IGNORED, 0, "");
+        }
+    }
+}
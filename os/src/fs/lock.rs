@@ -0,0 +1,331 @@
+//! Advisory locks of files, which only those asking for locks respect.
+//!
+//! flock locks a whole file for an open file, shared by its duplicates,
+//! until it is unlocked or the open file is gone. The record locks of
+//! fcntl lock byte ranges for a process, until they are unlocked, the
+//! process closes a descriptor of the file or exits. The two kinds do not
+//! see each other, as on Linux.
+//!
+//! A task waiting for a lock sleeps in the wait queue of the file, which
+//! is woken whenever a lock of the file goes. A process is refused a
+//! record lock it would wait for if that closes a cycle of processes
+//! waiting for each other.
+
+use super::Inode;
+use crate::sync::UPIntrFreeCell;
+use crate::task::{block_current_and_run_next, current_task, wakeup_task, TaskControlBlock};
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use lazy_static::*;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum LockKind {
+    Shared,
+    Exclusive,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Owner {
+    /// the address of an open file, for flock
+    File(usize),
+    /// a pid, for record locks
+    Process(usize),
+}
+
+impl Owner {
+    fn is_file(&self) -> bool {
+        matches!(self, Owner::File(_))
+    }
+}
+
+/// A lock of the bytes from `start` up to `end`, which flock takes from 0
+/// to u64::MAX.
+#[derive(Clone, Copy)]
+pub struct Lock {
+    owner: Owner,
+    pub kind: LockKind,
+    pub start: u64,
+    pub end: u64,
+}
+
+impl Lock {
+    /// Whether `self` keeps `other` from being taken.
+    fn conflicts(&self, other: &Lock) -> bool {
+        self.owner != other.owner
+            && self.owner.is_file() == other.owner.is_file()
+            && self.start < other.end
+            && other.start < self.end
+            && (self.kind == LockKind::Exclusive || other.kind == LockKind::Exclusive)
+    }
+
+    /// The pid of the process holding a record lock.
+    pub fn pid(&self) -> usize {
+        match self.owner {
+            Owner::Process(pid) => pid,
+            Owner::File(_) => 0,
+        }
+    }
+}
+
+#[derive(Default)]
+struct FileLocks {
+    locks: Vec<Lock>,
+    wait_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+struct LockTable {
+    /// by the address of the inode
+    files: BTreeMap<usize, FileLocks>,
+    /// the record lock each waiting process waits for, and where
+    waiting: BTreeMap<usize, (usize, Lock)>,
+}
+
+lazy_static! {
+    static ref LOCKS: UPIntrFreeCell<LockTable> = unsafe {
+        UPIntrFreeCell::new(LockTable {
+            files: BTreeMap::new(),
+            waiting: BTreeMap::new(),
+        })
+    };
+}
+
+/// Errors of taking a lock.
+pub enum LockError {
+    /// held by others, and not to be waited for
+    WouldBlock,
+    /// waiting would close a cycle of processes waiting for each other
+    Deadlock,
+}
+
+fn inode_key(inode: &Arc<dyn Inode>) -> usize {
+    Arc::as_ptr(inode) as *const u8 as usize
+}
+
+impl LockTable {
+    fn conflicts(&self, key: usize, lock: &Lock) -> impl Iterator<Item = &Lock> + '_ {
+        let locks = self.files.get(&key).map(|file| file.locks.as_slice());
+        let lock = *lock;
+        locks
+            .unwrap_or(&[])
+            .iter()
+            .filter(move |held| held.conflicts(&lock))
+    }
+
+    /// Whether `pid` waiting for `lock` would wait for itself, through the
+    /// processes holding what it waits for and what they wait for.
+    fn would_deadlock(&self, pid: usize, key: usize, lock: &Lock) -> bool {
+        let mut stack: Vec<usize> = self.conflicts(key, lock).map(|held| held.pid()).collect();
+        let mut seen = Vec::new();
+        while let Some(blocker) = stack.pop() {
+            if blocker == pid {
+                return true;
+            }
+            if seen.contains(&blocker) {
+                continue;
+            }
+            seen.push(blocker);
+            if let Some((key, lock)) = self.waiting.get(&blocker) {
+                stack.extend(self.conflicts(*key, lock).map(|held| held.pid()));
+            }
+        }
+        false
+    }
+
+    /// Take away from the locks of `owner` on `key` the bytes from `start`
+    /// to `end`, and wake the waiters if any lock shrank.
+    fn release(&mut self, key: usize, owner: Owner, start: u64, end: u64) {
+        let file = match self.files.get_mut(&key) {
+            Some(file) => file,
+            None => return,
+        };
+        let mut kept = Vec::new();
+        for lock in file.locks.drain(..) {
+            if lock.owner != owner || lock.end <= start || end <= lock.start {
+                kept.push(lock);
+                continue;
+            }
+            // what is left on either side
+            if lock.start < start {
+                kept.push(Lock { end: start, ..lock });
+            }
+            if end < lock.end {
+                kept.push(Lock { start: end, ..lock });
+            }
+        }
+        file.locks = kept;
+        for task in file.wait_queue.drain(..) {
+            wakeup_task(task);
+        }
+        if file.locks.is_empty() {
+            self.files.remove(&key);
+        }
+    }
+
+    /// Drop every lock of `owner`, on the inode of `key` only if given.
+    fn release_all(&mut self, key: Option<usize>, owner: Owner) {
+        let keys: Vec<usize> = self
+            .files
+            .iter()
+            .filter(|(file_key, file)| {
+                key.map_or(true, |key| key == **file_key)
+                    && file.locks.iter().any(|lock| lock.owner == owner)
+            })
+            .map(|(file_key, _)| *file_key)
+            .collect();
+        for key in keys {
+            self.release(key, owner, 0, u64::MAX);
+        }
+    }
+}
+
+/// Take `lock` of the inode of `key` once nothing conflicts, waiting if
+/// `wait`. The lock takes the place of those of its owner over the same
+/// bytes, and merges with those of the same kind next to it.
+fn acquire(key: usize, lock: Lock, wait: bool) -> Result<(), LockError> {
+    loop {
+        let mut table = LOCKS.exclusive_access();
+        if table.conflicts(key, &lock).next().is_none() {
+            table.release(key, lock.owner, lock.start, lock.end);
+            let file = table.files.entry(key).or_default();
+            // one with the locks of the same owner and kind it touches
+            let mut merged = lock;
+            file.locks.retain(|held| {
+                let touches = held.owner == merged.owner
+                    && held.kind == merged.kind
+                    && held.start <= merged.end
+                    && merged.start <= held.end;
+                if touches {
+                    merged.start = merged.start.min(held.start);
+                    merged.end = merged.end.max(held.end);
+                }
+                !touches
+            });
+            file.locks.push(merged);
+            return Ok(());
+        }
+        if !wait {
+            return Err(LockError::WouldBlock);
+        }
+        if let Owner::Process(pid) = lock.owner {
+            if table.would_deadlock(pid, key, &lock) {
+                return Err(LockError::Deadlock);
+            }
+            table.waiting.insert(pid, (key, lock));
+        }
+        table
+            .files
+            .entry(key)
+            .or_default()
+            .wait_queue
+            .push_back(current_task().unwrap());
+        drop(table);
+        block_current_and_run_next();
+        if let Owner::Process(pid) = lock.owner {
+            LOCKS.exclusive_access().waiting.remove(&pid);
+        }
+    }
+}
+
+/// flock of the open file at `file` on `inode`: lock it with `kind`, or
+/// unlock it if None. A lock already held is dropped before waiting for
+/// another kind.
+pub fn flock(
+    file: usize,
+    inode: &Arc<dyn Inode>,
+    kind: Option<LockKind>,
+    wait: bool,
+) -> Result<(), LockError> {
+    let key = inode_key(inode);
+    let owner = Owner::File(file);
+    match kind {
+        Some(kind) => {
+            let lock = Lock {
+                owner,
+                kind,
+                start: 0,
+                end: u64::MAX,
+            };
+            if LOCKS
+                .exclusive_access()
+                .conflicts(key, &lock)
+                .next()
+                .is_some()
+            {
+                LOCKS.exclusive_access().release(key, owner, 0, u64::MAX);
+            }
+            acquire(key, lock, wait)
+        }
+        None => {
+            LOCKS.exclusive_access().release(key, owner, 0, u64::MAX);
+            Ok(())
+        }
+    }
+}
+
+/// Lock the bytes of `inode` from `start` to `end` for `pid` with `kind`,
+/// or unlock them if None.
+pub fn lock_range(
+    pid: usize,
+    inode: &Arc<dyn Inode>,
+    kind: Option<LockKind>,
+    start: u64,
+    end: u64,
+    wait: bool,
+) -> Result<(), LockError> {
+    let key = inode_key(inode);
+    let owner = Owner::Process(pid);
+    match kind {
+        Some(kind) => acquire(
+            key,
+            Lock {
+                owner,
+                kind,
+                start,
+                end,
+            },
+            wait,
+        ),
+        None => {
+            LOCKS.exclusive_access().release(key, owner, start, end);
+            Ok(())
+        }
+    }
+}
+
+/// A record lock which would keep `pid` from locking the bytes from
+/// `start` to `end` with `kind`, for F_GETLK.
+pub fn blocking_lock(
+    pid: usize,
+    inode: &Arc<dyn Inode>,
+    kind: LockKind,
+    start: u64,
+    end: u64,
+) -> Option<Lock> {
+    let lock = Lock {
+        owner: Owner::Process(pid),
+        kind,
+        start,
+        end,
+    };
+    LOCKS
+        .exclusive_access()
+        .conflicts(inode_key(inode), &lock)
+        .next()
+        .copied()
+}
+
+/// The open file at `file` is gone, and so are its flocks.
+pub fn release_file_locks(file: usize) {
+    LOCKS
+        .exclusive_access()
+        .release_all(None, Owner::File(file));
+}
+
+/// Drop the record locks of `pid`, on `inode` only if given, as it closes
+/// a descriptor of `inode` or exits.
+pub fn release_process_locks(pid: usize, inode: Option<&Arc<dyn Inode>>) {
+    LOCKS
+        .exclusive_access()
+        .release_all(inode.map(inode_key), Owner::Process(pid));
+}
@@ -1,16 +1,159 @@
+mod devfs;
+mod easyfs;
+mod endpoint;
+mod eventfd;
+mod fat32;
+mod fifo;
+mod initramfs;
 mod inode;
+mod inotify;
+mod lock;
+mod mqueue;
+mod page_cache;
+mod pidfd;
 mod pipe;
+mod poll;
+mod procfs;
+mod ring;
+mod signalfd;
+mod stat;
 mod stdio;
+mod timerfd;
+mod tmpfs;
+mod tty;
+mod vfs;
 
-use crate::mm::UserBuffer;
+use crate::cmdline;
+use crate::mm::{release_initrd, UserBuffer};
+use alloc::sync::Arc;
+use core::any::Any;
+use vfs::FileSystem;
+
+/// Where lseek counts from.
+pub enum SeekFrom {
+    Start(usize),
+    Current(isize),
+    End(isize),
+}
 
 pub trait File: Send + Sync {
     fn readable(&self) -> bool;
     fn writable(&self) -> bool;
     fn read(&self, buf: UserBuffer) -> usize;
     fn write(&self, buf: UserBuffer) -> usize;
+    /// The status flags fcntl may change, O_APPEND and O_NONBLOCK.
+    fn status(&self) -> OpenFlags {
+        OpenFlags::empty()
+    }
+    /// Files which would not behave differently keep none.
+    fn set_status(&self, _status: OpenFlags) {}
+    /// Whether read would return without waiting, be it with data or at
+    /// the end of the file. Reads with O_NONBLOCK fail with EAGAIN if not.
+    fn read_ready(&self) -> bool {
+        true
+    }
+    /// Whether write would take something without waiting, or fail at
+    /// once for want of a reader.
+    fn write_ready(&self) -> bool {
+        true
+    }
+    /// Whether the other side has gone, like the writers of a pipe for the
+    /// reading end; poll reports it along with what is ready.
+    fn hung_up(&self) -> bool {
+        false
+    }
+    /// Move the offset and return it, for files which have one.
+    fn seek(&self, _pos: SeekFrom) -> Option<usize> {
+        None
+    }
+    /// Read at `offset`, leaving the offset alone, for files which have one.
+    fn read_at(&self, _offset: usize, _buf: UserBuffer) -> Option<usize> {
+        None
+    }
+    /// Write at `offset`, leaving the offset alone, for files which have one.
+    fn write_at(&self, _offset: usize, _buf: UserBuffer) -> Option<usize> {
+        None
+    }
+    /// Metadata for fstat, by default that of the inode behind.
+    fn stat(&self) -> Stat {
+        match self.inode() {
+            Some(inode) => inode.stat(),
+            None => Stat::new(0),
+        }
+    }
+    /// A device specific request, by default for the inode behind.
+    fn ioctl(&self, cmd: usize, arg: usize) -> isize {
+        match self.inode() {
+            Some(inode) => inode.ioctl(cmd, arg),
+            None => -1,
+        }
+    }
+    /// Write what is cached of the file back to its device, for fsync.
+    fn sync(&self) {
+        if let Some(inode) = self.inode() {
+            inode.sync();
+        }
+    }
+    /// The file on disk behind this one if any, e.g. for exec to read
+    /// segments from on demand or for mmap.
+    fn inode(&self) -> Option<Arc<dyn Inode>> {
+        None
+    }
+    /// For the syscalls of one kind of file, like those of inotify, to
+    /// tell it among others.
+    fn as_any(&self) -> Option<&dyn Any> {
+        None
+    }
 }
 
-pub use inode::{list_apps, open_file, OSInode, OpenFlags, ROOT_INODE};
-pub use pipe::{make_pipe, Pipe};
+pub use endpoint::{Endpoint, IpcMessage, IPC_MSG_SIZE};
+pub use eventfd::EventFd;
+pub use inode::{list_apps, open_file, OSInode, OpenFlags};
+pub use inotify::{Inotify, IN_ALL_EVENTS};
+pub use lock::{blocking_lock, flock, lock_range, release_process_locks, LockError, LockKind};
+pub use mqueue::{
+    mq_open, mq_unlink, MessageQueue, MqFile, MqOpenError, MQ_MAXMSG_DEFAULT, MQ_MAXMSG_MAX,
+    MQ_MSGSIZE_DEFAULT, MQ_MSGSIZE_MAX, MQ_PRIO_MAX,
+};
+pub use pidfd::PidFd;
+pub use pipe::{make_pipe, Pipe, PipeRingBuffer, PIPE_SIZE_MAX};
+pub use poll::{poll_file, wait_ready, Epoll, POLLNVAL};
+pub use ring::{make_ring, ring_size, RingEnd};
+pub use signalfd::SignalFd;
+pub use stat::{Stat, S_IFCHR, S_IFIFO, S_IFSOCK};
 pub use stdio::{Stdin, Stdout};
+pub use timerfd::TimerFd;
+pub use tty::{control_signal, signal_foreground};
+pub use vfs::{
+    chmod, chown, create, link, lookup, lookup_nofollow, may_access, mkdir, mkfifo, mksock, mount,
+    readlink, register_filesystem, rename, rmdir, symlink, sync, umount, unlink, working_dir,
+    Inode, MAY_EXEC, MAY_READ, MAY_WRITE,
+};
+
+/// The root file system: easy-fs on the root disk, or the initramfs if
+/// there is no disk or `root=initramfs` asks for it.
+fn root_fs() -> Arc<dyn FileSystem> {
+    let disk = || easyfs::ROOT_FS.clone().map(|fs| fs as Arc<dyn FileSystem>);
+    let root = if cmdline::option("root") == Some("initramfs") {
+        initramfs::unpack().or_else(disk)
+    } else {
+        disk().or_else(initramfs::unpack)
+    };
+    // unpacked, or not to be
+    release_initrd();
+    root.expect("no root file system, neither a disk nor an initramfs")
+}
+
+pub fn init() {
+    easyfs::init();
+    devfs::init();
+    procfs::init();
+    tmpfs::init();
+    fat32::init();
+    for (path, fs_type) in [("/dev", "devfs"), ("/proc", "proc"), ("/tmp", "tmpfs")] {
+        if lookup(path).is_none() {
+            mkdir(path);
+        }
+        assert!(mount(fs_type, path, fs_type));
+    }
+}
@@ -0,0 +1,190 @@
+//! POSIX message queues, named by a component after the / of mq_open,
+//! each holding up to `maxmsg` messages of up to `msgsize` bytes, handed
+//! out highest priority first and in the order sent within a priority.
+//!
+//! An open queue is a file descriptor, readable for poll while there is a
+//! message and writable while there is room; messages go in and out by
+//! mq_timedsend and mq_timedreceive, not by read and write. A queue lives
+//! on while it is open after mq_unlink took its name away.
+
+use super::{File, OpenFlags, Stat};
+use crate::mm::UserBuffer;
+use crate::sync::UPIntrFreeCell;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+use lazy_static::*;
+
+/// Priorities are below it.
+pub const MQ_PRIO_MAX: u32 = 32768;
+/// What a queue created without attributes holds.
+pub const MQ_MAXMSG_DEFAULT: usize = 10;
+pub const MQ_MSGSIZE_DEFAULT: usize = 8192;
+/// The most a queue may be created to hold, as /proc/sys/fs/mqueue says.
+pub const MQ_MAXMSG_MAX: usize = 256;
+pub const MQ_MSGSIZE_MAX: usize = 65536;
+
+pub struct MessageQueue {
+    maxmsg: usize,
+    msgsize: usize,
+    mode: u32,
+    inner: UPIntrFreeCell<MessageQueueInner>,
+}
+
+struct MessageQueueInner {
+    /// by priority, each in the order sent
+    messages: BTreeMap<u32, VecDeque<Vec<u8>>>,
+    count: usize,
+}
+
+impl MessageQueue {
+    pub fn new(maxmsg: usize, msgsize: usize, mode: u32) -> Arc<Self> {
+        Arc::new(Self {
+            maxmsg,
+            msgsize,
+            mode,
+            inner: unsafe {
+                UPIntrFreeCell::new(MessageQueueInner {
+                    messages: BTreeMap::new(),
+                    count: 0,
+                })
+            },
+        })
+    }
+
+    pub fn maxmsg(&self) -> usize {
+        self.maxmsg
+    }
+
+    pub fn msgsize(&self) -> usize {
+        self.msgsize
+    }
+
+    /// How many messages it holds.
+    pub fn count(&self) -> usize {
+        self.inner.exclusive_access().count
+    }
+
+    /// Put in a copy of `msg` of `prio`; false if the queue is full.
+    pub fn try_send(&self, msg: &[u8], prio: u32) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        if inner.count == self.maxmsg {
+            return false;
+        }
+        inner.messages.entry(prio).or_default().push_back(msg.to_vec());
+        inner.count += 1;
+        true
+    }
+
+    /// Take the first message of the highest priority, and the priority;
+    /// None if the queue is empty.
+    pub fn try_receive(&self) -> Option<(Vec<u8>, u32)> {
+        let mut inner = self.inner.exclusive_access();
+        let mut entry = inner.messages.last_entry()?;
+        let prio = *entry.key();
+        let msg = entry.get_mut().pop_front().unwrap();
+        if entry.get().is_empty() {
+            entry.remove();
+        }
+        inner.count -= 1;
+        Some((msg, prio))
+    }
+}
+
+lazy_static! {
+    /// by name
+    static ref QUEUES: UPIntrFreeCell<BTreeMap<String, Arc<MessageQueue>>> =
+        unsafe { UPIntrFreeCell::new(BTreeMap::new()) };
+}
+
+/// Errors of opening a queue by name.
+pub enum MqOpenError {
+    /// none of the name, and none to be created
+    NotFound,
+    /// one of the name already, and O_EXCL
+    Exists,
+}
+
+/// The queue of `name`; if there is none and `create` gives the maxmsg,
+/// msgsize and mode of one, a new one, and with `excl` only a new one.
+pub fn mq_open(
+    name: &str,
+    create: Option<(usize, usize, u32)>,
+    excl: bool,
+) -> Result<Arc<MessageQueue>, MqOpenError> {
+    let mut queues = QUEUES.exclusive_access();
+    if let Some(queue) = queues.get(name) {
+        if create.is_some() && excl {
+            return Err(MqOpenError::Exists);
+        }
+        return Ok(queue.clone());
+    }
+    let (maxmsg, msgsize, mode) = create.ok_or(MqOpenError::NotFound)?;
+    let queue = MessageQueue::new(maxmsg, msgsize, mode);
+    queues.insert(String::from(name), queue.clone());
+    Ok(queue)
+}
+
+/// Take the name away from its queue; false if there is none.
+pub fn mq_unlink(name: &str) -> bool {
+    QUEUES.exclusive_access().remove(name).is_some()
+}
+
+/// An open message queue.
+pub struct MqFile {
+    queue: Arc<MessageQueue>,
+    readable: bool,
+    writable: bool,
+    /// O_NONBLOCK, if set
+    status: UPIntrFreeCell<OpenFlags>,
+}
+
+impl MqFile {
+    pub fn new(queue: Arc<MessageQueue>, flags: OpenFlags) -> Arc<Self> {
+        Arc::new(Self {
+            queue,
+            readable: !flags.contains(OpenFlags::WRONLY),
+            writable: flags.intersects(OpenFlags::WRONLY | OpenFlags::RDWR),
+            status: unsafe { UPIntrFreeCell::new(flags & OpenFlags::NONBLOCK) },
+        })
+    }
+
+    pub fn queue(&self) -> &Arc<MessageQueue> {
+        &self.queue
+    }
+}
+
+impl File for MqFile {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+    fn writable(&self) -> bool {
+        self.writable
+    }
+    fn read(&self, _buf: UserBuffer) -> usize {
+        0
+    }
+    fn write(&self, _buf: UserBuffer) -> usize {
+        0
+    }
+    fn status(&self) -> OpenFlags {
+        *self.status.exclusive_access()
+    }
+    fn set_status(&self, status: OpenFlags) {
+        *self.status.exclusive_access() = status & OpenFlags::NONBLOCK;
+    }
+    fn read_ready(&self) -> bool {
+        self.queue.count() > 0
+    }
+    fn write_ready(&self) -> bool {
+        self.queue.count() < self.queue.maxmsg
+    }
+    fn stat(&self) -> Stat {
+        Stat::new(self.queue.mode)
+    }
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
@@ -0,0 +1,210 @@
+//! The page cache: the pages of a file kept in memory, so that reads and
+//! writes of a few bytes do not go to the disk each time.
+//!
+//! Written pages are marked dirty in the frame metadata and written back
+//! later, by the flusher, by fsync or when the file is no longer in use.
+//! Until then the file may be longer in the cache than on the disk. Pages
+//! mapped writable by MAP_SHARED may be written at any time, so they count
+//! as dirty at each write-back for as long as they stay mapped.
+//!
+//! Once tasks run, a page is read in by kworker, the blocks of it started
+//! together, while the thread which wants it parks; before, it is read
+//! block by block.
+
+use crate::config::PAGE_SIZE;
+use crate::mm::{frame_alloc, FrameTracker};
+use crate::sync::UPIntrFreeCell;
+use crate::task::{block_on, BoxFuture};
+use crate::DEV_NON_BLOCKING_ACCESS;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+/// Where the pages of a cache are read from and written back to.
+pub trait PageIo {
+    /// Read what there is at `offset`, returning how much that was.
+    fn read_page(&self, offset: usize, buf: &mut [u8]) -> usize;
+    /// Read the page at `offset` into `frame` as `read_page` does, as a
+    /// future which waits for the disk without a thread waiting with it.
+    fn read_page_async(&self, offset: usize, frame: FrameTracker) -> BoxFuture;
+    /// Write `buf` at `offset`, growing the file as needed.
+    fn write_page(&self, offset: usize, buf: &[u8]);
+}
+
+pub struct PageCache {
+    inner: UPIntrFreeCell<PageCacheInner>,
+}
+
+struct PageCacheInner {
+    /// size of the file, ahead of the one on the disk while written pages
+    /// wait to be written back
+    size: usize,
+    /// pages by index in the file
+    pages: BTreeMap<usize, FrameTracker>,
+}
+
+impl PageCacheInner {
+    /// The first page of `[offset, end)` which is not cached yet.
+    fn missing(&self, offset: usize, end: usize) -> Option<usize> {
+        if offset >= end {
+            return None;
+        }
+        (offset / PAGE_SIZE..=(end - 1) / PAGE_SIZE).find(|index| !self.pages.contains_key(index))
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let end = (offset + buf.len()).min(self.size);
+        let mut pos = offset;
+        while pos < end {
+            let page_offset = pos % PAGE_SIZE;
+            let len = (PAGE_SIZE - page_offset).min(end - pos);
+            let bytes = self.pages[&(pos / PAGE_SIZE)].ppn.get_bytes_array();
+            buf[pos - offset..pos - offset + len]
+                .copy_from_slice(&bytes[page_offset..page_offset + len]);
+            pos += len;
+        }
+        pos.saturating_sub(offset)
+    }
+
+    fn write_at(&mut self, offset: usize, buf: &[u8]) -> usize {
+        let end = offset + buf.len();
+        let mut pos = offset;
+        while pos < end {
+            let page_offset = pos % PAGE_SIZE;
+            let len = (PAGE_SIZE - page_offset).min(end - pos);
+            let page = &self.pages[&(pos / PAGE_SIZE)];
+            page.ppn.get_bytes_array()[page_offset..page_offset + len]
+                .copy_from_slice(&buf[pos - offset..pos - offset + len]);
+            page.set_dirty();
+            pos += len;
+        }
+        self.size = self.size.max(end);
+        buf.len()
+    }
+}
+
+impl PageCache {
+    /// An empty cache of a file of `size` bytes.
+    pub fn new(size: usize) -> Self {
+        Self {
+            inner: unsafe {
+                UPIntrFreeCell::new(PageCacheInner {
+                    size,
+                    pages: BTreeMap::new(),
+                })
+            },
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.inner.exclusive_access().size
+    }
+
+    /// Read the page `index` in, unless it has been meanwhile. Pages past
+    /// the end of the file on the disk start out zeroed.
+    fn fill(&self, index: usize, io: &dyn PageIo) {
+        let frame = frame_alloc().unwrap();
+        // the disk may sleep, so outside the cache
+        if *DEV_NON_BLOCKING_ACCESS.exclusive_access() {
+            block_on(io.read_page_async(index * PAGE_SIZE, frame.clone()));
+        } else {
+            io.read_page(index * PAGE_SIZE, frame.ppn.get_bytes_array());
+        }
+        frame.set_cached();
+        self.inner
+            .exclusive_access()
+            .pages
+            .entry(index)
+            .or_insert(frame);
+    }
+
+    /// The page `index`, read in if it is not cached yet, for a mapping to
+    /// share; None past the end of the file, which write-back would not
+    /// know what to do with.
+    pub fn page(&self, index: usize, io: &dyn PageIo) -> Option<FrameTracker> {
+        loop {
+            let inner = self.inner.exclusive_access();
+            if index * PAGE_SIZE >= inner.size {
+                return None;
+            }
+            if let Some(page) = inner.pages.get(&index) {
+                return Some(page.clone());
+            }
+            drop(inner);
+            self.fill(index, io);
+        }
+    }
+
+    pub fn read_at(&self, offset: usize, buf: &mut [u8], io: &dyn PageIo) -> usize {
+        loop {
+            let inner = self.inner.exclusive_access();
+            let end = (offset + buf.len()).min(inner.size);
+            match inner.missing(offset, end) {
+                Some(index) => {
+                    drop(inner);
+                    self.fill(index, io);
+                }
+                None => return inner.read_at(offset, buf),
+            }
+        }
+    }
+
+    pub fn write_at(&self, offset: usize, buf: &[u8], io: &dyn PageIo) -> usize {
+        loop {
+            let mut inner = self.inner.exclusive_access();
+            match inner.missing(offset, offset + buf.len()) {
+                Some(index) => {
+                    drop(inner);
+                    self.fill(index, io);
+                }
+                None => return inner.write_at(offset, buf),
+            }
+        }
+    }
+
+    /// Write `buf` at the end and return where that was. The end is found
+    /// under the same borrow as the write, once the pages are all there.
+    pub fn append(&self, buf: &[u8], io: &dyn PageIo) -> usize {
+        loop {
+            let mut inner = self.inner.exclusive_access();
+            let offset = inner.size;
+            match inner.missing(offset, offset + buf.len()) {
+                Some(index) => {
+                    drop(inner);
+                    self.fill(index, io);
+                }
+                None => {
+                    inner.write_at(offset, buf);
+                    return offset;
+                }
+            }
+        }
+    }
+
+    /// Forget all pages, dirty ones included, as the file is truncated to
+    /// nothing.
+    pub fn clear(&self) {
+        let mut inner = self.inner.exclusive_access();
+        inner.pages.clear();
+        inner.size = 0;
+    }
+
+    /// Write the dirty pages back, in order so that the file grows on the
+    /// disk without holes, and return how many there were.
+    pub fn write_back(&self, io: &dyn PageIo) -> usize {
+        let inner = self.inner.exclusive_access();
+        let size = inner.size;
+        let dirty: Vec<_> = inner
+            .pages
+            .iter()
+            .filter(|(_, page)| page.test_clear_dirty())
+            .map(|(index, page)| (*index, page.clone()))
+            .collect();
+        drop(inner);
+        for (index, page) in dirty.iter() {
+            let offset = index * PAGE_SIZE;
+            let len = (size - offset).min(PAGE_SIZE);
+            io.write_page(offset, &page.ppn.get_bytes_array()[..len]);
+        }
+        dirty.len()
+    }
+}
@@ -0,0 +1,78 @@
+//! pidfd, a file referring to a process, for signals to be sent to it and
+//! its exit waited for without its pid having been reused meanwhile by
+//! another process. It is readable for poll once the process has exited.
+//!
+//! The file does not keep the process alive: once it is reaped, the pidfd
+//! refers to no process at all, whichever takes its pid next.
+
+use super::{File, OpenFlags, Stat};
+use crate::mm::UserBuffer;
+use crate::sync::UPIntrFreeCell;
+use crate::task::ProcessControlBlock;
+use alloc::sync::{Arc, Weak};
+use core::any::Any;
+
+pub struct PidFd {
+    pid: usize,
+    process: Weak<ProcessControlBlock>,
+    /// O_NONBLOCK, if set
+    status: UPIntrFreeCell<OpenFlags>,
+}
+
+impl PidFd {
+    pub fn new(process: &Arc<ProcessControlBlock>, flags: OpenFlags) -> Arc<Self> {
+        Arc::new(Self {
+            pid: process.getpid(),
+            process: Arc::downgrade(process),
+            status: unsafe { UPIntrFreeCell::new(flags & OpenFlags::NONBLOCK) },
+        })
+    }
+
+    /// The pid the process had, which may have been reused if it was
+    /// reaped.
+    pub fn pid(&self) -> usize {
+        self.pid
+    }
+
+    /// The process, unless it was reaped.
+    pub fn process(&self) -> Option<Arc<ProcessControlBlock>> {
+        self.process.upgrade()
+    }
+
+    /// Whether the process has exited, reaped or not.
+    pub fn exited(&self) -> bool {
+        self.process()
+            .map_or(true, |process| process.inner_exclusive_access().is_zombie)
+    }
+}
+
+impl File for PidFd {
+    fn readable(&self) -> bool {
+        true
+    }
+    fn writable(&self) -> bool {
+        false
+    }
+    /// There is nothing to read, only readiness to poll.
+    fn read(&self, _buf: UserBuffer) -> usize {
+        0
+    }
+    fn write(&self, _buf: UserBuffer) -> usize {
+        0
+    }
+    fn status(&self) -> OpenFlags {
+        *self.status.exclusive_access()
+    }
+    fn set_status(&self, status: OpenFlags) {
+        *self.status.exclusive_access() = status & OpenFlags::NONBLOCK;
+    }
+    fn read_ready(&self) -> bool {
+        self.exited()
+    }
+    fn stat(&self) -> Stat {
+        Stat::new(0o600)
+    }
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
@@ -1,98 +1,214 @@
-use super::File;
+use super::inotify::{notify, IN_MODIFY};
+use super::{File, Inode, OpenFlags, Stat, S_IFIFO};
+use crate::config::PAGE_SIZE;
 use crate::mm::UserBuffer;
 use crate::sync::UPIntrFreeCell;
-use alloc::sync::{Arc, Weak};
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::any::Any;
 
-use crate::task::suspend_current_and_run_next;
+use crate::task::{signal_pending, suspend_current_and_run_next};
 
 pub struct Pipe {
     readable: bool,
     writable: bool,
     buffer: Arc<UPIntrFreeCell<PipeRingBuffer>>,
+    /// O_NONBLOCK, if set
+    status: UPIntrFreeCell<OpenFlags>,
+    /// the FIFO opened, for a named pipe, kept for fstat only
+    inode: Option<Arc<dyn Inode>>,
 }
 
 impl Pipe {
-    pub fn read_end_with_buffer(buffer: Arc<UPIntrFreeCell<PipeRingBuffer>>) -> Self {
+    /// An end of the pipe of `buffer`, which may be both for a FIFO.
+    pub fn new(
+        buffer: Arc<UPIntrFreeCell<PipeRingBuffer>>,
+        readable: bool,
+        writable: bool,
+        inode: Option<Arc<dyn Inode>>,
+    ) -> Self {
+        let mut ring_buffer = buffer.exclusive_access();
+        if readable {
+            ring_buffer.readers += 1;
+            ring_buffer.readers_opened += 1;
+        }
+        if writable {
+            ring_buffer.writers += 1;
+            ring_buffer.writers_opened += 1;
+        }
+        drop(ring_buffer);
         Self {
-            readable: true,
-            writable: false,
+            readable,
+            writable,
             buffer,
+            status: unsafe { UPIntrFreeCell::new(OpenFlags::empty()) },
+            inode,
         }
     }
+    pub fn read_end_with_buffer(buffer: Arc<UPIntrFreeCell<PipeRingBuffer>>) -> Self {
+        Self::new(buffer, true, false, None)
+    }
     pub fn write_end_with_buffer(buffer: Arc<UPIntrFreeCell<PipeRingBuffer>>) -> Self {
-        Self {
-            readable: false,
-            writable: true,
-            buffer,
-        }
+        Self::new(buffer, false, true, None)
     }
 }
 
-const RING_BUFFER_SIZE: usize = 32;
-
-#[derive(Copy, Clone, PartialEq)]
-enum RingBufferStatus {
-    Full,
-    Empty,
-    Normal,
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        let mut ring_buffer = self.buffer.exclusive_access();
+        if self.readable {
+            ring_buffer.readers -= 1;
+        }
+        if self.writable {
+            ring_buffer.writers -= 1;
+        }
+    }
 }
 
+/// What a pipe holds until F_SETPIPE_SZ says otherwise.
+const PIPE_SIZE_DEFAULT: usize = 4 * PAGE_SIZE;
+/// The most F_SETPIPE_SZ gives, as /proc/sys/fs/pipe-max-size.
+pub const PIPE_SIZE_MAX: usize = 1 << 20;
+
 pub struct PipeRingBuffer {
-    arr: [u8; RING_BUFFER_SIZE],
+    arr: Vec<u8>,
+    /// where the bytes start, and how many there are
     head: usize,
-    tail: usize,
-    status: RingBufferStatus,
-    write_end: Option<Weak<Pipe>>,
+    len: usize,
+    /// ends open for reading and for writing
+    pub readers: usize,
+    pub writers: usize,
+    /// ends ever opened for each, for the opener of a FIFO waiting for the
+    /// other side to see one came, even if it is gone again
+    pub readers_opened: usize,
+    pub writers_opened: usize,
 }
 
 impl PipeRingBuffer {
     pub fn new() -> Self {
         Self {
-            arr: [0; RING_BUFFER_SIZE],
+            arr: vec![0; PIPE_SIZE_DEFAULT],
             head: 0,
-            tail: 0,
-            status: RingBufferStatus::Empty,
-            write_end: None,
+            len: 0,
+            readers: 0,
+            writers: 0,
+            readers_opened: 0,
+            writers_opened: 0,
         }
     }
-    pub fn set_write_end(&mut self, write_end: &Arc<Pipe>) {
-        self.write_end = Some(Arc::downgrade(write_end));
-    }
     pub fn write_byte(&mut self, byte: u8) {
-        self.status = RingBufferStatus::Normal;
-        self.arr[self.tail] = byte;
-        self.tail = (self.tail + 1) % RING_BUFFER_SIZE;
-        if self.tail == self.head {
-            self.status = RingBufferStatus::Full;
-        }
+        let tail = (self.head + self.len) % self.arr.len();
+        self.arr[tail] = byte;
+        self.len += 1;
     }
     pub fn read_byte(&mut self) -> u8 {
-        self.status = RingBufferStatus::Normal;
         let c = self.arr[self.head];
-        self.head = (self.head + 1) % RING_BUFFER_SIZE;
-        if self.head == self.tail {
-            self.status = RingBufferStatus::Empty;
-        }
+        self.head = (self.head + 1) % self.arr.len();
+        self.len -= 1;
         c
     }
+    /// Take as many bytes as fit `dst`; how many.
+    pub fn read_bytes(&mut self, dst: &mut [u8]) -> usize {
+        let len = dst.len().min(self.len);
+        // up to the end of the array, then from its start
+        let first = len.min(self.arr.len() - self.head);
+        dst[..first].copy_from_slice(&self.arr[self.head..self.head + first]);
+        dst[first..len].copy_from_slice(&self.arr[..len - first]);
+        self.head = (self.head + len) % self.arr.len();
+        self.len -= len;
+        len
+    }
     pub fn available_read(&self) -> usize {
-        if self.status == RingBufferStatus::Empty {
-            0
-        } else if self.tail > self.head {
-            self.tail - self.head
-        } else {
-            self.tail + RING_BUFFER_SIZE - self.head
-        }
+        self.len
     }
     pub fn available_write(&self) -> usize {
-        if self.status == RingBufferStatus::Full {
-            0
-        } else {
-            RING_BUFFER_SIZE - self.available_read()
+        self.arr.len() - self.len
+    }
+    pub fn capacity(&self) -> usize {
+        self.arr.len()
+    }
+    /// Hold `capacity` bytes from now on, keeping what is there; false if
+    /// that does not fit.
+    pub fn set_capacity(&mut self, capacity: usize) -> bool {
+        if capacity < self.len {
+            return false;
         }
+        let mut arr = vec![0; capacity];
+        let len = self.len;
+        self.read_bytes(&mut arr[..len]);
+        self.arr = arr;
+        self.head = 0;
+        self.len = len;
+        true
     }
     pub fn all_write_ends_closed(&self) -> bool {
-        self.write_end.as_ref().unwrap().upgrade().is_none()
+        self.writers == 0
+    }
+    pub fn all_read_ends_closed(&self) -> bool {
+        self.readers == 0
+    }
+}
+
+impl Pipe {
+    /// The bytes the pipe holds at most.
+    pub fn size(&self) -> usize {
+        self.buffer.exclusive_access().capacity()
+    }
+
+    /// Make the pipe hold `size` bytes, rounded up to pages, and return
+    /// how many; None if more than that are in it.
+    pub fn set_size(&self, size: usize) -> Option<usize> {
+        let size = size.max(1).div_ceil(PAGE_SIZE) * PAGE_SIZE;
+        self.buffer
+            .exclusive_access()
+            .set_capacity(size)
+            .then_some(size)
+    }
+
+    /// Move up to `len` bytes from the pipe to `inode` at `offset`, or at
+    /// its end for `append`, through the page cache rather than a buffer
+    /// in user space. Like read it waits for the first, then takes what
+    /// is there; it returns how many it moved.
+    pub fn splice_to(
+        &self,
+        inode: &Arc<dyn Inode>,
+        offset: usize,
+        append: bool,
+        len: usize,
+    ) -> usize {
+        assert!(self.readable());
+        let mut page = vec![0u8; PAGE_SIZE];
+        let mut moved = 0;
+        while moved < len {
+            let mut ring_buffer = self.buffer.exclusive_access();
+            if ring_buffer.available_read() == 0 {
+                if moved > 0
+                    || ring_buffer.all_write_ends_closed()
+                    || self.status().contains(OpenFlags::NONBLOCK)
+                    || signal_pending()
+                {
+                    break;
+                }
+                drop(ring_buffer);
+                suspend_current_and_run_next();
+                continue;
+            }
+            let chunk = (len - moved).min(PAGE_SIZE);
+            let chunk = ring_buffer.read_bytes(&mut page[..chunk]);
+            // the file system may sleep, not with the pipe held
+            drop(ring_buffer);
+            if append {
+                inode.append(&page[..chunk]);
+            } else {
+                inode.write_at(offset + moved, &page[..chunk]);
+            }
+            moved += chunk;
+        }
+        if moved > 0 {
+            notify(inode, IN_MODIFY, "");
+        }
+        moved
     }
 }
 
@@ -100,8 +216,7 @@ impl PipeRingBuffer {
 pub fn make_pipe() -> (Arc<Pipe>, Arc<Pipe>) {
     let buffer = Arc::new(unsafe { UPIntrFreeCell::new(PipeRingBuffer::new()) });
     let read_end = Arc::new(Pipe::read_end_with_buffer(buffer.clone()));
-    let write_end = Arc::new(Pipe::write_end_with_buffer(buffer.clone()));
-    buffer.exclusive_access().set_write_end(&write_end);
+    let write_end = Arc::new(Pipe::write_end_with_buffer(buffer));
     (read_end, write_end)
 }
 
@@ -112,6 +227,39 @@ impl File for Pipe {
     fn writable(&self) -> bool {
         self.writable
     }
+    /// That of the FIFO, which is no file to map or run, for a named pipe.
+    fn stat(&self) -> Stat {
+        match &self.inode {
+            Some(inode) => inode.stat(),
+            None => Stat::new(S_IFIFO | 0o600),
+        }
+    }
+    fn status(&self) -> OpenFlags {
+        *self.status.exclusive_access()
+    }
+    fn set_status(&self, status: OpenFlags) {
+        *self.status.exclusive_access() = status & OpenFlags::NONBLOCK;
+    }
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+    fn read_ready(&self) -> bool {
+        let ring_buffer = self.buffer.exclusive_access();
+        ring_buffer.available_read() > 0 || ring_buffer.all_write_ends_closed()
+    }
+    fn write_ready(&self) -> bool {
+        let ring_buffer = self.buffer.exclusive_access();
+        ring_buffer.available_write() > 0 || ring_buffer.all_read_ends_closed()
+    }
+    /// Both ends of a FIFO opened for both never hang up.
+    fn hung_up(&self) -> bool {
+        let ring_buffer = self.buffer.exclusive_access();
+        match (self.readable, self.writable) {
+            (true, false) => ring_buffer.all_write_ends_closed(),
+            (false, true) => ring_buffer.all_read_ends_closed(),
+            _ => false,
+        }
+    }
     fn read(&self, buf: UserBuffer) -> usize {
         assert!(self.readable());
         let want_to_read = buf.len();
@@ -121,7 +269,14 @@ impl File for Pipe {
             let mut ring_buffer = self.buffer.exclusive_access();
             let loop_read = ring_buffer.available_read();
             if loop_read == 0 {
-                if ring_buffer.all_write_ends_closed() {
+                // without waiting for more, once there is something
+                if ring_buffer.all_write_ends_closed()
+                    || (already_read > 0 && self.status().contains(OpenFlags::NONBLOCK))
+                {
+                    return already_read;
+                }
+                // a signal to deliver ends the wait, with what was read
+                if signal_pending() {
                     return already_read;
                 }
                 drop(ring_buffer);
@@ -150,8 +305,15 @@ impl File for Pipe {
         let mut already_write = 0usize;
         loop {
             let mut ring_buffer = self.buffer.exclusive_access();
+            // nobody to read it any more
+            if ring_buffer.all_read_ends_closed() {
+                return already_write;
+            }
             let loop_write = ring_buffer.available_write();
             if loop_write == 0 {
+                if signal_pending() {
+                    return already_write;
+                }
                 drop(ring_buffer);
                 suspend_current_and_run_next();
                 continue;
@@ -0,0 +1,171 @@
+//! Waiting for any of several files to be ready, as ppoll does for a list
+//! of descriptors and epoll for those registered with it.
+//!
+//! A file tells what it is ready for by `read_ready`, `write_ready` and
+//! `hung_up`; a waiter looks at all its files, and gives the processor up
+//! until one is ready or its time is over, as a pipe does for its reader.
+//! An epoll holds its files weakly, so that what is registered goes when
+//! the last descriptor of it is closed.
+
+use super::{File, Stat};
+use crate::mm::UserBuffer;
+use crate::sync::UPIntrFreeCell;
+use crate::task::{signal_pending, suspend_current_and_run_next};
+use crate::timer::ktime_now;
+use crate::trace::{trace, TraceEvent};
+use crate::watchdog::poll_site;
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::any::Any;
+use core::panic::Location;
+
+pub const POLLIN: u32 = 0x1;
+pub const POLLOUT: u32 = 0x4;
+pub const POLLERR: u32 = 0x8;
+pub const POLLHUP: u32 = 0x10;
+pub const POLLNVAL: u32 = 0x20;
+
+/// What of `events` `file` is ready for, with POLLHUP or POLLERR if the
+/// other side is gone, whether asked for or not.
+pub fn poll_file(file: &Arc<dyn File + Send + Sync>, events: u32) -> u32 {
+    let mut revents = 0;
+    if file.readable() && file.read_ready() {
+        revents |= POLLIN;
+    }
+    if file.writable() && file.write_ready() {
+        revents |= POLLOUT;
+    }
+    revents &= events;
+    if file.hung_up() {
+        revents |= if file.readable() { POLLHUP } else { POLLERR };
+    }
+    revents
+}
+
+/// Call `check` until it finds something, giving the processor up between
+/// the calls; None once `deadline`, in ns, has passed, or a signal is to
+/// be delivered.
+#[track_caller]
+pub fn wait_ready<T>(deadline: Option<u64>, mut check: impl FnMut() -> Option<T>) -> Option<T> {
+    poll_site(Location::caller());
+    loop {
+        let found = check();
+        trace(TraceEvent::Poll {
+            ready: found.is_some(),
+        });
+        if let Some(found) = found {
+            return Some(found);
+        }
+        if deadline.map_or(false, |deadline| ktime_now() >= deadline) || signal_pending() {
+            return None;
+        }
+        suspend_current_and_run_next();
+    }
+}
+
+/// A file registered with an epoll.
+struct Interest {
+    file: Weak<dyn File + Send + Sync>,
+    events: u32,
+    /// handed back with the events
+    data: u64,
+}
+
+pub struct Epoll {
+    /// by descriptor
+    interests: UPIntrFreeCell<BTreeMap<usize, Interest>>,
+}
+
+impl Epoll {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            interests: unsafe { UPIntrFreeCell::new(BTreeMap::new()) },
+        })
+    }
+
+    /// Register `file` of descriptor `fd`; false if it is there already,
+    /// or an epoll itself.
+    pub fn add(
+        &self,
+        fd: usize,
+        file: &Arc<dyn File + Send + Sync>,
+        events: u32,
+        data: u64,
+    ) -> bool {
+        if file.as_any().map_or(false, |file| file.is::<Epoll>()) {
+            return false;
+        }
+        let mut interests = self.interests.exclusive_access();
+        interests.retain(|_, interest| interest.file.strong_count() > 0);
+        if interests.contains_key(&fd) {
+            return false;
+        }
+        interests.insert(
+            fd,
+            Interest {
+                file: Arc::downgrade(file),
+                events,
+                data,
+            },
+        );
+        true
+    }
+
+    /// Change what is asked of `fd`; false if it is not registered.
+    pub fn modify(&self, fd: usize, events: u32, data: u64) -> bool {
+        match self.interests.exclusive_access().get_mut(&fd) {
+            Some(interest) if interest.file.strong_count() > 0 => {
+                interest.events = events;
+                interest.data = data;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn remove(&self, fd: usize) -> bool {
+        self.interests.exclusive_access().remove(&fd).is_some()
+    }
+
+    /// The events and data of up to `max` files ready.
+    pub fn ready(&self, max: usize) -> Vec<(u32, u64)> {
+        let interests: Vec<(Arc<dyn File + Send + Sync>, u32, u64)> = self
+            .interests
+            .exclusive_access()
+            .values()
+            .filter_map(|interest| {
+                let file = interest.file.upgrade()?;
+                Some((file, interest.events, interest.data))
+            })
+            .collect();
+        // outside the table, for a file to look at the net or wait a bit
+        interests
+            .iter()
+            .map(|(file, events, data)| (poll_file(file, *events), *data))
+            .filter(|(revents, _)| *revents != 0)
+            .take(max)
+            .collect()
+    }
+}
+
+impl File for Epoll {
+    fn readable(&self) -> bool {
+        false
+    }
+    fn writable(&self) -> bool {
+        false
+    }
+    fn read(&self, _buf: UserBuffer) -> usize {
+        0
+    }
+    fn write(&self, _buf: UserBuffer) -> usize {
+        0
+    }
+    fn stat(&self) -> Stat {
+        Stat::new(0o600)
+    }
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
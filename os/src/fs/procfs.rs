@@ -0,0 +1,376 @@
+//! procfs, the state of the kernel as read-only text files, mounted on
+//! /proc.
+//!
+//! The files have no contents of their own: each read generates the text
+//! anew and returns what is at the offset, so a file read in several
+//! pieces may change in between. Their size is 0, as on Linux.
+
+use super::stat::{Stat, S_IFDIR, S_IFREG};
+use super::vfs::{DirEntry, FileSystem, Inode};
+use crate::board::irq_counts;
+use crate::cmdline::cmdline;
+use crate::logging::log_text;
+use crate::mm::mem_info;
+use crate::net::interfaces;
+#[cfg(feature = "netbench")]
+use crate::net::netbench_report;
+use crate::task::{current_process, kernel_tasks, pid2process, pids, TaskStatus};
+use crate::trace::trace_text;
+use crate::trap::timer_interrupts;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::fmt::Write;
+use lazy_static::*;
+
+const ROOT_INO: u64 = 1;
+
+/// Inode number of the directory of `pid`, its files numbered after it.
+fn pid_ino(pid: usize) -> u64 {
+    (pid as u64 + 1) << 8
+}
+
+fn dir_stat(ino: u64) -> Stat {
+    let mut stat = Stat::new(S_IFDIR | 0o555);
+    stat.ino = ino;
+    stat.nlink = 2;
+    stat
+}
+
+/// Copy what of `text` is at `offset` into `buf`.
+fn read_text(text: &str, offset: usize, buf: &mut [u8]) -> usize {
+    let bytes = text.as_bytes();
+    if offset >= bytes.len() {
+        return 0;
+    }
+    let len = buf.len().min(bytes.len() - offset);
+    buf[..len].copy_from_slice(&bytes[offset..offset + len]);
+    len
+}
+
+fn state_name(status: TaskStatus) -> &'static str {
+    match status {
+        TaskStatus::Ready => "R (ready)",
+        TaskStatus::Running => "R (running)",
+        TaskStatus::Blocked => "S (sleeping)",
+    }
+}
+
+/// A file whose text `generate` makes up at each read.
+struct ProcFile {
+    ino: u64,
+    generate: fn() -> String,
+}
+
+impl Inode for ProcFile {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn is_dir(&self) -> bool {
+        false
+    }
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        read_text(&(self.generate)(), offset, buf)
+    }
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> usize {
+        0
+    }
+    fn size(&self) -> usize {
+        0
+    }
+    fn stat(&self) -> Stat {
+        let mut stat = Stat::new(S_IFREG | 0o444);
+        stat.ino = self.ino;
+        stat
+    }
+}
+
+fn cmdline_text() -> String {
+    format!("{}\n", cmdline())
+}
+
+fn meminfo() -> String {
+    let info = mem_info();
+    let mut text = String::new();
+    for (name, bytes) in [
+        ("MemTotal", info.total),
+        ("MemFree", info.free),
+        ("Cached", info.page_cache),
+        ("HeapTotal", info.heap_total),
+        ("HeapUsed", info.heap_used),
+        ("SwapTotal", info.swap_total),
+        ("SwapFree", info.swap_total - info.swap_used),
+    ] {
+        writeln!(text, "{:<16}{:>8} kB", format!("{}:", name), bytes / 1024).unwrap();
+    }
+    text
+}
+
+fn interrupts() -> String {
+    let mut text = String::new();
+    for (id, name, count) in irq_counts() {
+        writeln!(text, "{:>4}: {:>10}  PLIC  {}", id, count, name).unwrap();
+    }
+    let timer = timer_interrupts();
+    writeln!(text, "{:>4}: {:>10}  Timer interrupts", "LOC", timer).unwrap();
+    text
+}
+
+fn netdev() -> String {
+    let mut text = String::new();
+    writeln!(
+        text,
+        "{:>6}  {:>10} {:>8} {:>6}  {:>10} {:>8}",
+        "face", "rx bytes", "packets", "drop", "tx bytes", "packets"
+    )
+    .unwrap();
+    for iface in interfaces() {
+        let stats = iface.stats();
+        writeln!(
+            text,
+            "{:>6}: {:>10} {:>8} {:>6}  {:>10} {:>8}",
+            iface.name(),
+            stats.rx_bytes,
+            stats.rx_packets,
+            stats.rx_dropped,
+            stats.tx_bytes,
+            stats.tx_packets
+        )
+        .unwrap();
+    }
+    text
+}
+
+fn ktasks() -> String {
+    let mut text = String::new();
+    for (name, status) in kernel_tasks() {
+        writeln!(text, "{:<16}{}", name, state_name(status)).unwrap();
+    }
+    text
+}
+
+/// /proc/<pid>/status of the process `pid`, or nothing once it is gone.
+fn status(pid: usize) -> String {
+    let process = match pid2process(pid) {
+        Some(process) => process,
+        None => return String::new(),
+    };
+    let inner = process.inner_exclusive_access();
+    let ppid = inner
+        .parent
+        .as_ref()
+        .and_then(|parent| parent.upgrade())
+        .map_or(0, |parent| parent.getpid());
+    let tasks: Vec<_> = inner.tasks.iter().flatten().cloned().collect();
+    let state = if inner.is_zombie {
+        "Z (zombie)"
+    } else if inner.signals.stopped.is_some() {
+        "T (stopped)"
+    } else {
+        // the busiest of its threads
+        let statuses: Vec<_> = tasks
+            .iter()
+            .map(|task| task.inner_exclusive_access().task_status)
+            .collect();
+        [TaskStatus::Running, TaskStatus::Ready, TaskStatus::Blocked]
+            .into_iter()
+            .find(|status| statuses.contains(status))
+            .map_or("Z (zombie)", state_name)
+    };
+    let vm_stat = inner.memory_set.vm_stat();
+    let fds = inner.fd_table.iter().filter(|fd| fd.is_some()).count();
+    let mut text = String::new();
+    writeln!(text, "Name:\t{}", inner.name).unwrap();
+    writeln!(text, "State:\t{}", state).unwrap();
+    writeln!(text, "Pid:\t{}", pid).unwrap();
+    writeln!(text, "PPid:\t{}", ppid).unwrap();
+    writeln!(text, "NSpgid:\t{}", inner.pgid).unwrap();
+    writeln!(text, "Uid:\t{}", inner.uid).unwrap();
+    writeln!(text, "Gid:\t{}", inner.gid).unwrap();
+    writeln!(text, "Threads:\t{}", tasks.len()).unwrap();
+    writeln!(text, "FDs:\t{}", fds).unwrap();
+    writeln!(text, "VmSize:\t{:>8} kB", vm_stat.vsz / 1024).unwrap();
+    writeln!(text, "VmRSS:\t{:>8} kB", vm_stat.rss / 1024).unwrap();
+    writeln!(text, "VmSwap:\t{:>8} kB", vm_stat.swap / 1024).unwrap();
+    text
+}
+
+/// /proc/<pid>/status
+struct StatusFile {
+    pid: usize,
+}
+
+impl Inode for StatusFile {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn is_dir(&self) -> bool {
+        false
+    }
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        read_text(&status(self.pid), offset, buf)
+    }
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> usize {
+        0
+    }
+    fn size(&self) -> usize {
+        0
+    }
+    fn stat(&self) -> Stat {
+        let mut stat = Stat::new(S_IFREG | 0o444);
+        stat.ino = pid_ino(self.pid) + 1;
+        stat
+    }
+}
+
+/// /proc/<pid>, the directory of a process.
+struct PidDir {
+    pid: usize,
+}
+
+impl Inode for PidDir {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn is_dir(&self) -> bool {
+        true
+    }
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> usize {
+        0
+    }
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> usize {
+        0
+    }
+    fn size(&self) -> usize {
+        0
+    }
+    fn stat(&self) -> Stat {
+        dir_stat(pid_ino(self.pid))
+    }
+    fn find(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        match name {
+            "status" => Some(Arc::new(StatusFile { pid: self.pid })),
+            _ => None,
+        }
+    }
+    fn ls(&self) -> Vec<String> {
+        vec![String::from("status")]
+    }
+}
+
+/// /proc itself, with the files of the kernel and a directory for each
+/// process, `self` being that of the process looking.
+struct ProcRoot {
+    files: Vec<(&'static str, Arc<dyn Inode>)>,
+}
+
+impl Inode for ProcRoot {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn is_dir(&self) -> bool {
+        true
+    }
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> usize {
+        0
+    }
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> usize {
+        0
+    }
+    fn size(&self) -> usize {
+        0
+    }
+    fn stat(&self) -> Stat {
+        dir_stat(ROOT_INO)
+    }
+    fn find(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        if let Some((_, inode)) = self.files.iter().find(|(file, _)| *file == name) {
+            return Some(inode.clone());
+        }
+        let pid = match name {
+            "self" => current_process().getpid(),
+            _ => name.parse().ok()?,
+        };
+        pid2process(pid)?;
+        Some(Arc::new(PidDir { pid }))
+    }
+    fn ls(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .files
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .collect();
+        names.push(String::from("self"));
+        names.extend(pids().iter().map(|pid| pid.to_string()));
+        names
+    }
+    /// The files, then `self`, then the processes at offsets after their
+    /// pids, which stay there however many come and go before them.
+    fn read_dir(&self, offset: usize) -> Option<(DirEntry, usize)> {
+        if let Some((name, inode)) = self.files.get(offset) {
+            let stat = inode.stat();
+            return Some((
+                DirEntry::new(stat.ino, stat.mode, name.to_string()),
+                offset + 1,
+            ));
+        }
+        let pids_from = self.files.len() + 1;
+        if offset < pids_from {
+            let pid = current_process().getpid();
+            let entry = DirEntry::new(pid_ino(pid), S_IFDIR, String::from("self"));
+            return Some((entry, pids_from));
+        }
+        let pid = pids()
+            .into_iter()
+            .filter(|&pid| pid >= offset - pids_from)
+            .min()?;
+        let entry = DirEntry::new(pid_ino(pid), S_IFDIR, pid.to_string());
+        Some((entry, pids_from + pid + 1))
+    }
+}
+
+pub struct ProcFs {
+    root: Arc<ProcRoot>,
+}
+
+impl FileSystem for ProcFs {
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+}
+
+lazy_static! {
+    static ref PROC_FS: Arc<ProcFs> = {
+        let file = |ino: u64, generate: fn() -> String| {
+            Arc::new(ProcFile { ino, generate }) as Arc<dyn Inode>
+        };
+        let root = ProcRoot {
+            files: vec![
+                ("cmdline", file(9, cmdline_text)),
+                ("interrupts", file(2, interrupts)),
+                ("kmsg", file(7, log_text)),
+                ("ktasks", file(3, ktasks)),
+                ("meminfo", file(4, meminfo)),
+                ("netdev", file(5, netdev)),
+                #[cfg(feature = "netbench")]
+                ("netbench", file(6, netbench_report)),
+                ("trace", file(8, trace_text)),
+            ],
+        };
+        Arc::new(ProcFs {
+            root: Arc::new(root),
+        })
+    };
+}
+
+/// There is one procfs, whatever the source.
+fn mount(_source: &str) -> Option<Arc<dyn FileSystem>> {
+    Some(PROC_FS.clone())
+}
+
+pub fn init() {
+    super::register_filesystem("proc", mount);
+}
@@ -0,0 +1,204 @@
+//! Rings of messages in memory shared by the processes which map them, so
+//! that they exchange messages without the kernel: a page of header, then
+//! `entries` slots of a sequence number, a length and `entry_size` bytes,
+//! which producers and consumers claim by compare and swap, so that any
+//! number of either may share a ring.
+//!
+//! The kernel only lays a ring out and rings its doorbells. Each end of it
+//! is a file descriptor like an eventfd with EFD_SEMAPHORE: writes to it
+//! ring the doorbell of the other end and reads wait for its own and take
+//! 1. Consumers wait on theirs while the ring is empty and producers while
+//! it is full, and each rings the other only if the header counts someone
+//! waiting, which is for the processes to keep. Either end maps the ring
+//! with mmap, MAP_SHARED.
+
+use super::{EventFd, File, Inode, OpenFlags, Stat};
+use crate::config::PAGE_SIZE;
+use crate::mm::{SharedMemory, UserBuffer};
+use crate::sync::UPIntrFreeCell;
+use alloc::sync::Arc;
+use core::any::Any;
+
+/// Offsets into the header page, each field on a cache line of its own.
+const RING_ENTRIES: usize = 0;
+const RING_ENTRY_SIZE: usize = 8;
+/// The position the next message is sent at.
+const RING_TAIL: usize = 64;
+/// The position the next message is received from.
+const RING_HEAD: usize = 128;
+/// A slot is a sequence number, the length of its message and the message.
+const SLOT_HEADER: usize = 16;
+/// The most entries of a ring, a power of two as all their numbers are.
+const RING_ENTRIES_MAX: usize = 4096;
+/// The most bytes of a ring, with its header.
+const RING_SIZE_MAX: usize = 1 << 20;
+
+/// The bytes of a ring of `entries` slots of `entry_size`, rounded up to
+/// pages; None if that is not a ring.
+pub fn ring_size(entries: usize, entry_size: usize) -> Option<usize> {
+    if !entries.is_power_of_two()
+        || entries > RING_ENTRIES_MAX
+        || entry_size == 0
+        || entry_size % 8 != 0
+    {
+        return None;
+    }
+    let size = entries
+        .checked_mul(SLOT_HEADER + entry_size)?
+        .div_ceil(PAGE_SIZE)
+        * PAGE_SIZE
+        + PAGE_SIZE;
+    (size <= RING_SIZE_MAX).then_some(size)
+}
+
+/// The memory of a ring, as mmap sees it.
+struct RingInode {
+    object: Arc<SharedMemory>,
+    size: usize,
+}
+
+impl RingInode {
+    fn write_u64(&self, offset: usize, value: u64) {
+        // fields are aligned, none crosses a page
+        let frame = self.object.page(offset / PAGE_SIZE).unwrap();
+        let start = offset % PAGE_SIZE;
+        frame.ppn.get_bytes_array()[start..start + 8].copy_from_slice(&value.to_ne_bytes());
+    }
+}
+
+impl Inode for RingInode {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn is_dir(&self) -> bool {
+        false
+    }
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let end = (offset + buf.len()).min(self.size);
+        let mut pos = offset;
+        while pos < end {
+            let page_offset = pos % PAGE_SIZE;
+            let len = (PAGE_SIZE - page_offset).min(end - pos);
+            let frame = match self.object.page(pos / PAGE_SIZE) {
+                Some(frame) => frame,
+                None => break,
+            };
+            buf[pos - offset..pos - offset + len]
+                .copy_from_slice(&frame.ppn.get_bytes_array()[page_offset..page_offset + len]);
+            pos += len;
+        }
+        pos.saturating_sub(offset)
+    }
+    /// Messages go through the mapping, not the file.
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> usize {
+        0
+    }
+    fn size(&self) -> usize {
+        self.size
+    }
+    fn stat(&self) -> Stat {
+        let mut stat = Stat::new(0o600);
+        stat.size = self.size as i64;
+        stat
+    }
+    fn shared_memory(&self) -> Option<Arc<SharedMemory>> {
+        Some(self.object.clone())
+    }
+}
+
+/// An end of a ring, of its producers or of its consumers.
+pub struct RingEnd {
+    inode: Arc<RingInode>,
+    /// rung by the other end
+    own: Arc<EventFd>,
+    /// of the other end, rung by this one
+    other: Arc<EventFd>,
+    /// O_NONBLOCK, if set
+    status: UPIntrFreeCell<OpenFlags>,
+}
+
+impl RingEnd {
+    /// The bytes of the ring, for the mapping made with it.
+    pub fn size(&self) -> usize {
+        self.inode.size
+    }
+
+    pub fn shared_memory(&self) -> Arc<SharedMemory> {
+        self.inode.object.clone()
+    }
+}
+
+/// A ring of `entries` slots of `entry_size` bytes, which `ring_size`
+/// takes, with every slot free: its consumer end and its producer end.
+/// None if there is no memory for it.
+pub fn make_ring(
+    entries: usize,
+    entry_size: usize,
+    flags: OpenFlags,
+) -> Option<(Arc<RingEnd>, Arc<RingEnd>)> {
+    let inode = Arc::new(RingInode {
+        object: SharedMemory::new_anonymous(),
+        size: ring_size(entries, entry_size)?,
+    });
+    inode.write_u64(RING_ENTRIES, entries as u64);
+    inode.write_u64(RING_ENTRY_SIZE, entry_size as u64);
+    inode.write_u64(RING_TAIL, 0);
+    inode.write_u64(RING_HEAD, 0);
+    // slot i is free for the message sent at position i
+    for i in 0..entries {
+        let offset = PAGE_SIZE + i * (SLOT_HEADER + entry_size);
+        if inode.object.page(offset / PAGE_SIZE).is_none() {
+            return None;
+        }
+        inode.write_u64(offset, i as u64);
+    }
+    let has_messages = EventFd::new(0, true, OpenFlags::empty());
+    let has_room = EventFd::new(0, true, OpenFlags::empty());
+    let end = |own: &Arc<EventFd>, other: &Arc<EventFd>| {
+        Arc::new(RingEnd {
+            inode: inode.clone(),
+            own: own.clone(),
+            other: other.clone(),
+            status: unsafe { UPIntrFreeCell::new(flags & OpenFlags::NONBLOCK) },
+        })
+    };
+    Some((end(&has_messages, &has_room), end(&has_room, &has_messages)))
+}
+
+impl File for RingEnd {
+    fn readable(&self) -> bool {
+        true
+    }
+    fn writable(&self) -> bool {
+        true
+    }
+    /// Wait for the doorbell of this end and take 1 of its count.
+    fn read(&self, buf: UserBuffer) -> usize {
+        self.own.read(buf)
+    }
+    /// Ring the doorbell of the other end, adding the value to its count.
+    fn write(&self, buf: UserBuffer) -> usize {
+        self.other.write(buf)
+    }
+    fn status(&self) -> OpenFlags {
+        *self.status.exclusive_access()
+    }
+    fn set_status(&self, status: OpenFlags) {
+        *self.status.exclusive_access() = status & OpenFlags::NONBLOCK;
+    }
+    fn read_ready(&self) -> bool {
+        self.own.read_ready()
+    }
+    fn write_ready(&self) -> bool {
+        self.other.write_ready()
+    }
+    fn stat(&self) -> Stat {
+        self.inode.stat()
+    }
+    fn inode(&self) -> Option<Arc<dyn Inode>> {
+        Some(self.inode.clone())
+    }
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
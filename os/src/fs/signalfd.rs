@@ -0,0 +1,157 @@
+//! signalfd, signals taken by reading a file rather than delivered to a
+//! handler, for an event loop to wait for them with ppoll or epoll along
+//! with its other files. The signals it takes are those of the thread
+//! which reads, and are meant to be blocked, not to be delivered first.
+
+use super::{File, OpenFlags, Stat};
+use crate::mm::UserBuffer;
+use crate::sync::UPIntrFreeCell;
+use crate::task::{
+    has_signal, signal_pending, suspend_current_and_run_next, take_signal, SigInfo, SignalFlags,
+};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::mem::size_of;
+use core::slice;
+
+/// `struct signalfd_siginfo`, one of which a read gives for each signal.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SignalfdSiginfo {
+    signo: u32,
+    errno: i32,
+    code: i32,
+    pid: u32,
+    uid: u32,
+    fd: i32,
+    tid: u32,
+    band: u32,
+    overrun: u32,
+    trapno: u32,
+    status: i32,
+    int: i32,
+    ptr: u64,
+    utime: u64,
+    stime: u64,
+    addr: u64,
+    addr_lsb: u16,
+    _pad: [u8; 46],
+}
+
+impl SignalfdSiginfo {
+    fn new(info: &SigInfo) -> Self {
+        Self {
+            signo: info.signo as u32,
+            errno: info.errno,
+            code: info.code,
+            pid: info.pid as u32,
+            uid: info.uid,
+            fd: 0,
+            tid: info.timerid as u32,
+            band: 0,
+            overrun: info.overrun as u32,
+            trapno: 0,
+            status: 0,
+            int: info.value as i32,
+            ptr: info.value as u64,
+            utime: 0,
+            stime: 0,
+            addr: info.addr as u64,
+            addr_lsb: 0,
+            _pad: [0; 46],
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self as *const _ as *const u8, size_of::<Self>()) }
+    }
+}
+
+pub struct SignalFd {
+    inner: UPIntrFreeCell<SignalFdInner>,
+}
+
+struct SignalFdInner {
+    /// the signals it takes
+    mask: SignalFlags,
+    /// O_NONBLOCK, if set
+    status: OpenFlags,
+}
+
+impl SignalFd {
+    pub fn new(mask: SignalFlags, status: OpenFlags) -> Arc<Self> {
+        Arc::new(Self {
+            inner: unsafe {
+                UPIntrFreeCell::new(SignalFdInner {
+                    mask,
+                    status: status & OpenFlags::NONBLOCK,
+                })
+            },
+        })
+    }
+
+    pub fn set_mask(&self, mask: SignalFlags) {
+        self.inner.exclusive_access().mask = mask;
+    }
+
+    fn mask(&self) -> SignalFlags {
+        self.inner.exclusive_access().mask
+    }
+}
+
+impl File for SignalFd {
+    fn readable(&self) -> bool {
+        true
+    }
+    fn writable(&self) -> bool {
+        false
+    }
+    /// Wait for a signal, then take as many as there are whole records of
+    /// room for; 0 if `buf` has none.
+    fn read(&self, buf: UserBuffer) -> usize {
+        let room = buf.len() / size_of::<SignalfdSiginfo>();
+        if room == 0 {
+            return 0;
+        }
+        // sys_read has turned away those who may not wait
+        while !has_signal(self.mask()) {
+            if signal_pending() {
+                return 0;
+            }
+            suspend_current_and_run_next();
+        }
+        let mut records = Vec::new();
+        for _ in 0..room {
+            match take_signal(self.mask()) {
+                Some(info) => records.extend_from_slice(SignalfdSiginfo::new(&info).as_bytes()),
+                None => break,
+            }
+        }
+        let mut copied = 0;
+        for slice in buf.buffers {
+            let len = slice.len().min(records.len() - copied);
+            slice[..len].copy_from_slice(&records[copied..copied + len]);
+            copied += len;
+        }
+        copied
+    }
+    fn write(&self, _buf: UserBuffer) -> usize {
+        0
+    }
+    fn status(&self) -> OpenFlags {
+        self.inner.exclusive_access().status
+    }
+    fn set_status(&self, status: OpenFlags) {
+        self.inner.exclusive_access().status = status & OpenFlags::NONBLOCK;
+    }
+    fn read_ready(&self) -> bool {
+        has_signal(self.mask())
+    }
+    fn stat(&self) -> Stat {
+        Stat::new(0o600)
+    }
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
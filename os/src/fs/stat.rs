@@ -0,0 +1,49 @@
+//! File metadata, as fstat and stat return it.
+
+/// The bits of `mode` giving the file type.
+pub const S_IFMT: u32 = 0o170000;
+pub const S_IFSOCK: u32 = 0o140000;
+pub const S_IFLNK: u32 = 0o120000;
+pub const S_IFREG: u32 = 0o100000;
+pub const S_IFDIR: u32 = 0o040000;
+pub const S_IFCHR: u32 = 0o020000;
+pub const S_IFIFO: u32 = 0o010000;
+
+/// `struct stat` of RISC-V Linux. The times stay zero until there is a
+/// real-time clock to take them from, but for those easy-fs images keep
+/// from the host.
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+pub struct Stat {
+    pub dev: u64,
+    pub ino: u64,
+    /// file type and permissions
+    pub mode: u32,
+    pub nlink: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub rdev: u64,
+    __pad1: u64,
+    pub size: i64,
+    pub blksize: i32,
+    __pad2: i32,
+    /// 512-byte blocks allocated
+    pub blocks: i64,
+    pub atime: i64,
+    pub atime_nsec: u64,
+    pub mtime: i64,
+    pub mtime_nsec: u64,
+    pub ctime: i64,
+    pub ctime_nsec: u64,
+    __unused: [u32; 2],
+}
+
+impl Stat {
+    pub fn new(mode: u32) -> Self {
+        Self {
+            mode,
+            nlink: 1,
+            ..Default::default()
+        }
+    }
+}
@@ -1,11 +1,24 @@
-use super::File;
+use super::tty::{tty_ioctl, wait_input};
+use super::{File, OpenFlags, Stat, S_IFCHR};
 use crate::drivers::chardev::CharDevice;
 use crate::drivers::chardev::UART;
 use crate::mm::UserBuffer;
+use crate::sync::UPIntrFreeCell;
 
-pub struct Stdin;
+pub struct Stdin {
+    /// O_NONBLOCK, if set
+    status: UPIntrFreeCell<OpenFlags>,
+}
 pub struct Stdout;
 
+impl Stdin {
+    pub fn new() -> Self {
+        Self {
+            status: unsafe { UPIntrFreeCell::new(OpenFlags::empty()) },
+        }
+    }
+}
+
 impl File for Stdin {
     fn readable(&self) -> bool {
         true
@@ -13,8 +26,23 @@ impl File for Stdin {
     fn writable(&self) -> bool {
         false
     }
+    fn stat(&self) -> Stat {
+        Stat::new(S_IFCHR | 0o620)
+    }
+    fn status(&self) -> OpenFlags {
+        *self.status.exclusive_access()
+    }
+    fn set_status(&self, status: OpenFlags) {
+        *self.status.exclusive_access() = status & OpenFlags::NONBLOCK;
+    }
+    fn read_ready(&self) -> bool {
+        !UART.read_buffer_is_empty()
+    }
     fn read(&self, mut user_buf: UserBuffer) -> usize {
         assert_eq!(user_buf.len(), 1);
+        if !wait_input() {
+            return 0;
+        }
         //println!("before UART.read() in Stdin::read()");
         let ch = UART.read();
         unsafe {
@@ -25,6 +53,9 @@ impl File for Stdin {
     fn write(&self, _user_buf: UserBuffer) -> usize {
         panic!("Cannot write to stdin!");
     }
+    fn ioctl(&self, cmd: usize, arg: usize) -> isize {
+        tty_ioctl(cmd, arg)
+    }
 }
 
 impl File for Stdout {
@@ -34,6 +65,9 @@ impl File for Stdout {
     fn writable(&self) -> bool {
         true
     }
+    fn stat(&self) -> Stat {
+        Stat::new(S_IFCHR | 0o620)
+    }
     fn read(&self, _user_buf: UserBuffer) -> usize {
         panic!("Cannot read from stdout!");
     }
@@ -0,0 +1,154 @@
+//! timerfd, a timer whose expirations are read from a file descriptor, as
+//! a count of those since the last read; it is readable, for poll too,
+//! while the count is not 0.
+//!
+//! Times are in ns, as the kernel's timers keep them, counted from boot for
+//! CLOCK_MONOTONIC and CLOCK_REALTIME alike as there is no clock of the
+//! time of day.
+
+use super::eventfd::write_value;
+use super::{File, OpenFlags, Stat};
+use crate::mm::UserBuffer;
+use crate::sync::UPIntrFreeCell;
+use crate::task::{
+    block_current_and_run_next, current_task, signal_pending, suspend_current_and_run_next,
+};
+use crate::timer::{add_timer, ktime_now};
+use alloc::sync::Arc;
+use core::any::Any;
+
+pub struct TimerFd {
+    inner: UPIntrFreeCell<TimerFdInner>,
+}
+
+struct TimerFdInner {
+    /// of the next expiration, if armed
+    deadline: Option<u64>,
+    /// between expirations, 0 for once
+    interval: u64,
+    /// since the last read
+    expirations: u64,
+    /// O_NONBLOCK, if set
+    status: OpenFlags,
+}
+
+impl TimerFdInner {
+    /// Count the expirations up to now.
+    fn update(&mut self) {
+        let now = ktime_now();
+        let deadline = match self.deadline {
+            Some(deadline) if deadline <= now => deadline,
+            _ => return,
+        };
+        if self.interval == 0 {
+            self.expirations += 1;
+            self.deadline = None;
+        } else {
+            let expired = (now - deadline) / self.interval + 1;
+            self.expirations += expired;
+            self.deadline = Some(deadline + expired * self.interval);
+        }
+    }
+
+    /// The ns left to the next expiration, 0 if disarmed, and the interval.
+    fn get(&self) -> (u64, u64) {
+        let left = self
+            .deadline
+            .map_or(0, |deadline| deadline.saturating_sub(ktime_now()));
+        (left, self.interval)
+    }
+}
+
+impl TimerFd {
+    pub fn new(status: OpenFlags) -> Arc<Self> {
+        Arc::new(Self {
+            inner: unsafe {
+                UPIntrFreeCell::new(TimerFdInner {
+                    deadline: None,
+                    interval: 0,
+                    expirations: 0,
+                    status: status & OpenFlags::NONBLOCK,
+                })
+            },
+        })
+    }
+
+    /// Arm the timer to expire at `deadline`, then every `interval` ns if
+    /// that is not 0, or disarm it with None; what it was set to before,
+    /// as `get` says.
+    pub fn set(&self, deadline: Option<u64>, interval: u64) -> (u64, u64) {
+        let mut inner = self.inner.exclusive_access();
+        inner.update();
+        let old = inner.get();
+        inner.deadline = deadline;
+        inner.interval = interval;
+        inner.expirations = 0;
+        old
+    }
+
+    /// The ns left to the next expiration, 0 if disarmed, and the interval.
+    pub fn get(&self) -> (u64, u64) {
+        let mut inner = self.inner.exclusive_access();
+        inner.update();
+        inner.get()
+    }
+}
+
+impl File for TimerFd {
+    fn readable(&self) -> bool {
+        true
+    }
+    fn writable(&self) -> bool {
+        false
+    }
+    /// Wait for an expiration, then take the count of them; 0 if `buf`
+    /// has no room for it.
+    fn read(&self, mut buf: UserBuffer) -> usize {
+        if buf.len() < 8 {
+            return 0;
+        }
+        let expirations = loop {
+            let mut inner = self.inner.exclusive_access();
+            inner.update();
+            if inner.expirations > 0 {
+                break core::mem::take(&mut inner.expirations);
+            }
+            // sys_read has turned away those who may not wait
+            let deadline = inner.deadline;
+            drop(inner);
+            if signal_pending() {
+                return 0;
+            }
+            match deadline {
+                // and look again then, as the timer may have been set since
+                Some(deadline) => {
+                    add_timer(deadline, current_task().unwrap());
+                    block_current_and_run_next();
+                }
+                None => suspend_current_and_run_next(),
+            }
+        };
+        write_value(&mut buf, expirations);
+        8
+    }
+    fn write(&self, _buf: UserBuffer) -> usize {
+        0
+    }
+    fn status(&self) -> OpenFlags {
+        self.inner.exclusive_access().status
+    }
+    fn set_status(&self, status: OpenFlags) {
+        self.inner.exclusive_access().status = status & OpenFlags::NONBLOCK;
+    }
+    fn read_ready(&self) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        inner.update();
+        inner.expirations > 0
+    }
+    fn stat(&self) -> Stat {
+        Stat::new(0o600)
+    }
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
@@ -0,0 +1,353 @@
+//! tmpfs, files and directories kept in memory only, mounted on /tmp, and
+//! on / with the initramfs unpacked into it.
+//!
+//! The contents of a file are anonymous pages, allocated as they are first
+//! written and freed when the file is truncated or its last name and user
+//! are gone. MAP_SHARED maps these very pages rather than copies. FIFOs
+//! hold nothing themselves. Each
+//! mount is a new, empty file system, which is lost when unmounted.
+
+use super::stat::{Stat, S_IFDIR, S_IFIFO, S_IFREG, S_IFSOCK};
+use super::vfs::{DirEntry, FileSystem, Inode};
+use crate::config::PAGE_SIZE;
+use crate::mm::SharedMemory;
+use crate::objects::{Live, OBJ_INODE};
+use crate::sync::UPIntrFreeCell;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// Device numbers of the tmpfs mounted so far, and inode numbers of all
+/// of their inodes.
+static NEXT_DEV: AtomicU64 = AtomicU64::new(1);
+static NEXT_INO: AtomicU64 = AtomicU64::new(1);
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains('/')
+}
+
+pub struct TmpInode {
+    /// the tmpfs this inode belongs to
+    dev: u64,
+    ino: u64,
+    /// S_IFREG, S_IFDIR, S_IFIFO or S_IFSOCK
+    file_type: u32,
+    /// for regular files only
+    pages: Option<Arc<SharedMemory>>,
+    _live: Live<OBJ_INODE>,
+    inner: UPIntrFreeCell<TmpInodeInner>,
+}
+
+struct TmpInodeInner {
+    size: usize,
+    /// names of a file; directories count their subdirectories instead
+    nlink: u32,
+    /// permission bits
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    entries: BTreeMap<String, Arc<dyn Inode>>,
+    /// the names of the entries by their offsets for getdents, which
+    /// follow the order they came in
+    offsets: BTreeMap<usize, String>,
+    next_offset: usize,
+}
+
+impl TmpInodeInner {
+    /// Add the entry `name`, in place of any entry of that name.
+    fn insert(&mut self, name: &str, inode: Arc<dyn Inode>) {
+        self.remove(name);
+        self.offsets.insert(self.next_offset, String::from(name));
+        self.next_offset += 1;
+        self.entries.insert(String::from(name), inode);
+    }
+
+    fn remove(&mut self, name: &str) -> Option<Arc<dyn Inode>> {
+        let inode = self.entries.remove(name)?;
+        self.offsets.retain(|_, entry| entry != name);
+        Some(inode)
+    }
+}
+
+impl TmpInode {
+    fn new(dev: u64, file_type: u32) -> Arc<Self> {
+        Arc::new(Self {
+            dev,
+            ino: NEXT_INO.fetch_add(1, Ordering::Relaxed),
+            file_type,
+            pages: if file_type == S_IFREG {
+                Some(SharedMemory::new_anonymous())
+            } else {
+                None
+            },
+            _live: Live::default(),
+            inner: unsafe {
+                UPIntrFreeCell::new(TmpInodeInner {
+                    size: 0,
+                    nlink: 1,
+                    mode: if file_type == S_IFDIR { 0o755 } else { 0o644 },
+                    uid: 0,
+                    gid: 0,
+                    entries: BTreeMap::new(),
+                    offsets: BTreeMap::new(),
+                    next_offset: 0,
+                })
+            },
+        })
+    }
+
+    /// `inode` if it is of the same tmpfs as this one.
+    fn same_fs<'a>(&self, inode: &'a Arc<dyn Inode>) -> Option<&'a TmpInode> {
+        inode
+            .as_any()
+            .downcast_ref::<TmpInode>()
+            .filter(|inode| inode.dev == self.dev)
+    }
+
+    /// Add the new entry `name`, of `file_type`, to this directory.
+    fn add(&self, name: &str, file_type: u32) -> Option<Arc<dyn Inode>> {
+        if !self.is_dir() || !valid_name(name) {
+            return None;
+        }
+        let mut inner = self.inner.exclusive_access();
+        if inner.entries.contains_key(name) {
+            return None;
+        }
+        let inode: Arc<dyn Inode> = TmpInode::new(self.dev, file_type);
+        inner.insert(name, Arc::clone(&inode));
+        Some(inode)
+    }
+
+    /// Remove the entry `name`, a directory or not as `is_dir` says.
+    fn remove(&self, name: &str, is_dir: bool) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        let inode = match inner.entries.get(name) {
+            Some(inode) if inode.is_dir() == is_dir => Arc::clone(inode),
+            _ => return false,
+        };
+        let tmp_inode = self.same_fs(&inode).unwrap();
+        if is_dir && !tmp_inode.inner.exclusive_access().entries.is_empty() {
+            return false;
+        }
+        inner.remove(name);
+        drop(inner);
+        tmp_inode.inner.exclusive_access().nlink -= 1;
+        true
+    }
+}
+
+impl Inode for TmpInode {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn is_dir(&self) -> bool {
+        self.file_type == S_IFDIR
+    }
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let pages = match &self.pages {
+            Some(pages) => pages,
+            None => return 0,
+        };
+        let end = (offset + buf.len()).min(self.size());
+        let mut pos = offset;
+        while pos < end {
+            let page_offset = pos % PAGE_SIZE;
+            let len = (PAGE_SIZE - page_offset).min(end - pos);
+            let dst = &mut buf[pos - offset..pos - offset + len];
+            match pages.get(pos / PAGE_SIZE) {
+                Some(frame) => dst
+                    .copy_from_slice(&frame.ppn.get_bytes_array()[page_offset..page_offset + len]),
+                // never written
+                None => dst.fill(0),
+            }
+            pos += len;
+        }
+        pos.saturating_sub(offset)
+    }
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        let pages = match &self.pages {
+            Some(pages) => pages,
+            None => return 0,
+        };
+        let end = offset + buf.len();
+        let mut pos = offset;
+        while pos < end {
+            let page_offset = pos % PAGE_SIZE;
+            let len = (PAGE_SIZE - page_offset).min(end - pos);
+            // out of memory, which leaves the write short
+            let frame = match pages.page(pos / PAGE_SIZE) {
+                Some(frame) => frame,
+                None => break,
+            };
+            frame.ppn.get_bytes_array()[page_offset..page_offset + len]
+                .copy_from_slice(&buf[pos - offset..pos - offset + len]);
+            pos += len;
+        }
+        if pos > offset {
+            let mut inner = self.inner.exclusive_access();
+            inner.size = inner.size.max(pos);
+        }
+        pos - offset
+    }
+    fn size(&self) -> usize {
+        self.inner.exclusive_access().size
+    }
+    fn stat(&self) -> Stat {
+        let inner = self.inner.exclusive_access();
+        let mut stat = Stat::new(self.file_type | inner.mode);
+        if self.is_dir() {
+            let subdirs = inner.entries.values().filter(|inode| inode.is_dir());
+            stat.nlink = 2 + subdirs.count() as u32;
+        } else {
+            stat.nlink = inner.nlink;
+        }
+        if let Some(pages) = &self.pages {
+            stat.blocks = (pages.page_count() * PAGE_SIZE / 512) as i64;
+        }
+        stat.dev = self.dev;
+        stat.ino = self.ino;
+        stat.uid = inner.uid;
+        stat.gid = inner.gid;
+        stat.size = inner.size as i64;
+        stat.blksize = PAGE_SIZE as i32;
+        stat
+    }
+    fn find(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        self.inner.exclusive_access().entries.get(name).cloned()
+    }
+    fn create(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        self.add(name, S_IFREG)
+    }
+    fn mkdir(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        self.add(name, S_IFDIR)
+    }
+    fn mkfifo(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        self.add(name, S_IFIFO)
+    }
+    fn mksock(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        self.add(name, S_IFSOCK)
+    }
+    fn rmdir(&self, name: &str) -> bool {
+        self.remove(name, true)
+    }
+    fn unlink(&self, name: &str) -> bool {
+        self.remove(name, false)
+    }
+    fn link(&self, name: &str, inode: &Arc<dyn Inode>) -> bool {
+        let tmp_inode = match self.same_fs(inode) {
+            Some(tmp_inode) if !tmp_inode.is_dir() => tmp_inode,
+            _ => return false,
+        };
+        if !self.is_dir() || !valid_name(name) {
+            return false;
+        }
+        let mut inner = self.inner.exclusive_access();
+        if inner.entries.contains_key(name) {
+            return false;
+        }
+        inner.insert(name, Arc::clone(inode));
+        drop(inner);
+        tmp_inode.inner.exclusive_access().nlink += 1;
+        true
+    }
+    /// Replace a file at `new_name`, as easy-fs does.
+    fn rename(&self, old_name: &str, new_dir: &Arc<dyn Inode>, new_name: &str) -> bool {
+        let new_dir = match self.same_fs(new_dir) {
+            Some(new_dir) if new_dir.is_dir() => new_dir,
+            _ => return false,
+        };
+        if !valid_name(new_name) {
+            return false;
+        }
+        let inode = match self.find(old_name) {
+            Some(inode) => inode,
+            None => return false,
+        };
+        if let Some(target) = new_dir.find(new_name) {
+            // two names of the same file
+            if Arc::as_ptr(&target) as *const u8 == Arc::as_ptr(&inode) as *const u8 {
+                return true;
+            }
+            if inode.is_dir() || target.is_dir() {
+                return false;
+            }
+            self.same_fs(&target)
+                .unwrap()
+                .inner
+                .exclusive_access()
+                .nlink -= 1;
+        }
+        self.inner.exclusive_access().remove(old_name);
+        new_dir.inner.exclusive_access().insert(new_name, inode);
+        true
+    }
+    fn ls(&self) -> Vec<String> {
+        self.inner
+            .exclusive_access()
+            .entries
+            .keys()
+            .cloned()
+            .collect()
+    }
+    fn read_dir(&self, offset: usize) -> Option<(DirEntry, usize)> {
+        let inner = self.inner.exclusive_access();
+        let (&offset, name) = inner.offsets.range(offset..).next()?;
+        let inode = Arc::clone(&inner.entries[name]);
+        let name = name.clone();
+        drop(inner);
+        let stat = inode.stat();
+        Some((DirEntry::new(stat.ino, stat.mode, name), offset + 1))
+    }
+    fn clear(&self) {
+        if let Some(pages) = &self.pages {
+            pages.truncate(0);
+            self.inner.exclusive_access().size = 0;
+        }
+    }
+    fn shared_memory(&self) -> Option<Arc<SharedMemory>> {
+        self.pages.clone()
+    }
+    fn chmod(&self, mode: u32) -> bool {
+        self.inner.exclusive_access().mode = mode & 0o7777;
+        true
+    }
+    fn chown(&self, uid: u32, gid: u32) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        inner.uid = uid;
+        inner.gid = gid;
+        true
+    }
+}
+
+pub struct TmpFs {
+    root: Arc<TmpInode>,
+}
+
+impl FileSystem for TmpFs {
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+}
+
+/// A new tmpfs, empty.
+pub fn new_tmpfs() -> Arc<TmpFs> {
+    let dev = NEXT_DEV.fetch_add(1, Ordering::Relaxed);
+    Arc::new(TmpFs {
+        root: TmpInode::new(dev, S_IFDIR),
+    })
+}
+
+/// A new tmpfs each time, whatever the source.
+fn mount(_source: &str) -> Option<Arc<dyn FileSystem>> {
+    let fs = new_tmpfs();
+    // anyone may make files in /tmp
+    fs.root.chmod(0o777);
+    Some(fs)
+}
+
+pub fn init() {
+    super::register_filesystem("tmpfs", mount);
+}
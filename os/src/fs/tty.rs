@@ -0,0 +1,91 @@
+//! The line discipline of the console: ^C and ^Z typed on it are not read
+//! but send SIGINT and SIGTSTP to its foreground process group, which a
+//! shell sets with the TIOCSPGRP ioctl for the job it waits for. While
+//! there is none, they are read as any other byte.
+
+use crate::drivers::chardev::UART;
+use crate::mm::UserPtr;
+use crate::sync::UPIntrFreeCell;
+use crate::syscall::EFAULT;
+use crate::task::{
+    current_user_token, group_processes, send_signal, signal_pending, SigInfo, IDLE_PID, SIGINT,
+    SIGTSTP, SI_KERNEL,
+};
+use lazy_static::*;
+
+const TIOCGPGRP: usize = 0x540f;
+const TIOCSPGRP: usize = 0x5410;
+
+lazy_static! {
+    /// the process group of the console
+    static ref FOREGROUND: UPIntrFreeCell<Option<usize>> = unsafe { UPIntrFreeCell::new(None) };
+}
+
+/// The signal `ch` received by the UART stands for, if there is a
+/// foreground group, which has not all exited, to send it to.
+pub fn control_signal(ch: u8) -> Option<usize> {
+    let signo = match ch {
+        0x03 => SIGINT,
+        0x1a => SIGTSTP,
+        _ => return None,
+    };
+    let pgid = (*FOREGROUND.exclusive_access())?;
+    (!group_processes(pgid).is_empty()).then_some(signo)
+}
+
+/// Send `signo` to the processes of the foreground group.
+pub fn signal_foreground(signo: usize) {
+    let pgid = match *FOREGROUND.exclusive_access() {
+        Some(pgid) => pgid,
+        None => return,
+    };
+    for process in group_processes(pgid) {
+        if process.getpid() != IDLE_PID {
+            send_signal(&process, SigInfo::new(signo, SI_KERNEL, 0, 0));
+        }
+    }
+}
+
+/// Wait for input on the console; false if a signal ends the wait.
+pub(super) fn wait_input() -> bool {
+    while UART.read_buffer_is_empty() {
+        if signal_pending() {
+            return false;
+        }
+        // woken by ^C or ^Z too
+        UART.wait_input();
+    }
+    true
+}
+
+/// The ioctls of the console: TIOCGPGRP and TIOCSPGRP, of the foreground
+/// group, which must have a process in it.
+pub(super) fn tty_ioctl(cmd: usize, arg: usize) -> isize {
+    let token = current_user_token();
+    match cmd {
+        TIOCGPGRP => {
+            let pgid = match *FOREGROUND.exclusive_access() {
+                Some(pgid) => pgid,
+                None => return -1,
+            };
+            match UserPtr::new(token, arg as *mut i32).write(pgid as i32) {
+                Some(()) => 0,
+                None => EFAULT,
+            }
+        }
+        TIOCSPGRP => {
+            let pgid = match UserPtr::new(token, arg as *const i32).read() {
+                Some(pgid) => pgid,
+                None => return EFAULT,
+            };
+            match usize::try_from(pgid) {
+                Ok(pgid) if !group_processes(pgid).is_empty() => {
+                    *FOREGROUND.exclusive_access() = Some(pgid);
+                    0
+                }
+                _ => -1,
+            }
+        }
+        _ => -1,
+    }
+}
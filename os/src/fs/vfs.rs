@@ -0,0 +1,667 @@
+//! The virtual file system: the file systems mounted into one tree.
+//!
+//! A file system hands out `Inode`s, starting from its root. The mount table
+//! grafts the root of each mounted file system onto a directory of the tree,
+//! hiding what was there until it is unmounted. Path resolution looks every
+//! prefix of the path up in the table, and so crosses into mounted file
+//! systems on the way down.
+//!
+//! Absolute paths are resolved from the root, relative ones from the
+//! working directory of the current process, which is kept as a physical
+//! path and so walked from the root as well. `..` goes back along the path
+//! walked so far, out of a mounted file system to
+//! the directory it is mounted on. A symbolic link puts its target in place
+//! of itself in the path, to be walked from the root if it is absolute and
+//! from the directory of the link otherwise; the mount table is kept by the
+//! physical paths this leads to.
+//!
+//! Walking through a directory takes the permission to search it, and
+//! changing its entries the permission to write it as well, by the owner,
+//! group and mode of the directory against those of the current process.
+//! Root may do anything but run what nobody may.
+
+use super::inotify::{
+    notify, notify_deleted, notify_rename, same_inode, IN_ATTRIB, IN_CREATE, IN_DELETE, IN_ISDIR,
+};
+use super::stat::{Stat, S_IFMT};
+use crate::mm::{FrameTracker, SharedMemory};
+use crate::sync::UPIntrFreeCell;
+use crate::task::current_task;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::any::Any;
+use lazy_static::*;
+
+/// An entry of a directory, as getdents reports it.
+pub struct DirEntry {
+    pub ino: u64,
+    /// the file type bits of the mode
+    pub file_type: u32,
+    pub name: String,
+}
+
+impl DirEntry {
+    pub fn new(ino: u64, mode: u32, name: String) -> Self {
+        Self {
+            ino,
+            file_type: mode & S_IFMT,
+            name,
+        }
+    }
+}
+
+/// A file or directory of some file system.
+pub trait Inode: Send + Sync {
+    /// For a file system to recognize its own inodes among others.
+    fn as_any(&self) -> &dyn Any;
+    fn is_dir(&self) -> bool;
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize;
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize;
+    fn size(&self) -> usize;
+    fn stat(&self) -> Stat;
+    /// Write `buf` at the end and return where that was. Writers appending
+    /// at the same time should not overwrite each other.
+    fn append(&self, buf: &[u8]) -> usize {
+        let offset = self.size();
+        self.write_at(offset, buf);
+        offset
+    }
+    /// The entry `name` of this directory.
+    fn find(&self, _name: &str) -> Option<Arc<dyn Inode>> {
+        None
+    }
+    /// A new file `name` in this directory, or None if it exists already.
+    fn create(&self, _name: &str) -> Option<Arc<dyn Inode>> {
+        None
+    }
+    /// A new directory `name` in this directory.
+    fn mkdir(&self, _name: &str) -> Option<Arc<dyn Inode>> {
+        None
+    }
+    /// A new symbolic link `name` in this directory, pointing at `target`.
+    fn symlink(&self, _name: &str, _target: &str) -> Option<Arc<dyn Inode>> {
+        None
+    }
+    /// A new named pipe `name` in this directory.
+    fn mkfifo(&self, _name: &str) -> Option<Arc<dyn Inode>> {
+        None
+    }
+    /// A new socket `name` in this directory, for AF_UNIX to bind to.
+    fn mksock(&self, _name: &str) -> Option<Arc<dyn Inode>> {
+        None
+    }
+    /// Where a symbolic link points, None for anything else.
+    fn readlink(&self) -> Option<String> {
+        None
+    }
+    /// Remove the empty directory `name` from this directory.
+    fn rmdir(&self, _name: &str) -> bool {
+        false
+    }
+    /// Remove the entry `name` of a file from this directory.
+    fn unlink(&self, _name: &str) -> bool {
+        false
+    }
+    /// Name the file `inode` `name` in this directory as well.
+    fn link(&self, _name: &str, _inode: &Arc<dyn Inode>) -> bool {
+        false
+    }
+    /// Move the entry `old_name` of this directory to `new_name` in
+    /// `new_dir`, which is of the same file system.
+    fn rename(&self, _old_name: &str, _new_dir: &Arc<dyn Inode>, _new_name: &str) -> bool {
+        false
+    }
+    /// The names of the entries of this directory.
+    fn ls(&self) -> Vec<String> {
+        Vec::new()
+    }
+    /// The first entry of this directory at `offset` or after it, and the
+    /// offset to go on from, for getdents. An entry keeps its offset while
+    /// it is there, so others coming and going do not make a reader skip
+    /// it or see it twice. The default takes the index in `ls`, which does
+    /// for directories whose entries never change.
+    fn read_dir(&self, offset: usize) -> Option<(DirEntry, usize)> {
+        let name = self.ls().into_iter().nth(offset)?;
+        let stat = self
+            .find(&name)
+            .map(|inode| inode.stat())
+            .unwrap_or_default();
+        Some((DirEntry::new(stat.ino, stat.mode, name), offset + 1))
+    }
+    /// Set the permission bits to the low 12 bits of `mode`.
+    fn chmod(&self, _mode: u32) -> bool {
+        false
+    }
+    fn chown(&self, _uid: u32, _gid: u32) -> bool {
+        false
+    }
+    /// Truncate the file to nothing.
+    fn clear(&self) {}
+    /// Where the file is in its file system, telling it apart from the
+    /// other files there, if the file system knows.
+    fn position(&self) -> Option<(usize, usize)> {
+        None
+    }
+    /// Write what is cached of the file back to its device.
+    fn sync(&self) {}
+    /// Page `index` of the page cache, read in if it is not there yet, for
+    /// MAP_SHARED to map; None past the end or if the file is not cached.
+    fn cached_page(&self, _index: usize) -> Option<FrameTracker> {
+        None
+    }
+    /// Whether read_at would return without waiting, for devices.
+    fn read_ready(&self) -> bool {
+        true
+    }
+    /// A device specific request, with `arg` often a user pointer.
+    fn ioctl(&self, _cmd: usize, _arg: usize) -> isize {
+        -1
+    }
+    /// The physical memory the file is, like a framebuffer, for mmap to map
+    /// rather than copy: its physical address and length.
+    fn phys_range(&self) -> Option<(usize, usize)> {
+        None
+    }
+    /// The pages the file is kept in, like a file of tmpfs, for MAP_SHARED
+    /// to map rather than copy.
+    fn shared_memory(&self) -> Option<Arc<SharedMemory>> {
+        None
+    }
+}
+
+pub trait FileSystem: Send + Sync {
+    fn root_inode(&self) -> Arc<dyn Inode>;
+    /// Write what is cached of the files back to the device.
+    fn sync(&self) {}
+}
+
+/// Make a file system of some type out of the source given to mount, e.g.
+/// the name of a block device.
+pub type MountFn = fn(source: &str) -> Option<Arc<dyn FileSystem>>;
+
+struct Mount {
+    /// physical path of the mount point
+    path: String,
+    fs: Arc<dyn FileSystem>,
+}
+
+lazy_static! {
+    static ref FS_TYPES: UPIntrFreeCell<Vec<(&'static str, MountFn)>> =
+        unsafe { UPIntrFreeCell::new(Vec::new()) };
+    /// Mounted file systems in the order mounted, the root one first.
+    static ref MOUNTS: UPIntrFreeCell<Vec<Mount>> = unsafe {
+        UPIntrFreeCell::new(vec![Mount {
+            path: String::from("/"),
+            fs: super::root_fs(),
+        }])
+    };
+}
+
+/// Let file systems of type `name` be mounted.
+pub fn register_filesystem(name: &'static str, mount: MountFn) {
+    FS_TYPES.exclusive_access().push((name, mount));
+}
+
+fn components(path: &str) -> impl DoubleEndedIterator<Item = &str> {
+    path.split('/')
+        .filter(|name| !name.is_empty() && *name != ".")
+}
+
+/// `path` made absolute, without empty, `.` or `..` components or a
+/// trailing `/`, which is the physical path if no symbolic link is on it.
+fn canonical(path: &str) -> String {
+    let mut names = Vec::new();
+    for name in components(path) {
+        if name == ".." {
+            names.pop();
+        } else {
+            names.push(name);
+        }
+    }
+    let mut canonical = String::new();
+    for name in names {
+        canonical.push('/');
+        canonical.push_str(name);
+    }
+    if canonical.is_empty() {
+        canonical.push('/');
+    }
+    canonical
+}
+
+/// The root of the file system mounted last at the canonical `path`.
+fn mounted_root(path: &str) -> Option<Arc<dyn Inode>> {
+    let fs = MOUNTS
+        .exclusive_access()
+        .iter()
+        .rev()
+        .find(|mount| mount.path == path)
+        .map(|mount| mount.fs.clone())?;
+    // the file system may sleep on its device, so outside the table
+    Some(fs.root_inode())
+}
+
+/// Symbolic links followed in resolving one path, beyond which it is taken
+/// to loop.
+const MAX_SYMLINKS: usize = 8;
+
+/// The longest target of a symbolic link.
+const PATH_MAX: usize = 4096;
+
+/// The inode at `path`, with its physical path: the canonical path it is
+/// at, once symbolic links are followed. A symbolic link as the last
+/// component is followed only if `follow` is.
+fn resolve(path: &str, follow: bool) -> Option<(Arc<dyn Inode>, String)> {
+    let path = absolute(path);
+    let credentials = credentials();
+    // the directories walked through, each with its physical path
+    let mut walked: Vec<(Arc<dyn Inode>, String)> = Vec::new();
+    let mut inode = mounted_root("/")?;
+    let mut prefix = String::new();
+    // the components left to walk, the next one last
+    let mut names: Vec<String> = components(&path).rev().map(String::from).collect();
+    let mut links = 0;
+    while let Some(name) = names.pop() {
+        if !inode.is_dir() || !permitted(&inode, credentials, MAY_EXEC) {
+            return None;
+        }
+        if name == ".." {
+            if let Some((parent, parent_prefix)) = walked.pop() {
+                inode = parent;
+                prefix = parent_prefix;
+            }
+            continue;
+        }
+        let child = inode.find(&name)?;
+        if let Some(target) = child.readlink() {
+            if follow || !names.is_empty() {
+                links += 1;
+                if links > MAX_SYMLINKS {
+                    return None;
+                }
+                names.extend(components(&target).rev().map(String::from));
+                if target.starts_with('/') {
+                    walked.clear();
+                    inode = mounted_root("/")?;
+                    prefix = String::new();
+                }
+                continue;
+            }
+        }
+        let child_prefix = prefix.clone() + "/" + &name;
+        walked.push((inode, prefix));
+        inode = mounted_root(&child_prefix).unwrap_or(child);
+        prefix = child_prefix;
+    }
+    if prefix.is_empty() {
+        prefix.push('/');
+    }
+    Some((inode, prefix))
+}
+
+pub const MAY_READ: u32 = 4;
+pub const MAY_WRITE: u32 = 2;
+pub const MAY_EXEC: u32 = 1;
+
+/// The user and group of the current process, root in the kernel's own
+/// context.
+fn credentials() -> (u32, u32) {
+    current_task()
+        .and_then(|task| task.process.upgrade())
+        .map_or((0, 0), |process| {
+            let inner = process.inner_exclusive_access();
+            (inner.uid, inner.gid)
+        })
+}
+
+/// Whether `uid` in `gid` may access `inode` as `want`, some of MAY_READ,
+/// MAY_WRITE and MAY_EXEC, says.
+fn permitted(inode: &Arc<dyn Inode>, (uid, gid): (u32, u32), want: u32) -> bool {
+    let stat = inode.stat();
+    if uid == 0 {
+        // root runs only what somebody may run
+        return want & MAY_EXEC == 0 || inode.is_dir() || stat.mode & 0o111 != 0;
+    }
+    let bits = if stat.uid == uid {
+        stat.mode >> 6
+    } else if stat.gid == gid {
+        stat.mode >> 3
+    } else {
+        stat.mode
+    };
+    bits & want == want
+}
+
+/// Whether the current process may access `inode` as `want` says.
+pub fn may_access(inode: &Arc<dyn Inode>, want: u32) -> bool {
+    permitted(inode, credentials(), want)
+}
+
+/// Give the new `inode` to the current process.
+fn own(inode: &Arc<dyn Inode>) {
+    let (uid, gid) = credentials();
+    if uid != 0 || gid != 0 {
+        inode.chown(uid, gid);
+    }
+}
+
+/// `path` to be walked from the root: the working directory of the current
+/// process, which is the root in the kernel's own context, followed by
+/// `path` if that is relative.
+fn absolute(path: &str) -> String {
+    if path.starts_with('/') {
+        return String::from(path);
+    }
+    let cwd = current_task()
+        .and_then(|task| task.process.upgrade())
+        .map(|process| process.inner_exclusive_access().cwd.clone());
+    cwd.unwrap_or_else(|| String::from("/")) + "/" + path
+}
+
+/// The inode at `path`.
+pub fn lookup(path: &str) -> Option<Arc<dyn Inode>> {
+    resolve(path, true).map(|(inode, _)| inode)
+}
+
+/// The inode at `path`, the symbolic link itself if it is one.
+pub fn lookup_nofollow(path: &str) -> Option<Arc<dyn Inode>> {
+    resolve(path, false).map(|(inode, _)| inode)
+}
+
+/// The directory holding the last component of `path` with its physical
+/// path, and that component, which is empty for the root.
+fn resolve_parent(path: &str) -> Option<(Arc<dyn Inode>, String, &str)> {
+    let trimmed = path.trim_end_matches('/');
+    let (parent, name) = match trimmed.rfind('/') {
+        Some(0) => ("/", &trimmed[1..]),
+        Some(pos) => (&trimmed[..pos], &trimmed[pos + 1..]),
+        // the root itself
+        None if path.starts_with('/') => ("/", ""),
+        None => (".", trimmed),
+    };
+    let (parent, prefix) = resolve(parent, true)?;
+    if !parent.is_dir() {
+        return None;
+    }
+    Some((parent, prefix, name))
+}
+
+/// The directory holding the last component of `path`, if the current
+/// process may change its entries, and that component.
+fn writable_parent(path: &str) -> Option<(Arc<dyn Inode>, String, &str)> {
+    resolve_parent(path).filter(|(parent, _, _)| may_access(parent, MAY_WRITE | MAY_EXEC))
+}
+
+/// Make the file `path`, owned by the current process.
+pub fn create(path: &str) -> Option<Arc<dyn Inode>> {
+    let (parent, _, name) = writable_parent(path)?;
+    let inode = parent.create(name)?;
+    own(&inode);
+    notify(&parent, IN_CREATE, name);
+    Some(inode)
+}
+
+/// The physical path of the directory `path`, to be a working directory.
+pub fn working_dir(path: &str) -> Option<String> {
+    match resolve(path, true) {
+        Some((inode, path)) if inode.is_dir() => Some(path),
+        _ => None,
+    }
+}
+
+/// Make the directory `path`.
+pub fn mkdir(path: &str) -> bool {
+    let (parent, _, name) = match writable_parent(path) {
+        Some(parent) => parent,
+        None => return false,
+    };
+    match parent.mkdir(name) {
+        Some(inode) => {
+            own(&inode);
+            notify(&parent, IN_CREATE | IN_ISDIR, name);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Remove the empty directory `path`, unless something is mounted on it.
+pub fn rmdir(path: &str) -> bool {
+    let (parent, prefix, name) = match writable_parent(path) {
+        Some(parent) => parent,
+        None => return false,
+    };
+    if is_mount_point(&canonical(&(prefix + "/" + name))) {
+        return false;
+    }
+    let inode = parent.find(name);
+    if !parent.rmdir(name) {
+        return false;
+    }
+    notify(&parent, IN_DELETE | IN_ISDIR, name);
+    if let Some(inode) = inode {
+        notify_deleted(&inode);
+    }
+    true
+}
+
+fn is_mount_point(physical: &str) -> bool {
+    MOUNTS
+        .exclusive_access()
+        .iter()
+        .any(|mount| mount.path == physical)
+}
+
+/// Remove the name `path` of a file.
+pub fn unlink(path: &str) -> bool {
+    let (parent, _, name) = match writable_parent(path) {
+        Some(parent) => parent,
+        None => return false,
+    };
+    let inode = parent.find(name);
+    if !parent.unlink(name) {
+        return false;
+    }
+    notify(&parent, IN_DELETE, name);
+    match inode {
+        Some(inode) if inode.stat().nlink == 0 => notify_deleted(&inode),
+        _ => {}
+    }
+    true
+}
+
+/// Name the file at `old_path` `new_path` as well, the symbolic link
+/// itself if it is one.
+pub fn link(old_path: &str, new_path: &str) -> bool {
+    let inode = match lookup_nofollow(old_path) {
+        Some(inode) => inode,
+        None => return false,
+    };
+    match writable_parent(new_path) {
+        Some((parent, _, name)) if parent.link(name, &inode) => {
+            notify(&parent, IN_CREATE, name);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Move `old_path` to `new_path`, within a file system. Neither may be a
+/// mount point, nor may a directory move below itself.
+pub fn rename(old_path: &str, new_path: &str) -> bool {
+    let (old_parent, old_prefix, old_name) = match writable_parent(old_path) {
+        Some(parent) => parent,
+        None => return false,
+    };
+    let (new_parent, new_prefix, new_name) = match writable_parent(new_path) {
+        Some(parent) => parent,
+        None => return false,
+    };
+    let old_physical = canonical(&(old_prefix + "/" + old_name));
+    let new_physical = canonical(&(new_prefix + "/" + new_name));
+    if is_mount_point(&old_physical)
+        || is_mount_point(&new_physical)
+        || new_physical.starts_with(&(old_physical + "/"))
+    {
+        return false;
+    }
+    let inode = match old_parent.find(old_name) {
+        Some(inode) => inode,
+        None => return false,
+    };
+    let replaced = new_parent.find(new_name);
+    if !old_parent.rename(old_name, &new_parent, new_name) {
+        return false;
+    }
+    notify_rename(&old_parent, old_name, &new_parent, new_name, inode.is_dir());
+    match replaced {
+        Some(replaced) if !same_inode(&replaced, &inode) && replaced.stat().nlink == 0 => {
+            notify_deleted(&replaced)
+        }
+        _ => {}
+    }
+    true
+}
+
+/// Make the symbolic link `link_path` pointing at `target`, which need not
+/// exist.
+pub fn symlink(target: &str, link_path: &str) -> bool {
+    if target.is_empty() || target.len() > PATH_MAX {
+        return false;
+    }
+    let (parent, _, name) = match writable_parent(link_path) {
+        Some(parent) => parent,
+        None => return false,
+    };
+    match parent.symlink(name, target) {
+        Some(inode) => {
+            own(&inode);
+            notify(&parent, IN_CREATE, name);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Make the named pipe `path`, with the permission bits of `mode`.
+pub fn mkfifo(path: &str, mode: u32) -> bool {
+    let (parent, _, name) = match writable_parent(path) {
+        Some(parent) => parent,
+        None => return false,
+    };
+    match parent.mkfifo(name) {
+        Some(inode) => {
+            inode.chmod(mode & 0o777);
+            own(&inode);
+            notify(&parent, IN_CREATE, name);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Make the socket `path`, with the permission bits of `mode`, for an
+/// AF_UNIX socket to be bound to.
+pub fn mksock(path: &str, mode: u32) -> Option<Arc<dyn Inode>> {
+    let (parent, _, name) = writable_parent(path)?;
+    let inode = parent.mksock(name)?;
+    inode.chmod(mode & 0o777);
+    own(&inode);
+    notify(&parent, IN_CREATE, name);
+    Some(inode)
+}
+
+/// Set the permission bits of `path`, for its owner or root.
+pub fn chmod(path: &str, mode: u32) -> bool {
+    let inode = match lookup(path) {
+        Some(inode) => inode,
+        None => return false,
+    };
+    let (uid, _) = credentials();
+    if (uid == 0 || inode.stat().uid == uid) && inode.chmod(mode) {
+        notify(&inode, IN_ATTRIB, "");
+        true
+    } else {
+        false
+    }
+}
+
+/// Give `path` to `uid` and `gid`, which only root may.
+pub fn chown(path: &str, uid: u32, gid: u32) -> bool {
+    match lookup(path) {
+        Some(inode) if credentials().0 == 0 && inode.chown(uid, gid) => {
+            notify(&inode, IN_ATTRIB, "");
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Where the symbolic link `path` points.
+pub fn readlink(path: &str) -> Option<String> {
+    lookup_nofollow(path)?.readlink()
+}
+
+/// Mount a file system of type `fstype` made out of `source` on the
+/// directory `target`.
+pub fn mount(source: &str, target: &str, fstype: &str) -> bool {
+    let new_fs = FS_TYPES
+        .exclusive_access()
+        .iter()
+        .find(|(name, _)| *name == fstype)
+        .map(|(_, mount)| *mount);
+    let new_fs = match new_fs {
+        Some(new_fs) => new_fs,
+        None => return false,
+    };
+    let path = match resolve(target, true) {
+        Some((inode, path)) if inode.is_dir() => path,
+        _ => return false,
+    };
+    let fs = match new_fs(source) {
+        Some(fs) => fs,
+        None => return false,
+    };
+    MOUNTS.exclusive_access().push(Mount { path, fs });
+    true
+}
+
+/// Write back what is cached of all mounted file systems.
+pub fn sync() {
+    let mounted: Vec<_> = MOUNTS
+        .exclusive_access()
+        .iter()
+        .map(|mount| mount.fs.clone())
+        .collect();
+    for fs in mounted {
+        fs.sync();
+    }
+}
+
+/// Unmount the file system mounted last on `target`. Files open in it stay
+/// usable, but the tree no longer leads to them. The root file system, and
+/// those with others mounted below them, stay.
+pub fn umount(target: &str) -> bool {
+    let path = match resolve(target, true) {
+        Some((_, path)) => path,
+        None => return false,
+    };
+    let mut mounts = MOUNTS.exclusive_access();
+    let index = match mounts.iter().rposition(|mount| mount.path == path) {
+        Some(0) | None => return false,
+        Some(index) => index,
+    };
+    let below = |other: &str| {
+        path == "/" || (other.starts_with(&path) && other[path.len()..].starts_with('/'))
+    };
+    if mounts[index + 1..].iter().any(|mount| below(&mount.path)) {
+        return false;
+    }
+    let mount = mounts.remove(index);
+    drop(mounts);
+    // the file system may flush to its device when dropped
+    drop(mount);
+    true
+}
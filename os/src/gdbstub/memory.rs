@@ -0,0 +1,72 @@
+//! Memory as the debugger sees it: the kernel image, the direct map and the
+//! rest of the upper half are the kernel's, every other address is in the
+//! address space of the process the stop came in, if there is one.
+//!
+//! Memory is reached by its frame in the direct map, so that breakpoints
+//! can be written into code, which is mapped read-only. A breakpoint in a
+//! frame shared by processes, like code in the page cache, is there for
+//! all of them.
+
+use crate::config::{MEMORY_END, MEMORY_START, PAGE_SIZE, PHYS_VIRT_OFFSET};
+use crate::kaslr::link_address;
+use crate::mm::{phys_to_virt, PageTable, VirtAddr, KERNEL_SPACE};
+use crate::task::try_current_user_token;
+use alloc::vec::Vec;
+use core::arch::asm;
+use core::ptr::{read_volatile, write_volatile};
+
+/// The frame of the kernel or user address `va` is in, and where in it, as
+/// a physical address; None if it is not mapped to memory, or the page
+/// table it is looked up in is being changed by whoever stopped.
+pub fn translate(va: usize) -> Option<usize> {
+    extern "C" {
+        fn skernel();
+        fn ekernel();
+    }
+    let pa = if (phys_to_virt(MEMORY_START)..phys_to_virt(MEMORY_END)).contains(&va) {
+        va - PHYS_VIRT_OFFSET
+    } else if (skernel as usize..ekernel as usize).contains(&va) {
+        link_address(va)
+    } else {
+        let token = if va >= PHYS_VIRT_OFFSET {
+            KERNEL_SPACE.try_exclusive_access()?.token()
+        } else {
+            try_current_user_token()?
+        };
+        let table = PageTable::from_token(token);
+        let pte = table.translate(VirtAddr::from(va).floor())?;
+        if !pte.is_valid() {
+            return None;
+        }
+        usize::from(pte.ppn()) * PAGE_SIZE + va % PAGE_SIZE
+    };
+    (MEMORY_START..MEMORY_END).contains(&pa).then_some(pa)
+}
+
+/// Read `buf.len()` bytes at `va`, or as many as are mapped; how many.
+pub fn read(va: usize, buf: &mut [u8]) -> usize {
+    for (i, byte) in buf.iter_mut().enumerate() {
+        match translate(va.wrapping_add(i)) {
+            Some(pa) => *byte = unsafe { read_volatile(phys_to_virt(pa) as *const u8) },
+            None => return i,
+        }
+    }
+    buf.len()
+}
+
+/// Write `bytes` at `va`, if all of it is mapped, and make sure that code
+/// written is the code run.
+pub fn write(va: usize, bytes: &[u8]) -> bool {
+    let mut frames = Vec::with_capacity(bytes.len());
+    for i in 0..bytes.len() {
+        match translate(va.wrapping_add(i)) {
+            Some(pa) => frames.push(pa),
+            None => return false,
+        }
+    }
+    for (pa, byte) in frames.into_iter().zip(bytes) {
+        unsafe { write_volatile(phys_to_virt(pa) as *mut u8, *byte) };
+    }
+    unsafe { asm!("fence.i") };
+    true
+}
@@ -0,0 +1,507 @@
+//! A GDB stub: the remote serial protocol on a serial port of its own, the
+//! pci-serial device `make run KGDB=on` adds, for `make kgdb` to debug the
+//! kernel and the process running with.
+//!
+//! The whole system stops while GDB has it: on a breakpoint, after a single
+//! step, or when GDB interrupts, which is noticed at the next timer tick.
+//! Breakpoints are `ebreak`s written into the code, and single steps put
+//! them where the instruction at the pc may go next, there being no way for
+//! S-mode to have the hart step. GDB sees a single thread, whatever ran
+//! when it stopped, with the registers it trapped with.
+
+mod memory;
+mod step;
+
+use crate::drivers::bus::pci::enable_io_device;
+use crate::drivers::chardev::NS16550aRaw;
+use crate::sync::UPIntrFreeCell;
+use crate::trap::TrapContext;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use core::arch::asm;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, Ordering};
+use lazy_static::*;
+
+/// QEMU's pci-serial, a 16550 behind an I/O window.
+const PCI_SERIAL_VENDOR: u16 = 0x1b36;
+const PCI_SERIAL_DEVICE: u16 = 0x0002;
+/// Where its window is put in the I/O space.
+const PCI_SERIAL_PORT: usize = 0x1000;
+
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+/// What GDB sends to stop the system while it runs.
+const INTERRUPT: u8 = 0x03;
+/// The registers of `g`: x0 to x31, then the pc.
+const REGS: usize = 33;
+const PC: usize = 32;
+/// Bytes __alltraps_k saves the registers of the kernel in, below where
+/// the stack pointer of the kernel was.
+const KERNEL_FRAME_SIZE: usize = 34 * 8;
+/// c.ebreak and ebreak, for breakpoints of GDB's kinds 2 and 4.
+const C_EBREAK: [u8; 2] = [0x02, 0x90];
+const EBREAK: [u8; 4] = [0x73, 0x00, 0x10, 0x00];
+
+struct Breakpoint {
+    /// where GDB put it
+    addr: usize,
+    /// what the ebreak took the place of
+    original: [u8; 4],
+    len: usize,
+}
+
+struct GdbStub {
+    port: NS16550aRaw,
+    /// GDB's breakpoints, by the physical address of each
+    breakpoints: BTreeMap<usize, Breakpoint>,
+    /// those of the single step going on
+    steps: BTreeMap<usize, Breakpoint>,
+    /// whether GDB is waiting to hear of the next stop
+    attached: bool,
+    last_signal: u8,
+}
+
+lazy_static! {
+    static ref GDB: UPIntrFreeCell<Option<GdbStub>> = unsafe { UPIntrFreeCell::new(None) };
+}
+
+/// Whether there is a port for GDB, for timer ticks to look at it only then.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// A stop, in user or kernel mode, with the registers trapped with.
+struct Stopped<'a> {
+    cx: &'a mut TrapContext,
+    user: bool,
+}
+
+impl Stopped<'_> {
+    fn reg(&self, n: usize) -> usize {
+        match n {
+            0 => 0,
+            PC => self.cx.sepc,
+            // the kernel's sp is where it was before the trap, and its tp
+            // is not saved, nor changed by the trap
+            2 if !self.user => self.cx as *const _ as usize + KERNEL_FRAME_SIZE,
+            4 if !self.user => {
+                let tp: usize;
+                unsafe { asm!("mv {}, tp", out(reg) tp) };
+                tp
+            }
+            _ => self.cx.x[n],
+        }
+    }
+
+    fn set_reg(&mut self, n: usize, value: usize) {
+        match n {
+            0 => {}
+            PC => self.cx.sepc = value,
+            2 | 4 if !self.user => {}
+            _ => self.cx.x[n] = value,
+        }
+    }
+}
+
+/// What a packet has the stub do.
+enum Action {
+    Reply(String),
+    Continue,
+    Step,
+    Detach,
+}
+
+/// Look for the serial port of the stub, and listen on it if there is one.
+pub fn init() {
+    if let Some(base) = enable_io_device(PCI_SERIAL_VENDOR, PCI_SERIAL_DEVICE, PCI_SERIAL_PORT)
+    {
+        let mut port = NS16550aRaw::new(base);
+        port.init();
+        *GDB.exclusive_access() = Some(GdbStub {
+            port,
+            breakpoints: BTreeMap::new(),
+            steps: BTreeMap::new(),
+            attached: false,
+            last_signal: SIGTRAP,
+        });
+        ENABLED.store(true, Ordering::Release);
+        info!("gdb stub listening on pci-serial");
+    }
+}
+
+/// Stop for GDB if it sent something while the system ran, at a timer
+/// tick which trapped with `cx`.
+pub fn poll(cx: &mut TrapContext, user: bool) {
+    if !ENABLED.load(Ordering::Acquire) {
+        return;
+    }
+    let mut gdb = GDB.exclusive_access();
+    let stub = gdb.as_mut().unwrap();
+    match stub.port.read() {
+        Some(INTERRUPT) => stub.session(&mut Stopped { cx, user }, SIGINT, None),
+        Some(b'$') => stub.session(&mut Stopped { cx, user }, SIGTRAP, Some(b'$')),
+        _ => {}
+    }
+}
+
+/// Stop for GDB at the ebreak trapped with `cx` if it is a breakpoint of
+/// the stub's; false if it is not.
+pub fn breakpoint(cx: &mut TrapContext, user: bool) -> bool {
+    if !ENABLED.load(Ordering::Acquire) {
+        return false;
+    }
+    let pa = match memory::translate(cx.sepc) {
+        Some(pa) => pa,
+        None => return false,
+    };
+    let mut gdb = match GDB.try_exclusive_access() {
+        Some(gdb) => gdb,
+        None => panic!("breakpoint at {:#x} in code the gdb stub runs", cx.sepc),
+    };
+    let stub = gdb.as_mut().unwrap();
+    let stepped = stub.steps.contains_key(&pa);
+    stub.remove_steps();
+    if !stepped && !stub.breakpoints.contains_key(&pa) {
+        return false;
+    }
+    stub.session(&mut Stopped { cx, user }, SIGTRAP, None);
+    true
+}
+
+fn hex_digit(c: u8) -> Option<u8> {
+    (c as char).to_digit(16).map(|d| d as u8)
+}
+
+fn parse_hex(text: &[u8]) -> Option<usize> {
+    if text.is_empty() {
+        return None;
+    }
+    text.iter()
+        .try_fold(0usize, |value, &c| Some(value << 4 | hex_digit(c)? as usize))
+}
+
+fn decode_hex(text: &[u8]) -> Option<Vec<u8>> {
+    text.chunks(2)
+        .map(|pair| match pair {
+            [hi, lo] => Some(hex_digit(*hi)? << 4 | hex_digit(*lo)?),
+            _ => None,
+        })
+        .collect()
+}
+
+fn encode_hex(out: &mut String, bytes: &[u8]) {
+    for byte in bytes {
+        write!(out, "{:02x}", byte).unwrap();
+    }
+}
+
+/// `addr,len` and what follows a `:` if anything does.
+fn parse_range(args: &[u8]) -> Option<(usize, usize, &[u8])> {
+    let (range, data) = match args.iter().position(|&c| c == b':') {
+        Some(colon) => (&args[..colon], &args[colon + 1..]),
+        None => (args, &[][..]),
+    };
+    let comma = range.iter().position(|&c| c == b',')?;
+    Some((parse_hex(&range[..comma])?, parse_hex(&range[comma + 1..])?, data))
+}
+
+fn reply(text: &str) -> Action {
+    Action::Reply(String::from(text))
+}
+
+impl GdbStub {
+    fn getc(&mut self) -> u8 {
+        loop {
+            if let Some(c) = self.port.read() {
+                return c;
+            }
+        }
+    }
+
+    /// The next packet whose checksum is right, acknowledging each,
+    /// `first` being a byte of it read already.
+    fn receive(&mut self, mut first: Option<u8>) -> Vec<u8> {
+        loop {
+            let c = first.take().unwrap_or_else(|| self.getc());
+            if c != b'$' {
+                // acks, and interrupts while stopped already
+                continue;
+            }
+            let mut data = Vec::new();
+            let mut sum = 0u8;
+            loop {
+                match self.getc() {
+                    b'#' => break,
+                    c => {
+                        sum = sum.wrapping_add(c);
+                        data.push(c);
+                    }
+                }
+            }
+            let (hi, lo) = (self.getc(), self.getc());
+            if decode_hex(&[hi, lo]) == Some(vec![sum]) {
+                self.port.write(b'+');
+                return data;
+            }
+            self.port.write(b'-');
+        }
+    }
+
+    fn send(&mut self, data: &str) {
+        let sum = data.bytes().fold(0u8, |sum, c| sum.wrapping_add(c));
+        loop {
+            self.port.write(b'$');
+            data.bytes().for_each(|c| self.port.write(c));
+            self.port.write(b'#');
+            for c in format!("{:02x}", sum).bytes() {
+                self.port.write(c);
+            }
+            loop {
+                match self.getc() {
+                    b'+' => return,
+                    b'-' => break,
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Serve GDB until it lets the system go on, telling it of the stop
+    /// first if it is waiting for one.
+    fn session(&mut self, stopped: &mut Stopped, signal: u8, mut first: Option<u8>) {
+        self.last_signal = signal;
+        if self.attached {
+            self.send(&format!("S{:02x}", signal));
+        }
+        loop {
+            let packet = self.receive(first.take());
+            self.attached = true;
+            // time stopped for GDB is not time stuck for the watchdog
+            crate::watchdog::touch();
+            match self.handle(stopped, &packet) {
+                Action::Reply(text) => self.send(&text),
+                Action::Continue => return,
+                Action::Step => {
+                    if self.insert_steps(stopped) {
+                        return;
+                    }
+                    // nowhere to step to: stopped again at once
+                    self.send(&format!("S{:02x}", SIGTRAP));
+                }
+                Action::Detach => {
+                    let addrs: Vec<_> = self.breakpoints.values().map(|b| b.addr).collect();
+                    for addr in addrs {
+                        self.remove_breakpoint(addr);
+                    }
+                    self.attached = false;
+                    return;
+                }
+            }
+        }
+    }
+
+    fn handle(&mut self, stopped: &mut Stopped, packet: &[u8]) -> Action {
+        let (&command, args) = match packet.split_first() {
+            Some(split) => split,
+            None => return reply(""),
+        };
+        match command {
+            b'?' => Action::Reply(format!("S{:02x}", self.last_signal)),
+            b'g' => {
+                let mut text = String::new();
+                for n in 0..REGS {
+                    encode_hex(&mut text, &stopped.reg(n).to_le_bytes());
+                }
+                Action::Reply(text)
+            }
+            b'G' => match decode_hex(args) {
+                Some(bytes) => {
+                    for (n, value) in bytes.chunks_exact(8).take(REGS).enumerate() {
+                        stopped.set_reg(n, usize::from_le_bytes(value.try_into().unwrap()));
+                    }
+                    reply("OK")
+                }
+                None => reply("E22"),
+            },
+            b'p' => match parse_hex(args) {
+                Some(n) if n < REGS => {
+                    let mut text = String::new();
+                    encode_hex(&mut text, &stopped.reg(n).to_le_bytes());
+                    Action::Reply(text)
+                }
+                // floating point registers and CSRs are not there to see
+                Some(_) => reply("xxxxxxxxxxxxxxxx"),
+                None => reply("E22"),
+            },
+            b'P' => {
+                let eq = args.iter().position(|&c| c == b'=');
+                let n = eq.and_then(|eq| parse_hex(&args[..eq]));
+                let value = eq.and_then(|eq| decode_hex(&args[eq + 1..]));
+                match (n, value) {
+                    (Some(n), Some(value)) if n < REGS && value.len() == 8 => {
+                        stopped.set_reg(n, usize::from_le_bytes(value.try_into().unwrap()));
+                        reply("OK")
+                    }
+                    _ => reply("E22"),
+                }
+            }
+            b'm' => match parse_range(args) {
+                Some((addr, len, _)) => {
+                    let mut buf = vec![0u8; len.min(0x800)];
+                    let read = memory::read(addr, &mut buf);
+                    if read == 0 && len > 0 {
+                        return reply("E14");
+                    }
+                    let mut text = String::new();
+                    encode_hex(&mut text, &buf[..read]);
+                    Action::Reply(text)
+                }
+                None => reply("E22"),
+            },
+            b'M' => match parse_range(args) {
+                Some((addr, len, data)) => match decode_hex(data) {
+                    Some(bytes) if bytes.len() == len => {
+                        reply(if memory::write(addr, &bytes) { "OK" } else { "E14" })
+                    }
+                    _ => reply("E22"),
+                },
+                None => reply("E22"),
+            },
+            b'Z' | b'z' if args.starts_with(b"0,") => match parse_range(&args[2..]) {
+                Some((addr, kind, _)) => {
+                    let done = if command == b'Z' {
+                        self.insert_breakpoint(addr, kind)
+                    } else {
+                        self.remove_breakpoint(addr)
+                    };
+                    reply(if done { "OK" } else { "E14" })
+                }
+                None => reply("E22"),
+            },
+            b'c' | b's' => {
+                if let Some(addr) = parse_hex(args) {
+                    stopped.set_reg(PC, addr);
+                }
+                if command == b'c' {
+                    Action::Continue
+                } else {
+                    Action::Step
+                }
+            }
+            b'D' => {
+                self.send("OK");
+                Action::Detach
+            }
+            // the kernel is not GDB's to kill
+            b'k' => Action::Detach,
+            b'H' | b'T' => reply("OK"),
+            b'q' => self.query(args),
+            _ => reply(""),
+        }
+    }
+
+    fn query(&self, args: &[u8]) -> Action {
+        if args.starts_with(b"Supported") {
+            reply("PacketSize=1000")
+        } else if args == b"Attached" {
+            reply("1")
+        } else if args == b"C" {
+            reply("QC1")
+        } else if args == b"fThreadInfo" {
+            reply("m1")
+        } else if args == b"sThreadInfo" {
+            reply("l")
+        } else {
+            reply("")
+        }
+    }
+
+    /// Put an ebreak of `kind` bytes at `addr`, keeping what was there.
+    fn place(addr: usize, kind: usize) -> Option<(usize, Breakpoint)> {
+        let pa = memory::translate(addr)?;
+        let ebreak: &[u8] = match kind {
+            2 => &C_EBREAK,
+            4 => &EBREAK,
+            _ => return None,
+        };
+        let mut original = [0u8; 4];
+        if memory::read(addr, &mut original[..kind]) != kind || !memory::write(addr, ebreak) {
+            return None;
+        }
+        Some((
+            pa,
+            Breakpoint {
+                addr,
+                original,
+                len: kind,
+            },
+        ))
+    }
+
+    fn insert_breakpoint(&mut self, addr: usize, kind: usize) -> bool {
+        if let Some(pa) = memory::translate(addr) {
+            if self.breakpoints.contains_key(&pa) {
+                return true;
+            }
+        }
+        match Self::place(addr, kind) {
+            Some((pa, breakpoint)) => {
+                self.breakpoints.insert(pa, breakpoint);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn remove_breakpoint(&mut self, addr: usize) -> bool {
+        let pa = match memory::translate(addr) {
+            Some(pa) => pa,
+            None => return false,
+        };
+        match self.breakpoints.remove(&pa) {
+            Some(breakpoint) => {
+                memory::write(breakpoint.addr, &breakpoint.original[..breakpoint.len])
+            }
+            None => true,
+        }
+    }
+
+    /// Put the breakpoints of a single step from the stop; false if there
+    /// was nowhere to put them.
+    fn insert_steps(&mut self, stopped: &Stopped) -> bool {
+        let pc = stopped.reg(PC);
+        let mut insn = [0u8; 4];
+        if memory::read(pc, &mut insn[..2]) != 2 {
+            return false;
+        }
+        let len = if insn[0] & 0b11 == 0b11 { 4 } else { 2 };
+        if len == 4 && memory::read(pc + 2, &mut insn[2..]) != 2 {
+            return false;
+        }
+        let insn = u32::from_le_bytes(insn);
+        for next in step::successors(pc, insn, |n| stopped.reg(n)) {
+            let mut low = [0u8; 1];
+            if memory::read(next, &mut low) != 1 {
+                continue;
+            }
+            let kind = if low[0] & 0b11 == 0b11 { 4 } else { 2 };
+            let taken = memory::translate(next).map_or(true, |pa| {
+                self.breakpoints.contains_key(&pa) || self.steps.contains_key(&pa)
+            });
+            if taken {
+                continue;
+            }
+            if let Some((pa, breakpoint)) = Self::place(next, kind) {
+                self.steps.insert(pa, breakpoint);
+            }
+        }
+        !self.steps.is_empty()
+    }
+
+    fn remove_steps(&mut self) {
+        for (_, breakpoint) in core::mem::take(&mut self.steps) {
+            memory::write(breakpoint.addr, &breakpoint.original[..breakpoint.len]);
+        }
+    }
+}
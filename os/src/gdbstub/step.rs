@@ -0,0 +1,79 @@
+//! Single steps without help from the hardware: where the instruction at
+//! the pc may go next, for breakpoints to be put there.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+fn bits(insn: u32, hi: u32, lo: u32) -> usize {
+    ((insn >> lo) & ((1 << (hi - lo + 1)) - 1)) as usize
+}
+
+fn sign_extend(value: usize, width: u32) -> usize {
+    let shift = usize::BITS - width;
+    (((value << shift) as isize) >> shift) as usize
+}
+
+/// The addresses the instruction `insn` at `pc` may be followed by, both
+/// ways of a branch, `reg` giving the registers for jumps through them.
+pub fn successors(pc: usize, insn: u32, reg: impl Fn(usize) -> usize) -> Vec<usize> {
+    if insn & 0b11 != 0b11 {
+        return compressed_successors(pc, insn as u16 as u32, reg);
+    }
+    let next = pc.wrapping_add(4);
+    match insn & 0x7f {
+        // jal
+        0x6f => {
+            let imm = bits(insn, 31, 31) << 20
+                | bits(insn, 30, 21) << 1
+                | bits(insn, 20, 20) << 11
+                | bits(insn, 19, 12) << 12;
+            vec![pc.wrapping_add(sign_extend(imm, 21))]
+        }
+        // jalr
+        0x67 => {
+            let imm = sign_extend(bits(insn, 31, 20), 12);
+            vec![reg(bits(insn, 19, 15)).wrapping_add(imm) & !1]
+        }
+        // beq, bne, blt, bge, bltu and bgeu
+        0x63 => {
+            let imm = bits(insn, 31, 31) << 12
+                | bits(insn, 30, 25) << 5
+                | bits(insn, 11, 8) << 1
+                | bits(insn, 7, 7) << 11;
+            vec![next, pc.wrapping_add(sign_extend(imm, 13))]
+        }
+        _ => vec![next],
+    }
+}
+
+fn compressed_successors(pc: usize, insn: u32, reg: impl Fn(usize) -> usize) -> Vec<usize> {
+    let next = pc.wrapping_add(2);
+    match (bits(insn, 15, 13), insn & 0b11) {
+        // c.j
+        (0b101, 0b01) => {
+            let imm = bits(insn, 12, 12) << 11
+                | bits(insn, 11, 11) << 4
+                | bits(insn, 10, 9) << 8
+                | bits(insn, 8, 8) << 10
+                | bits(insn, 7, 7) << 6
+                | bits(insn, 6, 6) << 7
+                | bits(insn, 5, 3) << 1
+                | bits(insn, 2, 2) << 5;
+            vec![pc.wrapping_add(sign_extend(imm, 12))]
+        }
+        // c.beqz and c.bnez
+        (0b110 | 0b111, 0b01) => {
+            let imm = bits(insn, 12, 12) << 8
+                | bits(insn, 11, 10) << 3
+                | bits(insn, 6, 5) << 6
+                | bits(insn, 4, 3) << 1
+                | bits(insn, 2, 2) << 5;
+            vec![next, pc.wrapping_add(sign_extend(imm, 9))]
+        }
+        // c.jr and c.jalr, but not c.ebreak
+        (0b100, 0b10) if bits(insn, 11, 7) != 0 && bits(insn, 6, 2) == 0 => {
+            vec![reg(bits(insn, 11, 7)) & !1]
+        }
+        _ => vec![next],
+    }
+}
@@ -0,0 +1,139 @@
+//! Hardening of the kernel for experiments with memory-safety failures, in
+//! kernels built with HARDEN=on, which turns the `hardening` feature on
+//! and has rustc put a stack canary in every function of the kernel:
+//!
+//! - a canary, random from boot on, is checked when each function returns,
+//!   and __stack_chk_fail panics, naming the function, if it changed;
+//! - the bottom word of each kernel stack holds a magic number, checked on
+//!   traps from the kernel and on switches, to catch a stack about to run
+//!   into its guard page, where the trap itself would fault again;
+//! - traps from the kernel keep a shadow of where they return to, the
+//!   frame and its sepc and ra, away from the stack, and panic if the frame
+//!   on the stack no longer agrees with it when they return.
+
+use crate::backtrace::{stack_bounds, symbolize};
+use crate::config::MAX_HARTS;
+use crate::mm::hart_id;
+use crate::random::random;
+use crate::sync::UPIntrFreeCell;
+use crate::trap::TrapContext;
+use alloc::vec::Vec;
+use core::arch::asm;
+use lazy_static::*;
+
+/// The canary of the functions of the kernel, which rustc's stack
+/// protector compares their copies with; set once at boot by rust_main,
+/// which never returns, lest a function return with another canary.
+#[no_mangle]
+#[allow(non_upper_case_globals)]
+pub static mut __stack_chk_guard: usize = 0x595e_9fbd_94fd_a766;
+
+/// What the bottom word of each kernel stack holds.
+const STACK_MAGIC: usize = 0x57ac_cafe_57ac_cafe;
+
+/// A canary for __stack_chk_guard.
+pub fn canary() -> usize {
+    // a zero byte first stops string copies running past it
+    random() & !0xff
+}
+
+#[no_mangle]
+pub extern "C" fn __stack_chk_fail() -> ! {
+    let ra: usize;
+    unsafe { asm!("mv {}, ra", out(reg) ra) };
+    match symbolize(ra) {
+        Some((name, _)) => panic!("stack smashed in {} (returning at {:#x})", name, ra),
+        None => panic!("stack smashed in the function returning at {:#x}", ra),
+    }
+}
+
+/// Mark the boot stack, which the idle loop runs on; kernel stacks are
+/// marked as they are allocated.
+pub fn init() {
+    extern "C" {
+        fn boot_stack_lower_bound();
+    }
+    mark_stack(boot_stack_lower_bound as usize);
+}
+
+/// Put the magic number at the bottom of a kernel stack.
+pub fn mark_stack(bottom: usize) {
+    unsafe { (bottom as *mut usize).write_volatile(STACK_MAGIC) };
+}
+
+/// Panic if the kernel stack `sp` is on has run past its bottom word, or
+/// `sp` is on no stack known.
+pub fn check_stack(sp: usize) {
+    let (bottom, top) = match stack_bounds(sp) {
+        Some(bounds) => bounds,
+        // the current task is being changed, and with it its stack
+        None => return,
+    };
+    let magic = unsafe { (bottom as *const usize).read_volatile() };
+    if magic != STACK_MAGIC {
+        panic!(
+            "kernel stack [{:#x}, {:#x}) overflowed: bottom word {:#x}, sp {:#x}",
+            bottom, top, magic, sp
+        );
+    }
+}
+
+/// Panic if the stack running on has overflowed.
+pub fn check_current_stack() {
+    let sp: usize;
+    unsafe { asm!("mv {}, sp", out(reg) sp) };
+    check_stack(sp);
+}
+
+/// What a trap from the kernel returns to, as it was when taken.
+#[derive(PartialEq, Eq, Debug)]
+struct ShadowFrame {
+    frame: usize,
+    sepc: usize,
+    ra: usize,
+}
+
+impl ShadowFrame {
+    fn of(cx: &TrapContext) -> Self {
+        Self {
+            frame: cx as *const _ as usize,
+            sepc: cx.sepc,
+            ra: cx.x[1],
+        }
+    }
+}
+
+lazy_static! {
+    /// The traps from the kernel each hart is in, the innermost last.
+    static ref SHADOW_STACKS: Vec<UPIntrFreeCell<Vec<ShadowFrame>>> = (0..MAX_HARTS)
+        .map(|_| unsafe { UPIntrFreeCell::new(Vec::new()) })
+        .collect();
+}
+
+/// A trap from the kernel taken with `cx` on the stack.
+pub fn trap_enter(cx: &TrapContext) {
+    check_stack(cx as *const _ as usize);
+    SHADOW_STACKS[hart_id()]
+        .exclusive_access()
+        .push(ShadowFrame::of(cx));
+}
+
+/// The trap of `cx` having changed where it returns to on purpose, as the
+/// GDB stub does.
+pub fn trap_update(cx: &TrapContext) {
+    if let Some(shadow) = SHADOW_STACKS[hart_id()].exclusive_access().last_mut() {
+        *shadow = ShadowFrame::of(cx);
+    }
+}
+
+/// The trap of `cx` returning; panic if its frame was overwritten.
+pub fn trap_leave(cx: &TrapContext) {
+    let shadow = SHADOW_STACKS[hart_id()].exclusive_access().pop();
+    let frame = ShadowFrame::of(cx);
+    if shadow.as_ref() != Some(&frame) {
+        panic!(
+            "trap frame overwritten: returning to {:x?}, taken with {:x?}",
+            frame, shadow
+        );
+    }
+}
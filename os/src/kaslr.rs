@@ -0,0 +1,135 @@
+//! Kernel address space layout randomization: a kernel built with the
+//! `kaslr` feature, as a position independent executable, moves itself to
+//! a random megapage of the upper half before rust_main.
+//!
+//! `_start` calls `kaslr_relocate` on the boot page table, still at the
+//! address the kernel is linked and loaded at, which is its physical one.
+//! That maps the image at the random address too, points what the
+//! R_RISCV_RELATIVE relocations of the linker name there, and tells
+//! `_start` how far the image moved, for it to jump to rust_main there.
+//!
+//! The seed is the rng-seed the firmware puts in the device tree, if it
+//! does, and the time of the boot. The symbol table of backtraces, and the
+//! PCs of kcov, stay the addresses the kernel is linked at.
+
+use crate::cmdline;
+use crate::config::{HUGE_PAGE_SIZE, KASLR_END, KASLR_START};
+use crate::random::mix;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use riscv::register::time;
+
+const R_RISCV_RELATIVE: usize = 3;
+/// The megapages of a page table for a gigapage.
+const MEGAPAGES: usize = 512;
+const GIGAPAGE_SIZE: usize = HUGE_PAGE_SIZE * MEGAPAGES;
+/// Valid, readable, writable, executable, accessed and dirty.
+const PTE_RWX: usize = 0xcf;
+const PTE_VALID: usize = 0x1;
+
+/// How far above the address it is linked at the kernel runs.
+static KERNEL_OFFSET: AtomicUsize = AtomicUsize::new(0);
+
+/// An entry of .rela.dyn.
+#[repr(C)]
+struct Rela {
+    offset: usize,
+    info: usize,
+    addend: usize,
+}
+
+extern "C" {
+    fn skernel();
+    fn ekernel();
+    fn srela_dyn();
+    fn erela_dyn();
+    fn boot_page_table();
+    fn boot_kaslr_table();
+}
+
+/// Where in [KASLR_START, KASLR_END) the image of `size` bytes goes, one
+/// of the megapages it fits after without crossing a gigapage, as `seed`
+/// picks it.
+fn pick_address(seed: u64, size: usize) -> usize {
+    let megapages = (size + HUGE_PAGE_SIZE - 1) / HUGE_PAGE_SIZE;
+    let gigapages = (KASLR_END - KASLR_START) / GIGAPAGE_SIZE;
+    let slots = MEGAPAGES - megapages + 1;
+    let gigapage = seed as usize % gigapages;
+    let megapage = (seed >> 32) as usize % slots;
+    KASLR_START + gigapage * GIGAPAGE_SIZE + megapage * HUGE_PAGE_SIZE
+}
+
+/// Map the image at `va` as well, with megapages of boot_kaslr_table, which
+/// the boot page table points to.
+unsafe fn map_image(va: usize) {
+    let (start, end) = (skernel as usize, ekernel as usize);
+    let root = boot_page_table as usize as *mut usize;
+    let table = boot_kaslr_table as usize as *mut usize;
+    root.add((va / GIGAPAGE_SIZE) % MEGAPAGES)
+        .write_volatile((table as usize >> 12) << 10 | PTE_VALID);
+    let first = (va / HUGE_PAGE_SIZE) % MEGAPAGES;
+    for (index, pa) in (start..end).step_by(HUGE_PAGE_SIZE).enumerate() {
+        table.add(first + index).write_volatile((pa >> 12) << 10 | PTE_RWX);
+    }
+    core::arch::asm!("sfence.vma");
+}
+
+/// Have every R_RISCV_RELATIVE relocation point `offset` further, writing
+/// them through the address the kernel was loaded at.
+unsafe fn apply_relocations(offset: usize) {
+    let count = (erela_dyn as usize - srela_dyn as usize) / core::mem::size_of::<Rela>();
+    let relas = core::slice::from_raw_parts(srela_dyn as usize as *const Rela, count);
+    for rela in relas {
+        // the linker leaves no other kind in a static PIE
+        if rela.info & 0xffff_ffff == R_RISCV_RELATIVE {
+            (rela.offset as *mut usize).write_volatile(rela.addend.wrapping_add(offset));
+        }
+    }
+}
+
+/// Called by `_start` on the boot stack, before .bss is cleared: move the
+/// kernel unless it is built without KASLR or `nokaslr` is on the command
+/// line, and return how far it moved.
+#[no_mangle]
+extern "C" fn kaslr_relocate(dtb_pa: usize) -> usize {
+    if !cfg!(feature = "kaslr") {
+        return 0;
+    }
+    // read again by rust_main, once .bss is cleared
+    cmdline::init(dtb_pa);
+    if cmdline::option("nokaslr").is_some() {
+        return 0;
+    }
+    let mut seed = mix(time::read() as u64);
+    for chunk in cmdline::fdt_seed(dtb_pa).unwrap_or(&[]).chunks(8) {
+        let mut bytes = [0; 8];
+        bytes[..chunk.len()].copy_from_slice(chunk);
+        seed = mix(seed ^ u64::from_le_bytes(bytes));
+    }
+    let start = skernel as usize;
+    let va = pick_address(seed, ekernel as usize - start);
+    let offset = va.wrapping_sub(start);
+    unsafe {
+        map_image(va);
+        apply_relocations(offset);
+    }
+    offset
+}
+
+/// Keep the offset `kaslr_relocate` returned, as rust_main is handed it.
+pub fn init(offset: usize) {
+    KERNEL_OFFSET.store(offset, Ordering::Relaxed);
+}
+
+/// How far above the address it is linked at the kernel runs, 0 without
+/// KASLR; inlined into the kcov hook, which may call nothing instrumented.
+#[inline(always)]
+pub fn kernel_offset() -> usize {
+    KERNEL_OFFSET.load(Ordering::Relaxed)
+}
+
+/// The address the kernel is linked at of `va` in its image, as its symbol
+/// table has it, which is its physical address too, the kernel being
+/// loaded where it is linked.
+pub fn link_address(va: usize) -> usize {
+    va.wrapping_sub(kernel_offset())
+}
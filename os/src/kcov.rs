@@ -0,0 +1,237 @@
+//! Coverage of the kernel for fuzzing, in kernels built with KCOV=on,
+//! which turns the `kcov` feature on and has LLVM call
+//! __sanitizer_cov_trace_pc at every edge of the kernel's code.
+//!
+//! As kcov of Linux, a process opens /dev/kcov, sizes an area of words
+//! with KCOV_INIT_TRACE, maps it shared and has a thread of its own
+//! KCOV_ENABLE it. The syscalls of that thread then leave in the area
+//! the addresses of the code they ran, word 0 counting them, which the
+//! thread zeroes before each syscall to tell its coverage apart from the
+//! last. Interrupts, and other tasks running while it sleeps, leave
+//! nothing.
+
+use crate::config::PAGE_SIZE;
+use crate::kaslr::kernel_offset;
+use crate::mm::{frame_alloc, FrameTracker, SharedMemory};
+use crate::sync::UPIntrFreeCell;
+use crate::syscall::{EBUSY, EINVAL, ENOMEM};
+use crate::task::{current_ids, current_process};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::arch::asm;
+use core::ptr::null_mut;
+use core::sync::atomic::{AtomicPtr, Ordering};
+use lazy_static::*;
+
+pub const KCOV_INIT_TRACE: usize = 0x8008_6301;
+pub const KCOV_ENABLE: usize = 0x6364;
+pub const KCOV_DISABLE: usize = 0x6365;
+
+/// The mode of KCOV_ENABLE, addresses of code; comparisons are not kept.
+const KCOV_TRACE_PC: usize = 0;
+
+/// The largest area, in words, 8 MiB.
+const KCOV_MAX_WORDS: usize = 1 << 20;
+
+const WORDS_PER_PAGE: usize = PAGE_SIZE / core::mem::size_of::<usize>();
+
+/// The words a process collects coverage into, which the kernel writes
+/// through the direct map and the process reads through its mapping.
+pub struct KcovArea {
+    memory: Arc<SharedMemory>,
+    /// where the kernel has each page
+    pages: Vec<usize>,
+    words: usize,
+}
+
+impl KcovArea {
+    fn new(words: usize) -> Option<Self> {
+        let frames = (0..(words + WORDS_PER_PAGE - 1) / WORDS_PER_PAGE)
+            .map(|_| frame_alloc())
+            .collect::<Option<Vec<FrameTracker>>>()?;
+        let pages = frames
+            .iter()
+            .map(|frame| frame.ppn.get_bytes_array().as_mut_ptr() as usize)
+            .collect();
+        Some(Self {
+            memory: SharedMemory::new_frames(frames),
+            pages,
+            words,
+        })
+    }
+
+    /// Word `index`, which has to be less than `words`; nothing called from
+    /// here may be instrumented, lest the hook run again within itself.
+    #[inline(always)]
+    unsafe fn word(&self, index: usize) -> *mut usize {
+        let page = *self.pages.get_unchecked(index / WORDS_PER_PAGE);
+        (page as *mut usize).add(index % WORDS_PER_PAGE)
+    }
+
+    #[inline(always)]
+    unsafe fn push(&self, pc: usize) {
+        let count = self.word(0).read_volatile();
+        // the count is the process's to write, so trust it no further
+        if count < self.words - 1 {
+            self.word(count + 1).write_volatile(pc);
+            self.word(0).write_volatile(count + 1);
+        }
+    }
+}
+
+/// The coverage of a process: its area, and the thread collecting into it
+/// if one has enabled it.
+pub struct Kcov {
+    area: Arc<KcovArea>,
+    tid: Option<usize>,
+}
+
+/// The area of the syscall running, null if none collects; read by the
+/// hook without taking any lock. The kernel runs on one hart only.
+static ACTIVE: AtomicPtr<KcovArea> = AtomicPtr::new(null_mut());
+
+lazy_static! {
+    /// The area ACTIVE points to, kept alive while it does.
+    static ref COLLECTING: UPIntrFreeCell<Option<Arc<KcovArea>>> =
+        unsafe { UPIntrFreeCell::new(None) };
+}
+
+/// Called by the code LLVM instruments at each edge, which LLVM leaves
+/// this function itself out of.
+#[no_mangle]
+pub extern "C" fn __sanitizer_cov_trace_pc() {
+    let pc: usize;
+    unsafe {
+        asm!("mv {}, ra", out(reg) pc);
+    }
+    let area = ACTIVE.load(Ordering::Relaxed);
+    if !area.is_null() {
+        // as linked, so that the PCs are the same from boot to boot
+        unsafe { (*area).push(pc.wrapping_sub(kernel_offset())) };
+    }
+}
+
+/// KCOV_INIT_TRACE: give the current process an area of `words` words.
+fn init_trace(words: usize) -> isize {
+    if !(2..=KCOV_MAX_WORDS).contains(&words) {
+        return EINVAL;
+    }
+    let process = current_process();
+    if process.inner_exclusive_access().kcov.is_some() {
+        return EBUSY;
+    }
+    let area = match KcovArea::new(words) {
+        Some(area) => Arc::new(area),
+        None => return ENOMEM,
+    };
+    let mut inner = process.inner_exclusive_access();
+    if inner.kcov.is_some() {
+        return EBUSY;
+    }
+    inner.kcov = Some(Kcov { area, tid: None });
+    0
+}
+
+/// KCOV_ENABLE: collect the syscalls of the current thread from its next
+/// one on.
+fn enable(mode: usize) -> isize {
+    if mode != KCOV_TRACE_PC {
+        return EINVAL;
+    }
+    let tid = match current_ids() {
+        Some((_, tid)) => tid,
+        None => return EINVAL,
+    };
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    match inner.kcov.as_mut() {
+        Some(kcov) if kcov.tid.is_none() => {
+            kcov.tid = Some(tid);
+            0
+        }
+        Some(_) => EBUSY,
+        None => EINVAL,
+    }
+}
+
+/// KCOV_DISABLE: stop collecting, from the thread which enabled it.
+fn disable() -> isize {
+    let tid = current_ids().map(|(_, tid)| tid);
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    match inner.kcov.as_mut() {
+        Some(kcov) if kcov.tid.is_some() && kcov.tid == tid => {
+            kcov.tid = None;
+            // the ioctl itself has been collecting until now
+            stop();
+            0
+        }
+        _ => EINVAL,
+    }
+}
+
+/// The ioctls of /dev/kcov.
+pub fn kcov_ioctl(cmd: usize, arg: usize) -> isize {
+    match cmd {
+        KCOV_INIT_TRACE => init_trace(arg),
+        KCOV_ENABLE => enable(arg),
+        KCOV_DISABLE => disable(),
+        _ => EINVAL,
+    }
+}
+
+/// The area of the current process, for /dev/kcov to be mapped.
+pub fn kcov_memory() -> Option<Arc<SharedMemory>> {
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    inner.kcov.as_ref().map(|kcov| kcov.area.memory.clone())
+}
+
+/// Start collecting at a syscall, if the current thread enabled kcov.
+pub fn start() {
+    let tid = match current_ids() {
+        Some((_, tid)) => tid,
+        None => return,
+    };
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    let area = match inner.kcov.as_ref() {
+        Some(kcov) if kcov.tid == Some(tid) => kcov.area.clone(),
+        _ => return,
+    };
+    drop(inner);
+    ACTIVE.store(Arc::as_ptr(&area) as *mut KcovArea, Ordering::Relaxed);
+    *COLLECTING.exclusive_access() = Some(area);
+}
+
+/// Stop collecting at the end of a syscall.
+pub fn stop() {
+    ACTIVE.store(null_mut(), Ordering::Relaxed);
+    COLLECTING.exclusive_access().take();
+}
+
+/// Stop collecting while another task runs; whether the syscall was
+/// collecting, for resume. Nothing is left to free should the task never
+/// run again.
+pub fn pause() -> bool {
+    let collecting = !ACTIVE.load(Ordering::Relaxed).is_null();
+    stop();
+    collecting
+}
+
+/// Collect again once back from another task, unless the thread disabled
+/// kcov or exited meanwhile.
+pub fn resume(collecting: bool) {
+    if collecting {
+        start();
+    }
+}
+
+/// Stop collecting while an interrupt is handled, giving what to restore.
+pub fn irq_enter() -> *mut KcovArea {
+    ACTIVE.swap(null_mut(), Ordering::Relaxed)
+}
+
+pub fn irq_leave(active: *mut KcovArea) {
+    ACTIVE.store(active, Ordering::Relaxed);
+}
@@ -0,0 +1,70 @@
+//! Tests of the file systems through the VFS, as the kernel's own context,
+//! which is root and walks from /.
+
+use crate::fs::{create, lookup, mkdir, rename, rmdir, symlink, unlink};
+use crate::kernel_test;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Write a file across blocks at `path`, read it back, then remove it.
+fn write_read_unlink(path: &str) {
+    let inode = create(path).unwrap();
+    let data: Vec<u8> = (0..10_000).map(|i| (i % 251) as u8).collect();
+    assert_eq!(inode.write_at(0, &data), data.len());
+    assert_eq!(inode.size(), data.len());
+    let mut back = vec![0u8; data.len()];
+    assert_eq!(lookup(path).unwrap().read_at(0, &mut back), data.len());
+    assert!(back == data);
+    // past the end, nothing
+    assert_eq!(inode.read_at(data.len(), &mut back), 0);
+    assert!(unlink(path));
+    assert!(lookup(path).is_none());
+    assert!(!unlink(path));
+}
+
+kernel_test! {
+    fn tmpfs_files() {
+        write_read_unlink("/tmp/ktest-file");
+    }
+
+    fn easyfs_files() {
+        write_read_unlink("/ktest-file");
+    }
+
+    fn directories() {
+        assert!(mkdir("/tmp/ktest-dir"));
+        assert!(!mkdir("/tmp/ktest-dir"));
+        assert!(lookup("/tmp/ktest-dir").unwrap().is_dir());
+        create("/tmp/ktest-dir/a").unwrap();
+        // a directory which is not empty stays
+        assert!(!rmdir("/tmp/ktest-dir"));
+        assert!(rename("/tmp/ktest-dir/a", "/tmp/ktest-dir/b"));
+        assert!(lookup("/tmp/ktest-dir/a").is_none());
+        assert!(lookup("/tmp/ktest-dir/../ktest-dir/b").is_some());
+        assert!(unlink("/tmp/ktest-dir/b"));
+        assert!(rmdir("/tmp/ktest-dir"));
+        assert!(lookup("/tmp/ktest-dir").is_none());
+    }
+
+    fn symlinks() {
+        create("/tmp/ktest-target").unwrap().write_at(0, b"here");
+        assert!(symlink("/tmp/ktest-target", "/tmp/ktest-link"));
+        let mut buf = [0u8; 4];
+        assert_eq!(lookup("/tmp/ktest-link").unwrap().read_at(0, &mut buf), 4);
+        assert_eq!(&buf, b"here");
+        assert!(unlink("/tmp/ktest-target"));
+        // dangling now
+        assert!(lookup("/tmp/ktest-link").is_none());
+        assert!(unlink("/tmp/ktest-link"));
+    }
+
+    fn pseudo_files() {
+        let mut buf = [0u8; 64];
+        let meminfo = lookup("/proc/meminfo").unwrap();
+        assert!(meminfo.read_at(0, &mut buf) > 0);
+        assert!(buf.starts_with(b"MemTotal"));
+        let null = lookup("/dev/null").unwrap();
+        assert_eq!(null.write_at(0, b"gone"), 4);
+        assert_eq!(null.read_at(0, &mut buf), 0);
+    }
+}
@@ -0,0 +1,96 @@
+//! Tests of the frame allocator, page tables and the kernel heap.
+
+use crate::kernel_test;
+use crate::mm::{frame_alloc, frame_alloc_contiguous, free_frame_count, FrameTracker};
+use crate::mm::{PTEFlags, PageTable, VirtAddr, VirtPageNum, KERNEL_SPACE};
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+kernel_test! {
+    fn frames_come_back() {
+        // allocated first, lest the heap take frames of its own meanwhile
+        let mut frames: Vec<FrameTracker> = Vec::with_capacity(16);
+        let free = free_frame_count();
+        frames.extend((0..16).map(|_| frame_alloc().unwrap()));
+        assert_eq!(free_frame_count(), free - 16);
+        for (i, frame) in frames.iter().enumerate() {
+            assert!(frames[..i].iter().all(|other| other.ppn.0 != frame.ppn.0));
+        }
+        drop(frames);
+        assert_eq!(free_frame_count(), free);
+    }
+
+    fn frames_are_zeroed() {
+        let frame = frame_alloc().unwrap();
+        frame.ppn.get_bytes_array().fill(0xa5);
+        drop(frame);
+        let frame = frame_alloc().unwrap();
+        assert!(frame.ppn.get_bytes_array().iter().all(|&byte| byte == 0));
+    }
+
+    fn contiguous_frames_are_aligned() {
+        for order in [0, 3, 6] {
+            let frames = frame_alloc_contiguous(order).unwrap();
+            assert_eq!(frames.len(), 1 << order);
+            assert_eq!(frames[0].ppn.0 % (1 << order), 0);
+            for (i, frame) in frames.iter().enumerate() {
+                assert_eq!(frame.ppn.0, frames[0].ppn.0 + i);
+            }
+        }
+    }
+
+    fn page_table_maps_and_unmaps() {
+        let mut page_table = PageTable::new(0);
+        let frame = frame_alloc().unwrap();
+        let va = VirtAddr::from(0x1234_5678);
+        let vpn: VirtPageNum = va.floor();
+        page_table.map(vpn, frame.ppn, PTEFlags::R | PTEFlags::W | PTEFlags::U);
+        let pte = page_table.translate(vpn).unwrap();
+        assert!(pte.is_valid() && pte.readable() && pte.writable() && pte.is_user());
+        assert!(!pte.executable());
+        assert_eq!(pte.ppn().0, frame.ppn.0);
+        let pa: usize = page_table.translate_va(va).unwrap().into();
+        assert_eq!(pa % 4096, 0x678);
+        page_table.unmap(vpn);
+        assert!(!page_table.translate(vpn).map_or(false, |pte| pte.is_valid()));
+        // the neighbours were never there
+        assert!(!page_table
+            .translate(VirtPageNum(vpn.0 + 1))
+            .map_or(false, |pte| pte.is_valid()));
+    }
+
+    fn kernel_space_is_protected() {
+        extern "C" {
+            fn stext();
+            fn etext();
+            fn sdata();
+        }
+        let kernel_space = KERNEL_SPACE.exclusive_access();
+        let text = kernel_space
+            .translate(VirtAddr::from(stext as usize).floor())
+            .unwrap();
+        assert!(text.executable() && !text.writable());
+        let end_of_text = kernel_space
+            .translate(VirtAddr::from(etext as usize - 1).floor())
+            .unwrap();
+        assert!(end_of_text.executable() && !end_of_text.writable());
+        let data = kernel_space
+            .translate(VirtAddr::from(sdata as usize).floor())
+            .unwrap();
+        assert!(data.writable() && !data.executable());
+    }
+
+    fn heap_grows() {
+        let a = Box::new(5);
+        assert_eq!(*a, 5);
+        let v: Vec<usize> = (0..100_000).collect();
+        assert!(v.iter().enumerate().all(|(i, &x)| i == x));
+        let mut map = BTreeMap::new();
+        for i in 0..1000 {
+            map.insert(i * 7 % 1000, i);
+        }
+        assert_eq!(map.len(), 1000);
+        assert_eq!(map.iter().next(), Some((&0, &0)));
+    }
+}
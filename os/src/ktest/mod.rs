@@ -0,0 +1,89 @@
+//! Tests of the kernel run inside it, in kernels built with `make ktest`,
+//! which turns the `ktest` feature on. Such a kernel boots as ever but,
+//! rather than starting initproc, runs every test registered with
+//! kernel_test! in a kernel thread of its own, telling each on the UART,
+//! and leaves QEMU through its test device with 0 if all passed and 1
+//! otherwise, for scripts to tell.
+//!
+//! A test fails by panicking, as with an assert!; the panic handler ends
+//! the run then.
+//!
+//! ```ignore
+//! kernel_test! {
+//!     fn heap_box() {
+//!         assert_eq!(*Box::new(5), 5);
+//!     }
+//! }
+//! ```
+
+mod fs;
+mod mm;
+mod sync;
+mod task;
+
+use crate::board::qemu_exit;
+use crate::task::spawn_kernel_thread;
+use crate::timer::get_time_ms;
+use riscv::register::sstatus;
+
+pub struct KernelTest {
+    pub name: &'static str,
+    pub run: fn(),
+}
+
+/// Define a test function and register it, in the section .ktest which
+/// the linker gathers between sktest and ektest.
+#[macro_export]
+macro_rules! kernel_test {
+    ($(fn $name:ident() $body:block)*) => {
+        $(
+            fn $name() $body
+
+            const _: () = {
+                #[used]
+                #[link_section = ".ktest"]
+                static TEST: $crate::ktest::KernelTest = $crate::ktest::KernelTest {
+                    name: concat!(module_path!(), "::", stringify!($name)),
+                    run: $name,
+                };
+            };
+        )*
+    };
+}
+
+/// The tests registered, in the order of the link.
+fn tests() -> &'static [KernelTest] {
+    extern "C" {
+        fn sktest();
+        fn ektest();
+    }
+    let count = (ektest as usize - sktest as usize) / core::mem::size_of::<KernelTest>();
+    unsafe { core::slice::from_raw_parts(sktest as usize as *const KernelTest, count) }
+}
+
+/// Run the tests once the scheduler runs, in place of initproc.
+pub fn start() {
+    spawn_kernel_thread("ktest", ktest);
+}
+
+fn ktest() -> ! {
+    unsafe {
+        sstatus::set_sie();
+    }
+    let tests = tests();
+    println!("ktest: running {} tests", tests.len());
+    let start = get_time_ms();
+    for test in tests {
+        print!("test {} ... ", test.name.strip_prefix("os::").unwrap_or(test.name));
+        (test.run)();
+        println!("ok");
+    }
+    println!("ktest: {} passed in {} ms", tests.len(), get_time_ms() - start);
+    qemu_exit(0)
+}
+
+/// The test running failed, and with it the run.
+pub fn fail() -> ! {
+    println!("ktest: FAILED");
+    qemu_exit(1)
+}
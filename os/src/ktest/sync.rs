@@ -0,0 +1,94 @@
+//! Tests of the ring buffer of pipes and of semaphores and mutexes between
+//! kernel threads.
+
+use crate::fs::PipeRingBuffer;
+use crate::kernel_test;
+use crate::sync::{Mutex, MutexBlocking, Semaphore};
+use crate::task::spawn_kernel_thread;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use lazy_static::*;
+use riscv::register::sstatus;
+
+kernel_test! {
+    fn pipe_buffer_wraps() {
+        let mut buffer = PipeRingBuffer::new();
+        let capacity = buffer.capacity();
+        let mut back = [0u8; 100];
+        // round and round the end of the array
+        for round in 0..3 * capacity / 64 {
+            for i in 0..64 {
+                buffer.write_byte((round + i) as u8);
+            }
+            assert_eq!(buffer.read_bytes(&mut back[..64]), 64);
+            assert!((0..64).all(|i| back[i] == (round + i) as u8));
+        }
+        assert_eq!(buffer.available_read(), 0);
+        assert_eq!(buffer.available_write(), capacity);
+        for i in 0..capacity {
+            buffer.write_byte(i as u8);
+        }
+        assert_eq!(buffer.available_write(), 0);
+        assert_eq!(buffer.read_byte(), 0);
+        assert_eq!(buffer.read_bytes(&mut back), back.len());
+        assert_eq!(back[99], 100);
+    }
+
+    fn pipe_buffer_resizes() {
+        let mut buffer = PipeRingBuffer::new();
+        let capacity = buffer.capacity();
+        for i in 0..capacity {
+            buffer.write_byte(i as u8);
+        }
+        let mut back = [0u8; 10];
+        buffer.read_bytes(&mut back);
+        // too small for what it holds
+        assert!(!buffer.set_capacity(16));
+        assert!(buffer.set_capacity(2 * capacity));
+        assert_eq!(buffer.available_read(), capacity - 10);
+        assert_eq!(buffer.read_byte(), 10);
+    }
+
+    fn semaphores_hand_over() {
+        spawn_kernel_thread("ktest-pong", pong);
+        for i in 1..=100 {
+            PING.up();
+            PONG.down();
+            assert_eq!(PONGS.load(Ordering::Relaxed), i);
+        }
+    }
+
+    fn mutex_excludes() {
+        let mutex = MutexBlocking::new();
+        mutex.lock();
+        mutex.unlock();
+        mutex.lock();
+        mutex.unlock();
+        // a semaphore of 2 lets two through before it blocks
+        let semaphore = Semaphore::new(2);
+        semaphore.down();
+        semaphore.down();
+        assert_eq!(semaphore.inner.exclusive_access().count, 0);
+        semaphore.up();
+        semaphore.up();
+        assert_eq!(semaphore.inner.exclusive_access().count, 2);
+    }
+}
+
+lazy_static! {
+    static ref PING: Semaphore = Semaphore::new(0);
+    static ref PONG: Semaphore = Semaphore::new(0);
+}
+
+/// Times pong answered PING.
+static PONGS: AtomicUsize = AtomicUsize::new(0);
+
+fn pong() -> ! {
+    unsafe {
+        sstatus::set_sie();
+    }
+    loop {
+        PING.down();
+        PONGS.fetch_add(1, Ordering::Relaxed);
+        PONG.up();
+    }
+}
@@ -0,0 +1,93 @@
+//! Tests of the scheduler and of timers: kernel threads take turns, a
+//! task sleeping on a timer wakes once it is due, the future of a timer
+//! runs in kworker, and a thread parked in `block_on` gets what its future
+//! gives.
+
+use crate::kernel_test;
+use crate::task::{block_current_and_run_next, block_on, current_task, kernel_tasks};
+use crate::task::{sleep_ns, spawn_kernel_thread, suspend_current_and_run_next, TaskStatus};
+use crate::timer::{add_timer, add_timer_async, add_timer_call, ktime_now, NSEC_PER_MSEC};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use riscv::register::sstatus;
+
+/// Turns the spinner had.
+static SPINS: AtomicUsize = AtomicUsize::new(0);
+/// Calls of the timer callback.
+static CALLS: AtomicUsize = AtomicUsize::new(0);
+/// Steps the future of the timer took, with interrupts on in each.
+static ASYNC_STEPS: AtomicUsize = AtomicUsize::new(0);
+
+fn spinner() -> ! {
+    unsafe {
+        sstatus::set_sie();
+    }
+    loop {
+        SPINS.fetch_add(1, Ordering::Relaxed);
+        suspend_current_and_run_next();
+    }
+}
+
+fn count_call() {
+    CALLS.fetch_add(1, Ordering::Relaxed);
+}
+
+async fn count_steps() {
+    for _ in 0..2 {
+        if sstatus::read().sie() {
+            ASYNC_STEPS.fetch_add(1, Ordering::Relaxed);
+        }
+        sleep_ns(10 * NSEC_PER_MSEC).await;
+    }
+}
+
+kernel_test! {
+    fn threads_take_turns() {
+        spawn_kernel_thread("ktest-spinner", spinner);
+        let spins = SPINS.load(Ordering::Relaxed);
+        for _ in 0..10 {
+            suspend_current_and_run_next();
+        }
+        assert!(SPINS.load(Ordering::Relaxed) >= spins + 9);
+        assert!(kernel_tasks()
+            .iter()
+            .any(|(name, status)| *name == "ktest-spinner" && *status == TaskStatus::Ready));
+    }
+
+    fn sleep_on_timer() {
+        let deadline = ktime_now() + 30 * NSEC_PER_MSEC;
+        add_timer(deadline, current_task().unwrap());
+        block_current_and_run_next();
+        assert!(ktime_now() >= deadline);
+    }
+
+    fn timer_calls_back() {
+        let calls = CALLS.load(Ordering::Relaxed);
+        let start = ktime_now();
+        add_timer_call(start + 20 * NSEC_PER_MSEC, count_call);
+        while ktime_now() < start + 50 * NSEC_PER_MSEC {
+            suspend_current_and_run_next();
+        }
+        assert_eq!(CALLS.load(Ordering::Relaxed), calls + 1);
+    }
+
+    fn timer_runs_future() {
+        let steps = ASYNC_STEPS.load(Ordering::Relaxed);
+        let start = ktime_now();
+        add_timer_async(start + 20 * NSEC_PER_MSEC, count_steps());
+        // the future sleeps between its steps
+        while ktime_now() < start + 60 * NSEC_PER_MSEC {
+            suspend_current_and_run_next();
+        }
+        assert_eq!(ASYNC_STEPS.load(Ordering::Relaxed), steps + 2);
+    }
+
+    fn block_on_future() {
+        let start = ktime_now();
+        let value = block_on(async {
+            sleep_ns(20 * NSEC_PER_MSEC).await;
+            42
+        });
+        assert_eq!(value, 42);
+        assert!(ktime_now() >= start + 20 * NSEC_PER_MSEC);
+    }
+}
@@ -1,38 +1,31 @@
+use crate::backtrace::print_backtrace;
+use crate::mm::hart_id;
+#[cfg(not(feature = "ktest"))]
 use crate::sbi::shutdown;
-use crate::task::current_kstack_top;
-use core::arch::asm;
+use crate::task::current_task_name;
 use core::panic::PanicInfo;
-use log::*;
 
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
     if let Some(location) = info.location() {
         error!(
-            "[kernel] Panicked at {}:{} {}",
+            "Panicked at {}:{} {}",
             location.file(),
             location.line(),
             info.message().unwrap()
         );
     } else {
-        error!("[kernel] Panicked: {}", info.message().unwrap());
+        error!("Panicked: {}", info.message().unwrap());
     }
-    unsafe {
-        backtrace();
+    // which is all that is seen of a panic before the console is up
+    crate::logging::flush_early();
+    match current_task_name() {
+        Some(name) => println!("in {} on hart {}", name, hart_id()),
+        None => println!("in no task on hart {}", hart_id()),
     }
+    print_backtrace();
+    #[cfg(feature = "ktest")]
+    crate::ktest::fail();
+    #[cfg(not(feature = "ktest"))]
     shutdown(true)
 }
-
-unsafe fn backtrace() {
-    let mut fp: usize;
-    let stop = current_kstack_top();
-    asm!("mv {}, s0", out(reg) fp);
-    println!("---START BACKTRACE---");
-    for i in 0..10 {
-        if fp == stop {
-            break;
-        }
-        println!("#{}:ra={:#x}", i, *((fp - 8) as *const usize));
-        fp = *((fp - 16) as *const usize);
-    }
-    println!("---END   BACKTRACE---");
-}
@@ -0,0 +1,311 @@
+//! The kernel log: records of the `log` macros, error! to trace!, kept as
+//! lines in a ring buffer for the syslog system call and /proc/kmsg to
+//! read, and written to the console too if at or above its level.
+//!
+//! Which records are kept is up to a filter of a default level and levels
+//! of modules, like `warn,net=debug,fs::easyfs=trace`, the level of the
+//! longest module path a record comes from applying. The filter built in
+//! is the `LOG` environment variable at compile time, `info` without it,
+//! which `loglevel=` on the command line overrides, and syslog changes it
+//! at run time. The `log_max_info` feature leaves the debug and trace
+//! records out of the kernel altogether.
+//!
+//! Each line tells the time since boot, the level, the hart and the pid
+//! and tid of the task running, and the module:
+//!
+//! `[    1.234] INFO  [0 1:0] net::dhcp: eth0 is 10.0.2.15/24`
+//!
+//! The log starts before the console does, and holds the lines for the
+//! console until it is up, or a panic comes first and they go to the
+//! console of the SBI.
+
+use crate::cmdline;
+use crate::console::{self, print};
+use crate::mm::hart_id;
+use crate::sync::UPIntrFreeCell;
+use crate::task::current_ids;
+use crate::timer::get_time_ms;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use lazy_static::*;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// Bytes of the ring buffer, the oldest lines going once it is full.
+pub const LOG_BUF_SIZE: usize = 1 << 16;
+
+/// The filter without `LOG`.
+const DEFAULT_FILTER: &str = "info";
+
+/// A default level and the levels of modules.
+struct Filter {
+    default: LevelFilter,
+    /// module paths without the crate, like `fs::easyfs`
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl Filter {
+    /// A filter of comma-separated levels and `module=level`s, the last
+    /// level alone being the default; None if any of them is not one.
+    fn parse(spec: &str) -> Option<Self> {
+        let mut filter = Self {
+            default: LevelFilter::Info,
+            modules: Vec::new(),
+        };
+        for item in spec.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+            match item.split_once('=') {
+                Some((module, level)) => {
+                    let level = level.trim().parse().ok()?;
+                    filter.modules.push((String::from(module.trim()), level));
+                }
+                None => filter.default = item.parse().ok()?,
+            }
+        }
+        Some(filter)
+    }
+
+    /// The level of records from the module `target`.
+    fn level(&self, target: &str) -> LevelFilter {
+        let target = target.strip_prefix("os::").unwrap_or(target);
+        self.modules
+            .iter()
+            .filter(|(module, _)| {
+                target
+                    .strip_prefix(module.as_str())
+                    .map_or(false, |rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .max_by_key(|(module, _)| module.len())
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    /// The level of the module which lets the most through.
+    fn max_level(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, Ord::max)
+    }
+}
+
+/// Lines of text, of which the last `LOG_BUF_SIZE` bytes are held.
+struct LogBuffer {
+    /// byte `n` of the log at `n % LOG_BUF_SIZE`, once it has wrapped
+    data: Vec<u8>,
+    /// bytes ever written
+    end: usize,
+    /// where reads taking what they read are up to
+    read: usize,
+    /// where the log starts since it was last cleared
+    cleared: usize,
+}
+
+impl LogBuffer {
+    /// The earliest byte held.
+    fn start(&self) -> usize {
+        self.end.saturating_sub(LOG_BUF_SIZE).max(self.cleared)
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            if self.data.len() < LOG_BUF_SIZE {
+                self.data.push(byte);
+            } else {
+                self.data[self.end % LOG_BUF_SIZE] = byte;
+            }
+            self.end += 1;
+        }
+    }
+
+    /// The bytes held in `[from, to)`.
+    fn copy(&self, from: usize, to: usize) -> Vec<u8> {
+        (from.max(self.start())..to)
+            .map(|pos| self.data[pos % LOG_BUF_SIZE])
+            .collect()
+    }
+}
+
+struct KernelLog {
+    buffer: LogBuffer,
+    filter: Filter,
+    /// the least important records written to the console too
+    console_level: LevelFilter,
+    /// the console level to go back to once turned on again
+    saved_console_level: Option<LevelFilter>,
+    /// lines for the console before it was up
+    early: String,
+}
+
+lazy_static! {
+    static ref KLOG: UPIntrFreeCell<KernelLog> = unsafe {
+        UPIntrFreeCell::new(KernelLog {
+            buffer: LogBuffer {
+                data: Vec::new(),
+                end: 0,
+                read: 0,
+                cleared: 0,
+            },
+            filter: Filter::parse(option_env!("LOG").unwrap_or(DEFAULT_FILTER))
+                .or_else(|| Filter::parse(DEFAULT_FILTER))
+                .unwrap(),
+            console_level: LevelFilter::Info,
+            saved_console_level: None,
+            early: String::new(),
+        })
+    };
+}
+
+/// The line of `record`.
+fn format_line(record: &Record) -> String {
+    let time = get_time_ms();
+    let target = record.target();
+    let mut line = String::new();
+    write!(
+        line,
+        "[{:>5}.{:03}] {:<5} [{} ",
+        time / 1000,
+        time % 1000,
+        record.level(),
+        hart_id()
+    )
+    .unwrap();
+    match current_ids() {
+        Some((pid, tid)) => write!(line, "{}:{}]", pid, tid).unwrap(),
+        None => line.push_str("-]"),
+    }
+    writeln!(
+        line,
+        " {}: {}",
+        target.strip_prefix("os::").unwrap_or(target),
+        record.args()
+    )
+    .unwrap();
+    line
+}
+
+struct KernelLogger;
+
+impl Log for KernelLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        match KLOG.try_exclusive_access() {
+            Some(klog) => metadata.level() <= klog.filter.level(metadata.target()),
+            // logging from the log itself, or a panic while it was held
+            None => metadata.level() <= Level::Warn,
+        }
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format_line(record);
+        let to_console = match KLOG.try_exclusive_access() {
+            Some(mut klog) => {
+                klog.buffer.push(line.as_bytes());
+                if record.level() <= klog.console_level && !console::ready() {
+                    klog.early.push_str(&line);
+                    false
+                } else {
+                    record.level() <= klog.console_level
+                }
+            }
+            None => true,
+        };
+        if to_console {
+            print(format_args!("{}", line));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: KernelLogger = KernelLogger;
+
+/// Take the records of the `log` macros from now on, which the early heap
+/// holds until the heap is there.
+pub fn init() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(KLOG.exclusive_access().filter.max_level());
+    if let Some(spec) = cmdline::option("loglevel") {
+        if !set_log_filter(spec) {
+            warn!("loglevel={} is no filter of the log", spec);
+        }
+    }
+}
+
+/// Write the lines held for the console while it was not up, to the
+/// console of the SBI if it still is not.
+pub fn flush_early() {
+    let early = match KLOG.try_exclusive_access() {
+        Some(mut klog) => core::mem::take(&mut klog.early),
+        None => return,
+    };
+    print(format_args!("{}", early));
+}
+
+/// Change the filter to the one `spec` tells, as `LOG` does; false if it
+/// is not one.
+pub fn set_log_filter(spec: &str) -> bool {
+    match Filter::parse(spec) {
+        Some(filter) => {
+            log::set_max_level(filter.max_level());
+            KLOG.exclusive_access().filter = filter;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Write records of `level` and above to the console, or none for Off.
+pub fn set_console_level(level: LevelFilter) {
+    let mut klog = KLOG.exclusive_access();
+    klog.console_level = level;
+    klog.saved_console_level = None;
+}
+
+/// Stop writing records to the console, or go back to writing them if
+/// `on`.
+pub fn set_console_on(on: bool) {
+    let mut klog = KLOG.exclusive_access();
+    if on {
+        if let Some(level) = klog.saved_console_level.take() {
+            klog.console_level = level;
+        }
+    } else if klog.saved_console_level.is_none() {
+        klog.saved_console_level = Some(klog.console_level);
+        klog.console_level = LevelFilter::Off;
+    }
+}
+
+/// The last `len` bytes of the log, whether read already or not.
+pub fn log_read_all(len: usize) -> Vec<u8> {
+    let klog = KLOG.exclusive_access();
+    let end = klog.buffer.end;
+    klog.buffer.copy(end.saturating_sub(len), end)
+}
+
+/// Up to `len` bytes of the log not read by log_take yet, taken.
+pub fn log_take(len: usize) -> Vec<u8> {
+    let mut klog = KLOG.exclusive_access();
+    let from = klog.buffer.read.max(klog.buffer.start());
+    let to = klog.buffer.end.min(from + len);
+    klog.buffer.read = to;
+    klog.buffer.copy(from, to)
+}
+
+/// How many bytes of the log log_take has yet to take.
+pub fn log_unread() -> usize {
+    let klog = KLOG.exclusive_access();
+    klog.buffer.end - klog.buffer.read.max(klog.buffer.start())
+}
+
+/// Forget what the log holds.
+pub fn log_clear() {
+    let mut klog = KLOG.exclusive_access();
+    klog.buffer.cleared = klog.buffer.end;
+}
+
+/// The whole log held, for /proc/kmsg.
+pub fn log_text() -> String {
+    let bytes = log_read_all(LOG_BUF_SIZE);
+    String::from_utf8_lossy(&bytes).into_owned()
+}
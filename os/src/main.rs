@@ -9,27 +9,47 @@ extern crate alloc;
 
 #[macro_use]
 extern crate bitflags;
+#[macro_use]
+extern crate log;
 
-#[path = "boards/qemu.rs"]
+#[cfg_attr(feature = "board_visionfive2", path = "boards/visionfive2.rs")]
+#[cfg_attr(not(feature = "board_visionfive2"), path = "boards/qemu.rs")]
 mod board;
 
 #[macro_use]
 mod console;
+mod backtrace;
+mod cmdline;
 mod config;
 mod drivers;
+#[cfg(feature = "fault_inject")]
+mod fault;
 mod fs;
+mod gdbstub;
+#[cfg(feature = "hardening")]
+mod hardening;
+mod kaslr;
+#[cfg(feature = "kcov")]
+mod kcov;
+#[cfg(feature = "ktest")]
+mod ktest;
 mod lang_items;
+mod logging;
 mod mm;
 mod net;
+mod objects;
+mod perf;
+mod power;
+mod random;
+mod replay;
 mod sbi;
 mod sync;
 mod syscall;
 mod task;
 mod timer;
+mod trace;
 mod trap;
-
-use crate::drivers::chardev::CharDevice;
-use crate::drivers::chardev::UART;
+mod watchdog;
 
 core::arch::global_asm!(include_str!("entry.asm"));
 
@@ -53,23 +73,56 @@ lazy_static! {
 }
 
 #[no_mangle]
-pub fn rust_main() -> ! {
+pub fn rust_main(hart_id: usize, dtb_pa: usize, kernel_offset: usize) -> ! {
     clear_bss();
+    kaslr::init(kernel_offset);
+    // the early phase, on the boot page table of entry.asm and the early
+    // heap, writing to the console of the SBI and the log holding its lines
+    // for the UART
+    cmdline::init(dtb_pa);
+    logging::init();
+    info!("command line: {}", cmdline::cmdline());
+    timer::init(dtb_pa);
+    if kernel_offset != 0 {
+        info!("KASLR: kernel offset {:#x}", kernel_offset);
+    }
+    sbi::init();
     mm::init();
-    UART.init();
-    println!("KERN: init gpu");
+    #[cfg(feature = "hardening")]
+    {
+        hardening::init();
+        // here, as rust_main never returns, and no function returns with
+        // a canary other than the one it started with
+        unsafe { hardening::__stack_chk_guard = hardening::canary() };
+    }
+    // the late phase, the drivers starting with the console
+    console::init();
+    info!("init gpu");
     let _gpu = GPU_DEVICE.clone();
-    println!("KERN: init keyboard");
+    info!("init keyboard");
     let _keyboard = KEYBOARD_DEVICE.clone();
-    println!("KERN: init mouse");
+    info!("init mouse");
     let _mouse = MOUSE_DEVICE.clone();
-    println!("KERN: init trap");
+    info!("init trap");
     trap::init();
     trap::enable_timer_interrupt();
     timer::set_next_trigger();
-    board::device_init();
+    perf::init();
+    board::device_init(hart_id);
+    gdbstub::init();
+    fs::init();
     fs::list_apps();
+    task::sched_init();
+    #[cfg(not(feature = "ktest"))]
     task::add_initproc();
+    #[cfg(feature = "ktest")]
+    ktest::start();
+    task::start_kswapd();
+    task::start_flusher();
+    task::start_executor();
+    net::start_netd();
+    #[cfg(feature = "netbench")]
+    net::start_netbench();
     *DEV_NON_BLOCKING_ACCESS.exclusive_access() = true;
     task::run_tasks();
     panic!("Unreachable in rust_main!");
@@ -1,5 +1,6 @@
-use super::PageTableEntry;
-use crate::config::{PAGE_SIZE, PAGE_SIZE_BITS};
+use super::{kernel_token, PageTable, PageTableEntry};
+use crate::config::{MEMORY_END, MEMORY_START, PAGE_SIZE, PAGE_SIZE_BITS, PHYS_VIRT_OFFSET};
+use crate::kaslr::link_address;
 use core::fmt::{self, Debug, Formatter};
 
 const PA_WIDTH_SV39: usize = 56;
@@ -167,22 +168,47 @@ impl VirtPageNum {
     }
 }
 
+/// Where the kernel reaches physical address `pa`, in the direct map.
+pub const fn phys_to_virt(pa: usize) -> usize {
+    pa + PHYS_VIRT_OFFSET
+}
+
+/// The physical address behind the kernel address `va`, which is only
+/// looked up in the page table outside the direct map and the kernel image.
+pub fn virt_to_phys(va: usize) -> usize {
+    extern "C" {
+        fn skernel();
+        fn ekernel();
+    }
+    if (phys_to_virt(MEMORY_START)..phys_to_virt(MEMORY_END)).contains(&va) {
+        va - PHYS_VIRT_OFFSET
+    } else if (skernel as usize..ekernel as usize).contains(&va) {
+        link_address(va)
+    } else {
+        // e.g. kernel stacks
+        PageTable::from_token(kernel_token())
+            .translate_va(VirtAddr::from(va))
+            .unwrap()
+            .0
+    }
+}
+
 impl PhysAddr {
     pub fn get_ref<T>(&self) -> &'static T {
-        unsafe { (self.0 as *const T).as_ref().unwrap() }
+        unsafe { (phys_to_virt(self.0) as *const T).as_ref().unwrap() }
     }
     pub fn get_mut<T>(&self) -> &'static mut T {
-        unsafe { (self.0 as *mut T).as_mut().unwrap() }
+        unsafe { (phys_to_virt(self.0) as *mut T).as_mut().unwrap() }
     }
 }
 impl PhysPageNum {
     pub fn get_pte_array(&self) -> &'static mut [PageTableEntry] {
         let pa: PhysAddr = (*self).into();
-        unsafe { core::slice::from_raw_parts_mut(pa.0 as *mut PageTableEntry, 512) }
+        unsafe { core::slice::from_raw_parts_mut(phys_to_virt(pa.0) as *mut PageTableEntry, 512) }
     }
     pub fn get_bytes_array(&self) -> &'static mut [u8] {
         let pa: PhysAddr = (*self).into();
-        unsafe { core::slice::from_raw_parts_mut(pa.0 as *mut u8, 4096) }
+        unsafe { core::slice::from_raw_parts_mut(phys_to_virt(pa.0) as *mut u8, 4096) }
     }
     pub fn get_mut<T>(&self) -> &'static mut T {
         let pa: PhysAddr = (*self).into();
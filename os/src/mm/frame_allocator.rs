@@ -1,8 +1,13 @@
 use super::{PhysAddr, PhysPageNum};
-use crate::config::MEMORY_END;
+use crate::cmdline;
+use crate::config::{FREE_FRAMES_LOW, MEMORY_END, PAGE_SIZE};
+use crate::kaslr::link_address;
 use crate::sync::UPIntrFreeCell;
+use crate::task::wakeup_kswapd;
 use alloc::vec::Vec;
+use bitflags::*;
 use core::fmt::{self, Debug, Formatter};
+use core::ops::Range;
 use lazy_static::*;
 
 pub struct FrameTracker {
@@ -18,6 +23,58 @@ impl FrameTracker {
         }
         Self { ppn }
     }
+    /// Number of trackers of this frame, i.e. of its owners.
+    pub fn ref_count(&self) -> usize {
+        FRAME_ALLOCATOR
+            .exclusive_access()
+            .page(self.ppn)
+            .ref_count()
+    }
+    /// Whether the frame is pinned, see `PinnedFrame`.
+    pub fn is_pinned(&self) -> bool {
+        FRAME_ALLOCATOR
+            .exclusive_access()
+            .page(self.ppn)
+            .flags
+            .contains(PageFlags::PINNED)
+    }
+    /// Mark the frame as a page of the page cache, which counts it.
+    pub fn set_cached(&self) {
+        FRAME_ALLOCATOR.exclusive_access().set_cached(self.ppn);
+    }
+    /// Mark the frame as written since it was last written back.
+    pub fn set_dirty(&self) {
+        FRAME_ALLOCATOR
+            .exclusive_access()
+            .allocated_page(self.ppn)
+            .flags
+            .insert(PageFlags::DIRTY);
+    }
+    /// Mark the frame as mapped writable by MAP_SHARED, or no longer.
+    pub fn set_mapped_write(&self, mapped: bool) {
+        FRAME_ALLOCATOR
+            .exclusive_access()
+            .allocated_page(self.ppn)
+            .flags
+            .set(PageFlags::MAPPED_WRITE, mapped);
+    }
+    /// Clear the dirty mark and return whether it was set, or the frame may
+    /// have been written through a mapping since.
+    pub fn test_clear_dirty(&self) -> bool {
+        let mut allocator = FRAME_ALLOCATOR.exclusive_access();
+        let flags = &mut allocator.allocated_page(self.ppn).flags;
+        let dirty = flags.intersects(PageFlags::DIRTY | PageFlags::MAPPED_WRITE);
+        flags.remove(PageFlags::DIRTY);
+        dirty
+    }
+}
+
+impl Clone for FrameTracker {
+    /// Share the frame, which is freed when the last tracker is dropped.
+    fn clone(&self) -> Self {
+        FRAME_ALLOCATOR.exclusive_access().get(self.ppn);
+        Self { ppn: self.ppn }
+    }
 }
 
 impl Debug for FrameTracker {
@@ -28,7 +85,87 @@ impl Debug for FrameTracker {
 
 impl Drop for FrameTracker {
     fn drop(&mut self) {
-        frame_dealloc(self.ppn);
+        FRAME_ALLOCATOR.exclusive_access().put(self.ppn);
+    }
+}
+
+/// A reference to a frame which must stay where it is while the kernel or
+/// a device accesses it, such as a user buffer a system call sleeps on.
+/// Reclaim leaves the frame alone as long as any pin is held.
+pub struct PinnedFrame {
+    frame: FrameTracker,
+}
+
+impl Drop for PinnedFrame {
+    fn drop(&mut self) {
+        // the frame itself goes with the tracker
+        FRAME_ALLOCATOR.exclusive_access().unpin(self.frame.ppn);
+    }
+}
+
+/// Pin the frame `ppn` if it comes from the frame allocator. Other frames,
+/// like the ones of devices, are never reclaimed anyway.
+pub fn pin_frame(ppn: PhysPageNum) -> Option<PinnedFrame> {
+    let mut allocator = FRAME_ALLOCATOR.exclusive_access();
+    if !allocator.is_allocated(ppn) {
+        return None;
+    }
+    allocator.get(ppn);
+    allocator.pin(ppn);
+    Some(PinnedFrame {
+        frame: FrameTracker { ppn },
+    })
+}
+
+bitflags! {
+    pub struct PageFlags: u8 {
+        /// handed out by the frame allocator
+        const ALLOCATED = 1 << 0;
+        /// first frame of a free block, on the free list of its order
+        const BUDDY = 1 << 1;
+        /// held by a `PinnedFrame`, not to be reclaimed
+        const PINNED = 1 << 2;
+        /// holds a page of a file in the page cache
+        const CACHED = 1 << 3;
+        /// cached page written since it was last written back
+        const DIRTY = 1 << 4;
+        /// mapped writable by MAP_SHARED, and so written behind the back of
+        /// the page cache at any time
+        const MAPPED_WRITE = 1 << 5;
+    }
+}
+
+/// Blocks of up to 2^(MAX_ORDER - 1) frames are handed out at once.
+pub const MAX_ORDER: usize = 11;
+
+const NO_PAGE: usize = usize::MAX;
+
+/// Metadata of a physical page frame, like `struct page` of Linux.
+pub struct Page {
+    flags: PageFlags,
+    ref_count: usize,
+    /// number of `PinnedFrame`s, which hold references of their own
+    pin_count: usize,
+    /// order of the free block it starts, if BUDDY
+    order: usize,
+    /// indices of the neighbours on the free list
+    prev_free: usize,
+    next_free: usize,
+}
+
+impl Page {
+    fn new() -> Self {
+        Self {
+            flags: PageFlags::empty(),
+            ref_count: 0,
+            pin_count: 0,
+            order: 0,
+            prev_free: NO_PAGE,
+            next_free: NO_PAGE,
+        }
+    }
+    pub fn ref_count(&self) -> usize {
+        self.ref_count
     }
 }
 
@@ -39,87 +176,313 @@ trait FrameAllocator {
     fn dealloc(&mut self, ppn: PhysPageNum);
 }
 
-pub struct StackFrameAllocator {
-    current: usize,
+/// Hands out the frames in `[base, end)` as blocks of 2^order frames
+/// aligned to their size, splitting larger free blocks and merging freed
+/// ones with their buddies. Frame metadata is kept in `mem_map` indexed by
+/// PFN from `base`, and free blocks are linked through it.
+pub struct BuddyFrameAllocator {
+    base: usize,
     end: usize,
-    recycled: Vec<usize>,
+    mem_map: Vec<Page>,
+    /// first free block of each order
+    free_area: [usize; MAX_ORDER],
+    free_frames: usize,
+    /// frames of the page cache
+    cached_frames: usize,
 }
 
-impl StackFrameAllocator {
-    pub fn init(&mut self, l: PhysPageNum, r: PhysPageNum) {
-        self.current = l.0;
+impl BuddyFrameAllocator {
+    /// Manage the frames of `[l, r)`, those of `reserved` handed out
+    /// already, to be freed one by one.
+    pub fn init(&mut self, l: PhysPageNum, r: PhysPageNum, reserved: Range<usize>) {
+        self.base = l.0;
         self.end = r.0;
-        // println!("last {} Physical Frames.", self.end - self.current);
+        self.mem_map = (l.0..r.0).map(|_| Page::new()).collect();
+        let reserved = reserved.start.clamp(l.0, r.0)..reserved.end.clamp(l.0, r.0);
+        for page in &mut self.mem_map[reserved.start - l.0..reserved.end - l.0] {
+            page.flags = PageFlags::ALLOCATED;
+            page.ref_count = 1;
+        }
+        self.push_range(l.0, reserved.start);
+        self.push_range(reserved.end, r.0);
+        self.free_frames = r.0 - l.0 - reserved.len();
+        // println!("last {} Physical Frames.", self.end - self.base);
+    }
+    /// Free `[from, to)`, cut into the largest aligned blocks.
+    fn push_range(&mut self, from: usize, to: usize) {
+        let mut pfn = from;
+        while pfn < to {
+            let mut order = MAX_ORDER - 1;
+            while pfn % (1 << order) != 0 || pfn + (1 << order) > to {
+                order -= 1;
+            }
+            self.push_free(pfn, order);
+            pfn += 1 << order;
+        }
+    }
+    pub fn page(&self, ppn: PhysPageNum) -> &Page {
+        &self.mem_map[ppn.0 - self.base]
+    }
+    fn allocated_page(&mut self, ppn: PhysPageNum) -> &mut Page {
+        let page = match ppn.0.checked_sub(self.base) {
+            Some(index) if ppn.0 < self.end => &mut self.mem_map[index],
+            _ => panic!("Frame ppn={:#x} has not been allocated!", ppn.0),
+        };
+        if !page.flags.contains(PageFlags::ALLOCATED) {
+            panic!("Frame ppn={:#x} has not been allocated!", ppn.0);
+        }
+        page
+    }
+    fn is_allocated(&self, ppn: PhysPageNum) -> bool {
+        (self.base..self.end).contains(&ppn.0)
+            && self.mem_map[ppn.0 - self.base]
+                .flags
+                .contains(PageFlags::ALLOCATED)
+    }
+    fn push_free(&mut self, pfn: usize, order: usize) {
+        let index = pfn - self.base;
+        let next = self.free_area[order];
+        let page = &mut self.mem_map[index];
+        page.flags = PageFlags::BUDDY;
+        page.ref_count = 0;
+        page.order = order;
+        page.prev_free = NO_PAGE;
+        page.next_free = next;
+        if next != NO_PAGE {
+            self.mem_map[next].prev_free = index;
+        }
+        self.free_area[order] = index;
+    }
+    fn remove_free(&mut self, index: usize) {
+        let page = &mut self.mem_map[index];
+        let (prev, next, order) = (page.prev_free, page.next_free, page.order);
+        page.flags = PageFlags::empty();
+        page.prev_free = NO_PAGE;
+        page.next_free = NO_PAGE;
+        if prev != NO_PAGE {
+            self.mem_map[prev].next_free = next;
+        } else {
+            self.free_area[order] = next;
+        }
+        if next != NO_PAGE {
+            self.mem_map[next].prev_free = prev;
+        }
+    }
+    /// Take 2^order contiguous frames aligned to their size and return the
+    /// first one. Each of them is tracked and freed on its own.
+    pub fn alloc_contiguous(&mut self, order: usize) -> Option<PhysPageNum> {
+        let mut k = (order..MAX_ORDER).find(|k| self.free_area[*k] != NO_PAGE)?;
+        let index = self.free_area[k];
+        self.remove_free(index);
+        let pfn = self.base + index;
+        // give back the upper halves
+        while k > order {
+            k -= 1;
+            self.push_free(pfn + (1 << k), k);
+        }
+        for page in &mut self.mem_map[index..index + (1 << order)] {
+            page.flags = PageFlags::ALLOCATED;
+            page.ref_count = 1;
+        }
+        self.free_frames -= 1 << order;
+        Some(pfn.into())
+    }
+    fn free_frames(&self) -> usize {
+        self.free_frames
+    }
+    /// Take one more reference to an allocated frame.
+    pub fn get(&mut self, ppn: PhysPageNum) {
+        self.allocated_page(ppn).ref_count += 1;
+    }
+    fn pin(&mut self, ppn: PhysPageNum) {
+        let page = self.allocated_page(ppn);
+        page.pin_count += 1;
+        page.flags.insert(PageFlags::PINNED);
+    }
+    fn unpin(&mut self, ppn: PhysPageNum) {
+        let page = self.allocated_page(ppn);
+        page.pin_count -= 1;
+        if page.pin_count == 0 {
+            page.flags.remove(PageFlags::PINNED);
+        }
+    }
+    fn set_cached(&mut self, ppn: PhysPageNum) {
+        let page = self.allocated_page(ppn);
+        if !page.flags.contains(PageFlags::CACHED) {
+            page.flags.insert(PageFlags::CACHED);
+            self.cached_frames += 1;
+        }
+    }
+    /// Drop a reference to an allocated frame and free it with the last one.
+    pub fn put(&mut self, ppn: PhysPageNum) {
+        let page = self.allocated_page(ppn);
+        page.ref_count -= 1;
+        if page.ref_count == 0 {
+            self.dealloc(ppn);
+        }
     }
 }
-impl FrameAllocator for StackFrameAllocator {
+
+impl FrameAllocator for BuddyFrameAllocator {
     fn new() -> Self {
         Self {
-            current: 0,
+            base: 0,
             end: 0,
-            recycled: Vec::new(),
+            mem_map: Vec::new(),
+            free_area: [NO_PAGE; MAX_ORDER],
+            free_frames: 0,
+            cached_frames: 0,
         }
     }
     fn alloc(&mut self) -> Option<PhysPageNum> {
-        if let Some(ppn) = self.recycled.pop() {
-            Some(ppn.into())
-        } else if self.current == self.end {
-            None
-        } else {
-            self.current += 1;
-            Some((self.current - 1).into())
-        }
+        self.alloc_contiguous(0)
     }
     fn alloc_more(&mut self, pages: usize) -> Option<Vec<PhysPageNum>> {
-        if self.current + pages >= self.end {
-            None
-        } else {
-            self.current += pages;
-            let arr: Vec<usize> = (1..pages + 1).collect();
-            let v = arr.iter().map(|x| (self.current - x).into()).collect();
-            Some(v)
+        let order = pages.next_power_of_two().trailing_zeros() as usize;
+        let first = self.alloc_contiguous(order)?.0;
+        // the rest of the block is not needed
+        for ppn in first + pages..first + (1 << order) {
+            self.dealloc(ppn.into());
         }
+        Some((first..first + pages).map(PhysPageNum).collect())
     }
     fn dealloc(&mut self, ppn: PhysPageNum) {
-        let ppn = ppn.0;
         // validity check
-        if ppn >= self.current || self.recycled.iter().any(|&v| v == ppn) {
-            panic!("Frame ppn={:#x} has not been allocated!", ppn);
+        let page = self.allocated_page(ppn);
+        if page.flags.contains(PageFlags::CACHED) {
+            self.cached_frames -= 1;
         }
-        // recycle
-        self.recycled.push(ppn);
+        // the frame is free even if it ends up in the block of its buddy
+        page.flags = PageFlags::empty();
+        page.ref_count = 0;
+        self.free_frames += 1;
+        // merge with free buddies as far as possible
+        let mut pfn = ppn.0;
+        let mut order = 0;
+        while order < MAX_ORDER - 1 {
+            let buddy = pfn ^ (1 << order);
+            if buddy < self.base || buddy >= self.end {
+                break;
+            }
+            let page = &self.mem_map[buddy - self.base];
+            if !page.flags.contains(PageFlags::BUDDY) || page.order != order {
+                break;
+            }
+            self.remove_free(buddy - self.base);
+            pfn = pfn.min(buddy);
+            order += 1;
+        }
+        self.push_free(pfn, order);
     }
 }
 
-type FrameAllocatorImpl = StackFrameAllocator;
+type FrameAllocatorImpl = BuddyFrameAllocator;
 
 lazy_static! {
     pub static ref FRAME_ALLOCATOR: UPIntrFreeCell<FrameAllocatorImpl> =
         unsafe { UPIntrFreeCell::new(FrameAllocatorImpl::new()) };
 }
 
+/// Manage the frames after the kernel image, but for those of the initrd,
+/// which are freed once it is unpacked.
 pub fn init_frame_allocator() {
     extern "C" {
         fn ekernel();
     }
+    let reserved = match cmdline::initrd() {
+        Some((start, end)) => PhysAddr::from(start).floor().0..PhysAddr::from(end).ceil().0,
+        None => 0..0,
+    };
     FRAME_ALLOCATOR.exclusive_access().init(
-        PhysAddr::from(ekernel as usize).ceil(),
+        PhysAddr::from(link_address(ekernel as usize)).ceil(),
         PhysAddr::from(MEMORY_END).floor(),
+        reserved,
     );
 }
 
+/// Free the frames of the initrd, which has been unpacked.
+pub fn release_initrd() {
+    let (start, end) = match cmdline::initrd() {
+        Some(initrd) => initrd,
+        None => return,
+    };
+    let mut allocator = FRAME_ALLOCATOR.exclusive_access();
+    let (base, limit) = (allocator.base, allocator.end);
+    let first = PhysAddr::from(start).floor().0.clamp(base, limit);
+    let last = PhysAddr::from(end).ceil().0.clamp(base, limit);
+    for pfn in first..last {
+        allocator.dealloc(pfn.into());
+    }
+    info!("freed {} KiB of initrd", (last - first) * PAGE_SIZE / 1024);
+}
+
+pub fn free_frame_count() -> usize {
+    FRAME_ALLOCATOR.exclusive_access().free_frames()
+}
+
+/// Wake up kswapd if the frames are running low.
+fn check_watermark() {
+    if free_frame_count() < FREE_FRAMES_LOW {
+        wakeup_kswapd();
+    }
+}
+
 pub fn frame_alloc() -> Option<FrameTracker> {
-    FRAME_ALLOCATOR
+    let frame = FRAME_ALLOCATOR
         .exclusive_access()
         .alloc()
-        .map(FrameTracker::new)
+        .map(FrameTracker::new);
+    check_watermark();
+    frame
+}
+
+/// Allocate a frame of a user page, whose failure the fault is failed
+/// with; the frames fault injection fails.
+pub fn frame_alloc_user() -> Option<FrameTracker> {
+    #[cfg(feature = "fault_inject")]
+    if crate::fault::should_fail(crate::fault::FaultPoint::Alloc) {
+        return None;
+    }
+    frame_alloc()
 }
 
 pub fn frame_alloc_more(num: usize) -> Option<Vec<FrameTracker>> {
-    FRAME_ALLOCATOR
+    let frames = FRAME_ALLOCATOR
         .exclusive_access()
         .alloc_more(num)
-        .map(|x| x.iter().map(|&t| FrameTracker::new(t)).collect())
+        .map(|x| x.iter().map(|&t| FrameTracker::new(t)).collect());
+    check_watermark();
+    frames
+}
+
+/// Allocate 2^order contiguous frames aligned to their size, lowest first.
+pub fn frame_alloc_contiguous(order: usize) -> Option<Vec<FrameTracker>> {
+    let first = FRAME_ALLOCATOR.exclusive_access().alloc_contiguous(order)?;
+    Some(
+        (first.0..first.0 + (1 << order))
+            .map(|ppn| FrameTracker::new(ppn.into()))
+            .collect(),
+    )
+}
+
+/// Take 2^order contiguous frames for good, for the kernel heap. Fail
+/// rather than panic if the allocator is busy, as when it grows its own
+/// metadata.
+pub fn frame_alloc_for_heap(order: usize) -> Option<PhysPageNum> {
+    FRAME_ALLOCATOR
+        .try_exclusive_access()?
+        .alloc_contiguous(order)
+}
+
+/// Numbers of free frames and of all frames.
+pub fn frame_stats() -> Option<(usize, usize)> {
+    let allocator = FRAME_ALLOCATOR.try_exclusive_access()?;
+    Some((allocator.free_frames(), allocator.end - allocator.base))
+}
+
+/// Number of frames of the page cache.
+pub fn cached_frames() -> Option<usize> {
+    Some(FRAME_ALLOCATOR.try_exclusive_access()?.cached_frames)
 }
 
 pub fn frame_dealloc(ppn: PhysPageNum) {
@@ -160,3 +523,17 @@ pub fn frame_allocator_alloc_more_test() {
     drop(v);
     println!("frame_allocator_test passed!");
 }
+
+#[allow(unused)]
+pub fn frame_allocator_contiguous_test() {
+    let frames = frame_alloc_contiguous(3).unwrap();
+    assert_eq!(frames[0].ppn.0 % 8, 0);
+    for (i, frame) in frames.iter().enumerate() {
+        assert_eq!(frame.ppn.0, frames[0].ppn.0 + i);
+    }
+    drop(frames);
+    // frames are freed one by one and merged back into blocks
+    let frames = frame_alloc_contiguous(MAX_ORDER - 1).unwrap();
+    assert_eq!(frames[0].ppn.0 % (1 << (MAX_ORDER - 1)), 0);
+    println!("frame_allocator_contiguous_test passed!");
+}
@@ -1,22 +1,199 @@
-use crate::config::KERNEL_HEAP_SIZE;
-use buddy_system_allocator::LockedHeap;
+use super::frame_allocator::{frame_alloc_for_heap, frame_stats, MAX_ORDER};
+use super::slab::{print_slab_stats, slab_alloc, slab_dealloc};
+use super::swap::swap_stats;
+use super::{phys_to_virt, PhysAddr};
+use crate::config::{KERNEL_HEAP_SIZE, PAGE_SIZE};
+use crate::task::task_counts;
+use alloc::vec::Vec;
+use buddy_system_allocator::{Heap, LockedHeap};
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// The kernel heap, which also backs the slabs.
+pub static HEAP: LockedHeap = LockedHeap::empty();
+
+/// The heap grows by at least 2^HEAP_GROW_ORDER frames at a time.
+const HEAP_GROW_ORDER: usize = 6;
+
+/// Bytes of frames added to the heap beyond HEAP_SPACE.
+static HEAP_GROWN: AtomicUsize = AtomicUsize::new(0);
+
+/// Add a block of frames large enough for `layout` to the heap. They are
+/// reached through the direct map and never given back.
+fn grow_heap(heap: &mut Heap, layout: Layout) -> bool {
+    let bytes = layout.size().max(layout.align()).next_power_of_two();
+    let pages = (bytes + PAGE_SIZE - 1) / PAGE_SIZE;
+    let order = (pages.trailing_zeros() as usize).max(HEAP_GROW_ORDER);
+    if order >= MAX_ORDER {
+        return false;
+    }
+    let start: PhysAddr = match frame_alloc_for_heap(order) {
+        Some(ppn) => ppn.into(),
+        None => return false,
+    };
+    let size = PAGE_SIZE << order;
+    let start = phys_to_virt(start.0);
+    unsafe {
+        heap.add_to_heap(start, start + size);
+    }
+    HEAP_GROWN.fetch_add(size, Ordering::Relaxed);
+    true
+}
+
+/// Allocate from the heap, growing it if needed, or return null.
+pub fn heap_alloc(layout: Layout) -> *mut u8 {
+    let mut heap = HEAP.lock();
+    loop {
+        if let Ok(ptr) = heap.alloc(layout) {
+            return ptr.as_ptr();
+        }
+        if !grow_heap(&mut heap, layout) {
+            return ptr::null_mut();
+        }
+    }
+}
+
+/// `len` zeroed bytes, or None if the heap has no room for them, for
+/// buffers as large as a user asks, which fail with ENOMEM rather than
+/// panicking. These are the heap allocations fault injection fails.
+pub fn try_zeroed_bytes(len: usize) -> Option<Vec<u8>> {
+    #[cfg(feature = "fault_inject")]
+    if crate::fault::should_fail(crate::fault::FaultPoint::Heap) {
+        return None;
+    }
+    let mut bytes = Vec::new();
+    bytes.try_reserve_exact(len).ok()?;
+    bytes.resize(len, 0);
+    Some(bytes)
+}
+
+/// Bytes the kernel may allocate before the heap is there, which the log
+/// takes most of.
+const EARLY_HEAP_SIZE: usize = 0x1_0000;
+
+static mut EARLY_HEAP: [u8; EARLY_HEAP_SIZE] = [0; EARLY_HEAP_SIZE];
+/// Bytes of EARLY_HEAP handed out.
+static EARLY_USED: AtomicUsize = AtomicUsize::new(0);
+/// Whether the heap and the slab caches serve the allocations.
+static HEAP_READY: AtomicBool = AtomicBool::new(false);
+
+/// Take `layout` off the early heap, which is never freed, or return null
+/// once it is used up.
+fn early_alloc(layout: Layout) -> *mut u8 {
+    let base = unsafe { EARLY_HEAP.as_ptr() as usize };
+    let mut used = EARLY_USED.load(Ordering::Relaxed);
+    loop {
+        let start = (base + used + layout.align() - 1) & !(layout.align() - 1);
+        let end = start + layout.size();
+        if end > base + EARLY_HEAP_SIZE {
+            return ptr::null_mut();
+        }
+        match EARLY_USED.compare_exchange(used, end - base, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => return start as *mut u8,
+            Err(now) => used = now,
+        }
+    }
+}
+
+fn in_early_heap(ptr: *mut u8) -> bool {
+    let base = unsafe { EARLY_HEAP.as_ptr() as usize };
+    (base..base + EARLY_HEAP_SIZE).contains(&(ptr as usize))
+}
+
+/// Serves small objects from the slab caches and the rest from the heap,
+/// or all of them from the early heap until those are up.
+struct KernelAllocator;
+
+unsafe impl GlobalAlloc for KernelAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if !HEAP_READY.load(Ordering::Acquire) {
+            return early_alloc(layout);
+        }
+        if let Some(ptr) = slab_alloc(layout) {
+            return ptr;
+        }
+        heap_alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if in_early_heap(ptr) {
+            // left where it is, as the early heap only ever grows
+            return;
+        }
+        if !slab_dealloc(ptr, layout) {
+            HEAP.lock().dealloc(NonNull::new_unchecked(ptr), layout);
+        }
+    }
+}
 
 #[global_allocator]
-static HEAP_ALLOCATOR: LockedHeap = LockedHeap::empty();
+static KERNEL_ALLOCATOR: KernelAllocator = KernelAllocator;
 
 #[alloc_error_handler]
 pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
+    print_memory_usage();
     panic!("Heap allocation error, layout = {:?}", layout);
 }
 
+/// Bytes of the heap in use and in all, or None if it is locked.
+pub fn heap_stats() -> Option<(usize, usize)> {
+    let heap = HEAP.try_lock()?;
+    Some((heap.stats_alloc_actual(), heap.stats_total_bytes()))
+}
+
+/// Print what the memory is used for, skipping whatever is locked at the
+/// moment.
+pub fn print_memory_usage() {
+    println!("[kernel] memory usage:");
+    match HEAP.try_lock() {
+        Some(heap) => println!(
+            "  heap: {} KiB of {} KiB in use ({} KiB requested), {} KiB grown",
+            heap.stats_alloc_actual() / 1024,
+            heap.stats_total_bytes() / 1024,
+            heap.stats_alloc_user() / 1024,
+            HEAP_GROWN.load(Ordering::Relaxed) / 1024
+        ),
+        None => println!("  heap: busy"),
+    }
+    match frame_stats() {
+        Some((free, total)) => println!("  frames: {} of {} free", free, total),
+        None => println!("  frames: busy"),
+    }
+    match swap_stats() {
+        Some((used, total)) => println!("  swap: {} of {} pages used", used, total),
+        None => println!("  swap: busy"),
+    }
+    print_slab_stats();
+    match easy_fs::cached_blocks() {
+        Some(blocks) => println!(
+            "  block cache: {} blocks ({} KiB)",
+            blocks,
+            blocks * easy_fs::BLOCK_SZ / 1024
+        ),
+        None => println!("  block cache: busy"),
+    }
+    match task_counts() {
+        Some((processes, ready)) => {
+            println!("  tasks: {} processes, {} ready tasks", processes, ready)
+        }
+        None => println!("  tasks: busy"),
+    }
+}
+
 static mut HEAP_SPACE: [u8; KERNEL_HEAP_SIZE] = [0; KERNEL_HEAP_SIZE];
 
 pub fn init_heap() {
     unsafe {
-        HEAP_ALLOCATOR
-            .lock()
+        HEAP.lock()
             .init(HEAP_SPACE.as_ptr() as usize, KERNEL_HEAP_SIZE);
     }
+    HEAP_READY.store(true, Ordering::Release);
+    info!(
+        "early heap: {} of {} bytes used",
+        EARLY_USED.load(Ordering::Relaxed),
+        EARLY_HEAP_SIZE
+    );
 }
 
 #[allow(unused)]
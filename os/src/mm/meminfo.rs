@@ -0,0 +1,49 @@
+//! Memory usage figures, for `sys_meminfo` and `sys_vmstat`.
+
+use super::frame_allocator::{cached_frames, frame_stats};
+use super::heap_allocator::heap_stats;
+use super::swap::swap_stats;
+use crate::config::PAGE_SIZE;
+
+/// Usage of the whole memory, in bytes.
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+pub struct MemInfo {
+    pub total: usize,
+    pub free: usize,
+    /// the kernel heap, slabs included, grown from frames as needed
+    pub heap_total: usize,
+    pub heap_used: usize,
+    /// pages of files and blocks of the file system cached in memory
+    pub page_cache: usize,
+    pub swap_total: usize,
+    pub swap_used: usize,
+}
+
+/// Usage of an address space, in bytes.
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+pub struct VmStat {
+    /// size of the user mappings
+    pub vsz: usize,
+    /// user pages in memory, shared ones included
+    pub rss: usize,
+    /// user pages in swap
+    pub swap: usize,
+}
+
+pub fn mem_info() -> MemInfo {
+    let (free_frames, total_frames) = frame_stats().unwrap_or_default();
+    let (heap_used, heap_total) = heap_stats().unwrap_or_default();
+    let (swap_used, swap_pages) = swap_stats().unwrap_or_default();
+    MemInfo {
+        total: total_frames * PAGE_SIZE,
+        free: free_frames * PAGE_SIZE,
+        heap_total,
+        heap_used,
+        page_cache: cached_frames().unwrap_or_default() * PAGE_SIZE
+            + easy_fs::cached_blocks().unwrap_or_default() * easy_fs::BLOCK_SZ,
+        swap_total: swap_pages * PAGE_SIZE,
+        swap_used: swap_used * PAGE_SIZE,
+    }
+}
@@ -1,15 +1,28 @@
-use super::{frame_alloc, FrameTracker};
-use super::{PTEFlags, PageTable, PageTableEntry};
+use super::frame_allocator::frame_alloc_user;
+use super::tlb::asid_alloc;
+use super::{frame_alloc, frame_alloc_contiguous, FrameTracker};
+use super::{phys_to_virt, VmStat};
+use super::{PTEFlags, PageTable, PageTableEntry, HUGE_PAGES};
 use super::{PhysAddr, PhysPageNum, VirtAddr, VirtPageNum};
-use super::{StepByOne, VPNRange};
-use crate::config::{MEMORY_END, MMIO, PAGE_SIZE, TRAMPOLINE};
+use super::{SharedMemory, StepByOne, SwapEntry, VPNRange};
+use crate::config::{
+    ASLR_PAGES, ELF_ET_DYN_BASE, ELF_INTERP_BASE, MEMORY_END, MEMORY_START, MMAP_BASE, MMAP_END,
+    MMIO, PAGE_SIZE, SIGRETURN_TRAMPOLINE, TRAMPOLINE, USER_STACK_BASE, USER_STACK_GUARD_GAP,
+};
+use crate::fs::Inode;
+use crate::kaslr::{kernel_offset, link_address};
+use crate::random::random;
 use crate::sync::UPIntrFreeCell;
-use alloc::collections::BTreeMap;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
 use alloc::sync::Arc;
+use alloc::vec;
 use alloc::vec::Vec;
 use core::arch::asm;
 use lazy_static::*;
 use riscv::register::satp;
+use xmas_elf::program::{self, ProgramHeader};
+use xmas_elf::{header, ElfFile};
 
 extern "C" {
     fn stext();
@@ -20,34 +33,124 @@ extern "C" {
     fn edata();
     fn sbss_with_stack();
     fn ebss();
-    fn ekernel();
     fn strampoline();
+    fn ssigreturn();
 }
 
 lazy_static! {
     pub static ref KERNEL_SPACE: Arc<UPIntrFreeCell<MemorySet>> =
         Arc::new(unsafe { UPIntrFreeCell::new(MemorySet::new_kernel()) });
+    /// Mapped copy-on-write for pages read before they are ever written.
+    static ref ZERO_PAGE: FrameTracker = frame_alloc().unwrap();
 }
 
 pub fn kernel_token() -> usize {
     KERNEL_SPACE.exclusive_access().token()
 }
 
+/// The physical range `[start_pa, end_pa)` at its place in the direct map.
+fn direct_map_area(start_pa: usize, end_pa: usize) -> MapArea {
+    let start_va = VirtAddr::from(phys_to_virt(start_pa));
+    MapArea::new(
+        start_va,
+        VirtAddr::from(phys_to_virt(end_pa)),
+        MapType::Linear(PhysAddr::from(start_pa).floor().0 as isize - start_va.floor().0 as isize),
+        MapPermission::R | MapPermission::W,
+    )
+}
+
+/// Whether `[start, end)` overlaps the lower half range the kernel image
+/// is mapped at without KASLR. User mappings keep clear of it, so that the
+/// kernel using a user address directly faults rather than reaching its own
+/// memory.
+pub fn overlaps_kernel(start: VirtPageNum, end: VirtPageNum) -> bool {
+    start < VirtAddr::from(MEMORY_END).ceil() && VirtAddr::from(MEMORY_START).floor() < end
+}
+
 pub struct MemorySet {
     page_table: PageTable,
-    areas: Vec<MapArea>,
+    /// indexed by the first page of each area
+    areas: BTreeMap<VirtPageNum, MapArea>,
+    /// The heap is `[brk_start, brk)`, starting at the end of the image.
+    brk_start: usize,
+    brk: usize,
+    /// where the next `reclaim` goes on scanning
+    reclaim_hand: VirtPageNum,
+}
+
+/// The area of `areas` containing `vpn`.
+fn find_area(areas: &mut BTreeMap<VirtPageNum, MapArea>, vpn: VirtPageNum) -> Option<&mut MapArea> {
+    areas
+        .range_mut(..=vpn)
+        .next_back()
+        .map(|(_, area)| area)
+        .filter(|area| vpn < area.vpn_range.get_end())
 }
 
 impl MemorySet {
     pub fn new_bare() -> Self {
+        Self::with_asid(asid_alloc())
+    }
+    fn with_asid(asid: usize) -> Self {
         Self {
-            page_table: PageTable::new(),
-            areas: Vec::new(),
+            page_table: PageTable::new(asid),
+            areas: BTreeMap::new(),
+            brk_start: 0,
+            brk: 0,
+            reclaim_hand: VirtPageNum(0),
         }
     }
     pub fn token(&self) -> usize {
         self.page_table.token()
     }
+    /// Size in bytes of the user accessible part of this address space.
+    pub fn user_size(&self) -> usize {
+        self.areas
+            .values()
+            .filter(|area| area.map_perm.contains(MapPermission::U))
+            .map(|area| area.vpn_range.get_end().0 - area.vpn_range.get_start().0)
+            .sum::<usize>()
+            * PAGE_SIZE
+    }
+    /// Sizes of the user mappings, of what of them is in memory and of what
+    /// is in swap.
+    pub fn vm_stat(&self) -> VmStat {
+        let user_areas = || {
+            self.areas
+                .values()
+                .filter(|area| area.map_perm.contains(MapPermission::U))
+        };
+        VmStat {
+            vsz: self.user_size(),
+            rss: user_areas()
+                .map(|area| area.data_frames.len())
+                .sum::<usize>()
+                * PAGE_SIZE,
+            swap: user_areas().map(|area| area.swapped.len()).sum::<usize>() * PAGE_SIZE,
+        }
+    }
+    /// The user areas of frames, with the pages of each which have been
+    /// allocated, in memory or in swap, for a core dump to hold.
+    pub fn core_areas(&self) -> Vec<CoreArea> {
+        self.areas
+            .values()
+            .filter(|area| {
+                area.map_perm.contains(MapPermission::U | MapPermission::R)
+                    && area.map_type == MapType::Framed
+            })
+            .map(|area| CoreArea {
+                start: area.vpn_range.get_start(),
+                end: area.vpn_range.get_end(),
+                perm: area.map_perm,
+                pages: area
+                    .data_frames
+                    .keys()
+                    .chain(area.swapped.keys())
+                    .copied()
+                    .collect(),
+            })
+            .collect()
+    }
     /// Assume that no conflicts.
     pub fn insert_framed_area(
         &mut self,
@@ -60,17 +163,51 @@ impl MemorySet {
             None,
         );
     }
+    /// Map `frames` one after the other from `start`, for a kernel buffer
+    /// whose frames need not be contiguous.
+    pub fn insert_frames(
+        &mut self,
+        start: VirtPageNum,
+        frames: Vec<FrameTracker>,
+        permission: MapPermission,
+    ) {
+        let end = VirtPageNum(start.0 + frames.len());
+        let mut map_area = MapArea::new(start.into(), end.into(), MapType::Framed, permission);
+        let flags = map_area.pte_flags();
+        for (vpn, frame) in map_area.vpn_range.into_iter().zip(frames) {
+            self.page_table.map(vpn, frame.ppn, flags);
+            map_area.data_frames.insert(vpn, frame);
+        }
+        self.areas.insert(start, map_area);
+    }
+    /// Map a user stack whose pages below `start_va` are added on faults.
+    pub fn insert_stack_area(&mut self, start_va: VirtAddr, end_va: VirtAddr) {
+        let mut map_area = MapArea::new(
+            start_va,
+            end_va,
+            MapType::Framed,
+            MapPermission::R | MapPermission::W | MapPermission::U,
+        );
+        map_area.map(&mut self.page_table);
+        // pages it grows into are allocated on first touch
+        map_area.backing = Some(MapBacking::Anonymous);
+        map_area.grows_down = true;
+        self.areas.insert(map_area.vpn_range.get_start(), map_area);
+    }
     pub fn remove_area_with_start_vpn(&mut self, start_vpn: VirtPageNum) {
-        if let Some((idx, area)) = self
-            .areas
-            .iter_mut()
-            .enumerate()
-            .find(|(_, area)| area.vpn_range.get_start() == start_vpn)
-        {
+        if let Some(mut area) = self.areas.remove(&start_vpn) {
             area.unmap(&mut self.page_table);
-            self.areas.remove(idx);
+            self.page_table.flush();
         }
     }
+    /// Remove the area containing `vpn`, e.g. a stack which may have grown.
+    pub fn remove_area_containing(&mut self, vpn: VirtPageNum) {
+        let start_vpn = match find_area(&mut self.areas, vpn) {
+            Some(area) => area.vpn_range.get_start(),
+            None => return,
+        };
+        self.remove_area_with_start_vpn(start_vpn);
+    }
     /// Add a new MapArea into this MemorySet.
     /// Assuming that there are no conflicts in the virtual address
     /// space.
@@ -79,19 +216,61 @@ impl MemorySet {
         if let Some(data) = data {
             map_area.copy_data(&mut self.page_table, data);
         }
-        self.areas.push(map_area);
+        self.areas.insert(map_area.vpn_range.get_start(), map_area);
+    }
+    /// Areas overlapping `[start, end)`, from the highest one.
+    fn overlapping_areas(
+        &self,
+        start: VirtPageNum,
+        end: VirtPageNum,
+    ) -> impl Iterator<Item = &MapArea> {
+        self.areas
+            .range(..end)
+            .rev()
+            .map(|(_, area)| area)
+            .take_while(move |area| area.vpn_range.get_end() > start)
+    }
+    /// Whether `[start, end)` is free for a user mapping.
+    pub fn is_free(&self, start: VirtPageNum, end: VirtPageNum) -> bool {
+        !overlaps_kernel(start, end) && self.overlapping_areas(start, end).next().is_none()
+    }
+    /// Cut the area containing `vpn` in two, so that an area starts at `vpn`.
+    fn split_at(&mut self, vpn: VirtPageNum) {
+        if let Some(area) = find_area(&mut self.areas, vpn) {
+            if area.vpn_range.get_start() != vpn {
+                let tail = area.split_off(vpn);
+                self.areas.insert(vpn, tail);
+            }
+        }
     }
     /// Mention that trampoline is not collected by areas.
     fn map_trampoline(&mut self) {
         self.page_table.map(
             VirtAddr::from(TRAMPOLINE).into(),
-            PhysAddr::from(strampoline as usize).into(),
+            PhysAddr::from(link_address(strampoline as usize)).into(),
             PTEFlags::R | PTEFlags::X,
         );
     }
+    /// The sigreturn trampoline of user spaces, not in areas either.
+    fn map_sigreturn(&mut self) {
+        self.page_table.map(
+            VirtAddr::from(SIGRETURN_TRAMPOLINE).into(),
+            PhysAddr::from(link_address(ssigreturn as usize)).into(),
+            PTEFlags::R | PTEFlags::X | PTEFlags::U,
+        );
+    }
     /// Without kernel stacks.
     pub fn new_kernel() -> Self {
-        let mut memory_set = Self::new_bare();
+        let mut memory_set = Self::with_asid(0);
+        // the image is at its physical address, unless KASLR moved it
+        let image = match kernel_offset() {
+            0 => MapType::Identical,
+            _ => {
+                let pa = PhysAddr::from(link_address(stext as usize));
+                let va = VirtAddr::from(stext as usize);
+                MapType::Linear(pa.floor().0 as isize - va.floor().0 as isize)
+            }
+        };
         // map trampoline
         memory_set.map_trampoline();
         // map kernel sections
@@ -107,7 +286,7 @@ impl MemorySet {
             MapArea::new(
                 (stext as usize).into(),
                 (etext as usize).into(),
-                MapType::Identical,
+                image,
                 MapPermission::R | MapPermission::X,
             ),
             None,
@@ -117,7 +296,7 @@ impl MemorySet {
             MapArea::new(
                 (srodata as usize).into(),
                 (erodata as usize).into(),
-                MapType::Identical,
+                image,
                 MapPermission::R,
             ),
             None,
@@ -127,7 +306,7 @@ impl MemorySet {
             MapArea::new(
                 (sdata as usize).into(),
                 (edata as usize).into(),
-                MapType::Identical,
+                image,
                 MapPermission::R | MapPermission::W,
             ),
             None,
@@ -137,89 +316,238 @@ impl MemorySet {
             MapArea::new(
                 (sbss_with_stack as usize).into(),
                 (ebss as usize).into(),
-                MapType::Identical,
+                image,
                 MapPermission::R | MapPermission::W,
             ),
             None,
         );
         // println!("mapping physical memory");
-        memory_set.push(
-            MapArea::new(
-                (ekernel as usize).into(),
-                MEMORY_END.into(),
-                MapType::Identical,
-                MapPermission::R | MapPermission::W,
-            ),
-            None,
-        );
+        memory_set.push(direct_map_area(MEMORY_START, MEMORY_END), None);
+        // devices are reached through the direct map as well, leaving the
+        // lower half to the user but for the kernel image
         //println!("mapping memory-mapped registers");
         for pair in MMIO {
-            memory_set.push(
-                MapArea::new(
-                    (*pair).0.into(),
-                    ((*pair).0 + (*pair).1).into(),
-                    MapType::Identical,
-                    MapPermission::R | MapPermission::W,
-                ),
-                None,
-            );
+            memory_set.push(direct_map_area((*pair).0, (*pair).0 + (*pair).1), None);
         }
         memory_set
     }
-    /// Include sections in elf and trampoline,
-    /// also returns user_sp_base and entry point.
-    pub fn from_elf(elf_data: &[u8]) -> (Self, usize, usize) {
+    /// Include sections in elf and trampoline, with ET_DYN images loaded at
+    /// a random base. Segments are only read from `file` when first touched.
+    ///
+    /// Return None if the image is malformed, or if it is a static PIE with
+    /// relocations other than R_RISCV_RELATIVE.
+    pub fn from_elf(file: &Arc<dyn Inode>) -> Option<(Self, ElfInfo)> {
         let mut memory_set = Self::new_bare();
         // map trampoline
         memory_set.map_trampoline();
+        memory_set.map_sigreturn();
+        let elf_head = read_elf_head(file.as_ref())?;
+        let elf = ElfFile::new(&elf_head).ok()?;
+        let bias = elf_load_bias(&elf, ELF_ET_DYN_BASE)?;
         // map program headers of elf, with U flag
-        let elf = xmas_elf::ElfFile::new(elf_data).unwrap();
-        let elf_header = elf.header;
-        let magic = elf_header.pt1.magic;
-        assert_eq!(magic, [0x7f, 0x45, 0x4c, 0x46], "invalid elf!");
-        let ph_count = elf_header.pt2.ph_count();
-        let mut max_end_vpn = VirtPageNum(0);
-        for i in 0..ph_count {
-            let ph = elf.program_header(i).unwrap();
-            if ph.get_type().unwrap() == xmas_elf::program::Type::Load {
-                let start_va: VirtAddr = (ph.virtual_addr() as usize).into();
-                let end_va: VirtAddr = ((ph.virtual_addr() + ph.mem_size()) as usize).into();
-                let mut map_perm = MapPermission::U;
-                let ph_flags = ph.flags();
-                if ph_flags.is_read() {
-                    map_perm |= MapPermission::R;
-                }
-                if ph_flags.is_write() {
-                    map_perm |= MapPermission::W;
-                }
-                if ph_flags.is_execute() {
-                    map_perm |= MapPermission::X;
-                }
-                let map_area = MapArea::new(start_va, end_va, MapType::Framed, map_perm);
-                max_end_vpn = map_area.vpn_range.get_end();
-                memory_set.push(
-                    map_area,
-                    Some(&elf.input[ph.offset() as usize..(ph.offset() + ph.file_size()) as usize]),
-                );
+        let max_end_vpn = memory_set.map_elf(&elf, file, bias)?;
+        let interp = match elf
+            .program_iter()
+            .find(|ph| ph.get_type() == Ok(program::Type::Interp))
+        {
+            Some(ph) => {
+                let data = segment_data(file.as_ref(), &ph)?;
+                let path = data.split(|c| *c == 0).next()?;
+                Some(String::from(core::str::from_utf8(path).ok()?))
             }
+            None => None,
+        };
+        // with an interpreter, relocating is its job
+        if interp.is_none() {
+            memory_set.relocate(&elf, file.as_ref(), bias)?;
         }
+        let ph_offset = elf.header.pt2.ph_offset() as usize;
+        let phdr = elf
+            .program_iter()
+            .find_map(|ph| match ph.get_type() {
+                Ok(program::Type::Phdr) => Some(ph.virtual_addr() as usize),
+                Ok(program::Type::Load)
+                    if (ph.offset() as usize..(ph.offset() + ph.file_size()) as usize)
+                        .contains(&ph_offset) =>
+                {
+                    Some(ph.virtual_addr() as usize + ph_offset - ph.offset() as usize)
+                }
+                _ => None,
+            })
+            .map_or(0, |va| va + bias);
+        let entry_point = elf.header.pt2.entry_point() as usize + bias;
         let max_end_va: VirtAddr = max_end_vpn.into();
-        let mut user_stack_base: usize = max_end_va.into();
-        user_stack_base += PAGE_SIZE;
-        (
-            memory_set,
-            user_stack_base,
-            elf.header.pt2.entry_point() as usize,
-        )
+        memory_set.brk_start = max_end_va.into();
+        memory_set.brk = memory_set.brk_start;
+        let elf_info = ElfInfo {
+            entry_point,
+            program_entry: entry_point,
+            phdr,
+            phent: elf.header.pt2.ph_entry_size() as usize,
+            phnum: elf.header.pt2.ph_count() as usize,
+            interp_base: 0,
+            interp,
+            ustack_base: USER_STACK_BASE.max(usize::from(max_end_va) + PAGE_SIZE),
+        };
+        Some((memory_set, elf_info))
+    }
+    /// Map the interpreter asked for by the program in `elf_info`, which then
+    /// starts at the interpreter's entry.
+    pub fn load_interp(&mut self, file: &Arc<dyn Inode>, elf_info: &mut ElfInfo) -> Option<()> {
+        let elf_head = read_elf_head(file.as_ref())?;
+        let elf = ElfFile::new(&elf_head).ok()?;
+        let bias = elf_load_bias(&elf, ELF_INTERP_BASE)?;
+        let max_end_vpn = self.map_elf(&elf, file, bias)?;
+        let max_end_va: VirtAddr = max_end_vpn.into();
+        elf_info.interp_base = bias;
+        elf_info.entry_point = elf.header.pt2.entry_point() as usize + bias;
+        elf_info.ustack_base = elf_info
+            .ustack_base
+            .max(usize::from(max_end_va) + PAGE_SIZE);
+        Some(())
+    }
+    /// Map the PT_LOAD segments of `elf` moved up by `bias` to be loaded
+    /// from `file` on demand, and return the end of the highest one.
+    fn map_elf(
+        &mut self,
+        elf: &ElfFile,
+        file: &Arc<dyn Inode>,
+        bias: usize,
+    ) -> Option<VirtPageNum> {
+        let mut max_end_vpn = VirtPageNum(0);
+        for ph in elf.program_iter() {
+            if ph.get_type().ok()? != program::Type::Load {
+                continue;
+            }
+            if ph.file_size() > ph.mem_size() {
+                return None;
+            }
+            let start_va: VirtAddr = (ph.virtual_addr() as usize + bias).into();
+            let end_va: VirtAddr = ((ph.virtual_addr() + ph.mem_size()) as usize + bias).into();
+            let mut map_perm = MapPermission::U;
+            let ph_flags = ph.flags();
+            if ph_flags.is_read() {
+                map_perm |= MapPermission::R;
+            }
+            if ph_flags.is_write() {
+                map_perm |= MapPermission::W;
+            }
+            if ph_flags.is_execute() {
+                map_perm |= MapPermission::X;
+            }
+            let mut map_area = MapArea::new(start_va, end_va, MapType::Framed, map_perm);
+            map_area.backing = Some(MapBacking::File {
+                file: Arc::clone(file),
+                start_va: start_va.into(),
+                offset: ph.offset() as usize,
+                len: ph.file_size() as usize,
+            });
+            let (start_vpn, end_vpn) =
+                (map_area.vpn_range.get_start(), map_area.vpn_range.get_end());
+            if !self.is_free(start_vpn, end_vpn) {
+                return None;
+            }
+            max_end_vpn = max_end_vpn.max(end_vpn);
+            self.push(map_area, None);
+        }
+        Some(max_end_vpn)
     }
-    pub fn from_existed_user(user_space: &MemorySet) -> MemorySet {
+    /// Apply the relocations of a static PIE, as nobody else will.
+    fn relocate(&mut self, elf: &ElfFile, file: &dyn Inode, bias: usize) -> Option<()> {
+        let dynamic = match elf
+            .program_iter()
+            .find(|ph| ph.get_type() == Ok(program::Type::Dynamic))
+        {
+            Some(ph) => segment_data(file, &ph)?,
+            None => return Some(()),
+        };
+        let (mut rela, mut rela_size, mut rela_ent) = (0, 0, 0);
+        for entry in dynamic.chunks_exact(16) {
+            let val = u64::from_le_bytes(entry[8..].try_into().unwrap()) as usize;
+            match u64::from_le_bytes(entry[..8].try_into().unwrap()) {
+                DT_NULL => break,
+                DT_RELA => rela = val,
+                DT_RELASZ => rela_size = val,
+                DT_RELAENT => rela_ent = val,
+                _ => {}
+            }
+        }
+        if rela_size == 0 {
+            return Some(());
+        }
+        if rela_ent == 0 {
+            return None;
+        }
+        for i in 0..rela_size / rela_ent {
+            // Elf64_Rela: r_offset, r_info, r_addend
+            let entry = bias + rela + i * rela_ent;
+            let offset = *self.user_word(entry)?;
+            let info = *self.user_word(entry + 8)?;
+            let addend = *self.user_word(entry + 16)?;
+            match info as u32 {
+                R_RISCV_NONE => {}
+                R_RISCV_RELATIVE => *self.user_word(bias + offset)? = bias.wrapping_add(addend),
+                _ => return None,
+            }
+        }
+        Some(())
+    }
+    /// The word at user address `va` of this address space, loaded if it
+    /// is not yet.
+    fn user_word(&mut self, va: usize) -> Option<&'static mut usize> {
+        let va = VirtAddr::from(va);
+        if !self
+            .translate(va.floor())
+            .map_or(false, |pte| pte.is_valid())
+        {
+            if let PageFault::Load(page) = self.handle_page_fault(va, false) {
+                let frame = page.load()?;
+                self.map_loaded(&page, frame);
+            }
+        }
+        // the word is written, so the page must not be shared, say the zero
+        // page
+        if self.translate(va.floor()).map_or(false, |pte| pte.is_cow()) {
+            self.handle_page_fault(va, true);
+        }
+        self.page_table.translate_va(va).map(|pa| pa.get_mut())
+    }
+    /// User pages are shared with `user_space` and copied on the first write
+    /// of either side, except for shared mappings, while trap contexts are
+    /// written by the kernel through their physical addresses and so copied
+    /// at once.
+    pub fn from_existed_user(user_space: &mut MemorySet) -> MemorySet {
         let mut memory_set = Self::new_bare();
         // map trampoline
         memory_set.map_trampoline();
-        // copy data sections/trap_context/user_stack
-        for area in user_space.areas.iter() {
-            let new_area = MapArea::from_another(area);
+        memory_set.map_sigreturn();
+        memory_set.brk_start = user_space.brk_start;
+        memory_set.brk = user_space.brk;
+        for area in user_space.areas.values() {
+            let mut new_area = MapArea::from_another(area);
+            if area.map_type == MapType::Framed && area.map_perm.contains(MapPermission::U) {
+                let mut flags = area.pte_flags();
+                if !area.is_shared() && flags.contains(PTEFlags::W) {
+                    flags = (flags - PTEFlags::W) | PTEFlags::COW;
+                }
+                for (vpn, frame) in area.data_frames.iter() {
+                    // PROT_NONE pages stay unmapped
+                    if area.is_accessible() {
+                        user_space.page_table.remap(*vpn, frame.ppn, flags);
+                        memory_set.page_table.map(*vpn, frame.ppn, flags);
+                    }
+                    new_area.data_frames.insert(*vpn, frame.clone());
+                }
+                new_area.swapped = area.swapped.clone();
+                memory_set
+                    .areas
+                    .insert(new_area.vpn_range.get_start(), new_area);
+                continue;
+            }
             memory_set.push(new_area, None);
+            if area.map_type != MapType::Framed {
+                continue;
+            }
             // copy data from another space
             for vpn in area.vpn_range {
                 let src_ppn = user_space.translate(vpn).unwrap().ppn();
@@ -229,29 +557,645 @@ impl MemorySet {
                     .copy_from_slice(src_ppn.get_bytes_array());
             }
         }
+        // other threads of the parent must not write on the shared pages
+        user_space.page_table.flush();
         memory_set
     }
-    pub fn activate(&self) {
-        let satp = self.page_table.token();
+    /// Resolve a fault on `va`, except for loading the page of a lazy area,
+    /// which may sleep and is left to the caller.
+    pub fn handle_page_fault(&mut self, va: VirtAddr, write: bool) -> PageFault {
+        let vpn = va.floor();
+        let area = match find_area(&mut self.areas, vpn) {
+            Some(area) => area,
+            None => return PageFault::Invalid,
+        };
+        if !area.map_perm.contains(MapPermission::U) || !area.is_accessible() {
+            return PageFault::Invalid;
+        }
+        if write && !area.map_perm.contains(MapPermission::W) {
+            return PageFault::Invalid;
+        }
+        if area.backing.is_some() && !area.data_frames.contains_key(&vpn) {
+            // pages are only given frames of their own on the first write
+            if !write && area.is_zero_fill(vpn) {
+                area.map_zero_page(&mut self.page_table, vpn);
+                return PageFault::Resolved;
+            }
+            // large anonymous mappings get megapages
+            if matches!(area.backing, Some(MapBacking::Anonymous))
+                && !area.grows_down
+                && area.map_huge_page(&mut self.page_table, vpn)
+            {
+                return PageFault::Resolved;
+            }
+            return PageFault::Load(LazyPage {
+                vpn,
+                index: vpn.0 - area.vpn_range.get_start().0,
+                backing: area.backing.clone().unwrap(),
+                swap: area.swapped.get(&vpn).cloned(),
+            });
+        }
+        if write && area.copy_on_write(&mut self.page_table, vpn) {
+            // other threads must not go on reading the old frame
+            self.page_table.flush();
+            PageFault::Resolved
+        } else {
+            PageFault::Invalid
+        }
+    }
+    /// Extend the stack right above `va` down to it, as long as the stack
+    /// stays within `stack_limit` bytes, the user mappings within `as_limit`
+    /// bytes and the guard gap below it free. The new pages are left to be
+    /// allocated by `handle_page_fault`.
+    pub fn grow_stack(&mut self, va: VirtAddr, stack_limit: usize, as_limit: usize) -> bool {
+        let vpn = va.floor();
+        if find_area(&mut self.areas, vpn).is_some() {
+            return false;
+        }
+        let (start, end) = match self.areas.range(vpn..).next() {
+            Some((start, area)) if area.grows_down => (*start, area.vpn_range.get_end()),
+            _ => return false,
+        };
+        if (end.0 - vpn.0) * PAGE_SIZE > stack_limit
+            || self.user_size() + (start.0 - vpn.0) * PAGE_SIZE > as_limit
+        {
+            return false;
+        }
+        let guard_start = VirtPageNum(vpn.0.saturating_sub(USER_STACK_GUARD_GAP / PAGE_SIZE));
+        if !self.is_free(guard_start, start) {
+            return false;
+        }
+        let mut area = self.areas.remove(&start).unwrap();
+        area.vpn_range = VPNRange::new(vpn, end);
+        self.areas.insert(vpn, area);
+        true
+    }
+    /// Map a page loaded after `handle_page_fault`, unless the area has
+    /// changed meanwhile.
+    pub fn map_loaded(&mut self, page: &LazyPage, frame: FrameTracker) -> bool {
+        let vpn = page.vpn;
+        match find_area(&mut self.areas, vpn) {
+            Some(area)
+                if area.backing.is_some()
+                    && area.is_accessible()
+                    && !area.data_frames.contains_key(&vpn)
+                    && area.swapped.get(&vpn).map(Arc::as_ptr)
+                        == page.swap.as_ref().map(Arc::as_ptr) =>
+            {
+                if area.is_shared() && area.map_perm.contains(MapPermission::W) {
+                    frame.set_mapped_write(true);
+                }
+                self.page_table.map(vpn, frame.ppn, area.pte_flags());
+                area.data_frames.insert(vpn, frame);
+                area.swapped.remove(&vpn);
+                true
+            }
+            _ => false,
+        }
+    }
+    /// A free range of `pages` pages for mmap, at `hint` if possible. Ranges
+    /// of a megapage or more are aligned to megapages.
+    pub fn find_free_area(&self, hint: VirtPageNum, pages: usize) -> Option<VirtPageNum> {
+        let mmap_end = VirtAddr::from(MMAP_END).floor();
+        if hint.0 != 0
+            && hint.0 + pages <= mmap_end.0
+            && self.is_free(hint, (hint.0 + pages).into())
+        {
+            return Some(hint);
+        }
+        // first fit
+        let align = if pages >= HUGE_PAGES { HUGE_PAGES } else { 1 };
+        let mut start = VirtAddr::from(MMAP_BASE).floor();
+        for area in self.areas.values() {
+            if area.vpn_range.get_end() <= start {
+                continue;
+            }
+            if start.0 + pages <= area.vpn_range.get_start().0 {
+                break;
+            }
+            start = VirtPageNum((area.vpn_range.get_end().0 + align - 1) / align * align);
+        }
+        if start.0 + pages <= mmap_end.0 {
+            Some(start)
+        } else {
+            None
+        }
+    }
+    /// Map `[start, end)` to be filled from `backing` on demand. Assume that
+    /// no conflicts.
+    pub fn insert_lazy_area(
+        &mut self,
+        start: VirtPageNum,
+        end: VirtPageNum,
+        permission: MapPermission,
+        backing: MapBacking,
+    ) {
+        let mut map_area = MapArea::new(start.into(), end.into(), MapType::Framed, permission);
+        map_area.backing = Some(backing);
+        self.push(map_area, None);
+    }
+    pub fn brk(&self) -> usize {
+        self.brk
+    }
+    /// Move the end of the heap to `new_brk`, growing it over free pages
+    /// only, whose frames are allocated on first touch.
+    ///
+    /// Pages given back are returned as areas to be dropped by the caller,
+    /// like for `munmap`.
+    pub fn set_brk(&mut self, new_brk: usize) -> Option<Vec<MapArea>> {
+        if new_brk < self.brk_start || new_brk > MMAP_END {
+            return None;
+        }
+        let heap_start = VirtAddr::from(self.brk_start).floor();
+        let old_end = VirtAddr::from(self.brk).ceil();
+        let new_end = VirtAddr::from(new_brk).ceil();
+        let mut removed = Vec::new();
+        if new_end > old_end {
+            if !self.is_free(old_end, new_end) {
+                return None;
+            }
+            let heap_perm = MapPermission::R | MapPermission::W | MapPermission::U;
+            let last_area = if old_end > heap_start {
+                find_area(&mut self.areas, VirtPageNum(old_end.0 - 1))
+            } else {
+                None
+            };
+            match last_area {
+                // extend the heap area rather than adding one for each sbrk
+                Some(area)
+                    if area.vpn_range.get_start() >= heap_start
+                        && area.map_perm == heap_perm
+                        && matches!(area.backing, Some(MapBacking::Anonymous)) =>
+                {
+                    area.vpn_range = VPNRange::new(area.vpn_range.get_start(), new_end);
+                }
+                _ => self.insert_lazy_area(old_end, new_end, heap_perm, MapBacking::Anonymous),
+            }
+        } else if new_end < old_end {
+            removed = self.munmap(new_end, old_end)?;
+        }
+        self.brk = new_brk;
+        Some(removed)
+    }
+    /// Whether `[start, end)` is covered by user areas without holes.
+    pub fn is_user_range(&self, start: VirtPageNum, end: VirtPageNum) -> bool {
+        let mut next = end;
+        for area in self.overlapping_areas(start, end) {
+            if !area.map_perm.contains(MapPermission::U) || area.vpn_range.get_end() < next {
+                return false;
+            }
+            next = area.vpn_range.get_start();
+        }
+        next <= start
+    }
+    /// Unmap whatever is in `[start, end)`, where only user areas may be.
+    ///
+    /// The removed areas are returned to be dropped by the caller without
+    /// anything locked, as that may write a shared file mapping back.
+    pub fn munmap(&mut self, start: VirtPageNum, end: VirtPageNum) -> Option<Vec<MapArea>> {
+        if self
+            .overlapping_areas(start, end)
+            .any(|area| !area.map_perm.contains(MapPermission::U))
+        {
+            return None;
+        }
+        self.split_at(start);
+        self.split_at(end);
+        let starts: Vec<VirtPageNum> = self.areas.range(start..end).map(|(vpn, _)| *vpn).collect();
+        let mut removed = Vec::new();
+        for vpn in starts {
+            let mut area = self.areas.remove(&vpn).unwrap();
+            area.unmap(&mut self.page_table);
+            removed.push(area);
+        }
+        self.page_table.flush();
+        Some(removed)
+    }
+    /// End of the mapping of a shared object which starts at `start` with
+    /// the first page of the object, across the areas it was split into.
+    pub fn shared_mapping_end(&self, start: VirtPageNum) -> Option<VirtPageNum> {
+        let first = self.areas.get(&start)?;
+        let object = match &first.backing {
+            Some(MapBacking::Shared { object, pgoff: 0 }) => object,
+            _ => return None,
+        };
+        let mut end = first.vpn_range.get_end();
+        for area in self.areas.range(end..).map(|(_, area)| area) {
+            match &area.backing {
+                Some(MapBacking::Shared {
+                    object: other,
+                    pgoff,
+                }) if area.vpn_range.get_start() == end
+                    && Arc::ptr_eq(other, object)
+                    && *pgoff == end.0 - start.0 =>
+                {
+                    end = area.vpn_range.get_end();
+                }
+                _ => break,
+            }
+        }
+        Some(end)
+    }
+    /// The shared object mapped at `vpn` and the index of its page there,
+    /// if a shared mapping covers `vpn`.
+    pub fn shared_page(&self, vpn: VirtPageNum) -> Option<(Arc<SharedMemory>, usize)> {
+        let area = self
+            .areas
+            .range(..=vpn)
+            .next_back()
+            .map(|(_, area)| area)
+            .filter(|area| vpn < area.vpn_range.get_end())?;
+        match &area.backing {
+            Some(MapBacking::Shared { object, pgoff }) => {
+                Some((object.clone(), pgoff + vpn.0 - area.vpn_range.get_start().0))
+            }
+            _ => None,
+        }
+    }
+    /// Change the permission of the user pages in `[start, end)`, which must
+    /// all be mapped.
+    pub fn mprotect(
+        &mut self,
+        start: VirtPageNum,
+        end: VirtPageNum,
+        permission: MapPermission,
+    ) -> bool {
+        if !self.is_user_range(start, end) {
+            return false;
+        }
+        self.split_at(start);
+        self.split_at(end);
+        for area in self.areas.range_mut(start..end).map(|(_, area)| area) {
+            area.protect(&mut self.page_table, permission | MapPermission::U);
+        }
+        self.page_table.flush();
+        true
+    }
+    /// Bytes of the user pages locked in memory.
+    pub fn locked_size(&self) -> usize {
+        self.areas
+            .values()
+            .filter(|area| area.locked)
+            .map(|area| area.vpn_range.get_end().0 - area.vpn_range.get_start().0)
+            .sum::<usize>()
+            * PAGE_SIZE
+    }
+    /// Lock the user pages in `[start, end)`, which must all be mapped, in
+    /// memory, or unlock them if not `locked`, as long as the locked pages
+    /// stay within `limit` bytes.
+    ///
+    /// Locked pages are not reclaimed, but the ones of lazy areas still
+    /// have to be faulted in, which may sleep, so they are returned to the
+    /// caller.
+    pub fn mlock(
+        &mut self,
+        start: VirtPageNum,
+        end: VirtPageNum,
+        locked: bool,
+        limit: usize,
+    ) -> Option<Vec<VirtPageNum>> {
+        if !self.is_user_range(start, end) {
+            return None;
+        }
+        let newly_locked = self
+            .overlapping_areas(start, end)
+            .filter(|area| !area.locked)
+            .map(|area| {
+                area.vpn_range.get_end().min(end).0 - area.vpn_range.get_start().max(start).0
+            })
+            .sum::<usize>();
+        if locked && self.locked_size() + newly_locked * PAGE_SIZE > limit {
+            return None;
+        }
+        self.split_at(start);
+        self.split_at(end);
+        let mut missing = Vec::new();
+        for area in self.areas.range_mut(start..end).map(|(_, area)| area) {
+            area.locked = locked;
+            if locked {
+                missing.extend(area.missing_pages());
+            }
+        }
+        Some(missing)
+    }
+    /// Lock all the user pages in memory, or unlock them if not `locked`,
+    /// see `mlock`.
+    pub fn mlock_all(&mut self, locked: bool, limit: usize) -> Option<Vec<VirtPageNum>> {
+        if locked && self.user_size() > limit {
+            return None;
+        }
+        let mut missing = Vec::new();
+        for area in self.areas.values_mut() {
+            if area.map_perm.contains(MapPermission::U) {
+                area.locked = locked;
+                if locked {
+                    missing.extend(area.missing_pages());
+                }
+            }
+        }
+        Some(missing)
+    }
+    /// Drop the pages in `[start, end)`, which must all be mapped by areas
+    /// loaded on demand and not locked, freeing their frames at once. They
+    /// are faulted in again from their backing on the next access, as
+    /// zeros for anonymous memory.
+    pub fn discard(&mut self, start: VirtPageNum, end: VirtPageNum) -> bool {
+        if !self.is_user_range(start, end)
+            || self
+                .overlapping_areas(start, end)
+                .any(|area| area.backing.is_none() || area.locked)
+        {
+            return false;
+        }
+        let mut frames = Vec::new();
+        for vpn in VPNRange::new(start, end) {
+            let area = find_area(&mut self.areas, vpn).unwrap();
+            frames.extend(area.discard(&mut self.page_table, vpn));
+        }
+        // the frames may only go once no hart can reach them
+        self.page_table.flush();
+        drop(frames);
+        true
+    }
+    /// Pages in `[start, end)`, which must all be mapped, that would be
+    /// read from swap or from a file on the next access, to be faulted in
+    /// ahead by the caller.
+    pub fn will_need(&self, start: VirtPageNum, end: VirtPageNum) -> Option<Vec<VirtPageNum>> {
+        if !self.is_user_range(start, end) {
+            return None;
+        }
+        Some(
+            self.overlapping_areas(start, end)
+                .flat_map(|area| {
+                    area.missing_pages()
+                        .into_iter()
+                        .filter(|vpn| start <= *vpn && *vpn < end && !area.is_zero_fill(*vpn))
+                })
+                .collect(),
+        )
+    }
+    pub fn activate(&mut self) {
+        let satp = self.page_table.enter();
         unsafe {
             satp::write(satp);
             asm!("sfence.vma");
         }
     }
+    /// Bring the TLB of this hart up to date before returning to this user
+    /// space, and return its token.
+    pub fn enter_user(&mut self) -> usize {
+        self.page_table.enter()
+    }
+    /// Flush the changes made through other methods, see `PageTable::flush`.
+    pub fn flush_tlb(&mut self) {
+        self.page_table.flush();
+    }
     pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
         self.page_table.translate(vpn)
     }
-    pub fn recycle_data_pages(&mut self) {
+    /// Take up to `count` cold pages out of this address space, scanning on
+    /// from where the last call stopped like the hand of a clock. Pages
+    /// accessed since the previous pass only lose their accessed bit.
+    ///
+    /// Clean pages of read-only file mappings are dropped to be read again.
+    /// Other private pages go to swap entries, which are returned with the
+    /// number of pages taken, to be written out by the caller without
+    /// anything locked. Pages shared with another address space or a shared
+    /// mapping, pinned or locked in memory are left alone.
+    pub fn reclaim(&mut self, count: usize) -> (usize, Vec<Arc<SwapEntry>>) {
+        let mut vpns: Vec<VirtPageNum> = self
+            .areas
+            .values()
+            .filter(|area| {
+                area.backing.is_some() && area.is_accessible() && !area.is_shared() && !area.locked
+            })
+            .flat_map(|area| area.data_frames.keys().copied())
+            .collect();
+        let hand = vpns.partition_point(|vpn| *vpn < self.reclaim_hand);
+        vpns.rotate_left(hand);
+        self.reclaim_hand = VirtPageNum(0);
+        let mut swapped = Vec::new();
+        let mut reclaimed = 0;
+        for vpn in vpns {
+            if reclaimed == count {
+                self.reclaim_hand = vpn;
+                break;
+            }
+            let pte = match self.page_table.translate(vpn) {
+                Some(pte) if pte.is_valid() => pte,
+                _ => continue,
+            };
+            // megapages are left whole
+            if self.page_table.is_huge(vpn) {
+                continue;
+            }
+            if pte.is_accessed() {
+                self.page_table.clear_accessed(vpn);
+                continue;
+            }
+            let area = find_area(&mut self.areas, vpn).unwrap();
+            let frame = &area.data_frames[&vpn];
+            if frame.ref_count() > 1 || frame.is_pinned() {
+                continue;
+            }
+            let clean = matches!(area.backing, Some(MapBacking::File { .. }))
+                && !area.map_perm.contains(MapPermission::W)
+                && !pte.is_dirty();
+            let frame = area.data_frames.remove(&vpn).unwrap();
+            if !clean {
+                match SwapEntry::new(frame) {
+                    Ok(entry) => {
+                        area.swapped.insert(vpn, Arc::clone(&entry));
+                        swapped.push(entry);
+                    }
+                    // the swap area is full
+                    Err(frame) => {
+                        area.data_frames.insert(vpn, frame);
+                        continue;
+                    }
+                }
+            }
+            self.page_table.unmap(vpn);
+            reclaimed += 1;
+        }
+        // let the hardware see the cleared bits, and the frames go
+        self.page_table.flush();
+        (reclaimed, swapped)
+    }
+    /// The areas are returned to be dropped by the caller without anything
+    /// locked, see `munmap`.
+    pub fn recycle_data_pages(&mut self) -> Vec<MapArea> {
         //*self = Self::new_bare();
-        self.areas.clear();
+        // the frames go with the areas
+        self.page_table.flush_all();
+        core::mem::take(&mut self.areas).into_values().collect()
+    }
+}
+
+impl Drop for MemorySet {
+    fn drop(&mut self) {
+        self.page_table.release_asid();
+    }
+}
+
+/// What exec needs to know about a loaded ELF image besides its mappings.
+pub struct ElfInfo {
+    /// Where the first thread starts, the interpreter's entry if there is one.
+    pub entry_point: usize,
+    /// Entry of the program itself, for AT_ENTRY.
+    pub program_entry: usize,
+    /// User address of the program headers, for AT_PHDR.
+    pub phdr: usize,
+    pub phent: usize,
+    pub phnum: usize,
+    /// Load base of the interpreter, 0 without one, for AT_BASE.
+    pub interp_base: usize,
+    /// Path named by PT_INTERP.
+    pub interp: Option<String>,
+    pub ustack_base: usize,
+}
+
+const DT_NULL: u64 = 0;
+const DT_RELA: u64 = 7;
+const DT_RELASZ: u64 = 8;
+const DT_RELAENT: u64 = 9;
+const R_RISCV_NONE: u32 = 0;
+const R_RISCV_RELATIVE: u32 = 3;
+
+/// 0 for ET_EXEC, which is linked at its final address, and a random base
+/// above `dyn_base` for ET_DYN.
+fn elf_load_bias(elf: &ElfFile, dyn_base: usize) -> Option<usize> {
+    if elf.header.pt1.magic != [0x7f, 0x45, 0x4c, 0x46] {
+        return None;
+    }
+    match elf.header.pt2.type_().as_type() {
+        header::Type::Executable => Some(0),
+        header::Type::SharedObject => Some(dyn_base + random() % ASLR_PAGES * PAGE_SIZE),
+        _ => None,
+    }
+}
+
+/// Read exactly `len` bytes at `offset` of `file`.
+fn read_file(file: &dyn Inode, offset: usize, len: usize) -> Option<Vec<u8>> {
+    let mut buf = vec![0u8; len];
+    if file.read_at(offset, &mut buf) != len {
+        return None;
+    }
+    Some(buf)
+}
+
+/// The beginning of an ELF file, long enough to hold the program headers.
+fn read_elf_head(file: &dyn Inode) -> Option<Vec<u8>> {
+    let mut head = vec![0u8; PAGE_SIZE];
+    let len = file.read_at(0, &mut head);
+    head.truncate(len);
+    let elf = ElfFile::new(&head).ok()?;
+    let ph_end = elf.header.pt2.ph_offset() as usize
+        + elf.header.pt2.ph_count() as usize * elf.header.pt2.ph_entry_size() as usize;
+    if ph_end <= head.len() {
+        return Some(head);
+    }
+    read_file(file, 0, ph_end)
+}
+
+/// File contents of the segment described by `ph`.
+fn segment_data(file: &dyn Inode, ph: &ProgramHeader) -> Option<Vec<u8>> {
+    read_file(file, ph.offset() as usize, ph.file_size() as usize)
+}
+
+/// A user area as `MemorySet::core_areas` tells of it.
+pub struct CoreArea {
+    pub start: VirtPageNum,
+    pub end: VirtPageNum,
+    pub perm: MapPermission,
+    /// those with contents, the others being zeros
+    pub pages: BTreeSet<VirtPageNum>,
+}
+
+/// Where the pages of a lazily allocated area come from.
+#[derive(Clone)]
+pub enum MapBacking {
+    /// zeroed pages
+    Anonymous,
+    /// `len` bytes from `offset` of `file` mapped at `start_va`, and zeros
+    /// around them
+    File {
+        file: Arc<dyn Inode>,
+        start_va: usize,
+        offset: usize,
+        len: usize,
+    },
+    /// pages of `object` from its page `pgoff` on, seen by every mapping
+    Shared {
+        object: Arc<SharedMemory>,
+        pgoff: usize,
+    },
+}
+
+/// How a page fault went, see `MemorySet::handle_page_fault`.
+pub enum PageFault {
+    /// the access can be retried
+    Resolved,
+    /// the page has to be read with `LazyPage::load` and then mapped with
+    /// `MemorySet::map_loaded`
+    Load(LazyPage),
+    /// the access is not allowed
+    Invalid,
+}
+
+pub struct LazyPage {
+    vpn: VirtPageNum,
+    /// index of the page in its area
+    index: usize,
+    backing: MapBacking,
+    /// where the page went if it was swapped out
+    swap: Option<Arc<SwapEntry>>,
+}
+
+impl LazyPage {
+    /// Allocate the page, reading it from swap or from a file if it is
+    /// backed by one.
+    pub fn load(&self) -> Option<FrameTracker> {
+        if let Some(entry) = &self.swap {
+            return entry.read_in();
+        }
+        let (file, start_va, offset, len) = match &self.backing {
+            MapBacking::Anonymous => return frame_alloc_user(),
+            MapBacking::Shared { object, pgoff } => return object.page(pgoff + self.index),
+            MapBacking::File {
+                file,
+                start_va,
+                offset,
+                len,
+            } => (file, *start_va, *offset, *len),
+        };
+        let frame = frame_alloc_user()?;
+        let page_start: usize = VirtAddr::from(self.vpn).into();
+        let start = page_start.max(start_va);
+        let end = (page_start + PAGE_SIZE).min(start_va + len);
+        if start < end {
+            // the rest is left zero if the file is shorter
+            let dst = &mut frame.ppn.get_bytes_array()[start - page_start..end - page_start];
+            file.read_at(offset + start - start_va, dst);
+        }
+        Some(frame)
     }
 }
 
 pub struct MapArea {
     vpn_range: VPNRange,
+    /// shared by the address spaces forked from each other
     data_frames: BTreeMap<VirtPageNum, FrameTracker>,
     map_type: MapType,
     map_perm: MapPermission,
+    /// pages of a lazy area are only allocated on the first access
+    backing: Option<MapBacking>,
+    /// a stack, extended down on faults right below it
+    grows_down: bool,
+    /// pages of a lazy area taken out by `MemorySet::reclaim`
+    swapped: BTreeMap<VirtPageNum, Arc<SwapEntry>>,
+    /// kept in memory by mlock, which fork does not pass on
+    locked: bool,
 }
 
 impl MapArea {
@@ -268,6 +1212,10 @@ impl MapArea {
             data_frames: BTreeMap::new(),
             map_type,
             map_perm,
+            backing: None,
+            grows_down: false,
+            swapped: BTreeMap::new(),
+            locked: false,
         }
     }
     pub fn from_another(another: &MapArea) -> Self {
@@ -276,8 +1224,70 @@ impl MapArea {
             data_frames: BTreeMap::new(),
             map_type: another.map_type,
             map_perm: another.map_perm,
+            backing: another.backing.clone(),
+            grows_down: another.grows_down,
+            swapped: BTreeMap::new(),
+            locked: false,
+        }
+    }
+    /// The frame of `vpn` in an area not backed by frames of its own.
+    fn fixed_ppn(&self, vpn: VirtPageNum) -> Option<PhysPageNum> {
+        match self.map_type {
+            MapType::Identical => Some(PhysPageNum(vpn.0)),
+            MapType::Framed => None,
+            MapType::Linear(pn_offset) => Some(PhysPageNum((vpn.0 as isize + pn_offset) as usize)),
         }
     }
+    /// Whether the pages from `vpn` on can be mapped with a megapage.
+    fn fits_huge(&self, vpn: VirtPageNum, ppn: PhysPageNum) -> bool {
+        vpn.0 % HUGE_PAGES == 0
+            && ppn.0 % HUGE_PAGES == 0
+            && vpn.0 >= self.vpn_range.get_start().0
+            && vpn.0 + HUGE_PAGES <= self.vpn_range.get_end().0
+    }
+    /// Back the megapage around `vpn` with contiguous frames at once, if the
+    /// area covers it and none of its pages is there yet.
+    fn map_huge_page(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
+        let start = VirtPageNum(vpn.0 / HUGE_PAGES * HUGE_PAGES);
+        let end = VirtPageNum(start.0 + HUGE_PAGES);
+        if !self.fits_huge(start, PhysPageNum(0))
+            || self.data_frames.range(start..end).next().is_some()
+            || self.swapped.range(start..end).next().is_some()
+        {
+            return false;
+        }
+        let frames = match frame_alloc_contiguous(HUGE_PAGES.trailing_zeros() as usize) {
+            Some(frames) => frames,
+            None => return false,
+        };
+        page_table.map_huge(start, frames[0].ppn, self.pte_flags());
+        for (i, frame) in frames.into_iter().enumerate() {
+            self.data_frames.insert(VirtPageNum(start.0 + i), frame);
+        }
+        true
+    }
+    /// Whether `vpn` is still all zeros, being private and writable and
+    /// never having been written: a page of an anonymous mapping, or one
+    /// past the data of a file mapping, like the bss. Read-only pages get
+    /// frames of their own, lest the kernel write on the zero page while
+    /// loading an image.
+    fn is_zero_fill(&self, vpn: VirtPageNum) -> bool {
+        if !self.map_perm.contains(MapPermission::W) || self.swapped.contains_key(&vpn) {
+            return false;
+        }
+        let page_start: usize = VirtAddr::from(vpn).into();
+        match &self.backing {
+            Some(MapBacking::Anonymous) => true,
+            Some(MapBacking::File { start_va, len, .. }) => page_start >= start_va + len,
+            _ => false,
+        }
+    }
+    /// Map `vpn` to the zero page, to be copied on the first write.
+    fn map_zero_page(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
+        let flags = (self.pte_flags() - PTEFlags::W) | PTEFlags::COW;
+        page_table.map(vpn, ZERO_PAGE.ppn, flags);
+        self.data_frames.insert(vpn, ZERO_PAGE.clone());
+    }
     pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
         let ppn: PhysPageNum;
         match self.map_type {
@@ -295,18 +1305,149 @@ impl MapArea {
                 ppn = PhysPageNum((vpn.0 as isize + pn_offset) as usize);
             }
         }
-        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
-        page_table.map(vpn, ppn, pte_flags);
+        page_table.map(vpn, ppn, self.pte_flags());
+    }
+    fn pte_flags(&self) -> PTEFlags {
+        PTEFlags::from_bits(self.map_perm.bits as u16).unwrap()
+    }
+    fn is_shared(&self) -> bool {
+        matches!(self.backing, Some(MapBacking::Shared { .. }))
+    }
+    /// PROT_NONE areas are not accessible, and so never mapped.
+    fn is_accessible(&self) -> bool {
+        self.map_perm
+            .intersects(MapPermission::R | MapPermission::W | MapPermission::X)
+    }
+    /// Pages of a lazy area not in memory, which would be faulted in on an
+    /// access.
+    fn missing_pages(&self) -> Vec<VirtPageNum> {
+        if self.backing.is_none() || !self.is_accessible() {
+            return Vec::new();
+        }
+        self.vpn_range
+            .into_iter()
+            .filter(|vpn| !self.data_frames.contains_key(vpn))
+            .collect()
+    }
+    /// Forget the page at `vpn`, and return its frame, which must not be
+    /// freed before the page table is flushed.
+    fn discard(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> Option<FrameTracker> {
+        self.swapped.remove(&vpn);
+        self.unmap_one(page_table, vpn);
+        self.data_frames.remove(&vpn)
+    }
+    /// Cut this area at `vpn` and return the upper part.
+    fn split_off(&mut self, vpn: VirtPageNum) -> MapArea {
+        let start = self.vpn_range.get_start();
+        let mut tail = MapArea::from_another(self);
+        tail.vpn_range = VPNRange::new(vpn, self.vpn_range.get_end());
+        tail.data_frames = self.data_frames.split_off(&vpn);
+        tail.swapped = self.swapped.split_off(&vpn);
+        tail.locked = self.locked;
+        if let Some(MapBacking::Shared { pgoff, .. }) = &mut tail.backing {
+            *pgoff += vpn.0 - start.0;
+        }
+        self.vpn_range = VPNRange::new(start, vpn);
+        tail
+    }
+    /// Change the permission of this area and of its mapped pages, where
+    /// private pages still shared after fork stay copy-on-write.
+    fn protect(&mut self, page_table: &mut PageTable, map_perm: MapPermission) {
+        self.map_perm = map_perm;
+        if self.is_shared() && map_perm.contains(MapPermission::W) {
+            for frame in self.data_frames.values() {
+                frame.set_mapped_write(true);
+            }
+        }
+        let flags = self.pte_flags();
+        for vpn in self.vpn_range {
+            let ppn = match self.fixed_ppn(vpn) {
+                Some(ppn) => ppn,
+                None => match self.data_frames.get(&vpn) {
+                    Some(frame) => frame.ppn,
+                    None => continue,
+                },
+            };
+            let mut flags = flags;
+            if flags.contains(PTEFlags::W)
+                && !self.is_shared()
+                && self
+                    .data_frames
+                    .get(&vpn)
+                    .map_or(false, |frame| frame.ref_count() > 1)
+            {
+                flags = (flags - PTEFlags::W) | PTEFlags::COW;
+            }
+            let mapped = page_table
+                .translate(vpn)
+                .map_or(false, |pte| pte.is_valid());
+            match (mapped, self.is_accessible()) {
+                (true, true) => page_table.remap(vpn, ppn, flags),
+                (true, false) => page_table.unmap(vpn),
+                (false, true) => page_table.map(vpn, ppn, flags),
+                (false, false) => {}
+            }
+        }
+        // megapages split by the change come back if they are still whole
+        let mut vpn = self.vpn_range.get_start();
+        vpn.0 = (vpn.0 + HUGE_PAGES - 1) / HUGE_PAGES * HUGE_PAGES;
+        while vpn.0 + HUGE_PAGES <= self.vpn_range.get_end().0 {
+            page_table.merge_huge(vpn);
+            vpn.0 += HUGE_PAGES;
+        }
+    }
+    /// Give `vpn` a frame of its own if it is still shared, and make it
+    /// writable again.
+    fn copy_on_write(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
+        match page_table.translate(vpn) {
+            Some(pte) if pte.is_valid() && pte.is_cow() => {}
+            _ => return false,
+        }
+        let frame = self.data_frames.get_mut(&vpn).unwrap();
+        if frame.ref_count() > 1 {
+            let new_frame = match frame_alloc_user() {
+                Some(new_frame) => new_frame,
+                None => return false,
+            };
+            new_frame
+                .ppn
+                .get_bytes_array()
+                .copy_from_slice(frame.ppn.get_bytes_array());
+            *frame = new_frame;
+        }
+        page_table.remap(vpn, frame.ppn, self.pte_flags());
+        true
     }
+    /// Frames stay in the area until it is dropped, which must not happen
+    /// before the page table is flushed.
     pub fn unmap_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
-        if self.map_type == MapType::Framed {
-            self.data_frames.remove(&vpn);
+        // pages of a lazy or PROT_NONE area may be unmapped already
+        if page_table
+            .translate(vpn)
+            .map_or(false, |pte| pte.is_valid())
+        {
+            page_table.unmap(vpn);
         }
-        page_table.unmap(vpn);
     }
+    /// Map the whole area, with megapages where the alignment allows if it
+    /// is not backed by frames of its own, like the physical memory of the
+    /// kernel.
     pub fn map(&mut self, page_table: &mut PageTable) {
-        for vpn in self.vpn_range {
-            self.map_one(page_table, vpn);
+        if self.backing.is_some() {
+            return;
+        }
+        let mut vpn = self.vpn_range.get_start();
+        while vpn < self.vpn_range.get_end() {
+            match self.fixed_ppn(vpn) {
+                Some(ppn) if self.fits_huge(vpn, ppn) => {
+                    page_table.map_huge(vpn, ppn, self.pte_flags());
+                    vpn = VirtPageNum(vpn.0 + HUGE_PAGES);
+                }
+                _ => {
+                    self.map_one(page_table, vpn);
+                    vpn.step();
+                }
+            }
         }
     }
     pub fn unmap(&mut self, page_table: &mut PageTable) {
@@ -1,22 +1,43 @@
 mod address;
 mod frame_allocator;
 mod heap_allocator;
+mod meminfo;
 mod memory_set;
 mod page_table;
+mod shm;
+mod slab;
+mod swap;
+mod tlb;
+mod user_ptr;
+mod vmalloc;
 
 pub use address::VPNRange;
+pub use address::{phys_to_virt, virt_to_phys};
 pub use address::{PhysAddr, PhysPageNum, StepByOne, VirtAddr, VirtPageNum};
-pub use frame_allocator::{frame_alloc, frame_alloc_more, frame_dealloc, FrameTracker};
+pub use frame_allocator::{
+    frame_alloc, frame_alloc_contiguous, frame_alloc_more, frame_dealloc, free_frame_count,
+    release_initrd, FrameTracker,
+};
+pub use heap_allocator::try_zeroed_bytes;
+pub use meminfo::{mem_info, MemInfo, VmStat};
 pub use memory_set::remap_test;
-pub use memory_set::{kernel_token, MapArea, MapPermission, MapType, MemorySet, KERNEL_SPACE};
-use page_table::PTEFlags;
-pub use page_table::{
-    translated_byte_buffer, translated_ref, translated_refmut, translated_str, PageTable,
-    PageTableEntry, UserBuffer, UserBufferIterator,
+pub use memory_set::{
+    kernel_token, overlaps_kernel, CoreArea, ElfInfo, MapArea, MapBacking, MapPermission, MapType,
+    MemorySet, PageFault, KERNEL_SPACE,
 };
+use page_table::HUGE_PAGES;
+pub use page_table::{PTEFlags, PageTable, PageTableEntry, UserBuffer, UserBufferIterator};
+pub use shm::{shm_get, shm_remove, shm_segment, SharedMemory};
+pub use swap::SwapEntry;
+pub use tlb::hart_id;
+pub use user_ptr::{UserPtr, UserSlice};
+pub use vmalloc::{vmalloc, VmBuffer};
 
+/// Set up the memory: until then, the kernel runs on the boot page table
+/// of entry.asm and allocates from the early heap.
 pub fn init() {
     heap_allocator::init_heap();
     frame_allocator::init_frame_allocator();
     KERNEL_SPACE.exclusive_access().activate();
+    tlb::init();
 }
@@ -1,11 +1,13 @@
-use super::{frame_alloc, FrameTracker, PhysAddr, PhysPageNum, StepByOne, VirtAddr, VirtPageNum};
-use alloc::string::String;
+use super::frame_allocator::PinnedFrame;
+use super::tlb::{self, TlbBatch, ASID_MASK, ASID_SHIFT};
+use super::{frame_alloc, FrameTracker, PhysAddr, PhysPageNum, VirtAddr, VirtPageNum};
+use crate::config::{HUGE_PAGE_SIZE, PAGE_SIZE};
 use alloc::vec;
 use alloc::vec::Vec;
 use bitflags::*;
 
 bitflags! {
-    pub struct PTEFlags: u8 {
+    pub struct PTEFlags: u16 {
         const V = 1 << 0;
         const R = 1 << 1;
         const W = 1 << 2;
@@ -14,6 +16,8 @@ bitflags! {
         const G = 1 << 5;
         const A = 1 << 6;
         const D = 1 << 7;
+        /// One of the RSW bits, set on a writable page shared after fork.
+        const COW = 1 << 8;
     }
 }
 
@@ -36,11 +40,21 @@ impl PageTableEntry {
         (self.bits >> 10 & ((1usize << 44) - 1)).into()
     }
     pub fn flags(&self) -> PTEFlags {
-        PTEFlags::from_bits(self.bits as u8).unwrap()
+        PTEFlags::from_bits_truncate(self.bits as u16)
     }
     pub fn is_valid(&self) -> bool {
         (self.flags() & PTEFlags::V) != PTEFlags::empty()
     }
+    /// Whether it maps a page rather than pointing to the next level.
+    pub fn is_leaf(&self) -> bool {
+        self.is_valid()
+            && self
+                .flags()
+                .intersects(PTEFlags::R | PTEFlags::W | PTEFlags::X)
+    }
+    pub fn is_user(&self) -> bool {
+        (self.flags() & PTEFlags::U) != PTEFlags::empty()
+    }
     pub fn readable(&self) -> bool {
         (self.flags() & PTEFlags::R) != PTEFlags::empty()
     }
@@ -50,20 +64,37 @@ impl PageTableEntry {
     pub fn executable(&self) -> bool {
         (self.flags() & PTEFlags::X) != PTEFlags::empty()
     }
+    pub fn is_cow(&self) -> bool {
+        (self.flags() & PTEFlags::COW) != PTEFlags::empty()
+    }
+    pub fn is_accessed(&self) -> bool {
+        (self.flags() & PTEFlags::A) != PTEFlags::empty()
+    }
+    pub fn is_dirty(&self) -> bool {
+        (self.flags() & PTEFlags::D) != PTEFlags::empty()
+    }
 }
 
+/// Pages in a megapage, which is mapped by a leaf of the middle level.
+pub const HUGE_PAGES: usize = HUGE_PAGE_SIZE / PAGE_SIZE;
+
 pub struct PageTable {
     root_ppn: PhysPageNum,
     frames: Vec<FrameTracker>,
+    asid: usize,
+    /// changes not flushed from the TLB yet
+    batch: TlbBatch,
 }
 
 /// Assume that it won't oom when creating/mapping.
 impl PageTable {
-    pub fn new() -> Self {
+    pub fn new(asid: usize) -> Self {
         let frame = frame_alloc().unwrap();
         PageTable {
             root_ppn: frame.ppn,
             frames: vec![frame],
+            asid,
+            batch: TlbBatch::new(),
         }
     }
     /// Temporarily used to get arguments from user space.
@@ -71,8 +102,12 @@ impl PageTable {
         Self {
             root_ppn: PhysPageNum::from(satp & ((1usize << 44) - 1)),
             frames: Vec::new(),
+            asid: satp >> ASID_SHIFT & ASID_MASK,
+            batch: TlbBatch::new(),
         }
     }
+    /// The last level entry of `vpn`, where a megapage covering it is
+    /// split first.
     fn find_pte_create(&mut self, vpn: VirtPageNum) -> Option<&mut PageTableEntry> {
         let idxs = vpn.indexes();
         let mut ppn = self.root_ppn;
@@ -83,6 +118,9 @@ impl PageTable {
                 result = Some(pte);
                 break;
             }
+            if pte.is_leaf() {
+                self.split(pte);
+            }
             if !pte.is_valid() {
                 let frame = frame_alloc().unwrap();
                 *pte = PageTableEntry::new(frame.ppn, PTEFlags::V);
@@ -92,40 +130,178 @@ impl PageTable {
         }
         result
     }
-    fn find_pte(&self, vpn: VirtPageNum) -> Option<&mut PageTableEntry> {
+    /// The leaf mapping `vpn` and its level, 1 for a megapage and 2 for a
+    /// page.
+    fn find_leaf(&self, vpn: VirtPageNum) -> Option<(&'static mut PageTableEntry, usize)> {
         let idxs = vpn.indexes();
         let mut ppn = self.root_ppn;
-        let mut result: Option<&mut PageTableEntry> = None;
         for (i, idx) in idxs.iter().enumerate() {
             let pte = &mut ppn.get_pte_array()[*idx];
-            if i == 2 {
-                result = Some(pte);
-                break;
+            if i == 2 || pte.is_leaf() {
+                return Some((pte, i));
             }
             if !pte.is_valid() {
                 return None;
             }
             ppn = pte.ppn();
         }
-        result
+        None
+    }
+    /// The last level entry of `vpn`, None if it is in a megapage.
+    fn find_pte(&self, vpn: VirtPageNum) -> Option<&mut PageTableEntry> {
+        match self.find_leaf(vpn) {
+            Some((pte, 2)) => Some(pte),
+            _ => None,
+        }
+    }
+    /// Replace the megapage leaf `pte` by a table of pages with the same
+    /// flags.
+    fn split(&mut self, pte: &mut PageTableEntry) {
+        let frame = frame_alloc().unwrap();
+        let (first, flags) = (pte.ppn().0, pte.flags());
+        for (i, entry) in frame.ppn.get_pte_array().iter_mut().enumerate() {
+            *entry = PageTableEntry::new(PhysPageNum(first + i), flags);
+        }
+        *pte = PageTableEntry::new(frame.ppn, PTEFlags::V);
+        self.frames.push(frame);
+    }
+    /// Split the megapage covering `vpn` if there is one, so that its pages
+    /// can be changed one by one.
+    pub fn split_huge(&mut self, vpn: VirtPageNum) {
+        if let Some((pte, 1)) = self.find_leaf(vpn) {
+            self.split(pte);
+        }
+    }
+    pub fn is_huge(&self, vpn: VirtPageNum) -> bool {
+        matches!(self.find_leaf(vpn), Some((_, level)) if level < 2)
+    }
+    /// The middle level entry of the megapage starting at `vpn`.
+    fn find_huge_pte_create(&mut self, vpn: VirtPageNum) -> &'static mut PageTableEntry {
+        assert_eq!(
+            vpn.0 % HUGE_PAGES,
+            0,
+            "vpn {:?} is not megapage aligned",
+            vpn
+        );
+        let idxs = vpn.indexes();
+        let root = &mut self.root_ppn.get_pte_array()[idxs[0]];
+        if !root.is_valid() {
+            let frame = frame_alloc().unwrap();
+            *root = PageTableEntry::new(frame.ppn, PTEFlags::V);
+            self.frames.push(frame);
+        }
+        &mut root.ppn().get_pte_array()[idxs[1]]
+    }
+    /// Free a table of the last level if nothing is mapped by it any more.
+    fn free_empty_table(&mut self, pte: &mut PageTableEntry) {
+        if pte.is_valid()
+            && !pte.is_leaf()
+            && pte
+                .ppn()
+                .get_pte_array()
+                .iter()
+                .all(|entry| !entry.is_valid())
+        {
+            let table = pte.ppn();
+            self.frames.retain(|frame| frame.ppn != table);
+            *pte = PageTableEntry::empty();
+            self.batch.add_all();
+        }
+    }
+    /// Map the megapage at `vpn` to the frames from `ppn` on, both aligned.
+    pub fn map_huge(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) {
+        assert_eq!(
+            ppn.0 % HUGE_PAGES,
+            0,
+            "ppn {:?} is not megapage aligned",
+            ppn
+        );
+        let pte = self.find_huge_pte_create(vpn);
+        // pages unmapped from here may have left their table behind
+        self.free_empty_table(pte);
+        assert!(!pte.is_valid(), "vpn {:?} is mapped before mapping", vpn);
+        *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
+        self.batch.add(vpn);
+    }
+    /// Map the pages from `vpn` on with a megapage again if they are mapped
+    /// to aligned contiguous frames with the same flags. Return whether it
+    /// was done.
+    pub fn merge_huge(&mut self, vpn: VirtPageNum) -> bool {
+        if vpn.0 % HUGE_PAGES != 0 {
+            return false;
+        }
+        let idxs = vpn.indexes();
+        let root = self.root_ppn.get_pte_array()[idxs[0]];
+        if !root.is_valid() || root.is_leaf() {
+            return false;
+        }
+        let middle = &mut root.ppn().get_pte_array()[idxs[1]];
+        if !middle.is_valid() || middle.is_leaf() {
+            return false;
+        }
+        let entries = middle.ppn().get_pte_array();
+        let first = entries[0];
+        if !first.is_leaf() || first.ppn().0 % HUGE_PAGES != 0 {
+            return false;
+        }
+        // accessed and dirty bits may differ, the megapage gets all of them
+        let usage = PTEFlags::A | PTEFlags::D;
+        let mut flags = first.flags();
+        for (i, entry) in entries.iter().enumerate() {
+            if !entry.is_leaf()
+                || entry.ppn().0 != first.ppn().0 + i
+                || entry.flags() - usage != first.flags() - usage
+            {
+                return false;
+            }
+            flags |= entry.flags() & usage;
+        }
+        let table = middle.ppn();
+        self.frames.retain(|frame| frame.ppn != table);
+        *middle = PageTableEntry::new(first.ppn(), flags);
+        self.batch.add_all();
+        true
     }
     #[allow(unused)]
     pub fn map(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) {
         let pte = self.find_pte_create(vpn).unwrap();
         assert!(!pte.is_valid(), "vpn {:?} is mapped before mapping", vpn);
         *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
+        self.batch.add(vpn);
     }
     #[allow(unused)]
     pub fn unmap(&mut self, vpn: VirtPageNum) {
+        self.split_huge(vpn);
         let pte = self.find_pte(vpn).unwrap();
         assert!(pte.is_valid(), "vpn {:?} is invalid before unmapping", vpn);
         *pte = PageTableEntry::empty();
+        self.batch.add(vpn);
     }
+    /// Change the frame and the flags of a mapped `vpn`.
+    pub fn remap(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) {
+        self.split_huge(vpn);
+        let pte = self.find_pte(vpn).unwrap();
+        assert!(pte.is_valid(), "vpn {:?} is invalid before remapping", vpn);
+        *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
+        self.batch.add(vpn);
+    }
+    /// Clear the accessed bit of a mapped `vpn`, which the hardware sets
+    /// again on the next access once the TLB is flushed.
+    pub fn clear_accessed(&mut self, vpn: VirtPageNum) {
+        self.split_huge(vpn);
+        let pte = self.find_pte(vpn).unwrap();
+        *pte = PageTableEntry::new(pte.ppn(), pte.flags() - PTEFlags::A);
+        self.batch.add(vpn);
+    }
+    /// The entry of `vpn` as if it were mapped by a page of its own.
     pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
-        self.find_pte(vpn).map(|pte| *pte)
+        self.find_leaf(vpn).map(|(pte, level)| {
+            let pages = 1 << (9 * (2 - level));
+            PageTableEntry::new(PhysPageNum(pte.ppn().0 + vpn.0 % pages), pte.flags())
+        })
     }
     pub fn translate_va(&self, va: VirtAddr) -> Option<PhysAddr> {
-        self.find_pte(va.clone().floor()).map(|pte| {
+        self.translate(va.clone().floor()).map(|pte| {
             let aligned_pa: PhysAddr = pte.ppn().into();
             let offset = va.page_offset();
             let aligned_pa_usize: usize = aligned_pa.into();
@@ -133,75 +309,43 @@ impl PageTable {
         })
     }
     pub fn token(&self) -> usize {
-        8usize << 60 | self.root_ppn.0
+        8usize << 60 | self.asid << ASID_SHIFT | self.root_ppn.0
     }
-}
-
-pub fn translated_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Vec<&'static mut [u8]> {
-    let page_table = PageTable::from_token(token);
-    let mut start = ptr as usize;
-    let end = start + len;
-    let mut v = Vec::new();
-    while start < end {
-        let start_va = VirtAddr::from(start);
-        let mut vpn = start_va.floor();
-        let ppn = page_table.translate(vpn).unwrap().ppn();
-        vpn.step();
-        let mut end_va: VirtAddr = vpn.into();
-        end_va = end_va.min(VirtAddr::from(end));
-        if end_va.page_offset() == 0 {
-            v.push(&mut ppn.get_bytes_array()[start_va.page_offset()..]);
-        } else {
-            v.push(&mut ppn.get_bytes_array()[start_va.page_offset()..end_va.page_offset()]);
-        }
-        start = end_va.into();
+    /// Flush the changes made so far from the TLB.
+    pub fn flush(&mut self) {
+        tlb::flush(self.asid, &mut self.batch);
+    }
+    /// Flush everything of this table, e.g. before its frames go away.
+    pub fn flush_all(&mut self) {
+        self.batch.add_all();
+        self.flush();
+    }
+    /// Make this hart ready to translate through this table, returning the
+    /// token to load.
+    pub fn enter(&mut self) -> usize {
+        self.flush();
+        tlb::switch_to(self.asid);
+        self.token()
+    }
+    /// Give the ASID back, flushing it first.
+    pub fn release_asid(&mut self) {
+        tlb::asid_dealloc(self.asid);
+        self.asid = 0;
     }
-    v
-}
-
-/// Load a string from other address spaces into kernel space without an end `\0`.
-pub fn translated_str(token: usize, ptr: *const u8) -> String {
-    let page_table = PageTable::from_token(token);
-    let mut string = String::new();
-    let mut va = ptr as usize;
-    loop {
-        let ch: u8 = *(page_table
-            .translate_va(VirtAddr::from(va))
-            .unwrap()
-            .get_mut());
-        if ch == 0 {
-            break;
-        }
-        string.push(ch as char);
-        va += 1;
-    }
-    string
-}
-
-pub fn translated_ref<T>(token: usize, ptr: *const T) -> &'static T {
-    let page_table = PageTable::from_token(token);
-    page_table
-        .translate_va(VirtAddr::from(ptr as usize))
-        .unwrap()
-        .get_ref()
-}
-
-pub fn translated_refmut<T>(token: usize, ptr: *mut T) -> &'static mut T {
-    let page_table = PageTable::from_token(token);
-    let va = ptr as usize;
-    page_table
-        .translate_va(VirtAddr::from(va))
-        .unwrap()
-        .get_mut()
 }
 
 pub struct UserBuffer {
     pub buffers: Vec<&'static mut [u8]>,
+    /// the frames of `buffers`, kept from being reclaimed
+    _pins: Vec<PinnedFrame>,
 }
 
 impl UserBuffer {
-    pub fn new(buffers: Vec<&'static mut [u8]>) -> Self {
-        Self { buffers }
+    pub fn new(buffers: Vec<&'static mut [u8]>, pins: Vec<PinnedFrame>) -> Self {
+        Self {
+            buffers,
+            _pins: pins,
+        }
     }
     pub fn len(&self) -> usize {
         let mut total: usize = 0;
@@ -210,6 +354,11 @@ impl UserBuffer {
         }
         total
     }
+    /// Put the pieces of `other` after those of this one.
+    pub fn append(&mut self, other: UserBuffer) {
+        self.buffers.extend(other.buffers);
+        self._pins.extend(other._pins);
+    }
 }
 
 impl IntoIterator for UserBuffer {
@@ -220,6 +369,7 @@ impl IntoIterator for UserBuffer {
             buffers: self.buffers,
             current_buffer: 0,
             current_idx: 0,
+            _pins: self._pins,
         }
     }
 }
@@ -228,6 +378,7 @@ pub struct UserBufferIterator {
     buffers: Vec<&'static mut [u8]>,
     current_buffer: usize,
     current_idx: usize,
+    _pins: Vec<PinnedFrame>,
 }
 
 impl Iterator for UserBufferIterator {
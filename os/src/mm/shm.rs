@@ -0,0 +1,250 @@
+use super::{frame_alloc, FrameTracker};
+use crate::config::PAGE_SIZE;
+use crate::fs::Inode;
+use crate::sync::UPIntrFreeCell;
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use lazy_static::*;
+
+struct SharedPage {
+    frame: FrameTracker,
+    /// bytes of the page that came from the file and are written back
+    file_len: usize,
+    /// a page of the page cache of the file, which writes it back itself
+    cached: bool,
+}
+
+/// Pages shared by every `MAP_SHARED` mapping of the same object. They are
+/// allocated on first touch and freed when the last mapping goes away.
+pub struct SharedMemory {
+    pages: UPIntrFreeCell<BTreeMap<usize, SharedPage>>,
+    /// the file, page 0 being its first page, if the object is a file
+    file: Option<Arc<dyn Inode>>,
+}
+
+lazy_static! {
+    /// The objects of the files mapped MAP_SHARED, by their position, one
+    /// for each file so that all of its mappings see the same pages.
+    static ref FILE_OBJECTS: UPIntrFreeCell<BTreeMap<(usize, usize), Weak<SharedMemory>>> =
+        unsafe { UPIntrFreeCell::new(BTreeMap::new()) };
+}
+
+impl SharedMemory {
+    pub fn new_anonymous() -> Arc<Self> {
+        Arc::new(Self {
+            pages: unsafe { UPIntrFreeCell::new(BTreeMap::new()) },
+            file: None,
+        })
+    }
+
+    /// The object of `file`, shared with its other mappings, whoever
+    /// opened it, if its file system can tell its files apart. Its pages
+    /// are those of the page cache if the file is in one, so that read and
+    /// write see them too.
+    pub fn of_file(file: Arc<dyn Inode>) -> Arc<Self> {
+        let key = file.position();
+        let mut objects = FILE_OBJECTS.exclusive_access();
+        if let Some(object) = key
+            .and_then(|key| objects.get(&key))
+            .and_then(|object| object.upgrade())
+        {
+            return object;
+        }
+        let object = Arc::new(Self {
+            pages: unsafe { UPIntrFreeCell::new(BTreeMap::new()) },
+            file: Some(file),
+        });
+        if let Some(key) = key {
+            objects.insert(key, Arc::downgrade(&object));
+        }
+        object
+    }
+
+    /// Pages of memory allocated already, `frames` in order, like those
+    /// the kernel writes into for a user to map.
+    pub fn new_frames(frames: Vec<FrameTracker>) -> Arc<Self> {
+        let pages = frames
+            .into_iter()
+            .enumerate()
+            .map(|(index, frame)| {
+                let page = SharedPage {
+                    frame,
+                    file_len: 0,
+                    cached: false,
+                };
+                (index, page)
+            })
+            .collect();
+        Arc::new(Self {
+            pages: unsafe { UPIntrFreeCell::new(pages) },
+            file: None,
+        })
+    }
+
+    /// Get page `index`, reading it from the file the first time.
+    pub fn page(&self, index: usize) -> Option<FrameTracker> {
+        if let Some(page) = self.pages.exclusive_access().get(&index) {
+            return Some(page.frame.clone());
+        }
+        // reading the file may sleep, so do not hold the lock
+        let cached = self.file.as_ref().and_then(|file| file.cached_page(index));
+        let page = match (cached, &self.file) {
+            (Some(frame), _) => SharedPage {
+                frame,
+                file_len: 0,
+                cached: true,
+            },
+            (None, Some(file)) => {
+                let frame = frame_alloc()?;
+                let file_len = file.read_at(index * PAGE_SIZE, frame.ppn.get_bytes_array());
+                SharedPage {
+                    frame,
+                    file_len,
+                    cached: false,
+                }
+            }
+            (None, None) => SharedPage {
+                frame: frame_alloc()?,
+                file_len: 0,
+                cached: false,
+            },
+        };
+        let mut pages = self.pages.exclusive_access();
+        // someone else may have loaded it meanwhile
+        let page = pages.entry(index).or_insert(page);
+        Some(page.frame.clone())
+    }
+
+    /// Page `index` if it has been touched.
+    pub fn get(&self, index: usize) -> Option<FrameTracker> {
+        self.pages
+            .exclusive_access()
+            .get(&index)
+            .map(|page| page.frame.clone())
+    }
+
+    /// Forget the pages from `index` on, which mappings still holding them
+    /// keep to themselves.
+    pub fn truncate(&self, index: usize) {
+        let mut pages = self.pages.exclusive_access();
+        let tail = pages.split_off(&index);
+        drop(pages);
+        drop(tail);
+    }
+
+    /// Number of pages touched.
+    pub fn page_count(&self) -> usize {
+        self.pages.exclusive_access().len()
+    }
+}
+
+impl Drop for SharedMemory {
+    fn drop(&mut self) {
+        if let Some(file) = &self.file {
+            if let Some(key) = file.position() {
+                let mut objects = FILE_OBJECTS.exclusive_access();
+                // unless the file has been mapped again meanwhile
+                if objects
+                    .get(&key)
+                    .map_or(false, |object| object.strong_count() == 0)
+                {
+                    objects.remove(&key);
+                }
+            }
+            let pages = core::mem::take(&mut *self.pages.exclusive_access());
+            for (index, page) in pages {
+                if page.cached {
+                    // written back with the rest of the cache, and with
+                    // what write put in it meanwhile
+                    page.frame.set_mapped_write(false);
+                    page.frame.set_dirty();
+                } else {
+                    let data = &page.frame.ppn.get_bytes_array()[..page.file_len];
+                    file.write_at(index * PAGE_SIZE, data);
+                }
+            }
+        }
+    }
+}
+
+/// A System V shared memory segment.
+#[derive(Clone)]
+pub struct ShmSegment {
+    pub object: Arc<SharedMemory>,
+    /// size in bytes, rounded up to pages
+    pub size: usize,
+}
+
+struct ShmTable {
+    /// id of the segment created for each key, IPC_PRIVATE ones excluded
+    keys: BTreeMap<usize, usize>,
+    segments: BTreeMap<usize, (usize, ShmSegment)>,
+    next_id: usize,
+}
+
+lazy_static! {
+    static ref SHM_TABLE: UPIntrFreeCell<ShmTable> = unsafe {
+        UPIntrFreeCell::new(ShmTable {
+            keys: BTreeMap::new(),
+            segments: BTreeMap::new(),
+            next_id: 0,
+        })
+    };
+}
+
+const IPC_PRIVATE: usize = 0;
+
+/// Find the segment of `key`, or create one of `size` bytes if `create` is
+/// set. An existing segment must be at least `size` bytes and is only
+/// accepted if `exclusive` is not set. Return its id.
+pub fn shm_get(key: usize, size: usize, create: bool, exclusive: bool) -> Option<usize> {
+    let mut table = SHM_TABLE.exclusive_access();
+    if key != IPC_PRIVATE {
+        if let Some(id) = table.keys.get(&key).copied() {
+            let (_, segment) = &table.segments[&id];
+            if exclusive || segment.size < size {
+                return None;
+            }
+            return Some(id);
+        }
+        if !create {
+            return None;
+        }
+    }
+    if size == 0 {
+        return None;
+    }
+    let id = table.next_id;
+    table.next_id += 1;
+    let segment = ShmSegment {
+        object: SharedMemory::new_anonymous(),
+        size: (size + PAGE_SIZE - 1) / PAGE_SIZE * PAGE_SIZE,
+    };
+    table.segments.insert(id, (key, segment));
+    if key != IPC_PRIVATE {
+        table.keys.insert(key, id);
+    }
+    Some(id)
+}
+
+pub fn shm_segment(id: usize) -> Option<ShmSegment> {
+    SHM_TABLE
+        .exclusive_access()
+        .segments
+        .get(&id)
+        .map(|(_, segment)| segment.clone())
+}
+
+/// Forget segment `id`, whose pages are freed once the last process
+/// attached to it detaches.
+pub fn shm_remove(id: usize) -> bool {
+    let mut table = SHM_TABLE.exclusive_access();
+    match table.segments.remove(&id) {
+        Some((key, _)) => {
+            table.keys.remove(&key);
+            true
+        }
+        None => false,
+    }
+}
@@ -0,0 +1,302 @@
+//! Slab caches for small kernel objects.
+//!
+//! Objects of one layout are packed into slabs of a few pages carved from
+//! the kernel heap, instead of being scattered over it. A slab is aligned
+//! to its size and starts with a header keeping the free list of its
+//! objects, so an object finds its slab by rounding its address down.
+//! The hottest kernel objects have caches of their own, and other small
+//! allocations go to power-of-two kmalloc caches.
+
+use super::heap_allocator::{heap_alloc, HEAP};
+use crate::config::PAGE_SIZE;
+use crate::sync::UPIntrFreeCell;
+use crate::task::{ProcessControlBlock, TaskControlBlock};
+use core::alloc::Layout;
+use core::mem::{align_of, size_of};
+use core::ptr::{self, NonNull};
+use lazy_static::*;
+
+/// Sizes of the kmalloc caches, larger allocations go to the heap.
+const KMALLOC_SIZES: [usize; 7] = [16, 32, 64, 128, 256, 512, 1024];
+const MAX_DEDICATED_CACHES: usize = 8;
+/// Slabs are made large enough for at least this many objects.
+const MIN_OBJECTS_PER_SLAB: usize = 8;
+
+/// Free objects of poisoned caches are filled with POISON_FREE, which is
+/// checked when they are handed out again, and new objects with
+/// POISON_INUSE, to catch writes after free and reads of uninitialized
+/// memory.
+const POISON_FREE: u8 = 0x6b;
+const POISON_INUSE: u8 = 0x5a;
+const SLAB_POISON: bool = cfg!(debug_assertions);
+
+#[repr(C)]
+struct SlabHeader {
+    /// first free object, whose first word points to the next one
+    free: *mut u8,
+    in_use: usize,
+    /// neighbours on the partial list of the cache
+    prev: *mut SlabHeader,
+    next: *mut SlabHeader,
+}
+
+pub struct SlabCache {
+    name: &'static str,
+    /// layout of the objects as asked for
+    layout: Layout,
+    object_size: usize,
+    slab_size: usize,
+    /// offset of the first object in a slab
+    first_object: usize,
+    objects_per_slab: usize,
+    poison: bool,
+    /// slabs with free objects, a fully free one included
+    partial: *mut SlabHeader,
+    slabs: usize,
+    objects: usize,
+}
+
+impl SlabCache {
+    pub fn new(name: &'static str, layout: Layout, poison: bool) -> Self {
+        let align = layout.align().max(align_of::<usize>());
+        let object_size = round_up(layout.size().max(size_of::<usize>()), align);
+        let first_object = round_up(size_of::<SlabHeader>(), align);
+        let mut slab_size = PAGE_SIZE;
+        while (slab_size - first_object) / object_size < MIN_OBJECTS_PER_SLAB {
+            slab_size *= 2;
+        }
+        Self {
+            name,
+            layout,
+            object_size,
+            slab_size,
+            first_object,
+            objects_per_slab: (slab_size - first_object) / object_size,
+            poison,
+            partial: ptr::null_mut(),
+            slabs: 0,
+            objects: 0,
+        }
+    }
+
+    fn slab_layout(&self) -> Layout {
+        Layout::from_size_align(self.slab_size, self.slab_size).unwrap()
+    }
+
+    /// Take a new slab from the heap and put all of its objects on its free
+    /// list.
+    unsafe fn grow(&mut self) -> bool {
+        let base = heap_alloc(self.slab_layout());
+        if base.is_null() {
+            return false;
+        }
+        let slab = base as *mut SlabHeader;
+        let mut free = ptr::null_mut();
+        for i in (0..self.objects_per_slab).rev() {
+            let object = base.add(self.first_object + i * self.object_size);
+            if self.poison {
+                ptr::write_bytes(object, POISON_FREE, self.object_size);
+            }
+            *(object as *mut *mut u8) = free;
+            free = object;
+        }
+        slab.write(SlabHeader {
+            free,
+            in_use: 0,
+            prev: ptr::null_mut(),
+            next: ptr::null_mut(),
+        });
+        self.push_partial(slab);
+        self.slabs += 1;
+        true
+    }
+
+    unsafe fn push_partial(&mut self, slab: *mut SlabHeader) {
+        (*slab).prev = ptr::null_mut();
+        (*slab).next = self.partial;
+        if !self.partial.is_null() {
+            (*self.partial).prev = slab;
+        }
+        self.partial = slab;
+    }
+
+    unsafe fn unlink_partial(&mut self, slab: *mut SlabHeader) {
+        let (prev, next) = ((*slab).prev, (*slab).next);
+        if prev.is_null() {
+            self.partial = next;
+        } else {
+            (*prev).next = next;
+        }
+        if !next.is_null() {
+            (*next).prev = prev;
+        }
+    }
+
+    unsafe fn check_poison(&self, object: *mut u8) {
+        let poisoned = core::slice::from_raw_parts(object, self.object_size);
+        // the first word links the free list
+        if poisoned[size_of::<usize>()..]
+            .iter()
+            .any(|byte| *byte != POISON_FREE)
+        {
+            panic!(
+                "slab cache {}: object {:#x} was written after being freed",
+                self.name, object as usize
+            );
+        }
+    }
+
+    unsafe fn alloc(&mut self) -> *mut u8 {
+        if self.partial.is_null() && !self.grow() {
+            return ptr::null_mut();
+        }
+        let slab = self.partial;
+        let object = (*slab).free;
+        (*slab).free = *(object as *mut *mut u8);
+        (*slab).in_use += 1;
+        if (*slab).free.is_null() {
+            self.unlink_partial(slab);
+        }
+        self.objects += 1;
+        if self.poison {
+            self.check_poison(object);
+            ptr::write_bytes(object, POISON_INUSE, self.object_size);
+        }
+        object
+    }
+
+    unsafe fn dealloc(&mut self, object: *mut u8) {
+        let slab = (object as usize & !(self.slab_size - 1)) as *mut SlabHeader;
+        if self.poison {
+            ptr::write_bytes(object, POISON_FREE, self.object_size);
+        }
+        let was_full = (*slab).free.is_null();
+        *(object as *mut *mut u8) = (*slab).free;
+        (*slab).free = object;
+        (*slab).in_use -= 1;
+        self.objects -= 1;
+        if was_full {
+            self.push_partial(slab);
+        }
+        // keep a single free slab around
+        if (*slab).in_use == 0 && !((*slab).prev.is_null() && (*slab).next.is_null()) {
+            self.unlink_partial(slab);
+            HEAP.lock()
+                .dealloc(NonNull::new_unchecked(slab as *mut u8), self.slab_layout());
+            self.slabs -= 1;
+        }
+    }
+}
+
+fn round_up(size: usize, align: usize) -> usize {
+    (size + align - 1) / align * align
+}
+
+/// Layout of the allocation behind an `Arc<T>`, which puts the counts
+/// before the value.
+fn arc_layout<T>() -> Layout {
+    #[repr(C)]
+    struct ArcInner<T> {
+        strong: usize,
+        weak: usize,
+        data: T,
+    }
+    Layout::new::<ArcInner<T>>()
+}
+
+pub struct SlabAllocator {
+    /// caches of the hot objects, matched by their exact layout
+    dedicated: [Option<SlabCache>; MAX_DEDICATED_CACHES],
+    kmalloc: [SlabCache; KMALLOC_SIZES.len()],
+}
+
+impl SlabAllocator {
+    fn new() -> Self {
+        const NO_CACHE: Option<SlabCache> = None;
+        let mut allocator = Self {
+            dedicated: [NO_CACHE; MAX_DEDICATED_CACHES],
+            kmalloc: KMALLOC_SIZES.map(|size| {
+                let layout = Layout::from_size_align(size, size).unwrap();
+                SlabCache::new("kmalloc", layout, SLAB_POISON)
+            }),
+        };
+        // these are all known up front, as an object must be freed to the
+        // cache it came from
+        allocator.add_cache("TaskControlBlock", arc_layout::<TaskControlBlock>());
+        allocator.add_cache("ProcessControlBlock", arc_layout::<ProcessControlBlock>());
+        allocator
+    }
+
+    fn add_cache(&mut self, name: &'static str, layout: Layout) {
+        // types with the same layout share a cache
+        if self.dedicated.iter().flatten().any(|c| c.layout == layout) {
+            return;
+        }
+        let slot = self.dedicated.iter_mut().find(|c| c.is_none()).unwrap();
+        *slot = Some(SlabCache::new(name, layout, SLAB_POISON));
+    }
+
+    fn cache(&mut self, layout: Layout) -> Option<&mut SlabCache> {
+        if let Some(cache) = self
+            .dedicated
+            .iter_mut()
+            .flatten()
+            .find(|c| c.layout == layout)
+        {
+            return Some(cache);
+        }
+        // kmalloc objects are aligned to their size
+        let size = layout.size().max(layout.align());
+        self.kmalloc.iter_mut().find(|c| c.object_size >= size)
+    }
+}
+
+lazy_static! {
+    static ref SLAB_ALLOCATOR: UPIntrFreeCell<SlabAllocator> =
+        unsafe { UPIntrFreeCell::new(SlabAllocator::new()) };
+}
+
+/// Print the usage of the caches in use.
+pub fn print_slab_stats() {
+    let allocator = match SLAB_ALLOCATOR.try_exclusive_access() {
+        Some(allocator) => allocator,
+        None => {
+            println!("  slab caches busy");
+            return;
+        }
+    };
+    let caches = allocator.dedicated.iter().flatten();
+    for cache in caches.chain(allocator.kmalloc.iter()) {
+        if cache.slabs == 0 {
+            continue;
+        }
+        println!(
+            "  slab {:<20} size {:>5} objects {:>6}/{:<6} slabs {:>4} ({} KiB)",
+            cache.name,
+            cache.object_size,
+            cache.objects,
+            cache.slabs * cache.objects_per_slab,
+            cache.slabs,
+            cache.slabs * cache.slab_size / 1024
+        );
+    }
+}
+
+/// Allocate from the slab cache for `layout`, or return None if there is
+/// none and the heap has to be used.
+pub unsafe fn slab_alloc(layout: Layout) -> Option<*mut u8> {
+    let mut allocator = SLAB_ALLOCATOR.exclusive_access();
+    allocator.cache(layout).map(|cache| cache.alloc())
+}
+
+/// Free `ptr` to its slab cache, or return false if it came from the heap.
+pub unsafe fn slab_dealloc(ptr: *mut u8, layout: Layout) -> bool {
+    let mut allocator = SLAB_ALLOCATOR.exclusive_access();
+    match allocator.cache(layout) {
+        Some(cache) => {
+            cache.dealloc(ptr);
+            true
+        }
+        None => false,
+    }
+}
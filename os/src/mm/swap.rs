@@ -0,0 +1,115 @@
+//! Swap area on the block device, right after the file system; without a
+//! disk, there is none.
+//!
+//! A page swapped out is kept in a `SwapEntry` taking its place in the map
+//! area. The entry holds on to the frame until it has been written out, so
+//! that a fault meanwhile finds the data in memory, and frees its slot when
+//! dropped.
+
+use super::{frame_alloc, FrameTracker};
+use crate::config::{PAGE_SIZE, SWAP_PAGES, SWAP_START_BLOCK};
+use crate::drivers::BLOCK_DEVICE;
+use crate::sync::UPIntrFreeCell;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use easy_fs::BLOCK_SZ;
+use lazy_static::*;
+
+const BLOCKS_PER_PAGE: usize = PAGE_SIZE / BLOCK_SZ;
+
+struct SwapArea {
+    /// slots from here on were never used
+    current: usize,
+    recycled: Vec<usize>,
+}
+
+lazy_static! {
+    static ref SWAP_AREA: UPIntrFreeCell<SwapArea> = unsafe {
+        UPIntrFreeCell::new(SwapArea {
+            current: 0,
+            recycled: Vec::new(),
+        })
+    };
+}
+
+fn slot_alloc() -> Option<usize> {
+    BLOCK_DEVICE.as_ref()?;
+    let mut area = SWAP_AREA.exclusive_access();
+    if let Some(slot) = area.recycled.pop() {
+        return Some(slot);
+    }
+    if area.current == SWAP_PAGES {
+        return None;
+    }
+    area.current += 1;
+    Some(area.current - 1)
+}
+
+/// Numbers of used slots and of all slots.
+pub fn swap_stats() -> Option<(usize, usize)> {
+    let area = SWAP_AREA.try_exclusive_access()?;
+    let total = if BLOCK_DEVICE.is_some() { SWAP_PAGES } else { 0 };
+    Some((area.current - area.recycled.len(), total))
+}
+
+pub struct SwapEntry {
+    slot: usize,
+    /// the page until it is written out
+    cache: UPIntrFreeCell<Option<FrameTracker>>,
+}
+
+impl SwapEntry {
+    /// Take `frame` out to a free slot, or give it back if there is none.
+    pub fn new(frame: FrameTracker) -> Result<Arc<Self>, FrameTracker> {
+        match slot_alloc() {
+            Some(slot) => Ok(Arc::new(Self {
+                slot,
+                cache: unsafe { UPIntrFreeCell::new(Some(frame)) },
+            })),
+            None => Err(frame),
+        }
+    }
+
+    fn first_block(&self) -> usize {
+        SWAP_START_BLOCK + self.slot * BLOCKS_PER_PAGE
+    }
+
+    /// Write the page to its slot and free the frame. This may sleep, so
+    /// nothing may be locked.
+    pub fn write_out(&self) {
+        let frame = match self.cache.exclusive_access().as_ref() {
+            Some(frame) => frame.clone(),
+            None => return,
+        };
+        let data = frame.ppn.get_bytes_array();
+        // a slot is only ever taken with a disk
+        let disk = BLOCK_DEVICE.as_ref().unwrap();
+        for (i, block) in data.chunks_exact(BLOCK_SZ).enumerate() {
+            disk.write_block(self.first_block() + i, block);
+        }
+        *self.cache.exclusive_access() = None;
+    }
+
+    /// A new frame with the page, which may have to be read and so sleep.
+    /// The entry may be shared after fork, so the frame is never handed out
+    /// itself.
+    pub fn read_in(&self) -> Option<FrameTracker> {
+        let frame = frame_alloc()?;
+        let data = frame.ppn.get_bytes_array();
+        if let Some(cached) = self.cache.exclusive_access().as_ref() {
+            data.copy_from_slice(cached.ppn.get_bytes_array());
+            return Some(frame);
+        }
+        let disk = BLOCK_DEVICE.as_ref().unwrap();
+        for (i, block) in data.chunks_exact_mut(BLOCK_SZ).enumerate() {
+            disk.read_block(self.first_block() + i, block);
+        }
+        Some(frame)
+    }
+}
+
+impl Drop for SwapEntry {
+    fn drop(&mut self) {
+        SWAP_AREA.exclusive_access().recycled.push(self.slot);
+    }
+}
@@ -0,0 +1,162 @@
+//! TLB maintenance: address space identifiers and batched flushes.
+//!
+//! Each user address space is tagged with an ASID, so that its translations
+//! may stay in the TLB while others run and switching to it flushes
+//! nothing. In exchange every change to a page table has to be flushed: the
+//! page table collects the pages it changed in a `TlbBatch`, which is then
+//! flushed page by page, or the whole ASID if there are many.
+//!
+//! Flushes are local to this hart, as the kernel runs on the boot hart
+//! alone: no other hart may hold a translation to shoot down. Once tasks
+//! run on several harts, a flush will have to reach those which used the
+//! ASID, and wait for them before unmapped frames are freed.
+//!
+//! The kernel space has ASID 0, which user spaces get as well when the
+//! hardware has no ASIDs or they have run out. Such untagged user spaces
+//! are flushed whole whenever this hart enters or leaves them.
+
+use super::{VirtAddr, VirtPageNum};
+use crate::sync::UPIntrFreeCell;
+use alloc::vec::Vec;
+use core::arch::asm;
+use lazy_static::*;
+use riscv::register::satp;
+
+/// Pages flushed one by one, beyond which the whole ASID is flushed.
+const FLUSH_BATCH: usize = 16;
+
+pub const ASID_SHIFT: usize = 44;
+pub const ASID_MASK: usize = 0xffff;
+
+/// Changes to a page table not flushed yet.
+pub struct TlbBatch {
+    pages: [VirtPageNum; FLUSH_BATCH],
+    count: usize,
+    /// too many pages, or a page table freed
+    all: bool,
+}
+
+impl TlbBatch {
+    pub fn new() -> Self {
+        Self {
+            pages: [VirtPageNum(0); FLUSH_BATCH],
+            count: 0,
+            all: false,
+        }
+    }
+    /// A mapping of `vpn` changed, came or went away.
+    pub fn add(&mut self, vpn: VirtPageNum) {
+        if self.count == FLUSH_BATCH {
+            self.all = true;
+        } else {
+            self.pages[self.count] = vpn;
+            self.count += 1;
+        }
+    }
+    /// A table was freed, whose entries may be cached for any address.
+    pub fn add_all(&mut self) {
+        self.all = true;
+    }
+    fn clear(&mut self) {
+        self.count = 0;
+        self.all = false;
+    }
+}
+
+fn local_flush_all() {
+    unsafe {
+        asm!("sfence.vma");
+    }
+}
+
+fn local_flush_asid(asid: usize) {
+    unsafe {
+        asm!("sfence.vma zero, {}", in(reg) asid);
+    }
+}
+
+fn local_flush_page(asid: usize, vpn: VirtPageNum) {
+    let va: VirtAddr = vpn.into();
+    unsafe {
+        asm!("sfence.vma {}, {}", in(reg) va.0, in(reg) asid);
+    }
+}
+
+/// Flush `batch` of the address space `asid` from the TLB of this hart.
+pub fn flush(asid: usize, batch: &mut TlbBatch) {
+    if batch.all {
+        local_flush_asid(asid);
+    } else {
+        for vpn in batch.pages[..batch.count].iter() {
+            local_flush_page(asid, *vpn);
+        }
+    }
+    batch.clear();
+}
+
+/// This hart is about to run in the address space `asid`.
+pub fn switch_to(asid: usize) {
+    if asid == 0 {
+        local_flush_all();
+    }
+}
+
+/// The hart running this code. Only hart 0 runs the kernel for now.
+pub fn hart_id() -> usize {
+    0
+}
+
+struct AsidAllocator {
+    current: usize,
+    /// ASIDs the hardware has
+    limit: usize,
+    recycled: Vec<usize>,
+}
+
+lazy_static! {
+    static ref ASID_ALLOCATOR: UPIntrFreeCell<AsidAllocator> = unsafe {
+        UPIntrFreeCell::new(AsidAllocator {
+            current: 1,
+            limit: 1,
+            recycled: Vec::new(),
+        })
+    };
+}
+
+/// A free ASID, or 0 if there is none.
+pub fn asid_alloc() -> usize {
+    let mut allocator = ASID_ALLOCATOR.exclusive_access();
+    if let Some(asid) = allocator.recycled.pop() {
+        return asid;
+    }
+    if allocator.current == allocator.limit {
+        return 0;
+    }
+    allocator.current += 1;
+    allocator.current - 1
+}
+
+/// Give `asid` back once it is flushed, lest the next user of it see
+/// translations of this one.
+pub fn asid_dealloc(asid: usize) {
+    let mut batch = TlbBatch::new();
+    batch.add_all();
+    flush(asid, &mut batch);
+    if asid != 0 {
+        ASID_ALLOCATOR.exclusive_access().recycled.push(asid);
+    }
+}
+
+/// Find out how many ASIDs the hardware has, by writing all ones to the
+/// field with paging on and reading back what stuck.
+pub fn init() {
+    let old = satp::read().bits();
+    let bits = unsafe {
+        satp::write(old | ASID_MASK << ASID_SHIFT);
+        let bits = (satp::read().bits() >> ASID_SHIFT & ASID_MASK).count_ones();
+        satp::write(old);
+        bits
+    };
+    local_flush_all();
+    ASID_ALLOCATOR.exclusive_access().limit = 1 << bits;
+}
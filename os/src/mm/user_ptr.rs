@@ -0,0 +1,180 @@
+//! Checked access to user memory for system calls.
+//!
+//! Every page touched through `UserPtr` or `UserSlice` must be mapped for
+//! the user with the permission needed, lazy and copy-on-write pages being
+//! faulted in first the way the user would. Anything else fails with None,
+//! which system calls return as EFAULT, instead of panicking.
+//!
+//! Faulting a page in may sleep, so nothing may be locked meanwhile. The
+//! pages are pinned as they are found, lest they be reclaimed while the
+//! next ones are faulted in or a system call sleeps on them.
+
+use super::frame_allocator::{pin_frame, PinnedFrame};
+use super::{PageTable, PageTableEntry, PhysPageNum, UserBuffer, VirtAddr, VirtPageNum};
+use crate::config::{MMAP_END, PAGE_SIZE};
+use crate::task::handle_page_fault;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::mem::{size_of, MaybeUninit};
+use core::slice;
+
+/// The frame behind `vpn` if the user may read it, or write it if `write`.
+fn user_page(page_table: &PageTable, vpn: VirtPageNum, write: bool) -> Option<PhysPageNum> {
+    let accessible = |pte: &PageTableEntry| {
+        pte.is_valid()
+            && pte.is_user()
+            && if write {
+                pte.writable()
+            } else {
+                pte.readable()
+            }
+    };
+    if let Some(pte) = page_table.translate(vpn).filter(|pte| accessible(pte)) {
+        return Some(pte.ppn());
+    }
+    let va: VirtAddr = vpn.into();
+    if !handle_page_fault(page_table.token(), va.into(), write) {
+        return None;
+    }
+    page_table
+        .translate(vpn)
+        .filter(|pte| accessible(pte))
+        .map(|pte| pte.ppn())
+}
+
+/// Pieces of user memory and the pins keeping them in place.
+type PinnedBytes = (Vec<&'static mut [u8]>, Vec<PinnedFrame>);
+
+/// The user bytes `[start, start + len)`, in pieces ending at page ends.
+fn user_bytes(token: usize, start: usize, len: usize, write: bool) -> Option<PinnedBytes> {
+    // only the lower half of the address space belongs to the user
+    let end = start.checked_add(len)?;
+    if end > MMAP_END {
+        return None;
+    }
+    let page_table = PageTable::from_token(token);
+    let mut pieces = Vec::new();
+    let mut pins = Vec::new();
+    let mut start = start;
+    while start < end {
+        let start_va = VirtAddr::from(start);
+        let ppn = user_page(&page_table, start_va.floor(), write)?;
+        pins.extend(pin_frame(ppn));
+        let offset = start_va.page_offset();
+        let piece_len = (PAGE_SIZE - offset).min(end - start);
+        pieces.push(&mut ppn.get_bytes_array()[offset..offset + piece_len]);
+        start += piece_len;
+    }
+    Some((pieces, pins))
+}
+
+/// `len` bytes at `ptr` in the address space `token`.
+pub struct UserSlice {
+    token: usize,
+    ptr: usize,
+    len: usize,
+}
+
+impl UserSlice {
+    pub fn new(token: usize, ptr: *const u8, len: usize) -> Self {
+        Self {
+            token,
+            ptr: ptr as usize,
+            len,
+        }
+    }
+    /// Fill `dst` from the start of the slice.
+    pub fn copy_from_user(&self, dst: &mut [u8]) -> Option<()> {
+        let len = dst.len().min(self.len);
+        let mut copied = 0;
+        let (pieces, _pins) = user_bytes(self.token, self.ptr, len, false)?;
+        for piece in pieces {
+            dst[copied..copied + piece.len()].copy_from_slice(piece);
+            copied += piece.len();
+        }
+        Some(())
+    }
+    /// Write `src` to the start of the slice.
+    pub fn copy_to_user(&self, src: &[u8]) -> Option<()> {
+        let len = src.len().min(self.len);
+        let mut copied = 0;
+        let (pieces, _pins) = user_bytes(self.token, self.ptr, len, true)?;
+        for piece in pieces {
+            piece.copy_from_slice(&src[copied..copied + piece.len()]);
+            copied += piece.len();
+        }
+        Some(())
+    }
+    /// The whole slice for a file to read into if `write`, or to write out
+    /// otherwise, pinned until the buffer is dropped.
+    pub fn buffer(&self, write: bool) -> Option<UserBuffer> {
+        let (pieces, pins) = user_bytes(self.token, self.ptr, self.len, write)?;
+        Some(UserBuffer::new(pieces, pins))
+    }
+}
+
+/// A `T` at `ptr` in the address space `token`, which need not be aligned.
+pub struct UserPtr<T> {
+    token: usize,
+    ptr: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Copy> UserPtr<T> {
+    pub fn new(token: usize, ptr: *const T) -> Self {
+        Self {
+            token,
+            ptr: ptr as usize,
+            _marker: PhantomData,
+        }
+    }
+    pub fn is_null(&self) -> bool {
+        self.ptr == 0
+    }
+    /// The `count`th `T` after this one.
+    pub fn add(&self, count: usize) -> Self {
+        Self {
+            token: self.token,
+            ptr: self.ptr.wrapping_add(count * size_of::<T>()),
+            _marker: PhantomData,
+        }
+    }
+    fn as_slice(&self) -> UserSlice {
+        UserSlice::new(self.token, self.ptr as *const u8, size_of::<T>())
+    }
+    pub fn read(&self) -> Option<T> {
+        let mut value = MaybeUninit::<T>::uninit();
+        let dst =
+            unsafe { slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, size_of::<T>()) };
+        self.as_slice().copy_from_user(dst)?;
+        Some(unsafe { value.assume_init() })
+    }
+    pub fn write(&self, value: T) -> Option<()> {
+        let src = unsafe { slice::from_raw_parts(&value as *const T as *const u8, size_of::<T>()) };
+        self.as_slice().copy_to_user(src)
+    }
+}
+
+impl UserPtr<u8> {
+    /// The string starting here up to a `\0`, which is left out.
+    pub fn read_str(&self) -> Option<String> {
+        let page_table = PageTable::from_token(self.token);
+        let mut string = String::new();
+        let mut va = self.ptr;
+        loop {
+            if va >= MMAP_END {
+                return None;
+            }
+            let start_va = VirtAddr::from(va);
+            let ppn = user_page(&page_table, start_va.floor(), false)?;
+            for ch in ppn.get_bytes_array()[start_va.page_offset()..].iter() {
+                if *ch == 0 {
+                    return Some(string);
+                }
+                string.push(*ch as char);
+            }
+            va += PAGE_SIZE - start_va.page_offset();
+        }
+    }
+}
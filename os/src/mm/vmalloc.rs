@@ -0,0 +1,85 @@
+//! Kernel virtual memory for buffers which need not be physically
+//! contiguous, like kernel stacks.
+//!
+//! Each buffer is backed by frames from anywhere, mapped one after the
+//! other in the vmalloc region of the kernel space and followed by an
+//! unmapped guard page, so that running off either end faults instead of
+//! reaching another buffer.
+
+use super::{frame_alloc, MapPermission, VirtAddr, VirtPageNum, KERNEL_SPACE};
+use crate::config::{PAGE_SIZE, VMALLOC_END, VMALLOC_START};
+use crate::sync::UPIntrFreeCell;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use lazy_static::*;
+
+/// Ranges of the vmalloc region in use, as their first page and their
+/// number of pages, guard page included.
+struct VmallocRegion {
+    used: BTreeMap<VirtPageNum, usize>,
+}
+
+impl VmallocRegion {
+    /// The first free range of `pages` pages.
+    fn alloc(&mut self, pages: usize) -> Option<VirtPageNum> {
+        let mut start = VirtAddr::from(VMALLOC_START).floor();
+        for (used_start, used_pages) in self.used.iter() {
+            if start.0 + pages <= used_start.0 {
+                break;
+            }
+            start = VirtPageNum(used_start.0 + used_pages);
+        }
+        if start.0 + pages > VirtAddr::from(VMALLOC_END).floor().0 {
+            return None;
+        }
+        self.used.insert(start, pages);
+        Some(start)
+    }
+}
+
+lazy_static! {
+    static ref VMALLOC_REGION: UPIntrFreeCell<VmallocRegion> = unsafe {
+        UPIntrFreeCell::new(VmallocRegion {
+            used: BTreeMap::new(),
+        })
+    };
+}
+
+/// A kernel buffer mapped in the vmalloc region, unmapped when dropped.
+pub struct VmBuffer {
+    start: VirtPageNum,
+    pages: usize,
+}
+
+impl VmBuffer {
+    pub fn start(&self) -> usize {
+        VirtAddr::from(self.start).into()
+    }
+    pub fn len(&self) -> usize {
+        self.pages * PAGE_SIZE
+    }
+}
+
+impl Drop for VmBuffer {
+    fn drop(&mut self) {
+        KERNEL_SPACE
+            .exclusive_access()
+            .remove_area_with_start_vpn(self.start);
+        VMALLOC_REGION.exclusive_access().used.remove(&self.start);
+    }
+}
+
+/// Map `size` bytes of zeroed memory, rounded up to whole pages, or return
+/// None if there are not enough frames or room left.
+pub fn vmalloc(size: usize) -> Option<VmBuffer> {
+    let pages = (size + PAGE_SIZE - 1) / PAGE_SIZE;
+    let mut frames = Vec::with_capacity(pages);
+    for _ in 0..pages {
+        frames.push(frame_alloc()?);
+    }
+    let start = VMALLOC_REGION.exclusive_access().alloc(pages + 1)?;
+    let mut kernel_space = KERNEL_SPACE.exclusive_access();
+    kernel_space.insert_frames(start, frames, MapPermission::R | MapPermission::W);
+    kernel_space.flush_tlb();
+    Some(VmBuffer { start, pages })
+}
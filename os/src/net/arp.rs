@@ -0,0 +1,55 @@
+//! The ARP cache: the MAC address of each neighbour, learnt from the
+//! frames it sends and kept for ARP_CACHE_NS, for frames to it not to be
+//! broadcast.
+
+use crate::sync::UPIntrFreeCell;
+use crate::timer::{ktime_now, NSEC_PER_SEC};
+use alloc::vec::Vec;
+use lazy_static::*;
+use lose_net_stack::{IPv4, MacAddress};
+
+/// How long an address is kept after it was last seen.
+const ARP_CACHE_NS: u64 = 60 * NSEC_PER_SEC;
+
+const BROADCAST: MacAddress = MacAddress::new([0xff; 6]);
+
+struct ArpEntry {
+    ip: IPv4,
+    mac: MacAddress,
+    expire_ns: u64,
+}
+
+lazy_static! {
+    static ref ARP_CACHE: UPIntrFreeCell<Vec<ArpEntry>> =
+        unsafe { UPIntrFreeCell::new(Vec::new()) };
+}
+
+/// `ip` was seen sending from `mac`.
+pub fn learn(ip: IPv4, mac: MacAddress) {
+    let expire_ns = ktime_now() + ARP_CACHE_NS;
+    let mut cache = ARP_CACHE.exclusive_access();
+    match cache.iter_mut().find(|entry| entry.ip == ip) {
+        Some(entry) => {
+            entry.mac = mac;
+            entry.expire_ns = expire_ns;
+        }
+        None => cache.push(ArpEntry { ip, mac, expire_ns }),
+    }
+}
+
+/// The MAC address of `ip`, broadcast if it is not known.
+pub fn resolve(ip: IPv4) -> MacAddress {
+    ARP_CACHE
+        .exclusive_access()
+        .iter()
+        .find(|entry| entry.ip == ip)
+        .map_or(BROADCAST, |entry| entry.mac)
+}
+
+/// Forget the addresses not seen for ARP_CACHE_NS by `now`, in ns; when
+/// the next one is to be, if any is left.
+pub fn expire(now: u64) -> Option<u64> {
+    let mut cache = ARP_CACHE.exclusive_access();
+    cache.retain(|entry| entry.expire_ns > now);
+    cache.iter().map(|entry| entry.expire_ns).min()
+}
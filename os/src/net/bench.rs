@@ -0,0 +1,310 @@
+//! netbench, a kernel thread measuring what the stack and the card carry
+//! with nobody in user space in the way: as a sink it takes in what a peer
+//! sends to a port, as a source it sends to a peer for a while, over TCP
+//! or UDP. sys_netbench starts a run, and /proc/netbench tells how the
+//! last one went. It is there with the netbench feature only.
+//!
+//! The sockets are those of user space, without O_NONBLOCK being of no use
+//! to a kernel thread: netbench gives the others a turn whenever its
+//! socket has to wait.
+
+use super::inet::{InetSocket, SockType};
+use super::udp::UDP_MAX_PAYLOAD;
+use super::IPv4;
+use crate::fs::OpenFlags;
+use crate::sync::UPIntrFreeCell;
+use crate::syscall::{EAGAIN, EALREADY, EBUSY, EINPROGRESS, EINTR};
+use crate::task::{
+    block_current_task, schedule, spawn_kernel_thread, suspend_current_and_run_next, wakeup_task,
+    TaskControlBlock,
+};
+use crate::timer::get_time_ms;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use core::fmt::Write;
+use lazy_static::*;
+use riscv::register::sstatus;
+
+/// How much a source hands the stack at a time.
+const CHUNK: usize = 16384;
+/// How long a UDP sink waits for more once datagrams stop coming.
+const IDLE_MS: usize = 1000;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum BenchMode {
+    TcpSink,
+    TcpSource,
+    UdpSink,
+    UdpSource,
+}
+
+impl BenchMode {
+    fn name(&self) -> &'static str {
+        match self {
+            BenchMode::TcpSink => "tcp sink",
+            BenchMode::TcpSource => "tcp source",
+            BenchMode::UdpSink => "udp sink",
+            BenchMode::UdpSource => "udp source",
+        }
+    }
+}
+
+/// A run: a sink takes on the port of `addr`, a source sends to `addr`,
+/// each for `secs` seconds at most from the first byte.
+#[derive(Copy, Clone)]
+pub struct BenchRun {
+    pub mode: BenchMode,
+    pub addr: (IPv4, u16),
+    pub secs: usize,
+}
+
+/// What a run carried, from its first byte to its last.
+#[derive(Copy, Clone, Default)]
+struct Counter {
+    bytes: usize,
+    packets: usize,
+    start_ms: Option<usize>,
+    end_ms: usize,
+}
+
+impl Counter {
+    fn count(&mut self, len: usize) {
+        let now = get_time_ms();
+        self.start_ms.get_or_insert(now);
+        self.end_ms = now;
+        self.bytes += len;
+        self.packets += 1;
+    }
+
+    fn elapsed_ms(&self) -> usize {
+        self.start_ms.map_or(0, |start| get_time_ms() - start)
+    }
+
+    fn ms(&self) -> usize {
+        self.start_ms.map_or(0, |start| self.end_ms - start)
+    }
+}
+
+struct Netbench {
+    task: Option<Arc<TaskControlBlock>>,
+    sleeping: bool,
+    /// The run asked for, until netbench takes it.
+    request: Option<BenchRun>,
+    /// The run going on, or the last one.
+    run: Option<BenchRun>,
+    running: bool,
+    /// The run is asked to stop.
+    stop: bool,
+    counter: Counter,
+    /// How the last run ended, if with an error.
+    error: Option<isize>,
+}
+
+lazy_static! {
+    static ref NETBENCH: UPIntrFreeCell<Netbench> = unsafe {
+        UPIntrFreeCell::new(Netbench {
+            task: None,
+            sleeping: false,
+            request: None,
+            run: None,
+            running: false,
+            stop: false,
+            counter: Counter::default(),
+            error: None,
+        })
+    };
+}
+
+pub fn start_netbench() {
+    NETBENCH.exclusive_access().task = Some(spawn_kernel_thread("netbench", netbench));
+}
+
+/// Have netbench do `run`; EBUSY while it does another.
+pub fn netbench_start(run: BenchRun) -> Result<(), isize> {
+    let mut bench = NETBENCH.exclusive_access();
+    if bench.running || bench.request.is_some() {
+        return Err(EBUSY);
+    }
+    bench.request = Some(run);
+    bench.stop = false;
+    if bench.sleeping {
+        bench.sleeping = false;
+        wakeup_task(Arc::clone(bench.task.as_ref().unwrap()));
+    }
+    Ok(())
+}
+
+/// End the run going on, if any, as though its time was up.
+pub fn netbench_stop() {
+    NETBENCH.exclusive_access().stop = true;
+}
+
+/// How the last run went, for /proc/netbench.
+pub fn netbench_report() -> String {
+    let bench = NETBENCH.exclusive_access();
+    let run = match bench.run {
+        Some(run) => run,
+        None => return String::from("idle\n"),
+    };
+    let counter = bench.counter;
+    let ms = counter.ms();
+    let mut text = String::new();
+    let state = if bench.running { "running" } else { "done" };
+    writeln!(text, "{}: {}", run.mode.name(), state).unwrap();
+    writeln!(text, "bytes: {}", counter.bytes).unwrap();
+    writeln!(text, "packets: {}", counter.packets).unwrap();
+    writeln!(text, "ms: {}", ms).unwrap();
+    writeln!(text, "KiB/s: {}", counter.bytes * 1000 / 1024 / ms.max(1)).unwrap();
+    if let Some(errno) = bench.error {
+        writeln!(text, "error: {}", errno).unwrap();
+    }
+    text
+}
+
+/// Give netd and the others a turn; false once the run is to stop.
+fn pause() -> bool {
+    suspend_current_and_run_next();
+    !NETBENCH.exclusive_access().stop
+}
+
+/// Count `len` bytes carried; false once the time of the run is up.
+fn count(len: usize, secs: usize) -> bool {
+    let mut bench = NETBENCH.exclusive_access();
+    bench.counter.count(len);
+    bench.counter.elapsed_ms() < secs * 1000
+}
+
+fn time_up(secs: usize) -> bool {
+    NETBENCH.exclusive_access().counter.elapsed_ms() >= secs * 1000
+}
+
+fn socket(kind: SockType) -> Arc<InetSocket> {
+    InetSocket::new(kind, OpenFlags::NONBLOCK)
+}
+
+fn tcp_sink(port: u16, secs: usize) -> Result<(), isize> {
+    let listener = socket(SockType::Stream);
+    listener.bind(port)?;
+    listener.listen(1)?;
+    let conn = loop {
+        match listener.accept(OpenFlags::NONBLOCK) {
+            Ok(conn) => break conn,
+            Err(EAGAIN) if pause() => {}
+            Err(EAGAIN) => return Err(EINTR),
+            Err(errno) => return Err(errno),
+        }
+    };
+    loop {
+        match conn.recv_from(CHUNK) {
+            // the peer is done
+            Ok((data, _, _)) if data.is_empty() => return Ok(()),
+            Ok((data, _, _)) if count(data.len(), secs) => {}
+            Ok(_) => return Ok(()),
+            Err(EAGAIN) if pause() && !time_up(secs) => {}
+            Err(EAGAIN) => return Ok(()),
+            Err(errno) => return Err(errno),
+        }
+    }
+}
+
+fn tcp_source((raddr, rport): (IPv4, u16), secs: usize) -> Result<(), isize> {
+    let conn = socket(SockType::Stream);
+    loop {
+        match conn.connect(raddr, rport) {
+            Ok(()) => break,
+            Err(EINPROGRESS | EALREADY) if pause() => {}
+            Err(EINPROGRESS | EALREADY) => return Err(EINTR),
+            Err(errno) => return Err(errno),
+        }
+    }
+    let data = vec![0u8; CHUNK];
+    loop {
+        match conn.send_to(&data, None) {
+            Ok(len) if count(len, secs) => {}
+            Ok(_) => return Ok(()),
+            Err(EAGAIN) if pause() && !time_up(secs) => {}
+            Err(EAGAIN) => return Ok(()),
+            Err(errno) => return Err(errno),
+        }
+    }
+}
+
+fn udp_sink(port: u16, secs: usize) -> Result<(), isize> {
+    let sink = socket(SockType::Dgram);
+    sink.bind(port)?;
+    let mut last_ms = None;
+    loop {
+        match sink.recv_from(UDP_MAX_PAYLOAD) {
+            Ok((data, _, _)) if count(data.len(), secs) => last_ms = Some(get_time_ms()),
+            Ok(_) => return Ok(()),
+            Err(EAGAIN) => {
+                let idle = last_ms.map_or(false, |last| get_time_ms() - last >= IDLE_MS);
+                if !pause() || idle || time_up(secs) {
+                    return Ok(());
+                }
+            }
+            Err(errno) => return Err(errno),
+        }
+    }
+}
+
+fn udp_source((raddr, rport): (IPv4, u16), secs: usize) -> Result<(), isize> {
+    let source = socket(SockType::Dgram);
+    source.connect(raddr, rport)?;
+    let data = vec![0u8; UDP_MAX_PAYLOAD];
+    loop {
+        let len = source.send_to(&data, None)?;
+        // the datagrams are queued as fast as they are made, so netd has to
+        // take them as they go
+        if !count(len, secs) || !pause() {
+            return Ok(());
+        }
+    }
+}
+
+fn netbench() -> ! {
+    unsafe {
+        sstatus::set_sie();
+    }
+    loop {
+        let task_cx_ptr = NETBENCH.exclusive_session(|bench| {
+            if bench.request.is_some() {
+                return None;
+            }
+            bench.sleeping = true;
+            Some(block_current_task())
+        });
+        if let Some(task_cx_ptr) = task_cx_ptr {
+            schedule(task_cx_ptr);
+            continue;
+        }
+        let run = NETBENCH.exclusive_session(|bench| {
+            let run = bench.request.take().unwrap();
+            bench.run = Some(run);
+            bench.running = true;
+            bench.counter = Counter::default();
+            bench.error = None;
+            run
+        });
+        let result = match run.mode {
+            BenchMode::TcpSink => tcp_sink(run.addr.1, run.secs),
+            BenchMode::TcpSource => tcp_source(run.addr, run.secs),
+            BenchMode::UdpSink => udp_sink(run.addr.1, run.secs),
+            BenchMode::UdpSource => udp_source(run.addr, run.secs),
+        };
+        let mut bench = NETBENCH.exclusive_access();
+        bench.running = false;
+        bench.error = result.err();
+        let counter = bench.counter;
+        drop(bench);
+        let ms = counter.ms().max(1);
+        println!(
+            "[kernel] netbench: {}: {} bytes in {} ms, {} KiB/s",
+            run.mode.name(),
+            counter.bytes,
+            ms,
+            counter.bytes * 1000 / 1024 / ms
+        );
+    }
+}
@@ -0,0 +1,330 @@
+//! The DHCP client of eth0, which netd runs from boot: it broadcasts
+//! DISCOVER, asks for the first address offered with REQUEST, and gives
+//! eth0 the address, netmask and gateway of the ACK and the resolver its
+//! nameserver, asking again when half of the lease is gone. Until then,
+//! or if nobody answers, eth0 keeps what it had; an address set by hand
+//! stops the client.
+//!
+//! The replies are broadcast, so they are taken before lose-net-stack,
+//! which only takes what is for our address.
+
+use super::dns::set_nameserver;
+use super::frame::{be16, push_udp, ETH_HLEN, ETH_P_IP, IPPROTO_UDP};
+use super::iface::{interface, our_mac, route, IfConfig, BROADCAST_IP};
+use super::netd::wakeup_netd;
+use crate::drivers::Mbuf;
+use crate::sync::UPIntrFreeCell;
+use crate::timer::{get_time, ktime_now, NSEC_PER_MSEC, NSEC_PER_SEC};
+use alloc::vec::Vec;
+use lazy_static::*;
+use lose_net_stack::IPv4;
+
+const CLIENT_PORT: u16 = 68;
+const SERVER_PORT: u16 = 67;
+
+const BOOTREQUEST: u8 = 1;
+const BOOTREPLY: u8 = 2;
+/// Ask for the reply to be broadcast, having no address yet.
+const FLAG_BROADCAST: u16 = 0x8000;
+/// Where the options start, after the fixed fields and the magic cookie.
+const OPTIONS: usize = 240;
+const MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+
+const DHCPDISCOVER: u8 = 1;
+const DHCPOFFER: u8 = 2;
+const DHCPREQUEST: u8 = 3;
+const DHCPACK: u8 = 5;
+const DHCPNAK: u8 = 6;
+
+const OPT_PAD: u8 = 0;
+const OPT_SUBNET_MASK: u8 = 1;
+const OPT_ROUTER: u8 = 3;
+const OPT_DNS: u8 = 6;
+const OPT_REQUESTED_IP: u8 = 50;
+const OPT_LEASE_TIME: u8 = 51;
+const OPT_MESSAGE_TYPE: u8 = 53;
+const OPT_SERVER_ID: u8 = 54;
+const OPT_PARAMS: u8 = 55;
+const OPT_END: u8 = 255;
+
+/// How long the first message waits for an answer, doubled each time it
+/// is sent again.
+const RETRY_NS: u64 = 1000 * NSEC_PER_MSEC;
+/// How many times a message is sent before the client gives up.
+const MAX_TRIES: usize = 4;
+/// The lease taken if the server tells none.
+const DEFAULT_LEASE_SECS: u32 = 3600;
+
+const ANY: IPv4 = IPv4::new(0, 0, 0, 0);
+
+/// What `dhcp_state` tells of the client.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum DhcpState {
+    Off,
+    /// Asking for an address, eth0 keeping its own meanwhile.
+    Asking,
+    Bound,
+}
+
+#[derive(Copy, Clone)]
+enum State {
+    Off,
+    Selecting,
+    Requesting { ip: IPv4, server: IPv4 },
+    Bound { ip: IPv4, server: IPv4 },
+}
+
+struct Dhcp {
+    state: State,
+    xid: u32,
+    /// Times the message of the state was sent.
+    tries: usize,
+    /// When to send it again, or ask for the lease to go on.
+    deadline: Option<u64>,
+}
+
+lazy_static! {
+    static ref DHCP: UPIntrFreeCell<Dhcp> = unsafe {
+        UPIntrFreeCell::new(Dhcp {
+            state: State::Off,
+            xid: 0,
+            tries: 0,
+            deadline: None,
+        })
+    };
+}
+
+impl Dhcp {
+    /// Send the first message of `state` when netd next looks.
+    fn enter(&mut self, state: State) {
+        self.state = state;
+        self.xid = get_time() as u32;
+        self.tries = 0;
+        self.deadline = Some(ktime_now());
+    }
+}
+
+/// Ask for an address for eth0 anew, if there is the card.
+pub fn dhcp_start() {
+    if interface("eth0").is_none() {
+        return;
+    }
+    DHCP.exclusive_access().enter(State::Selecting);
+    wakeup_netd();
+}
+
+pub fn dhcp_stop() {
+    let mut dhcp = DHCP.exclusive_access();
+    dhcp.state = State::Off;
+    dhcp.deadline = None;
+}
+
+pub fn dhcp_state() -> DhcpState {
+    match DHCP.exclusive_access().state {
+        State::Off => DhcpState::Off,
+        State::Selecting | State::Requesting { .. } => DhcpState::Asking,
+        State::Bound { .. } => DhcpState::Bound,
+    }
+}
+
+/// Send what is due by `now`, in ns; when something is next, if anything
+/// is.
+pub fn poll(now: u64) -> Option<u64> {
+    let mut dhcp = DHCP.exclusive_access();
+    match dhcp.deadline {
+        Some(deadline) if deadline <= now => {}
+        deadline => return deadline,
+    }
+    if let State::Bound { ip, server } = dhcp.state {
+        dhcp.enter(State::Requesting { ip, server });
+    }
+    if dhcp.tries == MAX_TRIES {
+        // eth0 keeps the address it has
+        dhcp.state = State::Off;
+        dhcp.deadline = None;
+        return None;
+    }
+    let message = match dhcp.state {
+        State::Selecting => message(DHCPDISCOVER, dhcp.xid, None),
+        State::Requesting { ip, server } => message(DHCPREQUEST, dhcp.xid, Some((ip, server))),
+        _ => unreachable!(),
+    };
+    dhcp.deadline = Some(now + (RETRY_NS << dhcp.tries));
+    dhcp.tries += 1;
+    let deadline = dhcp.deadline;
+    drop(dhcp);
+    send(&message);
+    deadline
+}
+
+/// A message of `kind`, asking for `ip` of `server` if given.
+fn message(kind: u8, xid: u32, request: Option<(IPv4, IPv4)>) -> Vec<u8> {
+    let mut message = Vec::with_capacity(300);
+    message.extend_from_slice(&[BOOTREQUEST, 1, 6, 0]);
+    message.extend_from_slice(&xid.to_be_bytes());
+    message.extend_from_slice(&[0, 0]);
+    message.extend_from_slice(&FLAG_BROADCAST.to_be_bytes());
+    // ciaddr, yiaddr, siaddr and giaddr
+    message.resize(28, 0);
+    message.extend_from_slice(&our_mac());
+    // the rest of chaddr, sname and file
+    message.resize(236, 0);
+    message.extend_from_slice(&MAGIC_COOKIE);
+    message.extend_from_slice(&[OPT_MESSAGE_TYPE, 1, kind]);
+    if let Some((ip, server)) = request {
+        message.extend_from_slice(&[OPT_REQUESTED_IP, 4]);
+        message.extend_from_slice(&ip.to_u32().to_be_bytes());
+        message.extend_from_slice(&[OPT_SERVER_ID, 4]);
+        message.extend_from_slice(&server.to_u32().to_be_bytes());
+    }
+    message.extend_from_slice(&[
+        OPT_PARAMS,
+        4,
+        OPT_SUBNET_MASK,
+        OPT_ROUTER,
+        OPT_DNS,
+        OPT_LEASE_TIME,
+    ]);
+    message.push(OPT_END);
+    // as long as a BOOTP message, for the servers which want that
+    message.resize(300, OPT_PAD);
+    message
+}
+
+/// Broadcast `message` from no address.
+fn send(message: &[u8]) {
+    let iface = match route(BROADCAST_IP) {
+        Some(iface) => iface,
+        None => return,
+    };
+    let mut packet = match Mbuf::from_slice(message) {
+        Some(packet) => packet,
+        None => return,
+    };
+    let dest_mac = iface.resolve(BROADCAST_IP);
+    push_udp(&mut packet, ANY, CLIENT_PORT, BROADCAST_IP, SERVER_PORT, dest_mac);
+    iface.transmit(packet);
+}
+
+fn ip_at(bytes: &[u8]) -> IPv4 {
+    IPv4::new(bytes[0], bytes[1], bytes[2], bytes[3])
+}
+
+/// The DHCP message in `frame`, if it is a UDP datagram to the client.
+fn dhcp_message(frame: &[u8]) -> Option<&[u8]> {
+    if frame.len() < ETH_HLEN + 20 || be16(&frame[12..14]) != ETH_P_IP {
+        return None;
+    }
+    let header = &frame[ETH_HLEN..];
+    let ihl = (header[0] & 0xf) as usize * 4;
+    if header[9] != IPPROTO_UDP || header.len() < ihl + 8 {
+        return None;
+    }
+    let udp = &header[ihl..];
+    if be16(&udp[2..4]) != CLIENT_PORT {
+        return None;
+    }
+    let len = (be16(&udp[4..6]) as usize).clamp(8, udp.len());
+    Some(&udp[8..len])
+}
+
+/// The options of a reply to us: the message type, netmask, router,
+/// nameserver, lease time and server.
+struct Options {
+    kind: u8,
+    netmask: Option<IPv4>,
+    router: Option<IPv4>,
+    dns: Option<IPv4>,
+    lease_secs: Option<u32>,
+    server: Option<IPv4>,
+}
+
+fn options(mut bytes: &[u8]) -> Options {
+    let mut options = Options {
+        kind: 0,
+        netmask: None,
+        router: None,
+        dns: None,
+        lease_secs: None,
+        server: None,
+    };
+    while let Some((&code, rest)) = bytes.split_first() {
+        match code {
+            OPT_PAD => {
+                bytes = rest;
+                continue;
+            }
+            OPT_END => break,
+            _ => {}
+        }
+        let len = match rest.first() {
+            Some(&len) if rest.len() > len as usize => len as usize,
+            _ => break,
+        };
+        let value = &rest[1..1 + len];
+        match (code, len) {
+            (OPT_MESSAGE_TYPE, 1) => options.kind = value[0],
+            (OPT_SUBNET_MASK, 4) => options.netmask = Some(ip_at(value)),
+            (OPT_ROUTER, 4..) if len % 4 == 0 => options.router = Some(ip_at(value)),
+            (OPT_DNS, 4..) if len % 4 == 0 => options.dns = Some(ip_at(value)),
+            (OPT_LEASE_TIME, 4) => {
+                options.lease_secs =
+                    Some(u32::from_be_bytes([value[0], value[1], value[2], value[3]]))
+            }
+            (OPT_SERVER_ID, 4) => options.server = Some(ip_at(value)),
+            _ => {}
+        }
+        bytes = &rest[1 + len..];
+    }
+    options
+}
+
+/// Take in `frame` if it is for the client; whether it was.
+pub fn receive(frame: &[u8]) -> bool {
+    let message = match dhcp_message(frame) {
+        Some(message) => message,
+        None => return false,
+    };
+    let mut dhcp = DHCP.exclusive_access();
+    if message.len() < OPTIONS
+        || message[0] != BOOTREPLY
+        || message[4..8] != dhcp.xid.to_be_bytes()
+        || message[28..34] != our_mac()
+        || message[236..240] != MAGIC_COOKIE
+    {
+        return true;
+    }
+    let yiaddr = ip_at(&message[16..20]);
+    let options = options(&message[OPTIONS..]);
+    match (dhcp.state, options.kind) {
+        (State::Selecting, DHCPOFFER) => {
+            let server = options.server.unwrap_or_else(|| ip_at(&message[20..24]));
+            dhcp.enter(State::Requesting { ip: yiaddr, server });
+        }
+        (State::Requesting { ip, server }, DHCPACK) if ip == yiaddr => {
+            let lease_secs = options.lease_secs.unwrap_or(DEFAULT_LEASE_SECS);
+            dhcp.state = State::Bound { ip, server };
+            dhcp.deadline = Some(ktime_now() + lease_secs as u64 * NSEC_PER_SEC / 2);
+            drop(dhcp);
+            let config = IfConfig {
+                ip,
+                netmask: options.netmask.unwrap_or(IPv4::new(255, 255, 255, 0)),
+                gateway: options.router.unwrap_or(ANY),
+            };
+            let eth0 = interface("eth0").unwrap();
+            let changed = eth0.config().ip != ip;
+            eth0.set_config(config);
+            if let Some(dns) = options.dns {
+                set_nameserver(dns);
+            }
+            if changed {
+                let [a, b, c, d] = ip.to_u32().to_be_bytes();
+                let prefix = config.netmask.to_u32().count_ones();
+                info!("eth0 is {}.{}.{}.{}/{}", a, b, c, d, prefix);
+            }
+        }
+        (State::Requesting { .. }, DHCPNAK) => dhcp.enter(State::Selecting),
+        _ => {}
+    }
+    true
+}
@@ -0,0 +1,215 @@
+//! The stub resolver: the IPv4 addresses of a name, asked of the nameserver
+//! over UDP and kept for as long as the answer says they hold. A name in
+//! dots is its own address, and localhost is the loopback.
+
+use super::inet::{InetSocket, SockType};
+use crate::fs::OpenFlags;
+use crate::sync::UPIntrFreeCell;
+use crate::syscall::{EAGAIN, EINVAL, ENOENT, ETIMEDOUT};
+use crate::task::{signal_pending, suspend_current_and_run_next, ERESTARTSYS};
+use crate::timer::{get_time, ktime_now, NSEC_PER_MSEC, NSEC_PER_SEC};
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use lazy_static::*;
+use lose_net_stack::IPv4;
+
+const DNS_PORT: u16 = 53;
+/// Where QEMU's user networking answers, until DHCP tells otherwise.
+const DEFAULT_NAMESERVER: IPv4 = IPv4::new(10, 0, 2, 3);
+/// How long a question waits for its answer before it is asked again.
+const TIMEOUT_NS: u64 = 1000 * NSEC_PER_MSEC;
+const TRIES: usize = 3;
+/// Names whose addresses are kept, the oldest forgotten first.
+const CACHE_SIZE: usize = 32;
+const MAX_NAME: usize = 253;
+const MAX_LABEL: usize = 63;
+/// As much as a reply over UDP carries.
+const MAX_REPLY: usize = 512;
+
+const FLAG_RESPONSE: u8 = 0x80;
+const FLAG_RECURSION_DESIRED: u16 = 0x0100;
+const RCODE_NXDOMAIN: u8 = 3;
+const TYPE_A: u16 = 1;
+const CLASS_IN: u16 = 1;
+
+struct CacheEntry {
+    name: String,
+    addrs: Vec<IPv4>,
+    expire_ns: u64,
+}
+
+lazy_static! {
+    static ref NAMESERVER: UPIntrFreeCell<IPv4> =
+        unsafe { UPIntrFreeCell::new(DEFAULT_NAMESERVER) };
+    static ref CACHE: UPIntrFreeCell<VecDeque<CacheEntry>> =
+        unsafe { UPIntrFreeCell::new(VecDeque::new()) };
+}
+
+pub fn nameserver() -> IPv4 {
+    *NAMESERVER.exclusive_access()
+}
+
+/// Ask `ip` from now on, forgetting what the last one told.
+pub fn set_nameserver(ip: IPv4) {
+    *NAMESERVER.exclusive_access() = ip;
+    CACHE.exclusive_access().clear();
+}
+
+fn parse_ip(text: &str) -> Option<IPv4> {
+    let mut ip = [0u8; 4];
+    let mut parts = text.split('.');
+    for byte in ip.iter_mut() {
+        *byte = parts.next()?.parse().ok()?;
+    }
+    match parts.next() {
+        Some(_) => None,
+        None => Some(IPv4::new(ip[0], ip[1], ip[2], ip[3])),
+    }
+}
+
+/// The addresses of `name`: ENOENT if it has none, ETIMEDOUT if the
+/// nameserver does not answer, EAGAIN if it fails to.
+pub fn lookup(name: &str) -> Result<Vec<IPv4>, isize> {
+    if let Some(ip) = parse_ip(name) {
+        return Ok(vec![ip]);
+    }
+    let name = name.strip_suffix('.').unwrap_or(name).to_ascii_lowercase();
+    if name == "localhost" {
+        return Ok(vec![IPv4::new(127, 0, 0, 1)]);
+    }
+    if name.is_empty()
+        || name.len() > MAX_NAME
+        || name
+            .split('.')
+            .any(|label| label.is_empty() || label.len() > MAX_LABEL)
+    {
+        return Err(EINVAL);
+    }
+    let now = ktime_now();
+    let mut cache = CACHE.exclusive_access();
+    cache.retain(|entry| entry.expire_ns > now);
+    if let Some(entry) = cache.iter().find(|entry| entry.name == name) {
+        return Ok(entry.addrs.clone());
+    }
+    drop(cache);
+
+    let (addrs, ttl) = query(&name)?;
+    let mut cache = CACHE.exclusive_access();
+    if cache.len() == CACHE_SIZE {
+        cache.pop_front();
+    }
+    cache.push_back(CacheEntry {
+        name,
+        addrs: addrs.clone(),
+        expire_ns: ktime_now() + ttl as u64 * NSEC_PER_SEC,
+    });
+    Ok(addrs)
+}
+
+/// Ask the nameserver for the addresses of `name`, with the time they
+/// hold for.
+fn query(name: &str) -> Result<(Vec<IPv4>, u32), isize> {
+    let socket = InetSocket::new(SockType::Dgram, OpenFlags::NONBLOCK);
+    socket.connect(nameserver(), DNS_PORT)?;
+    let id = get_time() as u16;
+    let question = question(id, name);
+    for _ in 0..TRIES {
+        socket.send_to(&question, None)?;
+        let deadline = ktime_now() + TIMEOUT_NS;
+        while ktime_now() < deadline {
+            match socket.recv_from(MAX_REPLY) {
+                Ok((reply, _, _)) => {
+                    if let Some(answer) = answer(&reply, id) {
+                        return answer;
+                    }
+                }
+                Err(EAGAIN) => {}
+                Err(errno) => return Err(errno),
+            }
+            // netd takes in the reply
+            if signal_pending() {
+                return Err(ERESTARTSYS);
+            }
+            suspend_current_and_run_next();
+        }
+    }
+    Err(ETIMEDOUT)
+}
+
+/// The question of `id` for the A records of `name`.
+fn question(id: u16, name: &str) -> Vec<u8> {
+    let mut question = Vec::with_capacity(18 + name.len());
+    question.extend_from_slice(&id.to_be_bytes());
+    question.extend_from_slice(&FLAG_RECURSION_DESIRED.to_be_bytes());
+    // a question, no answers, authorities nor additions
+    question.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.split('.') {
+        question.push(label.len() as u8);
+        question.extend_from_slice(label.as_bytes());
+    }
+    question.push(0);
+    question.extend_from_slice(&TYPE_A.to_be_bytes());
+    question.extend_from_slice(&CLASS_IN.to_be_bytes());
+    question
+}
+
+fn be16(message: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([
+        *message.get(pos)?,
+        *message.get(pos + 1)?,
+    ]))
+}
+
+/// Where what follows the name at `pos` starts.
+fn skip_name(message: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *message.get(pos)? as usize;
+        match len {
+            0 => return Some(pos + 1),
+            // a pointer to the rest of the name elsewhere
+            0xc0.. => return Some(pos + 2),
+            _ => pos += 1 + len,
+        }
+    }
+}
+
+/// The addresses of the A records of `reply` and the least time they
+/// hold for, if it is the reply to `id`.
+fn answer(reply: &[u8], id: u16) -> Option<Result<(Vec<IPv4>, u32), isize>> {
+    if be16(reply, 0)? != id || reply.get(2)? & FLAG_RESPONSE == 0 {
+        return None;
+    }
+    match reply[3] & 0xf {
+        0 => {}
+        RCODE_NXDOMAIN => return Some(Err(ENOENT)),
+        _ => return Some(Err(EAGAIN)),
+    }
+    let questions = be16(reply, 4)?;
+    let answers = be16(reply, 6)?;
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = skip_name(reply, pos)? + 4;
+    }
+    let mut addrs = Vec::new();
+    let mut ttl = u32::MAX;
+    for _ in 0..answers {
+        pos = skip_name(reply, pos)?;
+        let kind = be16(reply, pos)?;
+        let class = be16(reply, pos + 2)?;
+        let record_ttl = u32::from_be_bytes(reply.get(pos + 4..pos + 8)?.try_into().ok()?);
+        let len = be16(reply, pos + 8)? as usize;
+        let data = reply.get(pos + 10..pos + 10 + len)?;
+        // the CNAME records on the way are skipped
+        if kind == TYPE_A && class == CLASS_IN && len == 4 {
+            addrs.push(IPv4::new(data[0], data[1], data[2], data[3]));
+            ttl = ttl.min(record_ttl);
+        }
+        pos += 10 + len;
+    }
+    match addrs.is_empty() {
+        true => Some(Err(ENOENT)),
+        false => Some(Ok((addrs, ttl))),
+    }
+}
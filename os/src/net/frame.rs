@@ -0,0 +1,138 @@
+//! The headers of the frames we send, written into the headroom of a
+//! packet buffer in front of what it holds, so that data is not copied
+//! again on its way to the card; lose-net-stack builds a frame of its own
+//! instead, and is left to parse what arrives.
+//!
+//! The checksums of TCP and UDP are left for the interface to complete,
+//! which the card does if it offers to and the loopback never needs to, so
+//! the data is not summed in software either.
+
+use super::iface::our_mac;
+use super::local_ip;
+use crate::drivers::Mbuf;
+use core::sync::atomic::{AtomicU16, Ordering};
+use lose_net_stack::{IPv4, MacAddress, TcpFlags};
+
+pub const ETH_HLEN: usize = 14;
+pub const ETH_P_IP: u16 = 0x0800;
+const IP_HLEN: usize = 20;
+const TCP_HLEN: usize = 20;
+const UDP_HLEN: usize = 8;
+pub const IPPROTO_ICMP: u8 = 1;
+const IPPROTO_TCP: u8 = 6;
+pub const IPPROTO_UDP: u8 = 17;
+pub const DEFAULT_TTL: u8 = 64;
+
+/// The identification of the next packet sent.
+static IP_ID: AtomicU16 = AtomicU16::new(0);
+
+pub fn be16(bytes: &[u8]) -> u16 {
+    u16::from_be_bytes([bytes[0], bytes[1]])
+}
+
+/// The one's complement sum of `data`, folded.
+fn sum16(data: &[u8]) -> u32 {
+    let mut sum: u32 = 0;
+    for chunk in data.chunks(2) {
+        let word = if chunk.len() == 2 {
+            be16(chunk)
+        } else {
+            (chunk[0] as u16) << 8
+        };
+        sum += word as u32;
+    }
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    sum
+}
+
+/// The Internet checksum of `data`.
+pub fn checksum(data: &[u8]) -> u16 {
+    !(sum16(data) as u16)
+}
+
+/// Leave the checksum of the TCP or UDP header at the start of `packet`,
+/// whose field is `offset` bytes in, to be completed: the field holds the
+/// sum of the pseudo-header of an IPv4 packet of `proto` from `src` to
+/// `dst`.
+fn partial_checksum(packet: &mut Mbuf, offset: usize, src: IPv4, dst: IPv4, proto: u8) {
+    let mut pseudo = [0u8; 12];
+    pseudo[0..4].copy_from_slice(&src.to_u32().to_be_bytes());
+    pseudo[4..8].copy_from_slice(&dst.to_u32().to_be_bytes());
+    pseudo[9] = proto;
+    pseudo[10..12].copy_from_slice(&(packet.len() as u16).to_be_bytes());
+    let sum = sum16(&pseudo) as u16;
+    packet[offset..offset + 2].copy_from_slice(&sum.to_be_bytes());
+    packet.set_checksum_partial(offset);
+}
+
+/// Push the IPv4 header of a packet of `proto` from `src` to `dst`, and
+/// the Ethernet header of a frame from us to `dest_mac`.
+pub fn push_ipv4(packet: &mut Mbuf, proto: u8, src: IPv4, dst: IPv4, dest_mac: MacAddress) {
+    let total_len = IP_HLEN + packet.len();
+    let header = packet.push(IP_HLEN);
+    header.fill(0);
+    header[0] = 0x45;
+    header[2..4].copy_from_slice(&(total_len as u16).to_be_bytes());
+    let id = IP_ID.fetch_add(1, Ordering::Relaxed);
+    header[4..6].copy_from_slice(&id.to_be_bytes());
+    header[8] = DEFAULT_TTL;
+    header[9] = proto;
+    header[12..16].copy_from_slice(&src.to_u32().to_be_bytes());
+    header[16..20].copy_from_slice(&dst.to_u32().to_be_bytes());
+    let sum = checksum(header);
+    header[10..12].copy_from_slice(&sum.to_be_bytes());
+
+    let ethernet = packet.push(ETH_HLEN);
+    ethernet[0..6].copy_from_slice(&dest_mac.to_bytes());
+    ethernet[6..12].copy_from_slice(&our_mac());
+    ethernet[12..14].copy_from_slice(&ETH_P_IP.to_be_bytes());
+}
+
+/// Make the datagram in `packet` a frame from `src`:`lport` to
+/// `dst`:`rport`.
+pub fn push_udp(
+    packet: &mut Mbuf,
+    src: IPv4,
+    lport: u16,
+    dst: IPv4,
+    rport: u16,
+    dest_mac: MacAddress,
+) {
+    let len = UDP_HLEN + packet.len();
+    let header = packet.push(UDP_HLEN);
+    header[0..2].copy_from_slice(&lport.to_be_bytes());
+    header[2..4].copy_from_slice(&rport.to_be_bytes());
+    header[4..6].copy_from_slice(&(len as u16).to_be_bytes());
+    partial_checksum(packet, 6, src, dst, IPPROTO_UDP);
+    push_ipv4(packet, IPPROTO_UDP, src, dst, dest_mac);
+}
+
+/// Make the data in `packet` a frame of a segment from our `lport` to
+/// `dst`:`rport`.
+#[allow(clippy::too_many_arguments)]
+pub fn push_tcp(
+    packet: &mut Mbuf,
+    lport: u16,
+    dst: IPv4,
+    rport: u16,
+    dest_mac: MacAddress,
+    seq: u32,
+    ack: u32,
+    flags: TcpFlags,
+    win: u16,
+) {
+    let src = local_ip();
+    let header = packet.push(TCP_HLEN);
+    header.fill(0);
+    header[0..2].copy_from_slice(&lport.to_be_bytes());
+    header[2..4].copy_from_slice(&rport.to_be_bytes());
+    header[4..8].copy_from_slice(&seq.to_be_bytes());
+    header[8..12].copy_from_slice(&ack.to_be_bytes());
+    header[12] = ((TCP_HLEN / 4) as u8) << 4;
+    header[13] = flags.bits();
+    header[14..16].copy_from_slice(&win.to_be_bytes());
+    partial_checksum(packet, 16, src, dst, IPPROTO_TCP);
+    push_ipv4(packet, IPPROTO_TCP, src, dst, dest_mac);
+}
@@ -0,0 +1,86 @@
+//! ICMP, which lose-net-stack leaves out: an echo request to our address
+//! is answered with its identifier, sequence number and data, and raw
+//! sockets send and take the rest.
+
+use super::frame::{be16, checksum, push_ipv4, DEFAULT_TTL, ETH_HLEN, ETH_P_IP, IPPROTO_ICMP};
+use super::iface::route;
+use super::local_ip;
+use crate::drivers::Mbuf;
+use lose_net_stack::{IPv4, MacAddress};
+
+const ICMP_ECHOREPLY: u8 = 0;
+const ICMP_ECHO: u8 = 8;
+/// The most an ICMP message carries, for the packet to fit a frame.
+pub const ICMP_MAX_LEN: usize = 1480;
+
+/// The IPv4 packet in `frame` if it is ICMP to `ip` with the checksums
+/// right, that of ICMP unless the card checked it, and the address of the
+/// sender; raw sockets take all of them.
+pub fn receive(frame: &Mbuf, ip: IPv4) -> Option<(&[u8], IPv4, MacAddress)> {
+    if frame.len() < ETH_HLEN + 20 || be16(&frame[12..14]) != ETH_P_IP {
+        return None;
+    }
+    let header = &frame[ETH_HLEN..];
+    let ihl = (header[0] & 0xf) as usize * 4;
+    let total_len = be16(&header[2..4]) as usize;
+    if header[0] >> 4 != 4
+        || ihl < 20
+        || total_len < ihl + 8
+        || total_len > header.len()
+        || header[9] != IPPROTO_ICMP
+        || header[16..20] != ip.to_u32().to_be_bytes()
+        || checksum(&header[..ihl]) != 0
+        || (!frame.checksum_verified() && checksum(&header[ihl..total_len]) != 0)
+    {
+        return None;
+    }
+    let source = &header[12..16];
+    let sender = IPv4::new(source[0], source[1], source[2], source[3]);
+    let mut mac = [0u8; 6];
+    mac.copy_from_slice(&frame[6..12]);
+    Some((&header[..total_len], sender, MacAddress::new(mac)))
+}
+
+/// The reply to `frame`, which receive took, if it is an echo request.
+pub fn echo_reply(frame: &[u8]) -> Option<Mbuf> {
+    let header = &frame[ETH_HLEN..];
+    let ihl = (header[0] & 0xf) as usize * 4;
+    let total_len = be16(&header[2..4]) as usize;
+    if header[ihl] != ICMP_ECHO {
+        return None;
+    }
+    // the request is the raw sockets' to read, the reply a copy
+    let mut reply = Mbuf::from_slice(&frame[..ETH_HLEN + total_len])?;
+    // Ethernet: back to the sender
+    reply.copy_within(6..12, 0);
+    reply[6..12].copy_from_slice(&frame[0..6]);
+    // IPv4: addresses swapped, the checksum again
+    let header = &mut reply[ETH_HLEN..];
+    header.copy_within(12..16, 16);
+    header[12..16].copy_from_slice(&frame[ETH_HLEN + 16..ETH_HLEN + 20]);
+    header[8] = DEFAULT_TTL;
+    header[10..12].fill(0);
+    let sum = checksum(&header[..ihl]);
+    header[10..12].copy_from_slice(&sum.to_be_bytes());
+    // ICMP: an echo reply of the same data
+    let icmp = &mut header[ihl..total_len];
+    icmp[0] = ICMP_ECHOREPLY;
+    icmp[2..4].fill(0);
+    let sum = checksum(icmp);
+    icmp[2..4].copy_from_slice(&sum.to_be_bytes());
+    Some(reply)
+}
+
+/// Send the ICMP message `icmp`, checksum and all, to `raddr`.
+pub fn send_icmp(raddr: IPv4, icmp: &[u8]) {
+    let iface = match route(raddr) {
+        Some(iface) => iface,
+        None => return,
+    };
+    let mut packet = match Mbuf::from_slice(icmp) {
+        Some(packet) => packet,
+        None => return,
+    };
+    push_ipv4(&mut packet, IPPROTO_ICMP, local_ip(), raddr, iface.resolve(raddr));
+    iface.transmit(packet);
+}
@@ -0,0 +1,253 @@
+//! The network interfaces, which the stack sends frames out of and takes
+//! them in from: `lo`, always there, hands frames to our own address back
+//! to netd, and `eth0` is the card if there is one. Each counts what it
+//! carries, and what it took in but no socket had room for.
+//!
+//! The stack has a single address, that of `eth0`, which is set at will or
+//! by DHCP; frames for other networks go to its gateway.
+
+use super::{arp, local_ip, netd::wakeup_netd, LOSE_NET_STACK};
+use crate::drivers::{Mbuf, NetDevice, NET_DEVICE};
+use crate::sync::UPIntrFreeCell;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use lazy_static::*;
+use lose_net_stack::{IPv4, MacAddress};
+
+pub const BROADCAST_IP: IPv4 = IPv4::new(255, 255, 255, 255);
+const BROADCAST_MAC: MacAddress = MacAddress::new([0xff; 6]);
+
+/// The address of an interface, the network it is on, and where frames
+/// for the others go.
+#[derive(Copy, Clone)]
+pub struct IfConfig {
+    pub ip: IPv4,
+    pub netmask: IPv4,
+    pub gateway: IPv4,
+}
+
+impl IfConfig {
+    /// Whether `ip` is on the network of the interface.
+    pub fn is_local(&self, ip: IPv4) -> bool {
+        let mask = self.netmask.to_u32();
+        ip.to_u32() & mask == self.ip.to_u32() & mask
+    }
+}
+
+/// What eth0 is until told otherwise, as QEMU's user networking has it.
+pub const DEFAULT_CONFIG: IfConfig = IfConfig {
+    ip: IPv4::new(10, 0, 2, 15),
+    netmask: IPv4::new(255, 255, 255, 0),
+    gateway: IPv4::new(10, 0, 2, 2),
+};
+
+const LOOPBACK_CONFIG: IfConfig = IfConfig {
+    ip: IPv4::new(127, 0, 0, 1),
+    netmask: IPv4::new(255, 0, 0, 0),
+    gateway: IPv4::new(0, 0, 0, 0),
+};
+
+/// What an interface carried.
+#[derive(Copy, Clone, Default)]
+pub struct IfStats {
+    pub rx_packets: usize,
+    pub rx_bytes: usize,
+    pub tx_packets: usize,
+    pub tx_bytes: usize,
+    /// Packets dropped, the receive buffer of their socket being full.
+    pub rx_dropped: usize,
+}
+
+impl IfStats {
+    fn count_rx(&mut self, len: usize) {
+        self.rx_packets += 1;
+        self.rx_bytes += len;
+    }
+    fn count_tx(&mut self, len: usize) {
+        self.tx_packets += 1;
+        self.tx_bytes += len;
+    }
+}
+
+pub trait NetInterface: Send + Sync {
+    fn name(&self) -> &'static str;
+    /// The hardware address, all zeroes for none.
+    fn mac(&self) -> [u8; 6];
+    /// The MAC address a frame for `ip` goes to.
+    fn resolve(&self, ip: IPv4) -> MacAddress;
+    fn transmit(&self, frame: Mbuf);
+    /// A frame taken in, if one is there.
+    fn receive(&self) -> Option<Mbuf>;
+    fn can_receive(&self) -> bool;
+    fn stats(&self) -> IfStats;
+    /// Count `count` packets taken in as dropped.
+    fn count_drops(&self, count: usize);
+    fn config(&self) -> IfConfig;
+    /// Give the interface `config`; false if it keeps its own.
+    fn set_config(&self, config: IfConfig) -> bool;
+}
+
+/// The MAC address of the card, all zeroes without one.
+pub fn our_mac() -> [u8; 6] {
+    NET_DEVICE.as_ref().map_or([0; 6], |device| device.mac())
+}
+
+/// The frames sent to ourselves, for netd to take in as they are, their
+/// checksums left undone as nobody checks them.
+struct Loopback {
+    queue: UPIntrFreeCell<VecDeque<Mbuf>>,
+    stats: UPIntrFreeCell<IfStats>,
+}
+
+impl NetInterface for Loopback {
+    fn name(&self) -> &'static str {
+        "lo"
+    }
+    fn mac(&self) -> [u8; 6] {
+        [0; 6]
+    }
+    fn resolve(&self, _ip: IPv4) -> MacAddress {
+        MacAddress::new(our_mac())
+    }
+    fn transmit(&self, frame: Mbuf) {
+        let mut stats = self.stats.exclusive_access();
+        stats.count_tx(frame.len());
+        stats.count_rx(frame.len());
+        #[cfg(feature = "fault_inject")]
+        if crate::fault::should_fail(crate::fault::FaultPoint::Packet) {
+            stats.rx_dropped += 1;
+            return;
+        }
+        drop(stats);
+        self.queue.exclusive_access().push_back(frame);
+        wakeup_netd();
+    }
+    fn receive(&self) -> Option<Mbuf> {
+        self.queue.exclusive_access().pop_front()
+    }
+    fn can_receive(&self) -> bool {
+        !self.queue.exclusive_access().is_empty()
+    }
+    fn stats(&self) -> IfStats {
+        *self.stats.exclusive_access()
+    }
+    fn count_drops(&self, count: usize) {
+        self.stats.exclusive_access().rx_dropped += count;
+    }
+    fn config(&self) -> IfConfig {
+        LOOPBACK_CONFIG
+    }
+    fn set_config(&self, _config: IfConfig) -> bool {
+        false
+    }
+}
+
+/// The card, its neighbours found in the ARP cache.
+struct Ethernet {
+    device: Arc<dyn NetDevice>,
+    stats: UPIntrFreeCell<IfStats>,
+    config: UPIntrFreeCell<IfConfig>,
+}
+
+impl NetInterface for Ethernet {
+    fn name(&self) -> &'static str {
+        "eth0"
+    }
+    fn mac(&self) -> [u8; 6] {
+        self.device.mac()
+    }
+    fn resolve(&self, ip: IPv4) -> MacAddress {
+        let config = *self.config.exclusive_access();
+        if ip == BROADCAST_IP {
+            BROADCAST_MAC
+        } else if config.is_local(ip) || config.gateway.to_u32() == 0 {
+            arp::resolve(ip)
+        } else {
+            arp::resolve(config.gateway)
+        }
+    }
+    fn transmit(&self, mut frame: Mbuf) {
+        #[cfg(feature = "fault_inject")]
+        if crate::fault::should_fail(crate::fault::FaultPoint::Packet) {
+            return;
+        }
+        self.stats.exclusive_access().count_tx(frame.len());
+        if !self.device.offloads().tx_csum {
+            frame.finish_checksum();
+        }
+        self.device.transmit(frame);
+    }
+    fn receive(&self) -> Option<Mbuf> {
+        if !self.device.can_receive() {
+            return None;
+        }
+        let frame = self.device.receive();
+        self.stats.exclusive_access().count_rx(frame.len());
+        #[cfg(feature = "fault_inject")]
+        if crate::fault::should_fail(crate::fault::FaultPoint::Packet) {
+            self.count_drops(1);
+            return self.receive();
+        }
+        Some(frame)
+    }
+    fn can_receive(&self) -> bool {
+        self.device.can_receive()
+    }
+    fn stats(&self) -> IfStats {
+        *self.stats.exclusive_access()
+    }
+    fn count_drops(&self, count: usize) {
+        self.stats.exclusive_access().rx_dropped += count;
+    }
+    fn config(&self) -> IfConfig {
+        *self.config.exclusive_access()
+    }
+    fn set_config(&self, config: IfConfig) -> bool {
+        *self.config.exclusive_access() = config;
+        LOSE_NET_STACK.0.exclusive_access().ip = config.ip;
+        true
+    }
+}
+
+lazy_static! {
+    /// `lo` first, then `eth0` if there is a card.
+    static ref INTERFACES: Vec<Arc<dyn NetInterface>> = {
+        let mut interfaces: Vec<Arc<dyn NetInterface>> = vec![Arc::new(Loopback {
+            queue: unsafe { UPIntrFreeCell::new(VecDeque::new()) },
+            stats: unsafe { UPIntrFreeCell::new(IfStats::default()) },
+        })];
+        if let Some(device) = NET_DEVICE.as_ref() {
+            interfaces.push(Arc::new(Ethernet {
+                device: device.clone(),
+                stats: unsafe { UPIntrFreeCell::new(IfStats::default()) },
+                config: unsafe { UPIntrFreeCell::new(DEFAULT_CONFIG) },
+            }));
+        }
+        interfaces
+    };
+}
+
+pub fn interfaces() -> &'static [Arc<dyn NetInterface>] {
+    &INTERFACES
+}
+
+pub fn interface(name: &str) -> Option<&'static Arc<dyn NetInterface>> {
+    INTERFACES.iter().find(|iface| iface.name() == name)
+}
+
+/// Whether `ip` is one of 127.0.0.0/8.
+pub fn is_loopback(ip: IPv4) -> bool {
+    ip.to_u32() >> 24 == 127
+}
+
+/// The interface a frame for `ip` goes out of, none for another host
+/// without a card.
+pub fn route(ip: IPv4) -> Option<&'static Arc<dyn NetInterface>> {
+    if ip == local_ip() || is_loopback(ip) {
+        INTERFACES.first()
+    } else {
+        INTERFACES.get(1)
+    }
+}
@@ -0,0 +1,738 @@
+//! Sockets of AF_INET, the files socket(2) makes: SOCK_STREAM over TCP,
+//! SOCK_DGRAM over UDP and SOCK_RAW for ICMP.
+//!
+//! A datagram socket is in the socket table from the time it has a port,
+//! taking from anybody until it connects. A stream socket listens through
+//! the listen table, and the connections accept hands out are in the socket
+//! table, as are those connect opens. Connecting sends SYN and waits for
+//! the SYN|ACK, sending SYN again now and then; with O_NONBLOCK it returns
+//! EINPROGRESS at once instead, the socket turning writable once the peer
+//! answered, and connect again telling how it went.
+//!
+//! A raw socket is in the socket table from the start, taking a copy of
+//! each ICMP packet to us, IPv4 header and all, and sending the ICMP
+//! messages its user makes with the header added.
+//!
+//! What arrives for a socket is dropped once its receive buffer is full,
+//! and counted on the interface; a connection tells its peer the room
+//! left as its window, and again once reading made room. Sending on a
+//! connection waits while the send buffer or the window of the peer is
+//! full of what is unacknowledged, probing the window now and then;
+//! datagrams go out at once.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+use lose_net_stack::{IPv4, TcpFlags};
+
+use super::icmp::{send_icmp, ICMP_MAX_LEN};
+use super::port_table::{
+    has_connection, listen, port_listening, set_listen_rcvbuf, take_connection, unlisten,
+};
+use super::socket::{
+    add_socket, free_space, get_s_a_by_index, get_socket, has_data, is_eof, is_syn_sent, peer,
+    pop_data, port_in_use, remove_socket, send_window, set_rcvbuf, set_remote, set_s_a_by_index,
+    set_syn_sent, unpop_data, Protocol, SOCK_BUF_DEFAULT, SOCK_BUF_MAX, SOCK_BUF_MIN,
+};
+use super::tcp::{send_tcp, TCP_MSS};
+use super::udp::{send_udp, UDP_MAX_PAYLOAD};
+use crate::fs::{File, OpenFlags, Stat, S_IFSOCK};
+use crate::mm::UserBuffer;
+use crate::sync::UPIntrFreeCell;
+use crate::syscall::{
+    EADDRINUSE, EAGAIN, EALREADY, ECONNREFUSED, EDESTADDRREQ, EINPROGRESS, EINVAL, EISCONN,
+    EMSGSIZE, ENOTCONN, EOPNOTSUPP, EPIPE, ETIMEDOUT,
+};
+use crate::task::{signal_pending, sleep_ns, suspend_current_and_run_next, ERESTARTSYS};
+use crate::timer::{add_timer_async, get_time, ktime_now, NSEC_PER_MSEC};
+
+/// Where ports are taken from for sockets which are not bound to one.
+const EPHEMERAL_PORTS: core::ops::RangeInclusive<u16> = 49152..=65535;
+
+const ANY: IPv4 = IPv4::new(0, 0, 0, 0);
+
+/// How long connect first waits for an answer to SYN, doubled each time
+/// it is sent again.
+const SYN_TIMEOUT_NS: u64 = 1000 * NSEC_PER_MSEC;
+/// How many times SYN is sent again before connect gives up.
+const SYN_RETRIES: usize = 4;
+/// How long sending waits on a full window before asking the peer how it
+/// is, lest the segment opening it was lost.
+const PROBE_NS: u64 = 500 * NSEC_PER_MSEC;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum SockType {
+    Stream,
+    Dgram,
+    Raw,
+}
+
+enum State {
+    /// Not in a table yet, on `lport` if it is not 0.
+    Idle { lport: u16 },
+    /// On the listen table at `index`.
+    Listening { lport: u16, index: usize },
+    /// On the socket table at `index`: a datagram socket, or a connection.
+    Open {
+        lport: u16,
+        index: usize,
+        remote: Option<(IPv4, u16)>,
+    },
+}
+
+struct InetSocketInner {
+    state: State,
+    status: OpenFlags,
+    shut_rd: bool,
+    shut_wr: bool,
+    /// connect opened the connection, and has not told how it went.
+    connecting: bool,
+    /// How many bytes may be in flight unacknowledged.
+    sndbuf: usize,
+    /// How many bytes of what arrived are held, given to the socket table
+    /// once the socket is in it.
+    rcvbuf: usize,
+}
+
+pub struct InetSocket {
+    kind: SockType,
+    inner: UPIntrFreeCell<InetSocketInner>,
+}
+
+/// Send SYN again to `raddr`:`rport` from `lport` while the connection at
+/// `index` waits for the answer, for a connect which does not wait itself;
+/// kworker runs it.
+async fn resend_syn(index: usize, lport: u16, (raddr, rport): (IPv4, u16), isn: u32) {
+    let mut timeout = SYN_TIMEOUT_NS;
+    for retry in 0..SYN_RETRIES {
+        if retry > 0 {
+            sleep_ns(timeout).await;
+        }
+        // the same connection, not one opened since at its index
+        if get_socket(Protocol::Tcp, raddr, lport, rport) != Some(index)
+            || !is_syn_sent(index)
+            || is_eof(index)
+        {
+            return;
+        }
+        send_tcp(lport, raddr, rport, isn, 0, TcpFlags::S, &[]);
+        timeout *= 2;
+    }
+}
+
+/// A free port of `kind`.
+fn ephemeral_port(kind: SockType) -> Option<u16> {
+    EPHEMERAL_PORTS.into_iter().find(|&port| match kind {
+        SockType::Stream => !port_listening(port) && !port_in_use(Protocol::Tcp, port),
+        SockType::Dgram => !port_in_use(Protocol::Udp, port),
+        SockType::Raw => false,
+    })
+}
+
+impl InetSocket {
+    pub fn new(kind: SockType, status: OpenFlags) -> Arc<Self> {
+        let state = match kind {
+            SockType::Raw => State::Open {
+                lport: 0,
+                index: add_socket(Protocol::Icmp, ANY, 0, 0).unwrap(),
+                remote: None,
+            },
+            _ => State::Idle { lport: 0 },
+        };
+        Self::with_state(kind, state, status)
+    }
+
+    fn with_state(kind: SockType, state: State, status: OpenFlags) -> Arc<Self> {
+        Arc::new(Self {
+            kind,
+            inner: unsafe {
+                UPIntrFreeCell::new(InetSocketInner {
+                    state,
+                    status: status & OpenFlags::NONBLOCK,
+                    shut_rd: false,
+                    shut_wr: false,
+                    connecting: false,
+                    sndbuf: SOCK_BUF_DEFAULT,
+                    rcvbuf: SOCK_BUF_DEFAULT,
+                })
+            },
+        })
+    }
+
+    /// The local port, 0 for none yet.
+    pub fn local_port(&self) -> u16 {
+        match self.inner.exclusive_access().state {
+            State::Idle { lport } | State::Listening { lport, .. } | State::Open { lport, .. } => {
+                lport
+            }
+        }
+    }
+
+    /// The peer, if connected.
+    pub fn peer(&self) -> Option<(IPv4, u16)> {
+        match self.inner.exclusive_access().state {
+            State::Open { remote, .. } => remote,
+            _ => None,
+        }
+    }
+
+    /// The sizes of the send and the receive buffer.
+    pub fn buffer_sizes(&self) -> (usize, usize) {
+        let inner = self.inner.exclusive_access();
+        (inner.sndbuf, inner.rcvbuf)
+    }
+
+    /// Have the send buffer, or the receive buffer unless `send`, hold
+    /// `size` bytes, within SOCK_BUF_MIN and SOCK_BUF_MAX.
+    pub fn set_buffer_size(&self, send: bool, size: usize) {
+        let size = size.clamp(SOCK_BUF_MIN, SOCK_BUF_MAX);
+        let mut inner = self.inner.exclusive_access();
+        if send {
+            inner.sndbuf = size;
+            return;
+        }
+        inner.rcvbuf = size;
+        match inner.state {
+            State::Idle { .. } => {}
+            State::Listening { index, .. } => set_listen_rcvbuf(index, size),
+            State::Open { index, .. } => set_rcvbuf(index, size),
+        }
+    }
+
+    /// Take `port`, or a free one for 0.
+    pub fn bind(&self, port: u16) -> Result<(), isize> {
+        // raw sockets have no port, and take what comes to our address
+        if self.kind == SockType::Raw {
+            return Ok(());
+        }
+        let mut inner = self.inner.exclusive_access();
+        match inner.state {
+            State::Idle { lport: 0 } => {}
+            _ => return Err(EINVAL),
+        }
+        let port = match port {
+            0 => ephemeral_port(self.kind).ok_or(EADDRINUSE)?,
+            port => port,
+        };
+        match self.kind {
+            SockType::Stream => {
+                if port_listening(port) {
+                    return Err(EADDRINUSE);
+                }
+                inner.state = State::Idle { lport: port };
+            }
+            SockType::Dgram => {
+                if port_in_use(Protocol::Udp, port) {
+                    return Err(EADDRINUSE);
+                }
+                let index = add_socket(Protocol::Udp, ANY, port, 0).ok_or(EADDRINUSE)?;
+                set_rcvbuf(index, inner.rcvbuf);
+                inner.state = State::Open {
+                    lport: port,
+                    index,
+                    remote: None,
+                };
+            }
+            SockType::Raw => unreachable!(),
+        }
+        Ok(())
+    }
+
+    /// Bind a datagram socket to a free port unless it has one; its index
+    /// in the socket table.
+    fn autobind(&self) -> Result<usize, isize> {
+        let unbound = matches!(
+            self.inner.exclusive_access().state,
+            State::Idle { lport: 0 }
+        );
+        if unbound {
+            self.bind(0)?;
+        }
+        match self.inner.exclusive_access().state {
+            State::Open { index, .. } => Ok(index),
+            _ => Err(EINVAL),
+        }
+    }
+
+    pub fn listen(&self, backlog: usize) -> Result<(), isize> {
+        if self.kind != SockType::Stream {
+            return Err(EOPNOTSUPP);
+        }
+        let mut inner = self.inner.exclusive_access();
+        let lport = match inner.state {
+            State::Idle { lport: 0 } => ephemeral_port(self.kind).ok_or(EADDRINUSE)?,
+            State::Idle { lport } => lport,
+            State::Listening { .. } => return Ok(()),
+            State::Open { .. } => return Err(EINVAL),
+        };
+        let index = listen(lport, backlog, inner.rcvbuf).ok_or(EADDRINUSE)?;
+        inner.state = State::Listening { lport, index };
+        Ok(())
+    }
+
+    /// A connection of the listening socket, with the status `status`.
+    pub fn accept(&self, status: OpenFlags) -> Result<Arc<InetSocket>, isize> {
+        loop {
+            let inner = self.inner.exclusive_access();
+            let (lport, listen_index) = match inner.state {
+                State::Listening { lport, index } => (lport, index),
+                _ => return Err(EINVAL),
+            };
+            if let Some(index) = take_connection(listen_index) {
+                let (raddr, rport) = peer(index);
+                let state = State::Open {
+                    lport,
+                    index,
+                    remote: Some((raddr, rport)),
+                };
+                // a connection has the buffers of its listener
+                let socket = Self::with_state(SockType::Stream, state, status);
+                socket.set_buffer_size(true, inner.sndbuf);
+                socket.set_buffer_size(false, inner.rcvbuf);
+                return Ok(socket);
+            }
+            if inner.status.contains(OpenFlags::NONBLOCK) {
+                return Err(EAGAIN);
+            }
+            drop(inner);
+            if signal_pending() {
+                return Err(ERESTARTSYS);
+            }
+            suspend_current_and_run_next();
+        }
+    }
+
+    /// Send datagrams to `raddr`:`rport` only, and take only theirs; or
+    /// open a connection to them.
+    pub fn connect(&self, raddr: IPv4, rport: u16) -> Result<(), isize> {
+        if self.kind == SockType::Stream {
+            return self.open(raddr, rport);
+        }
+        let index = self.autobind()?;
+        set_remote(index, raddr, rport);
+        if let State::Open { remote, .. } = &mut self.inner.exclusive_access().state {
+            *remote = Some((raddr, rport));
+        }
+        Ok(())
+    }
+
+    /// Send SYN to `raddr`:`rport`, or look at how the connection connect
+    /// opened before goes.
+    fn open(&self, raddr: IPv4, rport: u16) -> Result<(), isize> {
+        let mut inner = self.inner.exclusive_access();
+        match inner.state {
+            State::Idle { lport } => {
+                let lport = match lport {
+                    0 => ephemeral_port(self.kind).ok_or(EADDRINUSE)?,
+                    lport => lport,
+                };
+                let index = add_socket(Protocol::Tcp, raddr, lport, rport).ok_or(EADDRINUSE)?;
+                set_rcvbuf(index, inner.rcvbuf);
+                // the SYN takes a sequence number
+                let isn = get_time() as u32;
+                set_s_a_by_index(index, isn.wrapping_add(1), 0);
+                set_syn_sent(index, true);
+                inner.state = State::Open {
+                    lport,
+                    index,
+                    remote: Some((raddr, rport)),
+                };
+                inner.connecting = true;
+                let nonblock = inner.status.contains(OpenFlags::NONBLOCK);
+                drop(inner);
+                send_tcp(lport, raddr, rport, isn, 0, TcpFlags::S, &[]);
+                if nonblock {
+                    let expire_ns = ktime_now() + SYN_TIMEOUT_NS;
+                    add_timer_async(expire_ns, resend_syn(index, lport, (raddr, rport), isn));
+                    return Err(EINPROGRESS);
+                }
+            }
+            State::Open { .. } if inner.connecting => {}
+            State::Open { .. } => return Err(EISCONN),
+            State::Listening { .. } => return Err(EINVAL),
+        }
+        self.wait_connected()
+    }
+
+    /// Wait for the peer to answer SYN, sending it again while it does not.
+    fn wait_connected(&self) -> Result<(), isize> {
+        let mut timeout = SYN_TIMEOUT_NS;
+        let mut resend_at = ktime_now() + timeout;
+        let mut retries = 0;
+        loop {
+            let mut inner = self.inner.exclusive_access();
+            let (lport, index, (raddr, rport)) = match inner.state {
+                State::Open {
+                    lport,
+                    index,
+                    remote: Some(remote),
+                } => (lport, index, remote),
+                _ => return Err(EINVAL),
+            };
+            if !is_syn_sent(index) {
+                inner.connecting = false;
+                return Ok(());
+            }
+            let refused = is_eof(index);
+            if refused || retries == SYN_RETRIES && ktime_now() >= resend_at {
+                // another connect may try again
+                inner.connecting = false;
+                inner.state = State::Idle { lport };
+                drop(inner);
+                remove_socket(index);
+                return Err(if refused { ECONNREFUSED } else { ETIMEDOUT });
+            }
+            if inner.status.contains(OpenFlags::NONBLOCK) {
+                return Err(EALREADY);
+            }
+            drop(inner);
+            if ktime_now() >= resend_at {
+                let (seq, _) = get_s_a_by_index(index).unwrap();
+                send_tcp(
+                    lport,
+                    raddr,
+                    rport,
+                    seq.wrapping_sub(1),
+                    0,
+                    TcpFlags::S,
+                    &[],
+                );
+                retries += 1;
+                timeout *= 2;
+                resend_at = ktime_now() + timeout;
+            }
+            // netd takes in the answer
+            if signal_pending() {
+                return Err(ERESTARTSYS);
+            }
+            suspend_current_and_run_next();
+        }
+    }
+
+    /// Send `data`, to `dest` if given for a datagram socket.
+    pub fn send_to(&self, data: &[u8], dest: Option<(IPv4, u16)>) -> Result<usize, isize> {
+        if self.inner.exclusive_access().shut_wr {
+            return Err(EPIPE);
+        }
+        match self.kind {
+            SockType::Dgram => {
+                if data.len() > UDP_MAX_PAYLOAD {
+                    return Err(EMSGSIZE);
+                }
+                self.autobind()?;
+                let (lport, (raddr, rport)) = match self.inner.exclusive_access().state {
+                    State::Open { lport, remote, .. } => {
+                        (lport, dest.or(remote).ok_or(EDESTADDRREQ)?)
+                    }
+                    _ => return Err(EINVAL),
+                };
+                send_udp(lport, raddr, rport, data);
+            }
+            SockType::Raw => {
+                if data.len() > ICMP_MAX_LEN {
+                    return Err(EMSGSIZE);
+                }
+                let raddr = match self.inner.exclusive_access().state {
+                    State::Open { remote, .. } => dest.or(remote).ok_or(EDESTADDRREQ)?.0,
+                    _ => return Err(EINVAL),
+                };
+                send_icmp(raddr, data);
+            }
+            SockType::Stream => {
+                let (lport, index, (raddr, rport)) = match self.inner.exclusive_access().state {
+                    State::Open {
+                        lport,
+                        index,
+                        remote: Some(remote),
+                    } => (lport, index, remote),
+                    _ => return Err(ENOTCONN),
+                };
+                if is_syn_sent(index) {
+                    return Err(ENOTCONN);
+                }
+                return self.send_stream(lport, index, (raddr, rport), data);
+            }
+        }
+        Ok(data.len())
+    }
+
+    /// How many more bytes the connection at `index` may have in flight.
+    fn send_room(&self, index: usize) -> usize {
+        let sndbuf = self.inner.exclusive_access().sndbuf;
+        let (seq, _) = get_s_a_by_index(index).unwrap();
+        let (snd_una, snd_wnd) = send_window(index);
+        let in_flight = seq.wrapping_sub(snd_una) as usize;
+        snd_wnd.min(sndbuf).saturating_sub(in_flight)
+    }
+
+    /// Send `data` on the connection at `index` as there is room for it,
+    /// waiting for room unless O_NONBLOCK; how much was sent.
+    fn send_stream(
+        &self,
+        lport: u16,
+        index: usize,
+        (raddr, rport): (IPv4, u16),
+        data: &[u8],
+    ) -> Result<usize, isize> {
+        let mut sent = 0;
+        let mut probe_at = ktime_now() + PROBE_NS;
+        while sent < data.len() {
+            if is_eof(index) {
+                return if sent > 0 { Ok(sent) } else { Err(EPIPE) };
+            }
+            let room = self.send_room(index);
+            if room > 0 {
+                let len = (data.len() - sent).min(room).min(TCP_MSS);
+                let (seq, ack) = get_s_a_by_index(index).unwrap();
+                let flags = TcpFlags::A | TcpFlags::P;
+                send_tcp(
+                    lport,
+                    raddr,
+                    rport,
+                    seq,
+                    ack,
+                    flags,
+                    &data[sent..sent + len],
+                );
+                set_s_a_by_index(index, seq.wrapping_add(len as u32), ack);
+                sent += len;
+                probe_at = ktime_now() + PROBE_NS;
+                continue;
+            }
+            let nonblock = self
+                .inner
+                .exclusive_access()
+                .status
+                .contains(OpenFlags::NONBLOCK);
+            if nonblock || signal_pending() {
+                return match sent {
+                    0 if nonblock => Err(EAGAIN),
+                    0 => Err(ERESTARTSYS),
+                    sent => Ok(sent),
+                };
+            }
+            if ktime_now() >= probe_at {
+                // a segment from before what the peer expects, for it to
+                // answer with its window
+                let (snd_una, _) = send_window(index);
+                let (_, ack) = get_s_a_by_index(index).unwrap();
+                let una = snd_una.wrapping_sub(1);
+                send_tcp(lport, raddr, rport, una, ack, TcpFlags::A, &[]);
+                probe_at = ktime_now() + PROBE_NS;
+            }
+            // netd takes in the acknowledgements
+            suspend_current_and_run_next();
+        }
+        Ok(sent)
+    }
+
+    /// Tell the peer of the connection at `index` of the room reading made,
+    /// if it had too little to send a segment before.
+    fn update_window(&self, index: usize, free_before: usize) {
+        if free_before >= TCP_MSS || free_space(index) < TCP_MSS {
+            return;
+        }
+        let (lport, (raddr, rport)) = match self.inner.exclusive_access().state {
+            State::Open {
+                lport,
+                remote: Some(remote),
+                ..
+            } => (lport, remote),
+            _ => return,
+        };
+        let (seq, ack) = get_s_a_by_index(index).unwrap();
+        send_tcp(lport, raddr, rport, seq, ack, TcpFlags::A, &[]);
+    }
+
+    /// Take at most `len` bytes, and who sent them; nothing once the peer
+    /// of a stream is done or reading is shut down.
+    pub fn recv_from(&self, len: usize) -> Result<(Vec<u8>, IPv4, u16), isize> {
+        let index = match self.kind {
+            SockType::Dgram | SockType::Raw => self.autobind()?,
+            SockType::Stream => match self.inner.exclusive_access().state {
+                State::Open { index, .. } => index,
+                _ => return Err(ENOTCONN),
+            },
+        };
+        loop {
+            let inner = self.inner.exclusive_access();
+            if inner.shut_rd {
+                return Ok((Vec::new(), ANY, 0));
+            }
+            let free_before = free_space(index);
+            if let Some(mut received) = pop_data(index) {
+                drop(inner);
+                let (raddr, rport) = (received.raddr, received.rport);
+                let data = received.data[..received.data.len().min(len)].to_vec();
+                if received.data.len() > len {
+                    // the rest of a datagram is gone, that of a stream is
+                    // read next
+                    if self.kind == SockType::Stream {
+                        received.data.pull(len);
+                        unpop_data(index, received);
+                    }
+                }
+                if self.kind == SockType::Stream {
+                    self.update_window(index, free_before);
+                }
+                return Ok((data, raddr, rport));
+            }
+            if self.kind == SockType::Stream && is_eof(index) {
+                return Ok((Vec::new(), ANY, 0));
+            }
+            if inner.status.contains(OpenFlags::NONBLOCK) {
+                return Err(EAGAIN);
+            }
+            drop(inner);
+            // netd pushes what arrives
+            if signal_pending() {
+                return Err(ERESTARTSYS);
+            }
+            suspend_current_and_run_next();
+        }
+    }
+
+    /// Stop reading if `read`, sending if `write`.
+    pub fn shutdown(&self, read: bool, write: bool) -> Result<(), isize> {
+        let mut inner = self.inner.exclusive_access();
+        let (lport, index, (raddr, rport)) = match inner.state {
+            State::Open {
+                lport,
+                index,
+                remote: Some(remote),
+            } => (lport, index, remote),
+            _ => return Err(ENOTCONN),
+        };
+        if self.kind == SockType::Stream && is_syn_sent(index) {
+            return Err(ENOTCONN);
+        }
+        inner.shut_rd |= read;
+        if write && !inner.shut_wr {
+            inner.shut_wr = true;
+            drop(inner);
+            if self.kind == SockType::Stream {
+                send_fin(lport, index, raddr, rport);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// End the sending of the connection at `index`.
+fn send_fin(lport: u16, index: usize, raddr: IPv4, rport: u16) {
+    let (seq, ack) = get_s_a_by_index(index).unwrap();
+    send_tcp(
+        lport,
+        raddr,
+        rport,
+        seq,
+        ack,
+        TcpFlags::F | TcpFlags::A,
+        &[],
+    );
+    set_s_a_by_index(index, seq.wrapping_add(1), ack);
+}
+
+impl Drop for InetSocket {
+    fn drop(&mut self) {
+        let mut inner = self.inner.exclusive_access();
+        let shut_wr = inner.shut_wr;
+        let state = core::mem::replace(&mut inner.state, State::Idle { lport: 0 });
+        drop(inner);
+        match state {
+            State::Idle { .. } => {}
+            State::Listening { index, .. } => unlisten(index),
+            State::Open {
+                lport,
+                index,
+                remote,
+            } => {
+                if let (SockType::Stream, Some((raddr, rport))) = (self.kind, remote) {
+                    if !shut_wr && !is_syn_sent(index) {
+                        send_fin(lport, index, raddr, rport);
+                    }
+                }
+                remove_socket(index);
+            }
+        }
+    }
+}
+
+impl File for InetSocket {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn read(&self, mut buf: UserBuffer) -> usize {
+        let data = match self.recv_from(buf.len()) {
+            Ok((data, _, _)) => data,
+            Err(_) => return 0,
+        };
+        let mut copied = 0;
+        for buffer in buf.buffers.iter_mut() {
+            let len = buffer.len().min(data.len() - copied);
+            buffer[..len].copy_from_slice(&data[copied..copied + len]);
+            copied += len;
+        }
+        copied
+    }
+
+    fn write(&self, buf: UserBuffer) -> usize {
+        let mut data = Vec::with_capacity(buf.len());
+        for buffer in buf.buffers.iter() {
+            data.extend_from_slice(buffer);
+        }
+        self.send_to(&data, None).unwrap_or(0)
+    }
+
+    fn status(&self) -> OpenFlags {
+        self.inner.exclusive_access().status
+    }
+
+    fn set_status(&self, status: OpenFlags) {
+        self.inner.exclusive_access().status = status & OpenFlags::NONBLOCK;
+    }
+
+    fn read_ready(&self) -> bool {
+        let inner = self.inner.exclusive_access();
+        match inner.state {
+            State::Idle { .. } => false,
+            State::Listening { index, .. } => has_connection(index),
+            State::Open { index, .. } => {
+                inner.shut_rd || has_data(index) || (self.kind == SockType::Stream && is_eof(index))
+            }
+        }
+    }
+
+    fn write_ready(&self) -> bool {
+        let state = match self.inner.exclusive_access().state {
+            State::Open { index, .. } => Some(index),
+            _ => None,
+        };
+        match state {
+            Some(index) if self.kind == SockType::Stream => {
+                is_eof(index) || !is_syn_sent(index) && self.send_room(index) > 0
+            }
+            _ => true,
+        }
+    }
+
+    fn hung_up(&self) -> bool {
+        match self.inner.exclusive_access().state {
+            State::Open { index, .. } => self.kind == SockType::Stream && is_eof(index),
+            _ => false,
+        }
+    }
+
+    fn stat(&self) -> Stat {
+        Stat::new(S_IFSOCK | 0o777)
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
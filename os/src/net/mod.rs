@@ -1,20 +1,48 @@
+mod arp;
+#[cfg(feature = "netbench")]
+mod bench;
+mod dhcp;
+mod dns;
+mod frame;
+mod icmp;
+mod iface;
+mod inet;
+mod netd;
 pub mod port_table;
 pub mod socket;
 pub mod tcp;
 pub mod udp;
+mod unix;
 
+#[cfg(feature = "netbench")]
+pub use bench::{
+    netbench_report, netbench_start, netbench_stop, start_netbench, BenchMode, BenchRun,
+};
+pub use dhcp::{dhcp_start, dhcp_state, dhcp_stop, DhcpState};
+pub use dns::{lookup, nameserver, set_nameserver};
+pub use iface::{interface, interfaces, is_loopback, IfConfig, NetInterface};
+pub use inet::{InetSocket, SockType};
 pub use lose_net_stack::IPv4;
+pub use netd::start_netd;
+pub use unix::{Rights, UnixSocket};
 
-use alloc::{sync::Arc, vec};
+use alloc::sync::Arc;
 use lose_net_stack::{results::Packet, LoseStack, MacAddress, TcpFlags};
 
 use crate::{
-    drivers::NET_DEVICE,
-    net::socket::{get_socket, push_data},
+    drivers::Mbuf,
+    net::socket::{get_socket, push_all, push_data},
     sync::UPIntrFreeCell,
 };
 
-use self::{port_table::check_accept, socket::set_s_a_by_index};
+use self::iface::{our_mac, route, DEFAULT_CONFIG};
+
+use self::port_table::check_accept;
+use self::socket::{
+    get_s_a_by_index, is_syn_sent, reset_send_window, set_eof, set_s_a_by_index, set_send_window,
+    set_syn_sent, Protocol,
+};
+use self::tcp::{send_reset, send_tcp};
 
 pub struct NetStack(UPIntrFreeCell<LoseStack>);
 
@@ -22,8 +50,8 @@ impl NetStack {
     pub fn new() -> Self {
         unsafe {
             NetStack(UPIntrFreeCell::new(LoseStack::new(
-                IPv4::new(10, 0, 2, 15),
-                MacAddress::new([0x52, 0x54, 0x00, 0x12, 0x34, 0x56]),
+                DEFAULT_CONFIG.ip,
+                MacAddress::new(our_mac()),
             )))
         }
     }
@@ -33,36 +61,59 @@ lazy_static::lazy_static! {
     static ref LOSE_NET_STACK: Arc<NetStack> = Arc::new(NetStack::new());
 }
 
-pub fn net_interrupt_handler() {
-    let mut recv_buf = vec![0u8; 1024];
+/// Our address.
+pub fn local_ip() -> IPv4 {
+    LOSE_NET_STACK.0.exclusive_access().ip
+}
 
-    let len = NET_DEVICE.receive(&mut recv_buf);
+/// Take in a frame `iface` received, counting on it what is dropped for
+/// want of room. The data sockets take in stays in the buffer of the
+/// frame.
+fn handle_frame(iface: &dyn NetInterface, frame: &Mbuf) {
+    if dhcp::receive(frame) {
+        return;
+    }
+    if let Some((packet, sender, mac)) = icmp::receive(frame, local_ip()) {
+        arp::learn(sender, mac);
+        iface.count_drops(push_all(Protocol::Icmp, sender, frame.share(packet)));
+        if let Some(reply) = icmp::echo_reply(frame) {
+            if let Some(iface) = route(sender) {
+                iface.transmit(reply);
+            }
+        }
+        return;
+    }
 
-    let packet = LOSE_NET_STACK
-        .0
-        .exclusive_access()
-        .analysis(&recv_buf[..len]);
+    let packet = LOSE_NET_STACK.0.exclusive_access().analysis(frame);
 
     // println!("[kernel] receive a packet");
-    // hexdump(&recv_buf[..len]);
+    // hexdump(frame);
 
     match packet {
         Packet::ARP(arp_packet) => {
+            arp::learn(arp_packet.sender_ip, arp_packet.sender_mac);
             let lose_stack = LOSE_NET_STACK.0.exclusive_access();
-            let reply_packet = arp_packet
-                .reply_packet(lose_stack.ip, lose_stack.mac)
-                .expect("can't build reply");
-            let reply_data = reply_packet.build_data();
-            NET_DEVICE.transmit(&reply_data)
+            // only requests for our address have a reply
+            if let Ok(reply_packet) = arp_packet.reply_packet(lose_stack.ip, lose_stack.mac) {
+                let reply_data = reply_packet.build_data();
+                drop(lose_stack);
+                let iface = route(arp_packet.sender_ip);
+                if let (Some(iface), Some(reply)) = (iface, Mbuf::from_slice(&reply_data)) {
+                    iface.transmit(reply);
+                }
+            }
         }
 
         Packet::UDP(udp_packet) => {
             let target = udp_packet.source_ip;
             let lport = udp_packet.dest_port;
             let rport = udp_packet.source_port;
+            arp::learn(target, udp_packet.source_mac);
 
-            if let Some(socket_index) = get_socket(target, lport, rport) {
-                push_data(socket_index, udp_packet.data.to_vec());
+            if let Some(socket_index) = get_socket(Protocol::Udp, target, lport, rport) {
+                if !push_data(socket_index, target, rport, frame.share(udp_packet.data)) {
+                    iface.count_drops(1);
+                }
             }
         }
 
@@ -71,30 +122,80 @@ pub fn net_interrupt_handler() {
             let lport = tcp_packet.dest_port;
             let rport = tcp_packet.source_port;
             let flags = tcp_packet.flags;
+            arp::learn(target, tcp_packet.source_mac);
 
+            let index = get_socket(Protocol::Tcp, target, lport, rport);
             if flags.contains(TcpFlags::S) {
-                // if it has a port to accept, then response the request
-                if check_accept(lport, &tcp_packet).is_some() {
-                    let mut reply_packet = tcp_packet.ack();
-                    reply_packet.flags = TcpFlags::S | TcpFlags::A;
-                    NET_DEVICE.transmit(&reply_packet.build_data());
+                match index {
+                    // the peer answers our SYN
+                    Some(index) if is_syn_sent(index) => {
+                        let (seq, _) = get_s_a_by_index(index).unwrap();
+                        if flags.contains(TcpFlags::A) && tcp_packet.ack == seq {
+                            let ack = tcp_packet.seq.wrapping_add(1);
+                            set_s_a_by_index(index, seq, ack);
+                            reset_send_window(index, tcp_packet.win as usize);
+                            set_syn_sent(index, false);
+                            send_tcp(lport, target, rport, seq, ack, TcpFlags::A, &[]);
+                        }
+                    }
+                    // our SYN|ACK was lost
+                    Some(index) => {
+                        let (seq, ack) = get_s_a_by_index(index).unwrap();
+                        let flags = TcpFlags::S | TcpFlags::A;
+                        send_tcp(lport, target, rport, seq.wrapping_sub(1), ack, flags, &[]);
+                    }
+                    // if it has a port to accept, then response the request
+                    None => {
+                        if check_accept(lport, &tcp_packet).is_none() {
+                            send_reset(&tcp_packet);
+                        }
+                    }
                 }
                 return;
-            } else if tcp_packet.flags.contains(TcpFlags::F) {
-                // tcp disconnected
-                let reply_packet = tcp_packet.ack();
-                NET_DEVICE.transmit(&reply_packet.build_data());
-
-                let mut end_packet = reply_packet.ack();
-                end_packet.flags |= TcpFlags::F;
-                NET_DEVICE.transmit(&end_packet.build_data());
-            } else if tcp_packet.flags.contains(TcpFlags::A) && tcp_packet.data_len == 0 {
+            }
+            let index = match index {
+                Some(index) => index,
+                None => {
+                    if !flags.contains(TcpFlags::R) {
+                        send_reset(&tcp_packet);
+                    }
+                    return;
+                }
+            };
+            if flags.contains(TcpFlags::R) {
+                set_eof(index);
+                return;
+            }
+            if is_syn_sent(index) {
                 return;
             }
 
-            if let Some(socket_index) = get_socket(target, lport, rport) {
-                push_data(socket_index, tcp_packet.data.to_vec());
-                set_s_a_by_index(socket_index, tcp_packet.seq, tcp_packet.ack);
+            if flags.contains(TcpFlags::A) {
+                set_send_window(index, tcp_packet.ack, tcp_packet.win as usize);
+            }
+            let (seq, mut ack) = get_s_a_by_index(index).unwrap();
+            let data_len = tcp_packet.data.len();
+            // what is out of order is dropped, for the peer to send again, and
+            // so is what does not fit the window we told
+            if tcp_packet.seq == ack {
+                let taken =
+                    data_len == 0 || push_data(index, target, rport, frame.share(tcp_packet.data));
+                if !taken {
+                    iface.count_drops(1);
+                } else {
+                    ack = ack.wrapping_add(data_len as u32);
+                    if flags.contains(TcpFlags::F) {
+                        ack = ack.wrapping_add(1);
+                        set_eof(index);
+                    }
+                    set_s_a_by_index(index, seq, ack);
+                }
+            }
+            // a probe of the window, from before what we expect, is answered
+            // as any data is
+            let probe = data_len == 0 && (ack.wrapping_sub(tcp_packet.seq) as i32) > 0;
+            if data_len > 0 || flags.contains(TcpFlags::F) || probe {
+                send_tcp(lport, target, rport, seq, ack, TcpFlags::A, &[]);
             }
         }
         _ => {}
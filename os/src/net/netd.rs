@@ -0,0 +1,86 @@
+//! netd, the kernel thread running the network stack. It takes in the
+//! frames of each interface as the card's interrupt or the loopback tells
+//! of them, and wakes on a timer as the ARP cache ages and for DHCP to go
+//! on, so that nobody polls the card.
+
+use super::iface::interfaces;
+use super::{arp, dhcp, handle_frame};
+use crate::drivers::NET_DEVICE;
+use crate::sync::UPIntrFreeCell;
+use crate::task::TaskControlBlock;
+use crate::task::{block_current_task, schedule, spawn_kernel_thread, wakeup_task};
+use crate::timer::{add_timer_call, ktime_now};
+use alloc::sync::Arc;
+use lazy_static::*;
+use riscv::register::sstatus;
+
+struct Netd {
+    task: Option<Arc<TaskControlBlock>>,
+    sleeping: bool,
+    /// When the timer armed last is due, if it is yet to be.
+    timer_ns: Option<u64>,
+}
+
+lazy_static! {
+    static ref NETD: UPIntrFreeCell<Netd> = unsafe {
+        UPIntrFreeCell::new(Netd {
+            task: None,
+            sleeping: false,
+            timer_ns: None,
+        })
+    };
+}
+
+pub fn start_netd() {
+    NETD.exclusive_access().task = Some(spawn_kernel_thread("netd", netd));
+    if let Some(device) = NET_DEVICE.as_ref() {
+        device.on_receive(wakeup_netd);
+        dhcp::dhcp_start();
+    }
+}
+
+pub(super) fn wakeup_netd() {
+    let mut netd = NETD.exclusive_access();
+    if netd.sleeping {
+        netd.sleeping = false;
+        wakeup_task(Arc::clone(netd.task.as_ref().unwrap()));
+    }
+}
+
+fn netd() -> ! {
+    unsafe {
+        sstatus::set_sie();
+    }
+    loop {
+        for iface in interfaces() {
+            while let Some(frame) = iface.receive() {
+                handle_frame(iface.as_ref(), &frame);
+            }
+        }
+        let now = ktime_now();
+        let deadline = [arp::expire(now), dhcp::poll(now)]
+            .into_iter()
+            .flatten()
+            .min();
+        let task_cx_ptr = NETD.exclusive_session(|netd| {
+            if netd.timer_ns.map_or(false, |timer_ns| timer_ns <= now) {
+                netd.timer_ns = None;
+            }
+            if let Some(deadline) = deadline {
+                if netd.timer_ns.map_or(true, |timer_ns| deadline < timer_ns) {
+                    netd.timer_ns = Some(deadline);
+                    add_timer_call(deadline, wakeup_netd);
+                }
+            }
+            // with interrupts off, no frame arrives before we sleep
+            if interfaces().iter().any(|iface| iface.can_receive()) {
+                return None;
+            }
+            netd.sleeping = true;
+            Some(block_current_task())
+        });
+        if let Some(task_cx_ptr) = task_cx_ptr {
+            schedule(task_cx_ptr);
+        }
+    }
+}
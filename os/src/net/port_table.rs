@@ -1,17 +1,26 @@
-use alloc::{sync::Arc, vec::Vec};
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
 use lazy_static::lazy_static;
 use lose_net_stack::packets::tcp::TCPPacket;
 
-use crate::fs::File;
 use crate::sync::UPIntrFreeCell;
-use crate::task::TaskControlBlock;
+use crate::timer::get_time;
 
-use super::tcp::TCP;
+use super::socket::{
+    add_socket, remove_socket, reset_send_window, set_rcvbuf, set_s_a_by_index, Protocol,
+};
+use super::tcp::send_tcp;
+use lose_net_stack::TcpFlags;
 
+/// A port a TCP socket listens on.
 pub struct Port {
     pub port: u16,
-    pub receivable: bool,
-    pub schedule: Option<Arc<TaskControlBlock>>,
+    /// Connections to queue at most before accept takes them.
+    pub backlog: usize,
+    /// Socket table indices of the connections yet to be accepted.
+    pub pending: VecDeque<usize>,
+    /// The receive buffer of the connections, told in their SYN|ACK.
+    pub rcvbuf: usize,
 }
 
 lazy_static! {
@@ -19,8 +28,13 @@ lazy_static! {
         unsafe { UPIntrFreeCell::new(Vec::new()) };
 }
 
-pub fn listen(port: u16) -> Option<usize> {
+/// Listen on `port`, the connections holding `rcvbuf` bytes of what
+/// arrives; None if something already does.
+pub fn listen(port: u16, backlog: usize, rcvbuf: usize) -> Option<usize> {
     let mut listen_table = LISTEN_TABLE.exclusive_access();
+    if listen_table.iter().flatten().any(|x| x.port == port) {
+        return None;
+    }
     let mut index = usize::MAX;
     for i in 0..listen_table.len() {
         if listen_table[i].is_none() {
@@ -31,8 +45,9 @@ pub fn listen(port: u16) -> Option<usize> {
 
     let listen_port = Port {
         port,
-        receivable: false,
-        schedule: None,
+        backlog: backlog.max(1),
+        pending: VecDeque::new(),
+        rcvbuf,
     };
 
     if index == usize::MAX {
@@ -44,98 +59,81 @@ pub fn listen(port: u16) -> Option<usize> {
     }
 }
 
-// can accept request
-pub fn accept(listen_index: usize, task: Arc<TaskControlBlock>) {
-    let mut listen_table = LISTEN_TABLE.exclusive_access();
-    assert!(listen_index < listen_table.len());
-    let listen_port = listen_table[listen_index].as_mut();
-    assert!(listen_port.is_some());
-    let listen_port = listen_port.unwrap();
-    listen_port.receivable = true;
-    listen_port.schedule = Some(task);
+/// Stop listening, dropping the connections nobody accepted.
+pub fn unlisten(listen_index: usize) {
+    if let Some(port) = LISTEN_TABLE.exclusive_access()[listen_index].take() {
+        for index in port.pending {
+            remove_socket(index);
+        }
+    }
 }
 
-pub fn port_acceptable(listen_index: usize) -> bool {
-    let mut listen_table = LISTEN_TABLE.exclusive_access();
-    assert!(listen_index < listen_table.len());
-
-    let listen_port = listen_table[listen_index].as_mut();
-    listen_port.map_or(false, |x| x.receivable)
+pub fn set_listen_rcvbuf(listen_index: usize, rcvbuf: usize) {
+    LISTEN_TABLE.exclusive_access()[listen_index]
+        .as_mut()
+        .unwrap()
+        .rcvbuf = rcvbuf;
 }
 
-// check whether it can accept request
-pub fn check_accept(port: u16, tcp_packet: &TCPPacket) -> Option<()> {
-    LISTEN_TABLE.exclusive_session(|listen_table| {
-        let mut listen_ports: Vec<&mut Option<Port>> = listen_table
-            .iter_mut()
-            .filter(|x| match x {
-                Some(t) => t.port == port && t.receivable == true,
-                None => false,
-            })
-            .collect();
-        if listen_ports.len() == 0 {
-            None
-        } else {
-            let listen_port = listen_ports[0].as_mut().unwrap();
-            let task = listen_port.schedule.clone().unwrap();
-            // wakeup_task(Arc::clone(&listen_port.schedule.clone().unwrap()));
-            listen_port.schedule = None;
-            listen_port.receivable = false;
+/// Whether anybody listens on `port`.
+pub fn port_listening(port: u16) -> bool {
+    LISTEN_TABLE
+        .exclusive_access()
+        .iter()
+        .flatten()
+        .any(|x| x.port == port)
+}
 
-            accept_connection(port, tcp_packet, task);
-            Some(())
-        }
-    })
+/// The socket table index of a connection to accept, if one is there.
+pub fn take_connection(listen_index: usize) -> Option<usize> {
+    let mut listen_table = LISTEN_TABLE.exclusive_access();
+    listen_table[listen_index]
+        .as_mut()
+        .unwrap()
+        .pending
+        .pop_front()
 }
 
-pub fn accept_connection(_port: u16, tcp_packet: &TCPPacket, task: Arc<TaskControlBlock>) {
-    let process = task.process.upgrade().unwrap();
-    let mut inner = process.inner_exclusive_access();
-    let fd = inner.alloc_fd();
+pub fn has_connection(listen_index: usize) -> bool {
+    let listen_table = LISTEN_TABLE.exclusive_access();
+    !listen_table[listen_index]
+        .as_ref()
+        .unwrap()
+        .pending
+        .is_empty()
+}
 
-    let tcp_socket = TCP::new(
+// check whether it can accept request
+pub fn check_accept(port: u16, tcp_packet: &TCPPacket) -> Option<()> {
+    let mut listen_table = LISTEN_TABLE.exclusive_access();
+    let listen_port = listen_table
+        .iter_mut()
+        .flatten()
+        .find(|x| x.port == port && x.pending.len() < x.backlog)?;
+    let index = add_socket(
+        Protocol::Tcp,
+        tcp_packet.source_ip,
+        port,
+        tcp_packet.source_port,
+    )?;
+    listen_port.pending.push_back(index);
+    let rcvbuf = listen_port.rcvbuf;
+    drop(listen_table);
+    set_rcvbuf(index, rcvbuf);
+
+    // the SYN takes a sequence number of each side
+    let isn = get_time() as u32;
+    let ack = tcp_packet.seq.wrapping_add(1);
+    set_s_a_by_index(index, isn.wrapping_add(1), ack);
+    reset_send_window(index, tcp_packet.win as usize);
+    send_tcp(
+        port,
         tcp_packet.source_ip,
-        tcp_packet.dest_port,
         tcp_packet.source_port,
-        tcp_packet.seq,
-        tcp_packet.ack,
+        isn,
+        ack,
+        TcpFlags::S | TcpFlags::A,
+        &[],
     );
-
-    inner.fd_table[fd] = Some(Arc::new(tcp_socket));
-
-    let cx = task.inner_exclusive_access().get_trap_cx();
-    cx.x[10] = fd;
-}
-
-// store in the fd_table, delete the listen table when close the application.
-pub struct PortFd(usize);
-
-impl PortFd {
-    pub fn new(port_index: usize) -> Self {
-        PortFd(port_index)
-    }
-}
-
-impl Drop for PortFd {
-    fn drop(&mut self) {
-        LISTEN_TABLE.exclusive_access()[self.0] = None
-    }
-}
-
-impl File for PortFd {
-    fn readable(&self) -> bool {
-        false
-    }
-
-    fn writable(&self) -> bool {
-        false
-    }
-
-    fn read(&self, _buf: crate::mm::UserBuffer) -> usize {
-        0
-    }
-
-    fn write(&self, _buf: crate::mm::UserBuffer) -> usize {
-        0
-    }
+    Some(())
 }
@@ -3,16 +3,54 @@ use alloc::vec::Vec;
 use lazy_static::lazy_static;
 use lose_net_stack::IPv4;
 
+use crate::drivers::Mbuf;
 use crate::sync::UPIntrFreeCell;
 
-// TODO: specify the protocol, TCP or UDP
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+    /// A raw socket, taking every ICMP packet; it has no ports.
+    Icmp,
+}
+
+/// How many bytes a socket holds of what arrived, or lets be in flight
+/// unacknowledged, until told otherwise.
+pub const SOCK_BUF_DEFAULT: usize = 65536;
+pub const SOCK_BUF_MIN: usize = 2048;
+pub const SOCK_BUF_MAX: usize = 1 << 20;
+
+/// What arrived for a socket, from whom, in the buffer of its frame.
+pub struct Received {
+    pub raddr: IPv4,
+    pub rport: u16,
+    pub data: Mbuf,
+}
+
+/// The end of a connection, or of a UDP socket taking from anybody while
+/// `rport` is 0.
 pub struct Socket {
-    pub raddr: IPv4,                // remote address
-    pub lport: u16,                 // local port
-    pub rport: u16,                 // rempote port
-    pub buffers: VecDeque<Vec<u8>>, // datas
+    pub proto: Protocol,
+    pub raddr: IPv4,                 // remote address
+    pub lport: u16,                  // local port
+    pub rport: u16,                  // rempote port
+    pub buffers: VecDeque<Received>, // datas
+    /// The bytes in `buffers`.
+    pub queued: usize,
+    /// How many bytes `buffers` may hold; what comes beyond is dropped.
+    pub rcvbuf: usize,
+    /// The first sequence number of ours the peer has not acknowledged.
+    pub snd_una: u32,
+    /// How many bytes from `snd_una` the peer takes.
+    pub snd_wnd: usize,
+    /// The next sequence number we send.
     pub seq: u32,
+    /// The next sequence number expected of the peer.
     pub ack: u32,
+    /// The peer sent FIN or RST, nothing more is coming.
+    pub eof: bool,
+    /// Our SYN is out, and the SYN|ACK of the peer is awaited.
+    pub syn_sent: bool,
 }
 
 lazy_static! {
@@ -44,28 +82,44 @@ pub fn set_s_a_by_index(index: usize, seq: u32, ack: u32) {
     sock.seq = seq;
 }
 
-pub fn get_socket(raddr: IPv4, lport: u16, rport: u16) -> Option<usize> {
+/// The socket of the connection, else the one taking from anybody on
+/// `lport`.
+pub fn get_socket(proto: Protocol, raddr: IPv4, lport: u16, rport: u16) -> Option<usize> {
     let socket_table = SOCKET_TABLE.exclusive_access();
-    for i in 0..socket_table.len() {
-        let sock = &socket_table[i];
-        if sock.is_none() {
-            continue;
-        }
+    let find = |connected: bool| {
+        socket_table.iter().position(|sock| match sock {
+            Some(sock) if sock.proto == proto && sock.lport == lport => {
+                if connected {
+                    sock.raddr == raddr && sock.rport == rport
+                } else {
+                    sock.rport == 0
+                }
+            }
+            _ => false,
+        })
+    };
+    find(true).or_else(|| find(false))
+}
 
-        let sock = sock.as_ref().unwrap();
-        if sock.raddr == raddr && sock.lport == lport && sock.rport == rport {
-            return Some(i);
-        }
-    }
-    None
+/// Whether a socket of `proto` is on `lport`.
+pub fn port_in_use(proto: Protocol, lport: u16) -> bool {
+    SOCKET_TABLE
+        .exclusive_access()
+        .iter()
+        .flatten()
+        .any(|sock| sock.proto == proto && sock.lport == lport)
 }
 
-pub fn add_socket(raddr: IPv4, lport: u16, rport: u16) -> Option<usize> {
-    if get_socket(raddr, lport, rport).is_some() {
+pub fn add_socket(proto: Protocol, raddr: IPv4, lport: u16, rport: u16) -> Option<usize> {
+    let mut socket_table = SOCKET_TABLE.exclusive_access();
+    if proto != Protocol::Icmp
+        && socket_table.iter().flatten().any(|sock| {
+            sock.proto == proto && sock.raddr == raddr && sock.lport == lport && sock.rport == rport
+        })
+    {
         return None;
     }
 
-    let mut socket_table = SOCKET_TABLE.exclusive_access();
     let mut index = usize::MAX;
     for i in 0..socket_table.len() {
         if socket_table[i].is_none() {
@@ -75,12 +129,19 @@ pub fn add_socket(raddr: IPv4, lport: u16, rport: u16) -> Option<usize> {
     }
 
     let socket = Socket {
+        proto,
         raddr,
         lport,
         rport,
         buffers: VecDeque::new(),
+        queued: 0,
+        rcvbuf: SOCK_BUF_DEFAULT,
+        snd_una: 0,
+        snd_wnd: u16::MAX as usize,
         seq: 0,
         ack: 0,
+        eof: false,
+        syn_sent: false,
     };
 
     if index == usize::MAX {
@@ -92,6 +153,21 @@ pub fn add_socket(raddr: IPv4, lport: u16, rport: u16) -> Option<usize> {
     }
 }
 
+/// The remote address and port of the socket of `index`.
+pub fn peer(index: usize) -> (IPv4, u16) {
+    let socket_table = SOCKET_TABLE.exclusive_access();
+    let sock = socket_table[index].as_ref().unwrap();
+    (sock.raddr, sock.rport)
+}
+
+/// Have the socket of `index` take only from `raddr`:`rport`.
+pub fn set_remote(index: usize, raddr: IPv4, rport: u16) {
+    let mut socket_table = SOCKET_TABLE.exclusive_access();
+    let sock = socket_table[index].as_mut().unwrap();
+    sock.raddr = raddr;
+    sock.rport = rport;
+}
+
 pub fn remove_socket(index: usize) {
     let mut socket_table = SOCKET_TABLE.exclusive_access();
 
@@ -100,7 +176,21 @@ pub fn remove_socket(index: usize) {
     socket_table[index] = None;
 }
 
-pub fn push_data(index: usize, data: Vec<u8>) {
+impl Socket {
+    /// Queue `received`, unless it does not fit; whether it did.
+    fn push(&mut self, received: Received) -> bool {
+        if self.queued + received.data.len() > self.rcvbuf {
+            return false;
+        }
+        self.queued += received.data.len();
+        self.buffers.push_back(received);
+        true
+    }
+}
+
+/// Queue `data` for the socket of `index`; false if it was dropped, the
+/// receive buffer being full.
+pub fn push_data(index: usize, raddr: IPv4, rport: u16, data: Mbuf) -> bool {
     let mut socket_table = SOCKET_TABLE.exclusive_access();
 
     assert!(socket_table.len() > index);
@@ -109,15 +199,121 @@ pub fn push_data(index: usize, data: Vec<u8>) {
     socket_table[index]
         .as_mut()
         .unwrap()
-        .buffers
-        .push_back(data);
+        .push(Received { raddr, rport, data })
+}
+
+/// Give every socket of `proto` `data` from `raddr`, which they share; how
+/// many had no room for it.
+pub fn push_all(proto: Protocol, raddr: IPv4, data: Mbuf) -> usize {
+    let mut socket_table = SOCKET_TABLE.exclusive_access();
+    let mut dropped = 0;
+    for sock in socket_table.iter_mut().flatten() {
+        if sock.proto == proto {
+            let received = Received {
+                raddr,
+                rport: 0,
+                data: data.clone(),
+            };
+            if !sock.push(received) {
+                dropped += 1;
+            }
+        }
+    }
+    dropped
 }
 
-pub fn pop_data(index: usize) -> Option<Vec<u8>> {
+pub fn pop_data(index: usize) -> Option<Received> {
     let mut socket_table = SOCKET_TABLE.exclusive_access();
 
     assert!(socket_table.len() > index);
     assert!(socket_table[index].is_some());
 
-    socket_table[index].as_mut().unwrap().buffers.pop_front()
+    let sock = socket_table[index].as_mut().unwrap();
+    let received = sock.buffers.pop_front()?;
+    sock.queued -= received.data.len();
+    Some(received)
+}
+
+/// Put back what was not read of a stream, to be read first.
+pub fn unpop_data(index: usize, received: Received) {
+    let mut socket_table = SOCKET_TABLE.exclusive_access();
+    let sock = socket_table[index].as_mut().unwrap();
+    sock.queued += received.data.len();
+    sock.buffers.push_front(received);
+}
+
+/// How many more bytes the socket of `index` takes in.
+pub fn free_space(index: usize) -> usize {
+    let socket_table = SOCKET_TABLE.exclusive_access();
+    let sock = socket_table[index].as_ref().unwrap();
+    sock.rcvbuf.saturating_sub(sock.queued)
+}
+
+pub fn set_rcvbuf(index: usize, rcvbuf: usize) {
+    SOCKET_TABLE.exclusive_access()[index]
+        .as_mut()
+        .unwrap()
+        .rcvbuf = rcvbuf;
+}
+
+/// The first byte of ours the peer of the socket of `index` has not
+/// acknowledged, and how many it takes from there.
+pub fn send_window(index: usize) -> (u32, usize) {
+    let socket_table = SOCKET_TABLE.exclusive_access();
+    let sock = socket_table[index].as_ref().unwrap();
+    (sock.snd_una, sock.snd_wnd)
+}
+
+/// Take in that the peer acknowledged all before `ack` and takes `window`
+/// bytes from there, unless `ack` is not of what is in flight.
+pub fn set_send_window(index: usize, ack: u32, window: usize) {
+    let mut socket_table = SOCKET_TABLE.exclusive_access();
+    let sock = socket_table[index].as_mut().unwrap();
+    if ack.wrapping_sub(sock.snd_una) <= sock.seq.wrapping_sub(sock.snd_una) {
+        sock.snd_una = ack;
+        sock.snd_wnd = window;
+    }
+}
+
+/// Have all sent by the socket of `index` so far count as acknowledged,
+/// once the connection is made.
+pub fn reset_send_window(index: usize, window: usize) {
+    let mut socket_table = SOCKET_TABLE.exclusive_access();
+    let sock = socket_table[index].as_mut().unwrap();
+    sock.snd_una = sock.seq;
+    sock.snd_wnd = window;
+}
+
+/// Whether data waits in the socket of `index`.
+pub fn has_data(index: usize) -> bool {
+    let socket_table = SOCKET_TABLE.exclusive_access();
+
+    assert!(socket_table.len() > index);
+    assert!(socket_table[index].is_some());
+
+    !socket_table[index].as_ref().unwrap().buffers.is_empty()
+}
+
+/// Whether the peer of the socket of `index` is done sending.
+pub fn is_eof(index: usize) -> bool {
+    SOCKET_TABLE.exclusive_access()[index].as_ref().unwrap().eof
+}
+
+pub fn set_eof(index: usize) {
+    SOCKET_TABLE.exclusive_access()[index].as_mut().unwrap().eof = true;
+}
+
+/// Whether the socket of `index` still awaits the SYN|ACK of its peer.
+pub fn is_syn_sent(index: usize) -> bool {
+    SOCKET_TABLE.exclusive_access()[index]
+        .as_ref()
+        .unwrap()
+        .syn_sent
+}
+
+pub fn set_syn_sent(index: usize, syn_sent: bool) {
+    SOCKET_TABLE.exclusive_access()[index]
+        .as_mut()
+        .unwrap()
+        .syn_sent = syn_sent;
 }
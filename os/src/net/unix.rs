@@ -0,0 +1,578 @@
+//! Sockets of AF_UNIX, for the processes of this machine to talk through:
+//! SOCK_STREAM connections and SOCK_DGRAM messages, with no network in
+//! between.
+//!
+//! A socket is bound to a path by making a socket file there, which stays
+//! until it is unlinked; connecting or sending to the path finds the
+//! socket through the inode. A connection is made at once, queued on the
+//! listener until accept takes it. Each socket has a queue of what is sent
+//! to it, holding at most its receive buffer; senders wait for room.
+//!
+//! sendmsg may send files along with the data, SCM_RIGHTS: they travel as
+//! the open files themselves, and recvmsg gives them new descriptors. A
+//! file sent on a socket and never taken is closed with the socket, but a
+//! socket sent on itself stays until the other side takes it.
+
+use super::inet::SockType;
+use super::socket::{SOCK_BUF_DEFAULT, SOCK_BUF_MAX, SOCK_BUF_MIN};
+use crate::fs::{lookup, may_access, mksock, File, Inode, OpenFlags, Stat, MAY_WRITE, S_IFSOCK};
+use crate::mm::UserBuffer;
+use crate::sync::UPIntrFreeCell;
+use crate::syscall::{
+    EADDRINUSE, EAGAIN, ECONNREFUSED, EDESTADDRREQ, EINVAL, EISCONN, EMSGSIZE, ENOENT, ENOTCONN,
+    EOPNOTSUPP, EPIPE,
+};
+use crate::task::{signal_pending, suspend_current_and_run_next, ERESTARTSYS};
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::any::Any;
+use lazy_static::*;
+
+/// Files sent along with data.
+pub type Rights = Vec<Arc<dyn File + Send + Sync>>;
+
+/// What is sent at once, and who sent it for a datagram.
+struct Message {
+    data: Vec<u8>,
+    rights: Rights,
+    from: Option<String>,
+}
+
+/// What is sent to a socket and not read yet.
+struct Queue {
+    messages: VecDeque<Message>,
+    /// The bytes in `messages`.
+    queued: usize,
+    capacity: usize,
+    /// The peer of the connection is done sending.
+    eof: bool,
+    /// The socket is closed, and takes nothing more.
+    closed: bool,
+}
+
+impl Queue {
+    fn new() -> Self {
+        Self {
+            messages: VecDeque::new(),
+            queued: 0,
+            capacity: SOCK_BUF_DEFAULT,
+            eof: false,
+            closed: false,
+        }
+    }
+
+    fn room(&self) -> usize {
+        self.capacity.saturating_sub(self.queued)
+    }
+
+    fn push(&mut self, message: Message) {
+        self.queued += message.data.len();
+        self.messages.push_back(message);
+    }
+}
+
+type QueueCell = Arc<UPIntrFreeCell<Queue>>;
+
+enum State {
+    Idle,
+    Listening {
+        backlog: usize,
+        pending: VecDeque<Arc<UnixSocket>>,
+    },
+    /// A connection, sending to `peer`, the queue of the other side, which
+    /// is bound to `peer_path` if at all.
+    Connected {
+        peer: QueueCell,
+        peer_path: Option<String>,
+    },
+    /// A datagram socket sending to `peer` by default.
+    Default {
+        peer: Weak<UnixSocket>,
+        peer_path: Option<String>,
+    },
+}
+
+struct UnixSocketInner {
+    state: State,
+    status: OpenFlags,
+    /// Where the socket is bound, and the socket file there.
+    path: Option<String>,
+    inode: Option<Arc<dyn Inode>>,
+    shut_rd: bool,
+    shut_wr: bool,
+    sndbuf: usize,
+}
+
+pub struct UnixSocket {
+    kind: SockType,
+    me: Weak<UnixSocket>,
+    queue: QueueCell,
+    inner: UPIntrFreeCell<UnixSocketInner>,
+}
+
+lazy_static! {
+    /// The sockets bound, by the address of their socket file.
+    static ref BOUND: UPIntrFreeCell<BTreeMap<usize, Weak<UnixSocket>>> =
+        unsafe { UPIntrFreeCell::new(BTreeMap::new()) };
+}
+
+fn inode_key(inode: &Arc<dyn Inode>) -> usize {
+    Arc::as_ptr(inode) as *const u8 as usize
+}
+
+/// The socket bound to `path`, if it is of `kind` and may be written to.
+fn find_bound(path: &str, kind: SockType) -> Result<Arc<UnixSocket>, isize> {
+    let inode = lookup(path).ok_or(ENOENT)?;
+    if inode.stat().mode & S_IFSOCK != S_IFSOCK {
+        return Err(ECONNREFUSED);
+    }
+    if !may_access(&inode, MAY_WRITE) {
+        return Err(-1);
+    }
+    let socket = BOUND
+        .exclusive_access()
+        .get(&inode_key(&inode))
+        .and_then(Weak::upgrade)
+        .ok_or(ECONNREFUSED)?;
+    match socket.kind == kind {
+        true => Ok(socket),
+        false => Err(ECONNREFUSED),
+    }
+}
+
+impl UnixSocket {
+    pub fn new(kind: SockType, status: OpenFlags) -> Arc<Self> {
+        Self::with_state(kind, State::Idle, status)
+    }
+
+    fn with_state(kind: SockType, state: State, status: OpenFlags) -> Arc<Self> {
+        Arc::new_cyclic(|me| Self {
+            kind,
+            me: me.clone(),
+            queue: Arc::new(unsafe { UPIntrFreeCell::new(Queue::new()) }),
+            inner: unsafe {
+                UPIntrFreeCell::new(UnixSocketInner {
+                    state,
+                    status: status & OpenFlags::NONBLOCK,
+                    path: None,
+                    inode: None,
+                    shut_rd: false,
+                    shut_wr: false,
+                    sndbuf: SOCK_BUF_DEFAULT,
+                })
+            },
+        })
+    }
+
+    /// Two sockets connected to each other, for socketpair.
+    pub fn pair(kind: SockType, status: OpenFlags) -> (Arc<Self>, Arc<Self>) {
+        let a = Self::new(kind, status);
+        let b = Self::new(kind, status);
+        let connect = |from: &Arc<Self>, to: &Arc<Self>| {
+            from.inner.exclusive_access().state = match kind {
+                SockType::Stream => State::Connected {
+                    peer: to.queue.clone(),
+                    peer_path: None,
+                },
+                _ => State::Default {
+                    peer: Arc::downgrade(to),
+                    peer_path: None,
+                },
+            };
+        };
+        connect(&a, &b);
+        connect(&b, &a);
+        (a, b)
+    }
+
+    /// Where the socket is bound, if anywhere.
+    pub fn path(&self) -> Option<String> {
+        self.inner.exclusive_access().path.clone()
+    }
+
+    /// Where the peer is bound, if connected.
+    pub fn peer_path(&self) -> Result<Option<String>, isize> {
+        match &self.inner.exclusive_access().state {
+            State::Connected { peer_path, .. } | State::Default { peer_path, .. } => {
+                Ok(peer_path.clone())
+            }
+            _ => Err(ENOTCONN),
+        }
+    }
+
+    /// Make the socket file `path`, and take what is sent to it.
+    pub fn bind(&self, path: &str) -> Result<(), isize> {
+        if self.path().is_some() {
+            return Err(EINVAL);
+        }
+        if lookup(path).is_some() {
+            return Err(EADDRINUSE);
+        }
+        let inode = mksock(path, 0o777).ok_or(-1isize)?;
+        BOUND
+            .exclusive_access()
+            .insert(inode_key(&inode), self.me.clone());
+        let mut inner = self.inner.exclusive_access();
+        inner.path = Some(String::from(path));
+        inner.inode = Some(inode);
+        Ok(())
+    }
+
+    pub fn listen(&self, backlog: usize) -> Result<(), isize> {
+        if self.kind != SockType::Stream {
+            return Err(EOPNOTSUPP);
+        }
+        let mut inner = self.inner.exclusive_access();
+        if inner.path.is_none() {
+            return Err(EINVAL);
+        }
+        match &mut inner.state {
+            State::Idle => {}
+            State::Listening { backlog: old, .. } => {
+                *old = backlog.max(1);
+                return Ok(());
+            }
+            _ => return Err(EINVAL),
+        }
+        inner.state = State::Listening {
+            backlog: backlog.max(1),
+            pending: VecDeque::new(),
+        };
+        Ok(())
+    }
+
+    /// A connection of the listening socket, with the status `status`.
+    pub fn accept(&self, status: OpenFlags) -> Result<Arc<UnixSocket>, isize> {
+        loop {
+            let mut inner = self.inner.exclusive_access();
+            let nonblock = inner.status.contains(OpenFlags::NONBLOCK);
+            match &mut inner.state {
+                State::Listening { pending, .. } => {
+                    if let Some(socket) = pending.pop_front() {
+                        socket.set_status(status);
+                        return Ok(socket);
+                    }
+                }
+                _ => return Err(EINVAL),
+            }
+            if nonblock {
+                return Err(EAGAIN);
+            }
+            drop(inner);
+            if signal_pending() {
+                return Err(ERESTARTSYS);
+            }
+            suspend_current_and_run_next();
+        }
+    }
+
+    /// Connect to the listener at `path`, or send datagrams there by
+    /// default.
+    pub fn connect(&self, path: &str) -> Result<(), isize> {
+        let target = find_bound(path, self.kind)?;
+        if self.kind != SockType::Stream {
+            self.inner.exclusive_access().state = State::Default {
+                peer: Arc::downgrade(&target),
+                peer_path: Some(String::from(path)),
+            };
+            return Ok(());
+        }
+        match self.inner.exclusive_access().state {
+            State::Idle => {}
+            State::Connected { .. } => return Err(EISCONN),
+            _ => return Err(EINVAL),
+        }
+        let rcvbuf = target.queue.exclusive_access().capacity;
+        loop {
+            let mut listener = target.inner.exclusive_access();
+            let listener_path = listener.path.clone();
+            let (backlog, pending) = match &mut listener.state {
+                State::Listening { backlog, pending } => (*backlog, pending),
+                _ => return Err(ECONNREFUSED),
+            };
+            if pending.len() < backlog {
+                // the side accept hands out
+                let server = Self::with_state(
+                    SockType::Stream,
+                    State::Connected {
+                        peer: self.queue.clone(),
+                        peer_path: self.path(),
+                    },
+                    OpenFlags::empty(),
+                );
+                server.queue.exclusive_access().capacity = rcvbuf;
+                pending.push_back(server.clone());
+                drop(listener);
+                self.inner.exclusive_access().state = State::Connected {
+                    peer: server.queue.clone(),
+                    peer_path: listener_path,
+                };
+                return Ok(());
+            }
+            drop(listener);
+            if self.status().contains(OpenFlags::NONBLOCK) {
+                return Err(EAGAIN);
+            }
+            if signal_pending() {
+                return Err(ERESTARTSYS);
+            }
+            suspend_current_and_run_next();
+        }
+    }
+
+    /// The queue to send to, `dest` if given for a datagram socket, and
+    /// the peer sending to it for a datagram socket.
+    fn peer_queue(&self, dest: Option<&str>) -> Result<QueueCell, isize> {
+        let inner = self.inner.exclusive_access();
+        match (&inner.state, dest) {
+            (State::Connected { peer, .. }, _) => Ok(peer.clone()),
+            (_, _) if self.kind == SockType::Stream => Err(ENOTCONN),
+            (_, Some(path)) => {
+                drop(inner);
+                Ok(find_bound(path, self.kind)?.queue.clone())
+            }
+            (State::Default { peer, .. }, None) => peer
+                .upgrade()
+                .map(|peer| peer.queue.clone())
+                .ok_or(ECONNREFUSED),
+            (_, None) => Err(EDESTADDRREQ),
+        }
+    }
+
+    /// Send `data` and `rights`, to `dest` if given for a datagram socket;
+    /// how much of `data` was sent, the rights going with the first of it.
+    pub fn send(&self, data: &[u8], rights: Rights, dest: Option<&str>) -> Result<usize, isize> {
+        if self.inner.exclusive_access().shut_wr {
+            return Err(EPIPE);
+        }
+        let queue = self.peer_queue(dest)?;
+        let from = self.path();
+        let stream = self.kind == SockType::Stream;
+        // nothing to carry the rights on a stream
+        if stream && data.is_empty() {
+            return Ok(0);
+        }
+        if !stream && data.len() > SOCK_BUF_MAX {
+            return Err(EMSGSIZE);
+        }
+        let mut rights = Some(rights);
+        let mut sent = 0;
+        loop {
+            let mut peer = queue.exclusive_access();
+            if peer.closed {
+                return if sent > 0 { Ok(sent) } else { Err(EPIPE) };
+            }
+            let room = peer.room();
+            // a datagram goes whole, a stream as it fits
+            let len = match stream {
+                true => room.min(data.len() - sent),
+                false if room >= data.len() || peer.messages.is_empty() => data.len(),
+                false => 0,
+            };
+            if len > 0 || data.is_empty() {
+                peer.push(Message {
+                    data: data[sent..sent + len].to_vec(),
+                    rights: rights.take().unwrap_or_default(),
+                    from: from.clone(),
+                });
+                sent += len;
+                if sent == data.len() {
+                    return Ok(sent);
+                }
+            }
+            drop(peer);
+            if self.status().contains(OpenFlags::NONBLOCK) {
+                return if sent > 0 { Ok(sent) } else { Err(EAGAIN) };
+            }
+            if signal_pending() {
+                return if sent > 0 { Ok(sent) } else { Err(ERESTARTSYS) };
+            }
+            suspend_current_and_run_next();
+        }
+    }
+
+    /// Take at most `len` bytes, the files sent with them and who sent
+    /// them; nothing once the peer of a stream is done or reading is shut
+    /// down. A stream read ends before the next files sent, so they come
+    /// with the first bytes sent with them.
+    pub fn recv(&self, len: usize) -> Result<(Vec<u8>, Rights, Option<String>), isize> {
+        loop {
+            let inner = self.inner.exclusive_access();
+            if inner.shut_rd {
+                return Ok((Vec::new(), Vec::new(), None));
+            }
+            let mut queue = self.queue.exclusive_access();
+            if let Some(mut message) = queue.messages.pop_front() {
+                let from = message.from.take();
+                let mut data = message.data;
+                let rights = core::mem::take(&mut message.rights);
+                if self.kind != SockType::Stream {
+                    queue.queued -= data.len();
+                    // the rest of a datagram is gone
+                    data.truncate(len);
+                    return Ok((data, rights, from));
+                }
+                if data.len() > len {
+                    message.data = data.split_off(len);
+                    queue.messages.push_front(message);
+                }
+                while data.len() < len {
+                    match queue.messages.front_mut() {
+                        Some(next) if next.rights.is_empty() => {
+                            let take = next.data.len().min(len - data.len());
+                            data.extend(next.data.drain(..take));
+                            if next.data.is_empty() {
+                                queue.messages.pop_front();
+                            }
+                        }
+                        _ => break,
+                    }
+                }
+                queue.queued -= data.len();
+                return Ok((data, rights, from));
+            }
+            if queue.eof {
+                return Ok((Vec::new(), Vec::new(), None));
+            }
+            drop(queue);
+            if inner.status.contains(OpenFlags::NONBLOCK) {
+                return Err(EAGAIN);
+            }
+            drop(inner);
+            if signal_pending() {
+                return Err(ERESTARTSYS);
+            }
+            suspend_current_and_run_next();
+        }
+    }
+
+    /// Stop reading if `read`, sending if `write`.
+    pub fn shutdown(&self, read: bool, write: bool) -> Result<(), isize> {
+        let mut inner = self.inner.exclusive_access();
+        let peer = match &inner.state {
+            State::Connected { peer, .. } => Some(peer.clone()),
+            State::Default { .. } => None,
+            _ => return Err(ENOTCONN),
+        };
+        inner.shut_rd |= read;
+        inner.shut_wr |= write;
+        drop(inner);
+        if let (true, Some(peer)) = (write, peer) {
+            peer.exclusive_access().eof = true;
+        }
+        Ok(())
+    }
+
+    /// The sizes of the send and the receive buffer.
+    pub fn buffer_sizes(&self) -> (usize, usize) {
+        let sndbuf = self.inner.exclusive_access().sndbuf;
+        (sndbuf, self.queue.exclusive_access().capacity)
+    }
+
+    /// Have the send buffer, or the receive buffer unless `send`, hold
+    /// `size` bytes, within SOCK_BUF_MIN and SOCK_BUF_MAX; what is sent to
+    /// the socket waits for room in the receive buffer, the send buffer
+    /// being only told.
+    pub fn set_buffer_size(&self, send: bool, size: usize) {
+        let size = size.clamp(SOCK_BUF_MIN, SOCK_BUF_MAX);
+        match send {
+            true => self.inner.exclusive_access().sndbuf = size,
+            false => self.queue.exclusive_access().capacity = size,
+        }
+    }
+}
+
+impl Drop for UnixSocket {
+    fn drop(&mut self) {
+        let mut queue = self.queue.exclusive_access();
+        queue.closed = true;
+        let messages = core::mem::take(&mut queue.messages);
+        drop(queue);
+        // the files sent and never taken are closed, outside the queue
+        drop(messages);
+        let mut inner = self.inner.exclusive_access();
+        let state = core::mem::replace(&mut inner.state, State::Idle);
+        let inode = inner.inode.take();
+        drop(inner);
+        if let State::Connected { peer, .. } = state {
+            peer.exclusive_access().eof = true;
+        }
+        if let Some(inode) = inode {
+            BOUND.exclusive_access().remove(&inode_key(&inode));
+        }
+    }
+}
+
+impl File for UnixSocket {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn read(&self, mut buf: UserBuffer) -> usize {
+        // files sent along are closed, as read takes no rights
+        let data = match self.recv(buf.len()) {
+            Ok((data, _, _)) => data,
+            Err(_) => return 0,
+        };
+        let mut copied = 0;
+        for buffer in buf.buffers.iter_mut() {
+            let len = buffer.len().min(data.len() - copied);
+            buffer[..len].copy_from_slice(&data[copied..copied + len]);
+            copied += len;
+        }
+        copied
+    }
+
+    fn write(&self, buf: UserBuffer) -> usize {
+        let mut data = Vec::with_capacity(buf.len());
+        for buffer in buf.buffers.iter() {
+            data.extend_from_slice(buffer);
+        }
+        self.send(&data, Vec::new(), None).unwrap_or(0)
+    }
+
+    fn status(&self) -> OpenFlags {
+        self.inner.exclusive_access().status
+    }
+
+    fn set_status(&self, status: OpenFlags) {
+        self.inner.exclusive_access().status = status & OpenFlags::NONBLOCK;
+    }
+
+    fn read_ready(&self) -> bool {
+        let inner = self.inner.exclusive_access();
+        if let State::Listening { pending, .. } = &inner.state {
+            return !pending.is_empty();
+        }
+        let queue = self.queue.exclusive_access();
+        inner.shut_rd || !queue.messages.is_empty() || queue.eof
+    }
+
+    fn write_ready(&self) -> bool {
+        match self.peer_queue(None) {
+            Ok(peer) => {
+                let peer = peer.exclusive_access();
+                peer.closed || peer.room() > 0
+            }
+            Err(_) => true,
+        }
+    }
+
+    fn hung_up(&self) -> bool {
+        self.kind == SockType::Stream && self.queue.exclusive_access().eof
+    }
+
+    fn stat(&self) -> Stat {
+        Stat::new(S_IFSOCK | 0o777)
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
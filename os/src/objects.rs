@@ -0,0 +1,82 @@
+//! Counts of kernel objects which live as long as something holds them:
+//! tasks, processes, inodes, open files and blocks cached by easy-fs.
+//!
+//! An object counted holds a `Live` of its kind, which counts it in when
+//! it is made and out when it is dropped, so an object a cycle of Arcs or
+//! a forgotten reference keeps alive shows as a count which only grows.
+//! sys_objstat gives the counts with how they changed since a mark, which
+//! usertests sets before each test to tell which left objects behind.
+
+use crate::sync::UPIntrFreeCell;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use lazy_static::*;
+
+pub const OBJ_TASK: usize = 0;
+pub const OBJ_PROCESS: usize = 1;
+/// inodes of easy-fs, tmpfs and FAT
+pub const OBJ_INODE: usize = 2;
+/// files of inodes opened
+pub const OBJ_FILE: usize = 3;
+/// easy-fs keeps the count of its block caches itself
+pub const OBJ_BLOCK_CACHE: usize = 4;
+pub const OBJ_KINDS: usize = 5;
+
+/// The counts of a kind, as sys_objstat gives them.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct ObjStat {
+    /// alive now
+    pub live: usize,
+    /// made since boot
+    pub made: usize,
+    /// how many more are alive than at the mark
+    pub delta: isize,
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicUsize = AtomicUsize::new(0);
+static LIVE: [AtomicUsize; OBJ_KINDS] = [ZERO; OBJ_KINDS];
+static MADE: [AtomicUsize; OBJ_KINDS] = [ZERO; OBJ_KINDS];
+
+lazy_static! {
+    /// The objects alive when the mark was set.
+    static ref MARK: UPIntrFreeCell<[usize; OBJ_KINDS]> =
+        unsafe { UPIntrFreeCell::new([0; OBJ_KINDS]) };
+}
+
+/// Counts the object holding it as one of `KIND` while it lives.
+pub struct Live<const KIND: usize>(());
+
+impl<const KIND: usize> Default for Live<KIND> {
+    fn default() -> Self {
+        LIVE[KIND].fetch_add(1, Ordering::Relaxed);
+        MADE[KIND].fetch_add(1, Ordering::Relaxed);
+        Self(())
+    }
+}
+
+impl<const KIND: usize> Drop for Live<KIND> {
+    fn drop(&mut self) {
+        LIVE[KIND].fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// The counts of each kind, then the mark set to them if `mark`.
+pub fn obj_stats(mark: bool) -> [ObjStat; OBJ_KINDS] {
+    let mut stats = [ObjStat::default(); OBJ_KINDS];
+    for (kind, stat) in stats.iter_mut().enumerate() {
+        stat.live = LIVE[kind].load(Ordering::Relaxed);
+        stat.made = MADE[kind].load(Ordering::Relaxed);
+    }
+    let (live, made) = easy_fs::block_cache_counts();
+    stats[OBJ_BLOCK_CACHE].live = live;
+    stats[OBJ_BLOCK_CACHE].made = made;
+    let mut marked = MARK.exclusive_access();
+    for (stat, marked) in stats.iter_mut().zip(marked.iter_mut()) {
+        stat.delta = stat.live as isize - *marked as isize;
+        if mark {
+            *marked = stat.live;
+        }
+    }
+    stats
+}
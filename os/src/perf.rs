@@ -0,0 +1,198 @@
+//! Performance counters of each task: the cycles, instructions retired
+//! and time a task ran for, and the hardware events the hpmcounters count
+//! meanwhile, if the SBI has its PMU extension to program them with.
+//!
+//! The counters count for the hart, whoever runs on it, so a task is
+//! given what they counted from when it was switched in to when it was
+//! switched out, both in the idle loop, which charges a task for its
+//! syscalls, faults and the interrupts it took as well. sys_perf_read
+//! reads them for a thread or for a whole process, which keeps what its
+//! threads counted once they exited.
+//!
+//! User mode may read cycle, time and instret itself, which count for the
+//! hart and whoever ran on it; the hpmcounters are the kernel's.
+
+use crate::config::MAX_HARTS;
+use crate::mm::hart_id;
+use crate::sbi::{pmu_counter_csr, pmu_num_counters, pmu_start_matching};
+use crate::sync::UPIntrFreeCell;
+use crate::task::TaskControlBlock;
+use crate::timer::ktime_raw;
+use core::arch::asm;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use lazy_static::*;
+
+/// The hardware events counted, of the general ones of the SBI: cache
+/// references and misses, branches and branches mispredicted.
+pub const PERF_EVENTS: usize = 4;
+const EVENT_CODES: [usize; PERF_EVENTS] = [3, 4, 5, 6];
+
+/// scounteren: cycle, time and instret readable in user mode.
+const SCOUNTEREN_CY_TM_IR: usize = 0b111;
+
+/// The counts of a task, as sys_perf_read gives them.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct PerfCounts {
+    pub cycles: u64,
+    pub instret: u64,
+    /// the time it ran for, in ns
+    pub time_ns: u64,
+    /// the times it was switched in
+    pub switches: u64,
+    /// the hardware events, 0 for those not counted
+    pub events: [u64; PERF_EVENTS],
+}
+
+impl PerfCounts {
+    pub fn add(&mut self, other: &PerfCounts) {
+        self.cycles += other.cycles;
+        self.instret += other.instret;
+        self.time_ns += other.time_ns;
+        self.switches += other.switches;
+        for (event, other) in self.events.iter_mut().zip(other.events.iter()) {
+            *event += other;
+        }
+    }
+}
+
+/// The counters of a hart as read at once.
+#[derive(Clone, Copy, Default)]
+struct Snapshot {
+    cycle: u64,
+    instret: u64,
+    /// ns since boot
+    time: u64,
+    events: [u64; PERF_EVENTS],
+}
+
+/// The CSR of the hpmcounter counting each event, 0 if none does.
+static EVENT_CSRS: [AtomicUsize; PERF_EVENTS] = [
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+];
+
+lazy_static! {
+    /// When the task running on each hart was switched in.
+    static ref SWITCHED_IN: UPIntrFreeCell<[Snapshot; MAX_HARTS]> =
+        unsafe { UPIntrFreeCell::new([Snapshot::default(); MAX_HARTS]) };
+}
+
+/// Read the hpmcounter of the CSR `csr`, 0 for one which is none.
+fn read_hpmcounter(csr: usize) -> u64 {
+    macro_rules! hpmcounters {
+        ($($n:literal)*) => {
+            match csr {
+                $(
+                    csr if csr == 0xc00 + $n => {
+                        let value: u64;
+                        unsafe {
+                            asm!(concat!("csrr {}, hpmcounter", stringify!($n)), out(reg) value)
+                        };
+                        value
+                    }
+                )*
+                _ => 0,
+            }
+        };
+    }
+    hpmcounters!(3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20 21 22 23 24 25 26 27 28 29 30 31)
+}
+
+fn snapshot() -> Snapshot {
+    let (cycle, instret): (u64, u64);
+    unsafe {
+        asm!("csrr {}, cycle", out(reg) cycle);
+        asm!("csrr {}, instret", out(reg) instret);
+    }
+    let mut events = [0; PERF_EVENTS];
+    for (event, csr) in events.iter_mut().zip(EVENT_CSRS.iter()) {
+        *event = read_hpmcounter(csr.load(Ordering::Relaxed));
+    }
+    Snapshot {
+        cycle,
+        instret,
+        time: ktime_raw(),
+        events,
+    }
+}
+
+/// What the hart counted since `from`.
+fn since(from: &Snapshot) -> PerfCounts {
+    let now = snapshot();
+    let mut events = [0; PERF_EVENTS];
+    for (event, (now, from)) in events
+        .iter_mut()
+        .zip(now.events.iter().zip(from.events.iter()))
+    {
+        *event = now.wrapping_sub(*from);
+    }
+    PerfCounts {
+        cycles: now.cycle.wrapping_sub(from.cycle),
+        instret: now.instret.wrapping_sub(from.instret),
+        time_ns: now.time.wrapping_sub(from.time),
+        switches: 0,
+        events,
+    }
+}
+
+/// Have the PMU count the hardware events it can, and let user mode read
+/// cycle, time and instret.
+pub fn init() {
+    let counters = pmu_num_counters();
+    if counters > 0 {
+        for (csr, code) in EVENT_CSRS.iter().zip(EVENT_CODES.iter()) {
+            if let Some(index) = pmu_start_matching(counters, *code) {
+                csr.store(pmu_counter_csr(index).unwrap_or(0), Ordering::Relaxed);
+            }
+        }
+    }
+    unsafe {
+        asm!("csrs scounteren, {}", in(reg) SCOUNTEREN_CY_TM_IR);
+    }
+    info!(
+        "perf: {} PMU counters, {} of {} events counted",
+        counters,
+        counted_events().count_ones(),
+        PERF_EVENTS
+    );
+}
+
+/// Which of the events are counted, as a mask of their indices.
+pub fn counted_events() -> usize {
+    EVENT_CSRS
+        .iter()
+        .enumerate()
+        .filter(|(_, csr)| csr.load(Ordering::Relaxed) != 0)
+        .fold(0, |mask, (index, _)| mask | 1 << index)
+}
+
+/// `task` is being switched in.
+pub fn switch_in(task: &TaskControlBlock) {
+    task.inner.exclusive_session(|inner| inner.perf.switches += 1);
+    SWITCHED_IN.exclusive_access()[hart_id()] = snapshot();
+}
+
+/// `task` was switched out, having run since it was switched in; a thread
+/// which exited leaves what it counted to its process.
+pub fn switch_out(task: &TaskControlBlock) {
+    let counts = since(&SWITCHED_IN.exclusive_access()[hart_id()]);
+    let (total, exited) = task.inner.exclusive_session(|inner| {
+        inner.perf.add(&counts);
+        (inner.perf, inner.exit_code.is_some())
+    });
+    if exited {
+        if let Some(process) = task.process.upgrade() {
+            process.inner_exclusive_access().perf_exited.add(&total);
+        }
+    }
+}
+
+/// The counts of the task running, `task`, to now.
+pub fn current_counts(task: &TaskControlBlock) -> PerfCounts {
+    let mut counts = since(&SWITCHED_IN.exclusive_access()[hart_id()]);
+    counts.add(&task.inner.exclusive_session(|inner| inner.perf));
+    counts
+}
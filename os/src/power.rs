@@ -0,0 +1,206 @@
+//! Bringing the system down: the file systems are synced and their blocks
+//! written to the disks, the other harts stopped, and only then is the
+//! power turned off or the system reset through the SBI.
+//!
+//! Or the system only sleeps: suspended to idle, the drivers registered
+//! quiesce, the interrupts of all but the wakeup sources are masked, and
+//! the hart waits in wfi for one of those or the timer.
+
+use crate::board;
+use crate::config::MAX_HARTS;
+use crate::fs;
+use crate::mm::hart_id;
+use crate::sbi::{self, hart_status, send_ipi, set_timer, HartState};
+use crate::sync::UPIntrFreeCell;
+use crate::task::{preempt_disable, preempt_enable};
+use crate::timer::{get_time_ms, ktime_raw, ns_to_ticks, set_next_trigger, NSEC_PER_MSEC};
+use crate::watchdog;
+use crate::DEV_NON_BLOCKING_ACCESS;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+use easy_fs::block_cache_sync_all;
+use lazy_static::*;
+use riscv::register::{sip, sstatus};
+
+/// The other harts wait this long at most to stop.
+const STOP_TIMEOUT_MS: usize = 100;
+
+/// The system is going down, and a hart taking a software interrupt stops.
+static STOPPING: AtomicBool = AtomicBool::new(false);
+
+/// What the system does once it is down.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PowerAction {
+    PowerOff,
+    Restart,
+    Halt,
+}
+
+/// The harts other than this one which run.
+fn other_harts() -> usize {
+    (0..MAX_HARTS)
+        .filter(|hart| *hart != hart_id() && hart_status(*hart) == Ok(HartState::Started))
+        .fold(0, |harts, hart| harts | 1 << hart)
+}
+
+/// Stop the other harts, waiting a while for them to.
+fn stop_other_harts() {
+    let harts = other_harts();
+    if harts == 0 {
+        return;
+    }
+    STOPPING.store(true, Ordering::SeqCst);
+    send_ipi(harts);
+    let deadline = get_time_ms() + STOP_TIMEOUT_MS;
+    while other_harts() != 0 {
+        if get_time_ms() >= deadline {
+            warn!("harts {:#x} did not stop", other_harts());
+            return;
+        }
+        core::hint::spin_loop();
+    }
+}
+
+/// On a software interrupt: stop this hart if the system is going down.
+pub fn handle_stop() {
+    unsafe { sip::clear_ssoft() };
+    if STOPPING.load(Ordering::SeqCst) {
+        sbi::hart_stop();
+    }
+}
+
+/// Sync everything to the disks and stop the other harts.
+fn teardown() {
+    // the disks are waited for busily from here on, as this may not be in
+    // a task which could sleep
+    *DEV_NON_BLOCKING_ACCESS.exclusive_access() = false;
+    info!("syncing file systems");
+    fs::sync();
+    block_cache_sync_all();
+    stop_other_harts();
+}
+
+/// Bring the system down and do `action`; returns only if the firmware
+/// cannot restart, once the system is down all the same.
+pub fn power_down(action: PowerAction, failure: bool) {
+    teardown();
+    match action {
+        PowerAction::PowerOff => {
+            info!("power off");
+            sbi::shutdown(failure)
+        }
+        PowerAction::Restart => {
+            info!("restarting system");
+            let error = sbi::reboot(false);
+            warn!("restart failed: {:?}", error);
+        }
+        PowerAction::Halt => {
+            info!("system halted");
+            unsafe { sstatus::clear_sie() };
+            loop {
+                unsafe { core::arch::asm!("wfi") };
+            }
+        }
+    }
+}
+
+/// What a driver does around a suspend: quiesce its device before, and
+/// bring it back after.
+pub trait PmOps: Send + Sync {
+    fn suspend(&self) {}
+    fn resume(&self) {}
+}
+
+/// A device the suspend knows of: its interrupt source, whether that wakes
+/// the system, and the hooks of its driver if it has any.
+#[derive(Clone)]
+struct PmDevice {
+    name: &'static str,
+    irq: usize,
+    wakeup: bool,
+    ops: Option<Arc<dyn PmOps>>,
+}
+
+lazy_static! {
+    /// The devices, in the order they were registered.
+    static ref PM_DEVICES: UPIntrFreeCell<Vec<PmDevice>> =
+        unsafe { UPIntrFreeCell::new(Vec::new()) };
+}
+
+/// Have a suspend quiesce device `name` interrupting at `irq`, with the
+/// hooks `ops`; only the `wakeup` ones end it.
+pub fn register_device(
+    name: &'static str,
+    irq: usize,
+    wakeup: bool,
+    ops: Option<Arc<dyn PmOps>>,
+) {
+    PM_DEVICES.exclusive_access().push(PmDevice {
+        name,
+        irq,
+        wakeup,
+        ops,
+    });
+}
+
+/// Suspend to idle until a wakeup source interrupts, or `timeout_ms` has
+/// passed unless it is 0; the milliseconds slept.
+pub fn suspend(timeout_ms: usize) -> usize {
+    // whatever happens asleep, the disks are as the system left them
+    fs::sync();
+    block_cache_sync_all();
+    let devices = PM_DEVICES.exclusive_access().clone();
+    let wakeups: Vec<_> = devices.iter().filter(|d| d.wakeup).map(|d| d.name).collect();
+    info!("suspending, woken by {:?}", wakeups);
+    preempt_disable();
+    let sie = sstatus::read().sie();
+    unsafe { sstatus::clear_sie() };
+    // the last registered is quiesced first, as it may depend on the others
+    for device in devices.iter().rev() {
+        if let Some(ops) = &device.ops {
+            ops.suspend();
+        }
+        if !device.wakeup {
+            board::irq_set_enabled(device.irq, false);
+        }
+    }
+    let start = ktime_raw();
+    let deadline = match timeout_ms {
+        0 => None,
+        ms => Some(start + ms as u64 * NSEC_PER_MSEC),
+    };
+    set_timer(deadline.map_or(usize::MAX, ns_to_ticks));
+    loop {
+        // with SIE clear, wfi still returns on an interrupt pending
+        unsafe { core::arch::asm!("wfi") };
+        if sip::read().sext() {
+            board::irq_handler();
+            break;
+        }
+        if sip::read().ssoft() {
+            handle_stop();
+        }
+        if deadline.map_or(false, |deadline| ktime_raw() >= deadline) {
+            break;
+        }
+    }
+    let slept_ms = ((ktime_raw() - start) / NSEC_PER_MSEC) as usize;
+    set_next_trigger();
+    for device in devices.iter() {
+        if !device.wakeup {
+            board::irq_set_enabled(device.irq, true);
+        }
+        if let Some(ops) = &device.ops {
+            ops.resume();
+        }
+    }
+    if sie {
+        unsafe { sstatus::set_sie() };
+    }
+    // the hart did not schedule while asleep, which is no lockup
+    watchdog::touch();
+    preempt_enable();
+    info!("resumed after {} ms", slept_ms);
+    slept_ms
+}
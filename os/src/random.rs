@@ -0,0 +1,60 @@
+//! A small entropy pool stirred by interrupt timings.
+//!
+//! The pool is good enough for address space randomization, but it is not
+//! a cryptographic generator.
+
+use crate::sync::UPIntrFreeCell;
+use crate::timer::get_time;
+use lazy_static::*;
+
+struct EntropyPool {
+    state: u64,
+    counter: u64,
+}
+
+/// The splitmix64 finalizer, so that every input bit affects every output bit.
+pub fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+impl EntropyPool {
+    fn new() -> Self {
+        Self {
+            state: 0x9e37_79b9_7f4a_7c15,
+            counter: 0,
+        }
+    }
+    fn add(&mut self, sample: u64) {
+        self.counter = self.counter.wrapping_add(1);
+        self.state = mix(self.state ^ sample.rotate_left(self.counter as u32 % 64));
+    }
+    fn next(&mut self) -> u64 {
+        self.add(get_time() as u64);
+        mix(self.state ^ self.counter)
+    }
+}
+
+lazy_static! {
+    static ref ENTROPY_POOL: UPIntrFreeCell<EntropyPool> =
+        unsafe { UPIntrFreeCell::new(EntropyPool::new()) };
+}
+
+/// Mix a sample whose low bits are hard to predict, e.g. the time of an
+/// interrupt, into the pool.
+pub fn add_entropy(sample: usize) {
+    ENTROPY_POOL.exclusive_access().add(sample as u64);
+}
+
+pub fn random() -> usize {
+    ENTROPY_POOL.exclusive_access().next() as usize
+}
+
+pub fn fill_random(buf: &mut [u8]) {
+    let mut pool = ENTROPY_POOL.exclusive_access();
+    for chunk in buf.chunks_mut(8) {
+        let bytes = pool.next().to_le_bytes();
+        chunk.copy_from_slice(&bytes[..chunk.len()]);
+    }
+}
@@ -0,0 +1,472 @@
+//! Record and replay of scheduling, for a race to happen again the same
+//! way once it happened once.
+//!
+//! While recording or replaying, what happens of itself does so only at
+//! points: syscalls and the scheduler picking a task. Interrupts of the
+//! PLIC stay pending until the next point, which handles them, and a
+//! timer interrupt leaves the timers and the time slice alone until then,
+//! the clock of the kernel advancing only there as well. A recording logs
+//! each point, which task the scheduler picked and which syscall was made,
+//! and what was handled at it: the interrupts, the bytes of the UART and
+//! the ticks with the time.
+//!
+//! A replay goes through the log, handing the scheduler the task the
+//! recording picked, waiting for the interrupts the recording handled and
+//! handling them at the same points, giving the UART the same bytes and
+//! the clock the same times, so that tasks run in the same order and see
+//! the same things happen between their syscalls. Should a point be other
+//! than the log says, the replay has diverged, which the log of the kernel
+//! tells, and it stops.
+//!
+//! The hart runs as one. A thread spinning in user mode is not preempted
+//! until its next syscall, and a replay only tells the same story from the
+//! same state: the same processes, made after a boot as they were when it
+//! was recorded; the data of disks and cards is that of the replay.
+
+use crate::board::{irq_claim, irq_complete, irq_dispatch, IRQS};
+use crate::drivers::chardev::UART;
+use crate::sync::UPIntrFreeCell;
+use crate::task::{
+    current_process, fetch_task_where, kernel_thread_index, tick_resched, TaskControlBlock,
+};
+use crate::timer::{check_timer, ktime_raw, NSEC_PER_MSEC};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use lazy_static::*;
+use riscv::register::sie;
+
+/// Events a log holds at most, 1.5 MiB of them.
+pub const REPLAY_MAX_EVENTS: usize = 1 << 16;
+/// How long a replay waits for an interrupt the recording handled.
+const IRQ_WAIT_MS: usize = 5000;
+
+/// The UART, whose bytes are logged rather than its interrupts.
+const UART_IRQ: usize = 10;
+
+const EVENT_SYSCALL: usize = 0;
+const EVENT_SCHEDULE: usize = 1;
+const EVENT_IRQ: usize = 2;
+const EVENT_INPUT: usize = 3;
+const EVENT_TICK: usize = 4;
+
+/// The process of a task picked: a kernel thread, the process which
+/// started the recording or replay, one made since, by the order of its
+/// pid, or another by its pid.
+const KEY_KERNEL: usize = 0;
+const KEY_STARTER: usize = 1;
+const KEY_NEW: usize = 2;
+const KEY_OTHER: usize = 1 << 32;
+
+/// What happened at a point, or at which point, as syscall gives the log:
+/// - EVENT_SYSCALL: the syscall `a` made;
+/// - EVENT_SCHEDULE: the task `b` of the process keyed `a` picked, `b`
+///   being the tid, or the index of a kernel thread;
+/// - EVENT_IRQ: an interrupt of the source `a` handled;
+/// - EVENT_INPUT: the byte `a` of the UART taken in;
+/// - EVENT_TICK: a tick, the clock then `a` ms after the start.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ReplayEvent {
+    kind: usize,
+    a: usize,
+    b: usize,
+}
+
+impl ReplayEvent {
+    fn new(kind: usize, a: usize, b: usize) -> Self {
+        Self { kind, a, b }
+    }
+
+    /// Whether it is a point rather than what was handled at one.
+    fn is_point(&self) -> bool {
+        self.kind == EVENT_SYSCALL || self.kind == EVENT_SCHEDULE
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Off,
+    Recording,
+    Replaying,
+}
+
+struct Replay {
+    mode: Mode,
+    events: Vec<ReplayEvent>,
+    /// the next event to replay
+    next: usize,
+    /// whether the last replay went other than its log
+    diverged: bool,
+    /// the process which started it
+    starter: usize,
+    /// the pids of the processes made since, in order
+    new_pids: Vec<usize>,
+    /// interrupts claimed while a replay waited for another, handled once
+    /// it ends
+    deferred: Vec<usize>,
+    /// the clock when it started, which ticks count from
+    start_ms: usize,
+}
+
+lazy_static! {
+    static ref REPLAY: UPIntrFreeCell<Replay> = unsafe {
+        UPIntrFreeCell::new(Replay {
+            mode: Mode::Off,
+            events: Vec::new(),
+            next: 0,
+            diverged: false,
+            starter: 0,
+            new_pids: Vec::new(),
+            deferred: Vec::new(),
+            start_ms: 0,
+        })
+    };
+}
+
+/// Whether a recording or replay is on, read without taking REPLAY.
+static ON: AtomicBool = AtomicBool::new(false);
+/// A timer interrupt came since the last point.
+static TICK_PENDING: AtomicBool = AtomicBool::new(false);
+/// The clock of the kernel while on, as of the last tick handled.
+static CLOCK_MS: AtomicUsize = AtomicUsize::new(0);
+
+/// The clock, whatever the kernel's says.
+fn uptime_ms() -> usize {
+    (ktime_raw() / NSEC_PER_MSEC) as usize
+}
+
+/// The clock of the kernel while a recording or replay is on.
+pub fn clock_ms() -> Option<usize> {
+    if ON.load(Ordering::Relaxed) {
+        Some(CLOCK_MS.load(Ordering::Relaxed))
+    } else {
+        None
+    }
+}
+
+/// A timer interrupt came: whether to leave the timers and the time slice
+/// alone until the next point.
+pub fn defer_tick() -> bool {
+    if ON.load(Ordering::Relaxed) {
+        TICK_PENDING.store(true, Ordering::Relaxed);
+        true
+    } else {
+        false
+    }
+}
+
+/// The pid `pid` was given out, by fork or spawn.
+pub fn pid_allocated(pid: usize) {
+    if ON.load(Ordering::Relaxed) {
+        REPLAY.exclusive_access().new_pids.push(pid);
+    }
+}
+
+/// Bytes the UART took in, to log while recording.
+pub fn record_input(input: &[u8]) {
+    if !ON.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut replay = REPLAY.exclusive_access();
+    let mut deferred = Vec::new();
+    for &byte in input {
+        if replay.mode == Mode::Recording {
+            deferred = push(&mut replay, ReplayEvent::new(EVENT_INPUT, byte as usize, 0));
+        }
+    }
+    drop(replay);
+    handle_deferred(deferred);
+}
+
+/// The key of the process of `task` and its tid, or its index for a kernel
+/// thread.
+fn task_key(replay: &Replay, task: &Arc<TaskControlBlock>) -> (usize, usize) {
+    let process = match task.process.upgrade() {
+        Some(process) => process,
+        None => return (KEY_KERNEL, kernel_thread_index(task).unwrap_or(usize::MAX)),
+    };
+    let pid = process.getpid();
+    let tid = task
+        .inner
+        .exclusive_session(|inner| inner.res.as_ref().map_or(0, |res| res.tid));
+    let key = if pid == replay.starter {
+        KEY_STARTER
+    } else if let Some(index) = replay.new_pids.iter().position(|&new| new == pid) {
+        KEY_NEW + index
+    } else {
+        KEY_OTHER + pid
+    };
+    (key, tid)
+}
+
+/// Stop; the interrupts it kept pending, to be handled once REPLAY is not
+/// held.
+fn stop(replay: &mut Replay) -> Vec<usize> {
+    replay.mode = Mode::Off;
+    ON.store(false, Ordering::Relaxed);
+    TICK_PENDING.store(false, Ordering::Relaxed);
+    unsafe {
+        sie::set_sext();
+    }
+    core::mem::take(&mut replay.deferred)
+}
+
+fn handle_deferred(deferred: Vec<usize>) {
+    for source in deferred {
+        irq_dispatch(source);
+        irq_complete(source);
+    }
+}
+
+/// The replay went other than its log at its next event.
+fn diverge(replay: &mut Replay, found: Option<ReplayEvent>) -> Vec<usize> {
+    warn!(
+        "replay: diverged at event {} of {}: {:?} where the log has {:?}",
+        replay.next,
+        replay.events.len(),
+        found,
+        replay.events.get(replay.next)
+    );
+    replay.diverged = true;
+    stop(replay)
+}
+
+/// Log `event` while recording, stopping once the log is full.
+fn push(replay: &mut Replay, event: ReplayEvent) -> Vec<usize> {
+    if replay.events.len() == REPLAY_MAX_EVENTS {
+        warn!("replay: the log is full, recording stops");
+        return stop(replay);
+    }
+    replay.events.push(event);
+    Vec::new()
+}
+
+/// Handle the tick at `ms` after the start.
+fn tick(ms: usize, start_ms: usize) {
+    CLOCK_MS.store(start_ms + ms, Ordering::Relaxed);
+    check_timer();
+    tick_resched();
+}
+
+/// Wait for an interrupt of `source` and handle it, keeping others pending
+/// for the end; false if none came.
+fn replay_irq(source: usize) -> bool {
+    let deadline = uptime_ms() + IRQ_WAIT_MS;
+    let claimed = REPLAY.exclusive_session(|replay| {
+        let index = replay.deferred.iter().position(|&deferred| deferred == source);
+        index.map(|index| replay.deferred.remove(index)).is_some()
+    });
+    if !claimed {
+        loop {
+            match irq_claim() {
+                Some(claimed) if claimed == source => break,
+                Some(claimed) => REPLAY.exclusive_access().deferred.push(claimed),
+                None if uptime_ms() >= deadline => return false,
+                None => core::hint::spin_loop(),
+            }
+        }
+    }
+    irq_dispatch(source);
+    irq_complete(source);
+    true
+}
+
+/// Handle what came since the last point, while recording, or what the
+/// log has before its next point, while replaying.
+fn handle_pending() {
+    loop {
+        let mut replay = REPLAY.exclusive_access();
+        match replay.mode {
+            Mode::Off => return,
+            Mode::Recording => {
+                drop(replay);
+                // the UART logs its bytes itself
+                while let Some(source) = irq_claim() {
+                    irq_dispatch(source);
+                    irq_complete(source);
+                    if source != UART_IRQ {
+                        let mut replay = REPLAY.exclusive_access();
+                        if replay.mode == Mode::Recording {
+                            let deferred =
+                                push(&mut replay, ReplayEvent::new(EVENT_IRQ, source, 0));
+                            drop(replay);
+                            handle_deferred(deferred);
+                        }
+                    }
+                }
+                if TICK_PENDING.swap(false, Ordering::Relaxed) {
+                    let mut replay = REPLAY.exclusive_access();
+                    if replay.mode == Mode::Recording {
+                        let start_ms = replay.start_ms;
+                        let ms = uptime_ms() - start_ms;
+                        let deferred = push(&mut replay, ReplayEvent::new(EVENT_TICK, ms, 0));
+                        drop(replay);
+                        handle_deferred(deferred);
+                        tick(ms, start_ms);
+                    }
+                }
+                return;
+            }
+            Mode::Replaying => {
+                TICK_PENDING.store(false, Ordering::Relaxed);
+                let event = match replay.events.get(replay.next) {
+                    Some(event) if !event.is_point() => *event,
+                    _ => return,
+                };
+                replay.next += 1;
+                let start_ms = replay.start_ms;
+                drop(replay);
+                match event.kind {
+                    EVENT_IRQ if !replay_irq(event.a) => {
+                        let mut replay = REPLAY.exclusive_access();
+                        replay.next -= 1;
+                        let deferred = diverge(&mut replay, None);
+                        drop(replay);
+                        handle_deferred(deferred);
+                        return;
+                    }
+                    EVENT_IRQ => {}
+                    EVENT_INPUT => UART.receive(&[event.a as u8]),
+                    _ => tick(event.a, start_ms),
+                }
+            }
+        }
+    }
+}
+
+/// Reach the point `event`, once what is pending was handled.
+fn point(event: ReplayEvent) {
+    handle_pending();
+    let mut replay = REPLAY.exclusive_access();
+    let deferred = match replay.mode {
+        Mode::Off => return,
+        Mode::Recording => push(&mut replay, event),
+        Mode::Replaying if replay.events.get(replay.next) == Some(&event) => {
+            replay.next += 1;
+            Vec::new()
+        }
+        Mode::Replaying => diverge(&mut replay, Some(event)),
+    };
+    drop(replay);
+    handle_deferred(deferred);
+}
+
+/// The syscall `id` is being made.
+pub fn syscall_point(id: usize) {
+    if ON.load(Ordering::Relaxed) {
+        point(ReplayEvent::new(EVENT_SYSCALL, id, 0));
+    }
+}
+
+/// The next task to run: the first ready, or while replaying the one the
+/// recording picked.
+pub fn fetch_task() -> Option<Arc<TaskControlBlock>> {
+    if !ON.load(Ordering::Relaxed) {
+        return crate::task::fetch_task();
+    }
+    handle_pending();
+    let (mode, next) = REPLAY.exclusive_session(|replay| {
+        (replay.mode, replay.events.get(replay.next).copied())
+    });
+    let task = match (mode, next) {
+        (Mode::Replaying, Some(event)) if event.kind == EVENT_SCHEDULE => {
+            fetch_task_where(|task| {
+                REPLAY.exclusive_session(|replay| task_key(replay, task)) == (event.a, event.b)
+            })
+        }
+        (Mode::Replaying, _) => None,
+        _ => crate::task::fetch_task(),
+    };
+    match task.as_ref() {
+        Some(task) => {
+            let (key, tid) = REPLAY.exclusive_session(|replay| task_key(replay, task));
+            point(ReplayEvent::new(EVENT_SCHEDULE, key, tid));
+        }
+        // nothing to run is no point while recording, but the recording ran
+        // something here
+        None if mode == Mode::Replaying => {
+            let deferred = REPLAY.exclusive_session(|replay| diverge(replay, None));
+            handle_deferred(deferred);
+        }
+        None => {}
+    }
+    task.or_else(crate::task::fetch_task)
+}
+
+fn start(mode: Mode, events: Vec<ReplayEvent>) -> bool {
+    let starter = current_process().getpid();
+    let mut replay = REPLAY.exclusive_access();
+    if replay.mode != Mode::Off {
+        return false;
+    }
+    let now = uptime_ms();
+    *replay = Replay {
+        mode,
+        events,
+        next: 0,
+        diverged: false,
+        starter,
+        new_pids: Vec::new(),
+        deferred: Vec::new(),
+        start_ms: now,
+    };
+    CLOCK_MS.store(now, Ordering::Relaxed);
+    TICK_PENDING.store(false, Ordering::Relaxed);
+    unsafe {
+        sie::clear_sext();
+    }
+    ON.store(true, Ordering::Relaxed);
+    true
+}
+
+/// Start recording, from the syscall of the current process after this
+/// one; false if a recording or replay is on already.
+pub fn record_start() -> bool {
+    start(Mode::Recording, Vec::new())
+}
+
+/// Start replaying `events`, from the syscall of the current process after
+/// this one; false if a recording or replay is on already.
+pub fn replay_start(events: Vec<ReplayEvent>) -> bool {
+    start(Mode::Replaying, events)
+}
+
+/// Stop recording or replaying; the events recorded, or replayed if the
+/// replay went as logged, or None if it diverged.
+pub fn replay_stop() -> Option<usize> {
+    let mut replay = REPLAY.exclusive_access();
+    let deferred = match replay.mode {
+        Mode::Off => Vec::new(),
+        _ => stop(&mut replay),
+    };
+    // a recording leaves nothing replayed
+    let result = if replay.diverged {
+        None
+    } else if replay.next > 0 {
+        Some(replay.next)
+    } else {
+        Some(replay.events.len())
+    };
+    drop(replay);
+    handle_deferred(deferred);
+    result
+}
+
+/// The events recorded from `from` on, at most `count` of them.
+pub fn replay_events(from: usize, count: usize) -> Vec<ReplayEvent> {
+    let replay = REPLAY.exclusive_access();
+    let from = from.min(replay.events.len());
+    let to = replay.events.len().min(from + count);
+    replay.events[from..to].to_vec()
+}
+
+/// Whether `event` is one a log may hold, for a log given to replay.
+pub fn valid_event(event: &ReplayEvent) -> bool {
+    match event.kind {
+        EVENT_SYSCALL | EVENT_SCHEDULE | EVENT_TICK => true,
+        EVENT_IRQ => IRQS.iter().any(|(source, _)| *source == event.a),
+        EVENT_INPUT => event.a < 256,
+        _ => false,
+    }
+}
@@ -1,15 +1,348 @@
-/// use sbi call to set timer
+//! Calls to the SBI, the firmware below the kernel, as version 2.0 has
+//! them: the base extension, TIME, IPI, HSM for starting and stopping
+//! harts, SRST for shutting down and rebooting, PMU, and DBCN, the console
+//! of the firmware which the kernel writes to before its own is up.
+//!
+//! Each extension is probed for before its first use. Where the firmware
+//! lacks TIME, IPI, SRST or DBCN, the legacy calls of version 0.1 stand in,
+//! which have no reboot.
+
+use crate::config::MAX_HARTS;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+const EID_BASE: usize = 0x10;
+const BASE_GET_SPEC_VERSION: usize = 0;
+const BASE_GET_IMPL_ID: usize = 1;
+const BASE_GET_IMPL_VERSION: usize = 2;
+const BASE_PROBE_EXTENSION: usize = 3;
+
+const EID_LEGACY_SET_TIMER: usize = 0x00;
+const EID_LEGACY_CONSOLE_PUTCHAR: usize = 0x01;
+const EID_LEGACY_SEND_IPI: usize = 0x04;
+const EID_LEGACY_SHUTDOWN: usize = 0x08;
+
+const TIME_SET_TIMER: usize = 0;
+const IPI_SEND_IPI: usize = 0;
+const HSM_HART_START: usize = 0;
+const HSM_HART_STOP: usize = 1;
+const HSM_HART_GET_STATUS: usize = 2;
+const SRST_SYSTEM_RESET: usize = 0;
+const SRST_SHUTDOWN: usize = 0;
+const SRST_COLD_REBOOT: usize = 1;
+const SRST_WARM_REBOOT: usize = 2;
+const SRST_NO_REASON: usize = 0;
+const SRST_SYSTEM_FAILURE: usize = 1;
+
+const DBCN_CONSOLE_WRITE_BYTE: usize = 2;
+
+const PMU_NUM_COUNTERS: usize = 0;
+const PMU_COUNTER_GET_INFO: usize = 1;
+const PMU_COUNTER_CONFIG_MATCHING: usize = 2;
+/// Zero the counter picked and start it counting.
+const PMU_CFG_CLEAR_AND_START: usize = 0b110;
+
+/// The errors of an SBI call.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SbiError {
+    Failed,
+    NotSupported,
+    InvalidParam,
+    Denied,
+    InvalidAddress,
+    AlreadyAvailable,
+    AlreadyStarted,
+    AlreadyStopped,
+    NoSharedMemory,
+    Unknown(isize),
+}
+
+impl From<isize> for SbiError {
+    fn from(error: isize) -> Self {
+        match error {
+            -1 => Self::Failed,
+            -2 => Self::NotSupported,
+            -3 => Self::InvalidParam,
+            -4 => Self::Denied,
+            -5 => Self::InvalidAddress,
+            -6 => Self::AlreadyAvailable,
+            -7 => Self::AlreadyStarted,
+            -8 => Self::AlreadyStopped,
+            -9 => Self::NoSharedMemory,
+            error => Self::Unknown(error),
+        }
+    }
+}
+
+/// The extensions the kernel uses, by their EID.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(usize)]
+pub enum Extension {
+    Time = 0x5449_4d45,
+    Ipi = 0x73_5049,
+    Hsm = 0x48_534d,
+    Srst = 0x5352_5354,
+    Pmu = 0x50_4d55,
+    Dbcn = 0x4442_434e,
+}
+
+const EXTENSIONS: [Extension; 6] = [
+    Extension::Time,
+    Extension::Ipi,
+    Extension::Hsm,
+    Extension::Srst,
+    Extension::Pmu,
+    Extension::Dbcn,
+];
+const NOT_PROBED: u8 = 0;
+const ABSENT: u8 = 1;
+const PRESENT: u8 = 2;
+const NEVER_PROBED: AtomicU8 = AtomicU8::new(NOT_PROBED);
+/// What probing each of EXTENSIONS found.
+static PROBED: [AtomicU8; 6] = [NEVER_PROBED; 6];
+
+/// The state of a hart, as HSM tells it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HartState {
+    Started,
+    Stopped,
+    StartPending,
+    StopPending,
+    Suspended,
+    SuspendPending,
+    ResumePending,
+}
+
+impl HartState {
+    fn from_raw(state: usize) -> Option<Self> {
+        Some(match state {
+            0 => Self::Started,
+            1 => Self::Stopped,
+            2 => Self::StartPending,
+            3 => Self::StopPending,
+            4 => Self::Suspended,
+            5 => Self::SuspendPending,
+            6 => Self::ResumePending,
+            _ => return None,
+        })
+    }
+}
+
+/// A call of function `fid` of extension `eid`; the value, or the error.
+fn sbi_call(eid: usize, fid: usize, args: [usize; 5]) -> Result<usize, SbiError> {
+    let (error, value): (isize, usize);
+    unsafe {
+        core::arch::asm!(
+            "ecall",
+            inlateout("a0") args[0] => error,
+            inlateout("a1") args[1] => value,
+            in("a2") args[2],
+            in("a3") args[3],
+            in("a4") args[4],
+            in("a6") fid,
+            in("a7") eid,
+        );
+    }
+    if error == 0 {
+        Ok(value)
+    } else {
+        Err(error.into())
+    }
+}
+
+/// A legacy call of v0.1, whose extension is the function and which has a
+/// single value for a result.
+fn legacy_call(eid: usize, arg0: usize) -> isize {
+    let result: isize;
+    unsafe {
+        core::arch::asm!(
+            "ecall",
+            inlateout("a0") arg0 => result,
+            in("a7") eid,
+        );
+    }
+    result
+}
+
+/// Whether the firmware has `extension`, which is asked once.
+pub fn has(extension: Extension) -> bool {
+    let index = EXTENSIONS.iter().position(|ext| *ext == extension).unwrap();
+    match PROBED[index].load(Ordering::Relaxed) {
+        NOT_PROBED => {
+            // v0.1 has no base extension, and fails the probe
+            let args = [extension as usize, 0, 0, 0, 0];
+            let present = sbi_call(EID_BASE, BASE_PROBE_EXTENSION, args).unwrap_or(0) != 0;
+            PROBED[index].store(if present { PRESENT } else { ABSENT }, Ordering::Relaxed);
+            present
+        }
+        probed => probed == PRESENT,
+    }
+}
+
+/// The major and minor version of the SBI, 0.1 for a firmware without the
+/// base extension.
+pub fn spec_version() -> (usize, usize) {
+    match sbi_call(EID_BASE, BASE_GET_SPEC_VERSION, [0; 5]) {
+        Ok(version) => ((version >> 24) & 0x7f, version & 0xff_ffff),
+        Err(_) => (0, 1),
+    }
+}
+
+/// The name of the firmware, from its implementation id.
+fn impl_name(id: usize) -> &'static str {
+    match id {
+        0 => "BBL",
+        1 => "OpenSBI",
+        2 => "Xvisor",
+        3 => "KVM",
+        4 => "RustSBI",
+        5 => "Diosix",
+        6 => "Coffer",
+        _ => "unknown",
+    }
+}
+
+/// Tell what the firmware is and has.
+pub fn init() {
+    let (major, minor) = spec_version();
+    let name = sbi_call(EID_BASE, BASE_GET_IMPL_ID, [0; 5]).map_or("unknown", impl_name);
+    let version = sbi_call(EID_BASE, BASE_GET_IMPL_VERSION, [0; 5]).unwrap_or(0);
+    info!("SBI v{}.{}, {} {:#x}", major, minor, name, version);
+    for extension in EXTENSIONS {
+        if !has(extension) {
+            info!("SBI without {:?}, the legacy calls standing in if any", extension);
+        }
+    }
+    if has(Extension::Hsm) {
+        let harts = (0..MAX_HARTS)
+            .filter(|hart| hart_status(*hart).is_ok())
+            .count();
+        info!("{} harts", harts);
+    }
+}
+
+/// Have the timer interrupt at `timer`, in ticks of the time CSR.
 pub fn set_timer(timer: usize) {
-    sbi_rt::set_timer(timer as _);
+    if has(Extension::Time) {
+        sbi_call(Extension::Time as usize, TIME_SET_TIMER, [timer, 0, 0, 0, 0]).unwrap();
+    } else {
+        legacy_call(EID_LEGACY_SET_TIMER, timer);
+    }
+}
+
+/// Write `byte` to the console of the firmware.
+pub fn console_putchar(byte: u8) {
+    if has(Extension::Dbcn) {
+        let args = [byte as usize, 0, 0, 0, 0];
+        // the byte is dropped rather than the kernel stopped if it fails
+        let _ = sbi_call(Extension::Dbcn as usize, DBCN_CONSOLE_WRITE_BYTE, args);
+    } else {
+        legacy_call(EID_LEGACY_CONSOLE_PUTCHAR, byte as usize);
+    }
+}
+
+/// Send a software interrupt to the harts in `hart_mask`.
+pub fn send_ipi(hart_mask: usize) {
+    if has(Extension::Ipi) {
+        sbi_call(Extension::Ipi as usize, IPI_SEND_IPI, [hart_mask, 0, 0, 0, 0]).unwrap();
+    } else {
+        // which takes the address of the mask
+        legacy_call(EID_LEGACY_SEND_IPI, &hart_mask as *const usize as usize);
+    }
+}
+
+/// Start `hart` at the physical address `start_addr`, in S mode with the
+/// MMU off, its id in a0 and `opaque` in a1.
+#[allow(unused)]
+pub fn hart_start(hart: usize, start_addr: usize, opaque: usize) -> Result<(), SbiError> {
+    if !has(Extension::Hsm) {
+        return Err(SbiError::NotSupported);
+    }
+    let args = [hart, start_addr, opaque, 0, 0];
+    sbi_call(Extension::Hsm as usize, HSM_HART_START, args).map(|_| ())
+}
+
+/// Stop the hart calling, which returns only if it could not.
+pub fn hart_stop() -> SbiError {
+    if !has(Extension::Hsm) {
+        return SbiError::NotSupported;
+    }
+    match sbi_call(Extension::Hsm as usize, HSM_HART_STOP, [0; 5]) {
+        Ok(_) => SbiError::Failed,
+        Err(error) => error,
+    }
+}
+
+/// The state of `hart`, InvalidParam if there is no such hart.
+pub fn hart_status(hart: usize) -> Result<HartState, SbiError> {
+    if !has(Extension::Hsm) {
+        return Err(SbiError::NotSupported);
+    }
+    let state = sbi_call(Extension::Hsm as usize, HSM_HART_GET_STATUS, [hart, 0, 0, 0, 0])?;
+    HartState::from_raw(state).ok_or(SbiError::Failed)
+}
+
+/// Reset the system with SRST, returning only if it could not.
+fn system_reset(reset_type: usize, reason: usize) -> SbiError {
+    if !has(Extension::Srst) {
+        return SbiError::NotSupported;
+    }
+    let args = [reset_type, reason, 0, 0, 0];
+    match sbi_call(Extension::Srst as usize, SRST_SYSTEM_RESET, args) {
+        Ok(_) => SbiError::Failed,
+        Err(error) => error,
+    }
 }
 
 /// use sbi call to shutdown the kernel
 pub fn shutdown(failure: bool) -> ! {
-    use sbi_rt::{system_reset, NoReason, Shutdown, SystemFailure};
-    if !failure {
-        system_reset(Shutdown, NoReason);
+    let reason = if failure {
+        SRST_SYSTEM_FAILURE
     } else {
-        system_reset(Shutdown, SystemFailure);
-    }
+        SRST_NO_REASON
+    };
+    system_reset(SRST_SHUTDOWN, reason);
+    legacy_call(EID_LEGACY_SHUTDOWN, 0);
     unreachable!()
 }
+
+/// Reboot the system, a warm reboot keeping what a cold one would reset,
+/// like the memory; returns only if it could not, as without SRST.
+pub fn reboot(warm: bool) -> SbiError {
+    let reset_type = if warm {
+        SRST_WARM_REBOOT
+    } else {
+        SRST_COLD_REBOOT
+    };
+    system_reset(reset_type, SRST_NO_REASON)
+}
+
+/// The counters of the PMU, 0 without the extension.
+pub fn pmu_num_counters() -> usize {
+    if !has(Extension::Pmu) {
+        return 0;
+    }
+    sbi_call(Extension::Pmu as usize, PMU_NUM_COUNTERS, [0; 5]).unwrap_or(0)
+}
+
+/// Have one of the `counters` count `event` from 0 on; which one.
+pub fn pmu_start_matching(counters: usize, event: usize) -> Option<usize> {
+    let mask = if counters >= usize::BITS as usize {
+        usize::MAX
+    } else {
+        (1 << counters) - 1
+    };
+    let args = [0, mask, PMU_CFG_CLEAR_AND_START, event, 0];
+    sbi_call(Extension::Pmu as usize, PMU_COUNTER_CONFIG_MATCHING, args).ok()
+}
+
+/// What the counter `index` is: its CSR, None for a counter of the
+/// firmware, which no CSR reads.
+pub fn pmu_counter_csr(index: usize) -> Option<usize> {
+    let args = [index, 0, 0, 0, 0];
+    let info = sbi_call(Extension::Pmu as usize, PMU_COUNTER_GET_INFO, args).ok()?;
+    if info >> (usize::BITS - 1) == 1 {
+        None
+    } else {
+        Some(info & 0xfff)
+    }
+}
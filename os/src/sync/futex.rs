@@ -0,0 +1,89 @@
+//! Futexes: waiting while a word of user memory holds a value, and waking
+//! those waiting on a word, for locks kept in user memory to enter the
+//! kernel only when they are contended.
+//!
+//! A word is known by its address space and address, unless it lies in a
+//! shared mapping and the futex is not private, when it is known by the
+//! object mapped and the offset into it, for every process mapping the
+//! object to find the same futex wherever it has it.
+
+use super::UPIntrFreeCell;
+use crate::fs::wait_ready;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, Ordering};
+use lazy_static::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FutexKey {
+    /// `va` of the address space `token`
+    Private { token: usize, va: usize },
+    /// `offset` bytes into the shared object at `object`, which those
+    /// keying on it keep alive lest another object take its address
+    Shared { object: usize, offset: usize },
+}
+
+lazy_static! {
+    /// Those waiting on each futex, in the order they came.
+    static ref FUTEXES: UPIntrFreeCell<BTreeMap<FutexKey, VecDeque<Arc<AtomicBool>>>> =
+        unsafe { UPIntrFreeCell::new(BTreeMap::new()) };
+}
+
+/// A thread waiting on a futex, from before it checks the word, lest a
+/// wake between the check and the wait be missed, until it is dropped.
+pub struct FutexWaiter {
+    key: FutexKey,
+    woken: Arc<AtomicBool>,
+}
+
+impl FutexWaiter {
+    pub fn new(key: FutexKey) -> Self {
+        let woken = Arc::new(AtomicBool::new(false));
+        FUTEXES
+            .exclusive_access()
+            .entry(key)
+            .or_default()
+            .push_back(woken.clone());
+        Self { key, woken }
+    }
+
+    /// Wait to be woken, until `deadline` if there is one or a signal;
+    /// false if the wait ended for either.
+    pub fn wait(&self, deadline: Option<u64>) -> bool {
+        wait_ready(deadline, || self.woken.load(Ordering::Acquire).then_some(())).is_some()
+    }
+}
+
+impl Drop for FutexWaiter {
+    fn drop(&mut self) {
+        let mut futexes = FUTEXES.exclusive_access();
+        if let Some(waiters) = futexes.get_mut(&self.key) {
+            waiters.retain(|woken| !Arc::ptr_eq(woken, &self.woken));
+            if waiters.is_empty() {
+                futexes.remove(&self.key);
+            }
+        }
+    }
+}
+
+/// Wake up to `count` of those waiting on `key`, the first to come first;
+/// how many were.
+pub fn futex_wake(key: FutexKey, count: usize) -> usize {
+    let mut futexes = FUTEXES.exclusive_access();
+    let waiters = match futexes.get_mut(&key) {
+        Some(waiters) => waiters,
+        None => return 0,
+    };
+    let mut woken = 0;
+    while woken < count {
+        match waiters.pop_front() {
+            Some(waiter) => waiter.store(true, Ordering::Release),
+            None => break,
+        }
+        woken += 1;
+    }
+    if waiters.is_empty() {
+        futexes.remove(&key);
+    }
+    woken
+}
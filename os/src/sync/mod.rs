@@ -1,9 +1,13 @@
 mod condvar;
+mod futex;
 mod mutex;
+mod named;
 mod semaphore;
 mod up;
 
 pub use condvar::Condvar;
+pub use futex::{futex_wake, FutexKey, FutexWaiter};
 pub use mutex::{Mutex, MutexBlocking, MutexSpin};
+pub use named::{named_condvar, named_mutex, unlink_named};
 pub use semaphore::Semaphore;
-pub use up::{UPIntrFreeCell, UPIntrRefMut};
+pub use up::{UPIntrFreeCell, UPIntrRefMut, UPSafeCellRaw};
@@ -0,0 +1,58 @@
+//! Mutexes and condition variables known by name to every process, for
+//! processes to open by name and use by the ids they are given like those
+//! they create, and so to synchronize with each other.
+
+use super::{Condvar, Mutex, MutexBlocking, MutexSpin, UPIntrFreeCell};
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use lazy_static::*;
+
+struct Names {
+    mutexes: BTreeMap<String, Arc<dyn Mutex>>,
+    condvars: BTreeMap<String, Arc<Condvar>>,
+}
+
+lazy_static! {
+    static ref NAMES: UPIntrFreeCell<Names> = unsafe {
+        UPIntrFreeCell::new(Names {
+            mutexes: BTreeMap::new(),
+            condvars: BTreeMap::new(),
+        })
+    };
+}
+
+/// The mutex of `name`, a new one, blocking or not, if there is none.
+pub fn named_mutex(name: &str, blocking: bool) -> Arc<dyn Mutex> {
+    let mut names = NAMES.exclusive_access();
+    if let Some(mutex) = names.mutexes.get(name) {
+        return mutex.clone();
+    }
+    let mutex: Arc<dyn Mutex> = if blocking {
+        Arc::new(MutexBlocking::new())
+    } else {
+        Arc::new(MutexSpin::new())
+    };
+    names.mutexes.insert(String::from(name), mutex.clone());
+    mutex
+}
+
+/// The condition variable of `name`, a new one if there is none.
+pub fn named_condvar(name: &str) -> Arc<Condvar> {
+    let mut names = NAMES.exclusive_access();
+    if let Some(condvar) = names.condvars.get(name) {
+        return condvar.clone();
+    }
+    let condvar = Arc::new(Condvar::new());
+    names.condvars.insert(String::from(name), condvar.clone());
+    condvar
+}
+
+/// Take `name` away from its mutex and its condition variable, which live
+/// on for those who opened them; false if neither had it.
+pub fn unlink_named(name: &str) -> bool {
+    let mut names = NAMES.exclusive_access();
+    let mutex = names.mutexes.remove(name).is_some();
+    let condvar = names.condvars.remove(name).is_some();
+    mutex || condvar
+}
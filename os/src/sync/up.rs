@@ -1,3 +1,4 @@
+use crate::task::{preempt_disable, preempt_enable};
 use core::cell::{RefCell, RefMut, UnsafeCell};
 use core::ops::{Deref, DerefMut};
 use lazy_static::*;
@@ -77,9 +78,11 @@ impl IntrMaskingInfo {
             self.sie_before_masking = sie;
         }
         self.nested_level += 1;
+        preempt_disable();
     }
 
     pub fn exit(&mut self) {
+        preempt_enable();
         self.nested_level -= 1;
         if self.nested_level == 0 && self.sie_before_masking {
             unsafe {
@@ -111,6 +114,18 @@ impl<T> UPIntrFreeCell<T> {
         UPIntrRefMut(Some(self.inner.borrow_mut()))
     }
 
+    /// Return None instead of panicking if the data has been borrowed.
+    pub fn try_exclusive_access(&self) -> Option<UPIntrRefMut<'_, T>> {
+        INTR_MASKING_INFO.get_mut().enter();
+        match self.inner.try_borrow_mut() {
+            Ok(inner) => Some(UPIntrRefMut(Some(inner))),
+            Err(_) => {
+                INTR_MASKING_INFO.get_mut().exit();
+                None
+            }
+        }
+    }
+
     pub fn exclusive_session<F, V>(&self, f: F) -> V
     where
         F: FnOnce(&mut T) -> V,
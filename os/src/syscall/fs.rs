@@ -1,10 +1,103 @@
-use crate::fs::{make_pipe, open_file, OpenFlags};
-use crate::mm::{translated_byte_buffer, translated_refmut, translated_str, UserBuffer};
-use crate::task::{current_process, current_user_token};
+use super::signal::wait_with_mask;
+use super::{EAGAIN, EBUSY, EDEADLK, EFAULT, EINTR, EINVAL, EPERM, ESPIPE};
+use crate::fs::{
+    blocking_lock, chmod, chown, flock, link, lock_range, lookup, lookup_nofollow, make_pipe,
+    may_access, mkdir, mkfifo, mount, open_file, readlink, release_process_locks, rename, rmdir,
+    symlink, umount, unlink, working_dir, File, Inotify, LockError, LockKind, OpenFlags, Pipe,
+    SeekFrom, Stat, IN_ALL_EVENTS, MAY_READ, PIPE_SIZE_MAX,
+};
+use crate::fs::{poll_file, wait_ready, Epoll, EventFd, SignalFd, TimerFd, POLLNVAL};
+use crate::mm::{UserBuffer, UserPtr, UserSlice};
+use crate::task::{
+    capable, current_process, current_user_token, signal_pending, Capabilities, SignalFlags,
+    ERESTARTSYS, RLIMIT_NOFILE,
+};
+use crate::timer::{ktime_now, NSEC_PER_MSEC, NSEC_PER_SEC};
 use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+/// unlinkat removes a directory instead of a file
+const AT_REMOVEDIR: u32 = 0x200;
+/// stat describes a symbolic link itself, like lstat
+const AT_SYMLINK_NOFOLLOW: u32 = 0x100;
+
+const F_DUPFD: usize = 0;
+const F_GETFD: usize = 1;
+const F_SETFD: usize = 2;
+const F_GETFL: usize = 3;
+const F_SETFL: usize = 4;
+const F_GETLK: usize = 5;
+const F_SETLK: usize = 6;
+const F_SETLKW: usize = 7;
+const F_DUPFD_CLOEXEC: usize = 1030;
+const F_SETPIPE_SZ: usize = 1031;
+const F_GETPIPE_SZ: usize = 1032;
+const FD_CLOEXEC: usize = 1;
+
+/// splice does not wait for the pipe.
+const SPLICE_F_NONBLOCK: usize = 2;
+
+const F_RDLCK: i16 = 0;
+const F_WRLCK: i16 = 1;
+const F_UNLCK: i16 = 2;
+
+const LOCK_SH: usize = 1;
+const LOCK_EX: usize = 2;
+const LOCK_NB: usize = 4;
+const LOCK_UN: usize = 8;
+
+const SEEK_SET: usize = 0;
+const SEEK_CUR: usize = 1;
+const SEEK_END: usize = 2;
+
+/// The most pieces readv and writev, and sendmsg and recvmsg, take.
+const IOV_MAX: usize = 1024;
+
+/// A piece of the data of readv and writev, or of sendmsg and recvmsg.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct IoVec {
+    pub base: *mut u8,
+    pub len: usize,
+}
+
+/// The `count` pieces at `iov`.
+pub(super) fn read_iovecs(iov: *const IoVec, count: usize) -> Result<Vec<IoVec>, isize> {
+    if count > IOV_MAX {
+        return Err(EINVAL);
+    }
+    let iov = UserPtr::new(current_user_token(), iov);
+    (0..count)
+        .map(|i| iov.add(i).read().ok_or(EFAULT))
+        .collect()
+}
+
+/// The pieces at `iov` as one buffer, for a file to read into if `write`,
+/// or to write out otherwise, and its length.
+fn iovec_buffer(
+    iov: *const IoVec,
+    count: usize,
+    write: bool,
+) -> Result<(UserBuffer, usize), isize> {
+    let token = current_user_token();
+    let mut buffer = UserBuffer::new(Vec::new(), Vec::new());
+    let mut len = 0usize;
+    for iov in read_iovecs(iov, count)? {
+        len = len.checked_add(iov.len).ok_or(EINVAL)?;
+        let piece = UserSlice::new(token, iov.base, iov.len).buffer(write).ok_or(EFAULT)?;
+        buffer.append(piece);
+    }
+    Ok((buffer, len))
+}
 
 pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
     let token = current_user_token();
+    write_file(fd, len, || UserSlice::new(token, buf, len).buffer(false).ok_or(EFAULT))
+}
+
+/// Write `len` bytes out of the buffer `buffer` gives, the pieces of
+/// writev as the slice of write.
+fn write_file(fd: usize, len: usize, buffer: impl FnOnce() -> Result<UserBuffer, isize>) -> isize {
     let process = current_process();
     let inner = process.inner_exclusive_access();
     if fd >= inner.fd_table.len() {
@@ -17,7 +110,19 @@ pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
         let file = file.clone();
         // release current task TCB manually to avoid multi-borrow
         drop(inner);
-        file.write(UserBuffer::new(translated_byte_buffer(token, buf, len))) as isize
+        if file.status().contains(OpenFlags::NONBLOCK) && !file.write_ready() {
+            return EAGAIN;
+        }
+        match buffer() {
+            // a signal ended the wait before anything was written
+            Ok(buffer) => match file.write(buffer) {
+                0 if len > 0 && !file.write_ready() && !file.hung_up() && signal_pending() => {
+                    ERESTARTSYS
+                }
+                written => written as isize,
+            },
+            Err(errno) => errno,
+        }
     } else {
         -1
     }
@@ -25,6 +130,12 @@ pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
 
 pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> isize {
     let token = current_user_token();
+    read_file(fd, len, || UserSlice::new(token, buf, len).buffer(true).ok_or(EFAULT))
+}
+
+/// Read at most `len` bytes into the buffer `buffer` gives, the pieces of
+/// readv as the slice of read.
+fn read_file(fd: usize, len: usize, buffer: impl FnOnce() -> Result<UserBuffer, isize>) -> isize {
     let process = current_process();
     let inner = process.inner_exclusive_access();
     if fd >= inner.fd_table.len() {
@@ -37,21 +148,313 @@ pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> isize {
         }
         // release current task TCB manually to avoid multi-borrow
         drop(inner);
-        file.read(UserBuffer::new(translated_byte_buffer(token, buf, len))) as isize
+        if file.status().contains(OpenFlags::NONBLOCK) && !file.read_ready() {
+            return EAGAIN;
+        }
+        match buffer() {
+            // a signal ended the wait before anything was read
+            Ok(buffer) => match file.read(buffer) {
+                0 if len > 0 && !file.read_ready() && !file.hung_up() && signal_pending() => {
+                    ERESTARTSYS
+                }
+                read => read as isize,
+            },
+            Err(errno) => errno,
+        }
     } else {
         -1
     }
 }
 
+/// Read into the `iovcnt` pieces at `iov` in turn, as one read of all.
+pub fn sys_readv(fd: usize, iov: *const IoVec, iovcnt: usize) -> isize {
+    match iovec_buffer(iov, iovcnt, true) {
+        Ok((buffer, len)) => read_file(fd, len, || Ok(buffer)),
+        Err(errno) => errno,
+    }
+}
+
+/// Write the `iovcnt` pieces at `iov` in turn, as one write of all.
+pub fn sys_writev(fd: usize, iov: *const IoVec, iovcnt: usize) -> isize {
+    match iovec_buffer(iov, iovcnt, false) {
+        Ok((buffer, len)) => write_file(fd, len, || Ok(buffer)),
+        Err(errno) => errno,
+    }
+}
+
+/// Move up to `len` bytes from the pipe `fd_in` to the file `fd_out`,
+/// at `*off_out`, which is moved on, or else at the offset of the file,
+/// with no copy in user space. Only this way round is there.
+pub fn sys_splice(
+    fd_in: usize,
+    off_in: *const i64,
+    fd_out: usize,
+    off_out: *mut i64,
+    len: usize,
+    flags: usize,
+) -> isize {
+    let token = current_user_token();
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    let (file_in, file_out) = match (inner.fd_table.get(fd_in), inner.fd_table.get(fd_out)) {
+        (Some(Some(file_in)), Some(Some(file_out))) => (file_in.clone(), file_out.clone()),
+        _ => return -1,
+    };
+    drop(inner);
+    if !file_in.readable() || !file_out.writable() {
+        return -1;
+    }
+    let pipe = match file_in.as_any().and_then(|file| file.downcast_ref::<Pipe>()) {
+        Some(pipe) => pipe,
+        None => return EINVAL,
+    };
+    // a pipe has no offset
+    if !off_in.is_null() {
+        return ESPIPE;
+    }
+    let inode = match file_out.inode() {
+        Some(inode) if !inode.is_dir() && file_out.seek(SeekFrom::Current(0)).is_some() => inode,
+        _ => return EINVAL,
+    };
+    let nonblock = flags & SPLICE_F_NONBLOCK != 0 || file_in.status().contains(OpenFlags::NONBLOCK);
+    if nonblock && !file_in.read_ready() {
+        return EAGAIN;
+    }
+    let user_offset = UserPtr::new(token, off_out as *const i64);
+    let offset = if off_out.is_null() {
+        file_out.seek(SeekFrom::Current(0)).unwrap()
+    } else {
+        match user_offset.read() {
+            Some(offset) if offset >= 0 => offset as usize,
+            Some(_) => return EINVAL,
+            None => return EFAULT,
+        }
+    };
+    // O_APPEND goes to the end whatever the offset
+    let append = off_out.is_null() && file_out.status().contains(OpenFlags::APPEND);
+    let moved = pipe.splice_to(&inode, offset, append, len);
+    if moved == 0 && len > 0 && !file_in.read_ready() && signal_pending() {
+        return ERESTARTSYS;
+    }
+    if !off_out.is_null() {
+        if user_offset.write((offset + moved) as i64).is_none() {
+            return EFAULT;
+        }
+    } else if append {
+        file_out.seek(SeekFrom::End(0));
+    } else {
+        file_out.seek(SeekFrom::Start(offset + moved));
+    }
+    moved as isize
+}
+
+pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    let file = match inner.fd_table.get(fd) {
+        Some(Some(file)) => file.clone(),
+        _ => return -1,
+    };
+    drop(inner);
+    let pos = match whence {
+        SEEK_SET if offset >= 0 => SeekFrom::Start(offset as usize),
+        SEEK_CUR => SeekFrom::Current(offset),
+        SEEK_END => SeekFrom::End(offset),
+        _ => return -1,
+    };
+    match file.seek(pos) {
+        Some(offset) => offset as isize,
+        None => -1,
+    }
+}
+
+/// Fill `buf` with `struct linux_dirent64` records of the directory `fd`,
+/// from its offset on, and return the bytes filled, 0 at the end. The
+/// offset of the file is that of the directory, which the records give
+/// as well.
+pub fn sys_getdents64(fd: usize, buf: *mut u8, len: usize) -> isize {
+    let token = current_user_token();
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    let file = match inner.fd_table.get(fd) {
+        Some(Some(file)) if file.readable() => file.clone(),
+        _ => return -1,
+    };
+    drop(inner);
+    let inode = match file.inode() {
+        Some(inode) if inode.is_dir() => inode,
+        _ => return -1,
+    };
+    let mut offset = match file.seek(SeekFrom::Current(0)) {
+        Some(offset) => offset,
+        None => return -1,
+    };
+    let mut records = Vec::new();
+    while let Some((entry, next)) = inode.read_dir(offset) {
+        // d_ino, d_off, d_reclen, d_type, then the name and a NUL
+        let reclen = (19 + entry.name.len() + 1 + 7) & !7;
+        if records.len() + reclen > len {
+            if records.is_empty() {
+                return EINVAL;
+            }
+            break;
+        }
+        records.extend_from_slice(&entry.ino.to_ne_bytes());
+        records.extend_from_slice(&(next as i64).to_ne_bytes());
+        records.extend_from_slice(&(reclen as u16).to_ne_bytes());
+        // DT_DIR, DT_REG and the like are the S_IF bits shifted down
+        records.push((entry.file_type >> 12) as u8);
+        records.extend_from_slice(entry.name.as_bytes());
+        records.resize(records.len() + reclen - 19 - entry.name.len(), 0);
+        offset = next;
+    }
+    if UserSlice::new(token, buf, records.len())
+        .copy_to_user(&records)
+        .is_none()
+    {
+        return EFAULT;
+    }
+    file.seek(SeekFrom::Start(offset));
+    records.len() as isize
+}
+
+pub fn sys_pread64(fd: usize, buf: *const u8, len: usize, offset: usize) -> isize {
+    let token = current_user_token();
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    let file = match inner.fd_table.get(fd) {
+        Some(Some(file)) if file.readable() => file.clone(),
+        _ => return -1,
+    };
+    drop(inner);
+    match UserSlice::new(token, buf, len).buffer(true) {
+        Some(buffer) => file.read_at(offset, buffer).map_or(-1, |len| len as isize),
+        None => EFAULT,
+    }
+}
+
+pub fn sys_pwrite64(fd: usize, buf: *const u8, len: usize, offset: usize) -> isize {
+    let token = current_user_token();
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    let file = match inner.fd_table.get(fd) {
+        Some(Some(file)) if file.writable() => file.clone(),
+        _ => return -1,
+    };
+    drop(inner);
+    match UserSlice::new(token, buf, len).buffer(false) {
+        Some(buffer) => file.write_at(offset, buffer).map_or(-1, |len| len as isize),
+        None => EFAULT,
+    }
+}
+
+pub fn sys_fstat(fd: usize, stat: *mut Stat) -> isize {
+    let token = current_user_token();
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    let file = match inner.fd_table.get(fd) {
+        Some(Some(file)) => file.clone(),
+        _ => return -1,
+    };
+    drop(inner);
+    match UserPtr::new(token, stat).write(file.stat()) {
+        Some(()) => 0,
+        None => EFAULT,
+    }
+}
+
+pub fn sys_fsync(fd: usize) -> isize {
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    let file = match inner.fd_table.get(fd) {
+        Some(Some(file)) => file.clone(),
+        _ => return -1,
+    };
+    drop(inner);
+    file.sync();
+    0
+}
+
+/// easy-fs writes the metadata along with the data, so this is fsync.
+pub fn sys_fdatasync(fd: usize) -> isize {
+    sys_fsync(fd)
+}
+
+pub fn sys_stat(path: *const u8, stat: *mut Stat, flags: u32) -> isize {
+    let token = current_user_token();
+    let path = match UserPtr::new(token, path).read_str() {
+        Some(path) => path,
+        None => return EFAULT,
+    };
+    let inode = if flags & AT_SYMLINK_NOFOLLOW != 0 {
+        lookup_nofollow(path.as_str())
+    } else {
+        lookup(path.as_str())
+    };
+    let inode = match inode {
+        Some(inode) => inode,
+        None => return -1,
+    };
+    match UserPtr::new(token, stat).write(inode.stat()) {
+        Some(()) => 0,
+        None => EFAULT,
+    }
+}
+
+pub fn sys_chdir(path: *const u8) -> isize {
+    let token = current_user_token();
+    let path = match UserPtr::new(token, path).read_str() {
+        Some(path) => path,
+        None => return EFAULT,
+    };
+    match working_dir(path.as_str()) {
+        Some(cwd) => {
+            current_process().inner_exclusive_access().cwd = cwd;
+            0
+        }
+        None => -1,
+    }
+}
+
+/// Copy the working directory with a trailing '\0' to `buf` and return its
+/// length with the '\0', or -1 if `len` is too short for it.
+pub fn sys_getcwd(buf: *mut u8, len: usize) -> isize {
+    let token = current_user_token();
+    let mut cwd = current_process().inner_exclusive_access().cwd.clone();
+    cwd.push('\0');
+    if cwd.len() > len {
+        return -1;
+    }
+    match UserSlice::new(token, buf, cwd.len()).copy_to_user(cwd.as_bytes()) {
+        Some(()) => cwd.len() as isize,
+        None => EFAULT,
+    }
+}
+
 pub fn sys_open(path: *const u8, flags: u32) -> isize {
     let process = current_process();
     let token = current_user_token();
-    let path = translated_str(token, path);
-    if let Some(inode) = open_file(path.as_str(), OpenFlags::from_bits(flags).unwrap()) {
+    let path = match UserPtr::new(token, path).read_str() {
+        Some(path) => path,
+        None => return EFAULT,
+    };
+    let flags = OpenFlags::from_bits(flags).unwrap();
+    let file = open_file(path.as_str(), flags);
+    // a FIFO opened waits for its other side, until a signal comes
+    if file.is_none() && signal_pending() {
+        return ERESTARTSYS;
+    }
+    if let Some(inode) = file {
         let mut inner = process.inner_exclusive_access();
-        let fd = inner.alloc_fd();
-        inner.fd_table[fd] = Some(inode);
-        fd as isize
+        if let Some(fd) = inner.alloc_fd() {
+            inner.fd_table[fd] = Some(inode);
+            if flags.contains(OpenFlags::CLOEXEC) {
+                inner.cloexec.insert(fd);
+            }
+            fd as isize
+        } else {
+            -1
+        }
     } else {
         -1
     }
@@ -66,21 +469,553 @@ pub fn sys_close(fd: usize) -> isize {
     if inner.fd_table[fd].is_none() {
         return -1;
     }
-    inner.fd_table[fd].take();
+    let file = inner.fd_table[fd].take().unwrap();
+    let pid = process.getpid();
+    drop(inner);
+    // closing any descriptor of a file lets go of the record locks on it
+    if let Some(inode) = file.inode() {
+        release_process_locks(pid, Some(&inode));
+    }
     0
 }
 
-pub fn sys_pipe(pipe: *mut usize) -> isize {
+/// flock of the open file `fd`: LOCK_SH or LOCK_EX, or LOCK_UN, with
+/// LOCK_NB not to wait.
+pub fn sys_flock(fd: usize, operation: usize) -> isize {
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    let file = match inner.fd_table.get(fd) {
+        Some(Some(file)) => file.clone(),
+        _ => return -1,
+    };
+    drop(inner);
+    let inode = match file.inode() {
+        Some(inode) => inode,
+        None => return EINVAL,
+    };
+    let kind = match operation & !LOCK_NB {
+        LOCK_SH => Some(LockKind::Shared),
+        LOCK_EX => Some(LockKind::Exclusive),
+        LOCK_UN => None,
+        _ => return EINVAL,
+    };
+    let addr = Arc::as_ptr(&file) as *const u8 as usize;
+    match flock(addr, &inode, kind, operation & LOCK_NB == 0) {
+        Ok(()) => 0,
+        Err(_) => EAGAIN,
+    }
+}
+
+/// A new inotify, with O_NONBLOCK and O_CLOEXEC the flags it may take.
+pub fn sys_inotify_init1(flags: u32) -> isize {
+    let flags = match OpenFlags::from_bits(flags) {
+        Some(flags) if (OpenFlags::NONBLOCK | OpenFlags::CLOEXEC).contains(flags) => flags,
+        _ => return -1,
+    };
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    match inner.alloc_fd() {
+        Some(fd) => {
+            inner.fd_table[fd] = Some(Inotify::new(flags));
+            if flags.contains(OpenFlags::CLOEXEC) {
+                inner.cloexec.insert(fd);
+            }
+            fd as isize
+        }
+        None => -1,
+    }
+}
+
+/// Watch `path` for the events in `mask` with the inotify `fd`, if the
+/// current process may read it, and return the watch descriptor.
+pub fn sys_inotify_add_watch(fd: usize, path: *const u8, mask: u32) -> isize {
+    let token = current_user_token();
+    let path = match UserPtr::new(token, path).read_str() {
+        Some(path) => path,
+        None => return EFAULT,
+    };
+    if mask & IN_ALL_EVENTS == 0 {
+        return EINVAL;
+    }
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    let file = match inner.fd_table.get(fd) {
+        Some(Some(file)) => file.clone(),
+        _ => return -1,
+    };
+    drop(inner);
+    let inotify = match file
+        .as_any()
+        .and_then(|file| file.downcast_ref::<Inotify>())
+    {
+        Some(inotify) => inotify,
+        None => return EINVAL,
+    };
+    match lookup(path.as_str()) {
+        Some(inode) if may_access(&inode, MAY_READ) => inotify.add_watch(inode, mask) as isize,
+        _ => -1,
+    }
+}
+
+pub fn sys_inotify_rm_watch(fd: usize, wd: i32) -> isize {
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    let file = match inner.fd_table.get(fd) {
+        Some(Some(file)) => file.clone(),
+        _ => return -1,
+    };
+    drop(inner);
+    match file
+        .as_any()
+        .and_then(|file| file.downcast_ref::<Inotify>())
+    {
+        Some(inotify) if inotify.rm_watch(wd) => 0,
+        _ => EINVAL,
+    }
+}
+
+/// `struct pollfd` of ppoll.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct PollFd {
+    fd: i32,
+    events: i16,
+    revents: i16,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct TimeSpec {
+    tv_sec: i64,
+    tv_nsec: i64,
+}
+
+impl TimeSpec {
+    /// In ns; None if not a valid time.
+    pub(super) fn to_ns(self) -> Option<u64> {
+        if self.tv_sec < 0 || !(0..1_000_000_000).contains(&self.tv_nsec) {
+            return None;
+        }
+        (self.tv_sec as u64)
+            .checked_mul(NSEC_PER_SEC)?
+            .checked_add(self.tv_nsec as u64)
+    }
+
+    pub(super) fn from_ns(ns: u64) -> Self {
+        Self {
+            tv_sec: (ns / NSEC_PER_SEC) as i64,
+            tv_nsec: (ns % NSEC_PER_SEC) as i64,
+        }
+    }
+}
+
+/// The ns by `ktime_now` at which a wait of `timeout_ns` is over, None for
+/// one without end.
+pub(super) fn deadline_after(timeout_ns: Option<u64>) -> Option<u64> {
+    timeout_ns.map(|timeout_ns| ktime_now().saturating_add(timeout_ns))
+}
+
+/// The timeout at `timeout` in ns, None for a null one, which waits for
+/// ever.
+pub(super) fn read_timeout(token: usize, timeout: *const TimeSpec) -> Result<Option<u64>, isize> {
+    if timeout.is_null() {
+        return Ok(None);
+    }
+    match UserPtr::new(token, timeout).read().map(TimeSpec::to_ns) {
+        Some(Some(ns)) => Ok(Some(ns)),
+        Some(None) => Err(EINVAL),
+        None => Err(EFAULT),
+    }
+}
+
+/// Wait until any of the `nfds` files in `fds` is ready for what is asked
+/// of it, or `timeout` is over, and return how many are; a null `timeout`
+/// waits for ever. Negative fds are skipped. The wait blocks the signals
+/// of `sigmask` instead, unless it is null, and a signal delivered ends it
+/// with EINTR.
+pub fn sys_ppoll(
+    fds: *mut PollFd,
+    nfds: usize,
+    timeout: *const TimeSpec,
+    sigmask: *const u64,
+) -> isize {
+    let token = current_user_token();
+    let timeout_ns = match read_timeout(token, timeout) {
+        Ok(timeout_ns) => timeout_ns,
+        Err(err) => return err,
+    };
+    let process = current_process();
+    if nfds > process.inner_exclusive_access().rlimits.cur(RLIMIT_NOFILE) {
+        return EINVAL;
+    }
+    let user_fds = UserPtr::new(token, fds as *const PollFd);
+    let mut poll_fds = Vec::with_capacity(nfds);
+    for i in 0..nfds {
+        match user_fds.add(i).read() {
+            Some(poll_fd) => poll_fds.push(poll_fd),
+            None => return EFAULT,
+        }
+    }
+    let inner = process.inner_exclusive_access();
+    let polled: Vec<_> = poll_fds
+        .iter()
+        .map(|poll_fd| {
+            let file = usize::try_from(poll_fd.fd)
+                .ok()
+                .map(|fd| inner.fd_table.get(fd).cloned().flatten());
+            (file, poll_fd.events as u16 as u32)
+        })
+        .collect();
+    drop(inner);
+    if !sigmask.is_null() && !wait_with_mask(sigmask) {
+        return EFAULT;
+    }
+    let revents = match wait_ready(deadline_after(timeout_ns), || {
+        let revents: Vec<u32> = polled
+            .iter()
+            .map(|(file, events)| match file {
+                Some(Some(file)) => poll_file(file, *events),
+                Some(None) => POLLNVAL,
+                None => 0,
+            })
+            .collect();
+        revents
+            .iter()
+            .any(|revents| *revents != 0)
+            .then_some(revents)
+    }) {
+        Some(revents) => revents,
+        None if signal_pending() => return EINTR,
+        None => alloc::vec![0; nfds],
+    };
+    for (i, (mut poll_fd, revents)) in poll_fds.into_iter().zip(revents.iter()).enumerate() {
+        poll_fd.revents = *revents as i16;
+        if user_fds.add(i).write(poll_fd).is_none() {
+            return EFAULT;
+        }
+    }
+    revents.iter().filter(|revents| **revents != 0).count() as isize
+}
+
+const EPOLL_CTL_ADD: usize = 1;
+const EPOLL_CTL_DEL: usize = 2;
+const EPOLL_CTL_MOD: usize = 3;
+
+/// `struct epoll_event`, which is not packed on riscv64.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct EpollEvent {
+    events: u32,
+    data: u64,
+}
+
+/// Put `file` in a new fd, with FD_CLOEXEC if `flags` say.
+pub(super) fn install_fd(file: Arc<dyn File + Send + Sync>, flags: OpenFlags) -> isize {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    match inner.alloc_fd() {
+        Some(fd) => {
+            inner.fd_table[fd] = Some(file);
+            if flags.contains(OpenFlags::CLOEXEC) {
+                inner.cloexec.insert(fd);
+            }
+            fd as isize
+        }
+        None => -1,
+    }
+}
+
+pub fn sys_epoll_create1(flags: u32) -> isize {
+    let flags = match OpenFlags::from_bits(flags) {
+        Some(flags) if OpenFlags::CLOEXEC.contains(flags) => flags,
+        _ => return EINVAL,
+    };
+    install_fd(Epoll::new(), flags)
+}
+
+/// Add `fd` to the epoll `epfd`, change what is asked of it or remove it.
+pub fn sys_epoll_ctl(epfd: usize, op: usize, fd: usize, event: *const EpollEvent) -> isize {
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    let (epoll_file, file) = match (inner.fd_table.get(epfd), inner.fd_table.get(fd)) {
+        (Some(Some(epoll_file)), Some(Some(file))) => (epoll_file.clone(), file.clone()),
+        _ => return -1,
+    };
+    drop(inner);
+    let epoll = match epoll_file
+        .as_any()
+        .and_then(|file| file.downcast_ref::<Epoll>())
+    {
+        Some(epoll) => epoll,
+        None => return EINVAL,
+    };
+    if op == EPOLL_CTL_DEL {
+        return if epoll.remove(fd) { 0 } else { -1 };
+    }
+    let event = match UserPtr::new(current_user_token(), event).read() {
+        Some(event) => event,
+        None => return EFAULT,
+    };
+    let done = match op {
+        EPOLL_CTL_ADD => epoll.add(fd, &file, event.events, event.data),
+        EPOLL_CTL_MOD => epoll.modify(fd, event.events, event.data),
+        _ => return EINVAL,
+    };
+    if done {
+        0
+    } else {
+        -1
+    }
+}
+
+/// Wait up to `timeout` ms, for ever if negative, for files of the epoll
+/// `epfd` to be ready, and fill in up to `maxevents` of them. `sigmask`
+/// is as for ppoll.
+pub fn sys_epoll_pwait(
+    epfd: usize,
+    events: *mut EpollEvent,
+    maxevents: usize,
+    timeout: isize,
+    sigmask: *const u64,
+) -> isize {
+    if maxevents == 0 {
+        return EINVAL;
+    }
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    let file = match inner.fd_table.get(epfd) {
+        Some(Some(file)) => file.clone(),
+        _ => return -1,
+    };
+    drop(inner);
+    let epoll = match file.as_any().and_then(|file| file.downcast_ref::<Epoll>()) {
+        Some(epoll) => epoll,
+        None => return EINVAL,
+    };
+    // epoll_wait takes its timeout in ms
+    let timeout_ns = u64::try_from(timeout).ok().map(|ms| ms.saturating_mul(NSEC_PER_MSEC));
+    if !sigmask.is_null() && !wait_with_mask(sigmask) {
+        return EFAULT;
+    }
+    let ready = match wait_ready(deadline_after(timeout_ns), || {
+        let ready = epoll.ready(maxevents);
+        (!ready.is_empty()).then_some(ready)
+    }) {
+        Some(ready) => ready,
+        None if signal_pending() => return EINTR,
+        None => Vec::new(),
+    };
+    let user_events = UserPtr::new(current_user_token(), events as *const EpollEvent);
+    for (i, (events, data)) in ready.iter().enumerate() {
+        let event = EpollEvent {
+            events: *events,
+            data: *data,
+        };
+        if user_events.add(i).write(event).is_none() {
+            return EFAULT;
+        }
+    }
+    ready.len() as isize
+}
+
+/// Reads take 1 from the count of an eventfd, not all of it.
+const EFD_SEMAPHORE: u32 = 1;
+
+/// A new eventfd counting from `initval`, EFD_SEMAPHORE, O_NONBLOCK and
+/// O_CLOEXEC being the flags it takes.
+pub fn sys_eventfd2(initval: u32, flags: u32) -> isize {
+    let semaphore = flags & EFD_SEMAPHORE != 0;
+    let flags = match OpenFlags::from_bits(flags & !EFD_SEMAPHORE) {
+        Some(flags) if (OpenFlags::NONBLOCK | OpenFlags::CLOEXEC).contains(flags) => flags,
+        _ => return EINVAL,
+    };
+    install_fd(EventFd::new(initval as u64, semaphore, flags), flags)
+}
+
+/// A new signalfd taking the signals of `mask` if `fd` is -1, or else
+/// the signalfd `fd` made to take those instead; O_NONBLOCK and O_CLOEXEC
+/// are the flags it takes.
+pub fn sys_signalfd4(fd: usize, mask: *const u64, sizemask: usize, flags: u32) -> isize {
+    if sizemask != 8 {
+        return EINVAL;
+    }
+    let flags = match OpenFlags::from_bits(flags) {
+        Some(flags) if (OpenFlags::NONBLOCK | OpenFlags::CLOEXEC).contains(flags) => flags,
+        _ => return EINVAL,
+    };
+    let mask = match UserPtr::new(current_user_token(), mask).read() {
+        Some(mask) => SignalFlags::from_bits_truncate(mask),
+        None => return EFAULT,
+    };
+    if fd == usize::MAX {
+        return install_fd(SignalFd::new(mask, flags), flags);
+    }
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    let file = match inner.fd_table.get(fd) {
+        Some(Some(file)) => file.clone(),
+        _ => return -1,
+    };
+    drop(inner);
+    match file
+        .as_any()
+        .and_then(|file| file.downcast_ref::<SignalFd>())
+    {
+        Some(signalfd) => {
+            signalfd.set_mask(mask);
+            fd as isize
+        }
+        None => EINVAL,
+    }
+}
+
+pub(super) const CLOCK_REALTIME: usize = 0;
+pub(super) const CLOCK_MONOTONIC: usize = 1;
+/// The time of timerfd_settime is when to expire, not how long from now.
+const TFD_TIMER_ABSTIME: u32 = 1;
+
+/// `struct itimerspec`: the time between expirations and to the next.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct ITimerSpec {
+    it_interval: TimeSpec,
+    it_value: TimeSpec,
+}
+
+impl ITimerSpec {
+    pub(super) fn from_ns((value, interval): (u64, u64)) -> Self {
+        Self {
+            it_interval: TimeSpec::from_ns(interval),
+            it_value: TimeSpec::from_ns(value),
+        }
+    }
+
+    /// The value and interval in ns; None if either is not a valid time.
+    pub(super) fn to_ns(self) -> Option<(u64, u64)> {
+        Some((self.it_value.to_ns()?, self.it_interval.to_ns()?))
+    }
+}
+
+pub fn sys_timerfd_create(clockid: usize, flags: u32) -> isize {
+    if clockid != CLOCK_REALTIME && clockid != CLOCK_MONOTONIC {
+        return EINVAL;
+    }
+    let flags = match OpenFlags::from_bits(flags) {
+        Some(flags) if (OpenFlags::NONBLOCK | OpenFlags::CLOEXEC).contains(flags) => flags,
+        _ => return EINVAL,
+    };
+    install_fd(TimerFd::new(flags), flags)
+}
+
+/// The timerfd `fd` of the current process.
+fn timerfd(fd: usize) -> Result<Arc<dyn File + Send + Sync>, isize> {
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    let file = match inner.fd_table.get(fd) {
+        Some(Some(file)) => file.clone(),
+        _ => return Err(-1),
+    };
+    if file.as_any().map_or(false, |file| file.is::<TimerFd>()) {
+        Ok(file)
+    } else {
+        Err(EINVAL)
+    }
+}
+
+/// Arm the timerfd `fd` as `new` says, or disarm it with a zero value,
+/// and put what it was set to before in `old` if not null.
+pub fn sys_timerfd_settime(
+    fd: usize,
+    flags: u32,
+    new: *const ITimerSpec,
+    old: *mut ITimerSpec,
+) -> isize {
+    if flags & !TFD_TIMER_ABSTIME != 0 {
+        return EINVAL;
+    }
+    let token = current_user_token();
+    let new = match UserPtr::new(token, new).read() {
+        Some(new) => new,
+        None => return EFAULT,
+    };
+    let (value, interval) = match new.to_ns() {
+        Some(times) => times,
+        None => return EINVAL,
+    };
+    let file = match timerfd(fd) {
+        Ok(file) => file,
+        Err(err) => return err,
+    };
+    let deadline = match value {
+        0 => None,
+        _ if flags & TFD_TIMER_ABSTIME != 0 => Some(value),
+        _ => Some(ktime_now().saturating_add(value)),
+    };
+    let timer = file.as_any().unwrap().downcast_ref::<TimerFd>().unwrap();
+    let was = ITimerSpec::from_ns(timer.set(deadline, interval));
+    let old = UserPtr::new(token, old as *const ITimerSpec);
+    if !old.is_null() && old.write(was).is_none() {
+        return EFAULT;
+    }
+    0
+}
+
+/// Put the time to the next expiration of the timerfd `fd`, 0 if it is
+/// disarmed, and the interval in `curr`.
+pub fn sys_timerfd_gettime(fd: usize, curr: *mut ITimerSpec) -> isize {
+    let file = match timerfd(fd) {
+        Ok(file) => file,
+        Err(err) => return err,
+    };
+    let timer = file.as_any().unwrap().downcast_ref::<TimerFd>().unwrap();
+    let now = ITimerSpec::from_ns(timer.get());
+    match UserPtr::new(current_user_token(), curr as *const ITimerSpec).write(now) {
+        Some(()) => 0,
+        None => EFAULT,
+    }
+}
+
+/// A pipe, its read end and its write end put in the fds at `pipe`, both
+/// with FD_CLOEXEC or O_NONBLOCK if `flags` say.
+pub fn sys_pipe(pipe: *mut usize, flags: u32) -> isize {
+    let flags = match OpenFlags::from_bits(flags) {
+        Some(flags) if (OpenFlags::NONBLOCK | OpenFlags::CLOEXEC).contains(flags) => flags,
+        _ => return EINVAL,
+    };
     let process = current_process();
     let token = current_user_token();
     let mut inner = process.inner_exclusive_access();
     let (pipe_read, pipe_write) = make_pipe();
-    let read_fd = inner.alloc_fd();
+    pipe_read.set_status(flags);
+    pipe_write.set_status(flags);
+    let read_fd = match inner.alloc_fd() {
+        Some(fd) => fd,
+        None => return -1,
+    };
     inner.fd_table[read_fd] = Some(pipe_read);
-    let write_fd = inner.alloc_fd();
+    let write_fd = match inner.alloc_fd() {
+        Some(fd) => fd,
+        None => {
+            inner.fd_table[read_fd].take();
+            return -1;
+        }
+    };
     inner.fd_table[write_fd] = Some(pipe_write);
-    *translated_refmut(token, pipe) = read_fd;
-    *translated_refmut(token, unsafe { pipe.add(1) }) = write_fd;
+    if flags.contains(OpenFlags::CLOEXEC) {
+        inner.cloexec.insert(read_fd);
+        inner.cloexec.insert(write_fd);
+    }
+    // writing to user memory may resolve a page fault of this process
+    drop(inner);
+    if UserPtr::new(token, pipe as *const [usize; 2])
+        .write([read_fd, write_fd])
+        .is_none()
+    {
+        let mut inner = process.inner_exclusive_access();
+        inner.fd_table[read_fd].take();
+        inner.fd_table[write_fd].take();
+        return EFAULT;
+    }
     0
 }
 
@@ -93,7 +1028,386 @@ pub fn sys_dup(fd: usize) -> isize {
     if inner.fd_table[fd].is_none() {
         return -1;
     }
-    let new_fd = inner.alloc_fd();
+    let new_fd = match inner.alloc_fd() {
+        Some(fd) => fd,
+        None => return -1,
+    };
     inner.fd_table[new_fd] = Some(Arc::clone(inner.fd_table[fd].as_ref().unwrap()));
     new_fd as isize
 }
+
+/// Make `new_fd` refer to the file of `old_fd`, closing whatever it referred
+/// to before. O_CLOEXEC is the only flag.
+pub fn sys_dup3(old_fd: usize, new_fd: usize, flags: u32) -> isize {
+    let flags = match OpenFlags::from_bits(flags) {
+        Some(flags) if (flags - OpenFlags::CLOEXEC).is_empty() => flags,
+        _ => return -1,
+    };
+    if old_fd == new_fd {
+        return -1;
+    }
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let file = match inner.fd_table.get(old_fd) {
+        Some(Some(file)) => file.clone(),
+        _ => return -1,
+    };
+    if new_fd >= inner.rlimits.cur(RLIMIT_NOFILE) {
+        return -1;
+    }
+    if new_fd >= inner.fd_table.len() {
+        inner.fd_table.resize(new_fd + 1, None);
+    }
+    let closed = inner.fd_table[new_fd].replace(file);
+    if flags.contains(OpenFlags::CLOEXEC) {
+        inner.cloexec.insert(new_fd);
+    } else {
+        inner.cloexec.remove(&new_fd);
+    }
+    drop(inner);
+    // closing may write the file back, which sleeps
+    drop(closed);
+    new_fd as isize
+}
+
+pub fn sys_ioctl(fd: usize, cmd: usize, arg: usize) -> isize {
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    let file = match inner.fd_table.get(fd) {
+        Some(Some(file)) => file.clone(),
+        _ => return -1,
+    };
+    drop(inner);
+    file.ioctl(cmd, arg)
+}
+
+pub fn sys_fcntl(fd: usize, cmd: usize, arg: usize) -> isize {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let file = match inner.fd_table.get(fd) {
+        Some(Some(file)) => file.clone(),
+        _ => return -1,
+    };
+    match cmd {
+        F_DUPFD | F_DUPFD_CLOEXEC => {
+            let new_fd = match inner.alloc_fd_from(arg) {
+                Some(fd) => fd,
+                None => return -1,
+            };
+            inner.fd_table[new_fd] = Some(file);
+            if cmd == F_DUPFD_CLOEXEC {
+                inner.cloexec.insert(new_fd);
+            }
+            new_fd as isize
+        }
+        F_GETFD => {
+            if inner.cloexec.contains(&fd) {
+                FD_CLOEXEC as isize
+            } else {
+                0
+            }
+        }
+        F_SETFD => {
+            if arg & FD_CLOEXEC != 0 {
+                inner.cloexec.insert(fd);
+            } else {
+                inner.cloexec.remove(&fd);
+            }
+            0
+        }
+        F_GETFL => {
+            drop(inner);
+            let access = match (file.readable(), file.writable()) {
+                (true, true) => OpenFlags::RDWR,
+                (false, true) => OpenFlags::WRONLY,
+                _ => OpenFlags::RDONLY,
+            };
+            (access | file.status()).bits() as isize
+        }
+        F_SETFL => {
+            drop(inner);
+            // the access mode and the flags of open only stay as they are
+            file.set_status(OpenFlags::from_bits_truncate(arg as u32) & OpenFlags::STATUS);
+            0
+        }
+        F_GETLK | F_SETLK | F_SETLKW => {
+            let pid = process.getpid();
+            drop(inner);
+            record_lock(pid, &file, cmd, arg as *mut Flock)
+        }
+        F_GETPIPE_SZ | F_SETPIPE_SZ => {
+            drop(inner);
+            let pipe = match file.as_any().and_then(|file| file.downcast_ref::<Pipe>()) {
+                Some(pipe) => pipe,
+                None => return -1,
+            };
+            if cmd == F_GETPIPE_SZ {
+                pipe.size() as isize
+            } else if arg > PIPE_SIZE_MAX {
+                EPERM
+            } else {
+                // not smaller than what is in it
+                pipe.set_size(arg).map_or(EBUSY, |size| size as isize)
+            }
+        }
+        _ => -1,
+    }
+}
+
+/// `struct flock` of fcntl.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Flock {
+    l_type: i16,
+    l_whence: i16,
+    l_start: i64,
+    l_len: i64,
+    l_pid: i32,
+}
+
+/// F_GETLK, F_SETLK and F_SETLKW of `file` for `pid`.
+fn record_lock(
+    pid: usize,
+    file: &Arc<dyn File + Send + Sync>,
+    cmd: usize,
+    arg: *mut Flock,
+) -> isize {
+    let user_flock = UserPtr::new(current_user_token(), arg as *const Flock);
+    let mut lock = match user_flock.read() {
+        Some(lock) => lock,
+        None => return EFAULT,
+    };
+    let inode = match file.inode() {
+        Some(inode) => inode,
+        None => return EINVAL,
+    };
+    let base = match lock.l_whence as usize {
+        SEEK_SET => 0,
+        SEEK_CUR => file.seek(SeekFrom::Current(0)).unwrap_or(0) as i64,
+        SEEK_END => inode.size() as i64,
+        _ => return EINVAL,
+    };
+    let start = base + lock.l_start;
+    // a length of 0 is up to wherever the file ends, a negative one the
+    // bytes before the start
+    let (start, end) = match lock.l_len {
+        0 => (start, None),
+        len if len > 0 => (start, Some(start + len)),
+        len => (start + len, Some(start)),
+    };
+    if start < 0 {
+        return EINVAL;
+    }
+    let (start, end) = (start as u64, end.map_or(u64::MAX, |end| end as u64));
+    let kind = match lock.l_type {
+        F_RDLCK => Some(LockKind::Shared),
+        F_WRLCK => Some(LockKind::Exclusive),
+        F_UNLCK => None,
+        _ => return EINVAL,
+    };
+    if cmd == F_GETLK {
+        let kind = match kind {
+            Some(kind) => kind,
+            None => return EINVAL,
+        };
+        match blocking_lock(pid, &inode, kind, start, end) {
+            Some(held) => {
+                lock.l_type = match held.kind {
+                    LockKind::Shared => F_RDLCK,
+                    LockKind::Exclusive => F_WRLCK,
+                };
+                lock.l_whence = SEEK_SET as i16;
+                lock.l_start = held.start as i64;
+                lock.l_len = if held.end == u64::MAX {
+                    0
+                } else {
+                    (held.end - held.start) as i64
+                };
+                lock.l_pid = held.pid() as i32;
+            }
+            None => lock.l_type = F_UNLCK,
+        }
+        return match user_flock.write(lock) {
+            Some(()) => 0,
+            None => EFAULT,
+        };
+    }
+    // reading locks of files open for reading, writing ones of files open
+    // for writing
+    match kind {
+        Some(LockKind::Shared) if !file.readable() => return -1,
+        Some(LockKind::Exclusive) if !file.writable() => return -1,
+        _ => {}
+    }
+    match lock_range(pid, &inode, kind, start, end, cmd == F_SETLKW) {
+        Ok(()) => 0,
+        Err(LockError::WouldBlock) => EAGAIN,
+        Err(LockError::Deadlock) => EDEADLK,
+    }
+}
+
+/// Mount `source` of `fstype` at `target`, for those with CAP_MOUNT.
+pub fn sys_mount(source: *const u8, target: *const u8, fstype: *const u8) -> isize {
+    if !capable(Capabilities::MOUNT) {
+        return EPERM;
+    }
+    let token = current_user_token();
+    let read_str = |ptr| UserPtr::new(token, ptr).read_str();
+    let (source, target, fstype) = match (read_str(source), read_str(target), read_str(fstype)) {
+        (Some(source), Some(target), Some(fstype)) => (source, target, fstype),
+        _ => return EFAULT,
+    };
+    if mount(source.as_str(), target.as_str(), fstype.as_str()) {
+        0
+    } else {
+        -1
+    }
+}
+
+pub fn sys_umount(target: *const u8) -> isize {
+    if !capable(Capabilities::MOUNT) {
+        return EPERM;
+    }
+    let token = current_user_token();
+    let target = match UserPtr::new(token, target).read_str() {
+        Some(target) => target,
+        None => return EFAULT,
+    };
+    if umount(target.as_str()) {
+        0
+    } else {
+        -1
+    }
+}
+
+pub fn sys_mkdir(path: *const u8) -> isize {
+    let token = current_user_token();
+    let path = match UserPtr::new(token, path).read_str() {
+        Some(path) => path,
+        None => return EFAULT,
+    };
+    if mkdir(path.as_str()) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// Make the named pipe `path`, with the permission bits of `mode`.
+pub fn sys_mkfifo(path: *const u8, mode: u32) -> isize {
+    let token = current_user_token();
+    let path = match UserPtr::new(token, path).read_str() {
+        Some(path) => path,
+        None => return EFAULT,
+    };
+    if mkfifo(path.as_str(), mode) {
+        0
+    } else {
+        -1
+    }
+}
+
+pub fn sys_unlinkat(path: *const u8, flags: u32) -> isize {
+    let token = current_user_token();
+    let path = match UserPtr::new(token, path).read_str() {
+        Some(path) => path,
+        None => return EFAULT,
+    };
+    let removed = if flags & AT_REMOVEDIR != 0 {
+        rmdir(path.as_str())
+    } else {
+        unlink(path.as_str())
+    };
+    if removed {
+        0
+    } else {
+        -1
+    }
+}
+
+pub fn sys_link(old_path: *const u8, new_path: *const u8) -> isize {
+    let token = current_user_token();
+    let read_str = |ptr| UserPtr::new(token, ptr).read_str();
+    let (old_path, new_path) = match (read_str(old_path), read_str(new_path)) {
+        (Some(old_path), Some(new_path)) => (old_path, new_path),
+        _ => return EFAULT,
+    };
+    if link(old_path.as_str(), new_path.as_str()) {
+        0
+    } else {
+        -1
+    }
+}
+
+pub fn sys_chmod(path: *const u8, mode: u32) -> isize {
+    let token = current_user_token();
+    let path = match UserPtr::new(token, path).read_str() {
+        Some(path) => path,
+        None => return EFAULT,
+    };
+    if chmod(path.as_str(), mode) {
+        0
+    } else {
+        -1
+    }
+}
+
+pub fn sys_chown(path: *const u8, uid: u32, gid: u32) -> isize {
+    let token = current_user_token();
+    let path = match UserPtr::new(token, path).read_str() {
+        Some(path) => path,
+        None => return EFAULT,
+    };
+    if chown(path.as_str(), uid, gid) {
+        0
+    } else {
+        -1
+    }
+}
+
+pub fn sys_symlink(target: *const u8, link_path: *const u8) -> isize {
+    let token = current_user_token();
+    let read_str = |ptr| UserPtr::new(token, ptr).read_str();
+    let (target, link_path) = match (read_str(target), read_str(link_path)) {
+        (Some(target), Some(link_path)) => (target, link_path),
+        _ => return EFAULT,
+    };
+    if symlink(target.as_str(), link_path.as_str()) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// Copy where the symbolic link `path` points to `buf`, without a NUL and
+/// cut to `len` bytes, and return the bytes copied.
+pub fn sys_readlink(path: *const u8, buf: *mut u8, len: usize) -> isize {
+    let token = current_user_token();
+    let path = match UserPtr::new(token, path).read_str() {
+        Some(path) => path,
+        None => return EFAULT,
+    };
+    let target = match readlink(path.as_str()) {
+        Some(target) => target,
+        None => return -1,
+    };
+    let len = len.min(target.len());
+    match UserSlice::new(token, buf, len).copy_to_user(&target.as_bytes()[..len]) {
+        Some(()) => len as isize,
+        None => EFAULT,
+    }
+}
+
+pub fn sys_rename(old_path: *const u8, new_path: *const u8) -> isize {
+    let token = current_user_token();
+    let read_str = |ptr| UserPtr::new(token, ptr).read_str();
+    let (old_path, new_path) = match (read_str(old_path), read_str(new_path)) {
+        (Some(old_path), Some(new_path)) => (old_path, new_path),
+        _ => return EFAULT,
+    };
+    if rename(old_path.as_str(), new_path.as_str()) {
+        0
+    } else {
+        -1
+    }
+}
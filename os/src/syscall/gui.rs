@@ -1,14 +1,18 @@
 use crate::drivers::GPU_DEVICE;
-use crate::mm::{MapArea, MapPermission, MapType, PhysAddr, VirtAddr};
+use crate::mm::{virt_to_phys, MapArea, MapPermission, MapType, PhysAddr, VirtAddr};
 use crate::task::current_process;
 
 const FB_VADDR: usize = 0x10000000;
 
 pub fn sys_framebuffer() -> isize {
-    let fb = GPU_DEVICE.get_framebuffer();
+    let gpu = match GPU_DEVICE.as_ref() {
+        Some(gpu) => gpu,
+        None => return -1,
+    };
+    let fb = gpu.get_framebuffer();
     let len = fb.len();
     // println!("[kernel] FrameBuffer: addr 0x{:X}, len {}", fb.as_ptr() as usize , len);
-    let fb_start_pa = PhysAddr::from(fb.as_ptr() as usize);
+    let fb_start_pa = PhysAddr::from(virt_to_phys(fb.as_ptr() as usize));
     assert!(fb_start_pa.aligned());
     let fb_start_ppn = fb_start_pa.floor();
     let fb_start_vpn = VirtAddr::from(FB_VADDR).floor();
@@ -16,6 +20,9 @@ pub fn sys_framebuffer() -> isize {
 
     let current_process = current_process();
     let mut inner = current_process.inner_exclusive_access();
+    if !inner.check_as_limit(len) {
+        return -1;
+    }
     inner.memory_set.push(
         MapArea::new(
             (FB_VADDR as usize).into(),
@@ -29,6 +36,11 @@ pub fn sys_framebuffer() -> isize {
 }
 
 pub fn sys_framebuffer_flush() -> isize {
-    GPU_DEVICE.flush();
-    0
+    match GPU_DEVICE.as_ref() {
+        Some(gpu) => {
+            gpu.flush();
+            0
+        }
+        None => -1,
+    }
 }
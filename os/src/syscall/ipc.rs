@@ -0,0 +1,421 @@
+use super::fs::{install_fd, read_timeout, TimeSpec};
+use super::{EAGAIN, EEXIST, EFAULT, EINTR, EINVAL, EMSGSIZE, ENOENT, ENOMEM, ETIMEDOUT};
+use crate::config::PAGE_SIZE;
+use crate::fs::{
+    make_ring, mq_open, mq_unlink, ring_size, wait_ready, Endpoint, File, IpcMessage, MessageQueue,
+    MqFile, MqOpenError, OpenFlags, IPC_MSG_SIZE, MQ_MAXMSG_DEFAULT, MQ_MAXMSG_MAX,
+    MQ_MSGSIZE_DEFAULT, MQ_MSGSIZE_MAX, MQ_PRIO_MAX,
+};
+use crate::mm::{
+    try_zeroed_bytes, MapBacking, MapPermission, UserPtr, UserSlice, VirtAddr, VirtPageNum,
+};
+use crate::task::{current_process, current_user_token, signal_pending};
+use alloc::string::String;
+use alloc::sync::Arc;
+
+/// The longest name of a message queue.
+const MQ_NAME_MAX: usize = 255;
+
+/// `struct mq_attr`: O_NONBLOCK of the descriptor, what the queue holds at
+/// most and how many messages it does.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct MqAttr {
+    flags: i64,
+    maxmsg: i64,
+    msgsize: i64,
+    curmsgs: i64,
+    pad: [i64; 4],
+}
+
+/// The name of a queue at `name`, one component without the / of
+/// mq_open.
+fn read_name(token: usize, name: *const u8) -> Result<String, isize> {
+    let name = UserPtr::new(token, name).read_str().ok_or(EFAULT)?;
+    if name.is_empty() || name.len() > MQ_NAME_MAX || name.contains('/') {
+        return Err(EINVAL);
+    }
+    Ok(name)
+}
+
+/// The descriptor `mqdes` of the current process and its message queue.
+type OpenQueue = (Arc<dyn File + Send + Sync>, Arc<MessageQueue>);
+
+fn mq_file(mqdes: usize) -> Result<OpenQueue, isize> {
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    let file = match inner.fd_table.get(mqdes) {
+        Some(Some(file)) => file.clone(),
+        _ => return Err(-1),
+    };
+    let queue = match file.as_any().and_then(|file| file.downcast_ref::<MqFile>()) {
+        Some(mq) => mq.queue().clone(),
+        None => return Err(-1),
+    };
+    Ok((file, queue))
+}
+
+/// Open the queue `name`, made with O_CREAT, of `mode` and what `attr`
+/// says it holds at most or the defaults if it is null, unless there is
+/// one; with O_EXCL there may not be.
+pub fn sys_mq_open(name: *const u8, oflag: u32, mode: u32, attr: *const MqAttr) -> isize {
+    let token = current_user_token();
+    let name = match read_name(token, name) {
+        Ok(name) => name,
+        Err(err) => return err,
+    };
+    // what mq_open takes of the flags of open
+    let allowed = OpenFlags::WRONLY
+        | OpenFlags::RDWR
+        | OpenFlags::CREATE
+        | OpenFlags::EXCL
+        | OpenFlags::NONBLOCK
+        | OpenFlags::CLOEXEC;
+    let flags = match OpenFlags::from_bits(oflag) {
+        Some(flags) if allowed.contains(flags) => flags,
+        _ => return EINVAL,
+    };
+    let create = if !flags.contains(OpenFlags::CREATE) {
+        None
+    } else if attr.is_null() {
+        Some((MQ_MAXMSG_DEFAULT, MQ_MSGSIZE_DEFAULT, mode & 0o777))
+    } else {
+        let attr = match UserPtr::new(token, attr).read() {
+            Some(attr) => attr,
+            None => return EFAULT,
+        };
+        if !(1..=MQ_MAXMSG_MAX as i64).contains(&attr.maxmsg)
+            || !(1..=MQ_MSGSIZE_MAX as i64).contains(&attr.msgsize)
+        {
+            return EINVAL;
+        }
+        Some((attr.maxmsg as usize, attr.msgsize as usize, mode & 0o777))
+    };
+    match mq_open(&name, create, flags.contains(OpenFlags::EXCL)) {
+        Ok(queue) => install_fd(MqFile::new(queue, flags), flags),
+        Err(MqOpenError::NotFound) => ENOENT,
+        Err(MqOpenError::Exists) => EEXIST,
+    }
+}
+
+/// Take the name `name` away from its queue, which those who have it open
+/// keep.
+pub fn sys_mq_unlink(name: *const u8) -> isize {
+    match read_name(current_user_token(), name) {
+        Ok(name) if mq_unlink(&name) => 0,
+        Ok(_) => ENOENT,
+        Err(err) => err,
+    }
+}
+
+/// Send the `len` bytes at `msg` with `prio` on `mqdes`, waiting for room
+/// unless O_NONBLOCK, until `abs_timeout` if not null, from boot as are
+/// all clocks.
+pub fn sys_mq_timedsend(
+    mqdes: usize,
+    msg: *const u8,
+    len: usize,
+    prio: u32,
+    abs_timeout: *const TimeSpec,
+) -> isize {
+    let (file, queue) = match mq_file(mqdes) {
+        Ok((file, queue)) if file.writable() => (file, queue),
+        Ok(_) => return -1,
+        Err(err) => return err,
+    };
+    if len > queue.msgsize() {
+        return EMSGSIZE;
+    }
+    if prio >= MQ_PRIO_MAX {
+        return EINVAL;
+    }
+    let token = current_user_token();
+    let mut data = match try_zeroed_bytes(len) {
+        Some(data) => data,
+        None => return ENOMEM,
+    };
+    if UserSlice::new(token, msg, len).copy_from_user(&mut data).is_none() {
+        return EFAULT;
+    }
+    if file.status().contains(OpenFlags::NONBLOCK) {
+        return if queue.try_send(&data, prio) { 0 } else { EAGAIN };
+    }
+    let deadline = match read_timeout(token, abs_timeout) {
+        Ok(deadline) => deadline,
+        Err(err) => return err,
+    };
+    match wait_ready(deadline, || queue.try_send(&data, prio).then_some(())) {
+        Some(()) => 0,
+        None if signal_pending() => EINTR,
+        None => ETIMEDOUT,
+    }
+}
+
+/// Take the first message of the highest priority of `mqdes` into the
+/// `len` bytes at `msg`, and its priority into `prio` if not null, waiting
+/// for one as mq_timedsend does for room; it returns the length of the
+/// message.
+pub fn sys_mq_timedreceive(
+    mqdes: usize,
+    msg: *mut u8,
+    len: usize,
+    prio: *mut u32,
+    abs_timeout: *const TimeSpec,
+) -> isize {
+    let (file, queue) = match mq_file(mqdes) {
+        Ok((file, queue)) if file.readable() => (file, queue),
+        Ok(_) => return -1,
+        Err(err) => return err,
+    };
+    if len < queue.msgsize() {
+        return EMSGSIZE;
+    }
+    let token = current_user_token();
+    let received = if file.status().contains(OpenFlags::NONBLOCK) {
+        match queue.try_receive() {
+            Some(received) => received,
+            None => return EAGAIN,
+        }
+    } else {
+        let deadline = match read_timeout(token, abs_timeout) {
+            Ok(deadline) => deadline,
+            Err(err) => return err,
+        };
+        match wait_ready(deadline, || queue.try_receive()) {
+            Some(received) => received,
+            None if signal_pending() => return EINTR,
+            None => return ETIMEDOUT,
+        }
+    };
+    let (data, msg_prio) = received;
+    // the message is taken even if it cannot be handed over, as in Linux
+    if UserSlice::new(token, msg, data.len()).copy_to_user(&data).is_none() {
+        return EFAULT;
+    }
+    if !prio.is_null() && UserPtr::new(token, prio).write(msg_prio).is_none() {
+        return EFAULT;
+    }
+    data.len() as isize
+}
+
+/// Put the attributes of `mqdes` in `oldattr` if not null, and then take
+/// O_NONBLOCK from `newattr` if not null; the rest is fixed.
+pub fn sys_mq_getsetattr(mqdes: usize, newattr: *const MqAttr, oldattr: *mut MqAttr) -> isize {
+    let (file, queue) = match mq_file(mqdes) {
+        Ok(open) => open,
+        Err(err) => return err,
+    };
+    let token = current_user_token();
+    let new = if newattr.is_null() {
+        None
+    } else {
+        match UserPtr::new(token, newattr).read() {
+            Some(new) if new.flags & !(OpenFlags::NONBLOCK.bits() as i64) == 0 => Some(new),
+            Some(_) => return EINVAL,
+            None => return EFAULT,
+        }
+    };
+    if !oldattr.is_null() {
+        let old = MqAttr {
+            flags: file.status().bits() as i64,
+            maxmsg: queue.maxmsg() as i64,
+            msgsize: queue.msgsize() as i64,
+            curmsgs: queue.count() as i64,
+            ..Default::default()
+        };
+        if UserPtr::new(token, oldattr).write(old).is_none() {
+            return EFAULT;
+        }
+    }
+    if let Some(new) = new {
+        file.set_status(OpenFlags::from_bits_truncate(new.flags as u32));
+    }
+    0
+}
+
+/// Make a ring of `entries` slots, a power of two, of `entry_size` bytes, a
+/// multiple of 8, map it shared anywhere and return its address, putting
+/// in `fds` its consumer end and its producer end, which take O_NONBLOCK
+/// and O_CLOEXEC.
+pub fn sys_ring_create(entries: usize, entry_size: usize, flags: u32, fds: *mut usize) -> isize {
+    let flags = match OpenFlags::from_bits(flags) {
+        Some(flags) if (OpenFlags::NONBLOCK | OpenFlags::CLOEXEC).contains(flags) => flags,
+        _ => return EINVAL,
+    };
+    if ring_size(entries, entry_size).is_none() {
+        return EINVAL;
+    }
+    let (consumer, producer) = match make_ring(entries, entry_size, flags) {
+        Some(ends) => ends,
+        None => return -1,
+    };
+    let size = consumer.size();
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    if !inner.rlimits.mapping_fits(&inner.memory_set, size) {
+        return -1;
+    }
+    let start = match inner.memory_set.find_free_area(VirtPageNum(0), size / PAGE_SIZE) {
+        Some(start) => start,
+        None => return -1,
+    };
+    let backing = MapBacking::Shared {
+        object: consumer.shared_memory(),
+        pgoff: 0,
+    };
+    inner.memory_set.insert_lazy_area(
+        start,
+        (start.0 + size / PAGE_SIZE).into(),
+        MapPermission::R | MapPermission::W | MapPermission::U,
+        backing,
+    );
+    let addr = usize::from(VirtAddr::from(start));
+    drop(inner);
+    let consumer_fd = install_fd(consumer, flags);
+    let producer_fd = install_fd(producer, flags);
+    if consumer_fd < 0 || producer_fd < 0 {
+        close_ring(addr, size, &[consumer_fd, producer_fd]);
+        return -1;
+    }
+    let ends = [consumer_fd as usize, producer_fd as usize];
+    if UserPtr::new(current_user_token(), fds as *const [usize; 2])
+        .write(ends)
+        .is_none()
+    {
+        close_ring(addr, size, &[consumer_fd, producer_fd]);
+        return EFAULT;
+    }
+    addr as isize
+}
+
+/// Undo what sys_ring_create did before it failed.
+fn close_ring(addr: usize, size: usize, fds: &[isize]) {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    for fd in fds.iter().filter(|fd| **fd >= 0) {
+        inner.fd_table[*fd as usize].take();
+    }
+    let start = VirtAddr::from(addr).floor();
+    let removed = inner.memory_set.munmap(start, (start.0 + size / PAGE_SIZE).into());
+    drop(inner);
+    drop(removed);
+}
+
+/// `struct ipc_msg`: up to IPC_MSG_SIZE bytes, and a descriptor whose file
+/// is passed along, -1 for none; the one received is a new descriptor.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct IpcMsg {
+    len: usize,
+    fd: isize,
+    data: [u8; IPC_MSG_SIZE],
+}
+
+/// The message at `msg`, with the file of its descriptor.
+fn read_message(token: usize, msg: *const IpcMsg) -> Result<IpcMessage, isize> {
+    let msg = UserPtr::new(token, msg).read().ok_or(EFAULT)?;
+    if msg.len > IPC_MSG_SIZE {
+        return Err(EMSGSIZE);
+    }
+    let file = match usize::try_from(msg.fd) {
+        Ok(fd) => {
+            let process = current_process();
+            let inner = process.inner_exclusive_access();
+            match inner.fd_table.get(fd) {
+                Some(Some(file)) => Some(file.clone()),
+                _ => return Err(-1),
+            }
+        }
+        Err(_) => None,
+    };
+    Ok(IpcMessage {
+        data: msg.data[..msg.len].to_vec(),
+        file,
+    })
+}
+
+/// Put `message` at `msg`, its file at a new descriptor.
+fn write_message(token: usize, msg: *mut IpcMsg, message: IpcMessage) -> isize {
+    let fd = match message.file {
+        Some(file) => install_fd(file, OpenFlags::empty()),
+        None => -1,
+    };
+    let mut data = [0u8; IPC_MSG_SIZE];
+    data[..message.data.len()].copy_from_slice(&message.data);
+    let written = IpcMsg {
+        len: message.data.len(),
+        fd,
+        data,
+    };
+    if UserPtr::new(token, msg).write(written).is_none() {
+        if fd >= 0 {
+            current_process().inner_exclusive_access().fd_table[fd as usize].take();
+        }
+        return EFAULT;
+    }
+    0
+}
+
+/// The endpoint `fd` of the current process.
+fn endpoint(fd: usize) -> Result<Arc<dyn File + Send + Sync>, isize> {
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    match inner.fd_table.get(fd) {
+        Some(Some(file)) if file.as_any().map_or(false, |file| file.is::<Endpoint>()) => {
+            Ok(file.clone())
+        }
+        _ => Err(-1),
+    }
+}
+
+/// A new endpoint, which takes O_CLOEXEC.
+pub fn sys_endpoint_create(flags: u32) -> isize {
+    let flags = match OpenFlags::from_bits(flags) {
+        Some(flags) if OpenFlags::CLOEXEC.contains(flags) => flags,
+        _ => return EINVAL,
+    };
+    install_fd(Endpoint::new(), flags)
+}
+
+/// Call a server of the endpoint `fd` with `msg` and wait for its reply,
+/// which goes in `reply`.
+pub fn sys_ipc_call(fd: usize, msg: *const IpcMsg, reply: *mut IpcMsg) -> isize {
+    let file = match endpoint(fd) {
+        Ok(file) => file,
+        Err(err) => return err,
+    };
+    let token = current_user_token();
+    let request = match read_message(token, msg) {
+        Ok(request) => request,
+        Err(err) => return err,
+    };
+    let endpoint = file.as_any().unwrap().downcast_ref::<Endpoint>().unwrap();
+    let answer = endpoint.call(request);
+    write_message(token, reply, answer)
+}
+
+/// Reply with `reply` to the call received last on the endpoint `fd`,
+/// unless it is null, and wait for the next call, whose message goes in
+/// `msg`, unless that is null, for a server to reply before it leaves.
+pub fn sys_ipc_reply_recv(fd: usize, reply: *const IpcMsg, msg: *mut IpcMsg) -> isize {
+    let file = match endpoint(fd) {
+        Ok(file) => file,
+        Err(err) => return err,
+    };
+    let endpoint = file.as_any().unwrap().downcast_ref::<Endpoint>().unwrap();
+    let token = current_user_token();
+    if !reply.is_null() {
+        let answer = match read_message(token, reply) {
+            Ok(answer) => answer,
+            Err(err) => return err,
+        };
+        if !endpoint.reply(answer) {
+            return EINVAL;
+        }
+    }
+    if msg.is_null() {
+        return 0;
+    }
+    let request = endpoint.receive();
+    write_message(token, msg, request)
+}
@@ -0,0 +1,453 @@
+use super::EFAULT;
+use crate::config::{MMAP_END, PAGE_SIZE};
+use crate::mm::{
+    mem_info, overlaps_kernel, shm_get, shm_remove, shm_segment, MapArea, MapBacking,
+    MapPermission, MapType, MemInfo, PhysAddr, SharedMemory, UserPtr, VirtAddr, VirtPageNum,
+    VmStat,
+};
+use crate::task::{current_process, current_user_token, handle_page_fault, pid2process};
+use alloc::vec::Vec;
+
+const PROT_READ: usize = 1;
+const PROT_WRITE: usize = 2;
+const PROT_EXEC: usize = 4;
+
+const MAP_SHARED: usize = 0x1;
+const MAP_PRIVATE: usize = 0x2;
+const MAP_FIXED: usize = 0x10;
+const MAP_ANONYMOUS: usize = 0x20;
+
+const MCL_CURRENT: usize = 1;
+
+const MADV_NORMAL: usize = 0;
+const MADV_RANDOM: usize = 1;
+const MADV_SEQUENTIAL: usize = 2;
+const MADV_WILLNEED: usize = 3;
+const MADV_DONTNEED: usize = 4;
+
+const IPC_CREAT: usize = 0o1000;
+const IPC_EXCL: usize = 0o2000;
+const IPC_RMID: usize = 0;
+const SHM_RDONLY: usize = 0o10000;
+
+fn prot_to_permission(prot: usize) -> Option<MapPermission> {
+    if prot & !(PROT_READ | PROT_WRITE | PROT_EXEC) != 0 {
+        return None;
+    }
+    // PROT_READ, PROT_WRITE and PROT_EXEC line up with R, W and X
+    Some(MapPermission::from_bits_truncate((prot << 1) as u8) | MapPermission::U)
+}
+
+/// Page range of `[addr, addr + len)`, which must start at a page boundary,
+/// be non-empty and lie in the lower half of the address space, off the
+/// kernel image.
+fn page_range(addr: usize, len: usize) -> Option<(VirtPageNum, VirtPageNum)> {
+    if addr % PAGE_SIZE != 0 || len == 0 || len > MMAP_END || addr > MMAP_END - len {
+        return None;
+    }
+    let (start, end) = (
+        VirtAddr::from(addr).floor(),
+        VirtAddr::from(addr + len).ceil(),
+    );
+    if overlaps_kernel(start, end) {
+        return None;
+    }
+    Some((start, end))
+}
+
+/// Move the program break to `addr` and return the new break, or return the
+/// current one if `addr` is 0 or the heap cannot be moved there.
+pub fn sys_brk(addr: usize) -> isize {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let brk = inner.memory_set.brk();
+    if addr == 0 {
+        return brk as isize;
+    }
+    let grow = VirtAddr::from(addr)
+        .ceil()
+        .0
+        .saturating_sub(VirtAddr::from(brk).ceil().0);
+    if !inner
+        .rlimits
+        .mapping_fits(&inner.memory_set, grow * PAGE_SIZE)
+    {
+        return brk as isize;
+    }
+    match inner.memory_set.set_brk(addr) {
+        Some(removed) => {
+            drop(inner);
+            drop(removed);
+            addr as isize
+        }
+        None => brk as isize,
+    }
+}
+
+/// Map `len` bytes of zeros or of the file `fd` from `offset` on. `addr` is
+/// only a hint unless MAP_FIXED is given, in which case whatever was there is
+/// unmapped first. Return the start address of the mapping.
+pub fn sys_mmap(
+    addr: usize,
+    len: usize,
+    prot: usize,
+    flags: usize,
+    fd: usize,
+    offset: usize,
+) -> isize {
+    let permission = match prot_to_permission(prot) {
+        Some(permission) => permission,
+        None => return -1,
+    };
+    let shared = match flags & (MAP_SHARED | MAP_PRIVATE) {
+        MAP_SHARED => true,
+        MAP_PRIVATE => false,
+        _ => return -1,
+    };
+    if len == 0 || offset % PAGE_SIZE != 0 {
+        return -1;
+    }
+    let pages = (len + PAGE_SIZE - 1) / PAGE_SIZE;
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let file = if flags & MAP_ANONYMOUS != 0 {
+        None
+    } else {
+        let file = match inner.fd_table.get(fd) {
+            Some(Some(file)) => file.clone(),
+            _ => return -1,
+        };
+        // a shared writable mapping writes the file back
+        if !file.readable() || (shared && prot & PROT_WRITE != 0 && !file.writable()) {
+            return -1;
+        }
+        match file.inode() {
+            Some(inode) => Some(inode),
+            None => return -1,
+        }
+    };
+    // device memory is mapped as it is, and only as far as it goes
+    let phys = file.as_ref().and_then(|file| file.phys_range());
+    if let Some((_, size)) = phys {
+        if !shared || offset + pages * PAGE_SIZE > (size + PAGE_SIZE - 1) / PAGE_SIZE * PAGE_SIZE {
+            return -1;
+        }
+    }
+    let mut removed = None;
+    let start = if flags & MAP_FIXED != 0 {
+        let (start, end) = match page_range(addr, pages * PAGE_SIZE) {
+            Some(range) => range,
+            None => return -1,
+        };
+        if start.0 == 0 {
+            return -1;
+        }
+        removed = match inner.memory_set.munmap(start, end) {
+            Some(areas) => Some(areas),
+            None => return -1,
+        };
+        start
+    } else {
+        let hint = VirtAddr::from(addr).floor();
+        match inner.memory_set.find_free_area(hint, pages) {
+            Some(start) => start,
+            None => return -1,
+        }
+    };
+    if !inner
+        .rlimits
+        .mapping_fits(&inner.memory_set, pages * PAGE_SIZE)
+    {
+        // the fixed range is unmapped anyway, as on Linux
+        drop(inner);
+        drop(removed);
+        return -1;
+    }
+    let start_va: usize = VirtAddr::from(start).into();
+    if let Some((pa, _)) = phys {
+        let pn_offset = PhysAddr::from(pa + offset).floor().0 as isize - start.0 as isize;
+        inner.memory_set.push(
+            MapArea::new(
+                start_va.into(),
+                (start_va + pages * PAGE_SIZE).into(),
+                MapType::Linear(pn_offset),
+                permission,
+            ),
+            None,
+        );
+        drop(inner);
+        drop(removed);
+        return start_va as isize;
+    }
+    let backing = match (file, shared) {
+        (None, false) => MapBacking::Anonymous,
+        (None, true) => MapBacking::Shared {
+            object: SharedMemory::new_anonymous(),
+            pgoff: 0,
+        },
+        (Some(file), false) => MapBacking::File {
+            file,
+            start_va,
+            offset,
+            len: pages * PAGE_SIZE,
+        },
+        (Some(file), true) => match file.shared_memory() {
+            Some(object) => MapBacking::Shared {
+                object,
+                pgoff: offset / PAGE_SIZE,
+            },
+            None => MapBacking::Shared {
+                object: SharedMemory::of_file(file),
+                pgoff: offset / PAGE_SIZE,
+            },
+        },
+    };
+    inner
+        .memory_set
+        .insert_lazy_area(start, (start.0 + pages).into(), permission, backing);
+    // dropping the old areas may write a shared file mapping back
+    drop(inner);
+    drop(removed);
+    start_va as isize
+}
+
+pub fn sys_munmap(addr: usize, len: usize) -> isize {
+    let (start, end) = match page_range(addr, len) {
+        Some(range) => range,
+        None => return -1,
+    };
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let removed = match inner.memory_set.munmap(start, end) {
+        Some(areas) => areas,
+        None => return -1,
+    };
+    // dropping the areas may write a shared file mapping back
+    drop(inner);
+    drop(removed);
+    0
+}
+
+/// All of `[addr, addr + len)` must be mapped.
+pub fn sys_mprotect(addr: usize, len: usize, prot: usize) -> isize {
+    let (start, end) = match page_range(addr, len) {
+        Some(range) => range,
+        None => return -1,
+    };
+    let permission = match prot_to_permission(prot) {
+        Some(permission) => permission,
+        None => return -1,
+    };
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    if inner.memory_set.mprotect(start, end, permission) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// Fault in the `missing` pages of a range just locked or advised to be
+/// needed, which may sleep.
+fn populate(missing: Vec<VirtPageNum>) -> isize {
+    let process = current_process();
+    let token = current_user_token();
+    for vpn in missing {
+        let present = process
+            .inner_exclusive_access()
+            .memory_set
+            .translate(vpn)
+            .map_or(false, |pte| pte.is_valid());
+        // a megapage may have brought the page in with its neighbours
+        if !present && !handle_page_fault(token, VirtAddr::from(vpn).into(), false) {
+            return -1;
+        }
+    }
+    0
+}
+
+/// Lock the pages in `[addr, addr + len)` in memory, faulting them in,
+/// within RLIMIT_MEMLOCK. All of them must be mapped.
+pub fn sys_mlock(addr: usize, len: usize) -> isize {
+    let offset = addr % PAGE_SIZE;
+    let (start, end) = match page_range(addr - offset, len + offset) {
+        Some(range) => range,
+        None => return -1,
+    };
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let limit = inner.rlimits.lock_limit();
+    let missing = match inner.memory_set.mlock(start, end, true, limit) {
+        Some(missing) => missing,
+        None => return -1,
+    };
+    drop(inner);
+    populate(missing)
+}
+
+pub fn sys_munlock(addr: usize, len: usize) -> isize {
+    let offset = addr % PAGE_SIZE;
+    let (start, end) = match page_range(addr - offset, len + offset) {
+        Some(range) => range,
+        None => return -1,
+    };
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    match inner.memory_set.mlock(start, end, false, 0) {
+        Some(_) => 0,
+        None => -1,
+    }
+}
+
+/// Lock all the pages mapped at the moment, for MCL_CURRENT, which is the
+/// only flag supported.
+pub fn sys_mlockall(flags: usize) -> isize {
+    if flags != MCL_CURRENT {
+        return -1;
+    }
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let limit = inner.rlimits.lock_limit();
+    let missing = match inner.memory_set.mlock_all(true, limit) {
+        Some(missing) => missing,
+        None => return -1,
+    };
+    drop(inner);
+    populate(missing)
+}
+
+pub fn sys_munlockall() -> isize {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    inner.memory_set.mlock_all(false, 0);
+    0
+}
+
+/// Act on the advice about how `[addr, addr + len)` is going to be used,
+/// which must all be mapped. MADV_DONTNEED frees the pages at once, to be
+/// faulted in again as zeros or from the file mapped, and MADV_WILLNEED
+/// reads the pages in swap or not yet read from the file. The access
+/// patterns are accepted but make no difference.
+pub fn sys_madvise(addr: usize, len: usize, advice: usize) -> isize {
+    let (start, end) = match page_range(addr, len) {
+        Some(range) => range,
+        None => return -1,
+    };
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    match advice {
+        MADV_NORMAL | MADV_RANDOM | MADV_SEQUENTIAL
+            if inner.memory_set.is_user_range(start, end) =>
+        {
+            0
+        }
+        MADV_WILLNEED => match inner.memory_set.will_need(start, end) {
+            Some(missing) => {
+                drop(inner);
+                populate(missing)
+            }
+            None => -1,
+        },
+        MADV_DONTNEED if inner.memory_set.discard(start, end) => 0,
+        _ => -1,
+    }
+}
+
+/// Get the id of the shared memory segment of `key`, creating one of `size`
+/// bytes if IPC_CREAT is given or `key` is IPC_PRIVATE.
+pub fn sys_shmget(key: usize, size: usize, shmflg: usize) -> isize {
+    match shm_get(key, size, shmflg & IPC_CREAT != 0, shmflg & IPC_EXCL != 0) {
+        Some(id) => id as isize,
+        None => -1,
+    }
+}
+
+/// Map segment `shmid` at `shmaddr`, or anywhere if it is 0, and return the
+/// address. Children inherit the attachment over fork.
+pub fn sys_shmat(shmid: usize, shmaddr: usize, shmflg: usize) -> isize {
+    let segment = match shm_segment(shmid) {
+        Some(segment) => segment,
+        None => return -1,
+    };
+    let permission = if shmflg & SHM_RDONLY != 0 {
+        MapPermission::R | MapPermission::U
+    } else {
+        MapPermission::R | MapPermission::W | MapPermission::U
+    };
+    let pages = segment.size / PAGE_SIZE;
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    if !inner.rlimits.mapping_fits(&inner.memory_set, segment.size) {
+        return -1;
+    }
+    let start = if shmaddr == 0 {
+        match inner.memory_set.find_free_area(VirtPageNum(0), pages) {
+            Some(start) => start,
+            None => return -1,
+        }
+    } else {
+        match page_range(shmaddr, segment.size) {
+            Some((start, end)) if inner.memory_set.is_free(start, end) => start,
+            _ => return -1,
+        }
+    };
+    let backing = MapBacking::Shared {
+        object: segment.object,
+        pgoff: 0,
+    };
+    inner
+        .memory_set
+        .insert_lazy_area(start, (start.0 + pages).into(), permission, backing);
+    usize::from(VirtAddr::from(start)) as isize
+}
+
+/// Unmap the segment attached at `shmaddr`.
+pub fn sys_shmdt(shmaddr: usize) -> isize {
+    if shmaddr % PAGE_SIZE != 0 {
+        return -1;
+    }
+    let start = VirtAddr::from(shmaddr).floor();
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let end = match inner.memory_set.shared_mapping_end(start) {
+        Some(end) => end,
+        None => return -1,
+    };
+    let removed = inner.memory_set.munmap(start, end);
+    // the last detach of a removed segment frees it
+    drop(inner);
+    drop(removed);
+    0
+}
+
+/// Only IPC_RMID is supported, which removes the segment at once for new
+/// users and frees it after its last detach.
+pub fn sys_shmctl(shmid: usize, cmd: usize, _buf: usize) -> isize {
+    match cmd {
+        IPC_RMID if shm_remove(shmid) => 0,
+        _ => -1,
+    }
+}
+
+pub fn sys_meminfo(info: *mut MemInfo) -> isize {
+    match UserPtr::new(current_user_token(), info).write(mem_info()) {
+        Some(()) => 0,
+        None => EFAULT,
+    }
+}
+
+/// Usage of the address space of `pid`, or of this process if `pid` is 0.
+pub fn sys_vmstat(pid: usize, stat: *mut VmStat) -> isize {
+    let process = if pid == 0 {
+        current_process()
+    } else if let Some(process) = pid2process(pid) {
+        process
+    } else {
+        return -1;
+    };
+    let vm_stat = process.inner_exclusive_access().memory_set.vm_stat();
+    match UserPtr::new(current_user_token(), stat).write(vm_stat) {
+        Some(()) => 0,
+        None => EFAULT,
+    }
+}
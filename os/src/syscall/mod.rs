@@ -1,86 +1,475 @@
-const SYSCALL_DUP: usize = 24;
-const SYSCALL_CONNECT: usize = 29;
-const SYSCALL_LISTEN: usize = 30;
-const SYSCALL_ACCEPT: usize = 31;
+const SYSCALL_GETCWD: usize = 17;
+const SYSCALL_EVENTFD2: usize = 19;
+const SYSCALL_EPOLL_CREATE1: usize = 20;
+const SYSCALL_EPOLL_CTL: usize = 21;
+const SYSCALL_EPOLL_PWAIT: usize = 22;
+const SYSCALL_DUP: usize = 23;
+const SYSCALL_DUP3: usize = 24;
+const SYSCALL_FCNTL: usize = 25;
+const SYSCALL_INOTIFY_INIT1: usize = 26;
+const SYSCALL_INOTIFY_ADD_WATCH: usize = 27;
+const SYSCALL_INOTIFY_RM_WATCH: usize = 28;
+const SYSCALL_IOCTL: usize = 29;
+const SYSCALL_FLOCK: usize = 32;
+/// where mknodat would be, for the FIFOs it is here to make
+const SYSCALL_MKFIFO: usize = 33;
+const SYSCALL_MKDIR: usize = 34;
+const SYSCALL_UNLINKAT: usize = 35;
+const SYSCALL_SYMLINK: usize = 36;
+const SYSCALL_LINK: usize = 37;
+const SYSCALL_RENAME: usize = 38;
+const SYSCALL_UMOUNT2: usize = 39;
+const SYSCALL_MOUNT: usize = 40;
+const SYSCALL_CHDIR: usize = 49;
+const SYSCALL_CHMOD: usize = 53;
+const SYSCALL_CHOWN: usize = 54;
 const SYSCALL_OPEN: usize = 56;
 const SYSCALL_CLOSE: usize = 57;
 const SYSCALL_PIPE: usize = 59;
+const SYSCALL_GETDENTS64: usize = 61;
+const SYSCALL_LSEEK: usize = 62;
 const SYSCALL_READ: usize = 63;
 const SYSCALL_WRITE: usize = 64;
+const SYSCALL_READV: usize = 65;
+const SYSCALL_WRITEV: usize = 66;
+const SYSCALL_PREAD64: usize = 67;
+const SYSCALL_PWRITE64: usize = 68;
+const SYSCALL_PPOLL: usize = 73;
+const SYSCALL_SIGNALFD4: usize = 74;
+const SYSCALL_SPLICE: usize = 76;
+const SYSCALL_READLINK: usize = 78;
+const SYSCALL_STAT: usize = 79;
+const SYSCALL_FSTAT: usize = 80;
+const SYSCALL_FSYNC: usize = 82;
+const SYSCALL_FDATASYNC: usize = 83;
+const SYSCALL_TIMERFD_CREATE: usize = 85;
+const SYSCALL_TIMERFD_SETTIME: usize = 86;
+const SYSCALL_TIMERFD_GETTIME: usize = 87;
 const SYSCALL_EXIT: usize = 93;
-const SYSCALL_SLEEP: usize = 101;
+const SYSCALL_WAITID: usize = 95;
+const SYSCALL_FUTEX: usize = 98;
+const SYSCALL_NANOSLEEP: usize = 101;
+const SYSCALL_GETITIMER: usize = 102;
+const SYSCALL_SETITIMER: usize = 103;
+const SYSCALL_TIMER_CREATE: usize = 107;
+const SYSCALL_TIMER_GETTIME: usize = 108;
+const SYSCALL_TIMER_GETOVERRUN: usize = 109;
+const SYSCALL_TIMER_SETTIME: usize = 110;
+const SYSCALL_TIMER_DELETE: usize = 111;
+const SYSCALL_CLOCK_GETTIME: usize = 113;
+const SYSCALL_CLOCK_GETRES: usize = 114;
+const SYSCALL_CLOCK_NANOSLEEP: usize = 115;
+const SYSCALL_SYSLOG: usize = 116;
 const SYSCALL_YIELD: usize = 124;
 const SYSCALL_KILL: usize = 129;
+const SYSCALL_TGKILL: usize = 131;
+const SYSCALL_RT_SIGSUSPEND: usize = 133;
+const SYSCALL_RT_SIGACTION: usize = 134;
+const SYSCALL_RT_SIGPROCMASK: usize = 135;
+const SYSCALL_RT_SIGPENDING: usize = 136;
+const SYSCALL_RT_SIGTIMEDWAIT: usize = 137;
+/// Its result is the a0 the handler interrupted, whatever it is.
+pub const SYSCALL_RT_SIGRETURN: usize = 139;
+const SYSCALL_REBOOT: usize = 142;
+const SYSCALL_SETGID: usize = 144;
+const SYSCALL_SETUID: usize = 146;
+const SYSCALL_SETPGID: usize = 154;
+const SYSCALL_GETPGID: usize = 155;
 const SYSCALL_GET_TIME: usize = 169;
 const SYSCALL_GETPID: usize = 172;
+const SYSCALL_GETUID: usize = 174;
+const SYSCALL_GETGID: usize = 176;
+const SYSCALL_MQ_OPEN: usize = 180;
+const SYSCALL_MQ_UNLINK: usize = 181;
+const SYSCALL_MQ_TIMEDSEND: usize = 182;
+const SYSCALL_MQ_TIMEDRECEIVE: usize = 183;
+const SYSCALL_MQ_GETSETATTR: usize = 185;
+const SYSCALL_SHMGET: usize = 194;
+const SYSCALL_SHMCTL: usize = 195;
+const SYSCALL_SHMAT: usize = 196;
+const SYSCALL_SHMDT: usize = 197;
+const SYSCALL_SOCKET: usize = 198;
+const SYSCALL_SOCKETPAIR: usize = 199;
+const SYSCALL_BIND: usize = 200;
+const SYSCALL_LISTEN: usize = 201;
+const SYSCALL_ACCEPT: usize = 202;
+const SYSCALL_CONNECT: usize = 203;
+const SYSCALL_GETSOCKNAME: usize = 204;
+const SYSCALL_GETPEERNAME: usize = 205;
+const SYSCALL_SENDTO: usize = 206;
+const SYSCALL_RECVFROM: usize = 207;
+const SYSCALL_SETSOCKOPT: usize = 208;
+const SYSCALL_GETSOCKOPT: usize = 209;
+const SYSCALL_SHUTDOWN: usize = 210;
+const SYSCALL_SENDMSG: usize = 211;
+const SYSCALL_RECVMSG: usize = 212;
+const SYSCALL_BRK: usize = 214;
+const SYSCALL_MUNMAP: usize = 215;
 const SYSCALL_FORK: usize = 220;
 const SYSCALL_EXEC: usize = 221;
+const SYSCALL_MMAP: usize = 222;
+const SYSCALL_MPROTECT: usize = 226;
+const SYSCALL_MLOCK: usize = 228;
+const SYSCALL_MUNLOCK: usize = 229;
+const SYSCALL_MLOCKALL: usize = 230;
+const SYSCALL_MUNLOCKALL: usize = 231;
+const SYSCALL_MADVISE: usize = 233;
+const SYSCALL_ACCEPT4: usize = 242;
 const SYSCALL_WAITPID: usize = 260;
+const SYSCALL_PRLIMIT: usize = 261;
+const SYSCALL_SPAWN: usize = 400;
+const SYSCALL_MEMINFO: usize = 410;
+const SYSCALL_VMSTAT: usize = 411;
+const SYSCALL_IFCONFIG: usize = 420;
+const SYSCALL_GETADDRINFO: usize = 421;
+const SYSCALL_NETBENCH: usize = 422;
+const SYSCALL_PIDFD_SEND_SIGNAL: usize = 424;
+const SYSCALL_RING_CREATE: usize = 430;
+const SYSCALL_ENDPOINT_CREATE: usize = 431;
+const SYSCALL_IPC_CALL: usize = 432;
+const SYSCALL_IPC_REPLY_RECV: usize = 433;
+const SYSCALL_PIDFD_OPEN: usize = 434;
+const SYSCALL_TRACE_CTL: usize = 440;
+#[cfg(feature = "fault_inject")]
+const SYSCALL_FAULT_INJECT: usize = 441;
+const SYSCALL_REPLAY: usize = 442;
+const SYSCALL_PERF_READ: usize = 443;
+const SYSCALL_OBJSTAT: usize = 444;
+const SYSCALL_SUSPEND: usize = 445;
+const SYSCALL_CAPGET: usize = 446;
+const SYSCALL_CAPSET: usize = 447;
 const SYSCALL_THREAD_CREATE: usize = 1000;
 const SYSCALL_GETTID: usize = 1001;
 const SYSCALL_WAITTID: usize = 1002;
 const SYSCALL_MUTEX_CREATE: usize = 1010;
 const SYSCALL_MUTEX_LOCK: usize = 1011;
 const SYSCALL_MUTEX_UNLOCK: usize = 1012;
+const SYSCALL_MUTEX_OPEN: usize = 1013;
 const SYSCALL_SEMAPHORE_CREATE: usize = 1020;
 const SYSCALL_SEMAPHORE_UP: usize = 1021;
 const SYSCALL_SEMAPHORE_DOWN: usize = 1022;
 const SYSCALL_CONDVAR_CREATE: usize = 1030;
 const SYSCALL_CONDVAR_SIGNAL: usize = 1031;
 const SYSCALL_CONDVAR_WAIT: usize = 1032;
+const SYSCALL_CONDVAR_OPEN: usize = 1033;
+const SYSCALL_SYNC_UNLINK: usize = 1040;
 const SYSCALL_FRAMEBUFFER: usize = 2000;
 const SYSCALL_FRAMEBUFFER_FLUSH: usize = 2001;
 const SYSCALL_EVENT_GET: usize = 3000;
 const SYSCALL_KEY_PRESSED: usize = 3001;
 
+/// No such process, like a pid prlimit is given which is not there or a
+/// pidfd whose process has exited.
+pub const ESRCH: isize = -3;
+/// Invalid argument, like a resource prlimit does not know or a buffer too
+/// small for a single entry.
+pub const EINVAL: isize = -22;
+/// Operation not permitted, like raising a hard limit or a pipe larger
+/// than the most allowed.
+pub const EPERM: isize = -1;
+/// Try again, for an operation which would have to wait with O_NONBLOCK.
+pub const EAGAIN: isize = -11;
+/// Bad address, for a user pointer which cannot be accessed.
+pub const EFAULT: isize = -14;
+/// No child processes, for waitid with none to wait for.
+pub const ECHILD: isize = -10;
+/// Interrupted system call, for a wait a signal handler ended.
+pub const EINTR: isize = -4;
+/// No such file or directory, or a host name with no address.
+pub const ENOENT: isize = -2;
+/// File exists, for a message queue created with O_EXCL.
+pub const EEXIST: isize = -17;
+/// No such device, like an interface of no such name.
+pub const ENODEV: isize = -19;
+/// Device or resource busy, for netbench while it runs, or a pipe made
+/// smaller than what it holds.
+pub const EBUSY: isize = -16;
+/// Input/output error, for a replay which went other than its recording.
+pub const EIO: isize = -5;
+/// Illegal seek, for an offset into a pipe.
+pub const ESPIPE: isize = -29;
+/// Function not implemented, for what the kernel was built without.
+#[cfg_attr(feature = "netbench", allow(unused))]
+pub const ENOSYS: isize = -38;
+/// Resource deadlock would occur, for a record lock waited for.
+pub const EDEADLK: isize = -35;
+/// Broken pipe, for a socket shut down for writing or reset.
+pub const EPIPE: isize = -32;
+/// Socket operation on non-socket.
+pub const ENOTSOCK: isize = -88;
+/// Destination address required, for a datagram to nobody.
+pub const EDESTADDRREQ: isize = -89;
+/// Message too long, for a datagram larger than a frame.
+pub const EMSGSIZE: isize = -90;
+/// Protocol not available, for a socket option there is not.
+pub const ENOPROTOOPT: isize = -92;
+/// Operation not supported, like listen on a datagram socket.
+pub const EOPNOTSUPP: isize = -95;
+/// Address family not supported, anything but AF_INET.
+pub const EAFNOSUPPORT: isize = -97;
+/// Address already in use, for a port taken.
+pub const EADDRINUSE: isize = -98;
+/// Transport endpoint is already connected.
+pub const EISCONN: isize = -106;
+/// Transport endpoint is not connected.
+pub const ENOTCONN: isize = -107;
+/// Out of memory, for a buffer as large as the caller asks or a kcov area
+/// too large for the frames left.
+pub const ENOMEM: isize = -12;
+/// Connection timed out, for a SYN nobody answered.
+pub const ETIMEDOUT: isize = -110;
+/// Connection refused, for a SYN answered with RST.
+pub const ECONNREFUSED: isize = -111;
+/// Operation already in progress, for connect again while connecting.
+pub const EALREADY: isize = -114;
+/// Operation now in progress, for connect with O_NONBLOCK.
+pub const EINPROGRESS: isize = -115;
+
 mod fs;
 mod gui;
 mod input;
+mod ipc;
+mod mm;
 mod net;
 mod process;
+mod signal;
 mod sync;
+mod syslog;
 mod thread;
+mod timer;
 
 use fs::*;
 use gui::*;
 use input::*;
+use ipc::*;
+use mm::*;
 use net::*;
 use process::*;
+use signal::*;
 use sync::*;
+use syslog::*;
 use thread::*;
+use timer::*;
 
-pub fn syscall(syscall_id: usize, args: [usize; 3]) -> isize {
+pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
     match syscall_id {
+        SYSCALL_GETCWD => sys_getcwd(args[0] as *mut u8, args[1]),
+        SYSCALL_EVENTFD2 => sys_eventfd2(args[0] as u32, args[1] as u32),
+        SYSCALL_EPOLL_CREATE1 => sys_epoll_create1(args[0] as u32),
+        SYSCALL_EPOLL_CTL => sys_epoll_ctl(args[0], args[1], args[2], args[3] as _),
+        SYSCALL_EPOLL_PWAIT => sys_epoll_pwait(
+            args[0],
+            args[1] as *mut EpollEvent,
+            args[2],
+            args[3] as isize,
+            args[4] as _,
+        ),
         SYSCALL_DUP => sys_dup(args[0]),
-        SYSCALL_CONNECT => sys_connect(args[0] as _, args[1] as _, args[2] as _),
-        SYSCALL_LISTEN => sys_listen(args[0] as _),
-        SYSCALL_ACCEPT => sys_accept(args[0] as _),
+        SYSCALL_INOTIFY_INIT1 => sys_inotify_init1(args[0] as u32),
+        SYSCALL_INOTIFY_ADD_WATCH => {
+            sys_inotify_add_watch(args[0], args[1] as *const u8, args[2] as u32)
+        }
+        SYSCALL_INOTIFY_RM_WATCH => sys_inotify_rm_watch(args[0], args[1] as i32),
+        SYSCALL_DUP3 => sys_dup3(args[0], args[1], args[2] as u32),
+        SYSCALL_FCNTL => sys_fcntl(args[0], args[1], args[2]),
+        SYSCALL_FLOCK => sys_flock(args[0], args[1]),
+        SYSCALL_IOCTL => sys_ioctl(args[0], args[1], args[2]),
+        SYSCALL_SOCKET => sys_socket(args[0], args[1], args[2]),
+        SYSCALL_SOCKETPAIR => sys_socketpair(args[0], args[1], args[2], args[3] as _),
+        SYSCALL_BIND => sys_bind(args[0], args[1] as _, args[2]),
+        SYSCALL_LISTEN => sys_listen(args[0], args[1]),
+        SYSCALL_ACCEPT => sys_accept(args[0], args[1] as _, args[2] as _),
+        SYSCALL_ACCEPT4 => sys_accept4(args[0], args[1] as _, args[2] as _, args[3] as u32),
+        SYSCALL_CONNECT => sys_connect(args[0], args[1] as _, args[2]),
+        SYSCALL_GETSOCKNAME => sys_getsockname(args[0], args[1] as _, args[2] as _),
+        SYSCALL_GETPEERNAME => sys_getpeername(args[0], args[1] as _, args[2] as _),
+        SYSCALL_SENDTO => sys_sendto(
+            args[0],
+            args[1] as _,
+            args[2],
+            args[3],
+            args[4] as _,
+            args[5],
+        ),
+        SYSCALL_RECVFROM => sys_recvfrom(
+            args[0],
+            args[1] as _,
+            args[2],
+            args[3],
+            args[4] as _,
+            args[5] as _,
+        ),
+        SYSCALL_SETSOCKOPT => sys_setsockopt(args[0], args[1], args[2], args[3] as _, args[4]),
+        SYSCALL_GETSOCKOPT => sys_getsockopt(args[0], args[1], args[2], args[3] as _, args[4] as _),
+        SYSCALL_SHUTDOWN => sys_shutdown(args[0], args[1]),
+        SYSCALL_SENDMSG => sys_sendmsg(args[0], args[1] as _, args[2]),
+        SYSCALL_RECVMSG => sys_recvmsg(args[0], args[1] as _, args[2]),
+        SYSCALL_MKFIFO => sys_mkfifo(args[0] as *const u8, args[1] as u32),
+        SYSCALL_MKDIR => sys_mkdir(args[0] as *const u8),
+        SYSCALL_UNLINKAT => sys_unlinkat(args[0] as *const u8, args[1] as u32),
+        SYSCALL_SYMLINK => sys_symlink(args[0] as *const u8, args[1] as *const u8),
+        SYSCALL_LINK => sys_link(args[0] as *const u8, args[1] as *const u8),
+        SYSCALL_RENAME => sys_rename(args[0] as *const u8, args[1] as *const u8),
+        SYSCALL_UMOUNT2 => sys_umount(args[0] as *const u8),
+        SYSCALL_MOUNT => sys_mount(
+            args[0] as *const u8,
+            args[1] as *const u8,
+            args[2] as *const u8,
+        ),
+        SYSCALL_CHDIR => sys_chdir(args[0] as *const u8),
+        SYSCALL_CHMOD => sys_chmod(args[0] as *const u8, args[1] as u32),
+        SYSCALL_CHOWN => sys_chown(args[0] as *const u8, args[1] as u32, args[2] as u32),
         SYSCALL_OPEN => sys_open(args[0] as *const u8, args[1] as u32),
         SYSCALL_CLOSE => sys_close(args[0]),
-        SYSCALL_PIPE => sys_pipe(args[0] as *mut usize),
+        SYSCALL_PIPE => sys_pipe(args[0] as *mut usize, args[1] as u32),
         SYSCALL_READ => sys_read(args[0], args[1] as *const u8, args[2]),
         SYSCALL_WRITE => sys_write(args[0], args[1] as *const u8, args[2]),
+        SYSCALL_READV => sys_readv(args[0], args[1] as *const IoVec, args[2]),
+        SYSCALL_WRITEV => sys_writev(args[0], args[1] as *const IoVec, args[2]),
+        SYSCALL_GETDENTS64 => sys_getdents64(args[0], args[1] as *mut u8, args[2]),
+        SYSCALL_LSEEK => sys_lseek(args[0], args[1] as isize, args[2]),
+        SYSCALL_PREAD64 => sys_pread64(args[0], args[1] as *const u8, args[2], args[3]),
+        SYSCALL_PWRITE64 => sys_pwrite64(args[0], args[1] as *const u8, args[2], args[3]),
+        SYSCALL_PPOLL => sys_ppoll(args[0] as *mut PollFd, args[1], args[2] as _, args[3] as _),
+        SYSCALL_SIGNALFD4 => sys_signalfd4(args[0], args[1] as _, args[2], args[3] as u32),
+        SYSCALL_SPLICE => sys_splice(
+            args[0],
+            args[1] as *const i64,
+            args[2],
+            args[3] as *mut i64,
+            args[4],
+            args[5],
+        ),
+        SYSCALL_READLINK => sys_readlink(args[0] as *const u8, args[1] as *mut u8, args[2]),
+        SYSCALL_STAT => sys_stat(args[0] as *const u8, args[1] as _, args[2] as u32),
+        SYSCALL_FSTAT => sys_fstat(args[0], args[1] as _),
+        SYSCALL_FSYNC => sys_fsync(args[0]),
+        SYSCALL_FDATASYNC => sys_fdatasync(args[0]),
+        SYSCALL_TIMERFD_CREATE => sys_timerfd_create(args[0], args[1] as u32),
+        SYSCALL_TIMERFD_SETTIME => {
+            sys_timerfd_settime(args[0], args[1] as u32, args[2] as _, args[3] as _)
+        }
+        SYSCALL_TIMERFD_GETTIME => sys_timerfd_gettime(args[0], args[1] as _),
         SYSCALL_EXIT => sys_exit(args[0] as i32),
-        SYSCALL_SLEEP => sys_sleep(args[0]),
+        SYSCALL_FUTEX => sys_futex(args[0] as _, args[1], args[2], args[3] as _),
+        SYSCALL_NANOSLEEP => sys_nanosleep(args[0] as _, args[1] as _),
+        SYSCALL_GETITIMER => sys_getitimer(args[0], args[1] as _),
+        SYSCALL_SETITIMER => sys_setitimer(args[0], args[1] as _, args[2] as _),
+        SYSCALL_TIMER_CREATE => sys_timer_create(args[0], args[1] as _, args[2] as _),
+        SYSCALL_TIMER_GETTIME => sys_timer_gettime(args[0], args[1] as _),
+        SYSCALL_TIMER_GETOVERRUN => sys_timer_getoverrun(args[0]),
+        SYSCALL_TIMER_SETTIME => {
+            sys_timer_settime(args[0], args[1] as u32, args[2] as _, args[3] as _)
+        }
+        SYSCALL_TIMER_DELETE => sys_timer_delete(args[0]),
+        SYSCALL_CLOCK_GETTIME => sys_clock_gettime(args[0], args[1] as _),
+        SYSCALL_CLOCK_GETRES => sys_clock_getres(args[0], args[1] as _),
+        SYSCALL_CLOCK_NANOSLEEP => {
+            sys_clock_nanosleep(args[0], args[1] as u32, args[2] as _, args[3] as _)
+        }
+        SYSCALL_SYSLOG => sys_syslog(args[0], args[1] as _, args[2]),
+        SYSCALL_REBOOT => sys_reboot(args[0], args[1], args[2]),
         SYSCALL_YIELD => sys_yield(),
-        SYSCALL_KILL => sys_kill(args[0], args[1] as u32),
+        SYSCALL_KILL => sys_kill(args[0] as isize, args[1]),
+        SYSCALL_PIDFD_SEND_SIGNAL => sys_pidfd_send_signal(args[0], args[1], args[2] as _, args[3]),
+        SYSCALL_TGKILL => sys_tgkill(args[0], args[1], args[2]),
+        SYSCALL_RT_SIGSUSPEND => sys_rt_sigsuspend(args[0] as _, args[1]),
+        SYSCALL_RT_SIGACTION => sys_rt_sigaction(args[0], args[1] as _, args[2] as _, args[3]),
+        SYSCALL_RT_SIGPROCMASK => sys_rt_sigprocmask(args[0], args[1] as _, args[2] as _, args[3]),
+        SYSCALL_RT_SIGPENDING => sys_rt_sigpending(args[0] as _, args[1]),
+        SYSCALL_RT_SIGTIMEDWAIT => {
+            sys_rt_sigtimedwait(args[0] as _, args[1] as _, args[2] as _, args[3])
+        }
+        SYSCALL_RT_SIGRETURN => sys_rt_sigreturn(),
+        SYSCALL_SETGID => sys_setgid(args[0] as u32),
+        SYSCALL_SETUID => sys_setuid(args[0] as u32),
+        SYSCALL_SETPGID => sys_setpgid(args[0], args[1]),
+        SYSCALL_GETPGID => sys_getpgid(args[0]),
         SYSCALL_GET_TIME => sys_get_time(),
         SYSCALL_GETPID => sys_getpid(),
+        SYSCALL_GETUID => sys_getuid(),
+        SYSCALL_GETGID => sys_getgid(),
         SYSCALL_FORK => sys_fork(),
-        SYSCALL_EXEC => sys_exec(args[0] as *const u8, args[1] as *const usize),
-        SYSCALL_WAITPID => sys_waitpid(args[0] as isize, args[1] as *mut i32),
+        SYSCALL_MQ_OPEN => sys_mq_open(
+            args[0] as *const u8,
+            args[1] as u32,
+            args[2] as u32,
+            args[3] as *const MqAttr,
+        ),
+        SYSCALL_MQ_UNLINK => sys_mq_unlink(args[0] as *const u8),
+        SYSCALL_MQ_TIMEDSEND => sys_mq_timedsend(
+            args[0],
+            args[1] as *const u8,
+            args[2],
+            args[3] as u32,
+            args[4] as *const TimeSpec,
+        ),
+        SYSCALL_MQ_TIMEDRECEIVE => sys_mq_timedreceive(
+            args[0],
+            args[1] as *mut u8,
+            args[2],
+            args[3] as *mut u32,
+            args[4] as *const TimeSpec,
+        ),
+        SYSCALL_MQ_GETSETATTR => {
+            sys_mq_getsetattr(args[0], args[1] as *const MqAttr, args[2] as *mut MqAttr)
+        }
+        SYSCALL_RING_CREATE => {
+            sys_ring_create(args[0], args[1], args[2] as u32, args[3] as *mut usize)
+        }
+        SYSCALL_ENDPOINT_CREATE => sys_endpoint_create(args[0] as u32),
+        SYSCALL_IPC_CALL => sys_ipc_call(args[0], args[1] as *const IpcMsg, args[2] as *mut IpcMsg),
+        SYSCALL_IPC_REPLY_RECV => {
+            sys_ipc_reply_recv(args[0], args[1] as *const IpcMsg, args[2] as *mut IpcMsg)
+        }
+        SYSCALL_SHMGET => sys_shmget(args[0], args[1], args[2]),
+        SYSCALL_SHMCTL => sys_shmctl(args[0], args[1], args[2]),
+        SYSCALL_SHMAT => sys_shmat(args[0], args[1], args[2]),
+        SYSCALL_SHMDT => sys_shmdt(args[0]),
+        SYSCALL_BRK => sys_brk(args[0]),
+        SYSCALL_MUNMAP => sys_munmap(args[0], args[1]),
+        SYSCALL_MMAP => sys_mmap(args[0], args[1], args[2], args[3], args[4], args[5]),
+        SYSCALL_MPROTECT => sys_mprotect(args[0], args[1], args[2]),
+        SYSCALL_MLOCK => sys_mlock(args[0], args[1]),
+        SYSCALL_MUNLOCK => sys_munlock(args[0], args[1]),
+        SYSCALL_MLOCKALL => sys_mlockall(args[0]),
+        SYSCALL_MUNLOCKALL => sys_munlockall(),
+        SYSCALL_MADVISE => sys_madvise(args[0], args[1], args[2]),
+        SYSCALL_MEMINFO => sys_meminfo(args[0] as _),
+        SYSCALL_VMSTAT => sys_vmstat(args[0], args[1] as _),
+        SYSCALL_IFCONFIG => sys_ifconfig(args[0] as _, args[1] as _, args[2]),
+        SYSCALL_GETADDRINFO => sys_getaddrinfo(args[0] as _, args[1] as _, args[2]),
+        SYSCALL_NETBENCH => sys_netbench(args[0], args[1] as _, args[2]),
+        SYSCALL_EXEC => sys_exec(args[0] as _, args[1] as _, args[2] as _),
+        SYSCALL_SPAWN => sys_spawn(args[0] as _, args[1] as _, args[2] as _),
+        SYSCALL_WAITPID => sys_waitpid(args[0] as isize, args[1] as *mut i32, args[2]),
+        SYSCALL_WAITID => sys_waitid(args[0], args[1], args[2] as _, args[3]),
+        SYSCALL_PIDFD_OPEN => sys_pidfd_open(args[0], args[1] as u32),
+        SYSCALL_TRACE_CTL => sys_trace_ctl(args[0]),
+        #[cfg(feature = "fault_inject")]
+        SYSCALL_FAULT_INJECT => sys_fault_inject(args[0], args[1], args[2]),
+        SYSCALL_REPLAY => sys_replay(args[0], args[1] as _, args[2], args[3]),
+        SYSCALL_PERF_READ => sys_perf_read(args[0], args[1] as _),
+        SYSCALL_OBJSTAT => sys_objstat(args[0] as _, args[1], args[2]),
+        SYSCALL_SUSPEND => sys_suspend(args[0]),
+        SYSCALL_CAPGET => sys_capget(),
+        SYSCALL_CAPSET => sys_capset(args[0] as u32),
+        SYSCALL_PRLIMIT => sys_prlimit(args[0], args[1], args[2] as _, args[3] as _),
         SYSCALL_THREAD_CREATE => sys_thread_create(args[0], args[1]),
         SYSCALL_GETTID => sys_gettid(),
         SYSCALL_WAITTID => sys_waittid(args[0]) as isize,
         SYSCALL_MUTEX_CREATE => sys_mutex_create(args[0] == 1),
         SYSCALL_MUTEX_LOCK => sys_mutex_lock(args[0]),
         SYSCALL_MUTEX_UNLOCK => sys_mutex_unlock(args[0]),
+        SYSCALL_MUTEX_OPEN => sys_mutex_open(args[0] as _, args[1] == 1),
         SYSCALL_SEMAPHORE_CREATE => sys_semaphore_create(args[0]),
         SYSCALL_SEMAPHORE_UP => sys_semaphore_up(args[0]),
         SYSCALL_SEMAPHORE_DOWN => sys_semaphore_down(args[0]),
         SYSCALL_CONDVAR_CREATE => sys_condvar_create(),
         SYSCALL_CONDVAR_SIGNAL => sys_condvar_signal(args[0]),
         SYSCALL_CONDVAR_WAIT => sys_condvar_wait(args[0], args[1]),
+        SYSCALL_CONDVAR_OPEN => sys_condvar_open(args[0] as _),
+        SYSCALL_SYNC_UNLINK => sys_sync_unlink(args[0] as _),
         SYSCALL_FRAMEBUFFER => sys_framebuffer(),
         SYSCALL_FRAMEBUFFER_FLUSH => sys_framebuffer_flush(),
         SYSCALL_EVENT_GET => sys_event_get(),
@@ -1,53 +1,949 @@
-use crate::net::port_table::{accept, listen, port_acceptable, PortFd};
-use crate::net::udp::UDP;
-use crate::net::{net_interrupt_handler, IPv4};
-use crate::task::{current_process, current_task, current_trap_cx};
+use super::fs::{install_fd, read_iovecs, sys_close, IoVec};
+use super::{
+    EAFNOSUPPORT, EFAULT, EINVAL, ENODEV, ENOMEM, ENOPROTOOPT, ENOTCONN, ENOTSOCK, EOPNOTSUPP,
+    EPERM,
+};
+use crate::fs::{File, OpenFlags};
+use crate::mm::{try_zeroed_bytes, UserPtr, UserSlice};
+use crate::net::{
+    dhcp_start, dhcp_state, dhcp_stop, interface, is_loopback, local_ip, lookup, nameserver,
+    set_nameserver, DhcpState, IPv4, IfConfig, InetSocket, Rights, SockType, UnixSocket,
+};
+use crate::task::{capable, current_process, current_user_token, Capabilities};
+use alloc::string::String;
 use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
 
-// just support udp
-pub fn sys_connect(raddr: u32, lport: u16, rport: u16) -> isize {
+const AF_UNIX: usize = 1;
+const AF_INET: usize = 2;
+const SOCK_STREAM: usize = 1;
+const SOCK_DGRAM: usize = 2;
+const SOCK_RAW: usize = 3;
+/// The flags socket takes in its type, above the type itself.
+const SOCK_TYPE_MASK: usize = 0xf;
+const IPPROTO_ICMP: usize = 1;
+const IPPROTO_TCP: usize = 6;
+const IPPROTO_UDP: usize = 17;
+
+const SHUT_RD: usize = 0;
+const SHUT_WR: usize = 1;
+const SHUT_RDWR: usize = 2;
+
+const SOL_SOCKET: usize = 1;
+const SO_SNDBUF: usize = 7;
+const SO_RCVBUF: usize = 8;
+/// A control message of SOL_SOCKET with descriptors to pass.
+const SCM_RIGHTS: i32 = 1;
+
+/// recvmsg had less room for control messages than they took.
+const MSG_CTRUNC: i32 = 0x8;
+/// recvmsg gives the descriptors passed O_CLOEXEC.
+const MSG_CMSG_CLOEXEC: usize = 0x4000_0000;
+
+/// The longest path of `struct sockaddr_un`, with its `\0`.
+const UNIX_PATH_MAX: usize = 108;
+
+/// `struct sockaddr_un`, a path ending with a `\0` or the address.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct SockAddrUn {
+    pub family: u16,
+    pub path: [u8; UNIX_PATH_MAX],
+}
+
+/// `struct sockaddr_in`, with the port and the address in network order.
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+pub struct SockAddrIn {
+    pub family: u16,
+    pub port: u16,
+    pub addr: u32,
+    pub zero: [u8; 8],
+}
+
+/// The address and port at `addr`, 127.0.0.0/8 being taken for our own
+/// address, the only one the stack answers to.
+fn read_addr(addr: *const SockAddrIn, addrlen: usize) -> Result<(IPv4, u16), isize> {
+    if addrlen < core::mem::size_of::<SockAddrIn>() {
+        return Err(EINVAL);
+    }
+    let addr = UserPtr::new(current_user_token(), addr)
+        .read()
+        .ok_or(EFAULT)?;
+    if addr.family as usize != AF_INET {
+        return Err(EAFNOSUPPORT);
+    }
+    let ip = IPv4::from_u32(u32::from_be(addr.addr));
+    let ip = if is_loopback(ip) { local_ip() } else { ip };
+    Ok((ip, u16::from_be(addr.port)))
+}
+
+/// Tell `(ip, port)` at `addr` unless it is null, with its length at
+/// `addrlen`.
+fn write_addr(addr: *mut SockAddrIn, addrlen: *mut u32, (ip, port): (IPv4, u16)) -> isize {
+    if addr.is_null() {
+        return 0;
+    }
+    let token = current_user_token();
+    let len = UserPtr::new(token, addrlen as *const u32).read();
+    if len.map_or(true, |len| {
+        (len as usize) < core::mem::size_of::<SockAddrIn>()
+    }) {
+        return EINVAL;
+    }
+    let sockaddr = SockAddrIn {
+        family: AF_INET as u16,
+        port: port.to_be(),
+        addr: ip.to_u32().to_be(),
+        zero: [0; 8],
+    };
+    match UserPtr::new(token, addr)
+        .write(sockaddr)
+        .and_then(|_| UserPtr::new(token, addrlen).write(core::mem::size_of::<SockAddrIn>() as u32))
+    {
+        Some(()) => 0,
+        None => EFAULT,
+    }
+}
+
+/// The path of the AF_UNIX address at `addr`, which has no abstract
+/// names.
+fn read_unix_addr(addr: *const u8, addrlen: usize) -> Result<String, isize> {
+    if addrlen <= 2 || addrlen > core::mem::size_of::<SockAddrUn>() {
+        return Err(EINVAL);
+    }
+    let mut bytes = vec![0u8; addrlen];
+    UserSlice::new(current_user_token(), addr, addrlen)
+        .copy_from_user(&mut bytes)
+        .ok_or(EFAULT)?;
+    if u16::from_ne_bytes([bytes[0], bytes[1]]) as usize != AF_UNIX {
+        return Err(EAFNOSUPPORT);
+    }
+    let path = &bytes[2..];
+    let end = path.iter().position(|ch| *ch == 0).unwrap_or(path.len());
+    if end == 0 {
+        return Err(EINVAL);
+    }
+    core::str::from_utf8(&path[..end])
+        .map(String::from)
+        .map_err(|_| EINVAL)
+}
+
+/// The AF_UNIX address of `path`, of a socket bound nowhere for None.
+fn unix_addr(path: Option<&str>) -> Vec<u8> {
+    let mut bytes = Vec::from((AF_UNIX as u16).to_ne_bytes());
+    if let Some(path) = path {
+        bytes.extend_from_slice(path.as_bytes());
+        bytes.push(0);
+    }
+    bytes
+}
+
+/// Put what of the address `bytes` fits in the `len` bytes at `addr`;
+/// the length of the whole address, which may have been cut short.
+fn copy_addr(addr: *mut u8, len: u32, bytes: &[u8]) -> Result<u32, isize> {
+    let len = bytes.len().min(len as usize);
+    UserSlice::new(current_user_token(), addr as *const u8, len)
+        .copy_to_user(&bytes[..len])
+        .ok_or(EFAULT)?;
+    Ok(bytes.len() as u32)
+}
+
+/// Tell the AF_UNIX address of `path` at `addr` unless it is null, with
+/// its length at `addrlen`.
+fn write_unix_addr(addr: *mut u8, addrlen: *mut u32, path: Option<&str>) -> isize {
+    if addr.is_null() {
+        return 0;
+    }
+    let token = current_user_token();
+    let len = match UserPtr::new(token, addrlen as *const u32).read() {
+        Some(len) => len,
+        None => return EFAULT,
+    };
+    match copy_addr(addr, len, &unix_addr(path))
+        .and_then(|len| UserPtr::new(token, addrlen).write(len).ok_or(EFAULT))
+    {
+        Ok(()) => 0,
+        Err(errno) => errno,
+    }
+}
+
+/// The open file `fd` of the current process.
+fn fd_file(fd: usize) -> Result<Arc<dyn File + Send + Sync>, isize> {
     let process = current_process();
-    let mut inner = process.inner_exclusive_access();
-    let fd = inner.alloc_fd();
-    let udp_node = UDP::new(IPv4::from_u32(raddr), lport, rport);
-    inner.fd_table[fd] = Some(Arc::new(udp_node));
-    fd as isize
+    let inner = process.inner_exclusive_access();
+    match inner.fd_table.get(fd) {
+        Some(Some(file)) => Ok(file.clone()),
+        _ => Err(-1),
+    }
+}
+
+/// Call `f` with the socket of `fd`.
+fn with_socket<T>(fd: usize, f: impl FnOnce(&InetSocket) -> Result<T, isize>) -> Result<T, isize> {
+    let file = fd_file(fd)?;
+    let socket = file
+        .as_any()
+        .and_then(|file| file.downcast_ref::<InetSocket>())
+        .ok_or(ENOTSOCK)?;
+    f(socket)
+}
+
+/// Call `f` with the socket of `fd` if it is one of AF_UNIX, for the
+/// syscalls to try before taking it for one of AF_INET.
+fn with_unix_socket<T>(
+    fd: usize,
+    f: impl FnOnce(&UnixSocket) -> Result<T, isize>,
+) -> Option<Result<T, isize>> {
+    let file = fd_file(fd).ok()?;
+    let socket = file.as_any()?.downcast_ref::<UnixSocket>()?;
+    Some(f(socket))
+}
+
+fn result(result: Result<usize, isize>) -> isize {
+    match result {
+        Ok(value) => value as isize,
+        Err(errno) => errno,
+    }
+}
+
+/// The flags of socket and socketpair in `kind`.
+fn socket_flags(kind: usize) -> Result<OpenFlags, isize> {
+    match OpenFlags::from_bits((kind & !SOCK_TYPE_MASK) as u32) {
+        Some(flags) if (OpenFlags::NONBLOCK | OpenFlags::CLOEXEC).contains(flags) => Ok(flags),
+        _ => Err(EINVAL),
+    }
+}
+
+/// The type of an AF_UNIX socket, SOCK_STREAM or SOCK_DGRAM.
+fn unix_type(kind: usize, protocol: usize) -> Result<SockType, isize> {
+    match (kind & SOCK_TYPE_MASK, protocol) {
+        (SOCK_STREAM, 0) => Ok(SockType::Stream),
+        (SOCK_DGRAM, 0) => Ok(SockType::Dgram),
+        _ => Err(EINVAL),
+    }
+}
+
+/// An AF_INET socket, SOCK_STREAM for TCP, SOCK_DGRAM for UDP or SOCK_RAW
+/// for ICMP, the last taking CAP_NET, or an AF_UNIX one, SOCK_STREAM or
+/// SOCK_DGRAM; it takes SOCK_NONBLOCK and SOCK_CLOEXEC in `kind`.
+pub fn sys_socket(domain: usize, kind: usize, protocol: usize) -> isize {
+    let flags = match socket_flags(kind) {
+        Ok(flags) => flags,
+        Err(errno) => return errno,
+    };
+    match domain {
+        AF_INET => {}
+        AF_UNIX => {
+            return match unix_type(kind, protocol) {
+                Ok(kind) => install_fd(UnixSocket::new(kind, flags), flags),
+                Err(errno) => errno,
+            }
+        }
+        _ => return EAFNOSUPPORT,
+    }
+    let kind = match (kind & SOCK_TYPE_MASK, protocol) {
+        (SOCK_STREAM, 0 | IPPROTO_TCP) => SockType::Stream,
+        (SOCK_DGRAM, 0 | IPPROTO_UDP) => SockType::Dgram,
+        (SOCK_RAW, IPPROTO_ICMP) => SockType::Raw,
+        _ => return EINVAL,
+    };
+    if kind == SockType::Raw && !capable(Capabilities::NET) {
+        return EPERM;
+    }
+    install_fd(InetSocket::new(kind, flags), flags)
+}
+
+/// Two AF_UNIX sockets connected to each other, their descriptors put at
+/// `sv` as for pipe.
+pub fn sys_socketpair(domain: usize, kind: usize, protocol: usize, sv: *mut usize) -> isize {
+    if domain != AF_UNIX {
+        return EAFNOSUPPORT;
+    }
+    let (flags, kind) = match (socket_flags(kind), unix_type(kind, protocol)) {
+        (Ok(flags), Ok(kind)) => (flags, kind),
+        (Err(errno), _) | (_, Err(errno)) => return errno,
+    };
+    let (a, b) = UnixSocket::pair(kind, flags);
+    let fd_a = install_fd(a, flags);
+    if fd_a < 0 {
+        return fd_a;
+    }
+    let fd_b = install_fd(b, flags);
+    if fd_b < 0 {
+        sys_close(fd_a as usize);
+        return fd_b;
+    }
+    let fds = [fd_a as usize, fd_b as usize];
+    if UserPtr::new(current_user_token(), sv as *const [usize; 2])
+        .write(fds)
+        .is_none()
+    {
+        fds.iter().for_each(|fd| {
+            sys_close(*fd);
+        });
+        return EFAULT;
+    }
+    0
 }
 
-// listen a port
-pub fn sys_listen(port: u16) -> isize {
-    match listen(port) {
-        Some(port_index) => {
-            let process = current_process();
-            let mut inner = process.inner_exclusive_access();
-            let fd = inner.alloc_fd();
-            let port_fd = PortFd::new(port_index);
-            inner.fd_table[fd] = Some(Arc::new(port_fd));
+/// Give the socket the port of `addr`, a free one for 0; the address is
+/// ours whatever it says. An AF_UNIX socket is bound to the path of `addr`,
+/// made a socket file.
+pub fn sys_bind(fd: usize, addr: *const SockAddrIn, addrlen: usize) -> isize {
+    if let Some(done) = with_unix_socket(fd, |socket| {
+        let path = read_unix_addr(addr as *const u8, addrlen)?;
+        socket.bind(&path).map(|_| 0)
+    }) {
+        return result(done);
+    }
+    result(with_socket(fd, |socket| {
+        let (_, port) = read_addr(addr, addrlen)?;
+        socket.bind(port).map(|_| 0)
+    }))
+}
 
-            // NOTICE: this return the port index, not the fd
-            port_index as isize
+pub fn sys_listen(fd: usize, backlog: usize) -> isize {
+    if let Some(done) = with_unix_socket(fd, |socket| socket.listen(backlog).map(|_| 0)) {
+        return result(done);
+    }
+    result(with_socket(fd, |socket| socket.listen(backlog).map(|_| 0)))
+}
+
+/// A connection of the listening socket, its peer told at `addr`.
+pub fn sys_accept4(fd: usize, addr: *mut SockAddrIn, addrlen: *mut u32, flags: u32) -> isize {
+    let flags = match OpenFlags::from_bits(flags) {
+        Some(flags) if (OpenFlags::NONBLOCK | OpenFlags::CLOEXEC).contains(flags) => flags,
+        _ => return EINVAL,
+    };
+    if let Some(done) = with_unix_socket(fd, |socket| socket.accept(flags)) {
+        let socket = match done {
+            Ok(socket) => socket,
+            Err(errno) => return errno,
+        };
+        let peer = socket.peer_path().unwrap();
+        let errno = write_unix_addr(addr as *mut u8, addrlen, peer.as_deref());
+        if errno < 0 {
+            return errno;
         }
-        None => -1,
+        return install_fd(socket, flags);
+    }
+    let socket = match with_socket(fd, |socket| socket.accept(flags)) {
+        Ok(socket) => socket,
+        Err(errno) => return errno,
+    };
+    let peer = socket.peer().unwrap();
+    let errno = write_addr(addr, addrlen, peer);
+    if errno < 0 {
+        return errno;
     }
+    install_fd(socket, flags)
 }
 
-// accept a tcp connection
-pub fn sys_accept(port_index: usize) -> isize {
-    println!("accepting port {}", port_index);
+pub fn sys_accept(fd: usize, addr: *mut SockAddrIn, addrlen: *mut u32) -> isize {
+    sys_accept4(fd, addr, addrlen, 0)
+}
 
-    let task = current_task().unwrap();
-    accept(port_index, task);
-    // block_current_and_run_next();
+/// Send datagrams of the socket to `addr` by default, and take only
+/// theirs; or connect a stream to `addr`, EINPROGRESS with O_NONBLOCK.
+pub fn sys_connect(fd: usize, addr: *const SockAddrIn, addrlen: usize) -> isize {
+    if let Some(done) = with_unix_socket(fd, |socket| {
+        let path = read_unix_addr(addr as *const u8, addrlen)?;
+        socket.connect(&path).map(|_| 0)
+    }) {
+        return result(done);
+    }
+    result(with_socket(fd, |socket| {
+        let (ip, port) = read_addr(addr, addrlen)?;
+        socket.connect(ip, port).map(|_| 0)
+    }))
+}
 
-    // NOTICE: There does not have interrupt handler, just call it munually.
-    loop {
-        net_interrupt_handler();
+/// Tell the port of the socket at `addr`, with our address if it has one.
+pub fn sys_getsockname(fd: usize, addr: *mut SockAddrIn, addrlen: *mut u32) -> isize {
+    if let Some(Ok(path)) = with_unix_socket(fd, |socket| Ok(socket.path())) {
+        return match addr.is_null() {
+            true => EFAULT,
+            false => write_unix_addr(addr as *mut u8, addrlen, path.as_deref()),
+        };
+    }
+    let port = match with_socket(fd, |socket| Ok(socket.local_port())) {
+        Ok(port) => port,
+        Err(errno) => return errno,
+    };
+    let ip = match port {
+        0 => IPv4::new(0, 0, 0, 0),
+        _ => local_ip(),
+    };
+    match addr.is_null() {
+        true => EFAULT,
+        false => write_addr(addr, addrlen, (ip, port)),
+    }
+}
 
-        if !port_acceptable(port_index) {
+/// Tell the peer of the socket at `addr`.
+pub fn sys_getpeername(fd: usize, addr: *mut SockAddrIn, addrlen: *mut u32) -> isize {
+    if let Some(done) = with_unix_socket(fd, |socket| socket.peer_path()) {
+        return match (done, addr.is_null()) {
+            (Err(errno), _) => errno,
+            (Ok(_), true) => EFAULT,
+            (Ok(path), false) => write_unix_addr(addr as *mut u8, addrlen, path.as_deref()),
+        };
+    }
+    let peer = match with_socket(fd, |socket| socket.peer().ok_or(ENOTCONN)) {
+        Ok(peer) => peer,
+        Err(errno) => return errno,
+    };
+    match addr.is_null() {
+        true => EFAULT,
+        false => write_addr(addr, addrlen, peer),
+    }
+}
+
+/// Send `len` bytes of `buf`, to `addr` unless it is null; `flags` are
+/// ignored.
+pub fn sys_sendto(
+    fd: usize,
+    buf: *const u8,
+    len: usize,
+    _flags: usize,
+    addr: *const SockAddrIn,
+    addrlen: usize,
+) -> isize {
+    let mut data = match try_zeroed_bytes(len) {
+        Some(data) => data,
+        None => return ENOMEM,
+    };
+    if UserSlice::new(current_user_token(), buf, len)
+        .copy_from_user(&mut data)
+        .is_none()
+    {
+        return EFAULT;
+    }
+    if let Some(done) = with_unix_socket(fd, |socket| {
+        let dest = match addr.is_null() {
+            true => None,
+            false => Some(read_unix_addr(addr as *const u8, addrlen)?),
+        };
+        socket.send(&data, Vec::new(), dest.as_deref())
+    }) {
+        return result(done);
+    }
+    result(with_socket(fd, |socket| {
+        let dest = match addr.is_null() {
+            true => None,
+            false => Some(read_addr(addr, addrlen)?),
+        };
+        socket.send_to(&data, dest)
+    }))
+}
+
+/// Take at most `len` bytes into `buf`, telling the sender at `addr`
+/// unless it is null; `flags` are ignored.
+pub fn sys_recvfrom(
+    fd: usize,
+    buf: *mut u8,
+    len: usize,
+    _flags: usize,
+    addr: *mut SockAddrIn,
+    addrlen: *mut u32,
+) -> isize {
+    if let Some(done) = with_unix_socket(fd, |socket| {
+        // files passed along are closed, as recvfrom takes none
+        let (data, _, from) = socket.recv(len)?;
+        UserSlice::new(current_user_token(), buf as *const u8, data.len())
+            .copy_to_user(&data)
+            .ok_or(EFAULT)?;
+        if !data.is_empty() {
+            let errno = write_unix_addr(addr as *mut u8, addrlen, from.as_deref());
+            if errno < 0 {
+                return Err(errno);
+            }
+        }
+        Ok(data.len())
+    }) {
+        return result(done);
+    }
+    result(with_socket(fd, |socket| {
+        let (data, ip, port) = socket.recv_from(len)?;
+        UserSlice::new(current_user_token(), buf as *const u8, data.len())
+            .copy_to_user(&data)
+            .ok_or(EFAULT)?;
+        if !data.is_empty() {
+            let errno = write_addr(addr, addrlen, (ip, port));
+            if errno < 0 {
+                return Err(errno);
+            }
+        }
+        Ok(data.len())
+    }))
+}
+
+pub fn sys_shutdown(fd: usize, how: usize) -> isize {
+    let (read, write) = match how {
+        SHUT_RD => (true, false),
+        SHUT_WR => (false, true),
+        SHUT_RDWR => (true, true),
+        _ => return EINVAL,
+    };
+    if let Some(done) = with_unix_socket(fd, |socket| socket.shutdown(read, write).map(|_| 0)) {
+        return result(done);
+    }
+    result(with_socket(fd, |socket| {
+        socket.shutdown(read, write).map(|_| 0)
+    }))
+}
+
+/// `struct msghdr`: the address of the peer, the pieces of the data and the
+/// control messages with it.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct MsgHdr {
+    pub name: *mut u8,
+    pub namelen: u32,
+    pub iov: *const IoVec,
+    pub iovlen: usize,
+    pub control: *mut u8,
+    pub controllen: usize,
+    pub flags: i32,
+}
+
+/// `struct cmsghdr`, followed by its data, the next one starting at the
+/// next multiple of 8.
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct CmsgHdr {
+    len: usize,
+    level: i32,
+    kind: i32,
+}
+
+const CMSG_HDR_LEN: usize = core::mem::size_of::<CmsgHdr>();
+
+/// The pieces of `msg`.
+fn iovecs(msg: &MsgHdr) -> Result<Vec<IoVec>, isize> {
+    read_iovecs(msg.iov, msg.iovlen)
+}
+
+/// The files of the SCM_RIGHTS messages in the control of `msg`.
+fn read_rights(msg: &MsgHdr) -> Result<Rights, isize> {
+    let token = current_user_token();
+    let mut rights = Vec::new();
+    let mut offset = 0;
+    while offset + CMSG_HDR_LEN <= msg.controllen {
+        let cmsg = UserPtr::new(token, msg.control.wrapping_add(offset) as *const CmsgHdr)
+            .read()
+            .ok_or(EFAULT)?;
+        if cmsg.len < CMSG_HDR_LEN || offset + cmsg.len > msg.controllen {
+            return Err(EINVAL);
+        }
+        if (cmsg.level as usize, cmsg.kind) != (SOL_SOCKET, SCM_RIGHTS) {
+            return Err(EINVAL);
+        }
+        let fds = UserPtr::new(
+            token,
+            msg.control.wrapping_add(offset + CMSG_HDR_LEN) as *const i32,
+        );
+        for i in 0..(cmsg.len - CMSG_HDR_LEN) / 4 {
+            let fd = fds.add(i).read().ok_or(EFAULT)?;
+            rights.push(fd_file(fd as usize)?);
+        }
+        offset += (cmsg.len + 7) & !7;
+    }
+    Ok(rights)
+}
+
+/// Give the files of `rights` descriptors, and tell them in an SCM_RIGHTS
+/// message in the control of `msg`, as many as it has room for; the length
+/// of the control messages, and MSG_CTRUNC if some files did not fit and
+/// were closed.
+fn write_rights(msg: &MsgHdr, rights: Rights, flags: OpenFlags) -> Result<(usize, i32), isize> {
+    if rights.is_empty() {
+        return Ok((0, 0));
+    }
+    let room = msg.controllen.saturating_sub(CMSG_HDR_LEN) / 4;
+    let truncated = match rights.len() > room {
+        true => MSG_CTRUNC,
+        false => 0,
+    };
+    if room == 0 {
+        return Ok((0, truncated));
+    }
+    let mut fds = Vec::new();
+    for file in rights.into_iter().take(room) {
+        let fd = install_fd(file, flags);
+        if fd < 0 {
             break;
         }
+        fds.push(fd as i32);
+    }
+    let token = current_user_token();
+    let cmsg = CmsgHdr {
+        len: CMSG_HDR_LEN + fds.len() * 4,
+        level: SOL_SOCKET as i32,
+        kind: SCM_RIGHTS,
+    };
+    let data = UserPtr::new(token, msg.control.wrapping_add(CMSG_HDR_LEN) as *const i32);
+    let written = UserPtr::new(token, msg.control as *const CmsgHdr)
+        .write(cmsg)
+        .and_then(|_| (0..fds.len()).try_for_each(|i| data.add(i).write(fds[i])));
+    if written.is_none() {
+        fds.iter().for_each(|fd| {
+            sys_close(*fd as usize);
+        });
+        return Err(EFAULT);
     }
+    Ok((cmsg.len, truncated))
+}
+
+/// Send the pieces of `msg` as one, to its address unless that is null;
+/// an AF_UNIX socket passes the files of the SCM_RIGHTS messages in its
+/// control along. `flags` are ignored.
+pub fn sys_sendmsg(fd: usize, msg: *const MsgHdr, _flags: usize) -> isize {
+    let token = current_user_token();
+    let msg = match UserPtr::new(token, msg).read() {
+        Some(msg) => msg,
+        None => return EFAULT,
+    };
+    let mut data = Vec::new();
+    let iovecs = match iovecs(&msg) {
+        Ok(iovecs) => iovecs,
+        Err(errno) => return errno,
+    };
+    for iov in iovecs {
+        let start = data.len();
+        data.resize(start + iov.len, 0);
+        if UserSlice::new(token, iov.base, iov.len)
+            .copy_from_user(&mut data[start..])
+            .is_none()
+        {
+            return EFAULT;
+        }
+    }
+    let rights = match read_rights(&msg) {
+        Ok(rights) => rights,
+        Err(errno) => return errno,
+    };
+    let named = !msg.name.is_null();
+    if let Some(done) = with_unix_socket(fd, |socket| {
+        let dest = match named {
+            true => Some(read_unix_addr(msg.name, msg.namelen as usize)?),
+            false => None,
+        };
+        socket.send(&data, rights.clone(), dest.as_deref())
+    }) {
+        return result(done);
+    }
+    result(with_socket(fd, |socket| {
+        if !rights.is_empty() {
+            return Err(EOPNOTSUPP);
+        }
+        let dest = match named {
+            true => Some(read_addr(
+                msg.name as *const SockAddrIn,
+                msg.namelen as usize,
+            )?),
+            false => None,
+        };
+        socket.send_to(&data, dest)
+    }))
+}
 
-    let cx = current_trap_cx();
-    cx.x[10] as isize
+/// Take into the pieces of `msg` what there is, telling the sender at its
+/// address unless that is null; an AF_UNIX socket gives the files passed
+/// along descriptors, told in an SCM_RIGHTS message in its control, which
+/// are O_CLOEXEC with MSG_CMSG_CLOEXEC in `flags`.
+pub fn sys_recvmsg(fd: usize, msg: *mut MsgHdr, flags: usize) -> isize {
+    let token = current_user_token();
+    let mut hdr = match UserPtr::new(token, msg as *const MsgHdr).read() {
+        Some(hdr) => hdr,
+        None => return EFAULT,
+    };
+    let iovecs = match iovecs(&hdr) {
+        Ok(iovecs) => iovecs,
+        Err(errno) => return errno,
+    };
+    let len = iovecs.iter().map(|iov| iov.len).sum();
+    let received = match with_unix_socket(fd, |socket| socket.recv(len)) {
+        Some(done) => done.map(|(data, rights, from)| (data, rights, unix_addr(from.as_deref()))),
+        None => with_socket(fd, |socket| {
+            let (data, ip, port) = socket.recv_from(len)?;
+            let addr = SockAddrIn {
+                family: AF_INET as u16,
+                port: port.to_be(),
+                addr: ip.to_u32().to_be(),
+                zero: [0; 8],
+            };
+            let addr = unsafe {
+                core::slice::from_raw_parts(
+                    &addr as *const SockAddrIn as *const u8,
+                    core::mem::size_of::<SockAddrIn>(),
+                )
+            };
+            Ok((data, Vec::new(), Vec::from(addr)))
+        }),
+    };
+    let (data, rights, addr) = match received {
+        Ok(received) => received,
+        Err(errno) => return errno,
+    };
+    let mut copied = 0;
+    for iov in iovecs {
+        let end = data.len().min(copied + iov.len);
+        if UserSlice::new(token, iov.base, end - copied)
+            .copy_to_user(&data[copied..end])
+            .is_none()
+        {
+            return EFAULT;
+        }
+        copied = end;
+    }
+    if !hdr.name.is_null() {
+        hdr.namelen = match copy_addr(hdr.name, hdr.namelen, &addr) {
+            Ok(len) => len,
+            Err(errno) => return errno,
+        };
+    }
+    let status = match flags & MSG_CMSG_CLOEXEC {
+        0 => OpenFlags::empty(),
+        _ => OpenFlags::CLOEXEC,
+    };
+    (hdr.controllen, hdr.flags) = match write_rights(&hdr, rights, status) {
+        Ok(control) => control,
+        Err(errno) => return errno,
+    };
+    match UserPtr::new(token, msg as *const MsgHdr).write(hdr) {
+        Some(()) => data.len() as isize,
+        None => EFAULT,
+    }
+}
+
+/// Whether `optname` of `level` is SO_SNDBUF rather than SO_RCVBUF, the
+/// options there are.
+fn buffer_option(level: usize, optname: usize) -> Result<bool, isize> {
+    match (level, optname) {
+        (SOL_SOCKET, SO_SNDBUF) => Ok(true),
+        (SOL_SOCKET, SO_RCVBUF) => Ok(false),
+        _ => Err(ENOPROTOOPT),
+    }
+}
+
+/// Set the size of the send or the receive buffer to the int at `optval`,
+/// as it is rather than doubled, within the bounds of the stack.
+pub fn sys_setsockopt(
+    fd: usize,
+    level: usize,
+    optname: usize,
+    optval: *const i32,
+    optlen: usize,
+) -> isize {
+    let option = buffer_option(level, optname).and_then(|send| {
+        if optlen < core::mem::size_of::<i32>() {
+            return Err(EINVAL);
+        }
+        let size = UserPtr::new(current_user_token(), optval)
+            .read()
+            .ok_or(EFAULT)?;
+        Ok((send, size.max(0) as usize))
+    });
+    if let Some(done) = with_unix_socket(fd, |socket| {
+        let (send, size) = option?;
+        socket.set_buffer_size(send, size);
+        Ok(0)
+    }) {
+        return result(done);
+    }
+    result(with_socket(fd, |socket| {
+        let (send, size) = option?;
+        socket.set_buffer_size(send, size);
+        Ok(0)
+    }))
+}
+
+/// Tell the size of the send or the receive buffer at `optval`, as an int.
+pub fn sys_getsockopt(
+    fd: usize,
+    level: usize,
+    optname: usize,
+    optval: *mut i32,
+    optlen: *mut u32,
+) -> isize {
+    let sizes = match with_unix_socket(fd, |socket| Ok(socket.buffer_sizes())) {
+        Some(done) => done,
+        None => with_socket(fd, |socket| Ok(socket.buffer_sizes())),
+    };
+    result(sizes.and_then(|(sndbuf, rcvbuf)| {
+        let send = buffer_option(level, optname)?;
+        let token = current_user_token();
+        let len = UserPtr::new(token, optlen as *const u32)
+            .read()
+            .ok_or(EFAULT)?;
+        if (len as usize) < core::mem::size_of::<i32>() {
+            return Err(EINVAL);
+        }
+        let size = if send { sndbuf } else { rcvbuf };
+        UserPtr::new(token, optval)
+            .write(size as i32)
+            .and_then(|_| UserPtr::new(token, optlen).write(core::mem::size_of::<i32>() as u32))
+            .ok_or(EFAULT)?;
+        Ok(0)
+    }))
+}
+
+const IFCONFIG_GET: usize = 0;
+const IFCONFIG_SET: usize = 1;
+/// Have eth0 ask DHCP for its address.
+const IFCONFIG_DHCP: usize = 2;
+
+/// What ifconfig tells of an interface, and gives it: the addresses in
+/// network order, the nameserver being that of eth0 and kept if 0, and for
+/// `dhcp` 0 if DHCP is off, 1 while it asks for an address and 2 once it
+/// has one.
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+pub struct IfReq {
+    pub addr: u32,
+    pub netmask: u32,
+    pub gateway: u32,
+    pub nameserver: u32,
+    pub mac: [u8; 6],
+    pub dhcp: u8,
+    pub pad: u8,
+}
+
+/// Tell the setup of the interface `name` at `req`, or, with CAP_NET, give it
+/// that at `req` or have DHCP set it up.
+pub fn sys_ifconfig(name: *const u8, req: *mut IfReq, op: usize) -> isize {
+    let token = current_user_token();
+    let name = match UserPtr::new(token, name).read_str() {
+        Some(name) => name,
+        None => return EFAULT,
+    };
+    let iface = match interface(&name) {
+        Some(iface) => iface,
+        None => return ENODEV,
+    };
+    if op != IFCONFIG_GET && !capable(Capabilities::NET) {
+        return EPERM;
+    }
+    let is_eth0 = iface.name() == "eth0";
+    match op {
+        IFCONFIG_GET => {
+            let config = iface.config();
+            let dhcp = match (is_eth0, dhcp_state()) {
+                (false, _) | (_, DhcpState::Off) => 0,
+                (_, DhcpState::Asking) => 1,
+                (_, DhcpState::Bound) => 2,
+            };
+            let ifreq = IfReq {
+                addr: config.ip.to_u32().to_be(),
+                netmask: config.netmask.to_u32().to_be(),
+                gateway: config.gateway.to_u32().to_be(),
+                nameserver: match is_eth0 {
+                    true => nameserver().to_u32().to_be(),
+                    false => 0,
+                },
+                mac: iface.mac(),
+                dhcp,
+                pad: 0,
+            };
+            match UserPtr::new(token, req).write(ifreq) {
+                Some(()) => 0,
+                None => EFAULT,
+            }
+        }
+        IFCONFIG_SET => {
+            let ifreq = match UserPtr::new(token, req as *const IfReq).read() {
+                Some(ifreq) => ifreq,
+                None => return EFAULT,
+            };
+            let config = IfConfig {
+                ip: IPv4::from_u32(u32::from_be(ifreq.addr)),
+                netmask: IPv4::from_u32(u32::from_be(ifreq.netmask)),
+                gateway: IPv4::from_u32(u32::from_be(ifreq.gateway)),
+            };
+            // the ones of the netmask come first
+            let mask = config.netmask.to_u32();
+            if config.ip.to_u32() == 0 || mask.leading_ones() != mask.count_ones() || !is_eth0 {
+                return EINVAL;
+            }
+            dhcp_stop();
+            iface.set_config(config);
+            if ifreq.nameserver != 0 {
+                set_nameserver(IPv4::from_u32(u32::from_be(ifreq.nameserver)));
+            }
+            0
+        }
+        IFCONFIG_DHCP if is_eth0 => {
+            dhcp_start();
+            0
+        }
+        _ => EINVAL,
+    }
+}
+
+/// Put at most `len` addresses of the host `name` at `addrs`, in network
+/// order, and return how many it has; ENOENT for none.
+pub fn sys_getaddrinfo(name: *const u8, addrs: *mut u32, len: usize) -> isize {
+    let token = current_user_token();
+    let name = match UserPtr::new(token, name).read_str() {
+        Some(name) => name,
+        None => return EFAULT,
+    };
+    let found = match lookup(&name) {
+        Ok(found) => found,
+        Err(errno) => return errno,
+    };
+    let count = found.len().min(len);
+    let bytes: Vec<u8> = found[..count]
+        .iter()
+        .flat_map(|ip| ip.to_u32().to_be_bytes())
+        .collect();
+    match UserSlice::new(token, addrs as *const u8, bytes.len()).copy_to_user(&bytes) {
+        Some(()) => found.len() as isize,
+        None => EFAULT,
+    }
+}
+
+const NETBENCH_STOP: usize = 0;
+const NETBENCH_TCP_SINK: usize = 1;
+const NETBENCH_TCP_SOURCE: usize = 2;
+const NETBENCH_UDP_SINK: usize = 3;
+const NETBENCH_UDP_SOURCE: usize = 4;
+
+/// Have netbench take what comes to the port of `addr`, or send to `addr`,
+/// for at most `secs` seconds, or stop the run going on; ENOSYS without
+/// the netbench feature.
+#[cfg(feature = "netbench")]
+pub fn sys_netbench(op: usize, addr: *const SockAddrIn, secs: usize) -> isize {
+    use crate::net::{netbench_start, netbench_stop, BenchMode, BenchRun};
+    let mode = match op {
+        NETBENCH_STOP => {
+            netbench_stop();
+            return 0;
+        }
+        NETBENCH_TCP_SINK => BenchMode::TcpSink,
+        NETBENCH_TCP_SOURCE => BenchMode::TcpSource,
+        NETBENCH_UDP_SINK => BenchMode::UdpSink,
+        NETBENCH_UDP_SOURCE => BenchMode::UdpSource,
+        _ => return EINVAL,
+    };
+    if secs == 0 {
+        return EINVAL;
+    }
+    let addr = match read_addr(addr, core::mem::size_of::<SockAddrIn>()) {
+        Ok(addr) => addr,
+        Err(errno) => return errno,
+    };
+    result(netbench_start(BenchRun { mode, addr, secs }).map(|_| 0))
+}
+
+#[cfg(not(feature = "netbench"))]
+pub fn sys_netbench(op: usize, _addr: *const SockAddrIn, _secs: usize) -> isize {
+    use super::ENOSYS;
+    match op {
+        NETBENCH_STOP | NETBENCH_TCP_SINK | NETBENCH_TCP_SOURCE | NETBENCH_UDP_SINK
+        | NETBENCH_UDP_SOURCE => ENOSYS,
+        _ => EINVAL,
+    }
 }
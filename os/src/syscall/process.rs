@@ -1,15 +1,22 @@
-use crate::fs::{open_file, OpenFlags};
-use crate::mm::{translated_ref, translated_refmut, translated_str};
+use super::fs::install_fd;
+use super::{ECHILD, EFAULT, EINTR, EINVAL, EPERM, ESRCH};
+use crate::fs::{lookup, may_access, wait_ready, Inode, OpenFlags, PidFd, MAY_EXEC};
+use crate::mm::UserPtr;
+use crate::perf::{counted_events, current_counts, PerfCounts};
 use crate::task::{
-    current_process, current_task, current_user_token, exit_current_and_run_next, pid2process,
-    suspend_current_and_run_next, SignalFlags,
+    current_process, current_task, current_user_token, exit_current_and_run_next, group_processes,
+    leave_syscall, pid2process, suspend_current_and_run_next, Capabilities, ProcessControlBlock,
+    RLimit, SigInfo, SIGCHLD,
 };
 use crate::timer::get_time_ms;
 use alloc::string::String;
 use alloc::sync::Arc;
+use alloc::vec;
 use alloc::vec::Vec;
 
 pub fn sys_exit(exit_code: i32) -> ! {
+    // other threads may go on
+    leave_syscall();
     exit_current_and_run_next(exit_code);
     panic!("Unreachable in sys_exit!");
 }
@@ -27,6 +34,99 @@ pub fn sys_getpid() -> isize {
     current_task().unwrap().process.upgrade().unwrap().getpid() as isize
 }
 
+pub fn sys_getuid() -> isize {
+    current_process().inner_exclusive_access().uid as isize
+}
+
+pub fn sys_getgid() -> isize {
+    current_process().inner_exclusive_access().gid as isize
+}
+
+/// Root may become any user, and others only who they are already; the
+/// capabilities go with root.
+pub fn sys_setuid(uid: u32) -> isize {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    if inner.uid != 0 && inner.uid != uid {
+        return -1;
+    }
+    inner.uid = uid;
+    inner.caps = inner.caps.kept_by(uid);
+    0
+}
+
+/// Only root may change its group.
+pub fn sys_setgid(gid: u32) -> isize {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    if inner.uid != 0 && inner.gid != gid {
+        return -1;
+    }
+    inner.gid = gid;
+    0
+}
+
+/// The capabilities of the current process.
+pub fn sys_capget() -> isize {
+    current_process().inner_exclusive_access().caps.bits() as isize
+}
+
+/// Drop the capabilities of the current process but for those of `caps`;
+/// EPERM for any it does not have, which it cannot take back.
+pub fn sys_capset(caps: u32) -> isize {
+    let caps = match Capabilities::from_bits(caps) {
+        Some(caps) => caps,
+        None => return EINVAL,
+    };
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    if !inner.caps.contains(caps) {
+        return EPERM;
+    }
+    inner.caps = caps;
+    0
+}
+
+/// Move the caller or a child of it, `pid` 0 being the caller, into the
+/// process group `pgid`, a new one led by it if `pgid` is 0 or its pid.
+/// Other groups must have a process in them.
+pub fn sys_setpgid(pid: usize, pgid: usize) -> isize {
+    let caller = current_process();
+    let process = if pid == 0 || pid == caller.getpid() {
+        caller
+    } else {
+        match caller
+            .inner_exclusive_access()
+            .children
+            .iter()
+            .find(|child| child.getpid() == pid)
+        {
+            Some(child) => child.clone(),
+            None => return -1,
+        }
+    };
+    let pgid = if pgid == 0 { process.getpid() } else { pgid };
+    if pgid != process.getpid() && group_processes(pgid).is_empty() {
+        return -1;
+    }
+    process.inner_exclusive_access().pgid = pgid;
+    0
+}
+
+/// The process group of `pid`, 0 being the caller.
+pub fn sys_getpgid(pid: usize) -> isize {
+    let process = if pid == 0 {
+        current_process()
+    } else {
+        match pid2process(pid) {
+            Some(process) => process,
+            None => return -1,
+        }
+    };
+    let pgid = process.inner_exclusive_access().pgid;
+    pgid as isize
+}
+
 pub fn sys_fork() -> isize {
     let current_process = current_process();
     let new_process = current_process.fork();
@@ -41,35 +141,180 @@ pub fn sys_fork() -> isize {
     new_pid as isize
 }
 
-pub fn sys_exec(path: *const u8, mut args: *const usize) -> isize {
-    let token = current_user_token();
-    let path = translated_str(token, path);
-    let mut args_vec: Vec<String> = Vec::new();
+/// Nested interpreter lines are followed at most this many times.
+const MAX_SHEBANG_DEPTH: usize = 4;
+/// Longer `#!` lines are cut, as on Linux.
+const SHEBANG_MAX_LEN: usize = 256;
+
+/// Collect a NULL-terminated array of user string pointers.
+fn user_str_array(token: usize, ptr: *const usize) -> Option<Vec<String>> {
+    let mut ptr = UserPtr::new(token, ptr);
+    let mut strings = Vec::new();
     loop {
-        let arg_str_ptr = *translated_ref(token, args);
-        if arg_str_ptr == 0 {
+        let str_ptr = ptr.read()?;
+        if str_ptr == 0 {
             break;
         }
-        args_vec.push(translated_str(token, arg_str_ptr as *const u8));
-        unsafe {
-            args = args.add(1);
+        strings.push(UserPtr::new(token, str_ptr as *const u8).read_str()?);
+        ptr = ptr.add(1);
+    }
+    Some(strings)
+}
+
+/// Parse a `#!interpreter [arg]` line into the interpreter and its optional
+/// argument, which like on Linux is the rest of the line as a single string.
+fn parse_shebang(data: &[u8]) -> Option<(String, Option<String>)> {
+    let line = data.strip_prefix(b"#!")?;
+    let line = &line[..line.iter().position(|c| *c == b'\n').unwrap_or(line.len())];
+    let line = core::str::from_utf8(line).ok()?.trim();
+    let (interp, arg) = match line.find([' ', '\t']) {
+        Some(i) => (&line[..i], Some(line[i..].trim())),
+        None => (line, None),
+    };
+    if interp.is_empty() {
+        return None;
+    }
+    Some((String::from(interp), arg.map(String::from)))
+}
+
+/// Find the image at `path`, following `#!` lines at most
+/// MAX_SHEBANG_DEPTH times, and return it with the final argv.
+fn resolve_exec(
+    mut path: String,
+    mut args_vec: Vec<String>,
+) -> Option<(Arc<dyn Inode>, Vec<String>)> {
+    for _ in 0..=MAX_SHEBANG_DEPTH {
+        // running takes the permission to, though not to read
+        let app_inode = lookup(path.as_str())?;
+        if app_inode.is_dir() || !may_access(&app_inode, MAY_EXEC) {
+            return None;
+        }
+        let mut head = [0u8; SHEBANG_MAX_LEN];
+        let len = app_inode.read_at(0, &mut head);
+        if !head[..len].starts_with(b"#!") {
+            return Some((app_inode, args_vec));
         }
+        let (interp, interp_arg) = parse_shebang(&head[..len])?;
+        // run `interp [interp_arg] path argv[1..]`
+        let mut new_args = vec![interp.clone()];
+        new_args.extend(interp_arg);
+        new_args.push(path);
+        new_args.extend(args_vec.into_iter().skip(1));
+        args_vec = new_args;
+        path = interp;
     }
-    if let Some(app_inode) = open_file(path.as_str(), OpenFlags::RDONLY) {
-        let all_data = app_inode.read_all();
-        let process = current_process();
-        let argc = args_vec.len();
-        process.exec(all_data.as_slice(), args_vec);
-        // return argc because cx.x[10] will be covered with it later
-        argc as isize
+    None
+}
+
+/// Copy the path, the argv and the envp (null means empty) of exec and
+/// spawn.
+fn user_exec_args(
+    token: usize,
+    path: *const u8,
+    args: *const usize,
+    envp: *const usize,
+) -> Option<(String, Vec<String>, Vec<String>)> {
+    let path = UserPtr::new(token, path).read_str()?;
+    let args_vec = user_str_array(token, args)?;
+    let envs_vec = if envp.is_null() {
+        Vec::new()
     } else {
-        -1
+        user_str_array(token, envp)?
+    };
+    Some((path, args_vec, envs_vec))
+}
+
+/// `envp` may be null, which is the same as an empty environment.
+pub fn sys_exec(path: *const u8, args: *const usize, envp: *const usize) -> isize {
+    let token = current_user_token();
+    let (path, args_vec, envs_vec) = match user_exec_args(token, path, args, envp) {
+        Some(exec_args) => exec_args,
+        None => return EFAULT,
+    };
+    let (app_inode, args_vec) = match resolve_exec(path, args_vec) {
+        Some(image) => image,
+        None => return -1,
+    };
+    let process = current_process();
+    let argc = args_vec.len();
+    if !process.exec(&app_inode, args_vec, envs_vec) {
+        return -1;
     }
+    // return argc because cx.x[10] will be covered with it later
+    argc as isize
+}
+
+/// Create a child running the image at `path` without duplicating the
+/// address space of the caller as fork + exec would. The child inherits
+/// the fd table and the resource limits. Return the pid of the child.
+pub fn sys_spawn(path: *const u8, args: *const usize, envp: *const usize) -> isize {
+    let token = current_user_token();
+    let (path, args_vec, envs_vec) = match user_exec_args(token, path, args, envp) {
+        Some(exec_args) => exec_args,
+        None => return EFAULT,
+    };
+    let (app_inode, args_vec) = match resolve_exec(path, args_vec) {
+        Some(image) => image,
+        None => return -1,
+    };
+    let process = current_process();
+    match process.spawn(&app_inode, args_vec, envs_vec) {
+        Some(child) => child.getpid() as isize,
+        None => -1,
+    }
+}
+
+/// Remove a child of `children` which `matches` and has exited, and give
+/// its pid and exit code.
+fn reap_zombie(
+    children: &mut Vec<Arc<ProcessControlBlock>>,
+    matches: impl Fn(&Arc<ProcessControlBlock>) -> bool,
+) -> Option<(usize, i32)> {
+    let idx = children.iter().position(|p| {
+        // ++++ temporarily access child PCB exclusively
+        p.inner_exclusive_access().is_zombie && matches(p)
+        // ++++ release child PCB
+    })?;
+    let child = children.remove(idx);
+    // confirm that child will be deallocated after being removed from children list
+    assert_eq!(Arc::strong_count(&child), 1);
+    // ++++ temporarily access child PCB exclusively
+    let exit_code = child.inner_exclusive_access().exit_code;
+    // ++++ release child PCB
+    Some((child.getpid(), exit_code))
 }
 
 /// If there is not a child process whose pid is same as given, return -1.
 /// Else if there is a child process but it is still running, return -2.
-pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
+/// waitpid also tells of children stopped, once for each stop.
+const WUNTRACED: usize = 2;
+
+/// What waitpid gives for a child stopped by `signo`, as WIFSTOPPED and
+/// WSTOPSIG take it apart.
+fn stop_status(signo: usize) -> i32 {
+    (signo as i32) << 8 | 0x7f
+}
+
+/// A child of `children` matching `pid` which stopped since waitpid last
+/// told of it, with its status.
+fn take_stop(children: &[Arc<ProcessControlBlock>], pid: isize) -> Option<(usize, i32)> {
+    children.iter().find_map(|child| {
+        if pid != -1 && pid as usize != child.getpid() {
+            return None;
+        }
+        let mut child_inner = child.inner_exclusive_access();
+        let signals = &mut child_inner.signals;
+        match signals.stopped {
+            Some(signo) if !signals.stop_reported => {
+                signals.stop_reported = true;
+                Some((child.getpid(), stop_status(signo)))
+            }
+            _ => None,
+        }
+    })
+}
+
+pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32, options: usize) -> isize {
     let process = current_process();
     // find a child process
 
@@ -82,36 +327,206 @@ pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
         return -1;
         // ---- release current PCB
     }
-    let pair = inner.children.iter().enumerate().find(|(_, p)| {
-        // ++++ temporarily access child PCB exclusively
-        p.inner_exclusive_access().is_zombie && (pid == -1 || pid as usize == p.getpid())
-        // ++++ release child PCB
-    });
-    if let Some((idx, _)) = pair {
-        let child = inner.children.remove(idx);
-        // confirm that child will be deallocated after being removed from children list
-        assert_eq!(Arc::strong_count(&child), 1);
-        let found_pid = child.getpid();
-        // ++++ temporarily access child PCB exclusively
-        let exit_code = child.inner_exclusive_access().exit_code;
-        // ++++ release child PCB
-        *translated_refmut(inner.memory_set.token(), exit_code_ptr) = exit_code;
-        found_pid as isize
+    let reaped = reap_zombie(&mut inner.children, |p| pid == -1 || pid as usize == p.getpid());
+    let (found_pid, exit_code) = if let Some(reaped) = reaped {
+        reaped
+    } else if options & WUNTRACED != 0 {
+        match take_stop(&inner.children, pid) {
+            Some(stop) => stop,
+            None => return -2,
+        }
     } else {
-        -2
+        return -2;
+    };
+    let token = inner.memory_set.token();
+    // writing to user memory may resolve a page fault of this process
+    drop(inner);
+    let exit_code_ptr = UserPtr::new(token, exit_code_ptr);
+    if !exit_code_ptr.is_null() && exit_code_ptr.write(exit_code).is_none() {
+        return EFAULT;
     }
+    found_pid as isize
     // ---- release current PCB automatically
 }
 
-pub fn sys_kill(pid: usize, signal: u32) -> isize {
-    if let Some(process) = pid2process(pid) {
-        if let Some(flag) = SignalFlags::from_bits(signal) {
-            process.inner_exclusive_access().signals |= flag;
-            0
-        } else {
-            -1
+/// waitid: which children to wait for.
+const P_ALL: usize = 0;
+const P_PID: usize = 1;
+const P_PIDFD: usize = 3;
+/// waitid: return at once if no child has exited.
+const WNOHANG: usize = 1;
+/// waitid: wait for children to exit, the only thing it waits for.
+const WEXITED: usize = 4;
+/// `code` of the SigInfo of a child which exited.
+const CLD_EXITED: i32 = 1;
+
+/// The pid of the process a pidfd refers to, and the process unless it was
+/// reaped.
+type PidFdTarget = (usize, Option<Arc<ProcessControlBlock>>);
+
+/// What the pidfd `fd` refers to.
+pub(super) fn pidfd_process(fd: usize) -> Result<PidFdTarget, isize> {
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    let file = match inner.fd_table.get(fd) {
+        Some(Some(file)) => file.clone(),
+        _ => return Err(-1),
+    };
+    drop(inner);
+    match file.as_any().and_then(|file| file.downcast_ref::<PidFd>()) {
+        Some(pidfd) => Ok((pidfd.pid(), pidfd.process())),
+        None => Err(EINVAL),
+    }
+}
+
+/// A pidfd of the process `pid`, always with FD_CLOEXEC, and O_NONBLOCK
+/// if `flags` say.
+pub fn sys_pidfd_open(pid: usize, flags: u32) -> isize {
+    let flags = match OpenFlags::from_bits(flags) {
+        Some(flags) if OpenFlags::NONBLOCK.contains(flags) => flags,
+        _ => return EINVAL,
+    };
+    match pid2process(pid) {
+        Some(process) => install_fd(PidFd::new(&process, flags), flags | OpenFlags::CLOEXEC),
+        None => ESRCH,
+    }
+}
+
+/// Wait for a child to exit: any of them for P_ALL, the one of pid `id`
+/// for P_PID, or the one the pidfd `id` refers to for P_PIDFD, which is
+/// then sure to be the child it was opened for. The child is reaped and
+/// told of in `infop`, unless it is null; with WNOHANG, if none exited
+/// yet, `infop` is zeroed instead. ECHILD if there is no such child.
+pub fn sys_waitid(idtype: usize, id: usize, infop: *mut SigInfo, options: usize) -> isize {
+    if options & WEXITED == 0 || options & !(WNOHANG | WEXITED) != 0 {
+        return EINVAL;
+    }
+    let pid = match idtype {
+        P_ALL => None,
+        P_PID => Some(id),
+        P_PIDFD => match pidfd_process(id) {
+            Ok((pid, Some(_))) => Some(pid),
+            // reaped already
+            Ok((_, None)) => return ECHILD,
+            Err(err) => return err,
+        },
+        _ => return EINVAL,
+    };
+    let process = current_process();
+    let matches = |child: &Arc<ProcessControlBlock>| pid.map_or(true, |pid| child.getpid() == pid);
+    if !process.inner_exclusive_access().children.iter().any(matches) {
+        return ECHILD;
+    }
+    let mut reap = || reap_zombie(&mut process.inner_exclusive_access().children, matches);
+    let reaped = if options & WNOHANG != 0 {
+        reap()
+    } else {
+        match wait_ready(None, reap) {
+            Some(reaped) => Some(reaped),
+            None => return EINTR,
         }
+    };
+    let info = match reaped {
+        Some((pid, exit_code)) => SigInfo {
+            status: exit_code,
+            ..SigInfo::new(SIGCHLD, CLD_EXITED, pid, 0)
+        },
+        None => SigInfo::default(),
+    };
+    let infop = UserPtr::new(current_user_token(), infop);
+    if !infop.is_null() && infop.write(info).is_none() {
+        return EFAULT;
+    }
+    0
+}
+
+/// pid 0 means the current process. Other processes must act for the same
+/// user, unless the caller is root. Either `new_limit` or `old_limit` may be
+/// null.
+pub fn sys_prlimit(
+    pid: usize,
+    resource: usize,
+    new_limit: *const RLimit,
+    old_limit: *mut RLimit,
+) -> isize {
+    let process = if pid == 0 {
+        current_process()
+    } else if let Some(process) = pid2process(pid) {
+        process
+    } else {
+        return ESRCH;
+    };
+    let uid = current_process().inner_exclusive_access().uid;
+    if uid != 0 && uid != process.inner_exclusive_access().uid {
+        return EPERM;
+    }
+    let token = current_user_token();
+    // reading user memory may resolve a page fault of this process
+    let new_limit = UserPtr::new(token, new_limit);
+    let new_limit = if new_limit.is_null() {
+        None
     } else {
-        -1
+        match new_limit.read() {
+            Some(limit) => Some(limit),
+            None => return EFAULT,
+        }
+    };
+    let mut inner = process.inner_exclusive_access();
+    let old = match inner.rlimits.get(resource) {
+        Some(limit) => limit,
+        None => return EINVAL,
+    };
+    if let Some(limit) = new_limit {
+        if limit.rlim_cur > limit.rlim_max {
+            return EINVAL;
+        }
+        if !inner.rlimits.set(resource, limit) {
+            return EPERM;
+        }
+    }
+    drop(inner);
+    let old_limit = UserPtr::new(token, old_limit);
+    if !old_limit.is_null() && old_limit.write(old).is_none() {
+        return EFAULT;
+    }
+    0
+}
+
+/// The counts of the current thread.
+const PERF_THREAD: usize = 0;
+/// The counts of the current process, its threads which exited among them.
+const PERF_PROCESS: usize = 1;
+
+/// Write what the performance counters counted while the current thread,
+/// or process, ran to `counts`; which hardware events are counted, as a
+/// mask of their indices.
+pub fn sys_perf_read(target: usize, counts: *mut PerfCounts) -> isize {
+    let task = current_task().unwrap();
+    let mut total = current_counts(&task);
+    match target {
+        PERF_THREAD => {}
+        PERF_PROCESS => {
+            let process = task.process.upgrade().unwrap();
+            let inner = process.inner_exclusive_access();
+            total.add(&inner.perf_exited);
+            let others = inner
+                .tasks
+                .iter()
+                .flatten()
+                .filter(|other| !Arc::ptr_eq(other, &task));
+            for other in others {
+                other.inner.exclusive_session(|other| {
+                    if other.exit_code.is_none() {
+                        total.add(&other.perf);
+                    }
+                });
+            }
+        }
+        _ => return EINVAL,
+    }
+    let counts = UserPtr::new(current_user_token(), counts);
+    if counts.write(total).is_none() {
+        return EFAULT;
     }
+    counted_events() as isize
 }
@@ -0,0 +1,302 @@
+use super::fs::{deadline_after, read_timeout, TimeSpec};
+use super::process::pidfd_process;
+use super::{EAGAIN, EFAULT, EINTR, EINVAL, EPERM, ESRCH};
+use crate::fs::wait_ready;
+use crate::mm::UserPtr;
+use crate::task::{
+    capable, current_process, current_task, current_user_token, group_processes, pid2process,
+    pids, send_fault_signal, send_signal, send_thread_signal, set_action, signal_pending,
+    sigreturn, suspend_current_and_run_next, take_signal, valid_signal, Capabilities, SigAction,
+    SigInfo, SignalFlags, IDLE_PID, SEGV_MAPERR, SIGSEGV, SI_TKILL, SI_USER,
+};
+use alloc::vec::Vec;
+
+const SIG_BLOCK: usize = 0;
+const SIG_UNBLOCK: usize = 1;
+const SIG_SETMASK: usize = 2;
+
+/// Bytes of a sigset_t, 64 signals.
+const SIGSET_SIZE: usize = 8;
+
+/// Send `signo` to the process `pid`, or if it is not positive: to the
+/// group of the sender for 0, to all processes but the sender for -1, and
+/// to the group -`pid` otherwise. Signal 0 sends nothing, but still finds
+/// whether there is anyone to send it to. The sender must have CAP_KILL or
+/// act for the same user as a receiver; the init process takes no signals.
+pub fn sys_kill(pid: isize, signo: usize) -> isize {
+    if signo != 0 && !valid_signal(signo) {
+        return EINVAL;
+    }
+    let sender = current_process();
+    let sender_inner = sender.inner_exclusive_access();
+    let (uid, pgid) = (sender_inner.uid, sender_inner.pgid);
+    drop(sender_inner);
+    let kill_any = capable(Capabilities::KILL);
+    let receivers: Vec<_> = match pid {
+        0 => group_processes(pgid),
+        -1 => pids()
+            .into_iter()
+            .filter(|pid| *pid != sender.getpid())
+            .filter_map(pid2process)
+            .collect(),
+        pid if pid < 0 => group_processes(pid.unsigned_abs()),
+        pid => pid2process(pid as usize).into_iter().collect(),
+    };
+    let mut sent = false;
+    for process in receivers {
+        if process.getpid() == IDLE_PID
+            || (!kill_any && uid != process.inner_exclusive_access().uid)
+        {
+            continue;
+        }
+        if signo != 0 {
+            send_signal(&process, SigInfo::new(signo, SI_USER, sender.getpid(), uid));
+        }
+        sent = true;
+    }
+    if sent {
+        0
+    } else {
+        -1
+    }
+}
+
+/// Send `signo` to the process the pidfd `pidfd` refers to, as kill does
+/// to a single process, or ESRCH once it has exited, so that the signal
+/// never reaches another process which took its pid. `info` must be null
+/// and `flags` 0.
+pub fn sys_pidfd_send_signal(
+    pidfd: usize,
+    signo: usize,
+    info: *const SigInfo,
+    flags: usize,
+) -> isize {
+    if (signo != 0 && !valid_signal(signo)) || !info.is_null() || flags != 0 {
+        return EINVAL;
+    }
+    let process = match pidfd_process(pidfd) {
+        Ok((_, Some(process))) => process,
+        Ok((_, None)) => return ESRCH,
+        Err(err) => return err,
+    };
+    let (is_zombie, receiver_uid) = {
+        let inner = process.inner_exclusive_access();
+        (inner.is_zombie, inner.uid)
+    };
+    if is_zombie {
+        return ESRCH;
+    }
+    let sender = current_process();
+    let uid = sender.inner_exclusive_access().uid;
+    if process.getpid() == IDLE_PID || (!capable(Capabilities::KILL) && uid != receiver_uid) {
+        return EPERM;
+    }
+    if signo != 0 {
+        send_signal(&process, SigInfo::new(signo, SI_USER, sender.getpid(), uid));
+    }
+    0
+}
+
+/// Send `signo` to the thread `tid` of the process `tgid` alone.
+pub fn sys_tgkill(tgid: usize, tid: usize, signo: usize) -> isize {
+    if signo != 0 && !valid_signal(signo) {
+        return EINVAL;
+    }
+    let process = match pid2process(tgid) {
+        Some(process) if tgid != IDLE_PID => process,
+        _ => return -1,
+    };
+    let sender = current_process();
+    let uid = sender.inner_exclusive_access().uid;
+    let kill_any = capable(Capabilities::KILL);
+    let inner = process.inner_exclusive_access();
+    if !kill_any && uid != inner.uid {
+        return -1;
+    }
+    let task = match inner.tasks.get(tid) {
+        Some(Some(task)) => task.clone(),
+        _ => return -1,
+    };
+    drop(inner);
+    if signo != 0 {
+        send_thread_signal(
+            &process,
+            &task,
+            SigInfo::new(signo, SI_TKILL, sender.getpid(), uid),
+        );
+    }
+    0
+}
+
+/// Either `act` or `oldact` may be null. The actions of SIGKILL and
+/// SIGSTOP stay the default.
+pub fn sys_rt_sigaction(
+    signo: usize,
+    act: *const SigAction,
+    oldact: *mut SigAction,
+    sigsetsize: usize,
+) -> isize {
+    if !valid_signal(signo) || sigsetsize != SIGSET_SIZE {
+        return EINVAL;
+    }
+    let token = current_user_token();
+    let act = UserPtr::new(token, act);
+    let act = if act.is_null() {
+        None
+    } else {
+        match act.read() {
+            Some(act) => Some(act),
+            None => return EFAULT,
+        }
+    };
+    let process = current_process();
+    let old = match act {
+        Some(_) if SignalFlags::unblockable().contains(SignalFlags::from_signum(signo)) => {
+            return EINVAL
+        }
+        Some(act) => set_action(&process, signo, act),
+        None => process.inner_exclusive_access().signals.actions[signo - 1],
+    };
+    let oldact = UserPtr::new(token, oldact);
+    if !oldact.is_null() && oldact.write(old).is_none() {
+        return EFAULT;
+    }
+    0
+}
+
+/// Change the mask of the current thread as `how` says; either `set` or
+/// `oldset` may be null. SIGKILL and SIGSTOP are never blocked.
+pub fn sys_rt_sigprocmask(
+    how: usize,
+    set: *const u64,
+    oldset: *mut u64,
+    sigsetsize: usize,
+) -> isize {
+    if sigsetsize != SIGSET_SIZE {
+        return EINVAL;
+    }
+    let token = current_user_token();
+    let set = UserPtr::new(token, set);
+    let set = if set.is_null() {
+        None
+    } else {
+        match set.read() {
+            Some(set) => Some(SignalFlags::from_bits_truncate(set)),
+            None => return EFAULT,
+        }
+    };
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    let old = task_inner.signals.mask;
+    if let Some(set) = set {
+        let mask = match how {
+            SIG_BLOCK => old | set,
+            SIG_UNBLOCK => old - set,
+            SIG_SETMASK => set,
+            _ => return EINVAL,
+        };
+        task_inner.signals.mask = mask - SignalFlags::unblockable();
+    }
+    drop(task_inner);
+    let oldset = UserPtr::new(token, oldset);
+    if !oldset.is_null() && oldset.write(old.bits()).is_none() {
+        return EFAULT;
+    }
+    0
+}
+
+/// The signals sent to the current thread or its process which it blocks.
+pub fn sys_rt_sigpending(set: *mut u64, sigsetsize: usize) -> isize {
+    if sigsetsize != SIGSET_SIZE {
+        return EINVAL;
+    }
+    let task = current_task().unwrap();
+    let process = current_process();
+    let task_inner = task.inner_exclusive_access();
+    let pending = (task_inner.signals.pending.set()
+        | process.inner_exclusive_access().signals.pending.set())
+        & task_inner.signals.mask;
+    drop(task_inner);
+    if UserPtr::new(current_user_token(), set)
+        .write(pending.bits())
+        .is_none()
+    {
+        return EFAULT;
+    }
+    0
+}
+
+/// Have the current thread wait with the mask at `mask`, which is back to
+/// what it was once the syscall returns and its signals are dealt with;
+/// false if it cannot be read.
+pub(super) fn wait_with_mask(mask: *const u64) -> bool {
+    let mask = match UserPtr::new(current_user_token(), mask).read() {
+        Some(mask) => SignalFlags::from_bits_truncate(mask),
+        None => return false,
+    };
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    task_inner.signals.saved_mask = Some(task_inner.signals.mask);
+    task_inner.signals.mask = mask - SignalFlags::unblockable();
+    true
+}
+
+/// Wait with the mask `mask` for a signal to deliver.
+pub fn sys_rt_sigsuspend(mask: *const u64, sigsetsize: usize) -> isize {
+    if sigsetsize != SIGSET_SIZE {
+        return EINVAL;
+    }
+    if !wait_with_mask(mask) {
+        return EFAULT;
+    }
+    while !signal_pending() {
+        suspend_current_and_run_next();
+    }
+    EINTR
+}
+
+/// Take one of the signals of `set` sent to the current thread or its
+/// process, waiting for one until `timeout` is over, or for ever if it is
+/// null, and return its number. Those of `set` ought to be blocked, or they
+/// may be delivered first; the wait ends with EINTR if another is.
+pub fn sys_rt_sigtimedwait(
+    set: *const u64,
+    info: *mut SigInfo,
+    timeout: *const TimeSpec,
+    sigsetsize: usize,
+) -> isize {
+    if sigsetsize != SIGSET_SIZE {
+        return EINVAL;
+    }
+    let token = current_user_token();
+    let set = match UserPtr::new(token, set).read() {
+        Some(set) => SignalFlags::from_bits_truncate(set),
+        None => return EFAULT,
+    };
+    let timeout_ns = match read_timeout(token, timeout) {
+        Ok(timeout_ns) => timeout_ns,
+        Err(err) => return err,
+    };
+    let taken = match wait_ready(deadline_after(timeout_ns), || take_signal(set)) {
+        Some(taken) => taken,
+        None if signal_pending() => return EINTR,
+        None => return EAGAIN,
+    };
+    let info = UserPtr::new(token, info);
+    if !info.is_null() && info.write(taken).is_none() {
+        return EFAULT;
+    }
+    taken.signo as isize
+}
+
+/// Called by the sigreturn trampoline once a handler returns. A frame
+/// which cannot be read is a fault of the process.
+pub fn sys_rt_sigreturn() -> isize {
+    match sigreturn() {
+        Some(a0) => a0 as isize,
+        None => {
+            send_fault_signal(SigInfo::fault(SIGSEGV, SEGV_MAPERR, 0));
+            EFAULT
+        }
+    }
+}
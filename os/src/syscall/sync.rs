@@ -1,23 +1,35 @@
-use crate::sync::{Condvar, Mutex, MutexBlocking, MutexSpin, Semaphore};
-use crate::task::{block_current_and_run_next, current_process, current_task};
-use crate::timer::{add_timer, get_time_ms};
+use super::fs::{deadline_after, read_timeout, TimeSpec};
+use super::{EAGAIN, EFAULT, EINTR, EINVAL, ENOENT, ENOSYS, ETIMEDOUT};
+use crate::config::PAGE_SIZE;
+use crate::mm::{SharedMemory, UserPtr, VirtAddr};
+use crate::sync::{
+    futex_wake, named_condvar, named_mutex, unlink_named, Condvar, FutexKey, FutexWaiter, Mutex,
+    MutexBlocking, MutexSpin, Semaphore,
+};
+use crate::task::{current_process, current_user_token, signal_pending};
+use alloc::string::String;
 use alloc::sync::Arc;
 
-pub fn sys_sleep(ms: usize) -> isize {
-    let expire_ms = get_time_ms() + ms;
-    let task = current_task().unwrap();
-    add_timer(expire_ms, task);
-    block_current_and_run_next();
-    0
-}
+/// The longest name of a named mutex or condition variable.
+const SYNC_NAME_MAX: usize = 255;
+
+const FUTEX_WAIT: usize = 0;
+const FUTEX_WAKE: usize = 1;
+/// The futex is of this process alone, even in a shared mapping.
+const FUTEX_PRIVATE_FLAG: usize = 128;
 
 pub fn sys_mutex_create(blocking: bool) -> isize {
-    let process = current_process();
-    let mutex: Option<Arc<dyn Mutex>> = if !blocking {
-        Some(Arc::new(MutexSpin::new()))
+    let mutex: Arc<dyn Mutex> = if !blocking {
+        Arc::new(MutexSpin::new())
     } else {
-        Some(Arc::new(MutexBlocking::new()))
+        Arc::new(MutexBlocking::new())
     };
+    add_mutex(mutex)
+}
+
+/// Give `mutex` the first free id of the current process.
+fn add_mutex(mutex: Arc<dyn Mutex>) -> isize {
+    let process = current_process();
     let mut process_inner = process.inner_exclusive_access();
     if let Some(id) = process_inner
         .mutex_list
@@ -26,14 +38,35 @@ pub fn sys_mutex_create(blocking: bool) -> isize {
         .find(|(_, item)| item.is_none())
         .map(|(id, _)| id)
     {
-        process_inner.mutex_list[id] = mutex;
+        process_inner.mutex_list[id] = Some(mutex);
         id as isize
     } else {
-        process_inner.mutex_list.push(mutex);
+        process_inner.mutex_list.push(Some(mutex));
         process_inner.mutex_list.len() as isize - 1
     }
 }
 
+/// The name at `name` of a named mutex or condition variable.
+fn read_sync_name(name: *const u8) -> Result<String, isize> {
+    let name = UserPtr::new(current_user_token(), name)
+        .read_str()
+        .ok_or(EFAULT)?;
+    if name.is_empty() || name.len() > SYNC_NAME_MAX {
+        return Err(EINVAL);
+    }
+    Ok(name)
+}
+
+/// Give the current process an id of the mutex of `name`, which any
+/// process opening the name shares, making one, blocking or not, if there
+/// is none.
+pub fn sys_mutex_open(name: *const u8, blocking: bool) -> isize {
+    match read_sync_name(name) {
+        Ok(name) => add_mutex(named_mutex(&name, blocking)),
+        Err(err) => err,
+    }
+}
+
 pub fn sys_mutex_lock(mutex_id: usize) -> isize {
     let process = current_process();
     let process_inner = process.inner_exclusive_access();
@@ -94,6 +127,11 @@ pub fn sys_semaphore_down(sem_id: usize) -> isize {
 }
 
 pub fn sys_condvar_create() -> isize {
+    add_condvar(Arc::new(Condvar::new()))
+}
+
+/// Give `condvar` the first free id of the current process.
+fn add_condvar(condvar: Arc<Condvar>) -> isize {
     let process = current_process();
     let mut process_inner = process.inner_exclusive_access();
     let id = if let Some(id) = process_inner
@@ -103,17 +141,34 @@ pub fn sys_condvar_create() -> isize {
         .find(|(_, item)| item.is_none())
         .map(|(id, _)| id)
     {
-        process_inner.condvar_list[id] = Some(Arc::new(Condvar::new()));
+        process_inner.condvar_list[id] = Some(condvar);
         id
     } else {
-        process_inner
-            .condvar_list
-            .push(Some(Arc::new(Condvar::new())));
+        process_inner.condvar_list.push(Some(condvar));
         process_inner.condvar_list.len() - 1
     };
     id as isize
 }
 
+/// Give the current process an id of the condition variable of `name`,
+/// which any process opening the name shares, making one if there is none.
+pub fn sys_condvar_open(name: *const u8) -> isize {
+    match read_sync_name(name) {
+        Ok(name) => add_condvar(named_condvar(&name)),
+        Err(err) => err,
+    }
+}
+
+/// Take `name` away from its mutex and condition variable, which those
+/// who opened them keep.
+pub fn sys_sync_unlink(name: *const u8) -> isize {
+    match read_sync_name(name) {
+        Ok(name) if unlink_named(&name) => 0,
+        Ok(_) => ENOENT,
+        Err(err) => err,
+    }
+}
+
 pub fn sys_condvar_signal(condvar_id: usize) -> isize {
     let process = current_process();
     let process_inner = process.inner_exclusive_access();
@@ -132,3 +187,66 @@ pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
     condvar.wait_with_mutex(mutex);
     0
 }
+
+
+/// The futex of the word at `uaddr` of the current process, with the
+/// object of the shared mapping it is in, if it is known by that, to be
+/// kept while the key is used.
+fn futex_key(uaddr: usize, private: bool) -> (FutexKey, Option<Arc<SharedMemory>>) {
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    let va = VirtAddr::from(uaddr);
+    match inner.memory_set.shared_page(va.floor()) {
+        Some((object, page)) if !private => {
+            let key = FutexKey::Shared {
+                object: Arc::as_ptr(&object) as usize,
+                offset: page * PAGE_SIZE + va.page_offset(),
+            };
+            (key, Some(object))
+        }
+        _ => {
+            let key = FutexKey::Private {
+                token: inner.memory_set.token(),
+                va: uaddr,
+            };
+            (key, None)
+        }
+    }
+}
+
+/// FUTEX_WAIT: wait while the word at `uaddr` is `val`, for up to the
+/// time at `timeout` unless it is null, and return 0 once woken; EAGAIN
+/// if the word is not `val`. FUTEX_WAKE: wake up to `val` of those
+/// waiting on `uaddr` and return how many were. With FUTEX_PRIVATE_FLAG
+/// a futex in a shared mapping is of this process alone.
+pub fn sys_futex(uaddr: *const u32, op: usize, val: usize, timeout: *const TimeSpec) -> isize {
+    if uaddr as usize % 4 != 0 {
+        return EINVAL;
+    }
+    let token = current_user_token();
+    let (key, _object) = futex_key(uaddr as usize, op & FUTEX_PRIVATE_FLAG != 0);
+    match op & !FUTEX_PRIVATE_FLAG {
+        FUTEX_WAIT => {
+            let deadline = match read_timeout(token, timeout) {
+                Ok(timeout_ns) => deadline_after(timeout_ns),
+                Err(err) => return err,
+            };
+            // waiting before the check, a wake after it is not missed
+            let waiter = FutexWaiter::new(key);
+            match UserPtr::new(token, uaddr).read() {
+                Some(value) if value == val as u32 => {}
+                Some(_) => return EAGAIN,
+                None => return EFAULT,
+            }
+            if waiter.wait(deadline) {
+                0
+            } else if signal_pending() {
+                EINTR
+            } else {
+                ETIMEDOUT
+            }
+        }
+        FUTEX_WAKE => futex_wake(key, val) as isize,
+        _ => ENOSYS,
+    }
+}
@@ -0,0 +1,273 @@
+use super::{EBUSY, EFAULT, EINTR, EINVAL, EIO, EOPNOTSUPP, EPERM};
+use crate::fs::wait_ready;
+use crate::logging::{
+    log_clear, log_read_all, log_take, log_unread, set_console_level, set_console_on,
+    set_log_filter, LOG_BUF_SIZE,
+};
+use crate::mm::UserSlice;
+use crate::objects::{obj_stats, ObjStat};
+use crate::power::{power_down, suspend, PowerAction};
+use crate::replay::{
+    record_start, replay_events, replay_start, replay_stop, valid_event, ReplayEvent,
+    REPLAY_MAX_EVENTS,
+};
+use crate::sbi::{has, Extension};
+use crate::task::{capable, current_user_token, Capabilities};
+use crate::trace::{trace_start, trace_stop};
+use core::mem::size_of;
+use log::LevelFilter;
+
+const SYSLOG_ACTION_READ: usize = 2;
+const SYSLOG_ACTION_READ_ALL: usize = 3;
+const SYSLOG_ACTION_READ_CLEAR: usize = 4;
+const SYSLOG_ACTION_CLEAR: usize = 5;
+const SYSLOG_ACTION_CONSOLE_OFF: usize = 6;
+const SYSLOG_ACTION_CONSOLE_ON: usize = 7;
+const SYSLOG_ACTION_CONSOLE_LEVEL: usize = 8;
+const SYSLOG_ACTION_SIZE_UNREAD: usize = 9;
+const SYSLOG_ACTION_SIZE_BUFFER: usize = 10;
+/// The magic numbers sys_reboot wants, lest it be called by mistake.
+const REBOOT_MAGIC1: usize = 0xfee1_dead;
+const REBOOT_MAGIC2: usize = 0x2812_1969;
+const REBOOT_CMD_RESTART: usize = 0x0123_4567;
+const REBOOT_CMD_HALT: usize = 0xcdef_0123;
+const REBOOT_CMD_POWER_OFF: usize = 0x4321_fedc;
+
+/// This kernel's own: change the filter of the log to the `len` bytes at
+/// `buf`, as the LOG variable tells it at compile time.
+const SYSLOG_ACTION_SET_FILTER: usize = 100;
+
+const TRACE_STOP: usize = 0;
+const TRACE_START: usize = 1;
+
+const REPLAY_STOP: usize = 0;
+const REPLAY_RECORD: usize = 1;
+const REPLAY_READ: usize = 2;
+const REPLAY_LOAD: usize = 3;
+
+/// The console level of the syslog levels 1 to 8, records of a priority
+/// below it being written, error being 3 and debug 7.
+fn console_level(level: usize) -> Option<LevelFilter> {
+    match level {
+        1..=3 => Some(LevelFilter::Off),
+        4 => Some(LevelFilter::Error),
+        5 | 6 => Some(LevelFilter::Warn),
+        7 => Some(LevelFilter::Info),
+        8 => Some(LevelFilter::Trace),
+        _ => None,
+    }
+}
+
+fn copy_out(buf: *mut u8, bytes: &[u8]) -> isize {
+    match UserSlice::new(current_user_token(), buf, bytes.len()).copy_to_user(bytes) {
+        Some(()) => bytes.len() as isize,
+        None => EFAULT,
+    }
+}
+
+/// The kernel log, as syslog(2) has it: READ waits for lines not read yet
+/// and takes up to `len` bytes of them, READ_ALL gives the last `len`
+/// bytes held, read or not. Anyone may READ_ALL and ask the SIZE_BUFFER;
+/// the rest takes CAP_RAW_IO.
+pub fn sys_syslog(action: usize, buf: *mut u8, len: usize) -> isize {
+    let raw_io = capable(Capabilities::RAW_IO);
+    if !raw_io && action != SYSLOG_ACTION_READ_ALL && action != SYSLOG_ACTION_SIZE_BUFFER {
+        return EPERM;
+    }
+    match action {
+        SYSLOG_ACTION_READ => {
+            if len == 0 {
+                return 0;
+            }
+            if wait_ready(None, || (log_unread() > 0).then_some(())).is_none() {
+                return EINTR;
+            }
+            copy_out(buf, &log_take(len))
+        }
+        SYSLOG_ACTION_READ_ALL | SYSLOG_ACTION_READ_CLEAR => {
+            let copied = copy_out(buf, &log_read_all(len));
+            if action == SYSLOG_ACTION_READ_CLEAR && copied >= 0 {
+                log_clear();
+            }
+            copied
+        }
+        SYSLOG_ACTION_CLEAR => {
+            log_clear();
+            0
+        }
+        SYSLOG_ACTION_CONSOLE_OFF | SYSLOG_ACTION_CONSOLE_ON => {
+            set_console_on(action == SYSLOG_ACTION_CONSOLE_ON);
+            0
+        }
+        SYSLOG_ACTION_CONSOLE_LEVEL => match console_level(len) {
+            Some(level) => {
+                set_console_level(level);
+                0
+            }
+            None => EINVAL,
+        },
+        SYSLOG_ACTION_SIZE_UNREAD => log_unread() as isize,
+        SYSLOG_ACTION_SIZE_BUFFER => LOG_BUF_SIZE as isize,
+        SYSLOG_ACTION_SET_FILTER => {
+            let mut spec = alloc::vec![0u8; len.min(LOG_BUF_SIZE)];
+            let token = current_user_token();
+            if UserSlice::new(token, buf, spec.len())
+                .copy_from_user(&mut spec)
+                .is_none()
+            {
+                return EFAULT;
+            }
+            match core::str::from_utf8(&spec) {
+                Ok(spec) if set_log_filter(spec) => 0,
+                _ => EINVAL,
+            }
+        }
+        _ => EINVAL,
+    }
+}
+
+/// Start tracing, throwing the records away, or stop it, for /proc/trace
+/// to give what was recorded; how many records there are. It takes
+/// CAP_RAW_IO.
+pub fn sys_trace_ctl(op: usize) -> isize {
+    if !capable(Capabilities::RAW_IO) {
+        return EPERM;
+    }
+    match op {
+        TRACE_START => {
+            trace_start();
+            0
+        }
+        TRACE_STOP => trace_stop() as isize,
+        _ => EINVAL,
+    }
+}
+
+/// Fault every `interval`th event at the point `point` from now on, or
+/// delay it by `delay_ms`, which only disks take, or none if `interval` is
+/// 0; how many faults were injected there since it was last armed. It
+/// takes CAP_RAW_IO.
+#[cfg(feature = "fault_inject")]
+pub fn sys_fault_inject(point: usize, interval: usize, delay_ms: usize) -> isize {
+    use crate::fault::{arm, FaultPoint};
+    if !capable(Capabilities::RAW_IO) {
+        return EPERM;
+    }
+    match FaultPoint::from_usize(point) {
+        Some(FaultPoint::Block) => arm(FaultPoint::Block, interval, delay_ms) as isize,
+        Some(point) if delay_ms == 0 => arm(point, interval, 0) as isize,
+        _ => EINVAL,
+    }
+}
+
+/// Record the scheduling from the next syscall of the current process on,
+/// or replay the `len` bytes of events at `buf` from then on, or stop;
+/// READ copies the events recorded from the `from`th on into the `len`
+/// bytes at `buf`. STOP gives how many events were recorded or replayed,
+/// or EIO if the replay diverged; READ how many it copied. It takes
+/// CAP_RAW_IO.
+pub fn sys_replay(op: usize, buf: *mut u8, len: usize, from: usize) -> isize {
+    if !capable(Capabilities::RAW_IO) {
+        return EPERM;
+    }
+    let size = core::mem::size_of::<ReplayEvent>();
+    match op {
+        REPLAY_STOP => replay_stop().map_or(EIO, |events| events as isize),
+        REPLAY_RECORD => {
+            if record_start() {
+                0
+            } else {
+                EBUSY
+            }
+        }
+        REPLAY_READ => {
+            let events = replay_events(from, len / size);
+            let bytes = unsafe {
+                core::slice::from_raw_parts(events.as_ptr() as *const u8, events.len() * size)
+            };
+            match copy_out(buf, bytes) {
+                copied if copied < 0 => copied,
+                _ => events.len() as isize,
+            }
+        }
+        REPLAY_LOAD => {
+            if len == 0 || len % size != 0 || len / size > REPLAY_MAX_EVENTS {
+                return EINVAL;
+            }
+            let mut bytes = alloc::vec![0u8; len];
+            let token = current_user_token();
+            if UserSlice::new(token, buf, len)
+                .copy_from_user(&mut bytes)
+                .is_none()
+            {
+                return EFAULT;
+            }
+            let events: alloc::vec::Vec<ReplayEvent> = bytes
+                .chunks_exact(size)
+                .map(|chunk| unsafe { (chunk.as_ptr() as *const ReplayEvent).read_unaligned() })
+                .collect();
+            if !events.iter().all(valid_event) {
+                return EINVAL;
+            }
+            if replay_start(events) {
+                0
+            } else {
+                EBUSY
+            }
+        }
+        _ => EINVAL,
+    }
+}
+
+/// Write the counts of the first `count` kinds of kernel objects to
+/// `stats`, each with how many more are alive than at the mark, then set
+/// the mark to now if `mark` is 1; how many kinds it wrote.
+pub fn sys_objstat(stats: *mut ObjStat, count: usize, mark: usize) -> isize {
+    if mark > 1 {
+        return EINVAL;
+    }
+    let all = obj_stats(mark == 1);
+    let count = count.min(all.len());
+    let bytes = unsafe {
+        core::slice::from_raw_parts(all.as_ptr() as *const u8, count * size_of::<ObjStat>())
+    };
+    match copy_out(stats as *mut u8, bytes) {
+        copied if copied < 0 => copied,
+        _ => count as isize,
+    }
+}
+
+/// Sync the file systems to the disks, stop the other harts, then restart,
+/// halt or power off as `cmd` says, with CAP_RAW_IO; EINVAL for magic numbers
+/// other than those of Linux or another `cmd`, EOPNOTSUPP for a restart
+/// the firmware has no SRST for, the system being left up then.
+pub fn sys_reboot(magic1: usize, magic2: usize, cmd: usize) -> isize {
+    if !capable(Capabilities::RAW_IO) {
+        return EPERM;
+    }
+    if magic1 != REBOOT_MAGIC1 || magic2 != REBOOT_MAGIC2 {
+        return EINVAL;
+    }
+    let action = match cmd {
+        REBOOT_CMD_RESTART => PowerAction::Restart,
+        REBOOT_CMD_HALT => PowerAction::Halt,
+        REBOOT_CMD_POWER_OFF => PowerAction::PowerOff,
+        _ => return EINVAL,
+    };
+    if action == PowerAction::Restart && !has(Extension::Srst) {
+        return EOPNOTSUPP;
+    }
+    power_down(action, false);
+    // the firmware refused to restart, with the disks synced all the same
+    EIO
+}
+
+/// Suspend the system to idle until a wakeup device interrupts, or for
+/// `timeout_ms` at most unless it is 0; the milliseconds it slept. It
+/// takes CAP_RAW_IO.
+pub fn sys_suspend(timeout_ms: usize) -> isize {
+    if !capable(Capabilities::RAW_IO) {
+        return EPERM;
+    }
+    suspend(timeout_ms) as isize
+}
@@ -8,6 +8,9 @@ use alloc::sync::Arc;
 pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
     let task = current_task().unwrap();
     let process = task.process.upgrade().unwrap();
+    if !process.inner_exclusive_access().check_stack_limit() {
+        return -1;
+    }
     // create a new thread
     let new_task = Arc::new(TaskControlBlock::new(
         Arc::clone(&process),
@@ -18,6 +21,8 @@ pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
             .ustack_base,
         true,
     ));
+    // blocking what its creator does
+    new_task.inner_exclusive_access().signals.mask = task.inner_exclusive_access().signals.mask;
     // add new task to scheduler
     add_task(Arc::clone(&new_task));
     let new_task_inner = new_task.inner_exclusive_access();
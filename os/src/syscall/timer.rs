@@ -0,0 +1,319 @@
+use super::fs::{ITimerSpec, TimeSpec, CLOCK_MONOTONIC, CLOCK_REALTIME};
+use super::{EAGAIN, EFAULT, EINTR, EINVAL};
+use crate::mm::UserPtr;
+use crate::task::{
+    current_process, current_user_token, set_timer, signal_pending, sleep_current_until,
+    valid_signal, TimerId, SIGALRM,
+};
+use crate::timer::{ktime_now, timebase_freq, NSEC_PER_SEC};
+
+/// The timer of setitimer counting real time. ITIMER_VIRTUAL and
+/// ITIMER_PROF, counting the time of the process, are not kept, as the
+/// kernel does not account for it.
+const ITIMER_REAL: usize = 0;
+
+/// The time of timer_settime and clock_nanosleep is when to expire, not
+/// how long from now.
+const TIMER_ABSTIME: u32 = 1;
+
+const NSEC_PER_USEC: u64 = 1000;
+
+/// `struct timeval`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct TimeVal {
+    tv_sec: i64,
+    tv_usec: i64,
+}
+
+impl TimeVal {
+    /// In ns; None if not a valid time.
+    fn to_ns(self) -> Option<u64> {
+        if self.tv_sec < 0 || !(0..1_000_000).contains(&self.tv_usec) {
+            return None;
+        }
+        (self.tv_sec as u64)
+            .checked_mul(NSEC_PER_SEC)?
+            .checked_add(self.tv_usec as u64 * NSEC_PER_USEC)
+    }
+
+    /// `ns` rounded up to a µs, not to be short of the time left.
+    fn from_ns(ns: u64) -> Self {
+        let us = (ns + NSEC_PER_USEC - 1) / NSEC_PER_USEC;
+        Self {
+            tv_sec: (us / 1_000_000) as i64,
+            tv_usec: (us % 1_000_000) as i64,
+        }
+    }
+}
+
+/// `struct itimerval`: the time between expirations and to the next.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct ITimerVal {
+    it_interval: TimeVal,
+    it_value: TimeVal,
+}
+
+impl ITimerVal {
+    fn from_ns((value, interval): (u64, u64)) -> Self {
+        Self {
+            it_interval: TimeVal::from_ns(interval),
+            it_value: TimeVal::from_ns(value),
+        }
+    }
+}
+
+/// Send a signal at expiration.
+const SIGEV_SIGNAL: i32 = 0;
+/// Send nothing: the timer is only read with timer_gettime.
+const SIGEV_NONE: i32 = 1;
+
+/// `struct sigevent`: how a timer of timer_create tells of expirations.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SigEvent {
+    value: usize,
+    signo: i32,
+    notify: i32,
+    _pad: [i32; 12],
+}
+
+/// Arm ITIMER_REAL as `new` says, or disarm it with a zero value, and put
+/// what it was set to before in `old` if not null.
+pub fn sys_setitimer(which: usize, new: *const ITimerVal, old: *mut ITimerVal) -> isize {
+    if which != ITIMER_REAL {
+        return EINVAL;
+    }
+    let token = current_user_token();
+    let new = match UserPtr::new(token, new).read() {
+        Some(new) => new,
+        None => return EFAULT,
+    };
+    let (value, interval) = match (new.it_value.to_ns(), new.it_interval.to_ns()) {
+        (Some(value), Some(interval)) => (value, interval),
+        _ => return EINVAL,
+    };
+    let deadline = (value != 0).then(|| ktime_now().saturating_add(value));
+    let was = set_timer(&current_process(), TimerId::Real, deadline, interval).unwrap();
+    let old = UserPtr::new(token, old as *const ITimerVal);
+    if !old.is_null() && old.write(ITimerVal::from_ns(was)).is_none() {
+        return EFAULT;
+    }
+    0
+}
+
+/// Put the time to the next expiration of ITIMER_REAL, 0 if it is
+/// disarmed, and the interval in `curr`.
+pub fn sys_getitimer(which: usize, curr: *mut ITimerVal) -> isize {
+    if which != ITIMER_REAL {
+        return EINVAL;
+    }
+    let now = current_process()
+        .inner_exclusive_access()
+        .timers
+        .get(TimerId::Real)
+        .unwrap()
+        .get();
+    match UserPtr::new(current_user_token(), curr as *const ITimerVal)
+        .write(ITimerVal::from_ns(now))
+    {
+        Some(()) => 0,
+        None => EFAULT,
+    }
+}
+
+/// A new timer, disarmed, telling of its expirations as `sevp` says, or
+/// with SIGALRM if it is null; its id is put in `timerid`.
+pub fn sys_timer_create(clockid: usize, sevp: *const SigEvent, timerid: *mut i32) -> isize {
+    if clockid != CLOCK_REALTIME && clockid != CLOCK_MONOTONIC {
+        return EINVAL;
+    }
+    let token = current_user_token();
+    let sevp = UserPtr::new(token, sevp);
+    let (signo, value) = if sevp.is_null() {
+        (Some(SIGALRM), None)
+    } else {
+        match sevp.read() {
+            Some(event) if event.notify == SIGEV_NONE => (None, Some(event.value)),
+            Some(event) if event.notify == SIGEV_SIGNAL && valid_signal(event.signo as usize) => {
+                (Some(event.signo as usize), Some(event.value))
+            }
+            Some(_) => return EINVAL,
+            None => return EFAULT,
+        }
+    };
+    let process = current_process();
+    let id = match process.inner_exclusive_access().timers.create(signo, value) {
+        Some(id) => id,
+        None => return EAGAIN,
+    };
+    if UserPtr::new(token, timerid).write(id as i32).is_none() {
+        process.inner_exclusive_access().timers.delete(id);
+        return EFAULT;
+    }
+    0
+}
+
+/// Arm the timer `timerid` as `new` says, or disarm it with a zero value,
+/// and put what it was set to before in `old` if not null.
+pub fn sys_timer_settime(
+    timerid: usize,
+    flags: u32,
+    new: *const ITimerSpec,
+    old: *mut ITimerSpec,
+) -> isize {
+    if flags & !TIMER_ABSTIME != 0 {
+        return EINVAL;
+    }
+    let token = current_user_token();
+    let new = match UserPtr::new(token, new).read() {
+        Some(new) => new,
+        None => return EFAULT,
+    };
+    let (value, interval) = match new.to_ns() {
+        Some(times) => times,
+        None => return EINVAL,
+    };
+    let deadline = match value {
+        0 => None,
+        _ if flags & TIMER_ABSTIME != 0 => Some(value),
+        _ => Some(ktime_now().saturating_add(value)),
+    };
+    let was = match set_timer(
+        &current_process(),
+        TimerId::Posix(timerid),
+        deadline,
+        interval,
+    ) {
+        Some(was) => was,
+        None => return EINVAL,
+    };
+    let old = UserPtr::new(token, old as *const ITimerSpec);
+    if !old.is_null() && old.write(ITimerSpec::from_ns(was)).is_none() {
+        return EFAULT;
+    }
+    0
+}
+
+/// Put the time to the next expiration of the timer `timerid`, 0 if it is
+/// disarmed, and the interval in `curr`.
+pub fn sys_timer_gettime(timerid: usize, curr: *mut ITimerSpec) -> isize {
+    let now = match current_process()
+        .inner_exclusive_access()
+        .timers
+        .get(TimerId::Posix(timerid))
+    {
+        Some(timer) => timer.get(),
+        None => return EINVAL,
+    };
+    match UserPtr::new(current_user_token(), curr as *const ITimerSpec)
+        .write(ITimerSpec::from_ns(now))
+    {
+        Some(()) => 0,
+        None => EFAULT,
+    }
+}
+
+/// The expirations of the timer `timerid` which found its last signal
+/// still queued.
+pub fn sys_timer_getoverrun(timerid: usize) -> isize {
+    match current_process()
+        .inner_exclusive_access()
+        .timers
+        .get(TimerId::Posix(timerid))
+    {
+        Some(timer) => timer.overrun() as isize,
+        None => EINVAL,
+    }
+}
+
+pub fn sys_timer_delete(timerid: usize) -> isize {
+    if current_process()
+        .inner_exclusive_access()
+        .timers
+        .delete(timerid)
+    {
+        0
+    } else {
+        EINVAL
+    }
+}
+
+/// The time of `clockid`, the ns since boot counted by the timebase for
+/// CLOCK_MONOTONIC and CLOCK_REALTIME alike, there being no clock of the
+/// wall to start the latter at.
+pub fn sys_clock_gettime(clockid: usize, tp: *mut TimeSpec) -> isize {
+    if clockid != CLOCK_REALTIME && clockid != CLOCK_MONOTONIC {
+        return EINVAL;
+    }
+    match UserPtr::new(current_user_token(), tp).write(TimeSpec::from_ns(ktime_now())) {
+        Some(()) => 0,
+        None => EFAULT,
+    }
+}
+
+/// The resolution of `clockid`, a tick of the timebase, put in `res`
+/// unless it is null.
+pub fn sys_clock_getres(clockid: usize, res: *mut TimeSpec) -> isize {
+    if clockid != CLOCK_REALTIME && clockid != CLOCK_MONOTONIC {
+        return EINVAL;
+    }
+    let res = UserPtr::new(current_user_token(), res);
+    if res.is_null() {
+        return 0;
+    }
+    let tick_ns = (NSEC_PER_SEC + timebase_freq() as u64 - 1) / timebase_freq() as u64;
+    match res.write(TimeSpec::from_ns(tick_ns)) {
+        Some(()) => 0,
+        None => EFAULT,
+    }
+}
+
+/// Sleep for the time at `req`, as clock_nanosleep of CLOCK_MONOTONIC.
+pub fn sys_nanosleep(req: *const TimeSpec, rem: *mut TimeSpec) -> isize {
+    sys_clock_nanosleep(CLOCK_MONOTONIC, 0, req, rem)
+}
+
+/// Sleep for the time at `req`, or until it with TIMER_ABSTIME, blocked
+/// until the timer wakes the thread. A signal ends the sleep with EINTR,
+/// the time left put in `rem` unless it is null or the time absolute.
+pub fn sys_clock_nanosleep(
+    clockid: usize,
+    flags: u32,
+    req: *const TimeSpec,
+    rem: *mut TimeSpec,
+) -> isize {
+    if clockid != CLOCK_REALTIME && clockid != CLOCK_MONOTONIC {
+        return EINVAL;
+    }
+    if flags & !TIMER_ABSTIME != 0 {
+        return EINVAL;
+    }
+    let token = current_user_token();
+    let req_ns = match UserPtr::new(token, req).read().map(TimeSpec::to_ns) {
+        Some(Some(ns)) => ns,
+        Some(None) => return EINVAL,
+        None => return EFAULT,
+    };
+    let deadline = if flags & TIMER_ABSTIME != 0 {
+        req_ns
+    } else {
+        ktime_now().saturating_add(req_ns)
+    };
+    loop {
+        let now = ktime_now();
+        if now >= deadline {
+            return 0;
+        }
+        if signal_pending() {
+            let rem = UserPtr::new(token, rem);
+            let left = TimeSpec::from_ns(deadline - now);
+            if flags & TIMER_ABSTIME == 0 && !rem.is_null() && rem.write(left).is_none() {
+                return EFAULT;
+            }
+            return EINTR;
+        }
+        sleep_current_until(deadline);
+    }
+}
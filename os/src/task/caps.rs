@@ -0,0 +1,44 @@
+//! Capabilities: what a process may do which its uid alone does not let
+//! it, each checked by the syscalls doing it rather than whether the caller
+//! is root.
+//!
+//! initproc starts with them all and a child has those of its parent. A
+//! process may drop some with capset but never take them back. Only root
+//! keeps them across exec and setuid, a process of another user having
+//! none, so a root process which dropped some runs what it execs without
+//! them.
+
+use super::current_process;
+use bitflags::*;
+
+bitflags! {
+    pub struct Capabilities: u32 {
+        /// the kernel log, tracing, replay, fault injection, reboot and
+        /// suspend
+        const RAW_IO = 1 << 0;
+        /// raw sockets and configuring the interfaces
+        const NET = 1 << 1;
+        /// signals to the processes of other users
+        const KILL = 1 << 2;
+        /// mount and umount
+        const MOUNT = 1 << 3;
+    }
+}
+
+impl Capabilities {
+    /// Those kept across exec or setuid by a process of `uid`.
+    pub fn kept_by(self, uid: u32) -> Self {
+        match uid {
+            0 => self,
+            _ => Self::empty(),
+        }
+    }
+}
+
+/// Whether the current process has `cap`.
+pub fn capable(cap: Capabilities) -> bool {
+    current_process()
+        .inner_exclusive_access()
+        .caps
+        .contains(cap)
+}
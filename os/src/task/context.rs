@@ -22,4 +22,11 @@ impl TaskContext {
             s: [0; 12],
         }
     }
+    pub fn goto_kernel_thread(entry: fn() -> !, kstack_ptr: usize) -> Self {
+        Self {
+            ra: entry as usize,
+            sp: kstack_ptr,
+            s: [0; 12],
+        }
+    }
 }
@@ -0,0 +1,284 @@
+//! Core dumps: the ELF core file a process killed by a signal such as
+//! SIGSEGV leaves in its working directory as `core.<pid>`, for gdb on the
+//! host to load along with the program.
+//!
+//! It has a PT_NOTE segment with the NT_PRPSINFO of the process and the
+//! NT_PRSTATUS of each thread, the one which took the signal first, then a
+//! PT_LOAD segment for each user area, pages never allocated being zeros.
+//! Threads are numbered pid + tid, to be told apart. Nothing is dumped
+//! while RLIMIT_CORE is 0, its default, and the file is cut at the limit.
+
+use super::rlimit::RLIMIT_CORE;
+use super::signal::SigInfo;
+use super::{current_process, current_task};
+use crate::config::PAGE_SIZE;
+use crate::fs::{open_file, OpenFlags};
+use crate::mm::{CoreArea, MapPermission, UserSlice, VirtAddr, VirtPageNum};
+use alloc::format;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+const ET_CORE: u16 = 4;
+const EM_RISCV: u16 = 243;
+/// EF_RISCV_RVC | EF_RISCV_FLOAT_ABI_DOUBLE, as for the user programs
+const EF_RISCV: u32 = 0x5;
+const PT_LOAD: u32 = 1;
+const PT_NOTE: u32 = 4;
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+const NT_PRSTATUS: u32 = 1;
+const NT_PRPSINFO: u32 = 3;
+
+const EHDR_SIZE: usize = 64;
+const PHDR_SIZE: usize = 56;
+
+/// The registers of a thread as `struct user_regs_struct`: pc in place of
+/// x0.
+type Regs = [usize; 32];
+
+/// What is kept of a thread, read before anything may sleep.
+struct Thread {
+    id: usize,
+    regs: Regs,
+    pending: u64,
+    mask: u64,
+}
+
+/// Little-endian fields of the file, appended in turn.
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u16(&mut self, value: u16) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+    fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+    fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+    /// Pad with zeros up to a multiple of `align`.
+    fn align(&mut self, align: usize) {
+        let len = (self.0.len() + align - 1) / align * align;
+        self.0.resize(len, 0);
+    }
+    /// A note named "CORE".
+    fn note(&mut self, kind: u32, desc: &[u8]) {
+        self.u32(5);
+        self.u32(desc.len() as u32);
+        self.u32(kind);
+        self.bytes(b"CORE\0");
+        self.align(4);
+        self.bytes(desc);
+        self.align(4);
+    }
+}
+
+/// `struct elf_prstatus` of riscv64.
+fn prstatus(info: &SigInfo, thread: &Thread, pid: usize, ppid: usize, pgid: usize) -> Vec<u8> {
+    let mut w = Writer(Vec::new());
+    // pr_info, pr_cursig
+    w.u32(info.signo as u32);
+    w.u32(info.code as u32);
+    w.u32(info.errno as u32);
+    w.u16(info.signo as u16);
+    w.align(8);
+    w.u64(thread.pending);
+    w.u64(thread.mask);
+    w.u32(thread.id as u32);
+    w.u32(ppid as u32);
+    w.u32(pgid as u32);
+    // pr_sid
+    w.u32(pid as u32);
+    // pr_utime, pr_stime, pr_cutime, pr_cstime
+    w.bytes(&[0; 64]);
+    for reg in thread.regs {
+        w.u64(reg as u64);
+    }
+    // pr_fpvalid
+    w.u32(0);
+    w.align(8);
+    w.0
+}
+
+/// `struct elf_prpsinfo` of riscv64.
+fn prpsinfo(pid: usize, ppid: usize, pgid: usize, uid: u32, gid: u32, name: &str) -> Vec<u8> {
+    let mut w = Writer(Vec::new());
+    // pr_state, pr_sname, pr_zomb, pr_nice
+    w.bytes(&[0, b'R', 0, 0]);
+    w.align(8);
+    // pr_flag
+    w.u64(0);
+    w.u32(uid);
+    w.u32(gid);
+    w.u32(pid as u32);
+    w.u32(ppid as u32);
+    w.u32(pgid as u32);
+    w.u32(pid as u32);
+    let mut fname = [0u8; 16];
+    let len = name.len().min(15);
+    fname[..len].copy_from_slice(&name.as_bytes()[..len]);
+    w.bytes(&fname);
+    let mut psargs = [0u8; 80];
+    let len = name.len().min(79);
+    psargs[..len].copy_from_slice(&name.as_bytes()[..len]);
+    w.bytes(&psargs);
+    w.0
+}
+
+/// The file header and program headers, for a PT_NOTE of `notes_len`
+/// bytes followed by `areas` from `data_offset` on.
+fn headers(notes_len: usize, areas: &[CoreArea], data_offset: usize) -> Vec<u8> {
+    let mut w = Writer(Vec::new());
+    w.bytes(&[0x7f, b'E', b'L', b'F', 2, 1, 1, 0]);
+    w.bytes(&[0; 8]);
+    w.u16(ET_CORE);
+    w.u16(EM_RISCV);
+    w.u32(1);
+    // e_entry, e_phoff, e_shoff
+    w.u64(0);
+    w.u64(EHDR_SIZE as u64);
+    w.u64(0);
+    w.u32(EF_RISCV);
+    w.u16(EHDR_SIZE as u16);
+    w.u16(PHDR_SIZE as u16);
+    w.u16(areas.len() as u16 + 1);
+    // e_shentsize, e_shnum, e_shstrndx
+    w.u16(64);
+    w.u16(0);
+    w.u16(0);
+    let notes_offset = EHDR_SIZE + PHDR_SIZE * (areas.len() + 1);
+    w.u32(PT_NOTE);
+    w.u32(0);
+    w.u64(notes_offset as u64);
+    w.u64(0);
+    w.u64(0);
+    w.u64(notes_len as u64);
+    w.u64(0);
+    w.u64(4);
+    let mut offset = data_offset;
+    for area in areas {
+        let start: VirtAddr = area.start.into();
+        let len = (area.end.0 - area.start.0) * PAGE_SIZE;
+        let mut flags = PF_R;
+        if area.perm.contains(MapPermission::W) {
+            flags |= PF_W;
+        }
+        if area.perm.contains(MapPermission::X) {
+            flags |= PF_X;
+        }
+        w.u32(PT_LOAD);
+        w.u32(flags);
+        w.u64(offset as u64);
+        w.u64(start.0 as u64);
+        w.u64(0);
+        w.u64(len as u64);
+        w.u64(len as u64);
+        w.u64(PAGE_SIZE as u64);
+        offset += len;
+    }
+    w.0
+}
+
+/// Write the core of the current process, which `info` kills; false if
+/// nothing was, RLIMIT_CORE being 0 or the file not to be made.
+pub fn dump_core(info: &SigInfo) -> bool {
+    let task = current_task().unwrap();
+    let process = current_process();
+    let pid = process.getpid();
+    let inner = process.inner_exclusive_access();
+    let limit = inner.rlimits.cur(RLIMIT_CORE);
+    if limit == 0 {
+        return false;
+    }
+    let ppid = inner
+        .parent
+        .as_ref()
+        .and_then(|parent| parent.upgrade())
+        .map_or(0, |parent| parent.getpid());
+    let (pgid, uid, gid, name) = (inner.pgid, inner.uid, inner.gid, inner.name.clone());
+    let process_pending = inner.signals.pending.set().bits();
+    let areas = inner.memory_set.core_areas();
+    let mut threads = Vec::new();
+    for thread in inner.tasks.iter().flatten() {
+        let thread_inner = thread.inner_exclusive_access();
+        let tid = match &thread_inner.res {
+            Some(res) => res.tid,
+            None => continue,
+        };
+        let cx = thread_inner.get_trap_cx();
+        let mut regs = cx.x;
+        regs[0] = cx.sepc;
+        let dumped = Thread {
+            id: pid + tid,
+            regs,
+            pending: thread_inner.signals.pending.set().bits() | process_pending,
+            mask: thread_inner.signals.mask.bits(),
+        };
+        // the one which took the signal first, for gdb to show
+        if Arc::ptr_eq(thread, &task) {
+            threads.insert(0, dumped);
+        } else {
+            threads.push(dumped);
+        }
+    }
+    let token = inner.get_user_token();
+    drop(inner);
+    drop(task);
+
+    let mut notes = Writer(Vec::new());
+    notes.note(
+        NT_PRPSINFO,
+        &prpsinfo(pid, ppid, pgid, uid, gid, name.as_str()),
+    );
+    for thread in threads.iter() {
+        notes.note(NT_PRSTATUS, &prstatus(info, thread, pid, ppid, pgid));
+    }
+    let notes_offset = EHDR_SIZE + PHDR_SIZE * (areas.len() + 1);
+    let data_offset = (notes_offset + notes.0.len() + PAGE_SIZE - 1) / PAGE_SIZE * PAGE_SIZE;
+    let mut head = headers(notes.0.len(), &areas, data_offset);
+    head.extend_from_slice(&notes.0);
+    head.resize(data_offset, 0);
+
+    let file = match open_file(
+        format!("core.{}", pid).as_str(),
+        OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::TRUNC,
+    )
+    .and_then(|file| file.inode())
+    {
+        Some(inode) if !inode.is_dir() => inode,
+        _ => return false,
+    };
+    let write = |offset: usize, bytes: &[u8]| {
+        if offset < limit {
+            let len = bytes.len().min(limit - offset);
+            file.write_at(offset, &bytes[..len]);
+        }
+    };
+    write(0, &head);
+    let mut offset = data_offset;
+    let mut page = vec![0u8; PAGE_SIZE];
+    for area in areas.iter() {
+        for vpn in (area.start.0..area.end.0).map(VirtPageNum) {
+            if offset >= limit {
+                break;
+            }
+            page.fill(0);
+            if area.pages.contains(&vpn) {
+                let va: VirtAddr = vpn.into();
+                // pages which cannot be read any more stay zeros
+                let _ =
+                    UserSlice::new(token, va.0 as *const u8, PAGE_SIZE).copy_from_user(&mut page);
+            }
+            write(offset, &page);
+            offset += PAGE_SIZE;
+        }
+    }
+    file.sync();
+    true
+}
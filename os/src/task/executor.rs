@@ -0,0 +1,194 @@
+//! kworker, the kernel thread polling futures: work which takes too long
+//! for an interrupt handler, like what a timer does once due, runs in it
+//! with interrupts on and the hart preemptible as for any task.
+//!
+//! A future spawned is polled at once, then again each time its waker is
+//! woken, until it is ready. A thread which needs what a future gives, like
+//! a read waiting for the disk, parks in `block_on` while kworker runs it.
+
+use super::manager::spawn_kernel_thread;
+use super::{block_current_task, current_task, preempt_point, schedule, wakeup_task, TaskContext};
+use super::TaskControlBlock;
+use crate::sync::UPIntrFreeCell;
+use crate::timer::{add_timer_waker, ktime_now};
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll, Waker};
+use lazy_static::*;
+use riscv::register::sstatus;
+
+pub type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// A future spawned, taken out while polled.
+struct AsyncTask {
+    future: UPIntrFreeCell<Option<BoxFuture>>,
+    /// in the queue, not to be put there twice
+    queued: AtomicBool,
+}
+
+impl Wake for AsyncTask {
+    fn wake(self: Arc<Self>) {
+        if !self.queued.swap(true, Ordering::AcqRel) {
+            enqueue(self);
+        }
+    }
+}
+
+struct Executor {
+    task: Option<Arc<TaskControlBlock>>,
+    sleeping: bool,
+    /// the futures to poll, the first woken first
+    queue: VecDeque<Arc<AsyncTask>>,
+}
+
+lazy_static! {
+    static ref EXECUTOR: UPIntrFreeCell<Executor> = unsafe {
+        UPIntrFreeCell::new(Executor {
+            task: None,
+            sleeping: false,
+            queue: VecDeque::new(),
+        })
+    };
+}
+
+pub fn start_executor() {
+    EXECUTOR.exclusive_access().task = Some(spawn_kernel_thread("kworker", kworker));
+}
+
+/// Have kworker poll `future` until it is ready; may be called with
+/// interrupts off, like from a timer.
+pub fn spawn(future: BoxFuture) {
+    enqueue(Arc::new(AsyncTask {
+        future: unsafe { UPIntrFreeCell::new(Some(future)) },
+        queued: AtomicBool::new(true),
+    }));
+}
+
+/// What `block_on` waits for.
+struct Completion<T> {
+    value: Option<T>,
+    /// the thread parked until the value is there
+    waiter: Option<Arc<TaskControlBlock>>,
+}
+
+/// Have kworker run `future`, the current thread parked until it is ready,
+/// and return what it gives. Not for kworker itself, which would wait for
+/// itself.
+pub fn block_on<T: Send + 'static>(future: impl Future<Output = T> + Send + 'static) -> T {
+    let completion = Arc::new(unsafe {
+        UPIntrFreeCell::new(Completion {
+            value: None,
+            waiter: None,
+        })
+    });
+    let done = Arc::clone(&completion);
+    spawn(Box::pin(async move {
+        let value = future.await;
+        let mut done = done.exclusive_access();
+        done.value = Some(value);
+        if let Some(waiter) = done.waiter.take() {
+            wakeup_task(waiter);
+        }
+    }));
+    loop {
+        // with interrupts off, the future is not done before we park
+        let next = completion.exclusive_session(|completion| match completion.value.take() {
+            Some(value) => Ok(value),
+            None => {
+                completion.waiter = Some(current_task().unwrap());
+                Err(block_current_task())
+            }
+        });
+        match next {
+            Ok(value) => return value,
+            Err(task_cx_ptr) => schedule(task_cx_ptr),
+        }
+    }
+}
+
+fn enqueue(task: Arc<AsyncTask>) {
+    let mut executor = EXECUTOR.exclusive_access();
+    executor.queue.push_back(task);
+    if executor.sleeping {
+        executor.sleeping = false;
+        wakeup_task(Arc::clone(executor.task.as_ref().unwrap()));
+    }
+}
+
+/// Poll `task` once, leaving it be if it is ready.
+fn poll(task: Arc<AsyncTask>) {
+    task.queued.store(false, Ordering::Release);
+    // out of its cell, which would keep interrupts off meanwhile
+    let mut future = match task.future.exclusive_access().take() {
+        Some(future) => future,
+        None => return,
+    };
+    let waker = Waker::from(Arc::clone(&task));
+    let mut cx = Context::from_waker(&waker);
+    if future.as_mut().poll(&mut cx).is_pending() {
+        *task.future.exclusive_access() = Some(future);
+    }
+}
+
+/// What kworker does next.
+enum Next {
+    Poll(Arc<AsyncTask>),
+    Sleep(*mut TaskContext),
+}
+
+fn kworker() -> ! {
+    unsafe {
+        sstatus::set_sie();
+    }
+    loop {
+        let next = EXECUTOR.exclusive_session(|executor| match executor.queue.pop_front() {
+            Some(task) => Next::Poll(task),
+            // with interrupts off, nothing is woken before we sleep
+            None => {
+                executor.sleeping = true;
+                Next::Sleep(block_current_task())
+            }
+        });
+        match next {
+            Next::Poll(task) => {
+                poll(task);
+                preempt_point();
+            }
+            Next::Sleep(task_cx_ptr) => schedule(task_cx_ptr),
+        }
+    }
+}
+
+/// A future ready once `expire_ns` has come, woken by a timer.
+pub struct Sleep {
+    expire_ns: u64,
+    armed: bool,
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if ktime_now() >= self.expire_ns {
+            return Poll::Ready(());
+        }
+        if !self.armed {
+            self.armed = true;
+            add_timer_waker(self.expire_ns, cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+/// Sleep `ns` in a future, kworker going on with the others meanwhile.
+pub fn sleep_ns(ns: u64) -> Sleep {
+    Sleep {
+        expire_ns: ktime_now().saturating_add(ns),
+        armed: false,
+    }
+}
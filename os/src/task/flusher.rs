@@ -0,0 +1,26 @@
+//! The flusher, the kernel thread writing dirty pages of the page cache
+//! back every DIRTY_WRITEBACK_MS, so that they reach the disk even if
+//! nobody asks for it.
+
+use super::manager::spawn_kernel_thread;
+use super::{block_current_and_run_next, current_task};
+use crate::config::DIRTY_WRITEBACK_MS;
+use crate::fs::sync;
+use crate::timer::{add_timer, ktime_now, NSEC_PER_MSEC};
+use riscv::register::sstatus;
+
+pub fn start_flusher() {
+    spawn_kernel_thread("flusher", flusher);
+}
+
+fn flusher() -> ! {
+    unsafe {
+        sstatus::set_sie();
+    }
+    loop {
+        let deadline = ktime_now() + DIRTY_WRITEBACK_MS as u64 * NSEC_PER_MSEC;
+        add_timer(deadline, current_task().unwrap());
+        block_current_and_run_next();
+        sync();
+    }
+}
@@ -1,6 +1,8 @@
 use super::ProcessControlBlock;
-use crate::config::{KERNEL_STACK_SIZE, PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT_BASE, USER_STACK_SIZE};
-use crate::mm::{MapPermission, PhysPageNum, VirtAddr, KERNEL_SPACE};
+use crate::config::{
+    KERNEL_STACK_SIZE, PAGE_SIZE, TRAP_CONTEXT_BASE, USER_STACK_SIZE, USER_STACK_SLOT,
+};
+use crate::mm::{vmalloc, MapPermission, PhysPageNum, VirtAddr, VmBuffer};
 use crate::sync::UPIntrFreeCell;
 use alloc::{
     sync::{Arc, Weak},
@@ -42,8 +44,6 @@ impl RecycleAllocator {
 lazy_static! {
     static ref PID_ALLOCATOR: UPIntrFreeCell<RecycleAllocator> =
         unsafe { UPIntrFreeCell::new(RecycleAllocator::new()) };
-    static ref KSTACK_ALLOCATOR: UPIntrFreeCell<RecycleAllocator> =
-        unsafe { UPIntrFreeCell::new(RecycleAllocator::new()) };
 }
 
 pub const IDLE_PID: usize = 0;
@@ -51,7 +51,9 @@ pub const IDLE_PID: usize = 0;
 pub struct PidHandle(pub usize);
 
 pub fn pid_alloc() -> PidHandle {
-    PidHandle(PID_ALLOCATOR.exclusive_access().alloc())
+    let pid = PID_ALLOCATOR.exclusive_access().alloc();
+    crate::replay::pid_allocated(pid);
+    PidHandle(pid)
 }
 
 impl Drop for PidHandle {
@@ -60,35 +62,15 @@ impl Drop for PidHandle {
     }
 }
 
-/// Return (bottom, top) of a kernel stack in kernel space.
-pub fn kernel_stack_position(kstack_id: usize) -> (usize, usize) {
-    let top = TRAMPOLINE - kstack_id * (KERNEL_STACK_SIZE + PAGE_SIZE);
-    let bottom = top - KERNEL_STACK_SIZE;
-    (bottom, top)
-}
-
-pub struct KernelStack(pub usize);
+/// A kernel stack in the vmalloc region, where an overflow runs into the
+/// guard page of the buffer below or into unmapped space.
+pub struct KernelStack(VmBuffer);
 
 pub fn kstack_alloc() -> KernelStack {
-    let kstack_id = KSTACK_ALLOCATOR.exclusive_access().alloc();
-    let (kstack_bottom, kstack_top) = kernel_stack_position(kstack_id);
-    KERNEL_SPACE.exclusive_access().insert_framed_area(
-        kstack_bottom.into(),
-        kstack_top.into(),
-        MapPermission::R | MapPermission::W,
-    );
-    KernelStack(kstack_id)
-}
-
-impl Drop for KernelStack {
-    fn drop(&mut self) {
-        let (kernel_stack_bottom, _) = kernel_stack_position(self.0);
-        let kernel_stack_bottom_va: VirtAddr = kernel_stack_bottom.into();
-        KERNEL_SPACE
-            .exclusive_access()
-            .remove_area_with_start_vpn(kernel_stack_bottom_va.into());
-        KSTACK_ALLOCATOR.exclusive_access().dealloc(self.0);
-    }
+    let stack = KernelStack(vmalloc(KERNEL_STACK_SIZE).expect("no memory left for a kernel stack"));
+    #[cfg(feature = "hardening")]
+    crate::hardening::mark_stack(stack.0.start());
+    stack
 }
 
 impl KernelStack {
@@ -105,8 +87,7 @@ impl KernelStack {
         ptr_mut
     }
     pub fn get_top(&self) -> usize {
-        let (_, kernel_stack_top) = kernel_stack_position(self.0);
-        kernel_stack_top
+        self.0.start() + self.0.len()
     }
 }
 
@@ -121,7 +102,7 @@ fn trap_cx_bottom_from_tid(tid: usize) -> usize {
 }
 
 fn ustack_bottom_from_tid(ustack_base: usize, tid: usize) -> usize {
-    ustack_base + tid * (PAGE_SIZE + USER_STACK_SIZE)
+    ustack_base + (tid + 1) * USER_STACK_SLOT - USER_STACK_SIZE
 }
 
 impl TaskUserRes {
@@ -148,11 +129,9 @@ impl TaskUserRes {
         // alloc user stack
         let ustack_bottom = ustack_bottom_from_tid(self.ustack_base, self.tid);
         let ustack_top = ustack_bottom + USER_STACK_SIZE;
-        process_inner.memory_set.insert_framed_area(
-            ustack_bottom.into(),
-            ustack_top.into(),
-            MapPermission::R | MapPermission::W | MapPermission::U,
-        );
+        process_inner
+            .memory_set
+            .insert_stack_area(ustack_bottom.into(), ustack_top.into());
         // alloc trap_cx
         let trap_cx_bottom = trap_cx_bottom_from_tid(self.tid);
         let trap_cx_top = trap_cx_bottom + PAGE_SIZE;
@@ -167,11 +146,11 @@ impl TaskUserRes {
         // dealloc tid
         let process = self.process.upgrade().unwrap();
         let mut process_inner = process.inner_exclusive_access();
-        // dealloc ustack manually
+        // dealloc ustack manually, whose bottom may have moved as it grew
         let ustack_bottom_va: VirtAddr = ustack_bottom_from_tid(self.ustack_base, self.tid).into();
         process_inner
             .memory_set
-            .remove_area_with_start_vpn(ustack_bottom_va.into());
+            .remove_area_containing(ustack_bottom_va.into());
         // dealloc trap_cx manually
         let trap_cx_bottom_va: VirtAddr = trap_cx_bottom_from_tid(self.tid).into();
         process_inner
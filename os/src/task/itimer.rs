@@ -0,0 +1,196 @@
+//! Interval timers of a process, which send it a signal each time they
+//! expire: the ITIMER_REAL of setitimer, sending SIGALRM, and those made
+//! by timer_create, sending the signal they are made with.
+//!
+//! An armed timer has its next expiration in the kernel's timer heap,
+//! which calls `expire_timer` once it is due; one which finds the timer
+//! set to another time since has gone stale, and does nothing. A timer
+//! whose signal is still queued when it expires again counts an overrun
+//! in it instead of queueing another. Times are in ns from boot.
+
+use super::signal::{send_signal, SigInfo, SIGALRM, SI_KERNEL};
+use super::ProcessControlBlock;
+use crate::timer::{add_timer_event, ktime_now};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+/// timer_create's timers a process may have.
+const MAX_TIMERS: usize = 32;
+
+/// Which timer of a process.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TimerId {
+    /// ITIMER_REAL
+    Real,
+    /// of timer_create
+    Posix(usize),
+}
+
+#[derive(Clone, Copy)]
+pub struct IntervalTimer {
+    /// of the next expiration, if armed
+    deadline: Option<u64>,
+    /// between expirations, 0 for once
+    interval: u64,
+    /// the signal to send, None for SIGEV_NONE
+    signo: Option<usize>,
+    /// sigev_value, handed over with the signal
+    value: usize,
+    /// expirations while the signal was queued
+    overrun: u32,
+}
+
+impl IntervalTimer {
+    fn new(signo: Option<usize>, value: usize) -> Self {
+        Self {
+            deadline: None,
+            interval: 0,
+            signo,
+            value,
+            overrun: 0,
+        }
+    }
+
+    /// The ns left to the next expiration, 0 if disarmed, and the interval.
+    pub fn get(&self) -> (u64, u64) {
+        let left = self
+            .deadline
+            .map_or(0, |deadline| deadline.saturating_sub(ktime_now()).max(1));
+        (left, self.interval)
+    }
+
+    pub fn overrun(&self) -> u32 {
+        self.overrun
+    }
+}
+
+/// The timers of a process.
+pub struct ProcessTimers {
+    real: IntervalTimer,
+    posix: Vec<Option<IntervalTimer>>,
+}
+
+impl ProcessTimers {
+    pub fn new() -> Self {
+        Self {
+            real: IntervalTimer::new(Some(SIGALRM), 0),
+            posix: Vec::new(),
+        }
+    }
+
+    /// ITIMER_REAL goes on through exec, while timer_create's timers are
+    /// deleted.
+    pub fn exec(&mut self) {
+        self.posix.clear();
+    }
+
+    pub fn get(&self, id: TimerId) -> Option<&IntervalTimer> {
+        match id {
+            TimerId::Real => Some(&self.real),
+            TimerId::Posix(id) => self.posix.get(id)?.as_ref(),
+        }
+    }
+
+    fn get_mut(&mut self, id: TimerId) -> Option<&mut IntervalTimer> {
+        match id {
+            TimerId::Real => Some(&mut self.real),
+            TimerId::Posix(id) => self.posix.get_mut(id)?.as_mut(),
+        }
+    }
+
+    /// Add a disarmed timer sending `signo`, or nothing if None, with
+    /// `value`, or its id if None; returns the id, or None if there are too
+    /// many.
+    pub fn create(&mut self, signo: Option<usize>, value: Option<usize>) -> Option<usize> {
+        let id = match self.posix.iter().position(Option::is_none) {
+            Some(id) => id,
+            None if self.posix.len() < MAX_TIMERS => {
+                self.posix.push(None);
+                self.posix.len() - 1
+            }
+            None => return None,
+        };
+        self.posix[id] = Some(IntervalTimer::new(signo, value.unwrap_or(id)));
+        Some(id)
+    }
+
+    /// False if there is no timer `id`.
+    pub fn delete(&mut self, id: usize) -> bool {
+        matches!(self.posix.get_mut(id).map(Option::take), Some(Some(_)))
+    }
+}
+
+/// Arm the timer `id` of `process` to expire at `deadline`, then every
+/// `interval` ns if that is not 0, or disarm it with None; what it was set
+/// to before, as `IntervalTimer::get` says, or None if it has no such
+/// timer.
+pub fn set_timer(
+    process: &Arc<ProcessControlBlock>,
+    id: TimerId,
+    deadline: Option<u64>,
+    interval: u64,
+) -> Option<(u64, u64)> {
+    let mut inner = process.inner_exclusive_access();
+    let timer = inner.timers.get_mut(id)?;
+    let old = timer.get();
+    timer.deadline = deadline;
+    timer.interval = interval;
+    timer.overrun = 0;
+    drop(inner);
+    if let Some(deadline) = deadline {
+        add_timer_event(deadline, Arc::downgrade(process), id);
+    }
+    Some(old)
+}
+
+/// Called by the timer heap at an expiration of the timer `id`, which
+/// sends its signal and is armed again if it has an interval.
+pub fn expire_timer(process: &Arc<ProcessControlBlock>, id: TimerId) {
+    let now = ktime_now();
+    let mut guard = process.inner_exclusive_access();
+    let inner = &mut *guard;
+    if inner.is_zombie {
+        return;
+    }
+    let timer = match inner.timers.get_mut(id) {
+        Some(timer) => timer,
+        None => return,
+    };
+    let deadline = match timer.deadline {
+        Some(deadline) if deadline <= now => deadline,
+        // stale
+        _ => return,
+    };
+    // a late expiration counts those it missed
+    let expired = if timer.interval == 0 {
+        timer.deadline = None;
+        1
+    } else {
+        let expired = (now - deadline) / timer.interval + 1;
+        timer.deadline = Some(deadline + expired * timer.interval);
+        expired
+    };
+    let next = timer.deadline;
+    let info = match (id, timer.signo) {
+        (_, None) => None,
+        (TimerId::Real, Some(signo)) => Some(SigInfo::new(signo, SI_KERNEL, 0, 0)),
+        (TimerId::Posix(id), Some(signo)) => match inner.signals.pending.timer_signal(id) {
+            Some(queued) => {
+                queued.overrun = queued.overrun.saturating_add(expired as i32);
+                timer.overrun = queued.overrun as u32;
+                None
+            }
+            None => {
+                timer.overrun = expired as u32 - 1;
+                Some(SigInfo::timer(signo, id, timer.overrun, timer.value))
+            }
+        },
+    };
+    drop(guard);
+    if let Some(next) = next {
+        add_timer_event(next, Arc::downgrade(process), id);
+    }
+    if let Some(info) = info {
+        send_signal(process, info);
+    }
+}
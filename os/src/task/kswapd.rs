@@ -0,0 +1,87 @@
+//! kswapd, the kernel thread reclaiming pages when frames run low.
+
+use super::manager::{spawn_kernel_thread, PID2PCB};
+use super::{block_current_task, preempt_point, schedule, wakeup_task, TaskControlBlock};
+use crate::config::FREE_FRAMES_HIGH;
+use crate::mm::free_frame_count;
+use crate::sync::UPIntrFreeCell;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use lazy_static::*;
+use riscv::register::sstatus;
+
+/// Pages taken from a process at a time, before moving on to the next.
+const SWAP_CLUSTER: usize = 32;
+
+struct Kswapd {
+    task: Option<Arc<TaskControlBlock>>,
+    sleeping: bool,
+}
+
+lazy_static! {
+    static ref KSWAPD: UPIntrFreeCell<Kswapd> = unsafe {
+        UPIntrFreeCell::new(Kswapd {
+            task: None,
+            sleeping: false,
+        })
+    };
+}
+
+pub fn start_kswapd() {
+    KSWAPD.exclusive_access().task = Some(spawn_kernel_thread("kswapd", kswapd));
+}
+
+pub fn wakeup_kswapd() {
+    let mut kswapd = KSWAPD.exclusive_access();
+    if kswapd.sleeping {
+        kswapd.sleeping = false;
+        wakeup_task(Arc::clone(kswapd.task.as_ref().unwrap()));
+    }
+}
+
+/// Go over every process once and write out what was taken from them.
+/// Return the number of pages reclaimed.
+fn reclaim_round() -> usize {
+    let processes: Vec<_> = PID2PCB.exclusive_access().values().cloned().collect();
+    let mut reclaimed = 0;
+    for process in processes {
+        let mut inner = process.inner_exclusive_access();
+        // user pages may be in use by the kernel through their frames
+        if inner.is_zombie || inner.in_syscall > 0 {
+            continue;
+        }
+        let (count, swapped) = inner.memory_set.reclaim(SWAP_CLUSTER);
+        drop(inner);
+        for entry in swapped {
+            entry.write_out();
+        }
+        reclaimed += count;
+        preempt_point();
+        if free_frame_count() >= FREE_FRAMES_HIGH {
+            break;
+        }
+    }
+    reclaimed
+}
+
+fn kswapd() -> ! {
+    unsafe {
+        sstatus::set_sie();
+    }
+    loop {
+        // the first round may only clear accessed bits
+        let mut idle_rounds = 0;
+        while free_frame_count() < FREE_FRAMES_HIGH && idle_rounds < 2 {
+            if reclaim_round() == 0 {
+                idle_rounds += 1;
+            } else {
+                idle_rounds = 0;
+            }
+        }
+        let task_cx_ptr = KSWAPD.exclusive_session(|kswapd| {
+            kswapd.sleeping = true;
+            block_current_task()
+        });
+        schedule(task_cx_ptr);
+    }
+}
@@ -2,6 +2,7 @@ use super::{ProcessControlBlock, TaskControlBlock, TaskStatus};
 use crate::sync::UPIntrFreeCell;
 use alloc::collections::{BTreeMap, VecDeque};
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use lazy_static::*;
 
 pub struct TaskManager {
@@ -18,9 +19,24 @@ impl TaskManager {
     pub fn add(&mut self, task: Arc<TaskControlBlock>) {
         self.ready_queue.push_back(task);
     }
+    /// Put `task` first, to run next.
+    pub fn add_first(&mut self, task: Arc<TaskControlBlock>) {
+        self.ready_queue.push_front(task);
+    }
     pub fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
         self.ready_queue.pop_front()
     }
+    /// Take the first task ready which `pred` holds for.
+    pub fn fetch_where(
+        &mut self,
+        pred: impl Fn(&Arc<TaskControlBlock>) -> bool,
+    ) -> Option<Arc<TaskControlBlock>> {
+        let index = self.ready_queue.iter().position(pred)?;
+        self.ready_queue.remove(index)
+    }
+    pub fn ready_count(&self) -> usize {
+        self.ready_queue.len()
+    }
 }
 
 lazy_static! {
@@ -28,6 +44,9 @@ lazy_static! {
         unsafe { UPIntrFreeCell::new(TaskManager::new()) };
     pub static ref PID2PCB: UPIntrFreeCell<BTreeMap<usize, Arc<ProcessControlBlock>>> =
         unsafe { UPIntrFreeCell::new(BTreeMap::new()) };
+    /// Kernel threads by name, which run until shutdown.
+    static ref KERNEL_TASKS: UPIntrFreeCell<Vec<(&'static str, Arc<TaskControlBlock>)>> =
+        unsafe { UPIntrFreeCell::new(Vec::new()) };
 }
 
 pub fn add_task(task: Arc<TaskControlBlock>) {
@@ -41,10 +60,25 @@ pub fn wakeup_task(task: Arc<TaskControlBlock>) {
     add_task(task);
 }
 
+/// Wake `task` to run next, ahead of those ready before it, for the task
+/// running to hand the processor over to it when it blocks.
+pub fn wakeup_task_next(task: Arc<TaskControlBlock>) {
+    task.inner_exclusive_access().task_status = TaskStatus::Ready;
+    TASK_MANAGER.exclusive_access().add_first(task);
+}
+
 pub fn fetch_task() -> Option<Arc<TaskControlBlock>> {
     TASK_MANAGER.exclusive_access().fetch()
 }
 
+/// Take the first task ready which `pred` holds for, whatever is ahead of
+/// it.
+pub fn fetch_task_where(
+    pred: impl Fn(&Arc<TaskControlBlock>) -> bool,
+) -> Option<Arc<TaskControlBlock>> {
+    TASK_MANAGER.exclusive_access().fetch_where(pred)
+}
+
 pub fn pid2process(pid: usize) -> Option<Arc<ProcessControlBlock>> {
     let map = PID2PCB.exclusive_access();
     map.get(&pid).map(Arc::clone)
@@ -60,3 +94,64 @@ pub fn remove_from_pid2process(pid: usize) {
         panic!("cannot find pid {} in pid2task!", pid);
     }
 }
+
+/// Numbers of processes and of ready tasks, or None if they are being
+/// changed.
+pub fn task_counts() -> Option<(usize, usize)> {
+    let processes = PID2PCB.try_exclusive_access()?.len();
+    let ready = TASK_MANAGER.try_exclusive_access()?.ready_count();
+    Some((processes, ready))
+}
+
+/// Start the kernel thread `name` running `entry`.
+pub fn spawn_kernel_thread(name: &'static str, entry: fn() -> !) -> Arc<TaskControlBlock> {
+    let task = Arc::new(TaskControlBlock::new_kernel_thread(entry));
+    KERNEL_TASKS
+        .exclusive_access()
+        .push((name, Arc::clone(&task)));
+    add_task(Arc::clone(&task));
+    task
+}
+
+/// Where `task` is among the kernel threads, in the order they started,
+/// if it is one and the list of them is not being changed.
+pub fn kernel_thread_index(task: &Arc<TaskControlBlock>) -> Option<usize> {
+    KERNEL_TASKS
+        .try_exclusive_access()?
+        .iter()
+        .position(|(_, thread)| Arc::ptr_eq(thread, task))
+}
+
+/// The name of `task` if it is a kernel thread, and the list of them is not
+/// being changed.
+pub fn kernel_thread_name(task: &Arc<TaskControlBlock>) -> Option<&'static str> {
+    KERNEL_TASKS
+        .try_exclusive_access()?
+        .iter()
+        .find(|(_, thread)| Arc::ptr_eq(thread, task))
+        .map(|(name, _)| *name)
+}
+
+/// The kernel threads and what they are doing, for /proc/ktasks.
+pub fn kernel_tasks() -> Vec<(&'static str, TaskStatus)> {
+    let tasks: Vec<_> = KERNEL_TASKS.exclusive_access().clone();
+    tasks
+        .into_iter()
+        .map(|(name, task)| (name, task.inner_exclusive_access().task_status))
+        .collect()
+}
+
+/// The processes of the group `pgid`.
+pub fn group_processes(pgid: usize) -> Vec<Arc<ProcessControlBlock>> {
+    PID2PCB
+        .exclusive_access()
+        .values()
+        .filter(|process| process.inner_exclusive_access().pgid == pgid)
+        .cloned()
+        .collect()
+}
+
+/// The pids of all processes, in order.
+pub fn pids() -> Vec<usize> {
+    PID2PCB.exclusive_access().keys().copied().collect()
+}
@@ -1,30 +1,60 @@
+mod caps;
 mod context;
+mod coredump;
+mod executor;
+mod flusher;
 mod id;
+mod itimer;
+mod kswapd;
 mod manager;
+mod preempt;
 mod process;
 mod processor;
+mod rlimit;
 mod signal;
 mod switch;
 #[allow(clippy::module_inception)]
 mod task;
 
 use self::id::TaskUserRes;
-use crate::fs::{open_file, OpenFlags};
-use crate::sbi::shutdown;
+use crate::fs::{open_file, release_process_locks, OpenFlags};
+use crate::mm::PageFault;
+use crate::power::{power_down, PowerAction};
+use crate::timer::add_sleep_timer;
 use alloc::{sync::Arc, vec::Vec};
 use lazy_static::*;
-use manager::fetch_task;
-use process::ProcessControlBlock;
+use rlimit::{RLIMIT_AS, RLIMIT_STACK};
 use switch::__switch;
 
+pub use caps::{capable, Capabilities};
 pub use context::TaskContext;
+pub use executor::{block_on, sleep_ns, spawn, start_executor, BoxFuture};
+pub use flusher::start_flusher;
 pub use id::{kstack_alloc, pid_alloc, KernelStack, PidHandle, IDLE_PID};
-pub use manager::{add_task, pid2process, remove_from_pid2process, wakeup_task};
+pub use itimer::{expire_timer, set_timer, TimerId};
+pub use kswapd::{start_kswapd, wakeup_kswapd};
+pub use manager::{
+    add_task, fetch_task, fetch_task_where, group_processes, kernel_tasks, kernel_thread_index,
+    pid2process, pids, remove_from_pid2process, spawn_kernel_thread, task_counts, wakeup_task,
+    wakeup_task_next,
+};
+pub use preempt::{
+    preempt_disable, preempt_enable, preempt_point, replace_preempt_count, sched_init,
+    take_need_resched, tick_resched,
+};
+pub use process::ProcessControlBlock;
 pub use processor::{
-    current_kstack_top, current_process, current_task, current_trap_cx, current_trap_cx_user_va,
-    current_user_token, run_tasks, schedule, take_current_task,
+    current_ids, current_kstack_top, current_process, current_task, current_task_name,
+    current_trap_cx, current_trap_cx_user_va, current_user_token, run_tasks, schedule,
+    take_current_task, try_current_user_token,
+};
+pub use rlimit::{RLimit, RLIMIT_NOFILE};
+pub use signal::{
+    handle_signals, has_signal, send_fault_signal, send_signal, send_thread_signal, set_action,
+    signal_pending, sigreturn, take_signal, valid_signal, SigAction, SigInfo, SignalFlags,
+    ERESTARTSYS, ILL_ILLOPC, SEGV_MAPERR, SIGALRM, SIGCHLD, SIGILL, SIGINT, SIGSEGV, SIGTSTP,
+    SI_KERNEL, SI_TKILL, SI_USER,
 };
-pub use signal::SignalFlags;
 pub use task::{TaskControlBlock, TaskStatus};
 
 pub fn suspend_current_and_run_next() {
@@ -58,6 +88,49 @@ pub fn block_current_and_run_next() {
     schedule(task_cx_ptr);
 }
 
+/// Sleep until `expire_ns`, or until a signal comes for the current thread,
+/// which may have come already; the caller looks at which it was.
+pub fn sleep_current_until(expire_ns: u64) {
+    let task = current_task().unwrap();
+    task.inner_exclusive_access().sleeping = true;
+    add_sleep_timer(expire_ns, task.clone());
+    if signal_pending() {
+        task.inner_exclusive_access().sleeping = false;
+        return;
+    }
+    drop(task);
+    let task = take_current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    // woken since by a signal, it only yields
+    let woken = !task_inner.sleeping;
+    task_inner.task_status = if woken {
+        TaskStatus::Ready
+    } else {
+        TaskStatus::Blocked
+    };
+    let task_cx_ptr = &mut task_inner.task_cx as *mut TaskContext;
+    drop(task_inner);
+    if woken {
+        add_task(task);
+    }
+    schedule(task_cx_ptr);
+}
+
+/// Wake `task` if it sleeps in sleep_current_until.
+pub fn wake_sleeper(task: &Arc<TaskControlBlock>) {
+    let mut task_inner = task.inner_exclusive_access();
+    if !task_inner.sleeping {
+        return;
+    }
+    task_inner.sleeping = false;
+    // or it is about to, and sees it need not
+    let blocked = task_inner.task_status == TaskStatus::Blocked;
+    drop(task_inner);
+    if blocked {
+        wakeup_task(task.clone());
+    }
+}
+
 /// Exit the current 'Running' task and run the next task in task list.
 pub fn exit_current_and_run_next(exit_code: i32) {
     let task = take_current_task().unwrap();
@@ -76,17 +149,9 @@ pub fn exit_current_and_run_next(exit_code: i32) {
     if tid == 0 {
         let pid = process.getpid();
         if pid == IDLE_PID {
-            println!(
-                "[kernel] Idle process exit with exit_code {} ...",
-                exit_code
-            );
-            if exit_code != 0 {
-                //crate::sbi::shutdown(255); //255 == -1 for err hint
-                shutdown(true);
-            } else {
-                //crate::sbi::shutdown(0); //0 for success hint
-                shutdown(false);
-            }
+            info!("Idle process exit with exit_code {} ...", exit_code);
+            // synced first, not to leave the disks dirty
+            power_down(PowerAction::PowerOff, exit_code != 0);
         }
         remove_from_pid2process(pid);
         let mut process_inner = process.inner_exclusive_access();
@@ -124,9 +189,13 @@ pub fn exit_current_and_run_next(exit_code: i32) {
         let mut process_inner = process.inner_exclusive_access();
         process_inner.children.clear();
         // deallocate other data in user space i.e. program code/data section
-        process_inner.memory_set.recycle_data_pages();
+        let areas = process_inner.memory_set.recycle_data_pages();
         // drop file descriptors
         process_inner.fd_table.clear();
+        // shared file mappings are written back on drop, which may sleep
+        drop(process_inner);
+        drop(areas);
+        release_process_locks(pid, None);
     }
     drop(process);
     // we do not have to save task context
@@ -137,8 +206,7 @@ pub fn exit_current_and_run_next(exit_code: i32) {
 lazy_static! {
     pub static ref INITPROC: Arc<ProcessControlBlock> = {
         let inode = open_file("initproc", OpenFlags::RDONLY).unwrap();
-        let v = inode.read_all();
-        ProcessControlBlock::new(v.as_slice())
+        ProcessControlBlock::new("initproc", &inode.inode().unwrap())
     };
 }
 
@@ -146,14 +214,65 @@ pub fn add_initproc() {
     let _initproc = INITPROC.clone();
 }
 
-pub fn check_signals_of_current() -> Option<(i32, &'static str)> {
-    let process = current_process();
-    let process_inner = process.inner_exclusive_access();
-    process_inner.signals.check_error()
+/// Count the threads of the current process in a syscall, whose user pages
+/// are not reclaimed meanwhile.
+pub fn enter_syscall() {
+    current_process().inner_exclusive_access().in_syscall += 1;
+}
+
+pub fn leave_syscall() {
+    current_process().inner_exclusive_access().in_syscall -= 1;
 }
 
-pub fn current_add_signal(signal: SignalFlags) {
-    let process = current_process();
+/// Times a fault waits for kswapd when out of frames, before giving up.
+const OOM_RETRIES: usize = 16;
+
+/// Resolve a fault on `va` in the address space `token` of the current
+/// process. Return false if there is nothing to resolve.
+pub fn handle_page_fault(token: usize, va: usize, write: bool) -> bool {
+    let process = match current_task() {
+        Some(task) => task.process.upgrade().unwrap(),
+        None => return false,
+    };
     let mut process_inner = process.inner_exclusive_access();
-    process_inner.signals |= signal;
+    if process_inner.memory_set.token() != token {
+        return false;
+    }
+    let mut fault = process_inner.memory_set.handle_page_fault(va.into(), write);
+    if let PageFault::Invalid = fault {
+        // a fault right below a stack grows it
+        let stack_limit = process_inner.rlimits.cur(RLIMIT_STACK);
+        let as_limit = process_inner.rlimits.cur(RLIMIT_AS);
+        if process_inner
+            .memory_set
+            .grow_stack(va.into(), stack_limit, as_limit)
+        {
+            fault = process_inner.memory_set.handle_page_fault(va.into(), write);
+        }
+    }
+    match fault {
+        PageFault::Resolved => true,
+        PageFault::Invalid => false,
+        PageFault::Load(page) => {
+            // reading the file may sleep, so do not hold the PCB meanwhile
+            drop(process_inner);
+            let mut retries = 0;
+            let frame = loop {
+                match page.load() {
+                    Some(frame) => break frame,
+                    // give kswapd a chance to free some frames
+                    None if retries < OOM_RETRIES => {
+                        retries += 1;
+                        wakeup_kswapd();
+                        suspend_current_and_run_next();
+                    }
+                    None => return false,
+                }
+            };
+            let mut process_inner = process.inner_exclusive_access();
+            // either we map it or someone else did, retrying is fine anyway
+            process_inner.memory_set.map_loaded(&page, frame);
+            true
+        }
+    }
 }
@@ -0,0 +1,95 @@
+//! Voluntary kernel preemption.
+//!
+//! A timer interrupt only sets `need_resched`. It is honored on the way
+//! back to user mode and at the preemption points placed in long kernel
+//! loops, as long as `preempt_count` is zero. Holding a `UPIntrFreeCell`
+//! counts as a critical section as well.
+//!
+//! Whether a tick sets it is up to `sched=` on the command line: `rr`, the
+//! default, has the tasks ready take turns at each tick, `fifo` leaves the
+//! task running to run until it blocks or yields.
+
+use super::{current_task, suspend_current_and_run_next};
+use crate::cmdline;
+use crate::sync::UPSafeCellRaw;
+use core::sync::atomic::{AtomicBool, Ordering};
+use lazy_static::*;
+
+/// Whether a tick preempts the task running, as `sched=rr` has it.
+static TICK_PREEMPTS: AtomicBool = AtomicBool::new(true);
+
+/// Preemption state of a hart, there is only one hart for now.
+pub struct PreemptInfo {
+    preempt_count: usize,
+    need_resched: bool,
+}
+
+lazy_static! {
+    static ref PREEMPT_INFO: UPSafeCellRaw<PreemptInfo> = unsafe {
+        UPSafeCellRaw::new(PreemptInfo {
+            preempt_count: 0,
+            need_resched: false,
+        })
+    };
+}
+
+pub fn preempt_disable() {
+    PREEMPT_INFO.get_mut().preempt_count += 1;
+}
+
+pub fn preempt_enable() {
+    let info = PREEMPT_INFO.get_mut();
+    assert!(info.preempt_count > 0, "unbalanced preempt_enable");
+    info.preempt_count -= 1;
+}
+
+/// Set the count of the current context and return the old one, used
+/// when switching tasks.
+pub fn replace_preempt_count(count: usize) -> usize {
+    core::mem::replace(&mut PREEMPT_INFO.get_mut().preempt_count, count)
+}
+
+/// Ask for a reschedule at the next preemption point or trap return.
+pub fn set_need_resched() {
+    PREEMPT_INFO.get_mut().need_resched = true;
+}
+
+/// A tick came: ask for a reschedule, unless the policy is FIFO.
+pub fn tick_resched() {
+    if TICK_PREEMPTS.load(Ordering::Relaxed) {
+        set_need_resched();
+    }
+}
+
+/// Take the policy of `sched=` on the command line, and tell if `smp=`
+/// asks for harts there are not.
+pub fn sched_init() {
+    match cmdline::option("sched") {
+        None | Some("rr") => {}
+        Some("fifo") => TICK_PREEMPTS.store(false, Ordering::Relaxed),
+        Some(policy) => warn!("sched={} is neither rr nor fifo, rr it is", policy),
+    }
+    if let Some(harts) = cmdline::option("smp").filter(|harts| *harts != "1") {
+        warn!("smp={}: tasks run on the boot hart alone", harts);
+    }
+}
+
+/// Clear and return the pending reschedule request.
+pub fn take_need_resched() -> bool {
+    core::mem::replace(&mut PREEMPT_INFO.get_mut().need_resched, false)
+}
+
+/// Yield the hart if a reschedule is pending and nothing forbids it.
+/// The caller must not hold any `UPIntrFreeCell` or spin lock.
+pub fn preempt_point() {
+    let info = PREEMPT_INFO.get_mut();
+    if !info.need_resched || info.preempt_count > 0 {
+        return;
+    }
+    // kernel code running before the first task cannot be preempted
+    if current_task().is_none() {
+        return;
+    }
+    info.need_resched = false;
+    suspend_current_and_run_next();
+}
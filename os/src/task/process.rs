@@ -1,37 +1,190 @@
+use super::add_task;
+use super::caps::Capabilities;
 use super::id::RecycleAllocator;
+use super::itimer::ProcessTimers;
 use super::manager::insert_into_pid2process;
+use super::rlimit::{RLimits, RLIMIT_AS, RLIMIT_NOFILE, RLIMIT_STACK};
+use super::signal::{ProcessSignals, ThreadSignals};
 use super::TaskControlBlock;
-use super::{add_task, SignalFlags};
 use super::{pid_alloc, PidHandle};
-use crate::fs::{File, Stdin, Stdout};
-use crate::mm::{translated_refmut, MemorySet, KERNEL_SPACE};
+use crate::config::PAGE_SIZE;
+use crate::config::USER_STACK_SIZE;
+use crate::fs::{open_file, File, Inode, OpenFlags, Stdin, Stdout};
+use crate::mm::{ElfInfo, MemorySet, UserPtr, UserSlice, KERNEL_SPACE};
+use crate::objects::{Live, OBJ_PROCESS};
+use crate::perf::PerfCounts;
+use crate::random::fill_random;
 use crate::sync::{Condvar, Mutex, Semaphore, UPIntrFreeCell, UPIntrRefMut};
 use crate::trap::{trap_handler, TrapContext};
+use alloc::collections::BTreeSet;
 use alloc::string::String;
 use alloc::sync::{Arc, Weak};
 use alloc::vec;
 use alloc::vec::Vec;
 
+// auxiliary vector entry types
+const AT_NULL: usize = 0;
+const AT_PHDR: usize = 3;
+const AT_PHENT: usize = 4;
+const AT_PHNUM: usize = 5;
+const AT_PAGESZ: usize = 6;
+const AT_BASE: usize = 7;
+const AT_ENTRY: usize = 9;
+const AT_RANDOM: usize = 25;
+/// Bytes of randomness pointed to by AT_RANDOM.
+const AT_RANDOM_SIZE: usize = 16;
+/// Entries of the auxiliary vector built by exec, AT_NULL included.
+const AUXV_LEN: usize = 8;
+
+/// Copy `bytes` below `user_sp` and return their address.
+fn push_bytes(token: usize, user_sp: &mut usize, bytes: &[u8]) -> usize {
+    *user_sp -= bytes.len();
+    UserSlice::new(token, *user_sp as *const u8, bytes.len())
+        .copy_to_user(bytes)
+        .expect("the user stack is mapped");
+    *user_sp
+}
+
+/// Copy `s` with a trailing '\0' below `user_sp` and return its address.
+fn push_str(token: usize, user_sp: &mut usize, s: &str) -> usize {
+    push_bytes(token, user_sp, &[0]);
+    push_bytes(token, user_sp, s.as_bytes())
+}
+
+/// Map the ELF `file`, and the interpreter it asks for if any.
+fn load_elf(file: &Arc<dyn Inode>) -> Option<(MemorySet, ElfInfo)> {
+    let (mut memory_set, mut elf_info) = MemorySet::from_elf(file)?;
+    if let Some(interp) = elf_info.interp.clone() {
+        let interp_file = open_file(interp.as_str(), OpenFlags::RDONLY)?.inode()?;
+        memory_set.load_interp(&interp_file, &mut elf_info)?;
+    }
+    Some((memory_set, elf_info))
+}
+
+/// Whether `args` and `envs` take at most a quarter of the user stack, with
+/// the vectors pointing to them.
+fn args_fit_stack(args: &[String], envs: &[String]) -> bool {
+    let strings_size: usize = args.iter().chain(envs.iter()).map(|s| s.len() + 1).sum();
+    // argc, argv[], NULL, envp[], NULL, auxv
+    let vectors_size = (args.len() + envs.len() + 3 + 2 * AUXV_LEN) * core::mem::size_of::<usize>();
+    strings_size + AT_RANDOM_SIZE + vectors_size <= USER_STACK_SIZE / 4
+}
+
+/// The name of a process running `args`, empty if there are none.
+fn process_name(args: &[String]) -> String {
+    args.first()
+        .and_then(|arg0| arg0.rsplit('/').next())
+        .map(String::from)
+        .unwrap_or_default()
+}
+
+/// Push strings first, then argc, argv[], envp[] and auxv below them in the
+/// SysV layout on the user stack of `task`, and start it at the entry.
+fn init_main_thread(task: &TaskControlBlock, elf_info: &ElfInfo, args: &[String], envs: &[String]) {
+    let token = task.get_user_token();
+    let task_inner = task.inner_exclusive_access();
+    let mut user_sp = task_inner.res.as_ref().unwrap().ustack_top();
+    let argv: Vec<usize> = args
+        .iter()
+        .map(|arg| push_str(token, &mut user_sp, arg))
+        .collect();
+    let envp: Vec<usize> = envs
+        .iter()
+        .map(|env| push_str(token, &mut user_sp, env))
+        .collect();
+    let mut random_bytes = [0u8; AT_RANDOM_SIZE];
+    fill_random(&mut random_bytes);
+    let at_random = push_bytes(token, &mut user_sp, &random_bytes);
+    let auxv: [(usize, usize); AUXV_LEN] = [
+        (AT_PHDR, elf_info.phdr),
+        (AT_PHENT, elf_info.phent),
+        (AT_PHNUM, elf_info.phnum),
+        (AT_PAGESZ, PAGE_SIZE),
+        (AT_BASE, elf_info.interp_base),
+        (AT_ENTRY, elf_info.program_entry),
+        (AT_RANDOM, at_random),
+        (AT_NULL, 0),
+    ];
+    let mut vectors = vec![args.len()];
+    vectors.extend(argv);
+    vectors.push(0);
+    vectors.extend(envp);
+    vectors.push(0);
+    for (key, value) in auxv {
+        vectors.extend([key, value]);
+    }
+    user_sp -= vectors.len() * core::mem::size_of::<usize>();
+    // the RISC-V psABI requires a 16B aligned sp
+    user_sp -= user_sp % 16;
+    let vectors_ptr = UserPtr::new(token, user_sp as *const usize);
+    for (i, word) in vectors.iter().enumerate() {
+        vectors_ptr
+            .add(i)
+            .write(*word)
+            .expect("the user stack is mapped");
+    }
+    let argv_base = user_sp + core::mem::size_of::<usize>();
+    let envp_base = argv_base + (args.len() + 1) * core::mem::size_of::<usize>();
+    // initialize trap_cx
+    let mut trap_cx = TrapContext::app_init_context(
+        elf_info.entry_point,
+        user_sp,
+        KERNEL_SPACE.exclusive_access().token(),
+        task.kstack.get_top(),
+        trap_handler as usize,
+    );
+    trap_cx.x[10] = args.len();
+    trap_cx.x[11] = argv_base;
+    trap_cx.x[12] = envp_base;
+    *task_inner.get_trap_cx() = trap_cx;
+}
+
 pub struct ProcessControlBlock {
     // immutable
     pub pid: PidHandle,
+    _live: Live<OBJ_PROCESS>,
     // mutable
     inner: UPIntrFreeCell<ProcessControlBlockInner>,
 }
 
 pub struct ProcessControlBlockInner {
+    /// the last component of argv[0] at exec, as /proc/<pid>/status shows
+    pub name: String,
     pub is_zombie: bool,
     pub memory_set: MemorySet,
     pub parent: Option<Weak<ProcessControlBlock>>,
     pub children: Vec<Arc<ProcessControlBlock>>,
     pub exit_code: i32,
+    /// physical path of the working directory, where relative paths start
+    pub cwd: String,
+    /// the user and group the process acts for, root being 0
+    pub uid: u32,
+    pub gid: u32,
+    /// what it may do beyond what its uid lets it
+    pub caps: Capabilities,
+    /// the process group, for kill and the terminal to signal as a whole
+    pub pgid: usize,
     pub fd_table: Vec<Option<Arc<dyn File + Send + Sync>>>,
-    pub signals: SignalFlags,
+    /// fds with FD_CLOEXEC, closed by exec
+    pub cloexec: BTreeSet<usize>,
+    /// actions, signals sent to the process as a whole, and whether it is
+    /// stopped or killed
+    pub signals: ProcessSignals,
+    /// ITIMER_REAL and the timers of timer_create
+    pub timers: ProcessTimers,
     pub tasks: Vec<Option<Arc<TaskControlBlock>>>,
     pub task_res_allocator: RecycleAllocator,
     pub mutex_list: Vec<Option<Arc<dyn Mutex>>>,
     pub semaphore_list: Vec<Option<Arc<Semaphore>>>,
     pub condvar_list: Vec<Option<Arc<Condvar>>>,
+    pub rlimits: RLimits,
+    /// threads in a syscall, which may hold user pages by their frames
+    pub in_syscall: usize,
+    /// what the threads which exited counted
+    pub perf_exited: PerfCounts,
+    /// the area /dev/kcov collects the coverage of syscalls into
+    #[cfg(feature = "kcov")]
+    pub kcov: Option<crate::kcov::Kcov>,
 }
 
 impl ProcessControlBlockInner {
@@ -40,13 +193,44 @@ impl ProcessControlBlockInner {
         self.memory_set.token()
     }
 
-    pub fn alloc_fd(&mut self) -> usize {
-        if let Some(fd) = (0..self.fd_table.len()).find(|fd| self.fd_table[*fd].is_none()) {
-            fd
-        } else {
-            self.fd_table.push(None);
-            self.fd_table.len() - 1
+    /// Return None if the lowest free fd would exceed RLIMIT_NOFILE.
+    pub fn alloc_fd(&mut self) -> Option<usize> {
+        self.alloc_fd_from(0)
+    }
+
+    /// The lowest free fd not below `min`, without FD_CLOEXEC.
+    pub fn alloc_fd_from(&mut self, min: usize) -> Option<usize> {
+        let fd = (min..self.fd_table.len())
+            .find(|fd| self.fd_table[*fd].is_none())
+            .unwrap_or(self.fd_table.len().max(min));
+        if fd >= self.rlimits.cur(RLIMIT_NOFILE) {
+            return None;
         }
+        if fd >= self.fd_table.len() {
+            self.fd_table.resize(fd + 1, None);
+        }
+        self.cloexec.remove(&fd);
+        Some(fd)
+    }
+
+    /// Take the files of the fds with FD_CLOEXEC out of the table, for the
+    /// caller to drop.
+    fn close_on_exec(&mut self) -> Vec<Arc<dyn File + Send + Sync>> {
+        let cloexec = core::mem::take(&mut self.cloexec);
+        cloexec
+            .into_iter()
+            .filter_map(|fd| self.fd_table.get_mut(fd).and_then(Option::take))
+            .collect()
+    }
+
+    /// Whether `extra` more bytes of user mappings fit in RLIMIT_AS.
+    pub fn check_as_limit(&self, extra: usize) -> bool {
+        self.memory_set.user_size().saturating_add(extra) <= self.rlimits.cur(RLIMIT_AS)
+    }
+
+    /// Whether a new user stack fits in both RLIMIT_STACK and RLIMIT_AS.
+    pub fn check_stack_limit(&self) -> bool {
+        USER_STACK_SIZE <= self.rlimits.cur(RLIMIT_STACK) && self.check_as_limit(USER_STACK_SIZE)
     }
 
     pub fn alloc_tid(&mut self) -> usize {
@@ -71,34 +255,55 @@ impl ProcessControlBlock {
         self.inner.exclusive_access()
     }
 
-    pub fn new(elf_data: &[u8]) -> Arc<Self> {
+    /// The inner, unless it is held already, for those which must not wait.
+    pub fn inner_try_exclusive_access(&self) -> Option<UPIntrRefMut<'_, ProcessControlBlockInner>> {
+        self.inner.try_exclusive_access()
+    }
+
+    pub fn new(name: &str, file: &Arc<dyn Inode>) -> Arc<Self> {
         // memory_set with elf program headers/trampoline/trap context/user stack
-        let (memory_set, ustack_base, entry_point) = MemorySet::from_elf(elf_data);
+        let (memory_set, elf_info) = MemorySet::from_elf(file).unwrap();
+        let (ustack_base, entry_point) = (elf_info.ustack_base, elf_info.entry_point);
         // allocate a pid
         let pid_handle = pid_alloc();
+        let pgid = pid_handle.0;
         let process = Arc::new(Self {
             pid: pid_handle,
+            _live: Live::default(),
             inner: unsafe {
                 UPIntrFreeCell::new(ProcessControlBlockInner {
+                    name: String::from(name),
                     is_zombie: false,
                     memory_set,
                     parent: None,
                     children: Vec::new(),
                     exit_code: 0,
+                    cwd: String::from("/"),
+                    uid: 0,
+                    gid: 0,
+                    caps: Capabilities::all(),
+                    pgid,
                     fd_table: vec![
                         // 0 -> stdin
-                        Some(Arc::new(Stdin)),
+                        Some(Arc::new(Stdin::new())),
                         // 1 -> stdout
                         Some(Arc::new(Stdout)),
                         // 2 -> stderr
                         Some(Arc::new(Stdout)),
                     ],
-                    signals: SignalFlags::empty(),
+                    cloexec: BTreeSet::new(),
+                    signals: ProcessSignals::new(),
+                    timers: ProcessTimers::new(),
                     tasks: Vec::new(),
                     task_res_allocator: RecycleAllocator::new(),
                     mutex_list: Vec::new(),
                     semaphore_list: Vec::new(),
                     condvar_list: Vec::new(),
+                    rlimits: RLimits::new(),
+                    in_syscall: 0,
+                    perf_exited: PerfCounts::default(),
+                    #[cfg(feature = "kcov")]
+                    kcov: None,
                 })
             },
         });
@@ -132,64 +337,139 @@ impl ProcessControlBlock {
     }
 
     /// Only support processes with a single thread.
-    pub fn exec(self: &Arc<Self>, elf_data: &[u8], args: Vec<String>) {
+    ///
+    /// Return false and keep the current image if the new one is not a valid
+    /// ELF, would exceed RLIMIT_AS or RLIMIT_STACK, or if `args` and `envs`
+    /// take more than a quarter of the user stack.
+    pub fn exec(
+        self: &Arc<Self>,
+        file: &Arc<dyn Inode>,
+        args: Vec<String>,
+        envs: Vec<String>,
+    ) -> bool {
         assert_eq!(self.inner_exclusive_access().thread_count(), 1);
+        if !args_fit_stack(&args, &envs) {
+            return false;
+        }
         // memory_set with elf program headers/trampoline/trap context/user stack
-        let (memory_set, ustack_base, entry_point) = MemorySet::from_elf(elf_data);
-        let new_token = memory_set.token();
+        let (memory_set, elf_info) = match load_elf(file) {
+            Some(loaded) => loaded,
+            None => return false,
+        };
+        if !self
+            .inner_exclusive_access()
+            .rlimits
+            .image_fits(&memory_set)
+        {
+            return false;
+        }
         // substitute memory_set
-        self.inner_exclusive_access().memory_set = memory_set;
+        let old_memory_set =
+            core::mem::replace(&mut self.inner_exclusive_access().memory_set, memory_set);
+        // shared file mappings are written back on drop, which may sleep
+        drop(old_memory_set);
+        self.inner_exclusive_access().name = process_name(&args);
+        let mut inner = self.inner_exclusive_access();
+        inner.signals.exec();
+        inner.timers.exec();
+        inner.caps = inner.caps.kept_by(inner.uid);
+        drop(inner);
+        let closed = self.inner_exclusive_access().close_on_exec();
+        // and so may files
+        drop(closed);
         // then we alloc user resource for main thread again
         // since memory_set has been changed
         let task = self.inner_exclusive_access().get_task(0);
         let mut task_inner = task.inner_exclusive_access();
-        task_inner.res.as_mut().unwrap().ustack_base = ustack_base;
+        task_inner.res.as_mut().unwrap().ustack_base = elf_info.ustack_base;
         task_inner.res.as_mut().unwrap().alloc_user_res();
         task_inner.trap_cx_ppn = task_inner.res.as_mut().unwrap().trap_cx_ppn();
-        // push arguments on user stack
-        let mut user_sp = task_inner.res.as_mut().unwrap().ustack_top();
-        user_sp -= (args.len() + 1) * core::mem::size_of::<usize>();
-        let argv_base = user_sp;
-        let mut argv: Vec<_> = (0..=args.len())
-            .map(|arg| {
-                translated_refmut(
-                    new_token,
-                    (argv_base + arg * core::mem::size_of::<usize>()) as *mut usize,
-                )
-            })
-            .collect();
-        *argv[args.len()] = 0;
-        for i in 0..args.len() {
-            user_sp -= args[i].len() + 1;
-            *argv[i] = user_sp;
-            let mut p = user_sp;
-            for c in args[i].as_bytes() {
-                *translated_refmut(new_token, p as *mut u8) = *c;
-                p += 1;
-            }
-            *translated_refmut(new_token, p as *mut u8) = 0;
+        drop(task_inner);
+        init_main_thread(&task, &elf_info, &args, &envs);
+        true
+    }
+
+    /// Create a child running the ELF `file` straight away, instead of copying
+    /// this address space for fork only to throw it away on exec. The child
+    /// inherits fds and rlimits.
+    ///
+    /// Return None on the same conditions as exec.
+    pub fn spawn(
+        self: &Arc<Self>,
+        file: &Arc<dyn Inode>,
+        args: Vec<String>,
+        envs: Vec<String>,
+    ) -> Option<Arc<Self>> {
+        if !args_fit_stack(&args, &envs) {
+            return None;
         }
-        // make the user_sp aligned to 8B for k210 platform
-        user_sp -= user_sp % core::mem::size_of::<usize>();
-        // initialize trap_cx
-        let mut trap_cx = TrapContext::app_init_context(
-            entry_point,
-            user_sp,
-            KERNEL_SPACE.exclusive_access().token(),
-            task.kstack.get_top(),
-            trap_handler as usize,
-        );
-        trap_cx.x[10] = args.len();
-        trap_cx.x[11] = argv_base;
-        *task_inner.get_trap_cx() = trap_cx;
+        let (memory_set, elf_info) = load_elf(file)?;
+        let mut parent = self.inner_exclusive_access();
+        if !parent.rlimits.image_fits(&memory_set) {
+            return None;
+        }
+        // as if the child had forked and exec'd
+        let mut fd_table = parent.fd_table.clone();
+        for fd in parent.cloexec.iter() {
+            fd_table[*fd] = None;
+        }
+        let child = Arc::new(Self {
+            pid: pid_alloc(),
+            _live: Live::default(),
+            inner: unsafe {
+                UPIntrFreeCell::new(ProcessControlBlockInner {
+                    name: process_name(&args),
+                    is_zombie: false,
+                    memory_set,
+                    parent: Some(Arc::downgrade(self)),
+                    children: Vec::new(),
+                    exit_code: 0,
+                    cwd: parent.cwd.clone(),
+                    uid: parent.uid,
+                    gid: parent.gid,
+                    caps: parent.caps.kept_by(parent.uid),
+                    pgid: parent.pgid,
+                    fd_table,
+                    cloexec: BTreeSet::new(),
+                    signals: ProcessSignals::new(),
+                    timers: ProcessTimers::new(),
+                    tasks: Vec::new(),
+                    task_res_allocator: RecycleAllocator::new(),
+                    mutex_list: Vec::new(),
+                    semaphore_list: Vec::new(),
+                    condvar_list: Vec::new(),
+                    rlimits: parent.rlimits,
+                    in_syscall: 0,
+                    perf_exited: PerfCounts::default(),
+                    #[cfg(feature = "kcov")]
+                    kcov: None,
+                })
+            },
+        });
+        parent.children.push(Arc::clone(&child));
+        drop(parent);
+        // create the main thread with its ustack and trap_cx
+        let task = Arc::new(TaskControlBlock::new(
+            Arc::clone(&child),
+            elf_info.ustack_base,
+            true,
+        ));
+        init_main_thread(&task, &elf_info, &args, &envs);
+        child
+            .inner_exclusive_access()
+            .tasks
+            .push(Some(Arc::clone(&task)));
+        insert_into_pid2process(child.getpid(), Arc::clone(&child));
+        add_task(task);
+        Some(child)
     }
 
     /// Only support processes with a single thread.
     pub fn fork(self: &Arc<Self>) -> Arc<Self> {
         let mut parent = self.inner_exclusive_access();
         assert_eq!(parent.thread_count(), 1);
-        // clone parent's memory_set completely including trampoline/ustacks/trap_cxs
-        let memory_set = MemorySet::from_existed_user(&parent.memory_set);
+        // clone parent's memory_set including trampoline/ustacks/trap_cxs, sharing user pages
+        let memory_set = MemorySet::from_existed_user(&mut parent.memory_set);
         // alloc a pid
         let pid = pid_alloc();
         // copy fd table
@@ -204,20 +484,34 @@ impl ProcessControlBlock {
         // create child process pcb
         let child = Arc::new(Self {
             pid,
+            _live: Live::default(),
             inner: unsafe {
                 UPIntrFreeCell::new(ProcessControlBlockInner {
+                    name: parent.name.clone(),
                     is_zombie: false,
                     memory_set,
                     parent: Some(Arc::downgrade(self)),
                     children: Vec::new(),
                     exit_code: 0,
+                    cwd: parent.cwd.clone(),
+                    uid: parent.uid,
+                    gid: parent.gid,
+                    caps: parent.caps,
+                    pgid: parent.pgid,
                     fd_table: new_fd_table,
-                    signals: SignalFlags::empty(),
+                    cloexec: parent.cloexec.clone(),
+                    signals: parent.signals.fork(),
+                    timers: ProcessTimers::new(),
                     tasks: Vec::new(),
                     task_res_allocator: RecycleAllocator::new(),
                     mutex_list: Vec::new(),
                     semaphore_list: Vec::new(),
                     condvar_list: Vec::new(),
+                    rlimits: parent.rlimits,
+                    in_syscall: 0,
+                    perf_exited: PerfCounts::default(),
+                    #[cfg(feature = "kcov")]
+                    kcov: None,
                 })
             },
         });
@@ -237,6 +531,9 @@ impl ProcessControlBlock {
             // but mention that we allocate a new kstack here
             false,
         ));
+        // with the mask of the thread which forked
+        task.inner_exclusive_access().signals =
+            ThreadSignals::with_mask(parent.get_task(0).inner_exclusive_access().signals.mask);
         // attach task to child process
         let mut child_inner = child.inner_exclusive_access();
         child_inner.tasks.push(Some(Arc::clone(&task)));
@@ -1,8 +1,13 @@
 use super::__switch;
-use super::{fetch_task, TaskStatus};
+use super::manager::kernel_thread_name;
+use super::{replace_preempt_count, take_need_resched, TaskStatus};
 use super::{ProcessControlBlock, TaskContext, TaskControlBlock};
+use crate::perf;
 use crate::sync::UPIntrFreeCell;
+use crate::trace::{trace, tracing, TraceEvent};
 use crate::trap::TrapContext;
+use alloc::format;
+use alloc::string::String;
 use alloc::sync::Arc;
 use lazy_static::*;
 
@@ -36,26 +41,50 @@ lazy_static! {
 
 pub fn run_tasks() {
     loop {
+        crate::watchdog::touch();
+        // before the processor is taken, as interrupts replayed may wake
+        // tasks
+        let task = crate::replay::fetch_task();
         let mut processor = PROCESSOR.exclusive_access();
-        if let Some(task) = fetch_task() {
+        if let Some(task) = task {
             let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
             // access coming task TCB exclusively
             let next_task_cx_ptr = task.inner.exclusive_session(|task_inner| {
                 task_inner.task_status = TaskStatus::Running;
                 &task_inner.task_cx as *const TaskContext
             });
+            trace_switch_in(&task);
+            perf::switch_in(&task);
+            // what it counts is known once it is back here
+            let running = Arc::clone(&task);
             processor.current = Some(task);
+            // the coming task starts with a full time slice
+            take_need_resched();
             // release processor manually
             drop(processor);
             unsafe {
                 __switch(idle_task_cx_ptr, next_task_cx_ptr);
             }
+            perf::switch_out(&running);
         } else {
-            println!("no tasks available in run_tasks");
+            trace!("no tasks available in run_tasks");
         }
     }
 }
 
+/// Record `task` starting to run, as its pid and tid, 0 and its tid for a
+/// kernel thread.
+fn trace_switch_in(task: &TaskControlBlock) {
+    if !tracing() {
+        return;
+    }
+    let pid = task.process.upgrade().map_or(0, |process| process.getpid());
+    let tid = task
+        .inner
+        .exclusive_session(|inner| inner.res.as_ref().map_or(0, |res| res.tid));
+    trace(TraceEvent::SwitchIn { pid, tid });
+}
+
 pub fn take_current_task() -> Option<Arc<TaskControlBlock>> {
     PROCESSOR.exclusive_access().take_current()
 }
@@ -64,6 +93,20 @@ pub fn current_task() -> Option<Arc<TaskControlBlock>> {
     PROCESSOR.exclusive_access().current()
 }
 
+/// The pid and tid of the current task, if there is one and neither it nor
+/// the processor is being changed, for the log to tell who wrote what.
+pub fn current_ids() -> Option<(usize, usize)> {
+    let task = PROCESSOR.try_exclusive_access()?.current()?;
+    let pid = task.process.upgrade()?.getpid();
+    let tid = task
+        .inner
+        .try_exclusive_access()?
+        .res
+        .as_ref()
+        .map_or(0, |res| res.tid);
+    Some((pid, tid))
+}
+
 pub fn current_process() -> Arc<ProcessControlBlock> {
     current_task().unwrap().process.upgrade().unwrap()
 }
@@ -90,14 +133,49 @@ pub fn current_trap_cx_user_va() -> usize {
         .trap_cx_user_va()
 }
 
-pub fn current_kstack_top() -> usize {
-    current_task().unwrap().kstack.get_top()
+/// The top of the kernel stack of the current task, if there is one and
+/// the processor is not being changed, for backtraces.
+pub fn current_kstack_top() -> Option<usize> {
+    let task = PROCESSOR.try_exclusive_access()?.current()?;
+    Some(task.kstack.get_top())
+}
+
+/// The token of the address space of the current process, if there is one
+/// and neither it nor the processor is being changed, for the GDB stub.
+pub fn try_current_user_token() -> Option<usize> {
+    let task = PROCESSOR.try_exclusive_access()?.current()?;
+    let process = task.process.upgrade()?;
+    let token = process.inner_try_exclusive_access()?.memory_set.token();
+    Some(token)
+}
+
+/// The current task as a panic tells it, like `initproc 2:0` or `kswapd`,
+/// if there is one and neither it nor the processor is being changed.
+pub fn current_task_name() -> Option<String> {
+    let task = PROCESSOR.try_exclusive_access()?.current()?;
+    if let Some(name) = kernel_thread_name(&task) {
+        return Some(String::from(name));
+    }
+    let (pid, tid) = current_ids()?;
+    let process = task.process.upgrade()?;
+    let name = process.inner_try_exclusive_access()?.name.clone();
+    Some(format!("{} {}:{}", name, pid, tid))
 }
 
 pub fn schedule(switched_task_cx_ptr: *mut TaskContext) {
     let idle_task_cx_ptr =
         PROCESSOR.exclusive_session(|processor| processor.get_idle_task_cx_ptr());
+    // a task blocked in a critical section keeps its count to itself
+    let preempt_count = replace_preempt_count(0);
+    #[cfg(feature = "hardening")]
+    crate::hardening::check_current_stack();
+    #[cfg(feature = "kcov")]
+    let kcov_collecting = crate::kcov::pause();
+    trace(TraceEvent::SwitchOut);
     unsafe {
         __switch(switched_task_cx_ptr, idle_task_cx_ptr);
     }
+    #[cfg(feature = "kcov")]
+    crate::kcov::resume(kcov_collecting);
+    replace_preempt_count(preempt_count);
 }
@@ -0,0 +1,89 @@
+use crate::config::{
+    DEFAULT_RLIMIT_AS, DEFAULT_RLIMIT_CORE, DEFAULT_RLIMIT_MEMLOCK, DEFAULT_RLIMIT_NOFILE,
+    DEFAULT_RLIMIT_STACK, USER_STACK_SIZE,
+};
+use crate::mm::MemorySet;
+
+pub const RLIMIT_STACK: usize = 3;
+pub const RLIMIT_CORE: usize = 4;
+pub const RLIMIT_NOFILE: usize = 7;
+pub const RLIMIT_MEMLOCK: usize = 8;
+pub const RLIMIT_AS: usize = 9;
+pub const RLIM_NLIMITS: usize = 16;
+
+pub const RLIM_INFINITY: usize = usize::MAX;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct RLimit {
+    /// soft limit, the one actually enforced
+    pub rlim_cur: usize,
+    /// hard limit, the ceiling of the soft limit
+    pub rlim_max: usize,
+}
+
+impl RLimit {
+    pub const fn new(rlim_cur: usize, rlim_max: usize) -> Self {
+        Self { rlim_cur, rlim_max }
+    }
+    pub const fn infinity() -> Self {
+        Self::new(RLIM_INFINITY, RLIM_INFINITY)
+    }
+}
+
+/// Resource limits of a process, inherited by fork and kept across exec.
+#[derive(Copy, Clone)]
+pub struct RLimits {
+    limits: [RLimit; RLIM_NLIMITS],
+}
+
+impl RLimits {
+    pub fn new() -> Self {
+        let mut limits = [RLimit::infinity(); RLIM_NLIMITS];
+        limits[RLIMIT_STACK] = RLimit::new(DEFAULT_RLIMIT_STACK, DEFAULT_RLIMIT_STACK);
+        limits[RLIMIT_NOFILE] = RLimit::new(DEFAULT_RLIMIT_NOFILE, DEFAULT_RLIMIT_NOFILE);
+        limits[RLIMIT_MEMLOCK] = RLimit::new(DEFAULT_RLIMIT_MEMLOCK, DEFAULT_RLIMIT_MEMLOCK);
+        limits[RLIMIT_AS] = RLimit::new(DEFAULT_RLIMIT_AS, DEFAULT_RLIMIT_AS);
+        limits[RLIMIT_CORE] = RLimit::new(DEFAULT_RLIMIT_CORE, RLIM_INFINITY);
+        Self { limits }
+    }
+
+    pub fn get(&self, resource: usize) -> Option<RLimit> {
+        self.limits.get(resource).copied()
+    }
+
+    /// Soft limit of `resource`, which must be a valid resource id.
+    pub fn cur(&self, resource: usize) -> usize {
+        self.limits[resource].rlim_cur
+    }
+
+    /// Whether a new image with `memory_set` plus the stack of its main
+    /// thread fits in RLIMIT_STACK and RLIMIT_AS.
+    pub fn image_fits(&self, memory_set: &MemorySet) -> bool {
+        USER_STACK_SIZE <= self.cur(RLIMIT_STACK)
+            && memory_set.user_size() + USER_STACK_SIZE <= self.cur(RLIMIT_AS)
+    }
+
+    /// Whether `len` more bytes can be mapped into `memory_set` under RLIMIT_AS.
+    pub fn mapping_fits(&self, memory_set: &MemorySet, len: usize) -> bool {
+        memory_set.user_size() + len <= self.cur(RLIMIT_AS)
+    }
+
+    /// Bytes which may be locked in memory, under RLIMIT_MEMLOCK.
+    pub fn lock_limit(&self) -> usize {
+        self.cur(RLIMIT_MEMLOCK)
+    }
+
+    /// The soft limit cannot exceed the hard one, and the hard limit can
+    /// only be lowered.
+    pub fn set(&mut self, resource: usize, new_limit: RLimit) -> bool {
+        if resource >= RLIM_NLIMITS || new_limit.rlim_cur > new_limit.rlim_max {
+            return false;
+        }
+        if new_limit.rlim_max > self.limits[resource].rlim_max {
+            return false;
+        }
+        self.limits[resource] = new_limit;
+        true
+    }
+}
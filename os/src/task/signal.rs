@@ -1,29 +1,720 @@
+//! Signals, numbered as on Linux, sent to a process or to one of its
+//! threads and delivered when a thread returns to user mode.
+//!
+//! A process has one action per signal, and signals sent to it as a whole
+//! wait in its queue until a thread not blocking them takes one; a thread
+//! has its own mask and queue, for the signals of its faults and tgkill.
+//! Standard signals are pending once at most, while real-time ones queue
+//! up with what came with each.
+//!
+//! A handler is entered with a frame on the user stack: the siginfo and a
+//! ucontext holding the registers and mask to go back to, which the
+//! handler may change. It returns to the sigreturn trampoline, a page of
+//! kernel text mapped into every user space, whose rt_sigreturn puts them
+//! back.
+//!
+//! Waiting syscalls give up when a signal is there to be delivered,
+//! returning ERESTARTSYS. They are restarted once the signal is dealt with,
+//! unless a handler without SA_RESTART ran, when they fail with EINTR.
+
+use super::coredump::dump_core;
+use super::{
+    block_current_and_run_next, current_process, current_task, current_trap_cx, current_user_token,
+    exit_current_and_run_next, wake_sleeper, wakeup_task, ProcessControlBlock, TaskControlBlock,
+};
+use crate::config::SIGRETURN_TRAMPOLINE;
+use crate::mm::UserPtr;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
 use bitflags::*;
+use core::arch::global_asm;
+use core::mem::size_of;
+
+global_asm!(include_str!("sigreturn.S"));
+
+/// Signals are numbered 1 to NSIG.
+pub const NSIG: usize = 64;
+/// The first of the real-time signals.
+pub const SIGRTMIN: usize = 32;
+pub const SIGINT: usize = 2;
+pub const SIGILL: usize = 4;
+pub const SIGKILL: usize = 9;
+pub const SIGSEGV: usize = 11;
+pub const SIGALRM: usize = 14;
+pub const SIGCHLD: usize = 17;
+pub const SIGTSTP: usize = 20;
 
 bitflags! {
-    pub struct SignalFlags: u32 {
-        const SIGINT    = 1 << 2;
-        const SIGILL    = 1 << 4;
-        const SIGABRT   = 1 << 6;
-        const SIGFPE    = 1 << 8;
-        const SIGSEGV   = 1 << 11;
+    /// A set of signals, signal n being bit n - 1 as in a Linux sigset_t.
+    #[derive(Default)]
+    pub struct SignalFlags: u64 {
+        const SIGHUP    = 1 << 0;
+        const SIGINT    = 1 << 1;
+        const SIGQUIT   = 1 << 2;
+        const SIGILL    = 1 << 3;
+        const SIGTRAP   = 1 << 4;
+        const SIGABRT   = 1 << 5;
+        const SIGBUS    = 1 << 6;
+        const SIGFPE    = 1 << 7;
+        const SIGKILL   = 1 << 8;
+        const SIGUSR1   = 1 << 9;
+        const SIGSEGV   = 1 << 10;
+        const SIGUSR2   = 1 << 11;
+        const SIGPIPE   = 1 << 12;
+        const SIGALRM   = 1 << 13;
+        const SIGTERM   = 1 << 14;
+        const SIGSTKFLT = 1 << 15;
+        const SIGCHLD   = 1 << 16;
+        const SIGCONT   = 1 << 17;
+        const SIGSTOP   = 1 << 18;
+        const SIGTSTP   = 1 << 19;
+        const SIGTTIN   = 1 << 20;
+        const SIGTTOU   = 1 << 21;
+        const SIGURG    = 1 << 22;
+        const SIGXCPU   = 1 << 23;
+        const SIGXFSZ   = 1 << 24;
+        const SIGVTALRM = 1 << 25;
+        const SIGPROF   = 1 << 26;
+        const SIGWINCH  = 1 << 27;
+        const SIGIO     = 1 << 28;
+        const SIGPWR    = 1 << 29;
+        const SIGSYS    = 1 << 30;
+        /// SIGRTMIN to NSIG
+        const SIGRT     = !0 << (SIGRTMIN - 1);
     }
 }
 
 impl SignalFlags {
-    pub fn check_error(&self) -> Option<(i32, &'static str)> {
-        if self.contains(Self::SIGINT) {
-            Some((-2, "Killed, SIGINT=2"))
-        } else if self.contains(Self::SIGILL) {
-            Some((-4, "Illegal Instruction, SIGILL=4"))
-        } else if self.contains(Self::SIGABRT) {
-            Some((-6, "Aborted, SIGABRT=6"))
-        } else if self.contains(Self::SIGFPE) {
-            Some((-8, "Erroneous Arithmetic Operation, SIGFPE=8"))
-        } else if self.contains(Self::SIGSEGV) {
-            Some((-11, "Segmentation Fault, SIGSEGV=11"))
-        } else {
-            None
+    /// The set of signal `signo` alone, which must be from 1 to NSIG.
+    pub fn from_signum(signo: usize) -> Self {
+        Self::from_bits_truncate(1 << (signo - 1))
+    }
+
+    /// The lowest numbered signal in the set.
+    fn first(&self) -> Option<usize> {
+        match self.bits() {
+            0 => None,
+            bits => Some(bits.trailing_zeros() as usize + 1),
+        }
+    }
+
+    /// Signals which can be neither blocked, caught nor ignored.
+    pub fn unblockable() -> Self {
+        Self::SIGKILL | Self::SIGSTOP
+    }
+
+    fn stop() -> Self {
+        Self::SIGSTOP | Self::SIGTSTP | Self::SIGTTIN | Self::SIGTTOU
+    }
+
+    /// Signals ignored by default.
+    fn ignored() -> Self {
+        Self::SIGCHLD | Self::SIGURG | Self::SIGWINCH
+    }
+
+    /// Signals which dump core by default, besides killing.
+    fn core() -> Self {
+        Self::SIGQUIT
+            | Self::SIGILL
+            | Self::SIGTRAP
+            | Self::SIGABRT
+            | Self::SIGBUS
+            | Self::SIGFPE
+            | Self::SIGSEGV
+            | Self::SIGXCPU
+            | Self::SIGXFSZ
+            | Self::SIGSYS
+    }
+}
+
+/// Whether `signo` is a signal number.
+pub fn valid_signal(signo: usize) -> bool {
+    (1..=NSIG).contains(&signo)
+}
+
+pub const SIG_DFL: usize = 0;
+pub const SIG_IGN: usize = 1;
+
+/// Syscalls the signal interrupts are restarted after the handler.
+pub const SA_RESTART: usize = 0x1000_0000;
+/// The signal is not blocked while its handler runs.
+pub const SA_NODEFER: usize = 0x4000_0000;
+/// The action goes back to SIG_DFL once the handler is entered.
+pub const SA_RESETHAND: usize = 0x8000_0000;
+
+/// `struct sigaction` of rt_sigaction, without sa_restorer as on riscv.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SigAction {
+    pub handler: usize,
+    pub flags: usize,
+    /// blocked besides while the handler runs
+    pub mask: u64,
+}
+
+impl SigAction {
+    const fn default() -> Self {
+        Self {
+            handler: SIG_DFL,
+            flags: 0,
+            mask: 0,
+        }
+    }
+}
+
+/// si_code of signals sent by kill.
+pub const SI_USER: i32 = 0;
+/// si_code of signals sent by the kernel.
+pub const SI_KERNEL: i32 = 0x80;
+/// si_code of signals sent by the timers of timer_create.
+pub const SI_TIMER: i32 = -2;
+/// si_code of signals sent by tgkill.
+pub const SI_TKILL: i32 = -6;
+/// si_code of SIGSEGV for an address not mapped.
+pub const SEGV_MAPERR: i32 = 1;
+/// si_code of SIGILL for an illegal opcode.
+pub const ILL_ILLOPC: i32 = 1;
+
+/// What a handler with SA_SIGINFO learns of its signal: who sent it, the
+/// timer which expired, or the address which faulted. These are the fields of Linux's siginfo_t
+/// this kernel fills in, though not at its offsets.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct SigInfo {
+    pub signo: i32,
+    pub errno: i32,
+    pub code: i32,
+    pub pid: i32,
+    pub uid: u32,
+    /// of a timer: which, and how many expirations it missed while this
+    /// was pending
+    pub timerid: i32,
+    pub overrun: i32,
+    /// of a child which exited, as waitid tells
+    pub status: i32,
+    pub addr: usize,
+    /// sigev_value of a timer
+    pub value: usize,
+}
+
+impl SigInfo {
+    pub fn new(signo: usize, code: i32, pid: usize, uid: u32) -> Self {
+        Self {
+            signo: signo as i32,
+            code,
+            pid: pid as i32,
+            uid,
+            ..Self::default()
+        }
+    }
+
+    /// SIGSEGV or SIGILL of a fault at `addr`.
+    pub fn fault(signo: usize, code: i32, addr: usize) -> Self {
+        Self {
+            signo: signo as i32,
+            code,
+            addr,
+            ..Self::default()
+        }
+    }
+
+    /// `signo` of the expiration of the timer `timerid`.
+    pub fn timer(signo: usize, timerid: usize, overrun: u32, value: usize) -> Self {
+        Self {
+            signo: signo as i32,
+            code: SI_TIMER,
+            timerid: timerid as i32,
+            overrun: overrun as i32,
+            value,
+            ..Self::default()
+        }
+    }
+
+    fn signum(&self) -> usize {
+        self.signo as usize
+    }
+}
+
+/// Real-time signals queued at most, per process or thread.
+const MAX_QUEUED: usize = 64;
+
+/// Signals sent and not yet delivered, in the order they came.
+#[derive(Clone, Default)]
+pub struct SigPending {
+    queue: VecDeque<SigInfo>,
+}
+
+impl SigPending {
+    pub fn set(&self) -> SignalFlags {
+        self.queue.iter().fold(SignalFlags::empty(), |set, info| {
+            set | SignalFlags::from_signum(info.signum())
+        })
+    }
+
+    /// Queue `info`, unless it is of a standard signal pending already.
+    fn push(&mut self, info: SigInfo) {
+        let signo = info.signum();
+        if signo < SIGRTMIN {
+            if self.set().contains(SignalFlags::from_signum(signo)) {
+                return;
+            }
+        } else if self.queue.len() >= MAX_QUEUED {
+            return;
+        }
+        self.queue.push_back(info);
+    }
+
+    /// Take the first of the lowest numbered signal in `allowed`.
+    fn take(&mut self, allowed: SignalFlags) -> Option<SigInfo> {
+        let signo = (self.set() & allowed).first()?;
+        let index = self.queue.iter().position(|info| info.signum() == signo)?;
+        self.queue.remove(index)
+    }
+
+    /// The signal of the timer `timerid` which is queued, if any.
+    pub fn timer_signal(&mut self, timerid: usize) -> Option<&mut SigInfo> {
+        self.queue
+            .iter_mut()
+            .find(|info| info.code == SI_TIMER && info.timerid == timerid as i32)
+    }
+
+    fn remove(&mut self, signals: SignalFlags) {
+        self.queue
+            .retain(|info| !signals.contains(SignalFlags::from_signum(info.signum())));
+    }
+}
+
+/// What a process does on signals.
+pub struct ProcessSignals {
+    /// of signal n at n - 1
+    pub actions: [SigAction; NSIG],
+    /// sent to the process, for any thread to take
+    pub pending: SigPending,
+    /// the stop signal the process is stopped by, until SIGCONT or SIGKILL
+    pub stopped: Option<usize>,
+    /// whether waitpid with WUNTRACED has told of the stop
+    pub stop_reported: bool,
+    /// threads blocked while the process is stopped
+    stopped_tasks: Vec<Arc<TaskControlBlock>>,
+    /// the exit code of a signal which ended the process, for all its
+    /// threads to exit with
+    pub killed: Option<i32>,
+}
+
+impl ProcessSignals {
+    pub fn new() -> Self {
+        Self {
+            actions: [SigAction::default(); NSIG],
+            pending: SigPending::default(),
+            stopped: None,
+            stop_reported: false,
+            stopped_tasks: Vec::new(),
+            killed: None,
         }
     }
+
+    /// For a child, which has the actions but none of the signals.
+    pub fn fork(&self) -> Self {
+        Self {
+            actions: self.actions,
+            ..Self::new()
+        }
+    }
+
+    /// Handlers are gone with the image, while ignored signals stay so.
+    pub fn exec(&mut self) {
+        for action in self.actions.iter_mut() {
+            if action.handler != SIG_IGN {
+                *action = SigAction::default();
+            }
+        }
+    }
+
+    fn action(&self, signo: usize) -> SigAction {
+        self.actions[signo - 1]
+    }
+
+    /// Whether `signo` would be thrown away if delivered now.
+    fn ignores(&self, signo: usize) -> bool {
+        match self.action(signo).handler {
+            SIG_IGN => true,
+            SIG_DFL => (SignalFlags::ignored() | SignalFlags::SIGCONT)
+                .contains(SignalFlags::from_signum(signo)),
+            _ => false,
+        }
+    }
+}
+
+/// The signals of a thread.
+#[derive(Clone, Default)]
+pub struct ThreadSignals {
+    /// blocked from delivery
+    pub mask: SignalFlags,
+    /// sent to this thread alone
+    pub pending: SigPending,
+    /// the mask to go back to after rt_sigsuspend
+    pub saved_mask: Option<SignalFlags>,
+}
+
+impl ThreadSignals {
+    /// For a thread which blocks what `mask` does.
+    pub fn with_mask(mask: SignalFlags) -> Self {
+        Self {
+            mask,
+            ..Self::default()
+        }
+    }
+
+    /// Signals which may be delivered to the thread.
+    fn allowed(&self) -> SignalFlags {
+        !self.mask | SignalFlags::unblockable()
+    }
+}
+
+/// What `signo` means for the process as soon as it is sent: SIGKILL and
+/// SIGCONT end a stop, and a stop signal and SIGCONT cancel each other.
+fn on_send(process: &Arc<ProcessControlBlock>, signo: usize) {
+    let signal = SignalFlags::from_signum(signo);
+    let cancelled = if signal == SignalFlags::SIGCONT {
+        SignalFlags::stop()
+    } else if SignalFlags::stop().contains(signal) {
+        SignalFlags::SIGCONT
+    } else {
+        SignalFlags::empty()
+    };
+    let mut inner = process.inner_exclusive_access();
+    if signal == SignalFlags::SIGKILL || signal == SignalFlags::SIGCONT {
+        inner.signals.stopped = None;
+        for task in inner.signals.stopped_tasks.drain(..) {
+            wakeup_task(task);
+        }
+    }
+    if cancelled.is_empty() {
+        return;
+    }
+    inner.signals.pending.remove(cancelled);
+    for task in inner.tasks.iter().flatten() {
+        task.inner_exclusive_access()
+            .signals
+            .pending
+            .remove(cancelled);
+    }
+}
+
+/// Set the action of `signo` in `process`, returning the one it had. The
+/// signal pending is thrown away if it is ignored now.
+pub fn set_action(
+    process: &Arc<ProcessControlBlock>,
+    signo: usize,
+    action: SigAction,
+) -> SigAction {
+    let mut inner = process.inner_exclusive_access();
+    let old = inner.signals.action(signo);
+    inner.signals.actions[signo - 1] = action;
+    if inner.signals.ignores(signo) {
+        let signal = SignalFlags::from_signum(signo);
+        inner.signals.pending.remove(signal);
+        for task in inner.tasks.iter().flatten() {
+            task.inner_exclusive_access().signals.pending.remove(signal);
+        }
+    }
+    old
+}
+
+/// Send `info` to `process`, for any of its threads to take.
+pub fn send_signal(process: &Arc<ProcessControlBlock>, info: SigInfo) {
+    on_send(process, info.signum());
+    let mut inner = process.inner_exclusive_access();
+    // blocked signals stay, as the action may change before they are taken
+    if inner.signals.ignores(info.signum()) && info.signum() != SIGKILL {
+        return;
+    }
+    inner.signals.pending.push(info);
+    // whichever thread sleeps sees whether it is the one to take it
+    let tasks: Vec<_> = inner.tasks.iter().flatten().cloned().collect();
+    drop(inner);
+    for task in tasks.iter() {
+        wake_sleeper(task);
+    }
+}
+
+/// Send `info` to the thread `task` of `process` alone.
+pub fn send_thread_signal(
+    process: &Arc<ProcessControlBlock>,
+    task: &Arc<TaskControlBlock>,
+    info: SigInfo,
+) {
+    on_send(process, info.signum());
+    if process
+        .inner_exclusive_access()
+        .signals
+        .ignores(info.signum())
+    {
+        return;
+    }
+    task.inner_exclusive_access().signals.pending.push(info);
+    wake_sleeper(task);
+}
+
+/// Send the current thread the signal of its fault, which it may neither
+/// block nor ignore: if it does, the default action kills the process.
+pub fn send_fault_signal(info: SigInfo) {
+    let task = current_task().unwrap();
+    let process = task.process.upgrade().unwrap();
+    let signal = SignalFlags::from_signum(info.signum());
+    let mut task_inner = task.inner_exclusive_access();
+    let mut process_inner = process.inner_exclusive_access();
+    let action = &mut process_inner.signals.actions[info.signum() - 1];
+    if task_inner.signals.mask.contains(signal) || action.handler == SIG_IGN {
+        *action = SigAction::default();
+        task_inner.signals.mask.remove(signal);
+    }
+    task_inner.signals.pending.push(info);
+}
+
+/// Whether the current thread has a signal to deal with, for a waiting
+/// syscall to give up: it is killed or stopped, or a signal which is not
+/// ignored may be delivered.
+pub fn signal_pending() -> bool {
+    let task = current_task().unwrap();
+    let process = task.process.upgrade().unwrap();
+    let task_inner = task.inner_exclusive_access();
+    let process_inner = process.inner_exclusive_access();
+    let signals = &process_inner.signals;
+    if signals.killed.is_some() || signals.stopped.is_some() {
+        return true;
+    }
+    let deliverable =
+        (task_inner.signals.pending.set() | signals.pending.set()) & task_inner.signals.allowed();
+    let mut iter = deliverable;
+    while let Some(signo) = iter.first() {
+        if !signals.ignores(signo) {
+            return true;
+        }
+        iter.remove(SignalFlags::from_signum(signo));
+    }
+    false
+}
+
+/// Take the first signal of `set` sent to the current thread or its
+/// process, blocked or not, for those which wait for signals rather than
+/// have them delivered.
+pub fn take_signal(set: SignalFlags) -> Option<SigInfo> {
+    let task = current_task().unwrap();
+    let process = task.process.upgrade().unwrap();
+    let set = set - SignalFlags::unblockable();
+    let taken = task.inner_exclusive_access().signals.pending.take(set);
+    taken.or_else(|| process.inner_exclusive_access().signals.pending.take(set))
+}
+
+/// Whether `take_signal` would find a signal of `set`.
+pub fn has_signal(set: SignalFlags) -> bool {
+    let task = current_task().unwrap();
+    let process = task.process.upgrade().unwrap();
+    let pending = task.inner_exclusive_access().signals.pending.set()
+        | process.inner_exclusive_access().signals.pending.set();
+    pending.intersects(set - SignalFlags::unblockable())
+}
+
+/// What the interrupted handler goes back to, as `struct ucontext` of
+/// riscv: the registers come after uc_sigmask padded to 1024 signals, pc
+/// in place of x0. The floating point state is not saved, as the kernel
+/// keeps none.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct UContext {
+    flags: usize,
+    link: usize,
+    /// the alternate signal stack, not supported
+    stack: [usize; 3],
+    sigmask: u64,
+    _unused: [u8; 120],
+    regs: [usize; 32],
+}
+
+/// Pushed on the user stack for a handler.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SigFrame {
+    info: SigInfo,
+    uc: UContext,
+}
+
+/// Set the current thread up to run the handler of `info` when it returns
+/// to user mode; false if its stack takes no frame.
+fn enter_handler(info: &SigInfo, action: &SigAction, mask: SignalFlags) -> bool {
+    let cx = current_trap_cx();
+    let mut regs = cx.x;
+    regs[0] = cx.sepc;
+    let frame = SigFrame {
+        info: *info,
+        uc: UContext {
+            flags: 0,
+            link: 0,
+            stack: [0; 3],
+            sigmask: mask.bits(),
+            _unused: [0; 120],
+            regs,
+        },
+    };
+    let sp = (cx.x[2].wrapping_sub(size_of::<SigFrame>())) & !0xf;
+    // the stack may fault in or grow
+    if UserPtr::new(current_user_token(), sp as *const SigFrame)
+        .write(frame)
+        .is_none()
+    {
+        return false;
+    }
+    let cx = current_trap_cx();
+    cx.x[1] = SIGRETURN_TRAMPOLINE;
+    cx.x[2] = sp;
+    cx.x[10] = info.signum();
+    cx.x[11] = sp;
+    cx.x[12] = sp + size_of::<SigInfo>();
+    cx.sepc = action.handler;
+    true
+}
+
+/// Put back what the handler interrupted, from the frame at the user sp,
+/// and return the a0 of it.
+pub fn sigreturn() -> Option<usize> {
+    let cx = current_trap_cx();
+    let frame = UserPtr::new(current_user_token(), cx.x[2] as *const SigFrame).read()?;
+    let cx = current_trap_cx();
+    cx.x[1..].copy_from_slice(&frame.uc.regs[1..]);
+    cx.sepc = frame.uc.regs[0];
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .signals
+        .mask = SignalFlags::from_bits_truncate(frame.uc.sigmask) - SignalFlags::unblockable();
+    Some(cx.x[10])
+}
+
+/// Returned by a waiting syscall which a signal interrupted.
+pub const ERESTARTSYS: isize = -512;
+const EINTR: isize = -4;
+
+/// The message printed when a signal kills a process.
+fn describe(signo: usize) -> &'static str {
+    match signo {
+        2 => "Killed, SIGINT=2",
+        4 => "Illegal Instruction, SIGILL=4",
+        6 => "Aborted, SIGABRT=6",
+        8 => "Erroneous Arithmetic Operation, SIGFPE=8",
+        9 => "Killed, SIGKILL=9",
+        11 => "Segmentation Fault, SIGSEGV=11",
+        15 => "Terminated, SIGTERM=15",
+        _ => "Killed by a signal",
+    }
+}
+
+/// Kill the current process by the default action of `info`, dumping its
+/// core first if the signal is one to.
+fn kill_by(process: &Arc<ProcessControlBlock>, info: &SigInfo) {
+    let signo = info.signum();
+    if SignalFlags::core().contains(SignalFlags::from_signum(signo)) && dump_core(info) {
+        info!("{} (core dumped)", describe(signo));
+    } else {
+        info!("{}", describe(signo));
+    }
+    process.inner_exclusive_access().signals.killed = Some(-(signo as i32));
+}
+
+/// Deal with the signals of the current thread before it returns to user
+/// mode: exit if its process is killed, wait while it is stopped, and take
+/// pending signals until one enters a handler. `restart` is the first
+/// argument of a syscall which returned ERESTARTSYS, to run it again.
+pub fn handle_signals(restart: Option<usize>) {
+    let task = current_task().unwrap();
+    let process = current_process();
+    let mut restart = restart;
+    loop {
+        let mut process_inner = process.inner_exclusive_access();
+        if let Some(exit_code) = process_inner.signals.killed {
+            drop(process_inner);
+            drop(process);
+            drop(task);
+            exit_current_and_run_next(exit_code);
+            return;
+        }
+        if process_inner.signals.stopped.is_some() {
+            // not to run again until SIGCONT or SIGKILL
+            process_inner.signals.stopped_tasks.push(task.clone());
+            drop(process_inner);
+            block_current_and_run_next();
+            continue;
+        }
+        let mut task_inner = task.inner_exclusive_access();
+        let allowed = task_inner.signals.allowed();
+        let info = match task_inner.signals.pending.take(allowed) {
+            Some(info) => info,
+            None => match process_inner.signals.pending.take(allowed) {
+                Some(info) => info,
+                None => break,
+            },
+        };
+        let signo = info.signum();
+        let signal = SignalFlags::from_signum(signo);
+        let action = process_inner.signals.action(signo);
+        match action.handler {
+            SIG_IGN => continue,
+            SIG_DFL if (SignalFlags::ignored() | SignalFlags::SIGCONT).contains(signal) => continue,
+            SIG_DFL if SignalFlags::stop().contains(signal) => {
+                process_inner.signals.stopped = Some(signo);
+                process_inner.signals.stop_reported = false;
+                continue;
+            }
+            SIG_DFL => {
+                drop(task_inner);
+                drop(process_inner);
+                kill_by(&process, &info);
+                continue;
+            }
+            _ => {}
+        }
+        // the handler runs with the mask it asks for added
+        let mask = task_inner
+            .signals
+            .saved_mask
+            .take()
+            .unwrap_or(task_inner.signals.mask);
+        let mut handler_mask =
+            task_inner.signals.mask | SignalFlags::from_bits_truncate(action.mask);
+        if action.flags & SA_NODEFER == 0 {
+            handler_mask |= signal;
+        }
+        task_inner.signals.mask = handler_mask - SignalFlags::unblockable();
+        if action.flags & SA_RESETHAND != 0 {
+            process_inner.signals.actions[signo - 1] = SigAction::default();
+        }
+        drop(task_inner);
+        drop(process_inner);
+        if let Some(arg0) = restart.take() {
+            let cx = current_trap_cx();
+            if action.flags & SA_RESTART != 0 {
+                cx.sepc -= 4;
+                cx.x[10] = arg0;
+            } else {
+                cx.x[10] = EINTR as usize;
+            }
+        }
+        if !enter_handler(&info, &action, mask) {
+            kill_by(&process, &SigInfo::fault(SIGSEGV, SEGV_MAPERR, 0));
+            continue;
+        }
+        return;
+    }
+    // no handler ran: an interrupted syscall goes on, and rt_sigsuspend's
+    // mask goes
+    if let Some(arg0) = restart {
+        let cx = current_trap_cx();
+        cx.sepc -= 4;
+        cx.x[10] = arg0;
+    }
+    let mut task_inner = task.inner_exclusive_access();
+    if let Some(mask) = task_inner.signals.saved_mask.take() {
+        task_inner.signals.mask = mask;
+    }
 }
@@ -1,5 +1,8 @@
 use super::id::TaskUserRes;
+use super::signal::ThreadSignals;
 use super::{kstack_alloc, KernelStack, ProcessControlBlock, TaskContext};
+use crate::objects::{Live, OBJ_TASK};
+use crate::perf::PerfCounts;
 use crate::trap::TrapContext;
 use crate::{
     mm::PhysPageNum,
@@ -11,6 +14,7 @@ pub struct TaskControlBlock {
     // immutable
     pub process: Weak<ProcessControlBlock>,
     pub kstack: KernelStack,
+    _live: Live<OBJ_TASK>,
     // mutable
     pub inner: UPIntrFreeCell<TaskControlBlockInner>,
 }
@@ -33,6 +37,12 @@ pub struct TaskControlBlockInner {
     pub task_cx: TaskContext,
     pub task_status: TaskStatus,
     pub exit_code: Option<i32>,
+    /// mask and signals sent to this thread alone
+    pub signals: ThreadSignals,
+    /// what the counters counted while it ran
+    pub perf: PerfCounts,
+    /// asleep until its timer or a signal wakes it, whichever is first
+    pub sleeping: bool,
 }
 
 impl TaskControlBlockInner {
@@ -59,6 +69,7 @@ impl TaskControlBlock {
         Self {
             process: Arc::downgrade(&process),
             kstack,
+            _live: Live::default(),
             inner: unsafe {
                 UPIntrFreeCell::new(TaskControlBlockInner {
                     res: Some(res),
@@ -66,6 +77,35 @@ impl TaskControlBlock {
                     task_cx: TaskContext::goto_trap_return(kstack_top),
                     task_status: TaskStatus::Ready,
                     exit_code: None,
+                    signals: ThreadSignals::default(),
+                    perf: PerfCounts::default(),
+                    sleeping: false,
+                })
+            },
+        }
+    }
+}
+
+impl TaskControlBlock {
+    /// A task running `entry` in the kernel address space, which belongs to
+    /// no process and never returns to user mode.
+    pub fn new_kernel_thread(entry: fn() -> !) -> Self {
+        let kstack = kstack_alloc();
+        let kstack_top = kstack.get_top();
+        Self {
+            process: Weak::new(),
+            kstack,
+            _live: Live::default(),
+            inner: unsafe {
+                UPIntrFreeCell::new(TaskControlBlockInner {
+                    res: None,
+                    trap_cx_ppn: PhysPageNum(0),
+                    task_cx: TaskContext::goto_kernel_thread(entry, kstack_top),
+                    task_status: TaskStatus::Ready,
+                    exit_code: None,
+                    signals: ThreadSignals::default(),
+                    perf: PerfCounts::default(),
+                    sleeping: false,
                 })
             },
         }
@@ -1,45 +1,136 @@
+//! The clock and the timers.
+//!
+//! `ktime_now` is the monotonic clock of the kernel, the ns since boot
+//! counted by the time CSR at the frequency of the timebase, which the
+//! device tree gives and the board's CLOCK_FREQ stands in for without one.
+//! Whatever keeps time, the timers, the trace and the clocks of the
+//! syscalls, goes by it rather than by ticks; the deadlines of the timer
+//! heap are in its ns, and ms are left to the syscalls taking them.
+//!
+//! A timer due wakes a task, expires an interval timer or calls a function,
+//! all in the interrupt of the timer; what takes longer is a future the
+//! timer hands to kworker instead, which runs it preemptible.
+
 use core::cmp::Ordering;
+use core::future::Future;
+use core::sync::atomic::{self, AtomicUsize};
+use core::task::Waker;
 
+use crate::cmdline;
 use crate::config::CLOCK_FREQ;
 use crate::sbi::set_timer;
 use crate::sync::UPIntrFreeCell;
-use crate::task::{wakeup_task, TaskControlBlock};
+use crate::task::{
+    expire_timer, spawn, wake_sleeper, wakeup_task, BoxFuture, ProcessControlBlock,
+    TaskControlBlock, TimerId,
+};
+use alloc::boxed::Box;
 use alloc::collections::BinaryHeap;
-use alloc::sync::Arc;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
 use lazy_static::*;
 use riscv::register::time;
 
 const TICKS_PER_SEC: usize = 100;
-const MSEC_PER_SEC: usize = 1000;
+pub const NSEC_PER_SEC: u64 = 1_000_000_000;
+pub const NSEC_PER_MSEC: u64 = 1_000_000;
+
+/// The frequency of the time CSR, in Hz.
+static TIMEBASE_FREQ: AtomicUsize = AtomicUsize::new(CLOCK_FREQ);
 
+/// Take the frequency of the timebase from the device tree at `dtb_pa`;
+/// run before the frames it lies in are handed out.
+pub fn init(dtb_pa: usize) {
+    match cmdline::fdt_timebase(dtb_pa) {
+        Some(freq) => {
+            if freq != CLOCK_FREQ {
+                warn!("timebase at {} Hz, not the {} Hz of the board", freq, CLOCK_FREQ);
+            }
+            TIMEBASE_FREQ.store(freq, atomic::Ordering::Relaxed);
+        }
+        None => info!("no timebase in the device tree, taking {} Hz", CLOCK_FREQ),
+    }
+}
+
+/// The frequency of the time CSR, in Hz.
+pub fn timebase_freq() -> usize {
+    TIMEBASE_FREQ.load(atomic::Ordering::Relaxed)
+}
+
+/// The time CSR, in ticks of the timebase; for what needs no unit, like
+/// entropy.
 pub fn get_time() -> usize {
     time::read()
 }
 
+/// `ticks` of the timebase in ns.
+pub fn ticks_to_ns(ticks: usize) -> u64 {
+    (ticks as u128 * NSEC_PER_SEC as u128 / timebase_freq() as u128) as u64
+}
+
+/// `ns` in ticks of the timebase, rounded up not to be early.
+pub fn ns_to_ticks(ns: u64) -> usize {
+    let freq = timebase_freq() as u128;
+    ((ns as u128 * freq + NSEC_PER_SEC as u128 - 1) / NSEC_PER_SEC as u128) as usize
+}
+
+/// The ns since boot, as of the last tick while a recording or replay is
+/// on.
+pub fn ktime_now() -> u64 {
+    match crate::replay::clock_ms() {
+        Some(ms) => ms as u64 * NSEC_PER_MSEC,
+        None => ktime_raw(),
+    }
+}
+
+/// The ns since boot by the time CSR, a recording or replay on or not; for
+/// what goes by the hardware, like the trigger of the timer, or measures,
+/// like the trace.
+pub fn ktime_raw() -> u64 {
+    ticks_to_ns(get_time())
+}
+
+/// The milliseconds since boot, as `ktime_now` says.
 pub fn get_time_ms() -> usize {
-    time::read() / (CLOCK_FREQ / MSEC_PER_SEC)
+    (ktime_now() / NSEC_PER_MSEC) as usize
 }
 
 pub fn set_next_trigger() {
-    set_timer(get_time() + CLOCK_FREQ / TICKS_PER_SEC);
+    set_timer(get_time() + timebase_freq() / TICKS_PER_SEC);
 }
 
 pub struct TimerCondVar {
-    pub expire_ms: usize,
-    pub task: Arc<TaskControlBlock>,
+    /// in ns, by `ktime_now`
+    pub expire_ns: u64,
+    pub event: TimerEvent,
+}
+
+/// What is done once a timer is due.
+pub enum TimerEvent {
+    /// wake a blocked task
+    Wakeup(Arc<TaskControlBlock>),
+    /// wake a task sleeping, unless a signal woke it first
+    Sleep(Arc<TaskControlBlock>),
+    /// expire an interval timer of a process, unless it is gone
+    Expire(Weak<ProcessControlBlock>, TimerId),
+    /// call a function, which wakes a kernel thread if it sleeps
+    Call(fn()),
+    /// have kworker run a future, for work too long for an interrupt
+    Async(BoxFuture),
+    /// wake a future waiting on the timer
+    Waker(Waker),
 }
 
 impl PartialEq for TimerCondVar {
     fn eq(&self, other: &Self) -> bool {
-        self.expire_ms == other.expire_ms
+        self.expire_ns == other.expire_ns
     }
 }
 impl Eq for TimerCondVar {}
 impl PartialOrd for TimerCondVar {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        let a = -(self.expire_ms as isize);
-        let b = -(other.expire_ms as isize);
-        Some(a.cmp(&b))
+        // the earliest on top of the max-heap
+        Some(other.expire_ns.cmp(&self.expire_ns))
     }
 }
 
@@ -54,21 +145,85 @@ lazy_static! {
         unsafe { UPIntrFreeCell::new(BinaryHeap::<TimerCondVar>::new()) };
 }
 
-pub fn add_timer(expire_ms: usize, task: Arc<TaskControlBlock>) {
+pub fn add_timer(expire_ns: u64, task: Arc<TaskControlBlock>) {
+    let mut timers = TIMERS.exclusive_access();
+    timers.push(TimerCondVar {
+        expire_ns,
+        event: TimerEvent::Wakeup(task),
+    });
+}
+
+/// Have `task` woken at `expire_ns` from sleep_current_until.
+pub fn add_sleep_timer(expire_ns: u64, task: Arc<TaskControlBlock>) {
+    let mut timers = TIMERS.exclusive_access();
+    timers.push(TimerCondVar {
+        expire_ns,
+        event: TimerEvent::Sleep(task),
+    });
+}
+
+/// Have the timer `id` of `process` expire at `expire_ns`.
+pub fn add_timer_event(expire_ns: u64, process: Weak<ProcessControlBlock>, id: TimerId) {
+    let mut timers = TIMERS.exclusive_access();
+    timers.push(TimerCondVar {
+        expire_ns,
+        event: TimerEvent::Expire(process, id),
+    });
+}
+
+/// Have `f` called at `expire_ns`.
+pub fn add_timer_call(expire_ns: u64, f: fn()) {
+    let mut timers = TIMERS.exclusive_access();
+    timers.push(TimerCondVar {
+        expire_ns,
+        event: TimerEvent::Call(f),
+    });
+}
+
+/// Have kworker run `future` from `expire_ns` on, with interrupts on and
+/// preemptible, rather than in the interrupt of the timer as a call is.
+pub fn add_timer_async(expire_ns: u64, future: impl Future<Output = ()> + Send + 'static) {
     let mut timers = TIMERS.exclusive_access();
-    timers.push(TimerCondVar { expire_ms, task });
+    timers.push(TimerCondVar {
+        expire_ns,
+        event: TimerEvent::Async(Box::pin(future)),
+    });
+}
+
+/// Have `waker` woken at `expire_ns`.
+pub fn add_timer_waker(expire_ns: u64, waker: Waker) {
+    let mut timers = TIMERS.exclusive_access();
+    timers.push(TimerCondVar {
+        expire_ns,
+        event: TimerEvent::Waker(waker),
+    });
 }
 
 pub fn check_timer() {
-    let current_ms = get_time_ms();
+    let now = ktime_now();
+    let mut due = Vec::new();
     TIMERS.exclusive_session(|timers| {
         while let Some(timer) = timers.peek() {
-            if timer.expire_ms <= current_ms {
-                wakeup_task(Arc::clone(&timer.task));
-                timers.pop();
+            if timer.expire_ns <= now {
+                due.push(timers.pop().unwrap().event);
             } else {
                 break;
             }
         }
     });
+    // outside of the session, as an interval timer adds its next expiration
+    for event in due {
+        match event {
+            TimerEvent::Wakeup(task) => wakeup_task(task),
+            TimerEvent::Sleep(task) => wake_sleeper(&task),
+            TimerEvent::Expire(process, id) => {
+                if let Some(process) = process.upgrade() {
+                    expire_timer(&process, id);
+                }
+            }
+            TimerEvent::Call(f) => f(),
+            TimerEvent::Async(future) => spawn(future),
+            TimerEvent::Waker(waker) => waker.wake(),
+        }
+    }
 }
@@ -0,0 +1,244 @@
+//! Tracepoints: scheduler switches, traps, interrupts and tasks polling
+//! for readiness, recorded with the time into a ring buffer of each hart
+//! while tracing is on, at the cost of a load of a flag while it is off.
+//!
+//! /proc/trace gives the records as a trace of the Trace Event Format,
+//! which chrome://tracing and ui.perfetto.dev show as a timeline: a track
+//! of each hart telling which task ran on it when, and a track of each
+//! task, and of the idle loop, with the traps and interrupts taken in it
+//! and its polls.
+
+use crate::config::MAX_HARTS;
+use crate::mm::hart_id;
+use crate::sync::UPIntrFreeCell;
+use crate::timer::ktime_raw;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, Ordering};
+use lazy_static::*;
+
+/// Records kept of each hart, the oldest going once there are more.
+pub const TRACE_RECORDS: usize = 4096;
+
+#[derive(Clone, Copy)]
+pub enum TraceEvent {
+    /// a trap of `cause`, as in scause, from user mode or the kernel
+    TrapEnter { cause: usize },
+    TrapExit,
+    /// an interrupt of the PLIC from `source` being handled
+    IrqEnter { source: usize },
+    IrqExit,
+    /// the task of `pid` and `tid` starting to run, pid 0 being a kernel
+    /// thread
+    SwitchIn { pid: usize, tid: usize },
+    /// the task running giving the hart back to the idle loop
+    SwitchOut,
+    /// a task waiting for something checking whether it has come
+    Poll { ready: bool },
+}
+
+struct TraceRing {
+    /// the ns since boot of each, and what happened
+    records: Vec<(u64, TraceEvent)>,
+    /// records ever written, the next going at `written % TRACE_RECORDS`
+    written: usize,
+}
+
+impl TraceRing {
+    fn push(&mut self, time: u64, event: TraceEvent) {
+        if self.records.len() < TRACE_RECORDS {
+            self.records.push((time, event));
+        } else {
+            self.records[self.written % TRACE_RECORDS] = (time, event);
+        }
+        self.written += 1;
+    }
+
+    /// The records held, the oldest first.
+    fn iter(&self) -> impl Iterator<Item = &(u64, TraceEvent)> {
+        let split = if self.records.len() < TRACE_RECORDS {
+            0
+        } else {
+            self.written % TRACE_RECORDS
+        };
+        self.records[split..].iter().chain(self.records[..split].iter())
+    }
+}
+
+static TRACING: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref RINGS: Vec<UPIntrFreeCell<TraceRing>> = (0..MAX_HARTS)
+        .map(|_| unsafe {
+            UPIntrFreeCell::new(TraceRing {
+                records: Vec::new(),
+                written: 0,
+            })
+        })
+        .collect();
+    /// The trace made of the records once tracing stopped, lest it be
+    /// made again for each read of /proc/trace.
+    static ref SNAPSHOT: UPIntrFreeCell<Option<String>> = unsafe { UPIntrFreeCell::new(None) };
+}
+
+/// Record `event` on this hart, if tracing is on.
+#[inline]
+pub fn trace(event: TraceEvent) {
+    if tracing() {
+        record(event);
+    }
+}
+
+/// Whether tracing is on, for tracepoints which take work to make their
+/// records.
+pub fn tracing() -> bool {
+    TRACING.load(Ordering::Relaxed)
+}
+
+fn record(event: TraceEvent) {
+    let time = ktime_raw();
+    // a record of the ring being read or written is lost
+    if let Some(mut ring) = RINGS[hart_id()].try_exclusive_access() {
+        ring.push(time, event);
+    }
+}
+
+/// Throw the records away and start tracing.
+pub fn trace_start() {
+    TRACING.store(false, Ordering::Relaxed);
+    for ring in RINGS.iter() {
+        let mut ring = ring.exclusive_access();
+        ring.records.clear();
+        ring.records.reserve_exact(TRACE_RECORDS);
+        ring.written = 0;
+    }
+    *SNAPSHOT.exclusive_access() = None;
+    TRACING.store(true, Ordering::Relaxed);
+}
+
+/// Stop tracing, keeping the records; how many there are.
+pub fn trace_stop() -> usize {
+    TRACING.store(false, Ordering::Relaxed);
+    RINGS
+        .iter()
+        .map(|ring| ring.exclusive_access().records.len())
+        .sum()
+}
+
+/// What a trap of `cause` is called on the timeline.
+fn trap_name(cause: usize) -> &'static str {
+    const INTERRUPT: usize = 1 << (usize::BITS - 1);
+    match cause {
+        c if c == INTERRUPT | 1 => "soft interrupt",
+        c if c == INTERRUPT | 5 => "timer",
+        c if c == INTERRUPT | 9 => "external interrupt",
+        3 => "breakpoint",
+        8 => "syscall",
+        12 | 13 | 15 => "page fault",
+        _ => "trap",
+    }
+}
+
+/// Microseconds since boot of the time `ns`, as the format has them.
+fn timestamp(out: &mut String, ns: u64) {
+    write!(out, "{}.{:03}", ns / 1000, ns % 1000).unwrap();
+}
+
+fn emit(out: &mut String, phase: char, name: &str, hart: usize, track: usize, ns: u64) {
+    write!(
+        out,
+        "{{\"name\":\"{}\",\"ph\":\"{}\",\"pid\":{},\"tid\":{},\"ts\":",
+        name, phase, hart, track
+    )
+    .unwrap();
+    timestamp(out, ns);
+    out.push_str("},\n");
+}
+
+fn metadata(out: &mut String, kind: &str, hart: usize, track: usize, name: &str) {
+    writeln!(
+        out,
+        "{{\"name\":\"{}\",\"ph\":\"M\",\"pid\":{},\"tid\":{},\"args\":{{\"name\":\"{}\"}}}},",
+        kind, hart, track, name
+    )
+    .unwrap();
+}
+
+/// The records of `hart` as events, on the track of the hart, 0, and of
+/// each task, from 2 on, or of the idle loop, 1.
+fn hart_events(out: &mut String, hart: usize, ring: &TraceRing) {
+    const SCHED: usize = 0;
+    const IDLE: usize = 1;
+    let mut tasks: Vec<(usize, usize)> = Vec::new();
+    let mut track = IDLE;
+    for &(time, event) in ring.iter() {
+        match event {
+            TraceEvent::SwitchIn { pid, tid } => {
+                let index = match tasks.iter().position(|&task| task == (pid, tid)) {
+                    Some(index) => index,
+                    None => {
+                        tasks.push((pid, tid));
+                        tasks.len() - 1
+                    }
+                };
+                track = IDLE + 1 + index;
+                let mut name = String::new();
+                write!(name, "{}:{}", pid, tid).unwrap();
+                emit(out, 'B', &name, hart, SCHED, time);
+            }
+            TraceEvent::SwitchOut => {
+                emit(out, 'E', "", hart, SCHED, time);
+                track = IDLE;
+            }
+            TraceEvent::TrapEnter { cause } => emit(out, 'B', trap_name(cause), hart, track, time),
+            TraceEvent::TrapExit => emit(out, 'E', "", hart, track, time),
+            TraceEvent::IrqEnter { source } => {
+                let mut name = String::new();
+                write!(name, "irq {}", source).unwrap();
+                emit(out, 'B', &name, hart, track, time);
+            }
+            TraceEvent::IrqExit => emit(out, 'E', "", hart, track, time),
+            TraceEvent::Poll { ready } => {
+                let name = if ready { "poll ready" } else { "poll pending" };
+                emit(out, 'i', name, hart, track, time);
+            }
+        }
+    }
+    let mut name = String::new();
+    write!(name, "hart {}", hart).unwrap();
+    metadata(out, "process_name", hart, SCHED, &name);
+    metadata(out, "thread_name", hart, SCHED, "tasks");
+    metadata(out, "thread_name", hart, IDLE, "idle");
+    for (index, (pid, tid)) in tasks.into_iter().enumerate() {
+        let mut name = String::new();
+        write!(name, "task {}:{}", pid, tid).unwrap();
+        metadata(out, "thread_name", hart, IDLE + 1 + index, &name);
+    }
+}
+
+fn make_trace() -> String {
+    let mut out = String::from("{\"traceEvents\":[\n");
+    for (hart, ring) in RINGS.iter().enumerate() {
+        let ring = ring.exclusive_access();
+        if !ring.records.is_empty() {
+            hart_events(&mut out, hart, &ring);
+        }
+    }
+    // the format allows no comma after the last event
+    if out.ends_with(",\n") {
+        out.truncate(out.len() - 2);
+    }
+    out.push_str("\n],\"displayTimeUnit\":\"ns\"}\n");
+    out
+}
+
+/// The records as a trace for /proc/trace: as they were when tracing
+/// stopped, or as they are while it goes on.
+pub fn trace_text() -> String {
+    if TRACING.load(Ordering::Relaxed) {
+        return make_trace();
+    }
+    let mut snapshot = SNAPSHOT.exclusive_access();
+    snapshot.get_or_insert_with(make_trace).clone()
+}
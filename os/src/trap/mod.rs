@@ -1,13 +1,22 @@
 mod context;
 
-use crate::config::TRAMPOLINE;
-use crate::syscall::syscall;
+use crate::config::{MMAP_END, TRAMPOLINE};
+use crate::gdbstub;
+use crate::power::handle_stop;
+use crate::random::add_entropy;
+use crate::replay;
+use crate::syscall::{syscall, SYSCALL_RT_SIGRETURN};
 use crate::task::{
-    check_signals_of_current, current_add_signal, current_trap_cx, current_trap_cx_user_va,
-    current_user_token, exit_current_and_run_next, suspend_current_and_run_next, SignalFlags,
+    current_process, current_trap_cx, current_trap_cx_user_va, current_user_token, enter_syscall,
+    handle_page_fault, handle_signals, leave_syscall, send_fault_signal,
+    suspend_current_and_run_next, take_need_resched, tick_resched, SigInfo, ERESTARTSYS,
+    ILL_ILLOPC, SEGV_MAPERR, SIGILL, SIGSEGV,
 };
-use crate::timer::{check_timer, set_next_trigger};
+use crate::timer::{check_timer, get_time, set_next_trigger};
+use crate::trace::{trace, TraceEvent};
+use crate::watchdog;
 use core::arch::{asm, global_asm};
+use core::sync::atomic::{AtomicUsize, Ordering};
 use riscv::register::{
     mtvec::TrapMode,
     scause::{self, Exception, Interrupt, Trap},
@@ -16,8 +25,24 @@ use riscv::register::{
 
 global_asm!(include_str!("trap.S"));
 
+/// Timer interrupts taken, from user and kernel mode alike.
+static TIMER_INTERRUPTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of timer interrupts taken so far, for /proc/interrupts.
+pub fn timer_interrupts() -> usize {
+    TIMER_INTERRUPTS.load(Ordering::Relaxed)
+}
+
+/// The kernel only reaches user memory through `UserPtr` and `UserSlice`,
+/// which translate user addresses with the page table of the process and
+/// access the frames through the direct map. User pages are never mapped
+/// in the kernel space, and SUM is kept clear, so that any other access to
+/// a user address faults in `trap_from_kernel`.
 pub fn init() {
     set_kernel_trap_entry();
+    unsafe {
+        sstatus::clear_sum();
+    }
 }
 
 fn set_kernel_trap_entry() {
@@ -60,7 +85,10 @@ fn disable_supervisor_interrupt() {
 pub fn trap_handler() -> ! {
     set_kernel_trap_entry();
     let scause = scause::read();
+    trace(TraceEvent::TrapEnter { cause: scause.bits() });
     let stval = stval::read();
+    // the first argument of a syscall to run again after signals
+    let mut restart = None;
     // println!("into {:?}", scause.cause());
     match scause.cause() {
         Trap::Exception(Exception::UserEnvCall) => {
@@ -71,11 +99,32 @@ pub fn trap_handler() -> ! {
             enable_supervisor_interrupt();
 
             // get system call return value
-            let result = syscall(cx.x[17], [cx.x[10], cx.x[11], cx.x[12]]);
+            enter_syscall();
+            let (syscall_id, arg0) = (cx.x[17], cx.x[10]);
+            replay::syscall_point(syscall_id);
+            #[cfg(feature = "kcov")]
+            crate::kcov::start();
+            let result = syscall(
+                syscall_id,
+                [cx.x[10], cx.x[11], cx.x[12], cx.x[13], cx.x[14], cx.x[15]],
+            );
+            #[cfg(feature = "kcov")]
+            crate::kcov::stop();
+            leave_syscall();
             // cx is changed during sys_exec, so we have to call it again
             cx = current_trap_cx();
+            // rt_sigreturn returns what a0 was, whatever it is
+            if result == ERESTARTSYS && syscall_id != SYSCALL_RT_SIGRETURN {
+                restart = Some(arg0);
+            }
             cx.x[10] = result as usize;
         }
+        // faults resolved by the kernel, e.g. a write to a CoW page
+        Trap::Exception(Exception::StorePageFault)
+            if handle_page_fault(current_user_token(), stval, true) => {}
+        Trap::Exception(Exception::InstructionPageFault)
+        | Trap::Exception(Exception::LoadPageFault)
+            if handle_page_fault(current_user_token(), stval, false) => {}
         Trap::Exception(Exception::StoreFault)
         | Trap::Exception(Exception::StorePageFault)
         | Trap::Exception(Exception::InstructionFault)
@@ -90,19 +139,29 @@ pub fn trap_handler() -> ! {
                 current_trap_cx().sepc,
             );
             */
-            current_add_signal(SignalFlags::SIGSEGV);
+            send_fault_signal(SigInfo::fault(SIGSEGV, SEGV_MAPERR, stval));
         }
         Trap::Exception(Exception::IllegalInstruction) => {
-            current_add_signal(SignalFlags::SIGILL);
+            send_fault_signal(SigInfo::fault(SIGILL, ILL_ILLOPC, current_trap_cx().sepc));
         }
+        // breakpoints and single steps of GDB
+        Trap::Exception(Exception::Breakpoint)
+            if gdbstub::breakpoint(current_trap_cx(), true) => {}
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
+            TIMER_INTERRUPTS.fetch_add(1, Ordering::Relaxed);
             set_next_trigger();
-            check_timer();
-            suspend_current_and_run_next();
+            if !replay::defer_tick() {
+                check_timer();
+                tick_resched();
+            }
+            watchdog::tick(None);
+            gdbstub::poll(current_trap_cx(), true);
         }
         Trap::Interrupt(Interrupt::SupervisorExternal) => {
+            add_entropy(get_time());
             crate::board::irq_handler();
         }
+        Trap::Interrupt(Interrupt::SupervisorSoft) => handle_stop(),
         _ => {
             panic!(
                 "Unsupported trap {:?}, stval = {:#x}!",
@@ -111,20 +170,24 @@ pub fn trap_handler() -> ! {
             );
         }
     }
-    // check signals
-    if let Some((errno, msg)) = check_signals_of_current() {
-        println!("[kernel] {}", msg);
-        exit_current_and_run_next(errno);
+    // the time slice may have run out in the kernel as well
+    if take_need_resched() {
+        suspend_current_and_run_next();
     }
+    handle_signals(restart);
     trap_return();
 }
 
 #[no_mangle]
 pub fn trap_return() -> ! {
     disable_supervisor_interrupt();
+    trace(TraceEvent::TrapExit);
     set_user_trap_entry();
     let trap_cx_user_va = current_trap_cx_user_va();
-    let user_satp = current_user_token();
+    let user_satp = current_process()
+        .inner_exclusive_access()
+        .memory_set
+        .enter_user();
     extern "C" {
         fn __alltraps();
         fn __restore();
@@ -144,17 +207,44 @@ pub fn trap_return() -> ! {
 }
 
 #[no_mangle]
-pub fn trap_from_kernel(_trap_cx: &TrapContext) {
+pub fn trap_from_kernel(trap_cx: &mut TrapContext) {
     let scause = scause::read();
     let stval = stval::read();
+    trace(TraceEvent::TrapEnter { cause: scause.bits() });
+    #[cfg(feature = "hardening")]
+    crate::hardening::trap_enter(trap_cx);
+    #[cfg(feature = "kcov")]
+    let kcov_active = crate::kcov::irq_enter();
     match scause.cause() {
         Trap::Interrupt(Interrupt::SupervisorExternal) => {
+            add_entropy(get_time());
             crate::board::irq_handler();
         }
+        Trap::Interrupt(Interrupt::SupervisorSoft) => handle_stop(),
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
+            TIMER_INTERRUPTS.fetch_add(1, Ordering::Relaxed);
             set_next_trigger();
-            check_timer();
-            // do not schedule now
+            if !replay::defer_tick() {
+                check_timer();
+                // do not schedule now, but at the next preemption point
+                tick_resched();
+            }
+            watchdog::tick(Some(trap_cx.sepc));
+            gdbstub::poll(trap_cx, false);
+            #[cfg(feature = "hardening")]
+            crate::hardening::trap_update(trap_cx);
+        }
+        Trap::Exception(Exception::Breakpoint) if gdbstub::breakpoint(trap_cx, false) => {
+            #[cfg(feature = "hardening")]
+            crate::hardening::trap_update(trap_cx);
+        }
+        Trap::Exception(
+            Exception::LoadPageFault | Exception::StorePageFault | Exception::InstructionPageFault,
+        ) if stval < MMAP_END => {
+            panic!(
+                "Kernel accessed user address {:#x} directly at {:#x}, not through UserPtr or UserSlice!",
+                stval, trap_cx.sepc
+            );
         }
         _ => {
             panic!(
@@ -164,6 +254,11 @@ pub fn trap_from_kernel(_trap_cx: &TrapContext) {
             );
         }
     }
+    #[cfg(feature = "kcov")]
+    crate::kcov::irq_leave(kcov_active);
+    #[cfg(feature = "hardening")]
+    crate::hardening::trap_leave(trap_cx);
+    trace(TraceEvent::TrapExit);
 }
 
 pub use context::TrapContext;
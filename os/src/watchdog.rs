@@ -0,0 +1,110 @@
+//! The watchdog, which tells of a hart stuck rather than letting the kernel
+//! hang silently: at each timer interrupt a hart checks that it has been
+//! through the scheduler lately, unless it is in user mode, and that the
+//! other harts have taken timer interrupts lately too.
+//!
+//! A hart looping in the kernel with interrupts on, say a task waiting for
+//! something which never comes without giving the processor up, is a soft
+//! lockup, reported with the task, where it is, and the last place which
+//! waited through wait_ready. A hart with interrupts off for good can only
+//! be told of by another hart.
+
+use crate::backtrace::{print_backtrace, symbolize};
+use crate::config::{MAX_HARTS, WATCHDOG_MS};
+use crate::mm::hart_id;
+use crate::task::current_task_name;
+use crate::timer::get_time_ms;
+use core::panic::Location;
+use core::ptr::null_mut;
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+
+const ZERO: AtomicUsize = AtomicUsize::new(0);
+const FALSE: AtomicBool = AtomicBool::new(false);
+const NOWHERE: AtomicPtr<Location<'static>> = AtomicPtr::new(null_mut());
+
+/// When each hart last took a timer interrupt, 0 if it never has.
+static LAST_TICK: [AtomicUsize; MAX_HARTS] = [ZERO; MAX_HARTS];
+/// When each hart was last in the scheduler.
+static LAST_SWITCH: [AtomicUsize; MAX_HARTS] = [ZERO; MAX_HARTS];
+/// The last wait_ready on each hart, and when.
+static LAST_POLL: [AtomicPtr<Location<'static>>; MAX_HARTS] = [NOWHERE; MAX_HARTS];
+static LAST_POLL_TIME: [AtomicUsize; MAX_HARTS] = [ZERO; MAX_HARTS];
+/// Whether each hart has been reported, soft locked up and taking no
+/// timer interrupts, until it recovers.
+static SOFT_REPORTED: [AtomicBool; MAX_HARTS] = [FALSE; MAX_HARTS];
+static HARD_REPORTED: [AtomicBool; MAX_HARTS] = [FALSE; MAX_HARTS];
+
+/// The hart is in the scheduler, and so not stuck.
+pub fn touch() {
+    let hart = hart_id();
+    LAST_SWITCH[hart].store(get_time_ms(), Ordering::Relaxed);
+    SOFT_REPORTED[hart].store(false, Ordering::Relaxed);
+}
+
+/// A wait at `site` polled.
+pub fn poll_site(site: &'static Location<'static>) {
+    let hart = hart_id();
+    LAST_POLL[hart].store(site as *const _ as *mut _, Ordering::Relaxed);
+    LAST_POLL_TIME[hart].store(get_time_ms(), Ordering::Relaxed);
+}
+
+/// Check the harts at a timer interrupt, from the kernel at `kernel_pc` or
+/// from user mode if None.
+pub fn tick(kernel_pc: Option<usize>) {
+    let hart = hart_id();
+    let now = get_time_ms();
+    LAST_TICK[hart].store(now, Ordering::Relaxed);
+    HARD_REPORTED[hart].store(false, Ordering::Relaxed);
+    for (other, last) in LAST_TICK.iter().enumerate() {
+        let last = last.load(Ordering::Relaxed);
+        if other != hart
+            && last != 0
+            && now - last.min(now) > WATCHDOG_MS
+            && !HARD_REPORTED[other].swap(true, Ordering::Relaxed)
+        {
+            error!(
+                "watchdog: hart {} has taken no timer interrupt for {} ms",
+                other,
+                now - last
+            );
+        }
+    }
+    // not before the scheduler has started
+    let last = LAST_SWITCH[hart].load(Ordering::Relaxed);
+    let stuck = now - last.min(now);
+    match kernel_pc {
+        Some(pc)
+            if last != 0
+                && stuck > WATCHDOG_MS
+                && !SOFT_REPORTED[hart].swap(true, Ordering::Relaxed) =>
+        {
+            report(hart, stuck, pc)
+        }
+        _ => {}
+    }
+}
+
+fn report(hart: usize, stuck: usize, pc: usize) {
+    let task = current_task_name().unwrap_or_else(|| "idle".into());
+    match symbolize(pc) {
+        Some((name, offset)) => error!(
+            "watchdog: soft lockup, hart {} stuck in the kernel for {} ms in {} at {}+{:#x}",
+            hart, stuck, task, name, offset
+        ),
+        None => error!(
+            "watchdog: soft lockup, hart {} stuck in the kernel for {} ms in {} at {:#x}",
+            hart, stuck, task, pc
+        ),
+    }
+    let site = LAST_POLL[hart].load(Ordering::Relaxed);
+    if !site.is_null() {
+        let site = unsafe { &*site };
+        error!(
+            "watchdog: last waited at {}:{}, {} ms ago",
+            site.file(),
+            site.line(),
+            get_time_ms() - LAST_POLL_TIME[hart].load(Ordering::Relaxed)
+        );
+    }
+    print_backtrace();
+}
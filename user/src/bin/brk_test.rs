@@ -0,0 +1,48 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use user_lib::{brk, exit, fork, sbrk, waitpid, PAGE_SIZE};
+
+/// Grow and shrink the heap by hand, then let the allocator grow it well
+/// past its initial arena.
+#[no_mangle]
+pub fn main() -> i32 {
+    let start = brk(0);
+    assert!(start > 0);
+    assert_eq!(sbrk(0), start);
+    assert_eq!(sbrk(2 * PAGE_SIZE as isize), start);
+    let heap = unsafe { core::slice::from_raw_parts_mut(start as *mut u8, 2 * PAGE_SIZE) };
+    // new pages read as zeros
+    assert!(heap.iter().all(|byte| *byte == 0));
+    heap.fill(0x5a);
+    assert_eq!(sbrk(-(PAGE_SIZE as isize)), start + 2 * PAGE_SIZE as isize);
+    assert_eq!(brk(0), start + PAGE_SIZE as isize);
+    assert_eq!(heap[PAGE_SIZE - 1], 0x5a);
+    // the page given back is gone
+    let pid = fork();
+    if pid == 0 {
+        heap[PAGE_SIZE] = 1;
+        exit(0);
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, -11);
+    // the break cannot move below the start of the heap
+    assert_eq!(brk(1), start + PAGE_SIZE as isize);
+    assert_eq!(brk(start as usize), start);
+
+    let mut v: Vec<usize> = Vec::new();
+    for i in 0..0x20000 {
+        v.push(i);
+    }
+    assert_eq!(v.iter().sum::<usize>(), 0x20000 * (0x20000 - 1) / 2);
+    assert!(brk(0) > start);
+    println!("brk_test passed!");
+    0
+}
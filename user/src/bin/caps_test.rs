@@ -0,0 +1,96 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    capget, capset, close, exec, exit, fork, kill, pipe, read, setuid, sleep, socket, waitpid,
+    write, AF_INET, CAP_KILL, CAP_MOUNT, CAP_NET, CAP_RAW_IO, EINVAL, EPERM, IPPROTO_ICMP,
+    SOCK_RAW,
+};
+
+const ALL: u32 = CAP_RAW_IO | CAP_NET | CAP_KILL | CAP_MOUNT;
+const USER: u32 = 1000;
+
+const CHILD_ARGS: [*const u8; 3] = [
+    "caps_test\0".as_ptr(),
+    "exec\0".as_ptr(),
+    core::ptr::null::<u8>(),
+];
+
+fn raw_socket() -> bool {
+    let fd = socket(AF_INET, SOCK_RAW, IPPROTO_ICMP);
+    if fd < 0 {
+        return false;
+    }
+    close(fd as usize);
+    true
+}
+
+/// A process of another user, alive until the caller has tried to signal
+/// it.
+fn other_user() -> isize {
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    let pid = fork();
+    if pid == 0 {
+        close(pipe_fd[0]);
+        assert_eq!(setuid(USER), 0);
+        // the capabilities go with root
+        assert_eq!(capget(), 0);
+        assert_eq!(capset(CAP_KILL), EPERM);
+        write(pipe_fd[1], b"x");
+        sleep(100);
+        exit(0);
+    }
+    close(pipe_fd[1]);
+    let mut buf = [0u8; 1];
+    assert_eq!(read(pipe_fd[0], &mut buf), 1);
+    close(pipe_fd[0]);
+    pid
+}
+
+/// Drop capabilities one by one, then exec.
+fn dropping() -> i32 {
+    assert!(raw_socket());
+    assert_eq!(capset(ALL & !CAP_NET), 0);
+    assert_eq!(capget(), ALL & !CAP_NET);
+    assert!(!raw_socket());
+    // none dropped comes back, nor are there others
+    assert_eq!(capset(ALL), EPERM);
+    assert_eq!(capset(1 << 31), EINVAL);
+
+    let pid = other_user();
+    assert_eq!(kill(pid as usize, 0), 0);
+    assert_eq!(capset(CAP_RAW_IO | CAP_MOUNT), 0);
+    assert_eq!(kill(pid as usize, 0), EPERM);
+    let mut exit_code = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+
+    // root keeps what it has left across exec
+    exec("caps_test\0", &CHILD_ARGS);
+    panic!("exec failed");
+}
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc > 1 && argv[1] == "exec" {
+        assert_eq!(capget(), CAP_RAW_IO | CAP_MOUNT);
+        assert!(!raw_socket());
+        return 0;
+    }
+    assert_eq!(capget(), ALL);
+    let pid = fork();
+    if pid == 0 {
+        exit(dropping());
+    }
+    let mut exit_code = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    // what a child dropped is still ours
+    assert_eq!(capget(), ALL);
+    println!("caps_test passed!");
+    0
+}
@@ -0,0 +1,51 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    clock_getres, clock_gettime, get_time, sleep, TimeSpec, CLOCK_MONOTONIC, CLOCK_REALTIME,
+    EINVAL,
+};
+
+fn now_ns(clockid: usize) -> u64 {
+    let mut tp = TimeSpec::default();
+    assert_eq!(clock_gettime(clockid, &mut tp), 0);
+    assert!((0..1_000_000_000).contains(&tp.tv_nsec));
+    tp.tv_sec as u64 * 1_000_000_000 + tp.tv_nsec as u64
+}
+
+/// The clocks count ns, never go back, and agree with get_time.
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut res = TimeSpec::default();
+    assert_eq!(clock_getres(CLOCK_MONOTONIC, Some(&mut res)), 0);
+    assert_eq!(res.tv_sec, 0);
+    assert!(res.tv_nsec > 0 && res.tv_nsec < 1_000_000, "{:?}", res);
+    assert_eq!(clock_getres(CLOCK_REALTIME, None), 0);
+    assert_eq!(clock_getres(42, Some(&mut res)), EINVAL);
+    assert_eq!(clock_gettime(42, &mut res), EINVAL);
+
+    // finer than a ms: of many readings in a row, some differ by less
+    let mut last = now_ns(CLOCK_MONOTONIC);
+    let mut fine = false;
+    for _ in 0..1000 {
+        let now = now_ns(CLOCK_MONOTONIC);
+        assert!(now >= last, "clock went back from {} to {}", last, now);
+        fine |= now > last && now - last < 1_000_000;
+        last = now;
+    }
+    assert!(fine);
+
+    let start_ns = now_ns(CLOCK_MONOTONIC);
+    let start_ms = get_time() as u64;
+    sleep(50);
+    let elapsed_ns = now_ns(CLOCK_REALTIME) - start_ns;
+    let elapsed_ms = get_time() as u64 - start_ms;
+    assert!(elapsed_ns >= 50_000_000, "slept {} ns", elapsed_ns);
+    // the same clock, read at slightly different times
+    assert!(elapsed_ns / 1_000_000 + 2 >= elapsed_ms && elapsed_ms + 2 >= elapsed_ns / 1_000_000);
+    println!("clock_test passed!");
+    0
+}
@@ -0,0 +1,136 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use core::ptr::{addr_of, addr_of_mut};
+use user_lib::{
+    close, exit, fork, fstat, open, pread, setrlimit, unlink, waitpid, OpenFlags, RLimit, Stat,
+    RLIMIT_CORE, RLIM_INFINITY, SIGSEGV,
+};
+
+const MARKER: &[u8; 16] = b"core dump marker";
+
+static mut DATA: [u8; 16] = [0; 16];
+
+fn read_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buf[offset], buf[offset + 1]])
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0u8; 4];
+    bytes.copy_from_slice(&buf[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+fn read_u64(buf: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&buf[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+/// A child which writes the marker, then faults with core dumps limited
+/// to `limit`; the path its core would be at.
+fn crash(limit: usize) -> String {
+    let pid = fork();
+    if pid == 0 {
+        setrlimit(
+            RLIMIT_CORE,
+            &RLimit {
+                rlim_cur: limit,
+                rlim_max: RLIM_INFINITY,
+            },
+        );
+        unsafe {
+            core::ptr::write_volatile(addr_of_mut!(DATA), *MARKER);
+            core::ptr::write_volatile(0x10 as *mut u8, 0);
+        }
+        exit(1);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, -(SIGSEGV as i32));
+    format!("core.{}", pid)
+}
+
+/// Nothing is dumped by default.
+fn no_core() {
+    let path = crash(0);
+    assert!(open(path.as_str(), OpenFlags::RDONLY) < 0);
+}
+
+/// The core has the signal, the registers, and the memory of the process.
+fn core() {
+    let path = crash(RLIM_INFINITY);
+    let fd = open(path.as_str(), OpenFlags::RDONLY);
+    assert!(fd >= 0);
+    let fd = fd as usize;
+    let mut header = [0u8; 64];
+    assert_eq!(pread(fd, &mut header, 0), 64);
+    assert_eq!(&header[..4], b"\x7fELF");
+    // ET_CORE, EM_RISCV
+    assert_eq!(read_u16(&header, 16), 4);
+    assert_eq!(read_u16(&header, 18), 243);
+    let phoff = read_u64(&header, 32) as usize;
+    let phnum = read_u16(&header, 56) as usize;
+    let mut marker_found = false;
+    let mut signal_found = false;
+    let data = addr_of!(DATA) as u64;
+    for i in 0..phnum {
+        let mut phdr = [0u8; 56];
+        assert_eq!(pread(fd, &mut phdr, phoff + i * 56), 56);
+        let offset = read_u64(&phdr, 8);
+        let vaddr = read_u64(&phdr, 16);
+        let filesz = read_u64(&phdr, 32);
+        match read_u32(&phdr, 0) {
+            // PT_NOTE: NT_PRPSINFO then the NT_PRSTATUS of the thread
+            4 => {
+                let mut notes = [0u8; 512];
+                assert!(pread(fd, &mut notes, offset as usize) > 0);
+                let prstatus = 12 + 8 + read_u32(&notes, 4) as usize;
+                assert_eq!(read_u32(&notes, prstatus + 8), 1);
+                // pr_cursig
+                assert_eq!(read_u16(&notes, prstatus + 20 + 12), SIGSEGV as u16);
+                signal_found = true;
+            }
+            // PT_LOAD
+            1 if vaddr <= data && data < vaddr + filesz => {
+                let mut buf = [0u8; 16];
+                let at = offset + data - vaddr;
+                assert_eq!(pread(fd, &mut buf, at as usize), 16);
+                assert_eq!(&buf, MARKER);
+                marker_found = true;
+            }
+            _ => {}
+        }
+    }
+    assert!(signal_found && marker_found);
+    close(fd);
+    assert_eq!(unlink(path.as_str()), 0);
+}
+
+/// A core is cut at RLIMIT_CORE.
+fn limited() {
+    let path = crash(4096);
+    let fd = open(path.as_str(), OpenFlags::RDONLY);
+    assert!(fd >= 0);
+    let mut stat = Stat::default();
+    assert_eq!(fstat(fd as usize, &mut stat), 0);
+    assert!(stat.size > 0 && stat.size <= 4096);
+    close(fd as usize);
+    assert_eq!(unlink(path.as_str()), 0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    no_core();
+    core();
+    limited();
+    println!("coredump_test passed!");
+    0
+}
@@ -0,0 +1,43 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, exit, fork, pipe, read, waitpid, write};
+
+const LEN: usize = 4 * 4096;
+static mut DATA: [u8; LEN] = [1; LEN];
+
+/// Parent and child write to the same pages after fork, by themselves and
+/// through the kernel, and must not see the writes of each other.
+#[no_mangle]
+pub fn main() -> i32 {
+    let data = unsafe { &mut DATA };
+    let mut pipe_fd = [0usize; 2];
+    pipe(&mut pipe_fd);
+    let pid = fork();
+    if pid == 0 {
+        close(pipe_fd[1]);
+        for byte in data.iter_mut().step_by(4096) {
+            *byte = 2;
+        }
+        // the kernel writes to the last page, still shared with the parent
+        assert_eq!(read(pipe_fd[0], &mut data[LEN - 3..]), 3);
+        assert!(data.iter().step_by(4096).all(|byte| *byte == 2));
+        assert_eq!(&data[LEN - 3..], b"cow");
+        close(pipe_fd[0]);
+        exit(0);
+    }
+    close(pipe_fd[0]);
+    data[0] = 3;
+    write(pipe_fd[1], b"cow");
+    close(pipe_fd[1]);
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(data[0], 3);
+    assert!(data[1..].iter().all(|byte| *byte == 1));
+    println!("cow_fork passed!");
+    0
+}
@@ -0,0 +1,101 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    chdir, close, exit, fork, getcwd, mkdir, open, read, rmdir, symlink, unlink, waitpid, write,
+    OpenFlags,
+};
+
+/// The working directory, without the trailing '\0'.
+fn cwd(buf: &mut [u8]) -> &str {
+    let len = getcwd(buf);
+    assert!(len > 0);
+    core::str::from_utf8(&buf[..len as usize - 1]).unwrap()
+}
+
+fn create(path: &str, data: &[u8]) {
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, data), data.len() as isize);
+    close(fd as usize);
+}
+
+/// What the file at `path` holds, if it exists.
+fn read_file<'a>(path: &str, buf: &'a mut [u8]) -> Option<&'a [u8]> {
+    let fd = open(path, OpenFlags::RDONLY);
+    if fd < 0 {
+        return None;
+    }
+    let len = read(fd as usize, buf) as usize;
+    close(fd as usize);
+    Some(&buf[..len])
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut path = [0u8; 64];
+    let mut buf = [0u8; 32];
+    assert_eq!(cwd(&mut path), "/");
+    assert_eq!(mkdir("cwd_test\0"), 0);
+    assert_eq!(mkdir("/cwd_test/dir\0"), 0);
+
+    // relative paths start at the working directory
+    assert_eq!(chdir("cwd_test/dir\0"), 0);
+    assert_eq!(cwd(&mut path), "/cwd_test/dir");
+    create("file\0", b"relative");
+    assert_eq!(
+        read_file("/cwd_test/dir/file\0", &mut buf),
+        Some(&b"relative"[..])
+    );
+    assert_eq!(
+        read_file("../dir/./file\0", &mut buf),
+        Some(&b"relative"[..])
+    );
+    assert_eq!(chdir("..\0"), 0);
+    assert_eq!(cwd(&mut path), "/cwd_test");
+    assert_eq!(chdir("dir/file\0"), -1);
+    assert_eq!(chdir("nowhere\0"), -1);
+    assert_eq!(getcwd(&mut path[..5]), -1);
+
+    // the working directory is a physical path
+    assert_eq!(symlink("/cwd_test/dir\0", "/cwd_test/link\0"), 0);
+    assert_eq!(chdir("link\0"), 0);
+    assert_eq!(cwd(&mut path), "/cwd_test/dir");
+    assert_eq!(chdir("..\0"), 0);
+    assert_eq!(cwd(&mut path), "/cwd_test");
+
+    // `..` leaves a mounted file system
+    assert_eq!(chdir("/tmp\0"), 0);
+    create("cwd_test\0", b"tmpfs");
+    assert_eq!(read_file("/tmp/cwd_test\0", &mut buf), Some(&b"tmpfs"[..]));
+    assert_eq!(unlink("cwd_test\0"), 0);
+    assert_eq!(chdir("..\0"), 0);
+    assert_eq!(cwd(&mut path), "/");
+    assert_eq!(chdir("..\0"), 0);
+    assert_eq!(cwd(&mut path), "/");
+
+    // inherited by fork, but changed by each process alone
+    assert_eq!(chdir("cwd_test\0"), 0);
+    let pid = fork();
+    if pid == 0 {
+        let mut path = [0u8; 64];
+        assert_eq!(cwd(&mut path), "/cwd_test");
+        assert_eq!(chdir("dir\0"), 0);
+        exit(0);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(cwd(&mut path), "/cwd_test");
+
+    assert_eq!(unlink("link\0"), 0);
+    assert_eq!(unlink("dir/file\0"), 0);
+    assert_eq!(rmdir("dir\0"), 0);
+    assert_eq!(chdir("/\0"), 0);
+    assert_eq!(rmdir("cwd_test\0"), 0);
+    println!("cwd_test passed!");
+    0
+}
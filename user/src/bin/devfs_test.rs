@@ -0,0 +1,72 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fstat, ioctl, mmap, munmap, open, read, stat, write};
+use user_lib::{OpenFlags, Stat, EAGAIN, MAP_PRIVATE, PROT_READ, PROT_WRITE, S_IFCHR, S_IFMT};
+
+const FBIOGET_VSCREENINFO: usize = 0x4600;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let null = open("/dev/null\0", OpenFlags::RDWR);
+    assert!(null >= 0);
+    let null = null as usize;
+    let mut buf = [0xffu8; 64];
+    assert_eq!(write(null, b"gone"), 4);
+    assert_eq!(read(null, &mut buf), 0);
+    let mut st = Stat::default();
+    assert_eq!(fstat(null, &mut st), 0);
+    assert_eq!(st.mode & S_IFMT, S_IFCHR);
+    close(null);
+
+    let zero = open("/dev/zero\0", OpenFlags::RDONLY);
+    assert!(zero >= 0);
+    let zero = zero as usize;
+    assert_eq!(read(zero, &mut buf), buf.len() as isize);
+    assert!(buf.iter().all(|b| *b == 0));
+    // a private mapping of /dev/zero is plain zeroed memory
+    let addr = mmap(0, 4096, PROT_READ | PROT_WRITE, MAP_PRIVATE, zero, 0);
+    assert!(addr > 0);
+    let page = unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, 4096) };
+    assert!(page.iter().all(|b| *b == 0));
+    page[0] = 1;
+    assert_eq!(munmap(addr as usize, 4096), 0);
+    close(zero);
+
+    let urandom = open("/dev/urandom\0", OpenFlags::RDONLY);
+    assert!(urandom >= 0);
+    let mut other = [0u8; 64];
+    assert_eq!(read(urandom as usize, &mut buf), buf.len() as isize);
+    assert_eq!(read(urandom as usize, &mut other), other.len() as isize);
+    assert_ne!(buf, other);
+    close(urandom as usize);
+
+    // nothing was pressed, and nothing is waited for
+    let event = open(
+        "/dev/input/event0\0",
+        OpenFlags::RDONLY | OpenFlags::NONBLOCK,
+    );
+    assert!(event >= 0);
+    assert_eq!(read(event as usize, &mut buf), EAGAIN);
+    close(event as usize);
+
+    let fb = open("/dev/fb0\0", OpenFlags::RDWR);
+    assert!(fb >= 0);
+    // struct fb_var_screeninfo, which starts with xres and yres
+    let mut info = [0u32; 40];
+    assert_eq!(
+        ioctl(fb as usize, FBIOGET_VSCREENINFO, info.as_mut_ptr() as usize),
+        0
+    );
+    assert!(info[0] > 0 && info[1] > 0);
+    assert_eq!(ioctl(fb as usize, 0x1234, 0), -1);
+    close(fb as usize);
+
+    assert_eq!(stat("/dev/console\0", &mut st), 0);
+    assert_eq!(st.mode & S_IFMT, S_IFCHR);
+    println!("devfs_test passed!");
+    0
+}
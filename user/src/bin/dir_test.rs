@@ -0,0 +1,68 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, mkdir, open, read, rmdir, unlink, write, OpenFlags};
+
+fn read_file(path: &str) -> bool {
+    let fd = open(path, OpenFlags::RDONLY);
+    if fd < 0 {
+        return false;
+    }
+    let mut buf = [0u8; 16];
+    let len = read(fd as usize, &mut buf) as usize;
+    close(fd as usize);
+    &buf[..len] == b"nested"
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(mkdir("/dir_test\0"), 0);
+    assert_eq!(mkdir("/dir_test\0"), -1);
+    assert_eq!(mkdir("/dir_test/a\0"), 0);
+    assert_eq!(mkdir("/dir_test/a/b\0"), 0);
+    assert_eq!(mkdir("/dir_test/missing/b\0"), -1);
+    assert_eq!(mkdir("/dir_test/a_name_longer_than_the_limit\0"), -1);
+
+    let fd = open(
+        "/dir_test/a/b/file\0",
+        OpenFlags::CREATE | OpenFlags::WRONLY,
+    );
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, b"nested"), 6);
+    close(fd as usize);
+    assert!(read_file("/dir_test/a/b/file\0"));
+    assert!(read_file("dir_test/./a/../a/b/file\0"));
+    assert!(read_file("/dir_test/a/b/../../a/b/file\0"));
+    // `..` of the root is the root
+    assert!(read_file("/../../dir_test/a/b/file\0"));
+    // a file is no directory
+    assert!(!read_file("/dir_test/a/b/file/../file\0"));
+    assert_eq!(mkdir("/dir_test/a/b/file/c\0"), -1);
+    // directories are not written to
+    assert_eq!(open("/dir_test/a\0", OpenFlags::WRONLY), -1);
+    let fd = open("/dir_test/a\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+
+    // only empty directories go away
+    assert_eq!(rmdir("/dir_test/a\0"), -1);
+    assert_eq!(rmdir("/dir_test/a/b/file\0"), -1);
+    assert_eq!(rmdir("/dir_test/a/.\0"), -1);
+    assert_eq!(mkdir("/dir_test/a/c\0"), 0);
+    assert_eq!(rmdir("/dir_test/a/c\0"), 0);
+    assert_eq!(rmdir("/dir_test/a/c\0"), -1);
+    // the slot of the removed entry is taken again
+    assert_eq!(mkdir("/dir_test/a/d\0"), 0);
+    assert_eq!(rmdir("/dir_test/a/d/\0"), 0);
+    assert!(read_file("/dir_test/a/b/file\0"));
+
+    assert_eq!(unlink("/dir_test/a/b/file\0"), 0);
+    assert_eq!(rmdir("/dir_test/a/b\0"), 0);
+    assert_eq!(rmdir("/dir_test/a\0"), 0);
+    assert_eq!(rmdir("/dir_test\0"), 0);
+    println!("dir_test passed!");
+    0
+}
@@ -0,0 +1,66 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec;
+use user_lib::{assert_root_only, close, dmesg, open, read, set_console_loglevel};
+use user_lib::{set_log_filter, syslog, OpenFlags, EINVAL};
+use user_lib::{SYSLOG_ACTION_READ, SYSLOG_ACTION_SIZE_BUFFER, SYSLOG_ACTION_SIZE_UNREAD};
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let size = syslog(SYSLOG_ACTION_SIZE_BUFFER, &mut []);
+    assert_eq!(size, 65536);
+    let mut buf = vec![0u8; size as usize];
+    let len = dmesg(&mut buf);
+    assert!(len > 0);
+    let log = core::str::from_utf8(&buf[..len as usize]).unwrap();
+    assert!(log.contains("INFO "));
+    assert!(log.contains("init trap"));
+
+    // the last bytes only
+    let mut tail = [0u8; 16];
+    assert_eq!(dmesg(&mut tail), 16);
+    assert_eq!(tail[15], b'\n');
+
+    // reads take what they read
+    let unread = syslog(SYSLOG_ACTION_SIZE_UNREAD, &mut []);
+    assert!(unread > 0);
+    let mut taken = vec![0u8; unread as usize];
+    assert_eq!(syslog(SYSLOG_ACTION_READ, &mut taken), unread);
+    assert!(syslog(SYSLOG_ACTION_SIZE_UNREAD, &mut []) < unread);
+
+    // the same lines in /proc/kmsg
+    let fd = open("/proc/kmsg\0", OpenFlags::RDONLY);
+    assert!(fd >= 0);
+    let mut text = String::new();
+    let mut piece = [0u8; 256];
+    loop {
+        let n = read(fd as usize, &mut piece);
+        if n <= 0 {
+            break;
+        }
+        text.push_str(core::str::from_utf8(&piece[..n as usize]).unwrap());
+    }
+    close(fd as usize);
+    assert!(text.contains("init trap"));
+
+    assert_eq!(set_log_filter("info,net=loud"), EINVAL);
+    assert_eq!(set_log_filter("info,net=debug"), 0);
+    assert_eq!(set_log_filter("info"), 0);
+    assert_eq!(set_console_loglevel(0), EINVAL);
+    assert_eq!(set_console_loglevel(7), 0);
+
+    // anyone may read the log, only root may change it
+    assert_root_only(|| {
+        assert!(dmesg(&mut tail) > 0);
+        syslog(SYSLOG_ACTION_READ, &mut tail)
+    });
+    assert_root_only(|| set_log_filter("trace"));
+    println!("dmesg_test passed!");
+    0
+}
@@ -0,0 +1,59 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, dup, dup2, dup3, fcntl, open, pipe, read, unlink};
+use user_lib::{OpenFlags, FD_CLOEXEC, F_GETFD};
+
+const PATH: &str = "/dup_test_file\0";
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // stdout goes to the file for a while
+    let saved = dup(1);
+    assert!(saved > 0);
+    let saved = saved as usize;
+    let fd = open(
+        PATH,
+        OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::WRONLY,
+    );
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(dup2(fd, 1), 1);
+    close(fd);
+    print!("redirected");
+    assert_eq!(dup2(saved, 1), 1);
+    close(saved);
+    let fd = open(PATH, OpenFlags::RDONLY) as usize;
+    let mut buf = [0u8; 16];
+    assert_eq!(read(fd, &mut buf), 10);
+    assert_eq!(&buf[..10], b"redirected");
+
+    // the target is closed first: here the only write end of a pipe
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    assert_eq!(dup2(fd, pipe_fd[1]), pipe_fd[1] as isize);
+    assert_eq!(read(pipe_fd[0], &mut buf), 0);
+    close(pipe_fd[0]);
+    close(pipe_fd[1]);
+
+    assert_eq!(dup2(fd, fd), fd as isize);
+    assert_eq!(dup3(fd, fd, OpenFlags::empty()), -1);
+    assert_eq!(dup3(fd, 30, OpenFlags::CLOEXEC), 30);
+    assert_eq!(fcntl(30, F_GETFD, 0), FD_CLOEXEC as isize);
+    // dup2 over it drops the flag again
+    assert_eq!(dup2(fd, 30), 30);
+    assert_eq!(fcntl(30, F_GETFD, 0), 0);
+    assert_eq!(dup3(fd, 31, OpenFlags::APPEND), -1);
+    close(30);
+    assert_eq!(dup2(30, 31), -1);
+    assert_eq!(dup2(30, 30), -1);
+    // beyond RLIMIT_NOFILE
+    assert_eq!(dup2(fd, 4096), -1);
+    close(fd);
+    assert_eq!(unlink(PATH), 0);
+    println!("dup_test passed!");
+    0
+}
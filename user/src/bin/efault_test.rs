@@ -0,0 +1,69 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::slice;
+use user_lib::{close, mmap, mprotect, munmap, open, pipe, read, write, OpenFlags};
+use user_lib::{MAP_ANONYMOUS, MAP_FIXED, MAP_PRIVATE, PROT_NONE, PROT_READ, PROT_WRITE};
+use user_lib::{EFAULT, PAGE_SIZE};
+
+/// Below any image, and so never mapped.
+const UNMAPPED: usize = 0x1000;
+/// The trampoline, mapped but not for the user.
+const KERNEL_ONLY: usize = usize::MAX - PAGE_SIZE + 1;
+/// Where the kernel image is, which the user cannot map.
+const KERNEL_IMAGE: usize = 0x8020_0000;
+
+fn bytes(addr: usize, len: usize) -> &'static mut [u8] {
+    unsafe { slice::from_raw_parts_mut(addr as *mut u8, len) }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(write(1, bytes(UNMAPPED, 16)), EFAULT);
+    assert_eq!(write(1, bytes(KERNEL_ONLY, 16)), EFAULT);
+    assert_eq!(write(1, bytes(KERNEL_IMAGE, 16)), EFAULT);
+    let fixed = mmap(
+        KERNEL_IMAGE,
+        PAGE_SIZE,
+        PROT_READ | PROT_WRITE,
+        MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED,
+        0,
+        0,
+    );
+    assert_eq!(fixed, -1);
+    let path = unsafe { core::str::from_utf8_unchecked(bytes(KERNEL_ONLY, 1)) };
+    assert_eq!(open(path, OpenFlags::RDONLY), EFAULT);
+    let bad_fds = unsafe { slice::from_raw_parts_mut(UNMAPPED as *mut usize, 2) };
+    assert_eq!(pipe(bad_fds), EFAULT);
+
+    let addr = mmap(
+        0,
+        2 * PAGE_SIZE,
+        PROT_READ | PROT_WRITE,
+        MAP_PRIVATE | MAP_ANONYMOUS,
+        0,
+        0,
+    ) as usize;
+    bytes(addr, PAGE_SIZE).fill(1);
+    // a buffer running into an inaccessible page is refused as a whole
+    assert_eq!(mprotect(addr + PAGE_SIZE, PAGE_SIZE, PROT_NONE), 0);
+    assert_eq!(write(1, bytes(addr + PAGE_SIZE - 8, 16)), EFAULT);
+    // read-only memory cannot be read into
+    assert_eq!(mprotect(addr, PAGE_SIZE, PROT_READ), 0);
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    assert_eq!(write(pipe_fd[1], b"efault"), 6);
+    assert_eq!(read(pipe_fd[0], bytes(addr, 6)), EFAULT);
+    assert_eq!(munmap(addr, 2 * PAGE_SIZE), 0);
+    // nothing was consumed by the failed read
+    let mut buf = [0u8; 6];
+    assert_eq!(read(pipe_fd[0], &mut buf), 6);
+    assert_eq!(&buf, b"efault");
+    close(pipe_fd[0]);
+    close(pipe_fd[1]);
+    println!("efault_test passed!");
+    0
+}
@@ -0,0 +1,76 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, eventfd, eventfd_read, eventfd_write, exit, fork, poll, read, sleep, waitpid, write,
+    PollFd, EAGAIN, EFD_NONBLOCK, EFD_SEMAPHORE, POLLIN, POLLOUT,
+};
+
+fn wait_child(pid: isize) {
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+}
+
+fn counter() {
+    let fd = eventfd(3, EFD_NONBLOCK);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let mut fds = [PollFd::new(fd, POLLIN | POLLOUT)];
+    assert_eq!(poll(&mut fds, 0), 1);
+    assert_eq!(fds[0].revents, POLLIN | POLLOUT);
+    // writes add up, a read takes all
+    assert_eq!(eventfd_write(fd, 4), 8);
+    assert_eq!(eventfd_read(fd), Some(7));
+    assert_eq!(read(fd, &mut [0u8; 8]), EAGAIN);
+    assert_eq!(poll(&mut fds, 0), 1);
+    assert_eq!(fds[0].revents, POLLOUT);
+    // only whole values, and not u64::MAX
+    assert_eq!(write(fd, &[1u8; 4]), 0);
+    assert_eq!(eventfd_write(fd, u64::MAX), 0);
+    // nor more than the count holds
+    assert_eq!(eventfd_write(fd, u64::MAX - 1), 8);
+    assert_eq!(eventfd_write(fd, 1), EAGAIN);
+    assert_eq!(eventfd_read(fd), Some(u64::MAX - 1));
+    close(fd);
+}
+
+fn semaphore() {
+    let fd = eventfd(2, EFD_SEMAPHORE | EFD_NONBLOCK);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(eventfd_read(fd), Some(1));
+    assert_eq!(eventfd_read(fd), Some(1));
+    assert_eq!(read(fd, &mut [0u8; 8]), EAGAIN);
+    close(fd);
+}
+
+/// A process waiting in read is woken by another which writes.
+fn wakeup() {
+    let fd = eventfd(0, 0);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(eventfd_read(fd), Some(5));
+        assert_eq!(eventfd_write(fd, 1), 8);
+        exit(0);
+    }
+    sleep(30);
+    assert_eq!(eventfd_write(fd, 5), 8);
+    wait_child(pid);
+    assert_eq!(eventfd_read(fd), Some(1));
+    close(fd);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    counter();
+    semaphore();
+    wakeup();
+    println!("eventfd_test passed!");
+    0
+}
@@ -0,0 +1,54 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    chmod, close, execve, fork, getenv, mkdir, open, rmdir, symlink, unlink, waitpid, write,
+    OpenFlags,
+};
+
+const SCRIPT: &str = "shebang_script\0";
+/// The interpreter, reached through a directory and a link to it.
+const INTERP: &str = "/shebang_bin/exec_shebang\0";
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc > 1 {
+        // run as the interpreter: exec_shebang interp_arg shebang_script user_arg
+        assert_eq!(argc, 4);
+        assert_eq!(argv[0], "/shebang_bin/exec_shebang");
+        assert_eq!(argv[1], "interp_arg");
+        assert_eq!(argv[2], "shebang_script");
+        assert_eq!(argv[3], "user_arg");
+        assert_eq!(getenv("SHEBANG"), Some("1"));
+        return 0;
+    }
+    assert_eq!(mkdir("/shebang_bin\0"), 0);
+    assert_eq!(symlink("/exec_shebang\0", INTERP), 0);
+    let fd = open(SCRIPT, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    write(fd, b"#!/shebang_bin/exec_shebang interp_arg\n");
+    close(fd);
+    assert_eq!(chmod(SCRIPT, 0o755), 0);
+    let pid = fork();
+    if pid == 0 {
+        let args = [
+            SCRIPT.as_ptr(),
+            "user_arg\0".as_ptr(),
+            core::ptr::null::<u8>(),
+        ];
+        let envp = ["SHEBANG=1\0".as_ptr(), core::ptr::null::<u8>()];
+        execve(SCRIPT, &args, &envp);
+        panic!("unreachable!");
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(unlink(INTERP), 0);
+    assert_eq!(rmdir("/shebang_bin\0"), 0);
+    println!("exec_shebang passed!");
+    0
+}
@@ -0,0 +1,84 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, link, mkdir, mount, open, pread, read, rename, rmdir, stat, umount};
+use user_lib::{unlink, write, OpenFlags, Stat};
+
+const LONG_NAME: &str = "/fat/A rather long file name.text\0";
+
+fn fill(buf: &mut [u8]) {
+    for (i, b) in buf.iter_mut().enumerate() {
+        *b = (i % 251) as u8;
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    if stat("/fat\0", &mut Stat::default()) != 0 {
+        assert_eq!(mkdir("/fat\0"), 0);
+    }
+    // the root disk is not FAT
+    assert_eq!(mount("/dev/vda\0", "/fat\0", "vfat\0"), -1);
+    assert_eq!(mount("/dev/vdb\0", "/fat\0", "vfat\0"), 0);
+
+    // a file over several clusters, under a long name
+    let mut data = [0u8; 3000];
+    fill(&mut data);
+    let fd = open(
+        LONG_NAME,
+        OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::TRUNC,
+    );
+    assert!(fd >= 0);
+    assert_eq!(write(fd as usize, &data), data.len() as isize);
+    close(fd as usize);
+    let mut st = Stat::default();
+    assert_eq!(stat(LONG_NAME, &mut st), 0);
+    assert!(st.is_file());
+    assert_eq!(st.size as usize, data.len());
+    // names are found whatever their case
+    assert_eq!(stat("/fat/A RATHER LONG FILE NAME.TEXT\0", &mut st), 0);
+    assert_eq!(link(LONG_NAME, "/fat/other\0"), -1);
+
+    // directories and renames
+    assert_eq!(mkdir("/fat/Sub Dir\0"), 0);
+    assert_eq!(mkdir("/fat/Sub Dir\0"), -1);
+    assert_eq!(rename(LONG_NAME, "/fat/Sub Dir/moved.txt\0"), 0);
+    assert_eq!(stat(LONG_NAME, &mut st), -1);
+    assert_eq!(rmdir("/fat/Sub Dir\0"), -1);
+    assert_eq!(mkdir("/fat/Sub Dir/inner\0"), 0);
+    assert_eq!(rename("/fat/Sub Dir/inner\0", "/fat/inner\0"), 0);
+    assert_eq!(stat("/fat/inner/..\0", &mut st), 0);
+
+    // all of it is on the disk
+    assert_eq!(umount("/fat\0"), 0);
+    assert_eq!(stat("/fat/inner\0", &mut st), -1);
+    assert_eq!(mount("/dev/vdb\0", "/fat\0", "vfat\0"), 0);
+    let fd = open("/fat/Sub Dir/moved.txt\0", OpenFlags::RDONLY);
+    assert!(fd >= 0);
+    let mut buf = [0u8; 3000];
+    let mut len = 0;
+    while len < buf.len() {
+        let n = read(fd as usize, &mut buf[len..]);
+        assert!(n > 0);
+        len += n as usize;
+    }
+    assert_eq!(read(fd as usize, &mut buf[..1]), 0);
+    assert!(buf == data);
+    let mut piece = [0u8; 4];
+    assert_eq!(pread(fd as usize, &mut piece, 1024), 4);
+    assert_eq!(piece, data[1024..1028]);
+    close(fd as usize);
+    assert_eq!(stat("/fat/inner\0", &mut st), 0);
+    assert!(st.is_dir());
+
+    assert_eq!(unlink("/fat/Sub Dir/moved.txt\0"), 0);
+    assert_eq!(rmdir("/fat/Sub Dir\0"), 0);
+    assert_eq!(rmdir("/fat/inner\0"), 0);
+    assert_eq!(umount("/fat\0"), 0);
+    assert_eq!(rmdir("/fat\0"), 0);
+    println!("fat_test passed!");
+    0
+}
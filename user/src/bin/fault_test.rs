@@ -0,0 +1,112 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{assert_root_only, bind, close, fault_inject, fsync, get_time, mlock, mmap};
+use user_lib::{munlock, open, poll, read, recvfrom, sendto, socket, unlink, write};
+use user_lib::{OpenFlags, PollFd, SockAddrIn, AF_INET, EINVAL, ENOMEM, ENOSYS, POLLIN};
+use user_lib::{FAULT_ALLOC, FAULT_BLOCK, FAULT_HEAP, FAULT_PACKET, MAP_ANONYMOUS, MAP_PRIVATE};
+use user_lib::{PAGE_SIZE, PROT_READ, PROT_WRITE, SOCK_DGRAM};
+
+/// Frames of user pages fail to be allocated, which mlock reports, while
+/// those of the page tables it needs do not.
+fn alloc() {
+    let addr = mmap(
+        0,
+        2 * PAGE_SIZE,
+        PROT_READ | PROT_WRITE,
+        MAP_PRIVATE | MAP_ANONYMOUS,
+        0,
+        0,
+    ) as usize;
+    assert_eq!(fault_inject(FAULT_ALLOC, 1, 0), 0);
+    assert_eq!(mlock(addr + PAGE_SIZE, PAGE_SIZE), -1);
+    assert!(fault_inject(FAULT_ALLOC, 0, 0) > 0);
+    assert_eq!(mlock(addr + PAGE_SIZE, PAGE_SIZE), 0);
+    assert_eq!(munlock(addr + PAGE_SIZE, PAGE_SIZE), 0);
+}
+
+/// Writes to the disk fail every other time and are tried again, then are
+/// slowed down.
+fn block() {
+    let path = "/fault_test_file\0";
+    let data = [0x5au8; 8192];
+    assert_eq!(fault_inject(FAULT_BLOCK, 2, 0), 0);
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::RDWR | OpenFlags::TRUNC) as usize;
+    assert_eq!(write(fd, &data), data.len() as isize);
+    assert_eq!(fsync(fd), 0);
+    close(fd);
+    assert!(fault_inject(FAULT_BLOCK, 0, 0) > 0);
+    let fd = open(path, OpenFlags::RDONLY) as usize;
+    let mut back = [0u8; 8192];
+    assert_eq!(read(fd, &mut back), back.len() as isize);
+    assert!(back == data);
+    close(fd);
+
+    let fd = open(path, OpenFlags::RDWR) as usize;
+    assert_eq!(write(fd, &data[..512]), 512);
+    assert_eq!(fault_inject(FAULT_BLOCK, 1, 20), 0);
+    let start = get_time();
+    assert_eq!(fsync(fd), 0);
+    assert!(get_time() - start >= 20);
+    assert!(fault_inject(FAULT_BLOCK, 0, 0) > 0);
+    close(fd);
+    unlink(path);
+}
+
+/// Every other packet is dropped, datagrams over the loopback among them.
+fn packet() {
+    let fd = socket(AF_INET, SOCK_DGRAM, 0) as usize;
+    let lo = SockAddrIn::new([127, 0, 0, 1], 5320);
+    assert_eq!(bind(fd, &lo), 0);
+    assert_eq!(fault_inject(FAULT_PACKET, 2, 0), 0);
+    let mut received = 0;
+    for i in 0..4u8 {
+        assert_eq!(sendto(fd, &[i], Some(&lo)), 1);
+    }
+    let mut buf = [0u8; 4];
+    loop {
+        let mut fds = [PollFd::new(fd, POLLIN)];
+        if poll(&mut fds, 200) != 1 {
+            break;
+        }
+        assert_eq!(recvfrom(fd, &mut buf, None), 1);
+        received += 1;
+    }
+    // the card may have had packets of its own meanwhile
+    assert!(fault_inject(FAULT_PACKET, 0, 0) >= 2);
+    assert!(received > 0 && received < 4);
+    close(fd);
+}
+
+/// The buffer of a datagram fails to be allocated, which sendto reports.
+fn heap() {
+    let fd = socket(AF_INET, SOCK_DGRAM, 0) as usize;
+    let lo = SockAddrIn::new([127, 0, 0, 1], 5321);
+    assert_eq!(fault_inject(FAULT_HEAP, 1, 0), 0);
+    assert_eq!(sendto(fd, b"heap", Some(&lo)), ENOMEM);
+    assert_eq!(fault_inject(FAULT_HEAP, 0, 0), 1);
+    assert_eq!(sendto(fd, b"heap", Some(&lo)), 4);
+    close(fd);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    if fault_inject(FAULT_ALLOC, 0, 0) == ENOSYS {
+        println!("fault_test: no fault injection, build the kernel with FEATURES=fault_inject");
+        return 0;
+    }
+    assert_eq!(fault_inject(4, 1, 0), EINVAL);
+    // only disks are delayed
+    assert_eq!(fault_inject(FAULT_PACKET, 1, 10), EINVAL);
+    assert_root_only(|| fault_inject(FAULT_ALLOC, 1, 0));
+
+    alloc();
+    block();
+    packet();
+    heap();
+    println!("fault_test passed!");
+    0
+}
@@ -0,0 +1,101 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, exec, fcntl, fork, open, pipe, read, unlink, waitpid, write};
+use user_lib::{OpenFlags, EAGAIN, FD_CLOEXEC, F_DUPFD, F_DUPFD_CLOEXEC, F_GETFD, F_GETFL};
+use user_lib::{F_SETFD, F_SETFL};
+
+const PATH: &str = "/fcntl_test_file\0";
+/// An fd well above the ones in use, for the exec'd test to look at.
+const KEPT_FD: usize = 20;
+const CLOSED_FD: usize = 21;
+const CHILD_ARGS: [*const u8; 3] = [
+    "fcntl_test\0".as_ptr(),
+    "child\0".as_ptr(),
+    core::ptr::null::<u8>(),
+];
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc > 1 && argv[1] == "child" {
+        // run by exec below
+        assert!(fcntl(KEPT_FD, F_GETFL, 0) >= 0);
+        assert_eq!(fcntl(CLOSED_FD, F_GETFL, 0), -1);
+        return 0;
+    }
+
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    let (rx, tx) = (pipe_fd[0], pipe_fd[1]);
+    assert_eq!(fcntl(rx, F_GETFL, 0), OpenFlags::RDONLY.bits() as isize);
+    assert_eq!(fcntl(tx, F_GETFL, 0), OpenFlags::WRONLY.bits() as isize);
+    assert_eq!(fcntl(rx, F_SETFL, OpenFlags::NONBLOCK.bits() as usize), 0);
+    assert_eq!(fcntl(rx, F_GETFL, 0), OpenFlags::NONBLOCK.bits() as isize);
+    let mut buf = [0u8; 8];
+    assert_eq!(read(rx, &mut buf), EAGAIN);
+    // what there is, without waiting for the rest
+    assert_eq!(write(tx, b"abc"), 3);
+    assert_eq!(read(rx, &mut buf), 3);
+    assert_eq!(&buf[..3], b"abc");
+    assert_eq!(read(rx, &mut buf), EAGAIN);
+    // the flag belongs to the open file, which the dup shares
+    let dup_fd = fcntl(rx, F_DUPFD, 10);
+    assert!(dup_fd >= 10);
+    assert_eq!(
+        fcntl(dup_fd as usize, F_GETFL, 0),
+        OpenFlags::NONBLOCK.bits() as isize
+    );
+    assert_eq!(fcntl(rx, F_SETFL, 0), 0);
+    assert_eq!(fcntl(dup_fd as usize, F_GETFL, 0), 0);
+    close(dup_fd as usize);
+    // with every writer gone, read returns the end of the file instead
+    close(tx);
+    assert_eq!(fcntl(rx, F_SETFL, OpenFlags::NONBLOCK.bits() as usize), 0);
+    assert_eq!(read(rx, &mut buf), 0);
+    close(rx);
+    assert_eq!(fcntl(rx, F_GETFL, 0), -1);
+
+    // O_APPEND can be turned on after open
+    let fd = open(PATH, OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::RDWR) as usize;
+    assert_eq!(write(fd, b"01234"), 5);
+    let other = open(PATH, OpenFlags::WRONLY) as usize;
+    assert_eq!(fcntl(other, F_SETFL, OpenFlags::APPEND.bits() as usize), 0);
+    assert_eq!(
+        fcntl(other, F_GETFL, 0),
+        (OpenFlags::WRONLY | OpenFlags::APPEND).bits() as isize
+    );
+    assert_eq!(write(other, b"56"), 2);
+    close(other);
+    close(fd);
+    let fd = open(PATH, OpenFlags::RDONLY) as usize;
+    assert_eq!(read(fd, &mut buf), 7);
+    assert_eq!(&buf[..7], b"0123456");
+    close(fd);
+
+    // close-on-exec is a flag of the fd, not of the file
+    let fd = open(PATH, OpenFlags::RDONLY | OpenFlags::CLOEXEC) as usize;
+    assert_eq!(fcntl(fd, F_GETFD, 0), FD_CLOEXEC as isize);
+    assert_eq!(fcntl(fd, F_SETFD, 0), 0);
+    assert_eq!(fcntl(fd, F_GETFD, 0), 0);
+    assert_eq!(fcntl(fd, F_DUPFD, KEPT_FD), KEPT_FD as isize);
+    assert_eq!(fcntl(fd, F_DUPFD_CLOEXEC, CLOSED_FD), CLOSED_FD as isize);
+    assert_eq!(fcntl(KEPT_FD, F_GETFD, 0), 0);
+    assert_eq!(fcntl(CLOSED_FD, F_GETFD, 0), FD_CLOEXEC as isize);
+    close(fd);
+    let pid = fork();
+    if pid == 0 {
+        exec("fcntl_test\0", &CHILD_ARGS);
+        panic!("exec failed");
+    }
+    let mut exit_code = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    close(KEPT_FD);
+    close(CLOSED_FD);
+    assert_eq!(unlink(PATH), 0);
+    println!("fcntl_test passed!");
+    0
+}
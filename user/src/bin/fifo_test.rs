@@ -0,0 +1,104 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit, fork, fstat, mkfifo, open, read, sleep, stat, unlink, waitpid, write, OpenFlags,
+    Stat,
+};
+
+fn wait_child(pid: isize) {
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+}
+
+/// Read `fd` to the end into `buf` and return how much came.
+fn read_all(fd: usize, buf: &mut [u8]) -> usize {
+    let mut len = 0;
+    loop {
+        let read_len = read(fd, &mut buf[len..]);
+        assert!(read_len >= 0);
+        if read_len == 0 {
+            return len;
+        }
+        len += read_len as usize;
+    }
+}
+
+fn check(path: &str) {
+    assert_eq!(mkfifo(path, 0o600), 0);
+    assert_eq!(mkfifo(path, 0o600), -1);
+    let mut st = Stat::default();
+    assert_eq!(stat(path, &mut st), 0);
+    assert!(st.is_fifo());
+    assert_eq!(st.mode & 0o777, 0o600);
+
+    // with O_NONBLOCK a writer needs a reader there, a reader goes on
+    assert_eq!(open(path, OpenFlags::WRONLY | OpenFlags::NONBLOCK), -1);
+    let reader = open(path, OpenFlags::RDONLY | OpenFlags::NONBLOCK);
+    assert!(reader > 0);
+    let reader = reader as usize;
+    let mut buf = [0u8; 32];
+    assert_eq!(read(reader, &mut buf), 0);
+    let writer = open(path, OpenFlags::WRONLY | OpenFlags::NONBLOCK);
+    assert!(writer > 0);
+    let writer = writer as usize;
+    assert_eq!(write(writer, b"hi"), 2);
+    assert_eq!(read(reader, &mut buf), 2);
+    assert_eq!(&buf[..2], b"hi");
+    let mut fifo_stat = Stat::default();
+    assert_eq!(fstat(writer, &mut fifo_stat), 0);
+    assert_eq!((fifo_stat.ino, fifo_stat.mode), (st.ino, st.mode));
+    close(writer);
+    close(reader);
+
+    // a writer waits for a reader
+    let pid = fork();
+    if pid == 0 {
+        let fd = open(path, OpenFlags::WRONLY);
+        assert!(fd > 0);
+        assert_eq!(write(fd as usize, b"hello, fifo"), 11);
+        close(fd as usize);
+        exit(0);
+    }
+    sleep(50);
+    let fd = open(path, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    assert_eq!(read_all(fd as usize, &mut buf), 11);
+    assert_eq!(&buf[..11], b"hello, fifo");
+    close(fd as usize);
+    wait_child(pid);
+
+    // and a reader for a writer, with more than the pipe holds at once
+    let pid = fork();
+    if pid == 0 {
+        sleep(50);
+        let fd = open(path, OpenFlags::WRONLY);
+        assert!(fd > 0);
+        for _ in 0..8 {
+            assert_eq!(write(fd as usize, b"0123456789"), 10);
+        }
+        close(fd as usize);
+        exit(0);
+    }
+    let fd = open(path, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut big = [0u8; 128];
+    assert_eq!(read_all(fd as usize, &mut big), 80);
+    assert!(big[..80].chunks(10).all(|chunk| chunk == b"0123456789"));
+    close(fd as usize);
+    wait_child(pid);
+
+    assert_eq!(unlink(path), 0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    check("/fifo_test_pipe\0");
+    check("/tmp/fifo_test_pipe\0");
+    println!("fifo_test passed!");
+    0
+}
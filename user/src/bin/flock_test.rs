@@ -0,0 +1,167 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit, fcntl_lock, flock, fork, getpid, open, pipe, read, sleep, unlink, waitpid, write,
+    Flock, OpenFlags, EAGAIN, EDEADLK,
+};
+use user_lib::{F_GETLK, F_RDLCK, F_SETLK, F_SETLKW, F_UNLCK, F_WRLCK};
+use user_lib::{LOCK_EX, LOCK_NB, LOCK_SH, LOCK_UN};
+
+const PATH: &str = "/flock_file\0";
+
+fn open_file() -> usize {
+    let fd = open(PATH, OpenFlags::RDWR);
+    assert!(fd > 0);
+    fd as usize
+}
+
+fn wait_child(pid: isize) {
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+}
+
+fn set_lock(fd: usize, cmd: usize, l_type: i16, start: i64, len: i64) -> isize {
+    fcntl_lock(fd, cmd, &mut Flock::new(l_type, start, len))
+}
+
+/// The lock in the way of a writing lock of the bytes from `start` on for
+/// `len`, F_UNLCK if none.
+fn get_lock(fd: usize, start: i64, len: i64) -> Flock {
+    let mut lock = Flock::new(F_WRLCK, start, len);
+    assert_eq!(fcntl_lock(fd, F_GETLK, &mut lock), 0);
+    lock
+}
+
+fn flocks() {
+    let fd = open_file();
+    assert_eq!(flock(fd, LOCK_EX), 0);
+    let pid = fork();
+    if pid == 0 {
+        // the same open file holds the lock already
+        assert_eq!(flock(fd, LOCK_EX | LOCK_NB), 0);
+        let other = open_file();
+        assert_eq!(flock(other, LOCK_EX | LOCK_NB), EAGAIN);
+        assert_eq!(flock(other, LOCK_SH | LOCK_NB), EAGAIN);
+        // until the parent unlocks
+        assert_eq!(flock(other, LOCK_SH), 0);
+        exit(0);
+    }
+    sleep(50);
+    assert_eq!(flock(fd, LOCK_UN), 0);
+    wait_child(pid);
+
+    // shared locks go together, and a lock held by a process which exits
+    // goes with it
+    assert_eq!(flock(fd, LOCK_SH), 0);
+    let pid = fork();
+    if pid == 0 {
+        let other = open_file();
+        assert_eq!(flock(other, LOCK_SH | LOCK_NB), 0);
+        assert_eq!(flock(other, LOCK_EX | LOCK_NB), EAGAIN);
+        // the parent's goes, then this one can be exclusive
+        assert_eq!(flock(other, LOCK_EX), 0);
+        sleep(50);
+        exit(0);
+    }
+    sleep(20);
+    assert_eq!(flock(fd, LOCK_UN), 0);
+    sleep(10);
+    assert_eq!(flock(fd, LOCK_SH | LOCK_NB), EAGAIN);
+    assert_eq!(flock(fd, LOCK_SH), 0);
+    wait_child(pid);
+    close(fd);
+}
+
+fn record_locks() {
+    let fd = open_file();
+    assert_eq!(set_lock(fd, F_SETLK, F_WRLCK, 0, 10), 0);
+    // a process never conflicts with itself
+    assert_eq!(get_lock(fd, 0, 10).l_type, F_UNLCK);
+    let parent = getpid();
+    let pid = fork();
+    if pid == 0 {
+        let lock = get_lock(fd, 5, 10);
+        assert_eq!(lock.l_type, F_WRLCK);
+        assert_eq!((lock.l_start, lock.l_len), (0, 10));
+        assert_eq!(lock.l_pid as isize, parent);
+        assert_eq!(get_lock(fd, 10, 0).l_type, F_UNLCK);
+        assert_eq!(set_lock(fd, F_SETLK, F_RDLCK, 10, 10), 0);
+        assert_eq!(set_lock(fd, F_SETLK, F_WRLCK, 5, 1), EAGAIN);
+        exit(0);
+    }
+    wait_child(pid);
+    // the locks of the child went when it exited
+    assert_eq!(set_lock(fd, F_SETLK, F_WRLCK, 10, 10), 0);
+    // unlocking the middle leaves the two ends
+    assert_eq!(set_lock(fd, F_SETLK, F_UNLCK, 4, 2), 0);
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(get_lock(fd, 4, 2).l_type, F_UNLCK);
+        let lock = get_lock(fd, 0, 5);
+        assert_eq!((lock.l_start, lock.l_len), (0, 4));
+        let lock = get_lock(fd, 5, 3);
+        assert_eq!((lock.l_start, lock.l_len), (6, 14));
+        exit(0);
+    }
+    wait_child(pid);
+
+    // closing any descriptor of the file drops the record locks on it
+    let other = open_file();
+    close(other);
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(get_lock(fd, 0, 0).l_type, F_UNLCK);
+        exit(0);
+    }
+    wait_child(pid);
+    close(fd);
+}
+
+fn deadlock() {
+    let fd = open_file();
+    assert_eq!(set_lock(fd, F_SETLK, F_WRLCK, 0, 10), 0);
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    let pid = fork();
+    if pid == 0 {
+        close(pipe_fd[0]);
+        assert_eq!(set_lock(fd, F_SETLK, F_WRLCK, 20, 10), 0);
+        assert_eq!(write(pipe_fd[1], b"x"), 1);
+        // waits for the parent, which then must not wait for this one
+        assert_eq!(set_lock(fd, F_SETLKW, F_WRLCK, 0, 10), 0);
+        exit(0);
+    }
+    close(pipe_fd[1]);
+    let mut buf = [0u8; 1];
+    assert_eq!(read(pipe_fd[0], &mut buf), 1);
+    close(pipe_fd[0]);
+    sleep(20);
+    assert_eq!(set_lock(fd, F_SETLKW, F_WRLCK, 20, 10), EDEADLK);
+    assert_eq!(set_lock(fd, F_SETLK, F_UNLCK, 0, 10), 0);
+    wait_child(pid);
+    // the child's locks are gone with it
+    assert_eq!(set_lock(fd, F_SETLKW, F_WRLCK, 0, 30), 0);
+    close(fd);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open(PATH, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, &[0u8; 64]), 64);
+    // reading locks need the file open for reading
+    assert_eq!(set_lock(fd as usize, F_SETLK, F_RDLCK, 0, 1), -1);
+    close(fd as usize);
+
+    flocks();
+    record_locks();
+    deadlock();
+    assert_eq!(unlink(PATH), 0);
+    println!("flock_test passed!");
+    0
+}
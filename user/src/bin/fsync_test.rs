@@ -0,0 +1,79 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fdatasync, fstat, fsync, open, pread, read, unlink, write};
+use user_lib::{OpenFlags, Stat};
+
+/// Spans a few pages, ending within one.
+const LEN: usize = 3 * 4096 + 100;
+
+fn byte(pos: usize) -> u8 {
+    (pos % 251) as u8
+}
+
+/// Check the contents of the file at `path` in pieces of 512 bytes.
+fn check(path: &str) {
+    let fd = open(path, OpenFlags::RDONLY) as usize;
+    let mut buf = [0u8; 512];
+    let mut pos = 0;
+    loop {
+        let len = read(fd, &mut buf) as usize;
+        if len == 0 {
+            break;
+        }
+        for (i, ch) in buf[..len].iter().enumerate() {
+            assert_eq!(*ch, byte(pos + i));
+        }
+        pos += len;
+    }
+    assert_eq!(pos, LEN);
+    close(fd);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let path = "/fsync_test_file\0";
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let mut chunk = [0u8; 1000];
+    let mut pos = 0;
+    while pos < LEN {
+        let len = chunk.len().min(LEN - pos);
+        for (i, ch) in chunk[..len].iter_mut().enumerate() {
+            *ch = byte(pos + i);
+        }
+        assert_eq!(write(fd, &chunk[..len]), len as isize);
+        pos += len;
+    }
+    // the cache knows the size before the disk does
+    let mut stat = Stat::default();
+    assert_eq!(fstat(fd, &mut stat), 0);
+    assert_eq!(stat.size, LEN as i64);
+    // across a page boundary
+    let mut buf = [0u8; 16];
+    assert_eq!(pread(fd, &mut buf, 4096 - 8), 16);
+    for (i, ch) in buf.iter().enumerate() {
+        assert_eq!(*ch, byte(4096 - 8 + i));
+    }
+    // another open file of the same inode sees the same pages
+    check(path);
+    assert_eq!(fsync(fd), 0);
+    assert_eq!(fdatasync(fd), 0);
+    close(fd);
+    assert_eq!(fsync(fd), -1);
+
+    // nobody has the file open, so this comes from the disk
+    check(path);
+    let fd = open(path, OpenFlags::RDWR | OpenFlags::TRUNC) as usize;
+    assert_eq!(fstat(fd, &mut stat), 0);
+    assert_eq!(stat.size, 0);
+    assert_eq!(read(fd, &mut buf), 0);
+    close(fd);
+    assert_eq!(unlink(path), 0);
+    println!("fsync_test passed!");
+    0
+}
@@ -0,0 +1,260 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::ptr::addr_of_mut;
+use user_lib::{close, exit, fork, get_time, getuid, kill, mmap, open, raw_syscall, setrlimit};
+use user_lib::{setuid, sleep, waitpid, waitpid_nb, Kcov, OpenFlags, RLimit};
+use user_lib::{MAP_ANONYMOUS, MAP_SHARED, PAGE_SIZE, PROT_READ, PROT_WRITE, RLIMIT_CORE, SIGKILL};
+
+/// Syscalls each child makes before the next takes over.
+const CALLS_PER_ROUND: usize = 200;
+/// How long a child may take before it is killed, in a wait the
+/// arguments made endless or in a hang.
+const ROUND_MS: isize = 3000;
+const CORPUS_SIZE: usize = 256;
+/// Bits for the edges of the kernel seen, hashed.
+const BITMAP_WORDS: usize = 1024;
+const KCOV_WORDS: usize = 1 << 16;
+const SCRATCH_SIZE: usize = 2 * PAGE_SIZE;
+/// Whom the children run as, lest they change what root alone may.
+const NOBODY: u32 = 65534;
+
+/// The syscalls of the kernel.
+const SYSCALLS: &[usize] = &[
+    17, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 32, 33, 34, 35, 36, 37, 38, 49, 53, 54, 56,
+    57, 59, 61, 62, 63, 64, 65, 66, 67, 68, 73, 74, 76, 78, 79, 80, 82, 83, 85, 86, 87, 95, 98,
+    101, 102, 103, 107, 108, 109, 110, 111, 113, 114, 115, 116, 124, 133, 134, 135, 136, 137, 144,
+    146, 154, 155, 169, 172, 174, 176, 180, 181, 182, 183, 185, 194, 195, 196, 197, 198, 199, 200,
+    201, 202, 203, 204, 205, 206, 207, 208, 209, 210, 211, 212, 214, 215, 222, 226, 228, 229, 230,
+    231, 233, 242, 260, 261, 410, 411, 420, 421, 430, 431, 432, 433, 434, 440, 441, 442, 443, 444,
+    446, 447, 1000, 1001, 1010, 1012, 1013, 1020, 1021, 1030, 1031, 1033, 1040, 2000, 2001,
+];
+// Not among them: exit, kill, tgkill, pidfd_send_signal, fork, exec and
+// spawn, which would leave the child or hit other processes; mount and
+// umount2; reboot and suspend; netbench; and mutex_lock, semaphore_down,
+// condvar_wait, waittid and event_get, which no signal ends, for a child to
+// hang in.
+
+/// Paths to pass, none of them to anything which matters.
+const PATHS: &[&str] = &[
+    "/tmp/fuzz\0",
+    "/tmp/fuzz/a\0",
+    "/tmp/fuzz-b\0",
+    "/dev/null\0",
+    "/proc/meminfo\0",
+    ".\0",
+    "\0",
+];
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Call {
+    id: usize,
+    args: [usize; 6],
+}
+
+/// What the children leave for the fuzzer, in memory they share.
+#[repr(C)]
+struct Shared {
+    bitmap: [u64; BITMAP_WORDS],
+    /// calls which covered new edges, to start from again
+    corpus: [Call; CORPUS_SIZE],
+    corpus_len: usize,
+    /// the call the child is in, to tell which one it is stuck in
+    current: Call,
+    calls: usize,
+}
+
+static mut SCRATCH: [u8; SCRATCH_SIZE] = [0; SCRATCH_SIZE];
+
+/// xorshift64*, enough to pick arguments by.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> usize {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d) as usize
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        self.next() % n
+    }
+}
+
+/// An argument, likely to be one the kernel has to refuse or treat with
+/// care: small numbers as fds, pointers at or running out of memory of
+/// ours, pointers at memory which is not, lengths and flags.
+fn arg(rng: &mut Rng) -> usize {
+    let scratch = unsafe { addr_of_mut!(SCRATCH) as usize };
+    match rng.below(12) {
+        0 => 0,
+        1 => rng.below(8),
+        2 => usize::MAX,
+        3 => scratch + rng.below(SCRATCH_SIZE),
+        4 => scratch + SCRATCH_SIZE - rng.below(16),
+        5 => PATHS[rng.below(PATHS.len())].as_ptr() as usize,
+        // never mapped, the kernel's image, and the kernel's direct map
+        6 => 0x1000,
+        7 => 0x8020_0000,
+        8 => 0xffff_ffc0_0000_0000,
+        9 => 1 << rng.below(64),
+        10 => rng.below(16) * PAGE_SIZE,
+        _ => rng.next(),
+    }
+}
+
+/// A call of a syscall of the corpus with an argument changed, or a new
+/// one.
+fn next_call(rng: &mut Rng, shared: &Shared) -> Call {
+    if shared.corpus_len > 0 && rng.below(2) == 0 {
+        let mut call = shared.corpus[rng.below(shared.corpus_len)];
+        let index = rng.below(6);
+        call.args[index] = match rng.below(2) {
+            0 => call.args[index] ^ (1 << rng.below(64)),
+            _ => arg(rng),
+        };
+        return call;
+    }
+    let id = match rng.below(32) {
+        // now and then one the kernel does not know
+        0 => rng.below(4096),
+        _ => SYSCALLS[rng.below(SYSCALLS.len())],
+    };
+    let mut args = [0; 6];
+    for a in args.iter_mut() {
+        *a = arg(rng);
+    }
+    Call { id, args }
+}
+
+/// Mark the edges `pcs` ran in the bitmap; how many it had not seen.
+fn cover(bitmap: &mut [u64; BITMAP_WORDS], pcs: &[usize]) -> usize {
+    let mut new = 0;
+    for &pc in pcs {
+        let hash = (pc as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 48;
+        let (word, bit) = (hash as usize / 64, hash % 64);
+        if bitmap[word] & (1 << bit) == 0 {
+            bitmap[word] |= 1 << bit;
+            new += 1;
+        }
+    }
+    new
+}
+
+/// Make `CALLS_PER_ROUND` syscalls, quietly and without dumping core
+/// should one of them kill it.
+fn child(shared: &mut Shared, seed: u64) -> ! {
+    for fd in 0..3 {
+        close(fd);
+        open("/dev/null\0", OpenFlags::RDWR);
+    }
+    let no_core = RLimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    setrlimit(RLIMIT_CORE, &no_core);
+    if getuid() == 0 {
+        setuid(NOBODY);
+    }
+    let kcov = Kcov::open(KCOV_WORDS);
+    let mut rng = Rng(seed | 1);
+    unsafe {
+        for byte in (*addr_of_mut!(SCRATCH)).iter_mut() {
+            *byte = rng.next() as u8;
+        }
+    }
+    for _ in 0..CALLS_PER_ROUND {
+        let call = next_call(&mut rng, shared);
+        shared.current = call;
+        if let Some(kcov) = &kcov {
+            kcov.reset();
+        }
+        raw_syscall(call.id, call.args);
+        shared.calls += 1;
+        if let Some(kcov) = &kcov {
+            if cover(&mut shared.bitmap, kcov.pcs()) > 0 && shared.corpus_len < CORPUS_SIZE {
+                shared.corpus[shared.corpus_len] = call;
+                shared.corpus_len += 1;
+            }
+        }
+    }
+    exit(0);
+}
+
+/// fuzz [ROUNDS [SEED]]: make random syscalls with arguments likely to be
+/// wrong in child after child, guided by the coverage of the kernel if it
+/// has /dev/kcov, for the kernel to survive; it is a crash of the kernel,
+/// or a child stuck in a syscall which should have ended, which tells of
+/// a bug, not a child killed.
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    let rounds: usize = match argc {
+        1 => 100,
+        _ => argv[1].parse().unwrap_or(100),
+    };
+    let seed = if argc > 2 {
+        argv[2].parse().unwrap_or(1)
+    } else {
+        get_time() as u64
+    };
+    println!("fuzz: {} rounds, seed {}", rounds, seed);
+    let size = (core::mem::size_of::<Shared>() + PAGE_SIZE - 1) / PAGE_SIZE * PAGE_SIZE;
+    let base = mmap(
+        0,
+        size,
+        PROT_READ | PROT_WRITE,
+        MAP_SHARED | MAP_ANONYMOUS,
+        0,
+        0,
+    );
+    if base < 0 {
+        println!("fuzz: cannot map memory");
+        return -1;
+    }
+    // zero, as fresh anonymous memory is
+    let shared = unsafe { &mut *(base as *mut Shared) };
+    let (mut killed, mut timed_out) = (0, 0);
+    for round in 0..rounds {
+        let pid = fork();
+        if pid == 0 {
+            child(shared, seed.wrapping_add(round as u64).wrapping_mul(0x9e37_79b9));
+        }
+        let deadline = get_time() + ROUND_MS;
+        let mut exit_code = 0;
+        while waitpid_nb(pid as usize, &mut exit_code) == -2 {
+            if get_time() > deadline {
+                println!(
+                    "fuzz: round {} timed out in syscall {} {:#x?}",
+                    round, shared.current.id, shared.current.args
+                );
+                kill(pid as usize, SIGKILL);
+                waitpid(pid as usize, &mut exit_code);
+                timed_out += 1;
+                exit_code = 0;
+                break;
+            }
+            sleep(10);
+        }
+        if exit_code < 0 {
+            killed += 1;
+        }
+        if (round + 1) % 10 == 0 || round + 1 == rounds {
+            let edges: u32 = shared.bitmap.iter().map(|word| word.count_ones()).sum();
+            println!(
+                "fuzz: {} rounds, {} calls, {} edges, corpus {}, {} killed, {} timed out",
+                round + 1,
+                shared.calls,
+                edges,
+                shared.corpus_len,
+                killed,
+                timed_out
+            );
+        }
+    }
+    0
+}
@@ -0,0 +1,147 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, dirents, getdents, lseek, mkdir, open, rmdir, stat, unlink, OpenFlags, Stat, DT_DIR,
+    DT_REG, EINVAL, SEEK_SET,
+};
+
+const FILES: usize = 12;
+
+fn path_of(buf: &mut [u8; 64], dir: &str, name: &str) -> usize {
+    let mut len = 0;
+    for part in [dir.as_bytes(), b"/", name.as_bytes()].iter() {
+        buf[len..len + part.len()].copy_from_slice(part);
+        len += part.len();
+    }
+    buf[len] = 0;
+    len + 1
+}
+
+fn file_name(buf: &mut [u8; 8], i: usize) -> &str {
+    buf[0] = b'f';
+    buf[1] = b'0' + (i / 10) as u8;
+    buf[2] = b'0' + (i % 10) as u8;
+    core::str::from_utf8(&buf[..3]).unwrap()
+}
+
+fn create(dir: &str, name: &str) {
+    let mut path = [0u8; 64];
+    let len = path_of(&mut path, dir, name);
+    let path = core::str::from_utf8(&path[..len]).unwrap();
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+}
+
+fn remove(dir: &str, name: &str) {
+    let mut path = [0u8; 64];
+    let len = path_of(&mut path, dir, name);
+    assert_eq!(unlink(core::str::from_utf8(&path[..len]).unwrap()), 0);
+}
+
+/// Iterate over `dir` a little at a time, removing and adding files on
+/// the way, and check that each file there all along is seen once.
+fn check(dir: &str) {
+    let mut dir_path = [0u8; 64];
+    dir_path[..dir.len()].copy_from_slice(dir.as_bytes());
+    let dir_path = core::str::from_utf8(&dir_path[..dir.len() + 1]).unwrap();
+    assert_eq!(mkdir(dir_path), 0);
+    let mut name = [0u8; 8];
+    for i in 0..FILES {
+        create(dir, file_name(&mut name, i));
+    }
+    create(dir, "sub");
+    let mut path = [0u8; 64];
+    let len = path_of(&mut path, dir, "sub");
+    let sub_path = core::str::from_utf8(&path[..len]).unwrap();
+    assert_eq!(unlink(sub_path), 0);
+    assert_eq!(mkdir(sub_path), 0);
+    let mut sub_stat = Stat::default();
+    assert_eq!(stat(sub_path, &mut sub_stat), 0);
+
+    let fd = open(dir_path, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    // too small for one entry
+    let mut buf = [0u8; 8];
+    assert_eq!(getdents(fd, &mut buf), EINVAL);
+
+    let mut seen = [0usize; FILES];
+    let mut sub_seen = 0;
+    let mut buf = [0u8; 64];
+    let mut rounds = 0;
+    loop {
+        let len = getdents(fd, &mut buf);
+        assert!(len >= 0);
+        if len == 0 {
+            break;
+        }
+        for entry in dirents(&buf[..len as usize]) {
+            if entry.name == "sub" {
+                assert_eq!(entry.type_, DT_DIR);
+                assert_eq!(entry.ino, sub_stat.ino);
+                sub_seen += 1;
+            } else if entry.name.starts_with('f') {
+                assert_eq!(entry.type_, DT_REG);
+                let i: usize = entry.name[1..].parse().unwrap();
+                if i < FILES {
+                    seen[i] += 1;
+                }
+            } else {
+                assert!(entry.name == "." || entry.name == "..");
+                assert_eq!(entry.type_, DT_DIR);
+            }
+        }
+        rounds += 1;
+        if rounds == 1 {
+            // one gone before being seen, and newcomers which may or may
+            // not be seen
+            remove(dir, file_name(&mut name, FILES - 1));
+            for i in FILES..FILES + 4 {
+                create(dir, file_name(&mut name, i));
+            }
+        }
+    }
+    assert!(rounds > 2);
+    assert_eq!(sub_seen, 1);
+    assert_eq!(seen[FILES - 1], 0);
+    assert!(seen[..FILES - 1].iter().all(|&count| count == 1));
+
+    // back to the start
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    let len = getdents(fd, &mut buf);
+    assert!(len > 0);
+    close(fd);
+
+    // getdents is for directories only
+    let mut file_path = [0u8; 64];
+    let len = path_of(&mut file_path, dir, "f00");
+    let fd = open(
+        core::str::from_utf8(&file_path[..len]).unwrap(),
+        OpenFlags::RDONLY,
+    );
+    assert!(fd > 0);
+    assert_eq!(getdents(fd as usize, &mut buf), -1);
+    close(fd as usize);
+
+    for i in 0..FILES - 1 {
+        remove(dir, file_name(&mut name, i));
+    }
+    for i in FILES..FILES + 4 {
+        remove(dir, file_name(&mut name, i));
+    }
+    assert_eq!(rmdir(sub_path), 0);
+    assert_eq!(rmdir(dir_path), 0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    check("/getdents_dir");
+    check("/tmp/getdents_dir");
+    println!("getdents_test passed!");
+    0
+}
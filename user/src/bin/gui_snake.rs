@@ -5,7 +5,7 @@ extern crate alloc;
 extern crate user_lib;
 
 use user_lib::console::getchar;
-use user_lib::{key_pressed, sleep, Display, VIRTGPU_XRES, VIRTGPU_YRES};
+use user_lib::{poll, Display, PollFd, POLLIN, VIRTGPU_XRES, VIRTGPU_YRES};
 
 use embedded_graphics::pixelcolor::*;
 use embedded_graphics::prelude::{Drawable, Point, RgbColor, Size};
@@ -331,7 +331,8 @@ pub fn main() -> i32 {
     let mut game = SnakeGame::<20, Rgb888>::new(1280, 800, 20, 20, Rgb888::RED, Rgb888::YELLOW, 50);
     let _ = disp.clear(Rgb888::BLACK).unwrap();
     loop {
-        if key_pressed() {
+        // a key, or 10ms for the next frame
+        if poll(&mut [PollFd::new(0, POLLIN)], 10) > 0 {
             let c = getchar();
             match c {
                 LF => break,
@@ -345,7 +346,6 @@ pub fn main() -> i32 {
         }
         let _ = disp.clear(Rgb888::BLACK).unwrap();
         game.draw(&mut disp);
-        sleep(10);
     }
     0
 }
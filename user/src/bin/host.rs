@@ -0,0 +1,29 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+// host NAME: the addresses of NAME, as the nameserver of eth0 tells
+
+use alloc::format;
+use user_lib::getaddrinfo;
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc != 2 {
+        println!("usage: host NAME");
+        return 2;
+    }
+    let mut addrs = [[0u8; 4]; 8];
+    let n = getaddrinfo(&format!("{}\0", argv[1]), &mut addrs);
+    if n < 0 {
+        println!("host: {}: error {}", argv[1], n);
+        return 1;
+    }
+    for [a, b, c, d] in &addrs[..(n as usize).min(addrs.len())] {
+        println!("{} has address {}.{}.{}.{}", argv[1], a, b, c, d);
+    }
+    0
+}
@@ -0,0 +1,93 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+// ifconfig: the setup of lo and eth0
+// ifconfig eth0 dhcp: have DHCP set up eth0
+// ifconfig eth0 ADDRESS/PREFIX [GATEWAY [NAMESERVER]]: give it an address
+// by hand
+
+use alloc::format;
+use user_lib::{ifconfig, ifconfig_dhcp, ifconfig_set, parse_ip, IfReq, DHCP_ASKING, DHCP_BOUND};
+
+fn show(name: &str) {
+    let mut req = IfReq::default();
+    if ifconfig(&format!("{}\0", name), &mut req) < 0 {
+        return;
+    }
+    let [a, b, c, d] = req.ip();
+    let prefix = u32::from_be_bytes(req.netmask()).count_ones();
+    print!("{:<6}inet {}.{}.{}.{}/{}", name, a, b, c, d, prefix);
+    if req.gateway != 0 {
+        let [a, b, c, d] = req.gateway();
+        print!(" gateway {}.{}.{}.{}", a, b, c, d);
+    }
+    match req.dhcp {
+        DHCP_ASKING => print!(" (dhcp asking)"),
+        DHCP_BOUND => print!(" (dhcp)"),
+        _ => {}
+    }
+    if req.nameserver != 0 {
+        let [a, b, c, d] = req.nameserver();
+        print!(" nameserver {}.{}.{}.{}", a, b, c, d);
+    }
+    let m = req.mac;
+    println!(
+        "\n      ether {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+        m[0], m[1], m[2], m[3], m[4], m[5]
+    );
+}
+
+/// The address and netmask of `text`, like 10.0.2.15/24.
+fn parse_cidr(text: &str) -> Option<([u8; 4], [u8; 4])> {
+    let (ip, prefix) = text.split_once('/')?;
+    let prefix: u32 = prefix.parse().ok().filter(|&prefix| prefix <= 32)?;
+    let netmask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+    Some((parse_ip(ip)?, netmask.to_be_bytes()))
+}
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc == 1 {
+        show("lo");
+        show("eth0");
+        return 0;
+    }
+    let name = format!("{}\0", argv[1]);
+    let ret = match argc {
+        3 if argv[2] == "dhcp" => ifconfig_dhcp(&name),
+        3..=5 => {
+            let gateway = match argc {
+                4 | 5 => parse_ip(argv[3]),
+                _ => Some([0; 4]),
+            };
+            let nameserver = match argc {
+                5 => parse_ip(argv[4]),
+                _ => Some([0; 4]),
+            };
+            match (parse_cidr(argv[2]), gateway, nameserver) {
+                (Some((ip, netmask)), Some(gateway), Some(nameserver)) => {
+                    let mut req = IfReq::new(ip, netmask, gateway);
+                    req.nameserver = u32::from_be_bytes(nameserver).to_be();
+                    ifconfig_set(&name, &req)
+                }
+                _ => {
+                    println!("ifconfig: bad address");
+                    return 2;
+                }
+            }
+        }
+        _ => {
+            println!("usage: ifconfig [eth0 dhcp | eth0 ADDRESS/PREFIX [GATEWAY [NAMESERVER]]]");
+            return 2;
+        }
+    };
+    if ret < 0 {
+        println!("ifconfig: {}: error {}", argv[1], ret);
+        return 1;
+    }
+    0
+}
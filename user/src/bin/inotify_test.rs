@@ -0,0 +1,134 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    chmod, close, exit, fork, inotify_add_watch, inotify_events, inotify_init1, inotify_rm_watch,
+    mkdir, open, read, rename, rmdir, sleep, unlink, waitpid, write, OpenFlags, EAGAIN, EINVAL,
+};
+use user_lib::{
+    IN_ALL_EVENTS, IN_ATTRIB, IN_CREATE, IN_DELETE, IN_DELETE_SELF, IN_IGNORED, IN_ISDIR,
+    IN_MODIFY, IN_MOVED_FROM, IN_MOVED_TO,
+};
+
+/// Read what is queued and check it is `expected`, in order, as (wd,
+/// mask, name); the two events of a rename must share a cookie.
+fn expect(fd: usize, expected: &[(isize, u32, &str)]) {
+    let mut buf = [0u8; 512];
+    let len = read(fd, &mut buf);
+    assert!(len > 0);
+    let mut count = 0;
+    let mut cookie = 0;
+    for event in inotify_events(&buf[..len as usize]) {
+        let (wd, mask, name) = expected[count];
+        assert_eq!(event.wd as isize, wd);
+        assert_eq!(event.mask, mask);
+        assert_eq!(event.name, name);
+        if mask == IN_MOVED_FROM {
+            assert_ne!(event.cookie, 0);
+            cookie = event.cookie;
+        } else if mask == IN_MOVED_TO {
+            assert_eq!(event.cookie, cookie);
+        }
+        count += 1;
+    }
+    assert_eq!(count, expected.len());
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = inotify_init1(OpenFlags::NONBLOCK);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let mut buf = [0u8; 64];
+    assert_eq!(read(fd, &mut buf), EAGAIN);
+
+    assert_eq!(mkdir("/inotify_dir\0"), 0);
+    let dir_wd = inotify_add_watch(fd, "/inotify_dir\0", IN_ALL_EVENTS);
+    assert!(dir_wd > 0);
+    assert_eq!(
+        inotify_add_watch(fd, "/inotify_nothing\0", IN_ALL_EVENTS),
+        -1
+    );
+
+    let file = open("/inotify_dir/a\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(file > 0);
+    let file = file as usize;
+    expect(fd, &[(dir_wd, IN_CREATE, "a")]);
+
+    // writes to a file are seen by a watch of the file, and one after
+    // another makes a single event
+    let file_wd = inotify_add_watch(fd, "/inotify_dir/a\0", IN_ALL_EVENTS);
+    assert!(file_wd > 0 && file_wd != dir_wd);
+    assert_eq!(write(file, b"hello"), 5);
+    assert_eq!(write(file, b" world"), 6);
+    close(file);
+    assert_eq!(chmod("/inotify_dir/a\0", 0o600), 0);
+    expect(fd, &[(file_wd, IN_MODIFY, ""), (file_wd, IN_ATTRIB, "")]);
+    // asking for less
+    assert_eq!(
+        inotify_add_watch(fd, "/inotify_dir/a\0", IN_DELETE_SELF),
+        file_wd
+    );
+    assert_eq!(chmod("/inotify_dir/a\0", 0o644), 0);
+    assert_eq!(read(fd, &mut buf), EAGAIN);
+
+    assert_eq!(rename("/inotify_dir/a\0", "/inotify_dir/b\0"), 0);
+    assert_eq!(mkdir("/inotify_dir/sub\0"), 0);
+    assert_eq!(rmdir("/inotify_dir/sub\0"), 0);
+    expect(
+        fd,
+        &[
+            (dir_wd, IN_MOVED_FROM, "a"),
+            (dir_wd, IN_MOVED_TO, "b"),
+            (dir_wd, IN_CREATE | IN_ISDIR, "sub"),
+            (dir_wd, IN_DELETE | IN_ISDIR, "sub"),
+        ],
+    );
+
+    // the last name of the file going ends its watch
+    assert_eq!(unlink("/inotify_dir/b\0"), 0);
+    expect(
+        fd,
+        &[
+            (dir_wd, IN_DELETE, "b"),
+            (file_wd, IN_DELETE_SELF, ""),
+            (file_wd, IN_IGNORED, ""),
+        ],
+    );
+    assert_eq!(inotify_rm_watch(fd, file_wd as i32), EINVAL);
+    assert_eq!(inotify_rm_watch(fd, dir_wd as i32), 0);
+    expect(fd, &[(dir_wd, IN_IGNORED, "")]);
+    assert_eq!(rmdir("/inotify_dir\0"), 0);
+    assert_eq!(read(fd, &mut buf), EAGAIN);
+    close(fd);
+
+    // a read waits for an event
+    let fd = inotify_init1(OpenFlags::empty());
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let tmp_wd = inotify_add_watch(fd, "/tmp\0", IN_CREATE | IN_DELETE);
+    assert!(tmp_wd > 0);
+    let pid = fork();
+    if pid == 0 {
+        sleep(50);
+        let file = open("/tmp/inotify_file\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+        assert!(file > 0);
+        close(file as usize);
+        exit(0);
+    }
+    expect(fd, &[(tmp_wd, IN_CREATE, "inotify_file")]);
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    // too small for the event, which stays
+    assert_eq!(unlink("/tmp/inotify_file\0"), 0);
+    let mut small = [0u8; 16];
+    assert_eq!(read(fd, &mut small), 0);
+    expect(fd, &[(tmp_wd, IN_DELETE, "inotify_file")]);
+    close(fd);
+    println!("inotify_test passed!");
+    0
+}
@@ -1,7 +1,7 @@
 #![no_std]
 #![no_main]
 
-use user_lib::{event_get, DecodeType, Key, KeyType};
+use user_lib::{open, poll, read, DecodeType, InputEvent, Key, KeyType, OpenFlags, PollFd, POLLIN};
 
 #[macro_use]
 extern crate user_lib;
@@ -9,17 +9,31 @@ extern crate user_lib;
 #[no_mangle]
 pub fn main() -> i32 {
     println!("Input device event test");
+    let fd = open("/dev/input/event0\0", OpenFlags::RDONLY);
+    if fd < 0 {
+        println!("Failed to open /dev/input/event0");
+        return -1;
+    }
+    let fd = fd as usize;
+    let mut buf = [0u8; 8 * 16];
     loop {
-        if let Some(event) = event_get() {
+        // sleep until the keyboard has something
+        let mut fds = [PollFd::new(fd, POLLIN)];
+        assert_eq!(poll(&mut fds, -1), 1);
+        let len = read(fd, &mut buf);
+        assert!(len > 0);
+        for chunk in buf[..len as usize].chunks_exact(8) {
+            let mut raw = [0u8; 8];
+            raw.copy_from_slice(chunk);
+            let event = InputEvent::from(u64::from_le_bytes(raw));
             if let Some(decoder_type) = event.decode() {
                 println!("{:?}", decoder_type);
                 if let DecodeType::Key(key, keytype) = decoder_type {
                     if key == Key::Enter && keytype == KeyType::Press {
-                        break;
+                        return 0;
                     }
                 }
             }
         }
     }
-    0
 }
@@ -0,0 +1,83 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, endpoint_create, exit, fork, get_time, ipc_call, ipc_reply_recv, pipe, read, waitpid,
+    write, IpcMsg, OpenFlags, EINVAL, EMSGSIZE,
+};
+
+const CALLS: usize = 1000;
+
+/// Answer each call with its bytes in upper case, write "ok" to a file
+/// passed along, and leave after replying to "quit".
+fn serve(ep: usize) -> ! {
+    let mut msg = IpcMsg::default();
+    assert_eq!(ipc_reply_recv(ep, None, Some(&mut msg)), 0);
+    loop {
+        if msg.fd >= 0 {
+            assert_eq!(write(msg.fd as usize, b"ok"), 2);
+            close(msg.fd as usize);
+        }
+        let mut upper = [0u8; 64];
+        for (dst, src) in upper.iter_mut().zip(msg.bytes()) {
+            *dst = src.to_ascii_uppercase();
+        }
+        let reply = IpcMsg::new(&upper[..msg.len]);
+        if msg.bytes() == b"quit" {
+            assert_eq!(ipc_reply_recv(ep, Some(&reply), None), 0);
+            exit(0);
+        }
+        assert_eq!(ipc_reply_recv(ep, Some(&reply), Some(&mut msg)), 0);
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let ep = endpoint_create(OpenFlags::empty());
+    assert!(ep >= 0);
+    let ep = ep as usize;
+    // nothing received yet to reply to
+    assert_eq!(ipc_reply_recv(ep, Some(&IpcMsg::new(b"x")), None), EINVAL);
+    let mut too_long = IpcMsg::new(b"");
+    too_long.len = 65;
+    let mut reply = IpcMsg::default();
+    assert_eq!(ipc_call(ep, &too_long, &mut reply), EMSGSIZE);
+
+    let pid = fork();
+    if pid == 0 {
+        serve(ep);
+    }
+    assert_eq!(ipc_call(ep, &IpcMsg::new(b"hello"), &mut reply), 0);
+    assert_eq!(reply.bytes(), b"HELLO");
+    assert_eq!(reply.fd, -1);
+
+    // the server writes to the pipe it was passed
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    assert_eq!(ipc_call(ep, &IpcMsg::with_fd(b"fd", fds[1]), &mut reply), 0);
+    assert_eq!(reply.bytes(), b"FD");
+    close(fds[1]);
+    let mut buf = [0u8; 4];
+    assert_eq!(read(fds[0], &mut buf), 2);
+    assert_eq!(&buf[..2], b"ok");
+    close(fds[0]);
+
+    let start = get_time();
+    for _ in 0..CALLS {
+        assert_eq!(ipc_call(ep, &IpcMsg::new(b"ping"), &mut reply), 0);
+        assert_eq!(reply.bytes(), b"PING");
+    }
+    println!("{} calls in {} ms", CALLS, get_time() - start);
+
+    assert_eq!(ipc_call(ep, &IpcMsg::new(b"quit"), &mut reply), 0);
+    assert_eq!(reply.bytes(), b"QUIT");
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    close(ep);
+    println!("ipc_call_test passed!");
+    0
+}
@@ -0,0 +1,178 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::{
+    alarm, exit, fork, getitimer, setitimer, sigaction, sigprocmask, sigsuspend, sigtimedwait,
+    sleep, timer_create, timer_delete, timer_getoverrun, timer_gettime, timer_settime, waitpid,
+    ITimerSpec, ITimerVal, SigAction, SigEvent, SigInfo, SignalFlags, CLOCK_MONOTONIC, EAGAIN,
+    EINTR, ITIMER_REAL, SIGALRM, SIGEV_NONE, SIGEV_SIGNAL, SIGRTMIN, SIGUSR1, SIG_BLOCK,
+    SIG_UNBLOCK, SI_KERNEL, SI_TIMER,
+};
+
+static ALARMS: AtomicUsize = AtomicUsize::new(0);
+static LAST_CODE: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn handler(_signo: usize, info: &SigInfo, _ucontext: usize) {
+    ALARMS.fetch_add(1, Ordering::SeqCst);
+    LAST_CODE.store(info.code as usize, Ordering::SeqCst);
+}
+
+fn alarms() -> usize {
+    ALARMS.load(Ordering::SeqCst)
+}
+
+/// Wait for a SIGALRM to be handled.
+fn wait_alarm() {
+    let set = SignalFlags::from_signum(SIGALRM);
+    let mut old = SignalFlags::empty();
+    sigprocmask(SIG_BLOCK, Some(&set), Some(&mut old));
+    let before = alarms();
+    while alarms() == before {
+        assert_eq!(sigsuspend(&old), EINTR);
+    }
+    sigprocmask(SIG_UNBLOCK, Some(&set), None);
+}
+
+/// ITIMER_REAL once, then every interval until disarmed.
+fn real() {
+    let action = SigAction::new(handler as usize, 0);
+    assert_eq!(sigaction(SIGALRM, Some(&action), None), 0);
+    let mut curr = ITimerVal::default();
+    assert_eq!(getitimer(ITIMER_REAL, &mut curr), 0);
+    assert_eq!(curr.it_value.as_ms(), 0);
+    assert_eq!(setitimer(ITIMER_REAL, &ITimerVal::from_ms(20, 0), None), 0);
+    assert_eq!(getitimer(ITIMER_REAL, &mut curr), 0);
+    assert!(curr.it_value.as_ms() > 0 && curr.it_value.as_ms() <= 20);
+    wait_alarm();
+    assert_eq!(LAST_CODE.load(Ordering::SeqCst), SI_KERNEL as usize);
+    assert_eq!(getitimer(ITIMER_REAL, &mut curr), 0);
+    assert_eq!(curr.it_value.as_ms(), 0);
+    // every 10 ms
+    let before = alarms();
+    assert_eq!(setitimer(ITIMER_REAL, &ITimerVal::from_ms(10, 10), None), 0);
+    for _ in 0..3 {
+        wait_alarm();
+    }
+    assert!(alarms() >= before + 3);
+    let mut old = ITimerVal::default();
+    assert_eq!(
+        setitimer(ITIMER_REAL, &ITimerVal::default(), Some(&mut old)),
+        0
+    );
+    assert_eq!(old.it_interval.as_ms(), 10);
+    let after = alarms();
+    sleep(30);
+    assert_eq!(alarms(), after);
+    // nor ITIMER_VIRTUAL nor ITIMER_PROF
+    assert!(getitimer(1, &mut curr) < 0);
+}
+
+/// alarm tells the seconds left to the one before.
+fn alarm_left() {
+    assert_eq!(alarm(5), 0);
+    assert_eq!(alarm(0), 5);
+    assert_eq!(alarm(0), 0);
+}
+
+/// A timer of timer_create sends its signal with its value.
+fn posix() {
+    let set = SignalFlags::from_signum(SIGRTMIN);
+    sigprocmask(SIG_BLOCK, Some(&set), None);
+    let event = SigEvent::new(SIGEV_SIGNAL, SIGRTMIN, 0x1234);
+    let mut timerid = -1;
+    assert_eq!(timer_create(CLOCK_MONOTONIC, Some(&event), &mut timerid), 0);
+    assert!(timerid >= 0);
+    assert_eq!(
+        timer_settime(timerid, 0, &ITimerSpec::from_ms(10, 0), None),
+        0
+    );
+    let mut info = SigInfo::default();
+    assert_eq!(
+        sigtimedwait(&set, Some(&mut info), Some(1000)),
+        SIGRTMIN as isize
+    );
+    assert_eq!(info.code, SI_TIMER);
+    assert_eq!(info.timerid, timerid);
+    assert_eq!(info.value, 0x1234);
+    assert_eq!(info.overrun, 0);
+    // once only
+    assert_eq!(sigtimedwait(&set, None, Some(30)), EAGAIN);
+    assert_eq!(timer_delete(timerid), 0);
+    assert!(timer_delete(timerid) < 0);
+    sigprocmask(SIG_UNBLOCK, Some(&set), None);
+}
+
+/// Expirations while the signal is queued count as overruns of it.
+fn overrun() {
+    let set = SignalFlags::from_signum(SIGUSR1);
+    sigprocmask(SIG_BLOCK, Some(&set), None);
+    let event = SigEvent::new(SIGEV_SIGNAL, SIGUSR1, 0);
+    let mut timerid = -1;
+    assert_eq!(timer_create(CLOCK_MONOTONIC, Some(&event), &mut timerid), 0);
+    assert_eq!(
+        timer_settime(timerid, 0, &ITimerSpec::from_ms(5, 5), None),
+        0
+    );
+    sleep(60);
+    let mut info = SigInfo::default();
+    assert_eq!(
+        sigtimedwait(&set, Some(&mut info), Some(0)),
+        SIGUSR1 as isize
+    );
+    assert!(info.overrun >= 5);
+    assert!(timer_getoverrun(timerid) >= 5);
+    assert_eq!(timer_delete(timerid), 0);
+    sigprocmask(SIG_UNBLOCK, Some(&set), None);
+}
+
+/// A timer with SIGEV_NONE sends nothing, and is only read.
+fn silent() {
+    let event = SigEvent::new(SIGEV_NONE, 0, 0);
+    let mut timerid = -1;
+    assert_eq!(timer_create(CLOCK_MONOTONIC, Some(&event), &mut timerid), 0);
+    let mut old = ITimerSpec::default();
+    assert_eq!(
+        timer_settime(timerid, 0, &ITimerSpec::from_ms(1000, 0), Some(&mut old)),
+        0
+    );
+    assert_eq!(old.it_value.as_ms(), 0);
+    let mut curr = ITimerSpec::default();
+    assert_eq!(timer_gettime(timerid, &mut curr), 0);
+    assert!(curr.it_value.as_ms() > 0 && curr.it_value.as_ms() <= 1000);
+    assert_eq!(timer_delete(timerid), 0);
+    assert!(timer_gettime(timerid, &mut curr) < 0);
+}
+
+/// A child has no timers of its parent's.
+fn forked() {
+    assert_eq!(
+        setitimer(ITIMER_REAL, &ITimerVal::from_ms(1000, 0), None),
+        0
+    );
+    let pid = fork();
+    if pid == 0 {
+        let mut curr = ITimerVal::default();
+        getitimer(ITIMER_REAL, &mut curr);
+        exit(curr.it_value.as_ms() as i32);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(setitimer(ITIMER_REAL, &ITimerVal::default(), None), 0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    real();
+    alarm_left();
+    posix();
+    overrun();
+    silent();
+    forked();
+    println!("itimer_test passed!");
+    0
+}
@@ -0,0 +1,51 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::collections::BTreeSet;
+use user_lib::{close, getpid, open, read, Kcov, OpenFlags};
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let kcov = match Kcov::open(1 << 16) {
+        Some(kcov) => kcov,
+        None => {
+            println!("kcov_test: no /dev/kcov, build the kernel with KCOV=on");
+            return 0;
+        }
+    };
+    // the same syscall runs the same code, once it has run before
+    getpid();
+    kcov.reset();
+    getpid();
+    let getpid_pcs: BTreeSet<usize> = kcov.pcs().iter().copied().collect();
+    assert!(!getpid_pcs.is_empty());
+    kcov.reset();
+    getpid();
+    let again: BTreeSet<usize> = kcov.pcs().iter().copied().collect();
+    assert_eq!(getpid_pcs, again);
+
+    // another runs code of its own, all in the kernel
+    let fd = open("/dev/zero\0", OpenFlags::RDONLY);
+    assert!(fd >= 0);
+    kcov.reset();
+    read(fd as usize, &mut [0u8; 16]);
+    let read_pcs: BTreeSet<usize> = kcov.pcs().iter().copied().collect();
+    close(fd as usize);
+    assert!(read_pcs.difference(&getpid_pcs).next().is_some());
+    assert!(read_pcs.iter().all(|&pc| pc >= 0x8020_0000));
+
+    // code in user space is not the kernel's to count
+    kcov.reset();
+    let mut sum = 0usize;
+    for i in 0..1000 {
+        sum = sum.wrapping_add(i * i);
+    }
+    assert!(sum > 0);
+    assert!(kcov.pcs().is_empty());
+    println!("kcov_test passed!");
+    0
+}
@@ -0,0 +1,25 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+const LEN: usize = 48 * 1024 * 1024;
+const STRIDE: usize = 1024 * 1024;
+static mut BSS: [u8; LEN] = [0; LEN];
+
+/// A bss this large only fits because its pages are allocated on first
+/// touch.
+#[no_mangle]
+pub fn main() -> i32 {
+    let bss = unsafe { &mut BSS };
+    for i in (0..LEN).step_by(STRIDE) {
+        assert_eq!(bss[i], 0);
+        bss[i] = (i / STRIDE) as u8;
+    }
+    for i in (0..LEN).step_by(STRIDE) {
+        assert_eq!(bss[i], (i / STRIDE) as u8);
+    }
+    println!("lazy_bss passed!");
+    0
+}
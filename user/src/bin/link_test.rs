@@ -0,0 +1,78 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, link, mkdir, open, read, rename, rmdir, unlink, write, OpenFlags};
+
+fn create(path: &str, data: &[u8]) {
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, data), data.len() as isize);
+    close(fd as usize);
+}
+
+/// What the file at `path` holds, if it exists.
+fn read_file<'a>(path: &str, buf: &'a mut [u8]) -> Option<&'a [u8]> {
+    let fd = open(path, OpenFlags::RDONLY);
+    if fd < 0 {
+        return None;
+    }
+    let len = read(fd as usize, buf) as usize;
+    close(fd as usize);
+    Some(&buf[..len])
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut buf = [0u8; 32];
+    assert_eq!(mkdir("/link_test\0"), 0);
+    create("/link_test/a\0", b"first");
+
+    // a second name for the same file
+    assert_eq!(link("/link_test/a\0", "/link_test/b\0"), 0);
+    assert_eq!(link("/link_test/a\0", "/link_test/b\0"), -1);
+    assert_eq!(link("/link_test\0", "/link_test/dir\0"), -1);
+    assert_eq!(unlink("/link_test/a\0"), 0);
+    assert_eq!(read_file("/link_test/a\0", &mut buf), None);
+    assert_eq!(read_file("/link_test/b\0", &mut buf), Some(&b"first"[..]));
+    assert_eq!(unlink("/link_test/a\0"), -1);
+    // directories are removed with rmdir only
+    assert_eq!(unlink("/link_test\0"), -1);
+
+    // an unlinked file stays while it is open
+    let fd = open("/link_test/b\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    assert_eq!(unlink("/link_test/b\0"), 0);
+    let len = read(fd as usize, &mut buf) as usize;
+    assert_eq!(&buf[..len], b"first");
+    close(fd as usize);
+
+    // within a directory
+    create("/link_test/c\0", b"second");
+    assert_eq!(rename("/link_test/c\0", "/link_test/d\0"), 0);
+    assert_eq!(read_file("/link_test/c\0", &mut buf), None);
+    assert_eq!(read_file("/link_test/d\0", &mut buf), Some(&b"second"[..]));
+    // replacing a file
+    create("/link_test/e\0", b"third");
+    assert_eq!(rename("/link_test/e\0", "/link_test/d\0"), 0);
+    assert_eq!(read_file("/link_test/e\0", &mut buf), None);
+    assert_eq!(read_file("/link_test/d\0", &mut buf), Some(&b"third"[..]));
+    // across directories, with a directory
+    assert_eq!(mkdir("/link_test/sub\0"), 0);
+    assert_eq!(rename("/link_test/d\0", "/link_test/sub/d\0"), 0);
+    assert_eq!(rename("/link_test/sub\0", "/link_test/moved\0"), 0);
+    assert_eq!(
+        read_file("/link_test/moved/../moved/d\0", &mut buf),
+        Some(&b"third"[..])
+    );
+    assert_eq!(rename("/link_test\0", "/link_test/moved/inside\0"), -1);
+    assert_eq!(rename("/link_test/missing\0", "/link_test/x\0"), -1);
+
+    assert_eq!(unlink("/link_test/moved/d\0"), 0);
+    assert_eq!(rmdir("/link_test/moved\0"), 0);
+    assert_eq!(rmdir("/link_test\0"), 0);
+    println!("link_test passed!");
+    0
+}
@@ -0,0 +1,78 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, madvise, meminfo, mmap, munmap, open, vmstat, write, OpenFlags};
+use user_lib::{MemInfo, VmStat, MADV_DONTNEED, MADV_SEQUENTIAL, MADV_WILLNEED};
+use user_lib::{MAP_ANONYMOUS, MAP_PRIVATE, PAGE_SIZE, PROT_READ, PROT_WRITE};
+
+const PAGES: usize = 64;
+const LEN: usize = PAGES * PAGE_SIZE;
+const FILE_PAGES: usize = 4;
+
+fn rss() -> usize {
+    let mut stat = VmStat::default();
+    assert_eq!(vmstat(0, &mut stat), 0);
+    stat.rss
+}
+
+fn free() -> usize {
+    let mut info = MemInfo::default();
+    assert_eq!(meminfo(&mut info), 0);
+    info.free
+}
+
+fn bytes(addr: usize, len: usize) -> &'static mut [u8] {
+    unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, len) }
+}
+
+fn dont_need() {
+    let rw = PROT_READ | PROT_WRITE;
+    let addr = mmap(0, LEN, rw, MAP_PRIVATE | MAP_ANONYMOUS, 0, 0) as usize;
+    let data = bytes(addr, LEN);
+    data.fill(1);
+    let (rss_before, free_before) = (rss(), free());
+    // the frames are given back at once, and the pages read as zeros
+    assert_eq!(madvise(addr, LEN, MADV_DONTNEED), 0);
+    assert_eq!(rss(), rss_before - LEN);
+    assert!(free() >= free_before + LEN);
+    assert!(data.iter().all(|byte| *byte == 0));
+    data[0] = 2;
+    assert_eq!(data[0], 2);
+    // only mapped ranges may be advised
+    assert_eq!(munmap(addr + PAGE_SIZE, PAGE_SIZE), 0);
+    assert_eq!(madvise(addr, 2 * PAGE_SIZE, MADV_DONTNEED), -1);
+    assert_eq!(madvise(addr, PAGE_SIZE, MADV_SEQUENTIAL), 0);
+    assert_eq!(munmap(addr, LEN), 0);
+}
+
+fn will_need() {
+    let fd = open("madvise_file\0", OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let page = [7u8; PAGE_SIZE];
+    for _ in 0..FILE_PAGES {
+        assert_eq!(write(fd as usize, &page), PAGE_SIZE as isize);
+    }
+    let len = FILE_PAGES * PAGE_SIZE;
+    let addr = mmap(0, len, PROT_READ, MAP_PRIVATE, fd as usize, 0) as usize;
+    close(fd as usize);
+    // the pages are read ahead of the first access
+    let before = rss();
+    assert_eq!(madvise(addr, len, MADV_WILLNEED), 0);
+    assert_eq!(rss(), before + len);
+    assert!(bytes(addr, len).iter().all(|byte| *byte == 7));
+    // and read again from the file once dropped
+    assert_eq!(madvise(addr, len, MADV_DONTNEED), 0);
+    assert!(bytes(addr, len).iter().all(|byte| *byte == 7));
+    assert_eq!(munmap(addr, len), 0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    dont_need();
+    will_need();
+    println!("madvise_test passed!");
+    0
+}
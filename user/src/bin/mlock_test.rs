@@ -0,0 +1,61 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{getrlimit, mlock, mlockall, mmap, munlock, munlockall, munmap, setrlimit};
+use user_lib::{vmstat, RLimit, VmStat, PAGE_SIZE, RLIMIT_MEMLOCK};
+use user_lib::{MAP_ANONYMOUS, MAP_PRIVATE, MCL_CURRENT, MCL_FUTURE, PROT_READ, PROT_WRITE};
+
+const PAGES: usize = 16;
+const LEN: usize = PAGES * PAGE_SIZE;
+
+fn rss() -> usize {
+    let mut stat = VmStat::default();
+    assert_eq!(vmstat(0, &mut stat), 0);
+    stat.rss
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let addr = mmap(
+        0,
+        LEN,
+        PROT_READ | PROT_WRITE,
+        MAP_PRIVATE | MAP_ANONYMOUS,
+        0,
+        0,
+    ) as usize;
+    // locking faults the pages in at once
+    let before = rss();
+    assert_eq!(mlock(addr, LEN), 0);
+    assert_eq!(rss(), before + LEN);
+    // an unaligned start takes the whole page
+    assert_eq!(munlock(addr + 1, PAGE_SIZE), 0);
+    assert_eq!(munlock(addr, LEN), 0);
+    // the whole range must be mapped
+    assert_eq!(mlock(addr, LEN + PAGE_SIZE), -1);
+
+    // RLIMIT_MEMLOCK bounds what can be locked
+    let mut old = RLimit::default();
+    assert_eq!(getrlimit(RLIMIT_MEMLOCK, &mut old), 0);
+    let limit = RLimit {
+        rlim_cur: 4 * PAGE_SIZE,
+        rlim_max: old.rlim_max,
+    };
+    assert_eq!(setrlimit(RLIMIT_MEMLOCK, &limit), 0);
+    assert_eq!(mlock(addr, 4 * PAGE_SIZE), 0);
+    // pages locked already are not counted twice
+    assert_eq!(mlock(addr, 4 * PAGE_SIZE), 0);
+    assert_eq!(mlock(addr + 4 * PAGE_SIZE, PAGE_SIZE), -1);
+    assert_eq!(mlockall(MCL_CURRENT), -1);
+    assert_eq!(setrlimit(RLIMIT_MEMLOCK, &old), 0);
+
+    assert_eq!(mlockall(MCL_FUTURE), -1);
+    assert_eq!(mlockall(MCL_CURRENT), 0);
+    assert_eq!(munlockall(), 0);
+    assert_eq!(munmap(addr, LEN), 0);
+    println!("mlock_test passed!");
+    0
+}
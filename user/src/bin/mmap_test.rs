@@ -0,0 +1,150 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit, fork, mmap, mprotect, munmap, open, pwrite, read, waitpid, write, OpenFlags,
+    MAP_ANONYMOUS, MAP_FIXED, MAP_PRIVATE, MAP_SHARED, PAGE_SIZE, PROT_READ, PROT_WRITE,
+};
+
+const HUGE_PAGE_SIZE: usize = 0x20_0000;
+const FILE_LEN: usize = PAGE_SIZE + 100;
+
+fn bytes(addr: isize, len: usize) -> &'static mut [u8] {
+    assert!(addr > 0);
+    unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, len) }
+}
+
+fn wait_for(pid: isize) -> i32 {
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    exit_code
+}
+
+fn anonymous() {
+    let rw = PROT_READ | PROT_WRITE;
+    let addr = mmap(0, 4 * PAGE_SIZE, rw, MAP_PRIVATE | MAP_ANONYMOUS, 0, 0);
+    let data = bytes(addr, 4 * PAGE_SIZE);
+    assert!(data.iter().all(|byte| *byte == 0));
+    data.fill(1);
+    // the second page becomes read-only, writing it must kill the child
+    assert_eq!(mprotect(addr as usize + PAGE_SIZE, PAGE_SIZE, PROT_READ), 0);
+    assert_eq!(data[PAGE_SIZE], 1);
+    let pid = fork();
+    if pid == 0 {
+        data[PAGE_SIZE] = 2;
+        exit(0);
+    }
+    assert_eq!(wait_for(pid), -11);
+    // punch a hole, then map it again in place, which gives zeros
+    assert_eq!(munmap(addr as usize + 2 * PAGE_SIZE, PAGE_SIZE), 0);
+    let flags = MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED;
+    let hole = mmap(addr as usize + 2 * PAGE_SIZE, PAGE_SIZE, rw, flags, 0, 0);
+    assert_eq!(hole, addr + 2 * PAGE_SIZE as isize);
+    assert_eq!(data[2 * PAGE_SIZE], 0);
+    assert_eq!(data[3 * PAGE_SIZE], 1);
+    assert_eq!(munmap(addr as usize, 4 * PAGE_SIZE), 0);
+    // unaligned and empty ranges are rejected
+    assert_eq!(munmap(addr as usize + 1, PAGE_SIZE), -1);
+    assert_eq!(mmap(0, 0, rw, MAP_PRIVATE | MAP_ANONYMOUS, 0, 0), -1);
+}
+
+fn shared_anonymous() {
+    let rw = PROT_READ | PROT_WRITE;
+    let addr = mmap(0, PAGE_SIZE, rw, MAP_SHARED | MAP_ANONYMOUS, 0, 0);
+    let data = bytes(addr, PAGE_SIZE);
+    let pid = fork();
+    if pid == 0 {
+        data[..5].copy_from_slice(b"child");
+        exit(0);
+    }
+    assert_eq!(wait_for(pid), 0);
+    assert_eq!(&data[..5], b"child");
+    assert_eq!(munmap(addr as usize, PAGE_SIZE), 0);
+}
+
+fn huge() {
+    let rw = PROT_READ | PROT_WRITE;
+    let len = 2 * HUGE_PAGE_SIZE;
+    let addr = mmap(0, len, rw, MAP_PRIVATE | MAP_ANONYMOUS, 0, 0);
+    // large mappings are aligned so that megapages fit
+    assert_eq!(addr as usize % HUGE_PAGE_SIZE, 0);
+    let data = bytes(addr, len);
+    for i in (0..len).step_by(PAGE_SIZE) {
+        data[i] = (i / PAGE_SIZE) as u8;
+    }
+    // changing a single page splits its megapage, the rest is untouched
+    let page = addr as usize + HUGE_PAGE_SIZE + 3 * PAGE_SIZE;
+    assert_eq!(mprotect(page, PAGE_SIZE, PROT_READ), 0);
+    assert_eq!(mprotect(page, PAGE_SIZE, rw), 0);
+    for i in (0..len).step_by(PAGE_SIZE) {
+        assert_eq!(data[i], (i / PAGE_SIZE) as u8);
+    }
+    // and so does unmapping part of it
+    assert_eq!(munmap(addr as usize + PAGE_SIZE, PAGE_SIZE), 0);
+    assert_eq!(data[2 * PAGE_SIZE], 2);
+    assert_eq!(munmap(addr as usize, len), 0);
+}
+
+fn file() {
+    let fd = open("mmap_file\0", OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let mut content = [0u8; FILE_LEN];
+    for (i, byte) in content.iter_mut().enumerate() {
+        *byte = (i % 251) as u8;
+    }
+    assert_eq!(write(fd, &content), FILE_LEN as isize);
+    let rw = PROT_READ | PROT_WRITE;
+    // writes to a private mapping stay there
+    let addr = mmap(0, FILE_LEN, rw, MAP_PRIVATE, fd, 0);
+    let data = bytes(addr, 2 * PAGE_SIZE);
+    assert_eq!(&data[..FILE_LEN], &content[..]);
+    assert!(data[FILE_LEN..].iter().all(|byte| *byte == 0));
+    data[0] = 0xff;
+    assert_eq!(munmap(addr as usize, FILE_LEN), 0);
+    // writes to a shared one reach the file, from the second page on here
+    let addr = mmap(0, PAGE_SIZE, rw, MAP_SHARED, fd, PAGE_SIZE);
+    let data = bytes(addr, PAGE_SIZE);
+    assert_eq!(&data[..100], &content[PAGE_SIZE..]);
+    data[..6].copy_from_slice(b"shared");
+    assert_eq!(munmap(addr as usize, PAGE_SIZE), 0);
+    // mappings of their own share the pages with each other and with
+    // write, which unmapping does not undo
+    let addr = mmap(0, PAGE_SIZE, rw, MAP_SHARED, fd, 0);
+    let data = bytes(addr, PAGE_SIZE);
+    let pid = fork();
+    if pid == 0 {
+        let other = bytes(mmap(0, PAGE_SIZE, rw, MAP_SHARED, fd, 0), PAGE_SIZE);
+        other[1] = 0xee;
+        exit(0);
+    }
+    assert_eq!(wait_for(pid), 0);
+    assert_eq!(data[1], 0xee);
+    assert_eq!(pwrite(fd, b"written", 8), 7);
+    assert_eq!(&data[8..15], b"written");
+    assert_eq!(munmap(addr as usize, PAGE_SIZE), 0);
+    close(fd);
+
+    let fd = open("mmap_file\0", OpenFlags::RDONLY) as usize;
+    let mut buf = [0u8; FILE_LEN + 1];
+    assert_eq!(read(fd, &mut buf), FILE_LEN as isize);
+    assert_eq!(buf[..2], [0, 0xee]);
+    assert_eq!(&buf[8..15], b"written");
+    assert_eq!(&buf[PAGE_SIZE..PAGE_SIZE + 6], b"shared");
+    // a read-only file cannot be mapped shared and writable
+    assert_eq!(mmap(0, PAGE_SIZE, rw, MAP_SHARED, fd, 0), -1);
+    close(fd);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    anonymous();
+    shared_anonymous();
+    huge();
+    file();
+    println!("mmap_test passed!");
+    0
+}
@@ -0,0 +1,46 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, mount, open, read, umount, unlink, write, OpenFlags};
+
+fn read_file(path: &str) -> bool {
+    let fd = open(path, OpenFlags::RDONLY);
+    if fd < 0 {
+        return false;
+    }
+    let mut buf = [0u8; 16];
+    let len = read(fd as usize, &mut buf) as usize;
+    close(fd as usize);
+    &buf[..len] == b"mounted"
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("/mount_test_file\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    write(fd as usize, b"mounted");
+    close(fd as usize);
+
+    assert_eq!(mount("/dev/vda\0", "/\0", "nofs\0"), -1);
+    assert_eq!(mount("/dev/vdz\0", "/\0", "easyfs\0"), -1);
+    // only directories can be mounted on
+    assert_eq!(mount("/dev/vda\0", "/mount_test_file\0", "easyfs\0"), -1);
+    assert_eq!(mount("/dev/vda\0", "/nonexistent\0", "easyfs\0"), -1);
+    // the root file system stays
+    assert_eq!(umount("/\0"), -1);
+
+    // the same file system once more, on top of itself
+    assert_eq!(mount("/dev/vda\0", "/\0", "easyfs\0"), 0);
+    assert!(read_file("/mount_test_file\0"));
+    assert!(read_file("mount_test_file\0"));
+    assert!(read_file("//./mount_test_file\0"));
+    assert_eq!(umount("/\0"), 0);
+    assert_eq!(umount("/\0"), -1);
+    assert!(read_file("/mount_test_file\0"));
+    assert_eq!(unlink("/mount_test_file\0"), 0);
+    println!("mount_test passed!");
+    0
+}
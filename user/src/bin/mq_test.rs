@@ -0,0 +1,116 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit, fork, get_time, mq_getattr, mq_open, mq_receive, mq_send, mq_setattr,
+    mq_timedreceive, mq_unlink, poll, waitpid, MqAttr, OpenFlags, PollFd, TimeSpec, EAGAIN,
+    EEXIST, EINVAL, EMSGSIZE, ENOENT, ETIMEDOUT, MQ_PRIO_MAX, POLLIN, POLLOUT,
+};
+
+const NAME: &str = "/mq_test\0";
+const MSGSIZE: usize = 64;
+
+fn poll_events(mqdes: usize) -> i16 {
+    let mut fds = [PollFd {
+        fd: mqdes as i32,
+        events: POLLIN | POLLOUT,
+        revents: 0,
+    }];
+    assert_eq!(poll(&mut fds, 0), (fds[0].revents != 0) as isize);
+    fds[0].revents
+}
+
+fn check_priorities(mqdes: usize) {
+    assert_eq!(poll_events(mqdes), POLLOUT);
+    for (msg, prio) in [(b"low 1", 1), (b"high ", 9), (b"low 2", 1), (b"mid  ", 5)] {
+        assert_eq!(mq_send(mqdes, msg, prio), 0);
+    }
+    // full, and not waited for with O_NONBLOCK
+    let mut attr = MqAttr::default();
+    assert_eq!(mq_getattr(mqdes, &mut attr), 0);
+    assert_eq!((attr.maxmsg, attr.msgsize, attr.curmsgs), (4, MSGSIZE as i64, 4));
+    assert_eq!(poll_events(mqdes), POLLIN);
+    attr.flags = OpenFlags::NONBLOCK.bits() as i64;
+    assert_eq!(mq_setattr(mqdes, &attr), 0);
+    assert_eq!(mq_send(mqdes, b"one too many", 0), EAGAIN);
+
+    let mut buf = [0u8; MSGSIZE];
+    let mut prio = 0;
+    for (expected, expected_prio) in [(b"high ", 9), (b"mid  ", 5), (b"low 1", 1), (b"low 2", 1)] {
+        assert_eq!(mq_receive(mqdes, &mut buf, Some(&mut prio)), 5);
+        assert_eq!(&buf[..5], expected);
+        assert_eq!(prio, expected_prio);
+    }
+    assert_eq!(mq_receive(mqdes, &mut buf, None), EAGAIN);
+    attr.flags = 0;
+    assert_eq!(mq_setattr(mqdes, &attr), 0);
+
+    // too long a message, too short a buffer, too high a priority
+    assert_eq!(mq_send(mqdes, &[0u8; MSGSIZE + 1], 0), EMSGSIZE);
+    assert_eq!(mq_receive(mqdes, &mut buf[..MSGSIZE - 1], None), EMSGSIZE);
+    assert_eq!(mq_send(mqdes, b"x", MQ_PRIO_MAX), EINVAL);
+
+    // nothing comes before the time is up
+    let deadline = TimeSpec::from_ms(get_time() as usize + 50);
+    assert_eq!(mq_timedreceive(mqdes, &mut buf, None, &deadline), ETIMEDOUT);
+    assert!(get_time() as usize >= deadline.as_ms());
+}
+
+fn check_blocking() {
+    let mqdes = mq_open(NAME, OpenFlags::RDONLY, 0, None);
+    assert!(mqdes >= 0);
+    let mqdes = mqdes as usize;
+    let pid = fork();
+    if pid == 0 {
+        let sender = mq_open(NAME, OpenFlags::WRONLY, 0, None);
+        assert!(sender >= 0);
+        for i in 0..16u8 {
+            assert_eq!(mq_send(sender as usize, &[i], 0), 0);
+        }
+        close(sender as usize);
+        exit(0);
+    }
+    // more than the queue holds, so the child waits for room
+    let mut buf = [0u8; MSGSIZE];
+    for i in 0..16u8 {
+        assert_eq!(mq_receive(mqdes, &mut buf, None), 1);
+        assert_eq!(buf[0], i);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    // a descriptor opened for reading sends nothing
+    assert!(mq_send(mqdes, b"x", 0) < 0);
+    close(mqdes);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let attr = MqAttr::new(4, MSGSIZE);
+    let flags = OpenFlags::RDWR | OpenFlags::CREATE | OpenFlags::EXCL;
+    assert_eq!(mq_open(NAME, OpenFlags::RDWR, 0, None), ENOENT);
+    let mqdes = mq_open(NAME, flags, 0o600, Some(&attr));
+    assert!(mqdes >= 0);
+    assert_eq!(mq_open(NAME, flags, 0o600, Some(&attr)), EEXIST);
+    assert_eq!(mq_open("/a/b\0", flags, 0o600, None), EINVAL);
+    assert_eq!(
+        mq_open("/big\0", flags, 0o600, Some(&MqAttr::new(0, MSGSIZE))),
+        EINVAL
+    );
+
+    check_priorities(mqdes as usize);
+    check_blocking();
+
+    // the name goes, the queue stays with those who have it open
+    assert_eq!(mq_unlink(NAME), 0);
+    assert_eq!(mq_unlink(NAME), ENOENT);
+    assert_eq!(mq_send(mqdes as usize, b"still here", 3), 0);
+    let mut buf = [0u8; MSGSIZE];
+    assert_eq!(mq_receive(mqdes as usize, &mut buf, None), 10);
+    close(mqdes as usize);
+    println!("mq_test passed!");
+    0
+}
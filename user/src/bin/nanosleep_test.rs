@@ -0,0 +1,112 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::{
+    clock_gettime, clock_nanosleep, nanosleep, perf_read, setitimer, sigaction, ITimerVal,
+    PerfCounts, SigAction, SigInfo, TimeSpec, CLOCK_MONOTONIC, EINTR, EINVAL, ITIMER_REAL,
+    PERF_THREAD, SIGALRM, TIMER_ABSTIME,
+};
+
+static ALARMS: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn handler(_signo: usize, _info: &SigInfo, _ucontext: usize) {
+    ALARMS.fetch_add(1, Ordering::SeqCst);
+}
+
+fn now_ns() -> u64 {
+    let mut tp = TimeSpec::default();
+    assert_eq!(clock_gettime(CLOCK_MONOTONIC, &mut tp), 0);
+    tp.tv_sec as u64 * 1_000_000_000 + tp.tv_nsec as u64
+}
+
+fn from_ns(ns: u64) -> TimeSpec {
+    TimeSpec {
+        tv_sec: (ns / 1_000_000_000) as i64,
+        tv_nsec: (ns % 1_000_000_000) as i64,
+    }
+}
+
+/// A sleep lasts as long as asked, blocked rather than running meanwhile.
+fn relative() {
+    let mut before = PerfCounts::default();
+    assert!(perf_read(PERF_THREAD, &mut before) >= 0);
+    let start = now_ns();
+    assert_eq!(nanosleep(&from_ns(100_000_000), None), 0);
+    let slept = now_ns() - start;
+    assert!(slept >= 100_000_000, "slept {} ns", slept);
+    let mut after = PerfCounts::default();
+    assert!(perf_read(PERF_THREAD, &mut after) >= 0);
+    let ran = after.time_ns - before.time_ns;
+    assert!(ran < 20_000_000, "ran {} ns of the sleep", ran);
+    // no time at all
+    assert_eq!(nanosleep(&TimeSpec::default(), None), 0);
+}
+
+/// Up to a time of the clock rather than for a while, one past at once.
+fn absolute() {
+    let deadline = now_ns() + 30_000_000;
+    assert_eq!(
+        clock_nanosleep(CLOCK_MONOTONIC, TIMER_ABSTIME, &from_ns(deadline), None),
+        0
+    );
+    assert!(now_ns() >= deadline);
+    let start = now_ns();
+    assert_eq!(
+        clock_nanosleep(CLOCK_MONOTONIC, TIMER_ABSTIME, &from_ns(1), None),
+        0
+    );
+    assert!(now_ns() - start < 10_000_000);
+}
+
+/// A signal ends the sleep early, telling the time left.
+fn interrupted() {
+    let action = SigAction::new(handler as usize, 0);
+    assert_eq!(sigaction(SIGALRM, Some(&action), None), 0);
+    assert_eq!(setitimer(ITIMER_REAL, &ITimerVal::from_ms(30, 0), None), 0);
+    let mut rem = TimeSpec::default();
+    assert_eq!(nanosleep(&from_ns(1_000_000_000), Some(&mut rem)), EINTR);
+    assert_eq!(ALARMS.load(Ordering::SeqCst), 1);
+    let left = rem.tv_sec as u64 * 1_000_000_000 + rem.tv_nsec as u64;
+    assert!(left > 500_000_000 && left < 1_000_000_000, "{:?} left", rem);
+    // an absolute one leaves rem alone
+    assert_eq!(setitimer(ITIMER_REAL, &ITimerVal::from_ms(30, 0), None), 0);
+    let mut rem = TimeSpec::default();
+    let deadline = from_ns(now_ns() + 1_000_000_000);
+    assert_eq!(
+        clock_nanosleep(CLOCK_MONOTONIC, TIMER_ABSTIME, &deadline, Some(&mut rem)),
+        EINTR
+    );
+    assert_eq!(ALARMS.load(Ordering::SeqCst), 2);
+    assert_eq!(rem.tv_sec, 0);
+    assert_eq!(rem.tv_nsec, 0);
+}
+
+fn invalid() {
+    let bad = TimeSpec {
+        tv_sec: 0,
+        tv_nsec: 1_000_000_000,
+    };
+    assert_eq!(nanosleep(&bad, None), EINVAL);
+    let negative = TimeSpec {
+        tv_sec: -1,
+        tv_nsec: 0,
+    };
+    assert_eq!(nanosleep(&negative, None), EINVAL);
+    let req = from_ns(1_000_000);
+    assert_eq!(clock_nanosleep(42, 0, &req, None), EINVAL);
+    assert_eq!(clock_nanosleep(CLOCK_MONOTONIC, 2, &req, None), EINVAL);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    relative();
+    absolute();
+    interrupted();
+    invalid();
+    println!("nanosleep_test passed!");
+    0
+}
@@ -0,0 +1,120 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+// netbench tcp-sink|udp-sink [PORT] [SECS]: have the kernel take what comes
+// to PORT, 5201 by default, until the peer is done or for SECS seconds
+// netbench tcp-source|udp-source HOST [PORT] [SECS]: have it send to HOST
+// netbench stop: end the run going on
+//
+// It waits for the run to end, and prints /proc/netbench.
+
+use alloc::format;
+use alloc::string::String;
+use user_lib::{
+    close, getaddrinfo, netbench, open, parse_ip, read, sleep, OpenFlags, SockAddrIn, EBUSY,
+    ENOSYS, NETBENCH_STOP, NETBENCH_TCP_SINK, NETBENCH_TCP_SOURCE, NETBENCH_UDP_SINK,
+    NETBENCH_UDP_SOURCE,
+};
+
+const PORT: u16 = 5201;
+const SECS: usize = 10;
+
+fn report() -> Option<String> {
+    let fd = open("/proc/netbench\0", OpenFlags::RDONLY);
+    if fd < 0 {
+        return None;
+    }
+    let mut buf = [0u8; 256];
+    let len = read(fd as usize, &mut buf);
+    close(fd as usize);
+    let text = core::str::from_utf8(&buf[..len.max(0) as usize]).ok()?;
+    Some(String::from(text))
+}
+
+fn resolve(host: &str) -> Option<[u8; 4]> {
+    parse_ip(host).or_else(|| {
+        let mut addrs = [[0u8; 4]; 1];
+        match getaddrinfo(&format!("{}\0", host), &mut addrs) {
+            n if n > 0 => Some(addrs[0]),
+            _ => None,
+        }
+    })
+}
+
+fn usage() -> i32 {
+    println!("usage: netbench tcp-sink|udp-sink [PORT] [SECS]");
+    println!("       netbench tcp-source|udp-source HOST [PORT] [SECS]");
+    println!("       netbench stop");
+    2
+}
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc < 2 {
+        return usage();
+    }
+    let (op, source) = match argv[1] {
+        "stop" => (NETBENCH_STOP, false),
+        "tcp-sink" => (NETBENCH_TCP_SINK, false),
+        "tcp-source" => (NETBENCH_TCP_SOURCE, true),
+        "udp-sink" => (NETBENCH_UDP_SINK, false),
+        "udp-source" => (NETBENCH_UDP_SOURCE, true),
+        _ => return usage(),
+    };
+    let mut args = argv[2..argc].iter();
+    let ip = match source {
+        true => match args.next().map(|host| (host, resolve(host))) {
+            Some((_, Some(ip))) => ip,
+            Some((host, None)) => {
+                println!("netbench: {}: cannot resolve", host);
+                return 1;
+            }
+            None => return usage(),
+        },
+        false => [0; 4],
+    };
+    let port = match args.next().map(|port| port.parse()) {
+        Some(Ok(port)) => port,
+        Some(Err(_)) => return usage(),
+        None => PORT,
+    };
+    let secs = match args.next().map(|secs| secs.parse()) {
+        Some(Ok(secs)) => secs,
+        Some(Err(_)) => return usage(),
+        None => SECS,
+    };
+    let ret = netbench(op, &SockAddrIn::new(ip, port), secs);
+    match ret {
+        0 => {}
+        EBUSY => {
+            println!("netbench: a run goes on, see /proc/netbench");
+            return 1;
+        }
+        ENOSYS => {
+            println!("netbench: the kernel is built without it");
+            return 1;
+        }
+        ret => {
+            println!("netbench: error {}", ret);
+            return 1;
+        }
+    }
+    if op == NETBENCH_STOP {
+        return 0;
+    }
+    loop {
+        sleep(500);
+        match report() {
+            Some(text) if text.contains("running") || text == "idle\n" => {}
+            Some(text) => {
+                print!("{}", text);
+                return 0;
+            }
+            None => return 1,
+        }
+    }
+}
@@ -0,0 +1,57 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, exit, fork, objstat, open, thread_create, waitpid, waittid};
+use user_lib::{ObjStat, OpenFlags, OBJ_FILE, OBJ_INODE, OBJ_KINDS, OBJ_PROCESS, OBJ_TASK};
+
+const ROUNDS: usize = 10;
+
+fn stats(mark: bool) -> [ObjStat; OBJ_KINDS] {
+    let mut stats = [ObjStat::default(); OBJ_KINDS];
+    assert_eq!(objstat(&mut stats, mark), OBJ_KINDS as isize);
+    stats
+}
+
+pub fn thread() -> ! {
+    exit(0)
+}
+
+/// Processes, threads and files made and done with leave the counts as
+/// they were, and one kept alive shows.
+#[no_mangle]
+pub fn main() -> i32 {
+    let before = stats(true);
+    for _ in 0..ROUNDS {
+        let pid = fork();
+        if pid == 0 {
+            exit(0);
+        }
+        let mut exit_code = -1;
+        assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+        let tid = thread_create(thread as usize, 0);
+        assert_eq!(waittid(tid as usize), 0);
+        let fd = open("objstat_test\0", OpenFlags::RDONLY);
+        assert!(fd >= 0);
+        close(fd as usize);
+    }
+    let after = stats(false);
+    for kind in [OBJ_TASK, OBJ_PROCESS, OBJ_FILE, OBJ_INODE] {
+        assert_eq!(after[kind].delta, 0);
+        assert_eq!(after[kind].live, before[kind].live);
+    }
+    assert!(after[OBJ_PROCESS].made - before[OBJ_PROCESS].made >= ROUNDS);
+    assert!(after[OBJ_TASK].made - before[OBJ_TASK].made >= 2 * ROUNDS);
+    assert!(after[OBJ_FILE].made - before[OBJ_FILE].made >= ROUNDS);
+
+    // an open file is one more, until it is closed
+    let fd = open("objstat_test\0", OpenFlags::RDONLY);
+    assert!(fd >= 0);
+    assert_eq!(stats(false)[OBJ_FILE].delta, 1);
+    close(fd as usize);
+    assert_eq!(stats(false)[OBJ_FILE].delta, 0);
+    println!("objstat_test passed!");
+    0
+}
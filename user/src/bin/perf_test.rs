@@ -0,0 +1,98 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::hint::black_box;
+use user_lib::{exit, fork, perf_read, raw_syscall, rdcycle, rdinstret, thread_create};
+use user_lib::{waitpid, waittid};
+use user_lib::{PerfCounts, EFAULT, EINVAL, PAGE_SIZE, PERF_PROCESS, PERF_THREAD};
+
+const SYSCALL_PERF_READ: usize = 443;
+const SPINS: u64 = 100_000;
+const PAGES: usize = 16;
+
+static mut DATA: [u8; PAGES * PAGE_SIZE] = [1; PAGES * PAGE_SIZE];
+
+fn spin() {
+    let mut sum = 0u64;
+    for i in 0..SPINS {
+        sum = black_box(sum + i);
+    }
+    black_box(sum);
+}
+
+fn thread_counts() -> PerfCounts {
+    let mut counts = PerfCounts::default();
+    assert!(perf_read(PERF_THREAD, &mut counts) >= 0);
+    counts
+}
+
+pub fn spinner() -> ! {
+    spin();
+    exit(0)
+}
+
+/// The instructions a child touching the pages it shares with its parent
+/// after fork takes, writing them, for the kernel to copy them, or only
+/// reading them.
+fn touch_after_fork(write: bool) -> u64 {
+    let pid = fork();
+    if pid == 0 {
+        let before = thread_counts();
+        let data = unsafe { &mut DATA };
+        for page in data.chunks_mut(PAGE_SIZE) {
+            if write {
+                page[0] = 2;
+            } else {
+                black_box(page[0]);
+            }
+        }
+        let after = thread_counts();
+        exit((after.instret - before.instret) as i32);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert!(exit_code > 0);
+    exit_code as u64
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut counts = PerfCounts::default();
+    assert_eq!(perf_read(2, &mut counts), EINVAL);
+    assert_eq!(raw_syscall(SYSCALL_PERF_READ, [PERF_THREAD, 0, 0, 0, 0, 0]), EFAULT);
+
+    // cycle and instret are readable here, and count more than the thread
+    let (cycle, instret) = (rdcycle(), rdinstret());
+    let before = thread_counts();
+    spin();
+    let after = thread_counts();
+    assert!(after.instret - before.instret >= SPINS);
+    assert!(after.cycles > before.cycles);
+    assert!(after.time_ns > before.time_ns);
+    assert!(rdcycle() > cycle && rdinstret() - instret >= SPINS);
+
+    // a process keeps what its threads counted once they exited
+    let mut process = PerfCounts::default();
+    let events = perf_read(PERF_PROCESS, &mut process);
+    assert!(events >= 0);
+    let tid = thread_create(spinner as usize, 0);
+    assert!(tid > 0);
+    assert_eq!(waittid(tid as usize), 0);
+    let mut later = PerfCounts::default();
+    assert_eq!(perf_read(PERF_PROCESS, &mut later), events);
+    assert!(later.instret - process.instret >= SPINS);
+    assert!(later.switches > process.switches);
+
+    let copied = touch_after_fork(true);
+    let shared = touch_after_fork(false);
+    println!(
+        "{} pages after fork: written {} instructions, read {}; events counted {:#b}",
+        PAGES, copied, shared, events
+    );
+    assert!(copied > shared);
+    println!("perf_test passed!");
+    0
+}
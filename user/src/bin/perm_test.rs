@@ -0,0 +1,115 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    chmod, chown, close, exec, exit, fork, getgid, getuid, mkdir, mount, open, rmdir, setgid,
+    setuid, stat, unlink, waitpid, write, OpenFlags, Stat,
+};
+
+const USER: u32 = 1000;
+const GROUP: u32 = 100;
+
+fn create(path: &str, data: &[u8]) {
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, data), data.len() as isize);
+    close(fd as usize);
+}
+
+fn can_open(path: &str, flags: OpenFlags) -> bool {
+    let fd = open(path, flags);
+    if fd < 0 {
+        return false;
+    }
+    close(fd as usize);
+    true
+}
+
+/// What an ordinary user may and may not do.
+fn as_user() -> i32 {
+    assert_eq!(setgid(GROUP), 0);
+    assert_eq!(setuid(USER), 0);
+    assert_eq!((getuid(), getgid()), (USER as isize, GROUP as isize));
+    assert_eq!(setuid(0), -1);
+    assert_eq!(setgid(0), -1);
+
+    // the bits of the owner, the group or the others
+    assert!(can_open("/perm_test/public\0", OpenFlags::RDONLY));
+    assert!(!can_open("/perm_test/public\0", OpenFlags::WRONLY));
+    assert!(can_open("/perm_test/group\0", OpenFlags::RDWR));
+    assert!(!can_open("/perm_test/secret\0", OpenFlags::RDONLY));
+    // no entries in a directory not to be written, nor a way through one
+    // not to be searched
+    assert!(!can_open(
+        "/perm_test/new\0",
+        OpenFlags::CREATE | OpenFlags::WRONLY
+    ));
+    assert_eq!(mkdir("/perm_test/dir\0"), -1);
+    assert_eq!(unlink("/perm_test/public\0"), -1);
+    assert!(!can_open("/perm_test/private/file\0", OpenFlags::RDONLY));
+    // nor running what may not be run
+    let args = ["/perm_test/public\0".as_ptr(), core::ptr::null::<u8>()];
+    assert_eq!(exec("/perm_test/public\0", &args), -1);
+
+    // what a user makes is theirs
+    create("/tmp/perm_test\0", b"mine");
+    let mut st = Stat::default();
+    assert_eq!(stat("/tmp/perm_test\0", &mut st), 0);
+    assert_eq!((st.uid, st.gid, st.mode & 0o777), (USER, GROUP, 0o644));
+    assert_eq!(chmod("/tmp/perm_test\0", 0o400), 0);
+    assert!(!can_open("/tmp/perm_test\0", OpenFlags::WRONLY));
+    assert_eq!(unlink("/tmp/perm_test\0"), 0);
+    // but not what root made
+    assert_eq!(chmod("/perm_test/public\0", 0o666), -1);
+    assert_eq!(chown("/perm_test/group\0", USER, GROUP), -1);
+    assert_eq!(mount("tmpfs\0", "/perm_test\0", "tmpfs\0"), -1);
+    0
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(getuid(), 0);
+    assert_eq!(mkdir("/perm_test\0"), 0);
+    create("/perm_test/public\0", b"public");
+    create("/perm_test/group\0", b"group");
+    create("/perm_test/secret\0", b"secret");
+    assert_eq!(chown("/perm_test/group\0", 0, GROUP), 0);
+    assert_eq!(chmod("/perm_test/group\0", 0o660), 0);
+    assert_eq!(chmod("/perm_test/secret\0", 0o600), 0);
+    assert_eq!(mkdir("/perm_test/private\0"), 0);
+    create("/perm_test/private/file\0", b"private");
+    assert_eq!(chmod("/perm_test/private\0", 0o700), 0);
+    let mut st = Stat::default();
+    assert_eq!(stat("/perm_test/group\0", &mut st), 0);
+    assert_eq!((st.uid, st.gid, st.mode & 0o7777), (0, GROUP, 0o660));
+
+    let pid = fork();
+    if pid == 0 {
+        exit(as_user());
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+
+    // root may do anything but run what nobody may
+    assert!(can_open("/perm_test/secret\0", OpenFlags::RDWR));
+    let args = ["/perm_test/secret\0".as_ptr(), core::ptr::null::<u8>()];
+    assert_eq!(exec("/perm_test/secret\0", &args), -1);
+    assert_eq!(unlink("/perm_test/private/file\0"), 0);
+    assert_eq!(rmdir("/perm_test/private\0"), 0);
+    for path in [
+        "/perm_test/public\0",
+        "/perm_test/group\0",
+        "/perm_test/secret\0",
+    ]
+    .iter()
+    {
+        assert_eq!(unlink(path), 0);
+    }
+    assert_eq!(rmdir("/perm_test\0"), 0);
+    println!("perm_test passed!");
+    0
+}
@@ -0,0 +1,115 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::{
+    exit, fork, getpgid, getpid, kill, killpg, setpgid, sigaction, sigprocmask, sigsuspend, sleep,
+    tcgetpgrp, tcsetpgrp, waitpid, waitpid_untraced, wifstopped, wstopsig, SigAction, SigInfo,
+    SignalFlags, EINTR, SIGCONT, SIGKILL, SIGSTOP, SIGTERM, SIGUSR1, SIG_BLOCK, SIG_UNBLOCK,
+};
+
+static HANDLED: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn handler(_signo: usize, _info: &SigInfo, _ucontext: usize) {
+    HANDLED.fetch_add(1, Ordering::SeqCst);
+}
+
+fn wait_child(pid: isize, expected: i32) {
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, expected);
+}
+
+/// A child which waits to be killed.
+fn idle_child(pgid: usize) -> isize {
+    let pid = fork();
+    if pid == 0 {
+        setpgid(0, pgid);
+        loop {
+            sleep(10);
+        }
+    }
+    setpgid(pid as usize, pgid);
+    pid
+}
+
+fn groups() {
+    let pid = getpid() as usize;
+    assert_eq!(setpgid(0, 0), 0);
+    assert_eq!(getpgid(0), pid as isize);
+    assert_eq!(getpgid(pid), pid as isize);
+    // a group must have someone in it already
+    assert!(setpgid(0, 99999) < 0);
+    assert!(killpg(99999, SIGTERM) < 0);
+}
+
+/// killpg reaches all of a group, and no one else.
+fn kill_group() {
+    let first = idle_child(0);
+    let second = idle_child(first as usize);
+    assert_eq!(getpgid(second as usize), first);
+    assert_eq!(killpg(first as usize, SIGTERM), 0);
+    wait_child(first, -(SIGTERM as i32));
+    wait_child(second, -(SIGTERM as i32));
+}
+
+/// kill with pid 0 reaches the caller's group, the caller too.
+fn kill_own_group() {
+    let action = SigAction::new(handler as usize, 0);
+    sigaction(SIGUSR1, Some(&action), None);
+    let set = SignalFlags::from_signum(SIGUSR1);
+    sigprocmask(SIG_BLOCK, Some(&set), None);
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(sigsuspend(&SignalFlags::empty()), EINTR);
+        exit(if HANDLED.load(Ordering::SeqCst) == 1 {
+            0
+        } else {
+            1
+        });
+    }
+    sleep(10);
+    assert_eq!(kill(0, SIGUSR1), 0);
+    sigprocmask(SIG_UNBLOCK, Some(&set), None);
+    assert_eq!(HANDLED.load(Ordering::SeqCst), 1);
+    wait_child(pid, 0);
+}
+
+/// waitpid_untraced tells of a stop once.
+fn stop() {
+    let pid = idle_child(0);
+    assert_eq!(kill(pid as usize, SIGSTOP), 0);
+    let mut status = 0;
+    assert_eq!(waitpid_untraced(pid as usize, &mut status), pid);
+    assert!(wifstopped(status));
+    assert_eq!(wstopsig(status), SIGSTOP);
+    assert_eq!(kill(pid as usize, SIGCONT), 0);
+    assert_eq!(kill(pid as usize, SIGKILL), 0);
+    wait_child(pid, -(SIGKILL as i32));
+}
+
+/// The foreground group of the console, if it has one yet.
+fn foreground() {
+    let old = tcgetpgrp(0);
+    if old < 0 {
+        return;
+    }
+    assert_eq!(tcsetpgrp(0, getpid() as usize), 0);
+    assert_eq!(tcgetpgrp(0), getpid());
+    assert!(tcsetpgrp(0, 99999) < 0);
+    assert_eq!(tcsetpgrp(0, old as usize), 0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    groups();
+    kill_group();
+    kill_own_group();
+    stop();
+    foreground();
+    println!("pgrp_test passed!");
+    0
+}
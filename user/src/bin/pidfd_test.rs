@@ -0,0 +1,93 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit, fcntl, fork, pidfd_open, pidfd_send_signal, pipe2, poll, read, sleep, waitid,
+    OpenFlags, PollFd, SigInfo, CLD_EXITED, EAGAIN, ECHILD, EINVAL, ESRCH, FD_CLOEXEC, F_GETFD,
+    F_GETFL, POLLIN, P_PID, P_PIDFD, SIGTERM, WEXITED, WNOHANG,
+};
+
+fn readable(fd: usize, timeout_ms: isize) -> bool {
+    let mut fds = [PollFd {
+        fd: fd as i32,
+        events: POLLIN,
+        revents: 0,
+    }];
+    poll(&mut fds, timeout_ms) == 1 && fds[0].revents & POLLIN != 0
+}
+
+/// pipe2 gives both ends the flags asked for.
+fn check_pipe2() {
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe2(&mut fds, OpenFlags::CLOEXEC | OpenFlags::NONBLOCK), 0);
+    for fd in fds {
+        assert_eq!(fcntl(fd, F_GETFD, 0), FD_CLOEXEC as isize);
+        assert_ne!(fcntl(fd, F_GETFL, 0) & OpenFlags::NONBLOCK.bits() as isize, 0);
+    }
+    let mut buf = [0u8; 4];
+    assert_eq!(read(fds[0], &mut buf), EAGAIN);
+    close(fds[0]);
+    close(fds[1]);
+    assert_eq!(pipe2(&mut fds, OpenFlags::TRUNC), EINVAL);
+}
+
+/// A pidfd signals its process, becomes readable when it exits and has
+/// waitid reap it, after which it refers to no process.
+fn check_signal() {
+    let pid = fork();
+    if pid == 0 {
+        loop {
+            sleep(10);
+        }
+    }
+    let pidfd = pidfd_open(pid as usize, OpenFlags::empty());
+    assert!(pidfd >= 0);
+    let pidfd = pidfd as usize;
+    assert_eq!(fcntl(pidfd, F_GETFD, 0), FD_CLOEXEC as isize);
+    assert!(!readable(pidfd, 0));
+    assert_eq!(pidfd_send_signal(pidfd, SIGTERM), 0);
+    assert!(readable(pidfd, -1));
+    let mut info = SigInfo::default();
+    assert_eq!(waitid(P_PIDFD, pidfd, Some(&mut info), WEXITED), 0);
+    assert_eq!(info.pid, pid as i32);
+    // reaped: nothing more to signal or wait for
+    assert!(readable(pidfd, 0));
+    assert_eq!(pidfd_send_signal(pidfd, SIGTERM), ESRCH);
+    assert_eq!(waitid(P_PIDFD, pidfd, None, WEXITED), ECHILD);
+    close(pidfd);
+}
+
+/// waitid with WNOHANG comes back at once, and without it waits for the
+/// exit code.
+fn check_wait() {
+    let pid = fork();
+    if pid == 0 {
+        sleep(50);
+        exit(7);
+    }
+    let pidfd = pidfd_open(pid as usize, OpenFlags::NONBLOCK) as usize;
+    let mut info = SigInfo::default();
+    info.pid = -1;
+    assert_eq!(waitid(P_PID, pid as usize, Some(&mut info), WEXITED | WNOHANG), 0);
+    assert_eq!(info.pid, 0);
+    assert_eq!(waitid(P_PID, pid as usize, None, WNOHANG), EINVAL);
+    assert_eq!(waitid(P_PIDFD, pidfd, Some(&mut info), WEXITED), 0);
+    assert_eq!(info.pid, pid as i32);
+    assert_eq!(info.code, CLD_EXITED);
+    assert_eq!(info.status, 7);
+    assert_eq!(waitid(P_PID, pid as usize, None, WEXITED), ECHILD);
+    close(pidfd);
+    assert_eq!(pidfd_open(pid as usize, OpenFlags::empty()), ESRCH);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    check_pipe2();
+    check_signal();
+    check_wait();
+    println!("pidfd_test passed!");
+    0
+}
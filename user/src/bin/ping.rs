@@ -0,0 +1,138 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+// ping [host] [count]: echo requests to the QEMU host by default, one a
+// second, each given a second for its reply
+
+use alloc::format;
+use user_lib::{
+    checksum, close, get_time, getaddrinfo, getpid, parse_ip, poll, recvfrom, sendto, sleep,
+    socket, PollFd, SockAddrIn, AF_INET, IPPROTO_ICMP, POLLIN, SOCK_RAW,
+};
+
+const ICMP_ECHOREPLY: u8 = 0;
+const ICMP_ECHO: u8 = 8;
+/// Data sent along, as much as ping of Linux does.
+const DATA_LEN: usize = 56;
+const TIMEOUT_MS: isize = 1000;
+
+/// An echo request of `id` and `seq`.
+fn echo_request(id: u16, seq: u16) -> [u8; 8 + DATA_LEN] {
+    let mut request = [0u8; 8 + DATA_LEN];
+    request[0] = ICMP_ECHO;
+    request[4..6].copy_from_slice(&id.to_be_bytes());
+    request[6..8].copy_from_slice(&seq.to_be_bytes());
+    for (i, byte) in request[8..].iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+    let sum = checksum(&request);
+    request[2..4].copy_from_slice(&sum.to_be_bytes());
+    request
+}
+
+/// The TTL and length of the ICMP message in `packet` if it is the reply
+/// to the request of `id` and `seq`.
+fn echo_reply(packet: &[u8], id: u16, seq: u16) -> Option<(u8, usize)> {
+    let ihl = (*packet.first()? & 0xf) as usize * 4;
+    let icmp = packet.get(ihl..)?;
+    if icmp.len() < 8
+        || icmp[0] != ICMP_ECHOREPLY
+        || icmp[4..6] != id.to_be_bytes()
+        || icmp[6..8] != seq.to_be_bytes()
+        || checksum(icmp) != 0
+    {
+        return None;
+    }
+    Some((packet[8], icmp.len()))
+}
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    let ip = match argc {
+        1 => Some([10, 0, 2, 2]),
+        _ => parse_ip(argv[1]).or_else(|| {
+            let mut addrs = [[0u8; 4]; 1];
+            match getaddrinfo(&format!("{}\0", argv[1]), &mut addrs) {
+                n if n > 0 => Some(addrs[0]),
+                n => {
+                    println!("ping: {}: cannot resolve: {}", argv[1], n);
+                    None
+                }
+            }
+        }),
+    };
+    let count: u16 = match argc {
+        0..=2 => Some(4),
+        _ => argv[2].parse().ok(),
+    }
+    .unwrap_or(0);
+    let ip = match ip {
+        Some(ip) if count > 0 => ip,
+        _ => {
+            println!("usage: ping [host] [count]");
+            return 2;
+        }
+    };
+    let fd = socket(AF_INET, SOCK_RAW, IPPROTO_ICMP);
+    if fd < 0 {
+        println!("ping: no raw socket: {}", fd);
+        return 2;
+    }
+    let fd = fd as usize;
+    let dest = SockAddrIn::new(ip, 0);
+    let id = getpid() as u16;
+    let mut received = 0;
+    println!(
+        "PING {}.{}.{}.{}: {} data bytes",
+        ip[0], ip[1], ip[2], ip[3], DATA_LEN
+    );
+    for seq in 0..count {
+        let start = get_time();
+        if sendto(fd, &echo_request(id, seq), Some(&dest)) < 0 {
+            println!("ping: sendto failed");
+            break;
+        }
+        let mut packet = [0u8; 1500];
+        let reply = loop {
+            let left = TIMEOUT_MS - (get_time() - start);
+            let mut fds = [PollFd::new(fd, POLLIN)];
+            if left <= 0 || poll(&mut fds, left) <= 0 {
+                break None;
+            }
+            let len = recvfrom(fd, &mut packet, None);
+            if len > 0 {
+                if let Some(reply) = echo_reply(&packet[..len as usize], id, seq) {
+                    break Some(reply);
+                }
+            }
+        };
+        let rtt = get_time() - start;
+        match reply {
+            Some((ttl, len)) => {
+                received += 1;
+                println!(
+                    "{} bytes from {}.{}.{}.{}: icmp_seq={} ttl={} time={} ms",
+                    len, ip[0], ip[1], ip[2], ip[3], seq, ttl, rtt
+                );
+            }
+            None => println!("Request timeout for icmp_seq {}", seq),
+        }
+        if seq + 1 < count && rtt < TIMEOUT_MS {
+            sleep((TIMEOUT_MS - rtt) as usize);
+        }
+    }
+    close(fd);
+    println!(
+        "{} packets transmitted, {} packets received",
+        count, received
+    );
+    if received > 0 {
+        0
+    } else {
+        1
+    }
+}
@@ -0,0 +1,109 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, fcntl, lseek, open, pipe, read, readv, splice, unlink, write, writev, IoVec, OpenFlags,
+    EAGAIN, EBUSY, F_GETPIPE_SZ, F_SETPIPE_SZ, SEEK_SET, SPLICE_F_NONBLOCK,
+};
+
+const PATH: &str = "pipe_splice_test\0";
+
+fn check_size() {
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    assert_eq!(fcntl(fds[0], F_GETPIPE_SZ, 0), 16384);
+    // rounded up to a page, and both ends see it
+    assert_eq!(fcntl(fds[1], F_SETPIPE_SZ, 5000), 8192);
+    assert_eq!(fcntl(fds[0], F_GETPIPE_SZ, 0), 8192);
+    let data = [7u8; 8192];
+    assert_eq!(write(fds[1], &data), 8192);
+    // what is in it does not fit a page
+    assert_eq!(fcntl(fds[1], F_SETPIPE_SZ, 4096), EBUSY);
+    assert_eq!(fcntl(fds[1], F_SETPIPE_SZ, 65536), 65536);
+    assert_eq!(write(fds[1], &data), 8192);
+    let mut buf = [0u8; 16384];
+    assert_eq!(read(fds[0], &mut buf), 16384);
+    assert!(buf.iter().all(|byte| *byte == 7));
+    close(fds[0]);
+    close(fds[1]);
+}
+
+fn check_vectored() {
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let mut first = *b"hello, ";
+    let mut second = *b"world";
+    let iov = [
+        IoVec {
+            base: first.as_mut_ptr(),
+            len: first.len(),
+        },
+        IoVec {
+            base: second.as_mut_ptr(),
+            len: second.len(),
+        },
+    ];
+    assert_eq!(writev(fds[1], &iov), 12);
+    let mut head = [0u8; 5];
+    let mut rest = [0u8; 16];
+    let iov = [
+        IoVec {
+            base: head.as_mut_ptr(),
+            len: head.len(),
+        },
+        IoVec {
+            base: rest.as_mut_ptr(),
+            len: rest.len(),
+        },
+    ];
+    assert_eq!(readv(fds[0], &iov), 12);
+    assert_eq!(&head, b"hello");
+    assert_eq!(&rest[..7], b", world");
+    close(fds[0]);
+    close(fds[1]);
+}
+
+fn check_splice() {
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let file = open(PATH, OpenFlags::CREATE | OpenFlags::RDWR | OpenFlags::TRUNC);
+    assert!(file >= 0);
+    let file = file as usize;
+
+    // at the file's offset, which moves on
+    assert_eq!(write(fds[1], b"0123456789"), 10);
+    assert_eq!(splice(fds[0], file, None, 4, 0), 4);
+    assert_eq!(splice(fds[0], file, None, 100, 0), 6);
+    // at an offset of our own, the file's left alone
+    assert_eq!(write(fds[1], b"abc"), 3);
+    let mut offset = 2i64;
+    assert_eq!(splice(fds[0], file, Some(&mut offset), 3, 0), 3);
+    assert_eq!(offset, 5);
+    // nothing there, and no waiting
+    assert_eq!(splice(fds[0], file, None, 1, SPLICE_F_NONBLOCK), EAGAIN);
+    // the writer gone, the end of the pipe
+    close(fds[1]);
+    assert_eq!(splice(fds[0], file, None, 1, 0), 0);
+    // only a pipe is spliced from
+    assert!(splice(file, fds[0], None, 1, 0) < 0);
+
+    assert_eq!(lseek(file, 0, SEEK_SET), 0);
+    let mut buf = [0u8; 16];
+    assert_eq!(read(file, &mut buf), 10);
+    assert_eq!(&buf[..10], b"01abc56789");
+    close(fds[0]);
+    close(file);
+    assert_eq!(unlink(PATH), 0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    check_size();
+    check_vectored();
+    check_splice();
+    println!("pipe_splice_test passed!");
+    0
+}
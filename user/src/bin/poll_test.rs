@@ -0,0 +1,158 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, epoll_create1, epoll_ctl, epoll_wait, exit, fork, get_time, pipe, poll, read, sleep,
+    waitpid, write, EpollEvent, OpenFlags, PollFd,
+};
+use user_lib::{EPOLLHUP, EPOLLIN, EPOLL_CTL_ADD, EPOLL_CTL_DEL, EPOLL_CTL_MOD};
+use user_lib::{POLLHUP, POLLIN, POLLNVAL, POLLOUT};
+
+fn wait_child(pid: isize) {
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+}
+
+fn make_pipe() -> (usize, usize) {
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    (pipe_fd[0], pipe_fd[1])
+}
+
+/// A child which writes `data` to `fd` after `ms`.
+fn write_later(fd: usize, ms: usize, data: &[u8]) -> isize {
+    let pid = fork();
+    if pid == 0 {
+        sleep(ms);
+        assert_eq!(write(fd, data), data.len() as isize);
+        exit(0);
+    }
+    pid
+}
+
+fn polls() {
+    let (read_end, write_end) = make_pipe();
+    let mut fds = [
+        PollFd::new(read_end, POLLIN),
+        PollFd::new(write_end, POLLOUT),
+    ];
+    assert_eq!(poll(&mut fds, 0), 1);
+    assert_eq!((fds[0].revents, fds[1].revents), (0, POLLOUT));
+
+    // nothing to read within the time
+    let start = get_time();
+    assert_eq!(poll(&mut fds[..1], 50), 0);
+    assert!(get_time() - start >= 50);
+
+    // until a writer comes
+    let pid = write_later(write_end, 30, b"x");
+    assert_eq!(poll(&mut fds[..1], -1), 1);
+    assert_eq!(fds[0].revents, POLLIN);
+    wait_child(pid);
+    let mut buf = [0u8; 4];
+    assert_eq!(read(read_end, &mut buf), 1);
+
+    // fds not open, and those skipped
+    let mut fds = [
+        PollFd::new(read_end, POLLIN),
+        PollFd::new(100, POLLIN),
+        PollFd {
+            fd: -1,
+            events: POLLIN,
+            revents: 0,
+        },
+    ];
+    assert_eq!(poll(&mut fds, 0), 1);
+    assert_eq!(
+        (fds[0].revents, fds[1].revents, fds[2].revents),
+        (0, POLLNVAL, 0)
+    );
+
+    // the reader is told when the writers are gone, the writer when the
+    // readers are
+    close(write_end);
+    let mut fds = [PollFd::new(read_end, POLLIN)];
+    assert_eq!(poll(&mut fds, -1), 1);
+    assert!(fds[0].revents & POLLHUP != 0);
+    close(read_end);
+    let (read_end, write_end) = make_pipe();
+    close(read_end);
+    let mut fds = [PollFd::new(write_end, POLLOUT)];
+    assert_eq!(poll(&mut fds, 0), 1);
+    assert!(fds[0].revents & POLLOUT != 0);
+    close(write_end);
+}
+
+fn epolls() {
+    let epfd = epoll_create1(OpenFlags::CLOEXEC);
+    assert!(epfd > 0);
+    let epfd = epfd as usize;
+    let (read_end, write_end) = make_pipe();
+    let (other_read, other_write) = make_pipe();
+    let event = |events, data| EpollEvent { events, data };
+    assert_eq!(
+        epoll_ctl(epfd, EPOLL_CTL_ADD, read_end, &event(EPOLLIN, 7)),
+        0
+    );
+    assert_eq!(
+        epoll_ctl(epfd, EPOLL_CTL_ADD, read_end, &event(EPOLLIN, 7)),
+        -1
+    );
+    assert_eq!(
+        epoll_ctl(epfd, EPOLL_CTL_ADD, other_read, &event(EPOLLIN, 8)),
+        0
+    );
+    // an epoll in itself
+    assert_eq!(epoll_ctl(epfd, EPOLL_CTL_ADD, epfd, &event(EPOLLIN, 0)), -1);
+    assert_eq!(
+        epoll_ctl(epfd, EPOLL_CTL_MOD, write_end, &event(EPOLLIN, 0)),
+        -1
+    );
+
+    let mut events = [EpollEvent::default(); 4];
+    assert_eq!(epoll_wait(epfd, &mut events, 0), 0);
+    let pid = write_later(other_write, 30, b"y");
+    assert_eq!(epoll_wait(epfd, &mut events, -1), 1);
+    assert_eq!((events[0].events, events[0].data), (EPOLLIN, 8));
+    wait_child(pid);
+
+    // what is asked and handed back may change
+    assert_eq!(
+        epoll_ctl(epfd, EPOLL_CTL_MOD, other_read, &event(EPOLLIN, 9)),
+        0
+    );
+    assert_eq!(write(write_end, b"z"), 1);
+    assert_eq!(epoll_wait(epfd, &mut events, 0), 2);
+    let mut data = [events[0].data, events[1].data];
+    data.sort();
+    assert_eq!(data, [7, 9]);
+    assert_eq!(epoll_wait(epfd, &mut events[..1], 0), 1);
+
+    // removed, or closed, a file is not waited for
+    assert_eq!(epoll_ctl(epfd, EPOLL_CTL_DEL, other_read, &event(0, 0)), 0);
+    assert_eq!(epoll_ctl(epfd, EPOLL_CTL_DEL, other_read, &event(0, 0)), -1);
+    let mut buf = [0u8; 4];
+    assert_eq!(read(read_end, &mut buf), 1);
+    assert_eq!(epoll_wait(epfd, &mut events, 50), 0);
+    close(write_end);
+    assert_eq!(epoll_wait(epfd, &mut events, 0), 1);
+    assert!(events[0].events & EPOLLHUP != 0);
+    close(read_end);
+    assert_eq!(epoll_wait(epfd, &mut events, 0), 0);
+
+    close(other_read);
+    close(other_write);
+    close(epfd);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    polls();
+    epolls();
+    println!("poll_test passed!");
+    0
+}
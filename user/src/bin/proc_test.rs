@@ -0,0 +1,86 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use user_lib::{close, exit, fork, getpid, open, read, stat, waitpid, yield_};
+use user_lib::{OpenFlags, Stat};
+
+/// The whole of the file at `path`, read in small pieces as it would be by
+/// a tool, or None if there is no such file.
+fn read_file(path: &str) -> Option<String> {
+    let fd = open(path, OpenFlags::RDONLY);
+    if fd < 0 {
+        return None;
+    }
+    let mut text = String::new();
+    let mut buf = [0u8; 16];
+    loop {
+        let len = read(fd as usize, &mut buf);
+        if len <= 0 {
+            break;
+        }
+        text.push_str(core::str::from_utf8(&buf[..len as usize]).unwrap());
+    }
+    close(fd as usize);
+    Some(text)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let status = read_file("/proc/self/status\0").unwrap();
+    assert!(status.contains("Name:\tproc_test\n"));
+    assert!(status.contains(&format!("Pid:\t{}\n", getpid())));
+    assert!(status.contains("State:\tR (running)\n"));
+    assert!(status.contains("Threads:\t1\n"));
+    assert_eq!(
+        read_file(&format!("/proc/{}/status\0", getpid())).map(|s| s.contains("VmRSS:")),
+        Some(true)
+    );
+
+    let meminfo = read_file("/proc/meminfo\0").unwrap();
+    assert!(meminfo.starts_with("MemTotal:"));
+    assert!(meminfo.contains("MemFree:"));
+    let interrupts = read_file("/proc/interrupts\0").unwrap();
+    assert!(interrupts.contains("LOC:"));
+    assert!(interrupts.contains("virtio-blk"));
+    let ktasks = read_file("/proc/ktasks\0").unwrap();
+    assert!(ktasks.contains("kswapd"));
+    assert!(ktasks.contains("flusher"));
+    assert!(ktasks.contains("netd"));
+    let netdev = read_file("/proc/netdev\0").unwrap();
+    assert!(netdev.contains("    lo:"));
+    // one line, empty without a command line
+    let cmdline = read_file("/proc/cmdline\0").unwrap();
+    assert!(cmdline.ends_with('\n') && cmdline.lines().count() <= 1);
+
+    // read-only
+    let mut st = Stat::default();
+    assert_eq!(stat("/proc/meminfo\0", &mut st), 0);
+    assert!(st.is_file());
+    assert_eq!(st.size, 0);
+    assert_eq!(open("/proc/meminfo\0", OpenFlags::WRONLY), -1);
+    assert_eq!(open("/proc/self/status\0", OpenFlags::RDWR), -1);
+
+    // a process has a directory only while it runs
+    let pid = fork();
+    if pid == 0 {
+        for _ in 0..10 {
+            yield_();
+        }
+        exit(0);
+    }
+    let child_status = format!("/proc/{}/status\0", pid);
+    let text = read_file(&child_status).unwrap();
+    assert!(text.contains(&format!("PPid:\t{}\n", getpid())));
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert!(read_file(&child_status).is_none());
+    assert!(read_file("/proc/100000/status\0").is_none());
+    println!("proc_test passed!");
+    0
+}
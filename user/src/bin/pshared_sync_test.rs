@@ -0,0 +1,159 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicU32, Ordering};
+use user_lib::{
+    condvar_open, condvar_signal, condvar_wait, exit, fork, futex_wait, futex_wake, mmap,
+    mutex_lock, mutex_open, mutex_unlock, munmap, sleep, sync_unlink, waitpid, yield_,
+    SharedCondvar, SharedMutex, EAGAIN, ENOENT, ETIMEDOUT, MAP_ANONYMOUS, MAP_SHARED, PROT_READ,
+    PROT_WRITE,
+};
+
+const CHILDREN: usize = 4;
+const ROUNDS: usize = 500;
+const NAME: &str = "pshared_sync_test\0";
+
+/// What the processes share, at the start of a MAP_SHARED mapping.
+#[repr(C)]
+struct Shared {
+    mutex: SharedMutex,
+    condvar: SharedCondvar,
+    counter: usize,
+    ready: bool,
+    woken: AtomicU32,
+    word: AtomicU32,
+}
+
+fn wait_all(pids: &[isize]) {
+    for &pid in pids {
+        let mut exit_code: i32 = 0;
+        assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+        assert_eq!(exit_code, 0);
+    }
+}
+
+/// Children add up under the futex mutex, racing without it.
+fn check_mutex(shared: &mut Shared) {
+    let mut pids = [0isize; CHILDREN];
+    for pid in pids.iter_mut() {
+        *pid = fork();
+        if *pid == 0 {
+            for round in 0..ROUNDS {
+                shared.mutex.lock();
+                let counter = unsafe { core::ptr::read_volatile(&shared.counter) };
+                if round % 16 == 0 {
+                    yield_();
+                }
+                unsafe { core::ptr::write_volatile(&mut shared.counter, counter + 1) };
+                shared.mutex.unlock();
+            }
+            exit(0);
+        }
+    }
+    wait_all(&pids);
+    assert_eq!(shared.counter, CHILDREN * ROUNDS);
+}
+
+/// Children wait on the futex condvar until told they are ready.
+fn check_condvar(shared: &mut Shared) {
+    let mut pids = [0isize; CHILDREN];
+    for pid in pids.iter_mut() {
+        *pid = fork();
+        if *pid == 0 {
+            shared.mutex.lock();
+            while !unsafe { core::ptr::read_volatile(&shared.ready) } {
+                shared.condvar.wait(&shared.mutex);
+            }
+            shared.mutex.unlock();
+            shared.woken.fetch_add(1, Ordering::AcqRel);
+            exit(0);
+        }
+    }
+    sleep(20);
+    assert_eq!(shared.woken.load(Ordering::Acquire), 0);
+    shared.mutex.lock();
+    unsafe { core::ptr::write_volatile(&mut shared.ready, true) };
+    shared.condvar.broadcast();
+    shared.mutex.unlock();
+    wait_all(&pids);
+    assert_eq!(shared.woken.load(Ordering::Acquire), CHILDREN as u32);
+}
+
+/// A wait of another value, a timeout, and a wake across processes.
+fn check_futex(shared: &Shared) {
+    let word = &shared.word;
+    assert_eq!(futex_wait(word, 1, None), EAGAIN);
+    assert_eq!(futex_wait(word, 0, Some(10)), ETIMEDOUT);
+    assert_eq!(futex_wake(word, 1), 0);
+    let pid = fork();
+    if pid == 0 {
+        while word.load(Ordering::Acquire) == 0 {
+            futex_wait(word, 0, None);
+        }
+        exit(0);
+    }
+    sleep(20);
+    word.store(1, Ordering::Release);
+    // the child is waiting, unless it was not scheduled yet
+    assert!(futex_wake(word, 1) <= 1);
+    wait_all(&[pid]);
+}
+
+/// A named mutex held by the parent blocks a child which opened it too,
+/// and a named condvar wakes the parent from the child.
+fn check_named(shared: &mut Shared) {
+    let mutex = mutex_open(NAME, true);
+    let condvar = condvar_open(NAME);
+    assert!(mutex >= 0 && condvar >= 0);
+    shared.ready = false;
+    mutex_lock(mutex as usize);
+    let pid = fork();
+    if pid == 0 {
+        let mutex = mutex_open(NAME, true) as usize;
+        let condvar = condvar_open(NAME) as usize;
+        mutex_lock(mutex);
+        unsafe { core::ptr::write_volatile(&mut shared.ready, true) };
+        condvar_signal(condvar);
+        mutex_unlock(mutex);
+        exit(0);
+    }
+    sleep(20);
+    assert!(!unsafe { core::ptr::read_volatile(&shared.ready) });
+    while !unsafe { core::ptr::read_volatile(&shared.ready) } {
+        condvar_wait(condvar as usize, mutex as usize);
+    }
+    mutex_unlock(mutex as usize);
+    wait_all(&[pid]);
+    assert_eq!(sync_unlink(NAME), 0);
+    assert_eq!(sync_unlink(NAME), ENOENT);
+}
+
+/// Processes sharing memory synchronize with futex mutexes and condvars
+/// placed in it, and with mutexes and condvars they open by name.
+#[no_mangle]
+pub fn main() -> i32 {
+    let size = core::mem::size_of::<Shared>();
+    let addr = mmap(0, size, PROT_READ | PROT_WRITE, MAP_SHARED | MAP_ANONYMOUS, 0, 0);
+    assert!(addr > 0);
+    let shared = unsafe { &mut *(addr as *mut Shared) };
+    unsafe {
+        (shared as *mut Shared).write(Shared {
+            mutex: SharedMutex::new(),
+            condvar: SharedCondvar::new(),
+            counter: 0,
+            ready: false,
+            woken: AtomicU32::new(0),
+            word: AtomicU32::new(0),
+        })
+    };
+    check_mutex(shared);
+    check_condvar(shared);
+    check_futex(shared);
+    check_named(shared);
+    assert_eq!(munmap(addr as usize, size), 0);
+    println!("pshared_sync_test passed!");
+    0
+}
@@ -0,0 +1,25 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{reboot, REBOOT_CMD_HALT, REBOOT_CMD_POWER_OFF, REBOOT_CMD_RESTART};
+
+/// reboot [-p | -h]: sync the disks and restart, or power off with -p, or
+/// halt with -h. Only root may.
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    let cmd = match (argc, argv.get(1).copied()) {
+        (1, _) => REBOOT_CMD_RESTART,
+        (2, Some("-p")) => REBOOT_CMD_POWER_OFF,
+        (2, Some("-h")) => REBOOT_CMD_HALT,
+        _ => {
+            println!("usage: reboot [-p | -h]");
+            return -1;
+        }
+    };
+    let error = reboot(cmd);
+    println!("reboot: failed with {}", error);
+    -1
+}
@@ -0,0 +1,29 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{assert_root_only, raw_syscall, reboot, EINVAL};
+use user_lib::{REBOOT_CMD_POWER_OFF, REBOOT_MAGIC1, REBOOT_MAGIC2};
+
+const SYSCALL_REBOOT: usize = 142;
+
+fn sys_reboot(magic1: usize, magic2: usize, cmd: usize) -> isize {
+    raw_syscall(SYSCALL_REBOOT, [magic1, magic2, cmd, 0, 0, 0])
+}
+
+/// Everything sys_reboot refuses, leaving the system up; that it goes
+/// down is left to trying it.
+#[no_mangle]
+pub fn main() -> i32 {
+    // wrong magic numbers, or no command
+    assert_eq!(sys_reboot(0, REBOOT_MAGIC2, REBOOT_CMD_POWER_OFF), EINVAL);
+    assert_eq!(sys_reboot(REBOOT_MAGIC1, 0, REBOOT_CMD_POWER_OFF), EINVAL);
+    assert_eq!(reboot(0), EINVAL);
+    assert_eq!(reboot(0x89ab_cdef), EINVAL);
+
+    assert_root_only(|| reboot(REBOOT_CMD_POWER_OFF));
+    println!("reboot_test passed!");
+    0
+}
@@ -0,0 +1,133 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::mem::{size_of, size_of_val};
+use user_lib::{close, exec, exit, fork, mmap, open, read, waitpid, write, OpenFlags};
+use user_lib::{replay_load, replay_read, replay_record, replay_stop, ReplayEvent};
+use user_lib::{EIO, MAP_ANONYMOUS, MAP_PRIVATE, PROT_READ, PROT_WRITE, REPLAY_MAX_EVENTS};
+
+/// Run `argv`, the program first, and wait for it; its exit code.
+fn run(argv: &[&str]) -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        // the arguments end with the NUL the kernel put after them
+        let mut args: Vec<*const u8> = argv.iter().map(|arg| arg.as_ptr()).collect();
+        args.push(core::ptr::null());
+        exec(argv[0], &args);
+        exit(-1);
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    exit_code
+}
+
+/// Room for the largest log, mapped rather than on the heap.
+fn log_buffer() -> &'static mut [ReplayEvent] {
+    let len = REPLAY_MAX_EVENTS * size_of::<ReplayEvent>();
+    let addr = mmap(
+        0,
+        len,
+        PROT_READ | PROT_WRITE,
+        MAP_PRIVATE | MAP_ANONYMOUS,
+        0,
+        0,
+    );
+    assert!(addr > 0);
+    unsafe { core::slice::from_raw_parts_mut(addr as *mut ReplayEvent, REPLAY_MAX_EVENTS) }
+}
+
+fn as_bytes(events: &[ReplayEvent]) -> &[u8] {
+    unsafe { core::slice::from_raw_parts(events.as_ptr() as *const u8, size_of_val(events)) }
+}
+
+fn record(path: &str, argv: &[&str]) -> i32 {
+    if replay_record() < 0 {
+        println!("replay: only root may record, and one thing at a time");
+        return -1;
+    }
+    let exit_code = run(argv);
+    let count = replay_stop();
+    let events = log_buffer();
+    let mut len = 0;
+    while len < count as usize {
+        match replay_read(&mut events[len..], len) {
+            copied if copied > 0 => len += copied as usize,
+            _ => break,
+        }
+    }
+    let fd = open(path, OpenFlags::WRONLY | OpenFlags::CREATE | OpenFlags::TRUNC);
+    if fd < 0 {
+        println!("replay: cannot write {}", path);
+        return -1;
+    }
+    write(fd as usize, as_bytes(&events[..len]));
+    close(fd as usize);
+    println!(
+        "replay: {} exited with {}, {} events in {}",
+        argv[0], exit_code, len, path
+    );
+    0
+}
+
+fn replay(path: &str, argv: &[&str]) -> i32 {
+    let fd = open(path, OpenFlags::RDONLY);
+    if fd < 0 {
+        println!("replay: cannot read {}", path);
+        return -1;
+    }
+    let events = log_buffer();
+    let bytes = unsafe {
+        core::slice::from_raw_parts_mut(events.as_mut_ptr() as *mut u8, size_of_val(events))
+    };
+    let mut len = 0;
+    loop {
+        let n = read(fd as usize, &mut bytes[len..]);
+        if n <= 0 {
+            break;
+        }
+        len += n as usize;
+    }
+    close(fd as usize);
+    if replay_load(&events[..len / size_of::<ReplayEvent>()]) < 0 {
+        println!("replay: {} is no log, or something is recorded already", path);
+        return -1;
+    }
+    let exit_code = run(argv);
+    match replay_stop() {
+        EIO => {
+            println!("replay: diverged, see dmesg");
+            -1
+        }
+        replayed => {
+            println!(
+                "replay: {} exited with {}, {} events replayed",
+                argv[0], exit_code, replayed
+            );
+            0
+        }
+    }
+}
+
+/// replay record LOG PROGRAM [ARGS...]: run the program recording the
+/// scheduling of the kernel into LOG; replay run LOG PROGRAM [ARGS...]:
+/// run it again as LOG says, for a race it lost to be lost again.
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc < 4 {
+        println!("usage: replay record|run LOG PROGRAM [ARGS...]");
+        return -1;
+    }
+    match argv[1] {
+        "record" => record(argv[2], &argv[3..]),
+        "run" => replay(argv[2], &argv[3..]),
+        _ => {
+            println!("usage: replay record|run LOG PROGRAM [ARGS...]");
+            -1
+        }
+    }
+}
@@ -0,0 +1,102 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{assert_root_only, close, exit, fork, mmap, pipe, read, sleep, waitpid, write};
+use user_lib::{replay_load, replay_read, replay_record, replay_stop, yield_, ReplayEvent};
+use user_lib::{EBUSY, EINVAL, EIO, MAP_ANONYMOUS, MAP_PRIVATE, PROT_READ, PROT_WRITE};
+
+const ROUNDS: usize = 20;
+/// Times to try, as a timer of the kernel's own due meanwhile makes a
+/// replay diverge.
+const TRIES: usize = 3;
+
+/// Two children passing a token back and forth through pipes, yielding
+/// and sleeping on the way, for the scheduling to be worth recording.
+fn ping_pong() {
+    let (mut there, mut back) = ([0usize; 2], [0usize; 2]);
+    assert_eq!(pipe(&mut there), 0);
+    assert_eq!(pipe(&mut back), 0);
+    let pid = fork();
+    if pid == 0 {
+        let mut token = [0u8; 1];
+        for _ in 0..ROUNDS {
+            assert_eq!(read(there[0], &mut token), 1);
+            token[0] += 1;
+            yield_();
+            assert_eq!(write(back[1], &token), 1);
+        }
+        exit(token[0] as i32);
+    }
+    let mut token = [0u8; 1];
+    for round in 0..ROUNDS {
+        assert_eq!(write(there[1], &token), 1);
+        if round % 5 == 0 {
+            sleep(2);
+        }
+        assert_eq!(read(back[0], &mut token), 1);
+    }
+    for fd in there.iter().chain(back.iter()) {
+        close(*fd);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, ROUNDS as i32);
+}
+
+/// Record ping_pong and replay it; whether the replay went as recorded.
+fn record_and_replay(events: &mut [ReplayEvent]) -> bool {
+    assert_eq!(replay_record(), 0);
+    ping_pong();
+    let recorded = replay_stop();
+    assert!(recorded > 0);
+    let recorded = recorded as usize;
+    assert!(recorded <= events.len());
+    assert_eq!(replay_read(events, 0), recorded as isize);
+    // from the middle on, as the recording is long enough
+    assert_eq!(replay_read(&mut events[1..], 1), recorded as isize - 1);
+
+    assert_eq!(replay_load(&events[..recorded]), 0);
+    ping_pong();
+    match replay_stop() {
+        EIO => false,
+        replayed => {
+            assert_eq!(replayed, recorded as isize);
+            true
+        }
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_root_only(replay_record);
+
+    assert_eq!(replay_record(), 0);
+    assert_eq!(replay_record(), EBUSY);
+    assert!(replay_stop() >= 0);
+    let bad = [ReplayEvent {
+        kind: 99,
+        a: 0,
+        b: 0,
+    }];
+    assert_eq!(replay_load(&bad), EINVAL);
+    assert_eq!(replay_load(&[]), EINVAL);
+
+    let count = 1 << 14;
+    let addr = mmap(
+        0,
+        count * core::mem::size_of::<ReplayEvent>(),
+        PROT_READ | PROT_WRITE,
+        MAP_PRIVATE | MAP_ANONYMOUS,
+        0,
+        0,
+    );
+    assert!(addr > 0);
+    let events = unsafe { core::slice::from_raw_parts_mut(addr as *mut ReplayEvent, count) };
+    let replayed = (0..TRIES).any(|_| record_and_replay(events));
+    assert!(replayed);
+    println!("replay_test passed!");
+    0
+}
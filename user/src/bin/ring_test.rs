@@ -0,0 +1,101 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::convert::TryInto;
+use user_lib::{exit, fork, ring_create, waitpid, OpenFlags, Ring};
+
+const MESSAGES: u32 = 1000;
+
+fn wait_child(pid: isize) {
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+}
+
+/// Send `MESSAGES` messages of `tag` and their number from a child.
+fn spawn_producer(ring: Ring, tag: u8) -> isize {
+    let pid = fork();
+    if pid == 0 {
+        for i in 0..MESSAGES {
+            let mut msg = [tag; 5];
+            msg[1..].copy_from_slice(&i.to_ne_bytes());
+            ring.send(&msg);
+        }
+        exit(0);
+    }
+    pid
+}
+
+fn check_single() {
+    let ring = ring_create(8, 64, OpenFlags::empty()).unwrap();
+    let mut buf = [0u8; 64];
+    assert_eq!(ring.try_receive(&mut buf), None);
+    for i in 0..8u8 {
+        assert!(ring.try_send(&[i; 3]));
+    }
+    assert!(!ring.try_send(b"full"));
+    // a second mapping of the ring sees the same messages
+    let other = Ring::from_fds(ring.consumer, ring.producer).unwrap();
+    assert_eq!(other.entry_size(), 64);
+    for i in 0..8u8 {
+        assert_eq!(other.try_receive(&mut buf), Some(3));
+        assert_eq!(&buf[..3], &[i; 3]);
+    }
+    assert_eq!(ring.try_receive(&mut buf), None);
+
+    // eight slots, so the producer waits on the doorbell again and again
+    let pid = spawn_producer(ring, 1);
+    for i in 0..MESSAGES {
+        assert_eq!(ring.receive(&mut buf), 5);
+        assert_eq!(buf[0], 1);
+        assert_eq!(u32::from_ne_bytes(buf[1..5].try_into().unwrap()), i);
+    }
+    wait_child(pid);
+    ring.close();
+}
+
+fn check_multiple() {
+    let ring = ring_create(4, 8, OpenFlags::empty()).unwrap();
+    let producers = [spawn_producer(ring, 0), spawn_producer(ring, 1)];
+    // a second consumer takes some, each sees a producer's in order
+    let consumer = fork();
+    if consumer == 0 {
+        let mut next = [0u32; 2];
+        let mut buf = [0u8; 8];
+        for _ in 0..MESSAGES {
+            assert_eq!(ring.receive(&mut buf), 5);
+            let i = u32::from_ne_bytes(buf[1..5].try_into().unwrap());
+            assert!(i >= next[buf[0] as usize]);
+            next[buf[0] as usize] = i + 1;
+        }
+        exit(0);
+    }
+    let mut next = [0u32; 2];
+    let mut buf = [0u8; 8];
+    for _ in 0..MESSAGES {
+        assert_eq!(ring.receive(&mut buf), 5);
+        let i = u32::from_ne_bytes(buf[1..5].try_into().unwrap());
+        assert!(i >= next[buf[0] as usize]);
+        next[buf[0] as usize] = i + 1;
+    }
+    for pid in producers {
+        wait_child(pid);
+    }
+    wait_child(consumer);
+    let mut buf = [0u8; 8];
+    assert_eq!(ring.try_receive(&mut buf), None);
+    ring.close();
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert!(ring_create(6, 64, OpenFlags::empty()).is_none());
+    assert!(ring_create(8, 12, OpenFlags::empty()).is_none());
+    check_single();
+    check_multiple();
+    println!("ring_test passed!");
+    0
+}
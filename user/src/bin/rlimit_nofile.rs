@@ -0,0 +1,59 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit, fork, getpid, getrlimit, pipe, prlimit, setrlimit, setuid, waitpid, RLimit,
+    RLIMIT_NOFILE,
+};
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut old = RLimit::default();
+    assert_eq!(getrlimit(RLIMIT_NOFILE, &mut old), 0);
+    // the hard limit can never be raised
+    let raised = RLimit {
+        rlim_cur: old.rlim_cur,
+        rlim_max: old.rlim_max + 1,
+    };
+    assert_eq!(setrlimit(RLIMIT_NOFILE, &raised), -1);
+    // nor the soft one above it, EINVAL
+    let inverted = RLimit {
+        rlim_cur: old.rlim_max + 1,
+        rlim_max: old.rlim_max,
+    };
+    assert_eq!(setrlimit(RLIMIT_NOFILE, &inverted), -22);
+    // no such resource, EINVAL, and no such process, ESRCH
+    assert_eq!(getrlimit(16, &mut RLimit::default()), -22);
+    assert_eq!(prlimit(0x7fff_ffff, RLIMIT_NOFILE, None, None), -3);
+    // stdin/stdout/stderr + one pipe
+    let limit = RLimit {
+        rlim_cur: 5,
+        rlim_max: old.rlim_max,
+    };
+    assert_eq!(setrlimit(RLIMIT_NOFILE, &limit), 0);
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    assert_eq!(pipe(&mut [0usize; 2]), -1);
+    close(pipe_fd[0]);
+    close(pipe_fd[1]);
+    // the soft limit can be raised up to the hard one
+    assert_eq!(setrlimit(RLIMIT_NOFILE, &old), 0);
+    // the limits of other users are for root alone, EPERM
+    let pid = getpid() as usize;
+    let child = fork();
+    if child == 0 {
+        assert_eq!(setuid(1000), 0);
+        let mut limit = RLimit::default();
+        assert_eq!(prlimit(pid, RLIMIT_NOFILE, None, Some(&mut limit)), -1);
+        assert_eq!(prlimit(0, RLIMIT_NOFILE, None, Some(&mut limit)), 0);
+        exit(0);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(child as usize, &mut exit_code), child);
+    assert_eq!(exit_code, 0);
+    println!("rlimit_nofile passed!");
+    0
+}
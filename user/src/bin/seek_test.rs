@@ -0,0 +1,131 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, exit, fork, lseek, open, pipe, pread, pwrite, read, unlink, wait, write};
+use user_lib::{OpenFlags, SEEK_CUR, SEEK_END, SEEK_SET};
+
+const WRITERS: usize = 4;
+const RECORDS: usize = 50;
+const RECORD_LEN: usize = 8;
+
+/// Check that `path` holds RECORDS whole records of each writer.
+fn check_records(path: &str) {
+    let fd = open(path, OpenFlags::RDONLY) as usize;
+    assert_eq!(
+        lseek(fd, 0, SEEK_END),
+        (WRITERS * RECORDS * RECORD_LEN) as isize
+    );
+    let mut counts = [0usize; WRITERS];
+    let mut record = [0u8; RECORD_LEN];
+    for i in 0..WRITERS * RECORDS {
+        assert_eq!(pread(fd, &mut record, i * RECORD_LEN), RECORD_LEN as isize);
+        let id = (record[0] - b'0') as usize;
+        assert!(record[..RECORD_LEN - 1].iter().all(|ch| *ch == record[0]));
+        counts[id] += 1;
+    }
+    assert!(counts.iter().all(|count| *count == RECORDS));
+    close(fd);
+}
+
+/// Have WRITERS children write their records through `fd`, or through a
+/// file of their own opened with O_APPEND if `fd` is None.
+fn write_records(path: &str, fd: Option<usize>) {
+    for id in 0..WRITERS {
+        if fork() == 0 {
+            let fd = fd.unwrap_or_else(|| {
+                open(path, OpenFlags::WRONLY | OpenFlags::APPEND) as usize
+            });
+            let mut record = [b'0' + id as u8; RECORD_LEN];
+            record[RECORD_LEN - 1] = b'\n';
+            for _ in 0..RECORDS {
+                assert_eq!(write(fd, &record), RECORD_LEN as isize);
+            }
+            close(fd);
+            exit(0);
+        }
+    }
+    let mut exit_code = 0;
+    for _ in 0..WRITERS {
+        assert!(wait(&mut exit_code) > 0);
+        assert_eq!(exit_code, 0);
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let path = "/seek_test_file\0";
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, b"0123456789"), 10);
+    assert_eq!(lseek(fd, 0, SEEK_CUR), 10);
+    assert_eq!(lseek(fd, 2, SEEK_SET), 2);
+    let mut buf = [0u8; 4];
+    assert_eq!(read(fd, &mut buf), 4);
+    assert_eq!(&buf, b"2345");
+    assert_eq!(lseek(fd, -3, SEEK_END), 7);
+    assert_eq!(read(fd, &mut buf), 3);
+    assert_eq!(&buf[..3], b"789");
+    assert_eq!(lseek(fd, -1, SEEK_SET), -1);
+    assert_eq!(lseek(fd, -11, SEEK_END), -1);
+    assert_eq!(lseek(fd, 0, 3), -1);
+
+    // the offset stays where it is
+    assert_eq!(lseek(fd, 4, SEEK_SET), 4);
+    assert_eq!(pread(fd, &mut buf, 0), 4);
+    assert_eq!(&buf, b"0123");
+    assert_eq!(pwrite(fd, b"ab", 8), 2);
+    assert_eq!(lseek(fd, 0, SEEK_CUR), 4);
+    assert_eq!(pread(fd, &mut buf, 6), 4);
+    assert_eq!(&buf, b"67ab");
+    assert_eq!(pread(fd, &mut buf, 100), 0);
+    close(fd);
+
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    assert_eq!(lseek(pipe_fd[0], 0, SEEK_SET), -1);
+    assert_eq!(pread(pipe_fd[0], &mut buf, 0), -1);
+    close(pipe_fd[0]);
+    close(pipe_fd[1]);
+
+    // appending writers never overwrite each other
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::WRONLY);
+    close(fd as usize);
+    write_records(path, None);
+    check_records(path);
+
+    // nor do those sharing an offset after fork, each write moving it past
+    // what it wrote
+    let fd = open(path, OpenFlags::WRONLY | OpenFlags::TRUNC) as usize;
+    write_records(path, Some(fd));
+    close(fd);
+    check_records(path);
+
+    // and readers sharing one read each record once
+    let fd = open(path, OpenFlags::RDONLY) as usize;
+    for _ in 0..WRITERS {
+        if fork() == 0 {
+            let mut record = [0u8; RECORD_LEN];
+            let mut read_records = 0;
+            while read(fd, &mut record) == RECORD_LEN as isize {
+                assert!(record[..RECORD_LEN - 1].iter().all(|ch| *ch == record[0]));
+                read_records += 1;
+            }
+            exit(read_records);
+        }
+    }
+    let mut total = 0;
+    let mut exit_code = 0;
+    for _ in 0..WRITERS {
+        assert!(wait(&mut exit_code) > 0);
+        total += exit_code as usize;
+    }
+    assert_eq!(total, WRITERS * RECORDS);
+    close(fd);
+    assert_eq!(unlink(path), 0);
+    println!("seek_test passed!");
+    0
+}
@@ -0,0 +1,101 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::{
+    exit, fork, shmat, shmctl, shmdt, shmget, waitpid, yield_, IPC_CREAT, IPC_EXCL, IPC_PRIVATE,
+    IPC_RMID, SHM_RDONLY,
+};
+
+const SLOTS: usize = 256;
+const ITEMS: usize = 10000;
+const KEY: usize = 0x5348_4d50;
+
+/// A single producer single consumer ring living in a shared segment.
+#[repr(C)]
+struct Ring {
+    head: AtomicUsize,
+    tail: AtomicUsize,
+    slots: [usize; SLOTS],
+}
+
+fn attach(shmid: usize) -> &'static mut Ring {
+    let addr = shmat(shmid, 0, 0);
+    assert!(addr > 0);
+    unsafe { &mut *(addr as *mut Ring) }
+}
+
+fn produce(ring: &mut Ring) {
+    for item in 0..ITEMS {
+        let tail = ring.tail.load(Ordering::Relaxed);
+        while tail - ring.head.load(Ordering::Acquire) == SLOTS {
+            yield_();
+        }
+        ring.slots[tail % SLOTS] = item * item;
+        ring.tail.store(tail + 1, Ordering::Release);
+    }
+}
+
+fn consume(ring: &mut Ring) -> usize {
+    let mut sum = 0usize;
+    for _ in 0..ITEMS {
+        let head = ring.head.load(Ordering::Relaxed);
+        while ring.tail.load(Ordering::Acquire) == head {
+            yield_();
+        }
+        sum = sum.wrapping_add(ring.slots[head % SLOTS]);
+        ring.head.store(head + 1, Ordering::Release);
+    }
+    sum
+}
+
+/// A child produces squares into a ring in a shared memory segment and the
+/// parent adds them up, with no pipe in between.
+#[no_mangle]
+pub fn main() -> i32 {
+    let size = core::mem::size_of::<Ring>();
+    let shmid = shmget(KEY, size, IPC_CREAT | IPC_EXCL);
+    assert!(shmid >= 0);
+    let shmid = shmid as usize;
+    // the key now names this segment
+    assert_eq!(shmget(KEY, size, 0), shmid as isize);
+    assert_eq!(shmget(KEY, size, IPC_CREAT | IPC_EXCL), -1);
+    let private = shmget(IPC_PRIVATE, size, 0);
+    assert!(private >= 0 && private != shmid as isize);
+    assert_eq!(shmctl(private as usize, IPC_RMID), 0);
+    let pid = fork();
+    if pid == 0 {
+        let ring = attach(shmid);
+        produce(ring);
+        assert_eq!(shmdt(ring as *mut Ring as usize), 0);
+        exit(0);
+    }
+    let ring = attach(shmid);
+    // a read-only attachment sees the same pages
+    let view = shmat(shmid, 0, SHM_RDONLY);
+    assert!(view > 0);
+    let sum = consume(ring);
+    let expected = (0..ITEMS).fold(0usize, |sum, item| sum.wrapping_add(item * item));
+    assert_eq!(sum, expected);
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(
+        unsafe { &*(view as *const Ring) }
+            .tail
+            .load(Ordering::Acquire),
+        ITEMS
+    );
+    // removed segments stay attached until the last detach
+    assert_eq!(shmctl(shmid, IPC_RMID), 0);
+    assert_eq!(shmat(shmid, 0, 0), -1);
+    assert_eq!(ring.head.load(Ordering::Relaxed), ITEMS);
+    assert_eq!(shmdt(ring as *mut Ring as usize), 0);
+    assert_eq!(shmdt(view as usize), 0);
+    assert_eq!(shmdt(view as usize), -1);
+    println!("shm_pc passed!");
+    0
+}
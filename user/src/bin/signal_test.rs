@@ -0,0 +1,265 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::{
+    close, exit, fork, getpid, gettid, kill, pipe, poll, read, sigaction, sigpending, sigprocmask,
+    sigsuspend, sleep, tgkill, waitpid, write, PollFd, SigAction, SigInfo, SignalFlags, EINTR,
+    POLLIN, SA_RESETHAND, SA_RESTART, SIGCONT, SIGKILL, SIGRTMIN, SIGSEGV, SIGSTOP, SIGTERM,
+    SIGUSR1, SIGUSR2, SIG_BLOCK, SIG_DFL, SIG_IGN, SIG_SETMASK, SIG_UNBLOCK, SI_TKILL, SI_USER,
+};
+
+static HANDLED: AtomicUsize = AtomicUsize::new(0);
+static REALTIME: AtomicUsize = AtomicUsize::new(0);
+static LAST_SIGNO: AtomicUsize = AtomicUsize::new(0);
+static LAST_CODE: AtomicUsize = AtomicUsize::new(0);
+static LAST_PID: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn handler(signo: usize, info: &SigInfo, _ucontext: usize) {
+    HANDLED.fetch_add(1, Ordering::SeqCst);
+    if signo == SIGRTMIN {
+        REALTIME.fetch_add(1, Ordering::SeqCst);
+    }
+    LAST_SIGNO.store(signo, Ordering::SeqCst);
+    LAST_CODE.store(info.code as usize, Ordering::SeqCst);
+    LAST_PID.store(info.pid as usize, Ordering::SeqCst);
+}
+
+extern "C" fn segv_handler(signo: usize, info: &SigInfo, _ucontext: usize) {
+    // going back would fault again
+    exit(if signo == SIGSEGV && info.addr == 0x10 {
+        42
+    } else {
+        1
+    });
+}
+
+fn catch(signo: usize, flags: usize) {
+    let action = SigAction::new(handler as usize, flags);
+    assert_eq!(sigaction(signo, Some(&action), None), 0);
+}
+
+fn handled() -> usize {
+    HANDLED.load(Ordering::SeqCst)
+}
+
+fn wait_child(pid: isize, expected: i32) {
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, expected);
+}
+
+/// The handler runs as the kill returns, and learns who sent it.
+fn handler_runs() {
+    catch(SIGUSR1, 0);
+    let before = handled();
+    assert_eq!(kill(getpid() as usize, SIGUSR1), 0);
+    assert_eq!(handled(), before + 1);
+    assert_eq!(LAST_SIGNO.load(Ordering::SeqCst), SIGUSR1);
+    assert_eq!(LAST_CODE.load(Ordering::SeqCst), SI_USER as usize);
+    assert_eq!(LAST_PID.load(Ordering::SeqCst), getpid() as usize);
+    assert_eq!(tgkill(getpid() as usize, gettid() as usize, SIGUSR1), 0);
+    assert_eq!(handled(), before + 2);
+    assert_eq!(LAST_CODE.load(Ordering::SeqCst), SI_TKILL as usize);
+    // nor SIGKILL nor SIGSTOP may be caught, and there is no signal 0
+    let action = SigAction::new(handler as usize, 0);
+    assert!(sigaction(SIGKILL, Some(&action), None) < 0);
+    assert!(sigaction(SIGSTOP, Some(&action), None) < 0);
+    assert!(sigaction(0, Some(&action), None) < 0);
+}
+
+/// A blocked signal waits, pending, until it is unblocked.
+fn masked() {
+    catch(SIGUSR1, 0);
+    let set = SignalFlags::from_signum(SIGUSR1);
+    assert_eq!(sigprocmask(SIG_BLOCK, Some(&set), None), 0);
+    let before = handled();
+    kill(getpid() as usize, SIGUSR1);
+    assert_eq!(handled(), before);
+    let mut pending = SignalFlags::empty();
+    assert_eq!(sigpending(&mut pending), 0);
+    assert_eq!(pending, set);
+    assert_eq!(sigprocmask(SIG_UNBLOCK, Some(&set), None), 0);
+    assert_eq!(handled(), before + 1);
+    assert_eq!(sigpending(&mut pending), 0);
+    assert!(pending.is_empty());
+    // SIGKILL is never blocked
+    let mut old = SignalFlags::empty();
+    let mut mask = SignalFlags::empty();
+    assert_eq!(
+        sigprocmask(SIG_SETMASK, Some(&SignalFlags::all()), Some(&mut old)),
+        0
+    );
+    assert!(old.is_empty());
+    assert_eq!(sigprocmask(SIG_SETMASK, Some(&old), Some(&mut mask)), 0);
+    assert!(!mask.contains(SignalFlags::SIGKILL));
+}
+
+/// A standard signal sent twice while blocked is delivered once, a
+/// real-time one twice.
+fn queued() {
+    catch(SIGUSR1, 0);
+    catch(SIGRTMIN, 0);
+    let set = SignalFlags::from_signum(SIGUSR1) | SignalFlags::from_signum(SIGRTMIN);
+    sigprocmask(SIG_BLOCK, Some(&set), None);
+    let before = handled();
+    for _ in 0..2 {
+        kill(getpid() as usize, SIGUSR1);
+        kill(getpid() as usize, SIGRTMIN);
+    }
+    sigprocmask(SIG_UNBLOCK, Some(&set), None);
+    assert_eq!(handled(), before + 3);
+    assert_eq!(REALTIME.load(Ordering::SeqCst), 2);
+}
+
+/// A read waiting on a pipe goes on after a handler with SA_RESTART, and
+/// fails with EINTR after one without.
+fn interrupted() {
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let parent = getpid() as usize;
+    for flags in [SA_RESTART, 0] {
+        catch(SIGUSR1, flags);
+        let pid = fork();
+        if pid == 0 {
+            sleep(20);
+            kill(parent, SIGUSR1);
+            sleep(20);
+            write(fds[1], b"x");
+            exit(0);
+        }
+        let before = handled();
+        let mut buf = [0u8; 1];
+        if flags == SA_RESTART {
+            assert_eq!(read(fds[0], &mut buf), 1);
+        } else {
+            assert_eq!(read(fds[0], &mut buf), EINTR);
+            assert_eq!(read(fds[0], &mut buf), 1);
+        }
+        assert_eq!(handled(), before + 1);
+        wait_child(pid, 0);
+    }
+    close(fds[0]);
+    close(fds[1]);
+}
+
+/// SA_RESETHAND catches the signal once.
+fn reset_hand() {
+    catch(SIGUSR2, SA_RESETHAND);
+    let before = handled();
+    kill(getpid() as usize, SIGUSR2);
+    assert_eq!(handled(), before + 1);
+    let mut old = SigAction::default();
+    assert_eq!(sigaction(SIGUSR2, None, Some(&mut old)), 0);
+    assert_eq!(old.handler, SIG_DFL);
+}
+
+/// A handler of SIGSEGV sees the address which faulted.
+fn segv() {
+    let pid = fork();
+    if pid == 0 {
+        let action = SigAction::new(segv_handler as usize, 0);
+        sigaction(SIGSEGV, Some(&action), None);
+        unsafe {
+            core::ptr::write_volatile(0x10 as *mut u8, 0);
+        }
+        exit(1);
+    }
+    wait_child(pid, 42);
+}
+
+/// sigsuspend waits with its mask, and puts the old one back.
+fn suspend() {
+    catch(SIGUSR1, 0);
+    let set = SignalFlags::from_signum(SIGUSR1);
+    sigprocmask(SIG_BLOCK, Some(&set), None);
+    let parent = getpid() as usize;
+    let pid = fork();
+    if pid == 0 {
+        sleep(20);
+        kill(parent, SIGUSR1);
+        exit(0);
+    }
+    let before = handled();
+    assert_eq!(sigsuspend(&SignalFlags::empty()), EINTR);
+    assert_eq!(handled(), before + 1);
+    let mut mask = SignalFlags::empty();
+    sigprocmask(SIG_BLOCK, None, Some(&mut mask));
+    assert_eq!(mask, set);
+    sigprocmask(SIG_UNBLOCK, Some(&set), None);
+    wait_child(pid, 0);
+}
+
+/// Read what is in the pipe now, returning whether there was anything.
+fn drain(fd: usize) -> bool {
+    let mut drained = false;
+    let mut buf = [0u8; 64];
+    while poll(&mut [PollFd::new(fd, POLLIN)], 0) > 0 {
+        read(fd, &mut buf);
+        drained = true;
+    }
+    drained
+}
+
+/// A stopped process writes nothing until it goes on.
+fn stop_and_continue() {
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let pid = fork();
+    if pid == 0 {
+        close(fds[0]);
+        loop {
+            write(fds[1], b"x");
+            sleep(5);
+        }
+    }
+    close(fds[1]);
+    sleep(20);
+    assert_eq!(kill(pid as usize, SIGSTOP), 0);
+    sleep(20);
+    drain(fds[0]);
+    sleep(40);
+    assert!(!drain(fds[0]));
+    assert_eq!(kill(pid as usize, SIGCONT), 0);
+    let mut buf = [0u8; 1];
+    assert_eq!(read(fds[0], &mut buf), 1);
+    assert_eq!(kill(pid as usize, SIGKILL), 0);
+    wait_child(pid, -(SIGKILL as i32));
+    close(fds[0]);
+}
+
+/// SIGTERM kills by default, and not if ignored.
+fn default_action() {
+    let pid = fork();
+    if pid == 0 {
+        kill(getpid() as usize, SIGTERM);
+        exit(0);
+    }
+    wait_child(pid, -(SIGTERM as i32));
+    let pid = fork();
+    if pid == 0 {
+        let action = SigAction::new(SIG_IGN, 0);
+        sigaction(SIGTERM, Some(&action), None);
+        kill(getpid() as usize, SIGTERM);
+        exit(0);
+    }
+    wait_child(pid, 0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    handler_runs();
+    masked();
+    queued();
+    interrupted();
+    reset_hand();
+    segv();
+    suspend();
+    stop_and_continue();
+    default_action();
+    println!("signal_test passed!");
+    0
+}
@@ -0,0 +1,106 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit, fork, getpid, kill, poll, signalfd, signalfd_read, sigprocmask, sigtimedwait,
+    sleep, waitpid, PollFd, SigInfo, SignalFlags, SignalfdSiginfo, EAGAIN, POLLIN, SFD_NONBLOCK,
+    SIGRTMIN, SIGUSR1, SIGUSR2, SIG_BLOCK, SIG_UNBLOCK, SI_USER,
+};
+
+fn wait_child(pid: isize, expected: i32) {
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, expected);
+}
+
+/// A signalfd is readable once a signal of its mask is pending, and a
+/// read takes it with who sent it.
+fn read_signals() {
+    let set = SignalFlags::from_signum(SIGUSR1) | SignalFlags::from_signum(SIGRTMIN);
+    sigprocmask(SIG_BLOCK, Some(&set), None);
+    let fd = signalfd(None, &set, SFD_NONBLOCK);
+    assert!(fd >= 0);
+    let fd = fd as usize;
+    let mut infos = [SignalfdSiginfo::default(); 4];
+    assert_eq!(signalfd_read(fd, &mut infos), EAGAIN);
+    assert_eq!(poll(&mut [PollFd::new(fd, POLLIN)], 0), 0);
+    // signals not in the mask are not for it
+    kill(getpid() as usize, SIGUSR1);
+    kill(getpid() as usize, SIGUSR1);
+    kill(getpid() as usize, SIGRTMIN);
+    kill(getpid() as usize, SIGRTMIN);
+    assert_eq!(poll(&mut [PollFd::new(fd, POLLIN)], 0), 1);
+    // a standard signal is pending once, a real-time one twice
+    assert_eq!(signalfd_read(fd, &mut infos), 3);
+    assert_eq!(infos[0].signo as usize, SIGUSR1);
+    assert_eq!(infos[0].code, SI_USER);
+    assert_eq!(infos[0].pid as isize, getpid());
+    assert_eq!(infos[1].signo as usize, SIGRTMIN);
+    assert_eq!(infos[2].signo as usize, SIGRTMIN);
+    assert_eq!(poll(&mut [PollFd::new(fd, POLLIN)], 0), 0);
+    // the mask may change
+    let other = SignalFlags::from_signum(SIGUSR2);
+    sigprocmask(SIG_BLOCK, Some(&other), None);
+    assert_eq!(signalfd(Some(fd), &other, 0), fd as isize);
+    kill(getpid() as usize, SIGUSR2);
+    assert_eq!(signalfd_read(fd, &mut infos[..1]), 1);
+    assert_eq!(infos[0].signo as usize, SIGUSR2);
+    sigprocmask(SIG_UNBLOCK, Some(&other), None);
+    close(fd);
+    sigprocmask(SIG_UNBLOCK, Some(&set), None);
+}
+
+/// A blocking poll of a signalfd wakes when another process signals.
+fn wait_in_poll() {
+    let set = SignalFlags::from_signum(SIGUSR1);
+    sigprocmask(SIG_BLOCK, Some(&set), None);
+    let fd = signalfd(None, &set, 0) as usize;
+    let parent = getpid() as usize;
+    let pid = fork();
+    if pid == 0 {
+        sleep(20);
+        kill(parent, SIGUSR1);
+        exit(0);
+    }
+    let mut fds = [PollFd::new(fd, POLLIN)];
+    assert_eq!(poll(&mut fds, -1), 1);
+    let mut infos = [SignalfdSiginfo::default(); 1];
+    assert_eq!(signalfd_read(fd, &mut infos), 1);
+    assert_eq!(infos[0].pid as isize, pid);
+    wait_child(pid, 0);
+    close(fd);
+    sigprocmask(SIG_UNBLOCK, Some(&set), None);
+}
+
+/// sigtimedwait gives up after its timeout, and takes a signal as it comes.
+fn timed_wait() {
+    let set = SignalFlags::from_signum(SIGUSR2);
+    sigprocmask(SIG_BLOCK, Some(&set), None);
+    assert_eq!(sigtimedwait(&set, None, Some(10)), EAGAIN);
+    let parent = getpid() as usize;
+    let pid = fork();
+    if pid == 0 {
+        sleep(20);
+        kill(parent, SIGUSR2);
+        exit(0);
+    }
+    let mut info = SigInfo::default();
+    assert_eq!(sigtimedwait(&set, Some(&mut info), None), SIGUSR2 as isize);
+    assert_eq!(info.pid as isize, pid);
+    wait_child(pid, 0);
+    kill(getpid() as usize, SIGUSR2);
+    assert_eq!(sigtimedwait(&set, None, Some(0)), SIGUSR2 as isize);
+    sigprocmask(SIG_UNBLOCK, Some(&set), None);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    read_signals();
+    wait_in_poll();
+    timed_wait();
+    println!("signalfd_test passed!");
+    0
+}
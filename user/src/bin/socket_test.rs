@@ -0,0 +1,370 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    accept, accept4, bind, checksum, close, connect, getaddrinfo, getpeername, getsockname,
+    getsockopt, ifconfig, ifconfig_dhcp, ifconfig_set, listen, netbench, open, pipe, poll, read,
+    recvfrom, sendto, setsockopt, shutdown, sleep, socket, write, IfReq, OpenFlags, PollFd,
+    SockAddrIn, AF_INET, EADDRINUSE, EAFNOSUPPORT, EAGAIN, EDESTADDRREQ, EINPROGRESS, EINVAL,
+    EMSGSIZE, ENODEV, ENOPROTOOPT, ENOSYS, ENOTCONN, ENOTSOCK, EOPNOTSUPP, IPPROTO_ICMP,
+    NETBENCH_STOP, POLLIN, POLLOUT, SHUT_RDWR, SOCK_DGRAM, SOCK_NONBLOCK, SOCK_RAW, SOCK_STREAM,
+    SOL_SOCKET, SO_RCVBUF, SO_SNDBUF,
+};
+
+fn any(port: u16) -> SockAddrIn {
+    SockAddrIn::new([0, 0, 0, 0], port)
+}
+
+fn socket_fd(kind: usize) -> usize {
+    let fd = socket(AF_INET, kind, 0);
+    assert!(fd > 0);
+    fd as usize
+}
+
+fn create() {
+    assert_eq!(socket(10, SOCK_STREAM, 0), EAFNOSUPPORT);
+    assert!(socket(AF_INET, 3, 0) < 0);
+    assert!(socket(AF_INET, SOCK_DGRAM, 6) < 0);
+    let fd = socket_fd(SOCK_STREAM);
+    let mut addr = SockAddrIn::default();
+    // unbound, and no peer
+    assert_eq!(getsockname(fd, &mut addr), 0);
+    assert_eq!(addr.port(), 0);
+    assert_eq!(getpeername(fd, &mut addr), ENOTCONN);
+    close(fd);
+}
+
+fn ports() {
+    let a = socket_fd(SOCK_DGRAM);
+    let b = socket_fd(SOCK_DGRAM);
+    assert_eq!(bind(a, &any(5300)), 0);
+    assert_eq!(bind(b, &any(5300)), EADDRINUSE);
+    // a free port for 0
+    assert_eq!(bind(b, &any(0)), 0);
+    let mut addr = SockAddrIn::default();
+    assert_eq!(getsockname(b, &mut addr), 0);
+    assert!(addr.port() >= 49152);
+    close(a);
+    close(b);
+    // the port is free again once closed
+    let a = socket_fd(SOCK_DGRAM);
+    assert_eq!(bind(a, &any(5300)), 0);
+    close(a);
+
+    let a = socket_fd(SOCK_STREAM);
+    let b = socket_fd(SOCK_STREAM);
+    assert_eq!(bind(a, &any(5301)), 0);
+    assert_eq!(listen(a, 4), 0);
+    assert_eq!(bind(b, &any(5301)), EADDRINUSE);
+    close(a);
+    close(b);
+}
+
+fn datagrams() {
+    let fd = socket(AF_INET, SOCK_DGRAM | SOCK_NONBLOCK, 0);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(listen(fd, 1), EOPNOTSUPP);
+    assert_eq!(sendto(fd, b"hello", None), EDESTADDRREQ);
+    let dest = SockAddrIn::new([10, 0, 2, 2], 26099);
+    assert_eq!(sendto(fd, &[0u8; 2000], Some(&dest)), EMSGSIZE);
+    assert_eq!(sendto(fd, b"hello", Some(&dest)), 5);
+    // sending bound it
+    let mut addr = SockAddrIn::default();
+    assert_eq!(getsockname(fd, &mut addr), 0);
+    assert!(addr.port() >= 49152);
+    assert_eq!(recvfrom(fd, &mut [0u8; 16], None), EAGAIN);
+    assert_eq!(shutdown(fd, SHUT_RDWR), ENOTCONN);
+    close(fd);
+}
+
+fn listener() {
+    let fd = socket(AF_INET, SOCK_STREAM | SOCK_NONBLOCK, 0);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(bind(fd, &any(5302)), 0);
+    assert_eq!(listen(fd, 4), 0);
+    assert_eq!(accept(fd, None), EAGAIN);
+    assert_eq!(accept4(fd, None, SOCK_NONBLOCK), EAGAIN);
+    assert_eq!(connect(fd, &SockAddrIn::new([10, 0, 2, 2], 80)), EINVAL);
+    assert_eq!(sendto(fd, b"hello", None), ENOTCONN);
+    assert_eq!(shutdown(fd, SHUT_RDWR), ENOTCONN);
+    // nobody connects
+    let mut fds = [PollFd::new(fd, POLLIN)];
+    assert_eq!(poll(&mut fds, 20), 0);
+    close(fd);
+}
+
+fn connecting() {
+    let fd = socket(AF_INET, SOCK_STREAM | SOCK_NONBLOCK, 0);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let dest = SockAddrIn::new([10, 0, 2, 2], 26099);
+    assert_eq!(connect(fd, &dest), EINPROGRESS);
+    // the port it went from, and where to
+    let mut addr = SockAddrIn::default();
+    assert_eq!(getsockname(fd, &mut addr), 0);
+    assert!(addr.port() >= 49152);
+    assert_eq!(getpeername(fd, &mut addr), 0);
+    assert_eq!((addr.ip(), addr.port()), ([10, 0, 2, 2], 26099));
+    close(fd);
+}
+
+/// Datagrams to ourselves, over the loopback.
+fn loopback_datagrams() {
+    let a = socket_fd(SOCK_DGRAM);
+    let b = socket_fd(SOCK_DGRAM);
+    let lo = |port| SockAddrIn::new([127, 0, 0, 1], port);
+    assert_eq!(bind(a, &any(5310)), 0);
+    assert_eq!(bind(b, &any(5311)), 0);
+    assert_eq!(sendto(a, b"ping", Some(&lo(5311))), 4);
+    let mut buf = [0u8; 16];
+    let mut from = SockAddrIn::default();
+    assert_eq!(recvfrom(b, &mut buf, Some(&mut from)), 4);
+    assert_eq!(&buf[..4], b"ping");
+    assert_eq!(from.port(), 5310);
+    // a connected socket answers whoever it connected to
+    assert_eq!(connect(b, &lo(5310)), 0);
+    assert_eq!(write(b, b"pong"), 4);
+    assert_eq!(read(a, &mut buf), 4);
+    assert_eq!(&buf[..4], b"pong");
+    close(a);
+    close(b);
+}
+
+/// A connection to ourselves, over the loopback.
+fn loopback_stream() {
+    let server = socket_fd(SOCK_STREAM);
+    assert_eq!(bind(server, &any(5312)), 0);
+    assert_eq!(listen(server, 4), 0);
+    let client = socket_fd(SOCK_STREAM);
+    assert_eq!(connect(client, &SockAddrIn::new([127, 0, 0, 1], 5312)), 0);
+    let mut peer = SockAddrIn::default();
+    let conn = accept(server, Some(&mut peer));
+    assert!(conn > 0);
+    let conn = conn as usize;
+    let mut local = SockAddrIn::default();
+    assert_eq!(getsockname(client, &mut local), 0);
+    assert_eq!(peer.port(), local.port());
+    assert_eq!(write(client, b"hello"), 5);
+    let mut buf = [0u8; 16];
+    assert_eq!(read(conn, &mut buf), 5);
+    assert_eq!(&buf[..5], b"hello");
+    assert_eq!(write(conn, b"bye"), 3);
+    assert_eq!(read(client, &mut buf), 3);
+    assert_eq!(&buf[..3], b"bye");
+    // closing one end is the end of the stream at the other
+    close(client);
+    assert_eq!(read(conn, &mut buf), 0);
+    close(conn);
+    close(server);
+}
+
+fn buffers() {
+    let fd = socket_fd(SOCK_DGRAM);
+    let mut size = 0;
+    assert_eq!(getsockopt(fd, SOL_SOCKET, SO_RCVBUF, &mut size), 0);
+    assert_eq!(size, 65536);
+    assert_eq!(setsockopt(fd, SOL_SOCKET, SO_RCVBUF, 4096), 0);
+    assert_eq!(getsockopt(fd, SOL_SOCKET, SO_RCVBUF, &mut size), 0);
+    assert_eq!(size, 4096);
+    // kept within bounds
+    assert_eq!(setsockopt(fd, SOL_SOCKET, SO_SNDBUF, 1), 0);
+    assert_eq!(getsockopt(fd, SOL_SOCKET, SO_SNDBUF, &mut size), 0);
+    assert_eq!(size, 2048);
+    assert_eq!(setsockopt(fd, SOL_SOCKET, SO_SNDBUF, i32::MAX), 0);
+    assert_eq!(getsockopt(fd, SOL_SOCKET, SO_SNDBUF, &mut size), 0);
+    assert_eq!(size, 1 << 20);
+    assert_eq!(setsockopt(fd, SOL_SOCKET, 99, 0), ENOPROTOOPT);
+    assert_eq!(setsockopt(fd, 6, SO_RCVBUF, 0), ENOPROTOOPT);
+    close(fd);
+}
+
+/// The packets lo took in but no socket had room for.
+fn lo_drops() -> usize {
+    let fd = open("/proc/netdev\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buf = [0u8; 512];
+    let len = read(fd as usize, &mut buf);
+    close(fd as usize);
+    let text = core::str::from_utf8(&buf[..len as usize]).unwrap();
+    let line = text
+        .lines()
+        .find(|line| line.trim_start().starts_with("lo:"));
+    line.unwrap()
+        .split_whitespace()
+        .nth(3)
+        .unwrap()
+        .parse()
+        .unwrap()
+}
+
+/// Datagrams beyond the receive buffer are dropped, and counted.
+fn loopback_overflow() {
+    let a = socket_fd(SOCK_DGRAM);
+    let b = socket(AF_INET, SOCK_DGRAM | SOCK_NONBLOCK, 0);
+    assert!(b > 0);
+    let b = b as usize;
+    assert_eq!(bind(b, &any(5313)), 0);
+    assert_eq!(setsockopt(b, SOL_SOCKET, SO_RCVBUF, 2048), 0);
+    let drops = lo_drops();
+    let datagram = [7u8; 1000];
+    for _ in 0..4 {
+        let lo = SockAddrIn::new([127, 0, 0, 1], 5313);
+        assert_eq!(sendto(a, &datagram, Some(&lo)), 1000);
+    }
+    // for netd to take them in
+    sleep(100);
+    let mut buf = [0u8; 1000];
+    assert_eq!(recvfrom(b, &mut buf, None), 1000);
+    assert_eq!(recvfrom(b, &mut buf, None), 1000);
+    assert_eq!(recvfrom(b, &mut buf, None), EAGAIN);
+    assert_eq!(lo_drops(), drops + 2);
+    close(a);
+    close(b);
+}
+
+/// A connection never has more unread and in flight than the receive
+/// buffer of its peer takes, and loses nothing.
+fn loopback_backpressure() {
+    const RCVBUF: usize = 4096;
+    let server = socket_fd(SOCK_STREAM);
+    assert_eq!(setsockopt(server, SOL_SOCKET, SO_RCVBUF, RCVBUF as i32), 0);
+    assert_eq!(bind(server, &any(5314)), 0);
+    assert_eq!(listen(server, 1), 0);
+    let client = socket(AF_INET, SOCK_STREAM | SOCK_NONBLOCK, 0);
+    assert!(client > 0);
+    let client = client as usize;
+    assert_eq!(
+        connect(client, &SockAddrIn::new([127, 0, 0, 1], 5314)),
+        EINPROGRESS
+    );
+    let conn = accept(server, None);
+    assert!(conn > 0);
+    let conn = conn as usize;
+    let drops = lo_drops();
+    let mut data = [0u8; 16384];
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte = (i % 251) as u8;
+    }
+    let (mut sent, mut received) = (0, 0);
+    let mut buf = [0u8; 2048];
+    while received < data.len() {
+        if sent < data.len() {
+            let mut fds = [PollFd::new(client, POLLOUT)];
+            assert_eq!(poll(&mut fds, 1000), 1);
+            let len = sendto(client, &data[sent..], None);
+            assert!(len > 0);
+            sent += len as usize;
+            // the window is full until the reader makes room
+            if sent < data.len() {
+                assert_eq!(sendto(client, &data[sent..], None), EAGAIN);
+            }
+        }
+        assert!(sent - received <= RCVBUF);
+        let mut fds = [PollFd::new(conn, POLLIN)];
+        assert_eq!(poll(&mut fds, 1000), 1);
+        let len = read(conn, &mut buf);
+        assert!(len > 0);
+        let len = len as usize;
+        assert_eq!(&buf[..len], &data[received..received + len]);
+        received += len;
+    }
+    assert_eq!(lo_drops(), drops);
+    close(client);
+    close(conn);
+    close(server);
+}
+
+/// An echo request to ourselves, which the kernel answers; the raw socket
+/// sees both.
+fn loopback_ping() {
+    let fd = socket(AF_INET, SOCK_RAW, IPPROTO_ICMP);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let mut request = [8u8, 0, 0, 0, 0x12, 0x34, 0, 1, b'p', b'i', b'n', b'g'];
+    let sum = checksum(&request);
+    request[2..4].copy_from_slice(&sum.to_be_bytes());
+    let lo = SockAddrIn::new([127, 0, 0, 1], 0);
+    assert_eq!(sendto(fd, &request, Some(&lo)), request.len() as isize);
+    let mut types = [0u8; 2];
+    for ty in types.iter_mut() {
+        let mut packet = [0u8; 64];
+        let mut fds = [PollFd::new(fd, POLLIN)];
+        assert_eq!(poll(&mut fds, 1000), 1);
+        let len = recvfrom(fd, &mut packet, None);
+        // the IPv4 header, then the message
+        assert_eq!(len, 20 + request.len() as isize);
+        let icmp = &packet[20..len as usize];
+        assert_eq!(checksum(icmp), 0);
+        assert_eq!(&icmp[4..], &request[4..]);
+        *ty = icmp[0];
+    }
+    assert_eq!(types, [8, 0]);
+    close(fd);
+}
+
+fn interfaces() {
+    let mut req = IfReq::default();
+    assert_eq!(ifconfig("lo\0", &mut req), 0);
+    assert_eq!(req.ip(), [127, 0, 0, 1]);
+    assert_eq!(req.netmask(), [255, 0, 0, 0]);
+    assert_eq!(ifconfig("wlan0\0", &mut req), ENODEV);
+    // lo is as it is
+    let req = IfReq::new([127, 0, 0, 2], [255, 0, 0, 0], [0; 4]);
+    assert_eq!(ifconfig_set("lo\0", &req), EINVAL);
+    assert_eq!(ifconfig_dhcp("lo\0"), EINVAL);
+}
+
+fn resolve() {
+    let mut addrs = [[0u8; 4]; 2];
+    assert_eq!(getaddrinfo("localhost\0", &mut addrs), 1);
+    assert_eq!(addrs[0], [127, 0, 0, 1]);
+    assert_eq!(getaddrinfo("10.0.2.2\0", &mut addrs), 1);
+    assert_eq!(addrs[0], [10, 0, 2, 2]);
+    // counted but not put anywhere
+    assert_eq!(getaddrinfo("localhost\0", &mut []), 1);
+    assert_eq!(getaddrinfo("\0", &mut addrs), EINVAL);
+    assert_eq!(getaddrinfo("a..b\0", &mut addrs), EINVAL);
+}
+
+fn netbench_ops() {
+    let addr = SockAddrIn::new([127, 0, 0, 1], 5201);
+    assert_eq!(netbench(99, &addr, 1), EINVAL);
+    // nothing to stop, or no netbench in this kernel
+    let ret = netbench(NETBENCH_STOP, &addr, 0);
+    assert!(ret == 0 || ret == ENOSYS);
+}
+
+fn not_socket() {
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    assert_eq!(recvfrom(pipe_fd[0], &mut [0u8; 4], None), ENOTSOCK);
+    assert_eq!(listen(pipe_fd[0], 1), ENOTSOCK);
+    close(pipe_fd[0]);
+    close(pipe_fd[1]);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    create();
+    ports();
+    datagrams();
+    listener();
+    connecting();
+    loopback_datagrams();
+    loopback_stream();
+    loopback_ping();
+    buffers();
+    loopback_overflow();
+    loopback_backpressure();
+    interfaces();
+    resolve();
+    netbench_ops();
+    not_socket();
+    println!("socket_test passed!");
+    0
+}
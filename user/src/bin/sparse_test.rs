@@ -0,0 +1,53 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fstat, fsync, lseek, open, pread, pwrite, unlink, write};
+use user_lib::{OpenFlags, Stat, SEEK_SET};
+
+/// Past what a 32-bit size holds.
+const FAR: usize = 5 << 30;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let path = "/sparse_test_file\0";
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::RDWR | OpenFlags::TRUNC);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, b"head"), 4);
+    assert_eq!(lseek(fd, FAR as isize, SEEK_SET), FAR as isize);
+    assert_eq!(write(fd, b"tail"), 4);
+    assert_eq!(fsync(fd), 0);
+
+    let mut stat = Stat::default();
+    assert_eq!(fstat(fd, &mut stat), 0);
+    assert_eq!(stat.size, FAR as i64 + 4);
+    // the two ends and the index blocks down to the far one, not gigabytes
+    assert!(stat.blocks > 0 && stat.blocks < 64);
+
+    // the hole reads as zeros
+    let mut buf = [0xffu8; 16];
+    assert_eq!(pread(fd, &mut buf, 3 << 30), 16);
+    assert!(buf.iter().all(|&b| b == 0));
+    assert_eq!(pread(fd, &mut buf, FAR - 4), 8);
+    assert_eq!(&buf[..8], b"\0\0\0\0tail");
+    assert_eq!(pread(fd, &mut buf, 0), 16);
+    assert_eq!(&buf[..4], b"head");
+    assert_eq!(pread(fd, &mut buf, FAR + 4), 0);
+
+    // filling part of the hole in allocates only there
+    assert_eq!(pwrite(fd, b"middle", 1 << 31), 6);
+    assert_eq!(fsync(fd), 0);
+    let mut filled = Stat::default();
+    assert_eq!(fstat(fd, &mut filled), 0);
+    assert_eq!(filled.size, stat.size);
+    assert!(filled.blocks > stat.blocks && filled.blocks < 2 * stat.blocks + 16);
+    assert_eq!(pread(fd, &mut buf, 1 << 31), 16);
+    assert_eq!(&buf[..6], b"middle");
+    close(fd);
+    assert_eq!(unlink(path), 0);
+    println!("sparse_test passed!");
+    0
+}
@@ -0,0 +1,50 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exec, fork, get_time, spawn, waitpid};
+
+const ROUNDS: usize = 20;
+const CHILD_ARGS: [*const u8; 3] = [
+    "spawn_bench\0".as_ptr(),
+    "child\0".as_ptr(),
+    core::ptr::null::<u8>(),
+];
+
+/// Start ROUNDS children running `spawn_bench child` with fork + exec and
+/// then with spawn, and compare the time taken.
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc > 1 && argv[1] == "child" {
+        return 7;
+    }
+    let mut exit_code: i32 = 0;
+    let start = get_time();
+    for _ in 0..ROUNDS {
+        let pid = fork();
+        if pid == 0 {
+            exec("spawn_bench\0", &CHILD_ARGS);
+            panic!("exec failed");
+        }
+        assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+        assert_eq!(exit_code, 7);
+    }
+    let fork_exec_ms = get_time() - start;
+    let start = get_time();
+    for _ in 0..ROUNDS {
+        let pid = spawn("spawn_bench\0", &CHILD_ARGS);
+        assert!(pid > 0);
+        assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+        assert_eq!(exit_code, 7);
+    }
+    let spawn_ms = get_time() - start;
+    assert_eq!(spawn("not_exist\0", &CHILD_ARGS), -1);
+    println!(
+        "{} rounds: fork + exec {}ms, spawn {}ms",
+        ROUNDS, fork_exec_ms, spawn_ms
+    );
+    println!("spawn_bench passed!");
+    0
+}
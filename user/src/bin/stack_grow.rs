@@ -0,0 +1,43 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exit, fork, setrlimit, waitpid, RLimit, RLIMIT_STACK};
+
+const FRAME_SIZE: usize = 1024;
+
+/// Use about `depth` KiB of stack.
+fn recurse(depth: usize) -> usize {
+    let mut frame = [0u8; FRAME_SIZE];
+    for byte in frame.iter_mut() {
+        unsafe { core::ptr::write_volatile(byte, depth as u8) };
+    }
+    let below = if depth == 0 { 0 } else { recurse(depth - 1) };
+    below + unsafe { core::ptr::read_volatile(&frame[FRAME_SIZE - 1]) } as usize
+}
+
+/// Recursion far deeper than the initial stack grows it, while recursion
+/// beyond RLIMIT_STACK still gets a segmentation fault.
+#[no_mangle]
+pub fn main() -> i32 {
+    let depth = 1024;
+    let expected: usize = (0..=depth).map(|d| d % 256).sum();
+    assert_eq!(recurse(depth), expected);
+    let pid = fork();
+    if pid == 0 {
+        let limit = RLimit {
+            rlim_cur: 256 * 1024,
+            rlim_max: 256 * 1024,
+        };
+        assert_eq!(setrlimit(RLIMIT_STACK, &limit), 0);
+        recurse(512);
+        exit(0);
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, -11);
+    println!("stack_grow passed!");
+    0
+}
@@ -0,0 +1,54 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fstat, link, mkdir, open, pipe, rmdir, stat, unlink, write};
+use user_lib::{OpenFlags, Stat, S_IFCHR, S_IFIFO, S_IFMT};
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut st = Stat::default();
+    assert_eq!(mkdir("/stat_test\0"), 0);
+    assert_eq!(stat("/stat_test\0", &mut st), 0);
+    assert!(st.is_dir());
+    assert_eq!(stat("/stat_test/missing\0", &mut st), -1);
+
+    let fd = open("/stat_test/file\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let data = [7u8; 1000];
+    assert_eq!(write(fd, &data), 1000);
+    assert_eq!(fstat(fd, &mut st), 0);
+    assert!(st.is_file());
+    assert_eq!(st.size, 1000);
+    assert_eq!(st.nlink, 1);
+    assert!(st.blocks >= 2);
+    let ino = st.ino;
+    close(fd);
+
+    // both names lead to the same inode
+    assert_eq!(link("/stat_test/file\0", "/stat_test/other\0"), 0);
+    assert_eq!(stat("/stat_test/other\0", &mut st), 0);
+    assert_eq!(st.ino, ino);
+    assert_eq!(st.nlink, 2);
+    assert_eq!(unlink("/stat_test/file\0"), 0);
+    assert_eq!(stat("/stat_test/other\0", &mut st), 0);
+    assert_eq!(st.nlink, 1);
+
+    assert_eq!(fstat(1, &mut st), 0);
+    assert_eq!(st.mode & S_IFMT, S_IFCHR);
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    assert_eq!(fstat(pipe_fd[0], &mut st), 0);
+    assert_eq!(st.mode & S_IFMT, S_IFIFO);
+    close(pipe_fd[0]);
+    close(pipe_fd[1]);
+    assert_eq!(fstat(100, &mut st), -1);
+
+    assert_eq!(unlink("/stat_test/other\0"), 0);
+    assert_eq!(rmdir("/stat_test\0"), 0);
+    println!("stat_test passed!");
+    0
+}
@@ -0,0 +1,23 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{assert_root_only, get_time, suspend};
+
+/// The system sleeps until the timeout, no key being pressed, and comes
+/// back with the processes as they were.
+#[no_mangle]
+pub fn main() -> i32 {
+    let start = get_time();
+    let slept = suspend(100);
+    let elapsed = get_time() - start;
+    assert!(slept >= 100, "woke after {} ms", slept);
+    assert!(elapsed >= slept);
+
+    // and the scheduler with it, a child running to its end
+    assert_root_only(|| suspend(100));
+    println!("suspend_test passed!");
+    0
+}
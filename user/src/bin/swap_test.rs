@@ -0,0 +1,67 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exit, fork, getpid, mmap, munmap, wait, yield_};
+use user_lib::{MAP_ANONYMOUS, MAP_PRIVATE, PAGE_SIZE, PROT_READ, PROT_WRITE};
+
+/// Together the children need more than the physical memory, so part of
+/// their pages has to go to swap.
+const CHILDREN: usize = 3;
+const CHILD_PAGES: usize = 36 * 256;
+
+fn marker(pid: usize, page: usize) -> usize {
+    pid << 32 | page
+}
+
+fn child() -> i32 {
+    let pid = getpid() as usize;
+    let len = CHILD_PAGES * PAGE_SIZE;
+    let addr = mmap(
+        0,
+        len,
+        PROT_READ | PROT_WRITE,
+        MAP_PRIVATE | MAP_ANONYMOUS,
+        0,
+        0,
+    );
+    assert!(addr > 0);
+    let words = unsafe { core::slice::from_raw_parts_mut(addr as *mut usize, len / 8) };
+    let per_page = PAGE_SIZE / 8;
+    for page in 0..CHILD_PAGES {
+        words[page * per_page] = marker(pid, page);
+        words[page * per_page + per_page - 1] = !marker(pid, page);
+        if page % 256 == 0 {
+            yield_();
+        }
+    }
+    // every page must come back as it was written, wherever it has been
+    for page in 0..CHILD_PAGES {
+        if words[page * per_page] != marker(pid, page)
+            || words[page * per_page + per_page - 1] != !marker(pid, page)
+        {
+            println!("page {} of process {} is corrupted", page, pid);
+            return -1;
+        }
+    }
+    assert_eq!(munmap(addr as usize, len), 0);
+    0
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    for _ in 0..CHILDREN {
+        if fork() == 0 {
+            exit(child());
+        }
+    }
+    let mut exit_code: i32 = 0;
+    for _ in 0..CHILDREN {
+        assert!(wait(&mut exit_code) > 0);
+        assert_eq!(exit_code, 0);
+    }
+    println!("swap_test passed!");
+    0
+}
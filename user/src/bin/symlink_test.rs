@@ -0,0 +1,136 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, link, lstat, mkdir, open, read, readlink, rename, rmdir, stat, symlink, unlink, write,
+    OpenFlags, Stat,
+};
+
+fn create(path: &str, data: &[u8]) {
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, data), data.len() as isize);
+    close(fd as usize);
+}
+
+/// What the file at `path` holds, if it exists.
+fn read_file<'a>(path: &str, buf: &'a mut [u8]) -> Option<&'a [u8]> {
+    let fd = open(path, OpenFlags::RDONLY);
+    if fd < 0 {
+        return None;
+    }
+    let len = read(fd as usize, buf) as usize;
+    close(fd as usize);
+    Some(&buf[..len])
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut buf = [0u8; 32];
+    assert_eq!(mkdir("/symlink_test\0"), 0);
+    assert_eq!(mkdir("/symlink_test/dir\0"), 0);
+    create("/symlink_test/dir/file\0", b"target");
+
+    // absolute and relative targets, the latter from the link's directory
+    assert_eq!(
+        symlink("/symlink_test/dir/file\0", "/symlink_test/abs\0"),
+        0
+    );
+    assert_eq!(symlink("dir/file\0", "/symlink_test/rel\0"), 0);
+    assert_eq!(symlink("../symlink_test/dir\0", "/symlink_test/up\0"), 0);
+    assert_eq!(symlink("x\0", "/symlink_test/abs\0"), -1);
+    assert_eq!(
+        read_file("/symlink_test/abs\0", &mut buf),
+        Some(&b"target"[..])
+    );
+    assert_eq!(
+        read_file("/symlink_test/rel\0", &mut buf),
+        Some(&b"target"[..])
+    );
+    assert_eq!(
+        read_file("/symlink_test/up/file\0", &mut buf),
+        Some(&b"target"[..])
+    );
+    assert_eq!(
+        read_file("/symlink_test/up/../rel\0", &mut buf),
+        Some(&b"target"[..])
+    );
+    let len = readlink("/symlink_test/rel\0", &mut buf);
+    assert_eq!(&buf[..len as usize], b"dir/file");
+    assert_eq!(readlink("/symlink_test/rel\0", &mut buf[..3]), 3);
+    assert_eq!(readlink("/symlink_test/dir/file\0", &mut buf), -1);
+
+    // stat follows the link, lstat does not
+    let mut st = Stat::default();
+    assert_eq!(stat("/symlink_test/rel\0", &mut st), 0);
+    assert!(st.is_file());
+    assert_eq!(st.size, 6);
+    assert_eq!(lstat("/symlink_test/rel\0", &mut st), 0);
+    assert!(st.is_symlink());
+    assert_eq!(st.size, 8);
+
+    // O_NOFOLLOW refuses a link as the last component only
+    assert!(
+        open(
+            "/symlink_test/rel\0",
+            OpenFlags::RDONLY | OpenFlags::NOFOLLOW
+        ) < 0
+    );
+    let fd = open(
+        "/symlink_test/up/file\0",
+        OpenFlags::RDONLY | OpenFlags::NOFOLLOW,
+    );
+    assert!(fd > 0);
+    close(fd as usize);
+
+    // dangling links and loops
+    assert_eq!(symlink("nowhere\0", "/symlink_test/dangling\0"), 0);
+    assert_eq!(read_file("/symlink_test/dangling\0", &mut buf), None);
+    assert_eq!(symlink("loop_b\0", "/symlink_test/loop_a\0"), 0);
+    assert_eq!(symlink("loop_a\0", "/symlink_test/loop_b\0"), 0);
+    assert_eq!(read_file("/symlink_test/loop_a\0", &mut buf), None);
+    assert_eq!(lstat("/symlink_test/loop_a\0", &mut st), 0);
+
+    // into another file system
+    assert_eq!(symlink("/tmp\0", "/symlink_test/tmp\0"), 0);
+    create("/symlink_test/tmp/symlink_test\0", b"tmpfs");
+    assert_eq!(
+        read_file("/tmp/symlink_test\0", &mut buf),
+        Some(&b"tmpfs"[..])
+    );
+    assert_eq!(unlink("/symlink_test/tmp/symlink_test\0"), 0);
+
+    // link, rename and unlink act on the link itself
+    assert_eq!(link("/symlink_test/rel\0", "/symlink_test/rel2\0"), 0);
+    assert_eq!(rename("/symlink_test/rel2\0", "/symlink_test/rel3\0"), 0);
+    assert_eq!(
+        read_file("/symlink_test/rel3\0", &mut buf),
+        Some(&b"target"[..])
+    );
+    for path in [
+        "/symlink_test/abs\0",
+        "/symlink_test/rel\0",
+        "/symlink_test/rel3\0",
+        "/symlink_test/up\0",
+        "/symlink_test/dangling\0",
+        "/symlink_test/loop_a\0",
+        "/symlink_test/loop_b\0",
+        "/symlink_test/tmp\0",
+    ]
+    .iter()
+    {
+        assert_eq!(unlink(path), 0);
+    }
+    assert_eq!(
+        read_file("/symlink_test/dir/file\0", &mut buf),
+        Some(&b"target"[..])
+    );
+    assert_eq!(unlink("/symlink_test/dir/file\0"), 0);
+    assert_eq!(rmdir("/symlink_test/dir\0"), 0);
+    assert_eq!(rmdir("/symlink_test\0"), 0);
+    println!("symlink_test passed!");
+    0
+}
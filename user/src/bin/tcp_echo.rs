@@ -0,0 +1,45 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+// use `nc localhost 6201` to talk to the echo server
+
+use user_lib::{
+    accept, bind, close, listen, read, socket, write, SockAddrIn, AF_INET, SOCK_STREAM,
+};
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = socket(AF_INET, SOCK_STREAM, 0);
+    if fd < 0
+        || bind(fd as usize, &SockAddrIn::new([0, 0, 0, 0], 80)) < 0
+        || listen(fd as usize, 8) < 0
+    {
+        println!("Failed to listen on port 80");
+        return -1;
+    }
+    let fd = fd as usize;
+    loop {
+        let mut peer = SockAddrIn::default();
+        let client = accept(fd, Some(&mut peer));
+        if client < 0 {
+            println!("Failed to accept a client on port 80");
+            return -1;
+        }
+        let client = client as usize;
+        println!("echo to {:?}:{}", peer.ip(), peer.port());
+        let mut buf = [0u8; 1024];
+        loop {
+            let len = read(client, &mut buf);
+            if len <= 0 {
+                break;
+            }
+            if write(client, &buf[..len as usize]) < 0 {
+                break;
+            }
+        }
+        close(client);
+    }
+}
@@ -10,7 +10,9 @@ extern crate alloc;
 
 // use http://localhost:6201/ to access the http server
 
-use user_lib::{accept, listen, read, write};
+use user_lib::{
+    accept, bind, close, listen, read, socket, write, SockAddrIn, AF_INET, SOCK_STREAM,
+};
 
 // get url from the tcp request list.
 fn get_url_from_tcp_request(req: &[u8]) -> String {
@@ -126,23 +128,34 @@ fn handle_tcp_client(client_fd: usize) -> bool {
 pub fn main() -> i32 {
     println!("This is a very simple http server");
 
-    let tcp_fd = listen(80);
+    let tcp_fd = socket(AF_INET, SOCK_STREAM, 0);
 
-    if tcp_fd < 0 {
+    if tcp_fd < 0
+        || bind(tcp_fd as usize, &SockAddrIn::new([0, 0, 0, 0], 80)) < 0
+        || listen(tcp_fd as usize, 8) < 0
+    {
         println!("Failed to listen on port 80");
         return -1;
     }
 
     loop {
-        let client = accept(tcp_fd as usize);
-        println!("client connected: {}", client);
+        let mut peer = SockAddrIn::default();
+        let client = accept(tcp_fd as usize, Some(&mut peer));
+        println!(
+            "client connected: {} from {:?}:{}",
+            client,
+            peer.ip(),
+            peer.port()
+        );
 
         if client < 1 {
             println!("Failed to accept a client on port 80");
             return -1;
         }
 
-        if handle_tcp_client(client as usize) {
+        let closing = handle_tcp_client(client as usize);
+        close(client as usize);
+        if closing {
             break;
         }
     }
@@ -0,0 +1,97 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, epoll_create1, epoll_ctl, epoll_wait, get_time, read, sleep, timerfd_create,
+    timerfd_gettime, timerfd_settime, EpollEvent, ITimerSpec, OpenFlags, CLOCK_MONOTONIC, EAGAIN,
+    EPOLLIN, EPOLL_CTL_ADD, TFD_NONBLOCK, TFD_TIMER_ABSTIME,
+};
+
+fn read_expirations(fd: usize) -> isize {
+    let mut buf = [0u8; 8];
+    let len = read(fd, &mut buf);
+    if len != 8 {
+        return len;
+    }
+    u64::from_ne_bytes(buf) as isize
+}
+
+fn one_shot() {
+    let fd = timerfd_create(CLOCK_MONOTONIC, 0);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let mut curr = ITimerSpec::default();
+    assert_eq!(timerfd_gettime(fd, &mut curr), 0);
+    assert_eq!(curr.it_value.as_ms(), 0);
+
+    let start = get_time();
+    assert_eq!(timerfd_settime(fd, 0, &ITimerSpec::from_ms(50, 0), None), 0);
+    assert_eq!(timerfd_gettime(fd, &mut curr), 0);
+    assert!(curr.it_value.as_ms() > 0 && curr.it_value.as_ms() <= 50);
+    // read sleeps until it expires, once
+    assert_eq!(read_expirations(fd), 1);
+    assert!(get_time() - start >= 50);
+    assert_eq!(timerfd_gettime(fd, &mut curr), 0);
+    assert_eq!(curr.it_value.as_ms(), 0);
+
+    // at a time of the clock, and disarmed before it comes
+    let at = get_time() as usize + 1000;
+    assert_eq!(
+        timerfd_settime(fd, TFD_TIMER_ABSTIME, &ITimerSpec::from_ms(at, 0), None),
+        0
+    );
+    let mut old = ITimerSpec::default();
+    assert_eq!(
+        timerfd_settime(fd, 0, &ITimerSpec::default(), Some(&mut old)),
+        0
+    );
+    assert!(old.it_value.as_ms() > 900);
+    close(fd);
+}
+
+fn periodic() {
+    let fd = timerfd_create(CLOCK_MONOTONIC, TFD_NONBLOCK);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(read_expirations(fd), EAGAIN);
+    assert_eq!(
+        timerfd_settime(fd, 0, &ITimerSpec::from_ms(20, 20), None),
+        0
+    );
+    // the expirations add up while nobody reads
+    sleep(105);
+    assert!(read_expirations(fd) >= 5);
+    assert_eq!(read_expirations(fd), EAGAIN);
+    let mut curr = ITimerSpec::default();
+    assert_eq!(timerfd_gettime(fd, &mut curr), 0);
+    assert_eq!(curr.it_interval.as_ms(), 20);
+
+    // and an event loop waits for them with epoll
+    let epfd = epoll_create1(OpenFlags::empty());
+    assert!(epfd > 0);
+    let epfd = epfd as usize;
+    let event = EpollEvent {
+        events: EPOLLIN,
+        data: 1,
+    };
+    assert_eq!(epoll_ctl(epfd, EPOLL_CTL_ADD, fd, &event), 0);
+    let mut events = [EpollEvent::default(); 1];
+    for _ in 0..3 {
+        assert_eq!(epoll_wait(epfd, &mut events, 1000), 1);
+        assert_eq!(events[0].data, 1);
+        assert!(read_expirations(fd) >= 1);
+    }
+    close(epfd);
+    close(fd);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    one_shot();
+    periodic();
+    println!("timerfd_test passed!");
+    0
+}
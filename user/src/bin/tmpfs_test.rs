@@ -0,0 +1,83 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, link, mkdir, mmap, mount, munmap, open, pread, pwrite, read, rename};
+use user_lib::{rmdir, stat, umount, unlink, write, OpenFlags, Stat, MAP_SHARED};
+use user_lib::{PAGE_SIZE, PROT_READ, PROT_WRITE};
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut tmp = Stat::default();
+    let mut root = Stat::default();
+    assert_eq!(stat("/tmp\0", &mut tmp), 0);
+    assert_eq!(stat("/\0", &mut root), 0);
+    assert!(tmp.is_dir());
+    assert_ne!(tmp.dev, root.dev);
+
+    let fd = open("/tmp/file\0", OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd >= 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, b"hello, tmpfs"), 12);
+    // a hole between the two writes reads as zeros
+    assert_eq!(pwrite(fd, b"end", 3 * PAGE_SIZE), 3);
+    let mut buf = [0xffu8; 16];
+    assert_eq!(pread(fd, &mut buf, 0), 16);
+    assert_eq!(&buf[..12], b"hello, tmpfs");
+    assert!(buf[12..].iter().all(|b| *b == 0));
+    assert_eq!(pread(fd, &mut buf, 2 * PAGE_SIZE), 16);
+    assert!(buf.iter().all(|b| *b == 0));
+    let mut st = Stat::default();
+    assert_eq!(stat("/tmp/file\0", &mut st), 0);
+    assert_eq!(st.size as usize, 3 * PAGE_SIZE + 3);
+    // only the pages written take memory
+    assert_eq!(st.blocks as usize, 2 * PAGE_SIZE / 512);
+
+    // a shared mapping is the file itself
+    let addr = mmap(0, PAGE_SIZE, PROT_READ | PROT_WRITE, MAP_SHARED, fd, 0);
+    assert!(addr > 0);
+    let page = unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, PAGE_SIZE) };
+    assert_eq!(&page[..5], b"hello");
+    page[..5].copy_from_slice(b"HELLO");
+    assert_eq!(pread(fd, &mut buf[..5], 0), 5);
+    assert_eq!(&buf[..5], b"HELLO");
+    assert_eq!(pwrite(fd, b"h", 0), 1);
+    assert_eq!(page[0], b'h');
+    assert_eq!(munmap(addr as usize, PAGE_SIZE), 0);
+    close(fd);
+
+    // directories, links and renames
+    assert_eq!(mkdir("/tmp/dir\0"), 0);
+    assert_eq!(mkdir("/tmp/dir\0"), -1);
+    assert_eq!(link("/tmp/file\0", "/tmp/dir/other\0"), 0);
+    assert_eq!(stat("/tmp/file\0", &mut st), 0);
+    assert_eq!(st.nlink, 2);
+    assert_eq!(rename("/tmp/dir/other\0", "/tmp/dir/moved\0"), 0);
+    assert_eq!(unlink("/tmp/file\0"), 0);
+    assert_eq!(rmdir("/tmp/dir\0"), -1);
+    let fd = open("/tmp/dir/moved\0", OpenFlags::RDONLY);
+    assert!(fd >= 0);
+    assert_eq!(read(fd as usize, &mut buf[..5]), 5);
+    assert_eq!(&buf[..5], b"hELLO");
+    close(fd as usize);
+    assert_eq!(stat("/tmp/dir/moved\0", &mut st), 0);
+    assert_eq!(st.nlink, 1);
+    // no links across file systems
+    assert_eq!(link("/tmp/dir/moved\0", "/tmpfs_test_link\0"), -1);
+    assert_eq!(unlink("/tmp/dir/moved\0"), 0);
+    assert_eq!(rmdir("/tmp/dir\0"), 0);
+
+    // a new tmpfs starts out empty, and is gone once unmounted
+    assert_eq!(mkdir("/tmp/mnt\0"), 0);
+    assert_eq!(mount("tmpfs\0", "/tmp/mnt\0", "tmpfs\0"), 0);
+    let fd = open("/tmp/mnt/file\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd >= 0);
+    close(fd as usize);
+    assert_eq!(umount("/tmp/mnt\0"), 0);
+    assert_eq!(open("/tmp/mnt/file\0", OpenFlags::RDONLY), -1);
+    assert_eq!(rmdir("/tmp/mnt\0"), 0);
+    println!("tmpfs_test passed!");
+    0
+}
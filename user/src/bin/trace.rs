@@ -0,0 +1,61 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use user_lib::{
+    close, exec, exit, fork, open, read, trace_start, trace_stop, waitpid, write, OpenFlags,
+};
+
+/// Run `argv`, the program first, and wait for it.
+fn run(argv: &[&str]) {
+    let pid = fork();
+    if pid == 0 {
+        // the arguments end with the NUL the kernel put after them
+        let mut args: Vec<*const u8> = argv.iter().map(|arg| arg.as_ptr()).collect();
+        args.push(core::ptr::null());
+        exec(argv[0], &args);
+        println!("trace: cannot run {}", argv[0]);
+        exit(-1);
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+}
+
+/// trace OUT PROGRAM [ARGS...]: trace the kernel while the program runs,
+/// and write the trace to OUT, for chrome://tracing or ui.perfetto.dev to
+/// show; OUT on the FAT disk, mounted at /fat, is there to take to the host.
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc < 3 {
+        println!("usage: trace OUT PROGRAM [ARGS...]");
+        return -1;
+    }
+    if trace_start() < 0 {
+        println!("trace: only root may trace");
+        return -1;
+    }
+    run(&argv[2..]);
+    let records = trace_stop();
+    let src = open("/proc/trace\0", OpenFlags::RDONLY);
+    let dst = open(argv[1], OpenFlags::WRONLY | OpenFlags::CREATE | OpenFlags::TRUNC);
+    if src < 0 || dst < 0 {
+        println!("trace: cannot write {}", argv[1]);
+        return -1;
+    }
+    let mut buf = [0u8; 4096];
+    loop {
+        let len = read(src as usize, &mut buf);
+        if len <= 0 {
+            break;
+        }
+        write(dst as usize, &buf[..len as usize]);
+    }
+    close(src as usize);
+    close(dst as usize);
+    println!("trace: {} records in {}", records, argv[1]);
+    0
+}
@@ -0,0 +1,71 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::string::String;
+use user_lib::{
+    assert_root_only, close, exit, fork, open, pipe, poll, read, sleep, trace_start, trace_stop,
+    waitpid, write,
+};
+use user_lib::{OpenFlags, PollFd, POLLIN};
+
+fn read_trace() -> String {
+    let fd = open("/proc/trace\0", OpenFlags::RDONLY);
+    assert!(fd >= 0);
+    let mut text = String::new();
+    let mut buf = [0u8; 1024];
+    loop {
+        let len = read(fd as usize, &mut buf);
+        if len <= 0 {
+            break;
+        }
+        text.push_str(core::str::from_utf8(&buf[..len as usize]).unwrap());
+    }
+    close(fd as usize);
+    text
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(trace_start(), 0);
+    // switches, syscalls, timer ticks and a task polling a pipe
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let pid = fork();
+    if pid == 0 {
+        sleep(20);
+        write(fds[1], b"x");
+        exit(0);
+    }
+    let mut pollfd = [PollFd {
+        fd: fds[0] as i32,
+        events: POLLIN,
+        revents: 0,
+    }];
+    assert_eq!(poll(&mut pollfd, 1000), 1);
+    let mut byte = [0u8; 1];
+    assert_eq!(read(fds[0], &mut byte), 1);
+    let mut exit_code = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    let records = trace_stop();
+    assert!(records > 0);
+
+    let trace = read_trace();
+    assert!(trace.starts_with("{\"traceEvents\":["));
+    assert!(trace.trim_end().ends_with('}'));
+    assert!(trace.contains("\"name\":\"syscall\",\"ph\":\"B\""));
+    assert!(trace.contains("\"name\":\"timer\""));
+    assert!(trace.contains("\"name\":\"poll pending\",\"ph\":\"i\""));
+    assert!(trace.contains("\"name\":\"poll ready\",\"ph\":\"i\""));
+    assert!(trace.contains("\"name\":\"hart 0\""));
+    assert!(trace.contains("\"name\":\"idle\""));
+    // stopped: the same trace each time
+    assert_eq!(read_trace(), trace);
+
+    assert_root_only(trace_start);
+    println!("trace_test passed!");
+    0
+}
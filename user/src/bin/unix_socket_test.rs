@@ -0,0 +1,218 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    accept_un, bind_un, close, connect_un, exit, fork, getpeername_un, getsockname_un, listen,
+    open, pipe, read, recv_fds, recvfrom_un, send_fds, sendto_un, shutdown, socket, socketpair,
+    stat, unlink, waitpid, write, OpenFlags, SockAddrUn, Stat, AF_UNIX, EADDRINUSE, EAGAIN,
+    ECONNREFUSED, EDESTADDRREQ, ENOENT, ENOTCONN, EOPNOTSUPP, EPIPE, SHUT_WR, SOCK_DGRAM,
+    SOCK_NONBLOCK, SOCK_STREAM,
+};
+
+const STREAM_PATH: &str = "/tmp/unix_stream\0";
+const DGRAM_A: &str = "/tmp/unix_dgram_a\0";
+const DGRAM_B: &str = "/tmp/unix_dgram_b\0";
+
+fn socket_fd(kind: usize) -> usize {
+    let fd = socket(AF_UNIX, kind, 0);
+    assert!(fd > 0);
+    fd as usize
+}
+
+fn pair(kind: usize) -> [usize; 2] {
+    let mut sv = [0usize; 2];
+    assert_eq!(socketpair(AF_UNIX, kind, 0, &mut sv), 0);
+    sv
+}
+
+fn path(path: &str) -> &str {
+    path.trim_end_matches('\0')
+}
+
+fn binding() {
+    let a = socket_fd(SOCK_STREAM);
+    let b = socket_fd(SOCK_STREAM);
+    let mut addr = SockAddrUn::new("");
+    assert_eq!(getsockname_un(a, &mut addr), 0);
+    assert_eq!(addr.path(), "");
+    assert_eq!(getpeername_un(a, &mut addr), ENOTCONN);
+    assert_eq!(bind_un(a, &SockAddrUn::new(STREAM_PATH)), 0);
+    assert_eq!(getsockname_un(a, &mut addr), 0);
+    assert_eq!(addr.path(), path(STREAM_PATH));
+    // the path is a socket file, taken until unlinked
+    let mut st = Stat::default();
+    assert_eq!(stat(STREAM_PATH, &mut st), 0);
+    assert!(st.is_socket());
+    assert!(open(STREAM_PATH, OpenFlags::RDONLY) < 0);
+    assert_eq!(bind_un(b, &SockAddrUn::new(STREAM_PATH)), EADDRINUSE);
+    // nobody listens yet
+    assert_eq!(connect_un(b, &SockAddrUn::new(STREAM_PATH)), ECONNREFUSED);
+    assert_eq!(
+        connect_un(b, &SockAddrUn::new("/tmp/unix_nowhere\0")),
+        ENOENT
+    );
+    close(a);
+    close(b);
+    let dgram = socket_fd(SOCK_DGRAM);
+    assert_eq!(listen(dgram, 1), EOPNOTSUPP);
+    close(dgram);
+    // the file stays with nobody bound to it
+    let b = socket_fd(SOCK_STREAM);
+    assert_eq!(connect_un(b, &SockAddrUn::new(STREAM_PATH)), ECONNREFUSED);
+    close(b);
+    assert_eq!(unlink(STREAM_PATH), 0);
+}
+
+fn stream() {
+    let listener = socket_fd(SOCK_STREAM);
+    assert_eq!(bind_un(listener, &SockAddrUn::new(STREAM_PATH)), 0);
+    assert_eq!(listen(listener, 2), 0);
+    let pid = fork();
+    if pid == 0 {
+        let fd = socket_fd(SOCK_STREAM);
+        assert_eq!(connect_un(fd, &SockAddrUn::new(STREAM_PATH)), 0);
+        let mut addr = SockAddrUn::new("");
+        assert_eq!(getpeername_un(fd, &mut addr), 0);
+        assert_eq!(addr.path(), path(STREAM_PATH));
+        assert_eq!(write(fd, b"hello, "), 7);
+        assert_eq!(write(fd, b"server"), 6);
+        let mut buf = [0u8; 16];
+        assert_eq!(read(fd, &mut buf), 5);
+        assert_eq!(&buf[..5], b"howdy");
+        close(fd);
+        exit(0);
+    }
+    let mut addr = SockAddrUn::new("x");
+    let conn = accept_un(listener, Some(&mut addr));
+    assert!(conn > 0);
+    let conn = conn as usize;
+    // the client is bound nowhere
+    assert_eq!(addr.path(), "");
+    let mut buf = [0u8; 32];
+    let mut len = 0;
+    while len < 13 {
+        let n = read(conn, &mut buf[len..]);
+        assert!(n > 0);
+        len += n as usize;
+    }
+    assert_eq!(&buf[..13], b"hello, server");
+    assert_eq!(write(conn, b"howdy"), 5);
+    // the client is done
+    assert_eq!(read(conn, &mut buf), 0);
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(sendto_un(conn, b"anyone?", None), EPIPE);
+    close(conn);
+    close(listener);
+    assert_eq!(unlink(STREAM_PATH), 0);
+}
+
+fn datagrams() {
+    let a = socket_fd(SOCK_DGRAM);
+    let b = socket_fd(SOCK_DGRAM);
+    assert_eq!(bind_un(a, &SockAddrUn::new(DGRAM_A)), 0);
+    assert_eq!(bind_un(b, &SockAddrUn::new(DGRAM_B)), 0);
+    assert_eq!(sendto_un(a, b"first", None), EDESTADDRREQ);
+    assert_eq!(sendto_un(a, b"first", Some(&SockAddrUn::new(DGRAM_B))), 5);
+    assert_eq!(sendto_un(a, b"second", Some(&SockAddrUn::new(DGRAM_B))), 6);
+    // one datagram at a time, the rest of it gone
+    let mut buf = [0u8; 3];
+    let mut addr = SockAddrUn::new("");
+    assert_eq!(recvfrom_un(b, &mut buf, Some(&mut addr)), 3);
+    assert_eq!(&buf, b"fir");
+    assert_eq!(addr.path(), path(DGRAM_A));
+    let mut buf = [0u8; 16];
+    assert_eq!(recvfrom_un(b, &mut buf, None), 6);
+    assert_eq!(&buf[..6], b"second");
+    // to the peer by default
+    assert_eq!(connect_un(b, &SockAddrUn::new(DGRAM_A)), 0);
+    assert_eq!(sendto_un(b, b"back", None), 4);
+    assert_eq!(recvfrom_un(a, &mut buf, None), 4);
+    assert_eq!(&buf[..4], b"back");
+    close(a);
+    assert_eq!(sendto_un(b, b"gone", None), ECONNREFUSED);
+    close(b);
+    assert_eq!(unlink(DGRAM_A), 0);
+    assert_eq!(unlink(DGRAM_B), 0);
+}
+
+fn pairs() {
+    let [a, b] = pair(SOCK_STREAM);
+    assert_eq!(write(a, b"ping"), 4);
+    let mut buf = [0u8; 16];
+    assert_eq!(read(b, &mut buf), 4);
+    assert_eq!(&buf[..4], b"ping");
+    assert_eq!(write(b, b"pong"), 4);
+    assert_eq!(read(a, &mut buf), 4);
+    assert_eq!(&buf[..4], b"pong");
+    assert_eq!(shutdown(a, SHUT_WR), 0);
+    assert_eq!(read(b, &mut buf), 0);
+    assert_eq!(sendto_un(a, b"late", None), EPIPE);
+    close(a);
+    close(b);
+
+    // message boundaries stay
+    let [a, b] = pair(SOCK_DGRAM);
+    assert_eq!(write(a, b"one"), 3);
+    assert_eq!(write(a, b"two"), 3);
+    assert_eq!(read(b, &mut buf), 3);
+    assert_eq!(&buf[..3], b"one");
+    assert_eq!(read(b, &mut buf), 3);
+    close(a);
+    close(b);
+
+    let [a, b] = pair(SOCK_STREAM | SOCK_NONBLOCK);
+    assert_eq!(read(a, &mut buf), EAGAIN);
+    close(a);
+    assert_eq!(read(b, &mut buf), 0);
+    close(b);
+}
+
+fn passing() {
+    let [a, b] = pair(SOCK_STREAM);
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    assert_eq!(send_fds(a, b"pipe", &pipe_fd[..1]), 4);
+    // the open file travels, not the number
+    close(pipe_fd[0]);
+    let mut buf = [0u8; 16];
+    let mut fds = [0usize; 4];
+    assert_eq!(recv_fds(b, &mut buf, &mut fds), (4, 1));
+    assert_eq!(&buf[..4], b"pipe");
+    assert_eq!(write(pipe_fd[1], b"through"), 7);
+    assert_eq!(read(fds[0], &mut buf), 7);
+    assert_eq!(&buf[..7], b"through");
+    close(fds[0]);
+    close(pipe_fd[1]);
+
+    // a read stops at the next files sent, so they come with their bytes
+    assert_eq!(write(a, b"plain"), 5);
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    assert_eq!(send_fds(a, b"both", &pipe_fd), 4);
+    assert_eq!(recv_fds(b, &mut buf, &mut fds), (5, 0));
+    assert_eq!(&buf[..5], b"plain");
+    // room for one: the other is closed
+    assert_eq!(recv_fds(b, &mut buf, &mut fds[..1]), (4, 1));
+    close(pipe_fd[0]);
+    close(pipe_fd[1]);
+    // the write end left behind was closed
+    assert_eq!(read(fds[0], &mut buf), 0);
+    close(fds[0]);
+    close(a);
+    close(b);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    binding();
+    stream();
+    datagrams();
+    pairs();
+    passing();
+    println!("unix_socket_test passed!");
+    0
+}
@@ -16,7 +16,10 @@ const LINE_START: &str = ">> ";
 use alloc::string::String;
 use alloc::vec::Vec;
 use user_lib::console::getchar;
-use user_lib::{close, dup, exec, fork, open, pipe, waitpid, OpenFlags};
+use user_lib::{
+    close, dup2, exec, fork, getpid, killpg, open, pipe, setpgid, sigaction, spawn, tcsetpgrp,
+    waitpid_untraced, wifstopped, OpenFlags, SigAction, SIGCONT, SIGINT, SIGTSTP, SIG_DFL, SIG_IGN,
+};
 
 #[derive(Debug)]
 struct ProcessArguments {
@@ -74,9 +77,58 @@ impl ProcessArguments {
     }
 }
 
+/// The processes of a command line, stopped by ^Z.
+struct Job {
+    pgid: usize,
+    pids: Vec<isize>,
+}
+
+/// Have the process group of the console be `pgid`, for ^C and ^Z.
+fn set_foreground(pgid: usize) {
+    tcsetpgrp(0, pgid);
+}
+
+/// Wait for the processes `pids` of the group `pgid` in the foreground,
+/// returning them as a job if they are stopped.
+fn wait_job(pgid: usize, mut pids: Vec<isize>) -> Option<Job> {
+    set_foreground(pgid);
+    let mut stopped = false;
+    while let Some(&pid) = pids.first() {
+        let mut exit_code: i32 = 0;
+        let exit_pid = waitpid_untraced(pid as usize, &mut exit_code);
+        assert_eq!(pid, exit_pid);
+        if wifstopped(exit_code) {
+            stopped = true;
+            break;
+        }
+        //println!("Shell: Process {} exited with code {}", pid, exit_code);
+        pids.remove(0);
+    }
+    set_foreground(getpid() as usize);
+    if stopped {
+        println!("[{}] Stopped", pgid);
+        Some(Job { pgid, pids })
+    } else {
+        None
+    }
+}
+
+/// The shell stays when ^C or ^Z is typed for it, and what it runs has
+/// them back.
+fn set_job_signals(handler: usize) {
+    let action = SigAction::new(handler, 0);
+    sigaction(SIGINT, Some(&action), None);
+    sigaction(SIGTSTP, Some(&action), None);
+}
+
 #[no_mangle]
 pub fn main() -> i32 {
     println!("Rust user shell");
+    // a group of its own, in the foreground of the console
+    setpgid(0, 0);
+    set_foreground(getpid() as usize);
+    set_job_signals(SIG_IGN);
+    let mut jobs: Vec<Job> = Vec::new();
     let mut line: String = String::new();
     print!("{}", LINE_START);
     loop {
@@ -84,7 +136,17 @@ pub fn main() -> i32 {
         match c {
             LF | CR => {
                 println!("");
-                if !line.is_empty() {
+                if line.trim() == "fg" {
+                    // go on with the job stopped last
+                    match jobs.pop() {
+                        Some(job) => {
+                            killpg(job.pgid, SIGCONT);
+                            jobs.extend(wait_job(job.pgid, job.pids));
+                        }
+                        None => println!("fg: no stopped job"),
+                    }
+                    line.clear();
+                } else if !line.is_empty() {
                     let splited: Vec<_> = line.as_str().split('|').collect();
                     let process_arguments_list: Vec<_> = splited
                         .iter()
@@ -121,9 +183,30 @@ pub fn main() -> i32 {
                             }
                         }
                         let mut children: Vec<_> = Vec::new();
+                        // led by the first of them
+                        let mut pgid = 0;
                         for (i, process_argument) in process_arguments_list.iter().enumerate() {
+                            // nothing to set up in the child, skip copying the shell
+                            if process_arguments_list.len() == 1
+                                && process_argument.input.is_empty()
+                                && process_argument.output.is_empty()
+                            {
+                                let args_copy = &process_argument.args_copy;
+                                let args_addr = &process_argument.args_addr;
+                                let pid = spawn(args_copy[0].as_str(), args_addr.as_slice());
+                                if pid == -1 {
+                                    println!("Error when executing!");
+                                } else {
+                                    setpgid(pid as usize, 0);
+                                    pgid = pid as usize;
+                                    children.push(pid);
+                                }
+                                continue;
+                            }
                             let pid = fork();
                             if pid == 0 {
+                                setpgid(0, pgid);
+                                set_job_signals(SIG_DFL);
                                 let input = &process_argument.input;
                                 let output = &process_argument.output;
                                 let args_copy = &process_argument.args_copy;
@@ -136,36 +219,32 @@ pub fn main() -> i32 {
                                         return -4;
                                     }
                                     let input_fd = input_fd as usize;
-                                    close(0);
-                                    assert_eq!(dup(input_fd), 0);
+                                    assert_eq!(dup2(input_fd, 0), 0);
                                     close(input_fd);
                                 }
                                 // redirect output
                                 if !output.is_empty() {
                                     let output_fd = open(
                                         output.as_str(),
-                                        OpenFlags::CREATE | OpenFlags::WRONLY,
+                                        OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::WRONLY,
                                     );
                                     if output_fd == -1 {
                                         println!("Error when opening file {}", output);
                                         return -4;
                                     }
                                     let output_fd = output_fd as usize;
-                                    close(1);
-                                    assert_eq!(dup(output_fd), 1);
+                                    assert_eq!(dup2(output_fd, 1), 1);
                                     close(output_fd);
                                 }
                                 // receive input from the previous process
                                 if i > 0 {
-                                    close(0);
                                     let read_end = pipes_fd.get(i - 1).unwrap()[0];
-                                    assert_eq!(dup(read_end), 0);
+                                    assert_eq!(dup2(read_end, 0), 0);
                                 }
                                 // send output to the next process
                                 if i < process_arguments_list.len() - 1 {
-                                    close(1);
                                     let write_end = pipes_fd.get(i).unwrap()[1];
-                                    assert_eq!(dup(write_end), 1);
+                                    assert_eq!(dup2(write_end, 1), 1);
                                 }
                                 // close all pipe ends inherited from the parent process
                                 for pipe_fd in pipes_fd.iter() {
@@ -179,6 +258,11 @@ pub fn main() -> i32 {
                                 }
                                 unreachable!();
                             } else {
+                                // here too, whichever of the two runs first
+                                if pgid == 0 {
+                                    pgid = pid as usize;
+                                }
+                                setpgid(pid as usize, pgid);
                                 children.push(pid);
                             }
                         }
@@ -186,11 +270,8 @@ pub fn main() -> i32 {
                             close(pipe_fd[0]);
                             close(pipe_fd[1]);
                         }
-                        let mut exit_code: i32 = 0;
-                        for pid in children.into_iter() {
-                            let exit_pid = waitpid(pid as usize, &mut exit_code);
-                            assert_eq!(pid, exit_pid);
-                            //println!("Shell: Process {} exited with code {}", pid, exit_code);
+                        if !children.is_empty() {
+                            jobs.extend(wait_job(pgid, children));
                         }
                     }
                     line.clear();
@@ -10,10 +10,14 @@ extern crate user_lib;
 // item of TESTS : app_name(argv_0), argv_1, argv_2, argv_3, exit_code
 static SUCC_TESTS: &[(&str, &str, &str, &str, i32)] = &[
     ("filetest_simple\0", "\0", "\0", "\0", 0),
+    ("brk_test\0", "\0", "\0", "\0", 0),
     ("cat\0", "filea\0", "\0", "\0", 0),
     ("cmdline_args\0", "1\0", "2\0", "3\0", 0),
+    ("cow_fork\0", "\0", "\0", "\0", 0),
     ("eisenberg\0", "\0", "\0", "\0", 0),
+    ("exec_shebang\0", "\0", "\0", "\0", 0),
     ("exit\0", "\0", "\0", "\0", 0),
+    ("efault_test\0", "\0", "\0", "\0", 0),
     ("fantastic_text\0", "\0", "\0", "\0", 0),
     ("forktest_simple\0", "\0", "\0", "\0", 0),
     ("forktest\0", "\0", "\0", "\0", 0),
@@ -21,12 +25,71 @@ static SUCC_TESTS: &[(&str, &str, &str, &str, i32)] = &[
     ("forktree\0", "\0", "\0", "\0", 0),
     ("hello_world\0", "\0", "\0", "\0", 0),
     ("huge_write\0", "\0", "\0", "\0", 0),
+    ("ipc_call_test\0", "\0", "\0", "\0", 0),
+    ("lazy_bss\0", "\0", "\0", "\0", 0),
     ("matrix\0", "\0", "\0", "\0", 0),
+    ("mmap_test\0", "\0", "\0", "\0", 0),
     ("mpsc_sem\0", "\0", "\0", "\0", 0),
+    ("mq_test\0", "\0", "\0", "\0", 0),
     ("peterson\0", "\0", "\0", "\0", 0),
     ("phil_din_mutex\0", "\0", "\0", "\0", 0),
+    ("pidfd_test\0", "\0", "\0", "\0", 0),
     ("pipe_large_test\0", "\0", "\0", "\0", 0),
+    ("pipe_splice_test\0", "\0", "\0", "\0", 0),
     ("pipetest\0", "\0", "\0", "\0", 0),
+    ("pshared_sync_test\0", "\0", "\0", "\0", 0),
+    ("ring_test\0", "\0", "\0", "\0", 0),
+    ("rlimit_nofile\0", "\0", "\0", "\0", 0),
+    ("shm_pc\0", "\0", "\0", "\0", 0),
+    ("spawn_bench\0", "\0", "\0", "\0", 0),
+    ("stack_grow\0", "\0", "\0", "\0", 0),
+    ("swap_test\0", "\0", "\0", "\0", 0),
+    ("mlock_test\0", "\0", "\0", "\0", 0),
+    ("madvise_test\0", "\0", "\0", "\0", 0),
+    ("vmstat_test\0", "\0", "\0", "\0", 0),
+    ("zero_page_test\0", "\0", "\0", "\0", 0),
+    ("mount_test\0", "\0", "\0", "\0", 0),
+    ("dir_test\0", "\0", "\0", "\0", 0),
+    ("link_test\0", "\0", "\0", "\0", 0),
+    ("seek_test\0", "\0", "\0", "\0", 0),
+    ("stat_test\0", "\0", "\0", "\0", 0),
+    ("fsync_test\0", "\0", "\0", "\0", 0),
+    ("fcntl_test\0", "\0", "\0", "\0", 0),
+    ("dup_test\0", "\0", "\0", "\0", 0),
+    ("devfs_test\0", "\0", "\0", "\0", 0),
+    ("proc_test\0", "\0", "\0", "\0", 0),
+    ("dmesg_test\0", "\0", "\0", "\0", 0),
+    ("trace_test\0", "\0", "\0", "\0", 0),
+    ("kcov_test\0", "\0", "\0", "\0", 0),
+    ("fault_test\0", "\0", "\0", "\0", 0),
+    ("replay_test\0", "\0", "\0", "\0", 0),
+    ("perf_test\0", "\0", "\0", "\0", 0),
+    ("objstat_test\0", "\0", "\0", "\0", 0),
+    ("reboot_test\0", "\0", "\0", "\0", 0),
+    ("suspend_test\0", "\0", "\0", "\0", 0),
+    ("tmpfs_test\0", "\0", "\0", "\0", 0),
+    ("fat_test\0", "\0", "\0", "\0", 0),
+    ("symlink_test\0", "\0", "\0", "\0", 0),
+    ("cwd_test\0", "\0", "\0", "\0", 0),
+    ("perm_test\0", "\0", "\0", "\0", 0),
+    ("caps_test\0", "\0", "\0", "\0", 0),
+    ("sparse_test\0", "\0", "\0", "\0", 0),
+    ("getdents_test\0", "\0", "\0", "\0", 0),
+    ("inotify_test\0", "\0", "\0", "\0", 0),
+    ("flock_test\0", "\0", "\0", "\0", 0),
+    ("fifo_test\0", "\0", "\0", "\0", 0),
+    ("poll_test\0", "\0", "\0", "\0", 0),
+    ("eventfd_test\0", "\0", "\0", "\0", 0),
+    ("timerfd_test\0", "\0", "\0", "\0", 0),
+    ("signal_test\0", "\0", "\0", "\0", 0),
+    ("pgrp_test\0", "\0", "\0", "\0", 0),
+    ("signalfd_test\0", "\0", "\0", "\0", 0),
+    ("itimer_test\0", "\0", "\0", "\0", 0),
+    ("clock_test\0", "\0", "\0", "\0", 0),
+    ("nanosleep_test\0", "\0", "\0", "\0", 0),
+    ("coredump_test\0", "\0", "\0", "\0", 0),
+    ("socket_test\0", "\0", "\0", "\0", 0),
+    ("unix_socket_test\0", "\0", "\0", "\0", 0),
     ("adder_peterson_spin\0", "\0", "\0", "\0", 0),
     ("adder_peterson_yield\0", "\0", "\0", "\0", 0),
     ("adder_mutex_blocking\0", "\0", "\0", "\0", 0),
@@ -57,7 +120,25 @@ static FAIL_TESTS: &[(&str, &str, &str, &str, i32)] = &[
     ("adder_simple_yield\0", "\0", "\0", "\0", -6),
 ];
 
-use user_lib::{exec, fork, waitpid};
+use user_lib::{exec, fork, objstat, waitpid, ObjStat, OBJ_FILE, OBJ_KINDS, OBJ_NAMES};
+use user_lib::{OBJ_PROCESS, OBJ_TASK};
+
+/// Tell the tasks, processes and files a test left alive; inodes and
+/// blocks may stay cached.
+fn report_leaks(test: &str) {
+    let mut stats = [ObjStat::default(); OBJ_KINDS];
+    if objstat(&mut stats, false) < 0 {
+        return;
+    }
+    for kind in [OBJ_TASK, OBJ_PROCESS, OBJ_FILE] {
+        if stats[kind].delta > 0 {
+            println!(
+                "\x1b[33mUsertests: {} left {} {} alive\x1b[0m",
+                test, stats[kind].delta, OBJ_NAMES[kind]
+            );
+        }
+    }
+}
 
 fn run_tests(tests: &[(&str, &str, &str, &str, i32)]) -> i32 {
     let mut pass_num = 0;
@@ -93,6 +174,8 @@ fn run_tests(tests: &[(&str, &str, &str, &str, i32)]) -> i32 {
             arr[3] = core::ptr::null::<u8>();
         }
 
+        let mut stats = [ObjStat::default(); OBJ_KINDS];
+        objstat(&mut stats, true);
         let pid = fork();
         if pid == 0 {
             exec(test.0, &arr[..]);
@@ -109,6 +192,7 @@ fn run_tests(tests: &[(&str, &str, &str, &str, i32)]) -> i32 {
                 "\x1b[32mUsertests: Test {} in Process {} exited with code {}\x1b[0m",
                 test.0, pid, exit_code
             );
+            report_leaks(test.0);
         }
     }
     pass_num
@@ -0,0 +1,84 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exit, fork, meminfo, mmap, munmap, vmstat, waitpid, MemInfo, VmStat};
+use user_lib::{MAP_ANONYMOUS, MAP_PRIVATE, PAGE_SIZE, PROT_READ, PROT_WRITE};
+
+const PAGES: usize = 64;
+const LEN: usize = PAGES * PAGE_SIZE;
+
+fn mem() -> MemInfo {
+    let mut info = MemInfo::default();
+    assert_eq!(meminfo(&mut info), 0);
+    info
+}
+
+fn stat(pid: usize) -> VmStat {
+    let mut stat = VmStat::default();
+    assert_eq!(vmstat(pid, &mut stat), 0);
+    stat
+}
+
+fn touch(addr: usize, value: u8) {
+    for page in 0..PAGES {
+        unsafe { ((addr + page * PAGE_SIZE) as *mut u8).write_volatile(value) };
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let info = mem();
+    assert!(info.free <= info.total && info.heap_used <= info.heap_total);
+    println!(
+        "memory: {} KiB free of {} KiB, heap {} KiB of {} KiB, page cache {} KiB",
+        info.free / 1024,
+        info.total / 1024,
+        info.heap_used / 1024,
+        info.heap_total / 1024,
+        info.page_cache / 1024
+    );
+
+    // an anonymous mapping takes no memory until it is touched
+    let before = stat(0);
+    let addr = mmap(
+        0,
+        LEN,
+        PROT_READ | PROT_WRITE,
+        MAP_PRIVATE | MAP_ANONYMOUS,
+        0,
+        0,
+    ) as usize;
+    let mapped = stat(0);
+    assert_eq!(mapped.vsz, before.vsz + LEN);
+    assert_eq!(mapped.rss, before.rss);
+    let free = mem().free;
+    touch(addr, 1);
+    let touched = stat(0);
+    assert_eq!(touched.rss, before.rss + LEN);
+    assert!(mem().free + LEN <= free);
+
+    // a child shares the pages until it writes them
+    let pid = fork();
+    if pid == 0 {
+        let shared = stat(0);
+        assert_eq!(shared.rss, touched.rss);
+        let free = mem().free;
+        touch(addr, 2);
+        let copied = free - mem().free;
+        assert!(copied >= LEN);
+        println!("copy on write: {} KiB copied", copied / 1024);
+        exit(0);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(vmstat(pid as usize, &mut VmStat::default()), -1);
+
+    assert_eq!(munmap(addr, LEN), 0);
+    assert_eq!(stat(0).rss, before.rss);
+    println!("vmstat_test passed!");
+    0
+}
@@ -0,0 +1,62 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use user_lib::{
+    exec, exit, fork, inotify_add_watch, inotify_events, inotify_init1, read, waitpid, OpenFlags,
+};
+use user_lib::{IN_DELETE_SELF, IN_IGNORED, IN_MODIFY};
+
+/// Run `argv`, the program first, and wait for it.
+fn run(argv: &[&str]) {
+    let pid = fork();
+    if pid == 0 {
+        // the arguments end with the NUL the kernel put after them
+        let mut args: Vec<*const u8> = argv.iter().map(|arg| arg.as_ptr()).collect();
+        args.push(core::ptr::null());
+        exec(argv[0], &args);
+        println!("watch: cannot run {}", argv[0]);
+        exit(-1);
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+}
+
+/// watch FILE PROGRAM [ARGS...]: run the program, and again each time the
+/// file changes, until it is removed.
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc < 3 {
+        println!("usage: watch FILE PROGRAM [ARGS...]");
+        return -1;
+    }
+    let fd = inotify_init1(OpenFlags::empty());
+    assert!(fd > 0);
+    let fd = fd as usize;
+    if inotify_add_watch(fd, argv[1], IN_MODIFY | IN_DELETE_SELF) < 0 {
+        println!("watch: cannot watch {}", argv[1]);
+        return -1;
+    }
+    run(&argv[2..]);
+    let mut buf = [0u8; 256];
+    loop {
+        let len = read(fd, &mut buf);
+        assert!(len > 0);
+        let mut modified = false;
+        for event in inotify_events(&buf[..len as usize]) {
+            if event.mask & IN_IGNORED != 0 {
+                println!("watch: {} is gone", argv[1]);
+                return 0;
+            }
+            modified |= event.mask & IN_MODIFY != 0;
+        }
+        if modified {
+            println!("watch: {} changed", argv[1]);
+            run(&argv[2..]);
+        }
+    }
+}
@@ -0,0 +1,57 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{meminfo, mmap, munmap, MemInfo};
+use user_lib::{MAP_ANONYMOUS, MAP_PRIVATE, PAGE_SIZE, PROT_READ, PROT_WRITE};
+
+const PAGES: usize = 1024;
+const LEN: usize = PAGES * PAGE_SIZE;
+
+static mut BSS: [u8; 64 * PAGE_SIZE] = [0; 64 * PAGE_SIZE];
+
+fn free() -> usize {
+    let mut info = MemInfo::default();
+    assert_eq!(meminfo(&mut info), 0);
+    info.free
+}
+
+fn page(addr: usize, index: usize) -> *mut u8 {
+    (addr + index * PAGE_SIZE) as *mut u8
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let addr = mmap(
+        0,
+        LEN,
+        PROT_READ | PROT_WRITE,
+        MAP_PRIVATE | MAP_ANONYMOUS,
+        0,
+        0,
+    ) as usize;
+    // reading every page costs no frames but for page tables and bookkeeping
+    let before = free();
+    for i in 0..PAGES {
+        assert_eq!(unsafe { page(addr, i).read_volatile() }, 0);
+    }
+    assert!(before - free() < PAGES * PAGE_SIZE / 4);
+    // the first write gives the page a frame of its own
+    unsafe { page(addr, 1).write_volatile(1) };
+    assert_eq!(unsafe { page(addr, 0).read_volatile() }, 0);
+    assert_eq!(unsafe { page(addr, 1).read_volatile() }, 1);
+    assert_eq!(unsafe { page(addr, 2).read_volatile() }, 0);
+    assert_eq!(munmap(addr, LEN), 0);
+
+    // so does the bss
+    let bss = unsafe { BSS.as_mut_ptr() as usize };
+    assert!((0..64).all(|i| unsafe { page(bss, i).read_volatile() } == 0));
+    unsafe { page(bss, 3).write_volatile(3) };
+    assert!((0..64)
+        .filter(|i| *i != 3)
+        .all(|i| unsafe { page(bss, i).read_volatile() } == 0));
+    println!("zero_page_test passed!");
+    0
+}
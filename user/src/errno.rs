@@ -0,0 +1,51 @@
+//! What syscalls return on failure, negated as the kernel returns them.
+
+/// Not allowed to a process without the capability it needs.
+pub const EPERM: isize = -1;
+/// What getaddrinfo returns for a host of no address.
+pub const ENOENT: isize = -2;
+/// No such process, for a pidfd whose process has exited.
+pub const ESRCH: isize = -3;
+/// What a waiting syscall returns when a handler without SA_RESTART ran.
+pub const EINTR: isize = -4;
+/// A replay which went other than its recording.
+pub const EIO: isize = -5;
+/// No child processes to wait for.
+pub const ECHILD: isize = -10;
+/// What read returns with O_NONBLOCK if there is nothing to read yet.
+pub const EAGAIN: isize = -11;
+/// Memory which could not be allocated.
+pub const ENOMEM: isize = -12;
+/// A buffer outside of the address space.
+pub const EFAULT: isize = -14;
+/// What netbench returns while a run goes on.
+pub const EBUSY: isize = -16;
+/// What mq_open returns for a queue created with O_EXCL which is there.
+pub const EEXIST: isize = -17;
+/// What ifconfig returns for an interface of no such name.
+pub const ENODEV: isize = -19;
+/// What mq_open returns for a name with a / in it, or a queue of no room.
+pub const EINVAL: isize = -22;
+/// What send returns once the connection can take no more.
+pub const EPIPE: isize = -32;
+/// What F_SETLKW returns rather than wait for a lock forever.
+pub const EDEADLK: isize = -35;
+/// What netbench returns in a kernel built without it.
+pub const ENOSYS: isize = -38;
+pub const ENOTSOCK: isize = -88;
+/// What sendto returns for an unconnected datagram socket given no address.
+pub const EDESTADDRREQ: isize = -89;
+pub const EMSGSIZE: isize = -90;
+pub const ENOPROTOOPT: isize = -92;
+pub const EOPNOTSUPP: isize = -95;
+pub const EAFNOSUPPORT: isize = -97;
+pub const EADDRINUSE: isize = -98;
+pub const EISCONN: isize = -106;
+pub const ENOTCONN: isize = -107;
+pub const ETIMEDOUT: isize = -110;
+pub const ECONNREFUSED: isize = -111;
+/// What connect returns with SOCK_NONBLOCK while still connecting.
+pub const EALREADY: isize = -114;
+/// What connect returns with SOCK_NONBLOCK for the connection to be made
+/// meanwhile; POLLOUT tells when it is.
+pub const EINPROGRESS: isize = -115;
@@ -5,14 +5,533 @@ bitflags! {
         const RDONLY = 0;
         const WRONLY = 1 << 0;
         const RDWR = 1 << 1;
+        const EXCL = 1 << 7;
         const CREATE = 1 << 9;
         const TRUNC = 1 << 10;
+        const APPEND = 1 << 11;
+        const NONBLOCK = 1 << 12;
+        const NOFOLLOW = 1 << 17;
+        const CLOEXEC = 1 << 19;
     }
 }
 
+pub const S_IFMT: u32 = 0o170000;
+pub const S_IFSOCK: u32 = 0o140000;
+pub const S_IFLNK: u32 = 0o120000;
+pub const S_IFREG: u32 = 0o100000;
+pub const S_IFDIR: u32 = 0o040000;
+pub const S_IFCHR: u32 = 0o020000;
+pub const S_IFIFO: u32 = 0o010000;
+
+/// File metadata. The times are zero until the kernel has a real-time
+/// clock.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Stat {
+    pub dev: u64,
+    pub ino: u64,
+    /// file type and permissions
+    pub mode: u32,
+    pub nlink: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub rdev: u64,
+    __pad1: u64,
+    pub size: i64,
+    pub blksize: i32,
+    __pad2: i32,
+    /// 512-byte blocks allocated
+    pub blocks: i64,
+    pub atime: i64,
+    pub atime_nsec: u64,
+    pub mtime: i64,
+    pub mtime_nsec: u64,
+    pub ctime: i64,
+    pub ctime_nsec: u64,
+    __unused: [u32; 2],
+}
+
+impl Stat {
+    pub fn is_dir(&self) -> bool {
+        self.mode & S_IFMT == S_IFDIR
+    }
+    pub fn is_file(&self) -> bool {
+        self.mode & S_IFMT == S_IFREG
+    }
+    pub fn is_symlink(&self) -> bool {
+        self.mode & S_IFMT == S_IFLNK
+    }
+    pub fn is_fifo(&self) -> bool {
+        self.mode & S_IFMT == S_IFIFO
+    }
+    pub fn is_socket(&self) -> bool {
+        self.mode & S_IFMT == S_IFSOCK
+    }
+}
+
+/// d_type of a directory entry, which is the file type bits shifted down.
+pub const DT_FIFO: u8 = 1;
+pub const DT_CHR: u8 = 2;
+pub const DT_DIR: u8 = 4;
+pub const DT_REG: u8 = 8;
+pub const DT_LNK: u8 = 10;
+pub const DT_SOCK: u8 = 12;
+
+/// A directory entry in what getdents filled a buffer with.
+pub struct Dirent<'a> {
+    pub ino: u64,
+    /// where the next entry is, for lseek
+    pub off: i64,
+    pub type_: u8,
+    pub name: &'a str,
+}
+
+/// The entries of the records getdents put in `buf`.
+pub fn dirents(buf: &[u8]) -> impl Iterator<Item = Dirent<'_>> {
+    let mut pos = 0;
+    core::iter::from_fn(move || {
+        let record = buf.get(pos..)?;
+        if record.len() < 19 {
+            return None;
+        }
+        let reclen = u16::from_ne_bytes([record[16], record[17]]) as usize;
+        let name = &record[19..reclen];
+        let name_len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+        pos += reclen;
+        let mut ino = [0u8; 8];
+        ino.copy_from_slice(&record[..8]);
+        let mut off = [0u8; 8];
+        off.copy_from_slice(&record[8..16]);
+        Some(Dirent {
+            ino: u64::from_ne_bytes(ino),
+            off: i64::from_ne_bytes(off),
+            type_: record[18],
+            name: core::str::from_utf8(&name[..name_len]).ok()?,
+        })
+    })
+}
+
+pub const SEEK_SET: usize = 0;
+pub const SEEK_CUR: usize = 1;
+pub const SEEK_END: usize = 2;
+
+pub const F_DUPFD: usize = 0;
+pub const F_GETFD: usize = 1;
+pub const F_SETFD: usize = 2;
+pub const F_GETFL: usize = 3;
+pub const F_SETFL: usize = 4;
+pub const F_GETLK: usize = 5;
+pub const F_SETLK: usize = 6;
+pub const F_SETLKW: usize = 7;
+pub const F_DUPFD_CLOEXEC: usize = 1030;
+/// Make a pipe hold the bytes of the argument, rounded up to pages; it
+/// returns how many.
+pub const F_SETPIPE_SZ: usize = 1031;
+pub const F_GETPIPE_SZ: usize = 1032;
+pub const FD_CLOEXEC: usize = 1;
+
+/// splice does not wait for the pipe.
+pub const SPLICE_F_NONBLOCK: usize = 2;
+
+pub const LOCK_SH: usize = 1;
+pub const LOCK_EX: usize = 2;
+pub const LOCK_NB: usize = 4;
+pub const LOCK_UN: usize = 8;
+
+pub const F_RDLCK: i16 = 0;
+pub const F_WRLCK: i16 = 1;
+pub const F_UNLCK: i16 = 2;
+
+/// A record lock of fcntl: the bytes from `l_start`, counted from where
+/// `l_whence` says, on for `l_len` bytes or to the end if 0.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Flock {
+    pub l_type: i16,
+    pub l_whence: i16,
+    pub l_start: i64,
+    pub l_len: i64,
+    /// the process holding the lock, from F_GETLK
+    pub l_pid: i32,
+}
+
+impl Flock {
+    pub fn new(l_type: i16, l_start: i64, l_len: i64) -> Self {
+        Self {
+            l_type,
+            l_whence: SEEK_SET as i16,
+            l_start,
+            l_len,
+            l_pid: 0,
+        }
+    }
+}
+
+pub const IN_MODIFY: u32 = 0x2;
+pub const IN_ATTRIB: u32 = 0x4;
+pub const IN_MOVED_FROM: u32 = 0x40;
+pub const IN_MOVED_TO: u32 = 0x80;
+pub const IN_CREATE: u32 = 0x100;
+pub const IN_DELETE: u32 = 0x200;
+pub const IN_DELETE_SELF: u32 = 0x400;
+pub const IN_ALL_EVENTS: u32 = 0x7c6;
+pub const IN_Q_OVERFLOW: u32 = 0x4000;
+pub const IN_IGNORED: u32 = 0x8000;
+pub const IN_ISDIR: u32 = 0x4000_0000;
+
+/// An event in what read put in a buffer from an inotify.
+pub struct InotifyEvent<'a> {
+    pub wd: i32,
+    pub mask: u32,
+    /// the same for the two events of a rename
+    pub cookie: u32,
+    /// the entry of a watched directory the event is about, if any
+    pub name: &'a str,
+}
+
+/// The events read from an inotify into `buf`.
+pub fn inotify_events(buf: &[u8]) -> impl Iterator<Item = InotifyEvent<'_>> {
+    let mut pos = 0;
+    core::iter::from_fn(move || {
+        let record = buf.get(pos..)?;
+        if record.len() < 16 {
+            return None;
+        }
+        let field =
+            |i: usize| u32::from_ne_bytes([record[i], record[i + 1], record[i + 2], record[i + 3]]);
+        let len = field(12) as usize;
+        let name = &record[16..16 + len];
+        let name_len = name.iter().position(|&b| b == 0).unwrap_or(len);
+        pos += 16 + len;
+        Some(InotifyEvent {
+            wd: field(0) as i32,
+            mask: field(4),
+            cookie: field(8),
+            name: core::str::from_utf8(&name[..name_len]).ok()?,
+        })
+    })
+}
+
+pub const POLLIN: i16 = 0x1;
+pub const POLLOUT: i16 = 0x4;
+pub const POLLERR: i16 = 0x8;
+pub const POLLHUP: i16 = 0x10;
+pub const POLLNVAL: i16 = 0x20;
+
+/// A file for poll to look at, and what it is ready for after.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PollFd {
+    /// skipped if negative
+    pub fd: i32,
+    pub events: i16,
+    pub revents: i16,
+}
+
+impl PollFd {
+    pub fn new(fd: usize, events: i16) -> Self {
+        Self {
+            fd: fd as i32,
+            events,
+            revents: 0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimeSpec {
+    pub tv_sec: i64,
+    pub tv_nsec: i64,
+}
+
+impl TimeSpec {
+    pub fn from_ms(ms: usize) -> Self {
+        Self {
+            tv_sec: (ms / 1000) as i64,
+            tv_nsec: (ms % 1000 * 1_000_000) as i64,
+        }
+    }
+    pub fn as_ms(&self) -> usize {
+        self.tv_sec as usize * 1000 + self.tv_nsec as usize / 1_000_000
+    }
+}
+
+/// The time between expirations of a timer, 0 for once, and to the next,
+/// 0 for none.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ITimerSpec {
+    pub it_interval: TimeSpec,
+    pub it_value: TimeSpec,
+}
+
+impl ITimerSpec {
+    pub fn from_ms(value_ms: usize, interval_ms: usize) -> Self {
+        Self {
+            it_interval: TimeSpec::from_ms(interval_ms),
+            it_value: TimeSpec::from_ms(value_ms),
+        }
+    }
+}
+
+/// Reads of an eventfd take 1 from the count, not all of it.
+pub const EFD_SEMAPHORE: u32 = 1;
+pub const EFD_NONBLOCK: u32 = OpenFlags::NONBLOCK.bits();
+pub const EFD_CLOEXEC: u32 = OpenFlags::CLOEXEC.bits();
+
+pub const SFD_NONBLOCK: u32 = OpenFlags::NONBLOCK.bits();
+pub const SFD_CLOEXEC: u32 = OpenFlags::CLOEXEC.bits();
+
+/// What a read of a signalfd gives for each signal taken.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SignalfdSiginfo {
+    pub signo: u32,
+    pub errno: i32,
+    pub code: i32,
+    pub pid: u32,
+    pub uid: u32,
+    pub fd: i32,
+    pub tid: u32,
+    pub band: u32,
+    pub overrun: u32,
+    pub trapno: u32,
+    pub status: i32,
+    pub int: i32,
+    pub ptr: u64,
+    pub utime: u64,
+    pub stime: u64,
+    pub addr: u64,
+    pub addr_lsb: u16,
+    _pad: [u8; 46],
+}
+
+impl Default for SignalfdSiginfo {
+    fn default() -> Self {
+        unsafe { core::mem::zeroed() }
+    }
+}
+
+pub const CLOCK_REALTIME: usize = 0;
+pub const CLOCK_MONOTONIC: usize = 1;
+pub const TFD_NONBLOCK: u32 = OpenFlags::NONBLOCK.bits();
+pub const TFD_CLOEXEC: u32 = OpenFlags::CLOEXEC.bits();
+/// The time set is when to expire, not how long from now.
+pub const TFD_TIMER_ABSTIME: u32 = 1;
+
+pub const EPOLLIN: u32 = 0x1;
+pub const EPOLLOUT: u32 = 0x4;
+pub const EPOLLERR: u32 = 0x8;
+pub const EPOLLHUP: u32 = 0x10;
+
+pub const EPOLL_CTL_ADD: usize = 1;
+pub const EPOLL_CTL_DEL: usize = 2;
+pub const EPOLL_CTL_MOD: usize = 3;
+
+/// What a file is registered for with an epoll, or is ready for, with
+/// `data` to tell it by.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EpollEvent {
+    pub events: u32,
+    pub data: u64,
+}
+
+/// unlinkat removes a directory instead of a file
+pub const AT_REMOVEDIR: u32 = 0x200;
+/// stat describes a symbolic link itself
+pub const AT_SYMLINK_NOFOLLOW: u32 = 0x100;
+
 pub fn dup(fd: usize) -> isize {
     sys_dup(fd)
 }
+/// dup3 refuses equal fds, where dup2 only checks that `old_fd` is open.
+pub fn dup2(old_fd: usize, new_fd: usize) -> isize {
+    if old_fd == new_fd {
+        return if sys_fcntl(old_fd, F_GETFD, 0) < 0 {
+            -1
+        } else {
+            new_fd as isize
+        };
+    }
+    sys_dup3(old_fd, new_fd, 0)
+}
+pub fn dup3(old_fd: usize, new_fd: usize, flags: OpenFlags) -> isize {
+    sys_dup3(old_fd, new_fd, flags.bits)
+}
+pub fn fcntl(fd: usize, cmd: usize, arg: usize) -> isize {
+    sys_fcntl(fd, cmd, arg)
+}
+/// F_GETLK, F_SETLK or F_SETLKW with `lock`.
+pub fn fcntl_lock(fd: usize, cmd: usize, lock: &mut Flock) -> isize {
+    sys_fcntl(fd, cmd, lock as *mut Flock as usize)
+}
+/// Lock the open file `fd` as a whole, LOCK_SH or LOCK_EX, maybe with
+/// LOCK_NB, or unlock it with LOCK_UN.
+pub fn flock(fd: usize, operation: usize) -> isize {
+    sys_flock(fd, operation)
+}
+/// A new inotify, O_NONBLOCK and O_CLOEXEC being the flags it takes.
+pub fn inotify_init1(flags: OpenFlags) -> isize {
+    sys_inotify_init1(flags.bits())
+}
+/// Watch `path` for the events in `mask` and return the watch descriptor.
+pub fn inotify_add_watch(fd: usize, path: &str, mask: u32) -> isize {
+    sys_inotify_add_watch(fd, path, mask)
+}
+pub fn inotify_rm_watch(fd: usize, wd: i32) -> isize {
+    sys_inotify_rm_watch(fd, wd)
+}
+/// Wait up to `timeout_ms`, for ever if negative, for any of `fds` to be
+/// ready, and return how many are.
+pub fn poll(fds: &mut [PollFd], timeout_ms: isize) -> isize {
+    let timeout = TimeSpec::from_ms(timeout_ms.max(0) as usize);
+    sys_ppoll(fds, (timeout_ms >= 0).then_some(&timeout))
+}
+/// A new epoll, O_CLOEXEC being the flag it takes.
+pub fn epoll_create1(flags: OpenFlags) -> isize {
+    sys_epoll_create1(flags.bits())
+}
+/// EPOLL_CTL_ADD, EPOLL_CTL_MOD or EPOLL_CTL_DEL `fd` with the epoll `epfd`.
+pub fn epoll_ctl(epfd: usize, op: usize, fd: usize, event: &EpollEvent) -> isize {
+    sys_epoll_ctl(epfd, op, fd, event)
+}
+/// Wait up to `timeout_ms`, for ever if negative, for files of `epfd` to
+/// be ready, and return how many of `events` are filled in.
+pub fn epoll_wait(epfd: usize, events: &mut [EpollEvent], timeout_ms: isize) -> isize {
+    sys_epoll_pwait(epfd, events, timeout_ms)
+}
+/// A new eventfd counting from `initval`, with EFD_SEMAPHORE, EFD_NONBLOCK
+/// and EFD_CLOEXEC as flags.
+pub fn eventfd(initval: u32, flags: u32) -> isize {
+    sys_eventfd2(initval, flags)
+}
+/// Add `value` to the count of the eventfd `fd`.
+pub fn eventfd_write(fd: usize, value: u64) -> isize {
+    write(fd, &value.to_ne_bytes())
+}
+/// Take the count of the eventfd `fd`, or 1 of it, waiting while it is 0.
+pub fn eventfd_read(fd: usize) -> Option<u64> {
+    let mut buf = [0u8; 8];
+    (read(fd, &mut buf) == 8).then(|| u64::from_ne_bytes(buf))
+}
+/// A new signalfd taking the signals of `mask`, which ought to be blocked,
+/// or the signalfd `fd` made to take those instead; SFD_NONBLOCK and
+/// SFD_CLOEXEC are the flags.
+pub fn signalfd(fd: Option<usize>, mask: &SignalFlags, flags: u32) -> isize {
+    sys_signalfd4(fd.map_or(-1, |fd| fd as isize), mask, flags)
+}
+/// Read as many signals from the signalfd `fd` as `infos` has room for,
+/// returning how many.
+pub fn signalfd_read(fd: usize, infos: &mut [SignalfdSiginfo]) -> isize {
+    let buf = unsafe {
+        core::slice::from_raw_parts_mut(
+            infos.as_mut_ptr() as *mut u8,
+            infos.len() * core::mem::size_of::<SignalfdSiginfo>(),
+        )
+    };
+    match read(fd, buf) {
+        len if len < 0 => len,
+        len => len / core::mem::size_of::<SignalfdSiginfo>() as isize,
+    }
+}
+/// A new timerfd, with TFD_NONBLOCK and TFD_CLOEXEC as flags.
+pub fn timerfd_create(clockid: usize, flags: u32) -> isize {
+    sys_timerfd_create(clockid, flags)
+}
+/// Arm the timerfd `fd` as `new` says, or disarm it with a zero value,
+/// and tell what it was set to in `old`.
+pub fn timerfd_settime(
+    fd: usize,
+    flags: u32,
+    new: &ITimerSpec,
+    old: Option<&mut ITimerSpec>,
+) -> isize {
+    sys_timerfd_settime(fd, flags, new, old)
+}
+pub fn timerfd_gettime(fd: usize, curr: &mut ITimerSpec) -> isize {
+    sys_timerfd_gettime(fd, curr)
+}
+/// Device-specific control of `fd`, e.g. FBIOGET_VSCREENINFO on /dev/fb0.
+pub fn ioctl(fd: usize, cmd: usize, arg: usize) -> isize {
+    sys_ioctl(fd, cmd, arg)
+}
+
+const TIOCGPGRP: usize = 0x540f;
+const TIOCSPGRP: usize = 0x5410;
+
+/// The foreground process group of the console `fd`, which ^C and ^Z
+/// signal.
+pub fn tcgetpgrp(fd: usize) -> isize {
+    let mut pgid = 0i32;
+    match sys_ioctl(fd, TIOCGPGRP, &mut pgid as *mut _ as usize) {
+        0 => pgid as isize,
+        err => err,
+    }
+}
+pub fn tcsetpgrp(fd: usize, pgid: usize) -> isize {
+    let pgid = pgid as i32;
+    sys_ioctl(fd, TIOCSPGRP, &pgid as *const _ as usize)
+}
+
+const KCOV_INIT_TRACE: usize = 0x8008_6301;
+const KCOV_ENABLE: usize = 0x6364;
+const KCOV_DISABLE: usize = 0x6365;
+const KCOV_TRACE_PC: usize = 0;
+
+/// The coverage of the syscalls of this thread, from /dev/kcov of a kernel
+/// built with KCOV=on: the addresses of the kernel code each one ran.
+pub struct Kcov {
+    fd: usize,
+    area: *mut usize,
+    words: usize,
+}
+
+impl Kcov {
+    /// Open /dev/kcov with room for `words - 1` addresses per syscall and
+    /// enable it for this thread; None if the kernel has no kcov.
+    pub fn open(words: usize) -> Option<Self> {
+        let fd = sys_open("/dev/kcov\0", OpenFlags::RDWR.bits);
+        if fd < 0 {
+            return None;
+        }
+        let fd = fd as usize;
+        let area = match sys_ioctl(fd, KCOV_INIT_TRACE, words) {
+            0 => mmap(0, words * 8, PROT_READ | PROT_WRITE, MAP_SHARED, fd, 0),
+            err => err,
+        };
+        if area < 0 || sys_ioctl(fd, KCOV_ENABLE, KCOV_TRACE_PC) < 0 {
+            sys_close(fd);
+            return None;
+        }
+        Some(Self {
+            fd,
+            area: area as *mut usize,
+            words,
+        })
+    }
+
+    /// Forget what was collected, before a syscall to collect.
+    pub fn reset(&self) {
+        unsafe { self.area.write_volatile(0) };
+    }
+
+    /// The addresses of the code the syscalls since the reset ran.
+    pub fn pcs(&self) -> &[usize] {
+        let count = unsafe { self.area.read_volatile() }.min(self.words - 1);
+        unsafe { core::slice::from_raw_parts(self.area.add(1), count) }
+    }
+}
+
+impl Drop for Kcov {
+    fn drop(&mut self) {
+        sys_ioctl(self.fd, KCOV_DISABLE, 0);
+        munmap(self.area as usize, self.words * 8);
+        sys_close(self.fd);
+    }
+}
+
 pub fn open(path: &str, flags: OpenFlags) -> isize {
     sys_open(path, flags.bits)
 }
@@ -20,7 +539,11 @@ pub fn close(fd: usize) -> isize {
     sys_close(fd)
 }
 pub fn pipe(pipe_fd: &mut [usize]) -> isize {
-    sys_pipe(pipe_fd)
+    sys_pipe(pipe_fd, 0)
+}
+/// A pipe whose ends have FD_CLOEXEC or O_NONBLOCK if `flags` say.
+pub fn pipe2(pipe_fd: &mut [usize], flags: OpenFlags) -> isize {
+    sys_pipe(pipe_fd, flags.bits())
 }
 pub fn read(fd: usize, buf: &mut [u8]) -> isize {
     sys_read(fd, buf)
@@ -28,3 +551,99 @@ pub fn read(fd: usize, buf: &mut [u8]) -> isize {
 pub fn write(fd: usize, buf: &[u8]) -> isize {
     sys_write(fd, buf)
 }
+pub fn mount(source: &str, target: &str, fstype: &str) -> isize {
+    sys_mount(source, target, fstype)
+}
+pub fn umount(target: &str) -> isize {
+    sys_umount(target)
+}
+/// Make the named pipe `path`, which readers and writers open to meet.
+pub fn mkfifo(path: &str, mode: u32) -> isize {
+    sys_mkfifo(path, mode)
+}
+pub fn mkdir(path: &str) -> isize {
+    sys_mkdir(path)
+}
+pub fn rmdir(path: &str) -> isize {
+    sys_unlinkat(path, AT_REMOVEDIR)
+}
+pub fn unlink(path: &str) -> isize {
+    sys_unlinkat(path, 0)
+}
+/// Copy the working directory with a trailing '\0' to `buf` and return its
+/// length with the '\0'.
+pub fn getcwd(buf: &mut [u8]) -> isize {
+    sys_getcwd(buf)
+}
+pub fn chdir(path: &str) -> isize {
+    sys_chdir(path)
+}
+pub fn chmod(path: &str, mode: u32) -> isize {
+    sys_chmod(path, mode)
+}
+pub fn chown(path: &str, uid: u32, gid: u32) -> isize {
+    sys_chown(path, uid, gid)
+}
+pub fn symlink(target: &str, link_path: &str) -> isize {
+    sys_symlink(target, link_path)
+}
+/// Where the symbolic link `path` points, cut to `buf`, without a NUL.
+pub fn readlink(path: &str, buf: &mut [u8]) -> isize {
+    sys_readlink(path, buf)
+}
+pub fn link(old_path: &str, new_path: &str) -> isize {
+    sys_link(old_path, new_path)
+}
+pub fn rename(old_path: &str, new_path: &str) -> isize {
+    sys_rename(old_path, new_path)
+}
+/// Fill `buf` with the next entries of the directory `fd`, to be read with
+/// `dirents`, and return the bytes filled, 0 at the end.
+pub fn getdents(fd: usize, buf: &mut [u8]) -> isize {
+    sys_getdents64(fd, buf)
+}
+pub fn lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    sys_lseek(fd, offset, whence)
+}
+pub fn pread(fd: usize, buf: &mut [u8], offset: usize) -> isize {
+    sys_pread64(fd, buf, offset)
+}
+pub fn pwrite(fd: usize, buf: &[u8], offset: usize) -> isize {
+    sys_pwrite64(fd, buf, offset)
+}
+/// Read into the pieces of `iov` in turn.
+pub fn readv(fd: usize, iov: &[IoVec]) -> isize {
+    sys_readv(fd, iov)
+}
+/// Write the pieces of `iov` in turn.
+pub fn writev(fd: usize, iov: &[IoVec]) -> isize {
+    sys_writev(fd, iov)
+}
+/// Move up to `len` bytes from the pipe `fd_in` to the file `fd_out`, at
+/// `*off_out` if given, which is moved on, else at the file's offset.
+pub fn splice(
+    fd_in: usize,
+    fd_out: usize,
+    off_out: Option<&mut i64>,
+    len: usize,
+    flags: usize,
+) -> isize {
+    let off_out = off_out.map_or(core::ptr::null_mut(), |offset| offset as *mut i64);
+    sys_splice(fd_in, fd_out, off_out, len, flags)
+}
+pub fn stat(path: &str, stat: &mut Stat) -> isize {
+    sys_stat(path, stat, 0)
+}
+/// stat of a symbolic link itself rather than what it points at.
+pub fn lstat(path: &str, stat: &mut Stat) -> isize {
+    sys_stat(path, stat, AT_SYMLINK_NOFOLLOW)
+}
+pub fn fstat(fd: usize, stat: &mut Stat) -> isize {
+    sys_fstat(fd, stat)
+}
+pub fn fsync(fd: usize) -> isize {
+    sys_fsync(fd)
+}
+pub fn fdatasync(fd: usize) -> isize {
+    sys_fdatasync(fd)
+}
@@ -0,0 +1,331 @@
+use super::*;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// Priorities of messages are below it.
+pub const MQ_PRIO_MAX: u32 = 32768;
+
+/// `struct mq_attr`: O_NONBLOCK of the descriptor, what the queue holds at
+/// most and how many messages it does; mq_open only reads `maxmsg` and
+/// `msgsize`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MqAttr {
+    pub flags: i64,
+    pub maxmsg: i64,
+    pub msgsize: i64,
+    pub curmsgs: i64,
+    pad: [i64; 4],
+}
+
+impl MqAttr {
+    pub fn new(maxmsg: usize, msgsize: usize) -> Self {
+        Self {
+            maxmsg: maxmsg as i64,
+            msgsize: msgsize as i64,
+            ..Default::default()
+        }
+    }
+}
+
+/// Open the message queue `name`, like "/name\0"; with CREATE it is made
+/// of `mode` and what `attr` says, or 10 messages of 8192 bytes at most,
+/// if there is none. It returns a descriptor.
+pub fn mq_open(name: &str, flags: OpenFlags, mode: u32, attr: Option<&MqAttr>) -> isize {
+    // the kernel takes the name without its /
+    let name = name.strip_prefix('/').unwrap_or(name);
+    sys_mq_open(name, flags.bits(), mode, attr)
+}
+pub fn mq_unlink(name: &str) -> isize {
+    sys_mq_unlink(name.strip_prefix('/').unwrap_or(name))
+}
+/// Send `msg` of `prio`, waiting for room unless O_NONBLOCK.
+pub fn mq_send(mqdes: usize, msg: &[u8], prio: u32) -> isize {
+    sys_mq_timedsend(mqdes, msg, prio, None)
+}
+/// Like mq_send, giving up with ETIMEDOUT at `abs_timeout`, the time of
+/// get_time.
+pub fn mq_timedsend(mqdes: usize, msg: &[u8], prio: u32, abs_timeout: &TimeSpec) -> isize {
+    sys_mq_timedsend(mqdes, msg, prio, Some(abs_timeout))
+}
+/// Take the first message of the highest priority into `buf`, which has
+/// to hold `msgsize` bytes, and its priority into `prio`; it returns the
+/// length of the message.
+pub fn mq_receive(mqdes: usize, buf: &mut [u8], prio: Option<&mut u32>) -> isize {
+    sys_mq_timedreceive(mqdes, buf, prio, None)
+}
+/// Like mq_receive, giving up with ETIMEDOUT at `abs_timeout`.
+pub fn mq_timedreceive(
+    mqdes: usize,
+    buf: &mut [u8],
+    prio: Option<&mut u32>,
+    abs_timeout: &TimeSpec,
+) -> isize {
+    sys_mq_timedreceive(mqdes, buf, prio, Some(abs_timeout))
+}
+pub fn mq_getattr(mqdes: usize, attr: &mut MqAttr) -> isize {
+    sys_mq_getsetattr(mqdes, None, Some(attr))
+}
+/// Take O_NONBLOCK from `attr.flags`.
+pub fn mq_setattr(mqdes: usize, attr: &MqAttr) -> isize {
+    sys_mq_getsetattr(mqdes, Some(attr), None)
+}
+
+/// Offsets into the header page of a ring, each field on a cache line of
+/// its own; the kernel sets the first two.
+const RING_ENTRIES: usize = 0;
+const RING_ENTRY_SIZE: usize = 8;
+const RING_TAIL: usize = 64;
+const RING_HEAD: usize = 128;
+const RING_PRODUCERS_WAITING: usize = 192;
+const RING_CONSUMERS_WAITING: usize = 256;
+const RING_HEADER: usize = 4096;
+/// A slot is a sequence number, the length of its message and the message.
+const SLOT_HEADER: usize = 16;
+
+/// A ring of messages in memory shared with other processes, which any
+/// number of them send on and receive from without the kernel, which is
+/// only there to wake those waiting. Children share it over fork, others
+/// take its descriptors, with SCM_RIGHTS say, to `Ring::from_fds`.
+#[derive(Clone, Copy)]
+pub struct Ring {
+    base: usize,
+    size: usize,
+    entries: u64,
+    entry_size: usize,
+    /// the consumer end, which waits for messages and rings for room
+    pub consumer: usize,
+    /// the producer end, which waits for room and rings for messages
+    pub producer: usize,
+}
+
+/// Make a ring of `entries` slots, a power of two, of `entry_size` bytes
+/// each, a multiple of 8, mapped into this process; `flags` may have
+/// CLOEXEC for its descriptors.
+pub fn ring_create(entries: usize, entry_size: usize, flags: OpenFlags) -> Option<Ring> {
+    let mut fds = [0usize; 2];
+    let base = sys_ring_create(entries, entry_size, flags.bits(), &mut fds);
+    if base < 0 {
+        return None;
+    }
+    Some(Ring {
+        base: base as usize,
+        size: RING_HEADER + (entries * (SLOT_HEADER + entry_size)).div_ceil(4096) * 4096,
+        entries: entries as u64,
+        entry_size,
+        consumer: fds[0],
+        producer: fds[1],
+    })
+}
+
+impl Ring {
+    /// Map the ring of the descriptors `consumer` and `producer`.
+    pub fn from_fds(consumer: usize, producer: usize) -> Option<Self> {
+        let mut stat = Stat::default();
+        if fstat(consumer, &mut stat) < 0 {
+            return None;
+        }
+        let size = stat.size as usize;
+        let base = mmap(0, size, PROT_READ | PROT_WRITE, MAP_SHARED, consumer, 0);
+        if base < 0 {
+            return None;
+        }
+        let mut ring = Self {
+            base: base as usize,
+            size,
+            entries: 0,
+            entry_size: 0,
+            consumer,
+            producer,
+        };
+        ring.entries = ring.field(RING_ENTRIES).load(Ordering::Relaxed);
+        ring.entry_size = ring.field(RING_ENTRY_SIZE).load(Ordering::Relaxed) as usize;
+        Some(ring)
+    }
+
+    /// Unmap the ring and close its descriptors.
+    pub fn close(self) {
+        munmap(self.base, self.size);
+        close(self.consumer);
+        close(self.producer);
+    }
+
+    pub fn entry_size(&self) -> usize {
+        self.entry_size
+    }
+
+    fn field(&self, offset: usize) -> &AtomicU64 {
+        unsafe { &*((self.base + offset) as *const AtomicU64) }
+    }
+
+    /// The sequence number of the slot of `pos`, and its length and bytes.
+    fn slot(&self, pos: u64) -> (&AtomicU64, *mut u64, *mut u8) {
+        let index = (pos & (self.entries - 1)) as usize;
+        let slot = self.base + RING_HEADER + index * (SLOT_HEADER + self.entry_size);
+        unsafe {
+            (
+                &*(slot as *const AtomicU64),
+                (slot + 8) as *mut u64,
+                (slot + SLOT_HEADER) as *mut u8,
+            )
+        }
+    }
+
+    /// Send `msg`, of `entry_size` bytes at most; false if the ring is full.
+    pub fn try_send(&self, msg: &[u8]) -> bool {
+        assert!(msg.len() <= self.entry_size);
+        let tail = self.field(RING_TAIL);
+        let mut pos = tail.load(Ordering::Relaxed);
+        let (seq, len, data) = loop {
+            let (seq, len, data) = self.slot(pos);
+            // the slot is free for `pos` once its message of the round
+            // before was received
+            match seq.load(Ordering::Acquire) as i64 - pos as i64 {
+                0 => match tail.compare_exchange_weak(
+                    pos,
+                    pos + 1,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => break (seq, len, data),
+                    Err(current) => pos = current,
+                },
+                diff if diff < 0 => return false,
+                _ => pos = tail.load(Ordering::Relaxed),
+            }
+        };
+        unsafe {
+            *len = msg.len() as u64;
+            core::ptr::copy_nonoverlapping(msg.as_ptr(), data, msg.len());
+        }
+        seq.store(pos + 1, Ordering::Release);
+        if self.field(RING_CONSUMERS_WAITING).load(Ordering::SeqCst) > 0 {
+            write(self.producer, &1u64.to_ne_bytes());
+        }
+        true
+    }
+
+    /// Take the first message into `buf`, cutting what does not fit, and
+    /// return its length; None if the ring is empty.
+    pub fn try_receive(&self, buf: &mut [u8]) -> Option<usize> {
+        let head = self.field(RING_HEAD);
+        let mut pos = head.load(Ordering::Relaxed);
+        let (seq, len, data) = loop {
+            let (seq, len, data) = self.slot(pos);
+            // the slot holds the message of `pos` once it was sent
+            match seq.load(Ordering::Acquire) as i64 - (pos + 1) as i64 {
+                0 => match head.compare_exchange_weak(
+                    pos,
+                    pos + 1,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => break (seq, len, data),
+                    Err(current) => pos = current,
+                },
+                diff if diff < 0 => return None,
+                _ => pos = head.load(Ordering::Relaxed),
+            }
+        };
+        let len = unsafe { *len as usize };
+        unsafe {
+            core::ptr::copy_nonoverlapping(data, buf.as_mut_ptr(), len.min(buf.len()));
+        }
+        // free for the message of the next round
+        seq.store(pos + self.entries, Ordering::Release);
+        if self.field(RING_PRODUCERS_WAITING).load(Ordering::SeqCst) > 0 {
+            write(self.consumer, &1u64.to_ne_bytes());
+        }
+        Some(len)
+    }
+
+    /// Send `msg`, waiting for room.
+    pub fn send(&self, msg: &[u8]) {
+        let waiting = self.field(RING_PRODUCERS_WAITING);
+        while !self.try_send(msg) {
+            waiting.fetch_add(1, Ordering::SeqCst);
+            // a consumer which made room before we were counted rang nobody
+            if self.try_send(msg) {
+                waiting.fetch_sub(1, Ordering::SeqCst);
+                return;
+            }
+            let mut count = [0u8; 8];
+            read(self.producer, &mut count);
+            waiting.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Take the first message, waiting for one, as try_receive does.
+    pub fn receive(&self, buf: &mut [u8]) -> usize {
+        let waiting = self.field(RING_CONSUMERS_WAITING);
+        loop {
+            if let Some(len) = self.try_receive(buf) {
+                return len;
+            }
+            waiting.fetch_add(1, Ordering::SeqCst);
+            let received = self.try_receive(buf);
+            if received.is_none() {
+                let mut count = [0u8; 8];
+                read(self.consumer, &mut count);
+            }
+            waiting.fetch_sub(1, Ordering::SeqCst);
+            if let Some(len) = received {
+                return len;
+            }
+        }
+    }
+}
+
+/// The most bytes of a message of ipc_call.
+pub const IPC_MSG_SIZE: usize = 64;
+
+/// A message of ipc_call or its reply: up to IPC_MSG_SIZE bytes and a
+/// descriptor whose file goes along, -1 for none; the one received is a
+/// new descriptor of the receiver.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct IpcMsg {
+    pub len: usize,
+    pub fd: isize,
+    pub data: [u8; IPC_MSG_SIZE],
+}
+
+impl IpcMsg {
+    pub fn new(data: &[u8]) -> Self {
+        let mut msg = Self::default();
+        msg.data[..data.len()].copy_from_slice(data);
+        msg.len = data.len();
+        msg
+    }
+    pub fn with_fd(data: &[u8], fd: usize) -> Self {
+        let mut msg = Self::new(data);
+        msg.fd = fd as isize;
+        msg
+    }
+    pub fn bytes(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+}
+
+impl Default for IpcMsg {
+    fn default() -> Self {
+        Self {
+            len: 0,
+            fd: -1,
+            data: [0; IPC_MSG_SIZE],
+        }
+    }
+}
+
+/// An endpoint for servers to receive calls on; `flags` may have CLOEXEC.
+pub fn endpoint_create(flags: OpenFlags) -> isize {
+    sys_endpoint_create(flags.bits())
+}
+/// Call a server of `ep` with `msg` and wait for its reply.
+pub fn ipc_call(ep: usize, msg: &IpcMsg, reply: &mut IpcMsg) -> isize {
+    sys_ipc_call(ep, msg, reply)
+}
+/// Reply to the call received last on `ep` if `reply` is given, and wait
+/// for the next call into `msg` if that is.
+pub fn ipc_reply_recv(ep: usize, reply: Option<&IpcMsg>, msg: Option<&mut IpcMsg>) -> isize {
+    sys_ipc_reply_recv(ep, reply, msg)
+}
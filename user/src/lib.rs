@@ -6,9 +6,12 @@
 
 #[macro_use]
 pub mod console;
+mod errno;
 mod file;
 mod io;
+mod ipc;
 mod lang_items;
+mod mm;
 mod net;
 mod sync;
 mod syscall;
@@ -20,49 +23,102 @@ extern crate bitflags;
 
 use alloc::vec::Vec;
 use buddy_system_allocator::LockedHeap;
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr::NonNull;
+pub use errno::*;
 pub use file::*;
 pub use io::*;
+pub use ipc::*;
+pub use mm::*;
 pub use net::*;
 pub use sync::*;
 use syscall::*;
 pub use task::*;
 
 const USER_HEAP_SIZE: usize = 32768;
+/// The heap grows by at least this much with sbrk once the arena is full.
+const USER_HEAP_GROW_SIZE: usize = 0x10000;
 
 static mut HEAP_SPACE: [u8; USER_HEAP_SIZE] = [0; USER_HEAP_SIZE];
 
+/// A heap starting with HEAP_SPACE and growing with sbrk when it runs out.
+struct GrowingHeap(LockedHeap);
+
+unsafe impl GlobalAlloc for GrowingHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let mut heap = self.0.lock();
+        if let Ok(ptr) = heap.alloc(layout) {
+            return ptr.as_ptr();
+        }
+        // twice the block size always holds an aligned block of that size
+        let block = layout.size().max(layout.align()).next_power_of_two();
+        let size = (block * 2).max(USER_HEAP_GROW_SIZE);
+        let start = sbrk(size as isize);
+        if start < 0 {
+            return core::ptr::null_mut();
+        }
+        heap.add_to_heap(start as usize, start as usize + size);
+        heap.alloc(layout)
+            .map_or(core::ptr::null_mut(), |ptr| ptr.as_ptr())
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.0.lock().dealloc(NonNull::new_unchecked(ptr), layout)
+    }
+}
+
 #[global_allocator]
-static HEAP: LockedHeap = LockedHeap::empty();
+static HEAP: GrowingHeap = GrowingHeap(LockedHeap::empty());
 
 #[alloc_error_handler]
 pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
     panic!("Heap allocation error, layout = {:?}", layout);
 }
 
+static mut ENVP: usize = 0;
+
+/// Read the '\0'-terminated string pointed to by the `i`th entry of `vector`.
+fn vector_str(vector: usize, i: usize) -> Option<&'static str> {
+    let str_start =
+        unsafe { ((vector + i * core::mem::size_of::<usize>()) as *const usize).read_volatile() };
+    if str_start == 0 {
+        return None;
+    }
+    let len = (0usize..)
+        .find(|i| unsafe { ((str_start + *i) as *const u8).read_volatile() == 0 })
+        .unwrap();
+    Some(
+        core::str::from_utf8(unsafe { core::slice::from_raw_parts(str_start as *const u8, len) })
+            .unwrap(),
+    )
+}
+
 #[no_mangle]
 #[link_section = ".text.entry"]
-pub extern "C" fn _start(argc: usize, argv: usize) -> ! {
+pub extern "C" fn _start(argc: usize, argv: usize, envp: usize) -> ! {
     unsafe {
-        HEAP.lock()
+        HEAP.0
+            .lock()
             .init(HEAP_SPACE.as_ptr() as usize, USER_HEAP_SIZE);
+        ENVP = envp;
     }
     let mut v: Vec<&'static str> = Vec::new();
     for i in 0..argc {
-        let str_start =
-            unsafe { ((argv + i * core::mem::size_of::<usize>()) as *const usize).read_volatile() };
-        let len = (0usize..)
-            .find(|i| unsafe { ((str_start + *i) as *const u8).read_volatile() == 0 })
-            .unwrap();
-        v.push(
-            core::str::from_utf8(unsafe {
-                core::slice::from_raw_parts(str_start as *const u8, len)
-            })
-            .unwrap(),
-        );
+        v.push(vector_str(argv, i).unwrap());
     }
     exit(main(argc, v.as_slice()));
 }
 
+/// Look up `key` in the environment passed by exec.
+pub fn getenv(key: &str) -> Option<&'static str> {
+    let envp = unsafe { ENVP };
+    if envp == 0 {
+        return None;
+    }
+    (0..)
+        .map_while(|i| vector_str(envp, i))
+        .find_map(|env| env.strip_prefix(key)?.strip_prefix('='))
+}
+
 #[linkage = "weak"]
 #[no_mangle]
 fn main(_argc: usize, _argv: &[&str]) -> i32 {
@@ -0,0 +1,117 @@
+use super::*;
+
+pub const PAGE_SIZE: usize = 4096;
+
+pub const PROT_NONE: usize = 0;
+pub const PROT_READ: usize = 1;
+pub const PROT_WRITE: usize = 2;
+pub const PROT_EXEC: usize = 4;
+
+pub const MAP_SHARED: usize = 0x1;
+pub const MAP_PRIVATE: usize = 0x2;
+pub const MAP_FIXED: usize = 0x10;
+pub const MAP_ANONYMOUS: usize = 0x20;
+
+pub const IPC_PRIVATE: usize = 0;
+pub const IPC_CREAT: usize = 0o1000;
+pub const IPC_EXCL: usize = 0o2000;
+pub const IPC_RMID: usize = 0;
+pub const SHM_RDONLY: usize = 0o10000;
+
+pub const MCL_CURRENT: usize = 1;
+pub const MCL_FUTURE: usize = 2;
+
+pub const MADV_NORMAL: usize = 0;
+pub const MADV_RANDOM: usize = 1;
+pub const MADV_SEQUENTIAL: usize = 2;
+pub const MADV_WILLNEED: usize = 3;
+pub const MADV_DONTNEED: usize = 4;
+
+/// Usage of the whole memory, in bytes.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MemInfo {
+    pub total: usize,
+    pub free: usize,
+    pub heap_total: usize,
+    pub heap_used: usize,
+    pub page_cache: usize,
+    pub swap_total: usize,
+    pub swap_used: usize,
+}
+
+/// Usage of an address space, in bytes.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct VmStat {
+    pub vsz: usize,
+    pub rss: usize,
+    pub swap: usize,
+}
+
+/// Return the new program break, or the current one on failure or if
+/// `addr` is 0.
+pub fn brk(addr: usize) -> isize {
+    sys_brk(addr)
+}
+/// Move the program break by `increment` bytes and return the old one, or -1.
+pub fn sbrk(increment: isize) -> isize {
+    let old = sys_brk(0);
+    let new = old + increment;
+    if new < 0 || sys_brk(new as usize) != new {
+        return -1;
+    }
+    old
+}
+/// Return the start address of the mapping, or -1. `fd` and `offset` are
+/// ignored for MAP_ANONYMOUS.
+pub fn mmap(addr: usize, len: usize, prot: usize, flags: usize, fd: usize, offset: usize) -> isize {
+    sys_mmap(addr, len, prot, flags, fd, offset)
+}
+pub fn munmap(addr: usize, len: usize) -> isize {
+    sys_munmap(addr, len)
+}
+pub fn mprotect(addr: usize, len: usize, prot: usize) -> isize {
+    sys_mprotect(addr, len, prot)
+}
+/// Keep the pages in `[addr, addr + len)` in memory, within RLIMIT_MEMLOCK.
+pub fn mlock(addr: usize, len: usize) -> isize {
+    sys_mlock(addr, len)
+}
+pub fn munlock(addr: usize, len: usize) -> isize {
+    sys_munlock(addr, len)
+}
+/// Only MCL_CURRENT is supported.
+pub fn mlockall(flags: usize) -> isize {
+    sys_mlockall(flags)
+}
+pub fn munlockall() -> isize {
+    sys_munlockall()
+}
+/// MADV_DONTNEED frees the pages at once, to read as zeros or as the file
+/// mapped again, and MADV_WILLNEED reads them in ahead.
+pub fn madvise(addr: usize, len: usize, advice: usize) -> isize {
+    sys_madvise(addr, len, advice)
+}
+pub fn meminfo(info: &mut MemInfo) -> isize {
+    sys_meminfo(info as *mut _)
+}
+/// Usage of the address space of `pid`, or of this process if `pid` is 0.
+pub fn vmstat(pid: usize, stat: &mut VmStat) -> isize {
+    sys_vmstat(pid, stat as *mut _)
+}
+/// Return the id of the shared memory segment of `key`, or -1.
+pub fn shmget(key: usize, size: usize, shmflg: usize) -> isize {
+    sys_shmget(key, size, shmflg)
+}
+/// Only IPC_RMID is supported.
+pub fn shmctl(shmid: usize, cmd: usize) -> isize {
+    sys_shmctl(shmid, cmd, 0)
+}
+/// Return the address the segment is attached at, or -1.
+pub fn shmat(shmid: usize, shmaddr: usize, shmflg: usize) -> isize {
+    sys_shmat(shmid, shmaddr, shmflg)
+}
+pub fn shmdt(shmaddr: usize) -> isize {
+    sys_shmdt(shmaddr)
+}
@@ -1,13 +1,410 @@
 use super::*;
+use alloc::vec;
+use alloc::vec::Vec;
 
-pub fn connect(ip: u32, sport: u16, dport: u16) -> isize {
-    sys_connect(ip, sport, dport)
+/// Sockets of the processes of this machine, bound to paths.
+pub const AF_UNIX: usize = 1;
+pub const AF_INET: usize = 2;
+pub const SOCK_STREAM: usize = 1;
+pub const SOCK_DGRAM: usize = 2;
+/// ICMP messages as they are, for root only.
+pub const SOCK_RAW: usize = 3;
+pub const SOCK_NONBLOCK: usize = OpenFlags::NONBLOCK.bits() as usize;
+pub const SOCK_CLOEXEC: usize = OpenFlags::CLOEXEC.bits() as usize;
+
+pub const IPPROTO_ICMP: usize = 1;
+
+pub const SHUT_RD: usize = 0;
+pub const SHUT_WR: usize = 1;
+pub const SHUT_RDWR: usize = 2;
+
+pub const SOL_SOCKET: usize = 1;
+/// How many bytes a connection lets be in flight unacknowledged.
+pub const SO_SNDBUF: usize = 7;
+/// How many bytes a socket holds of what arrived; more is dropped.
+pub const SO_RCVBUF: usize = 8;
+/// A control message of SOL_SOCKET passing descriptors on an AF_UNIX
+/// socket.
+pub const SCM_RIGHTS: i32 = 1;
+
+/// What recvmsg tells when descriptors did not fit in the control; those
+/// are closed.
+pub const MSG_CTRUNC: i32 = 0x8;
+/// recvmsg gives the descriptors passed O_CLOEXEC.
+pub const MSG_CMSG_CLOEXEC: usize = 0x4000_0000;
+
+/// Runs of netbench, the kernel thread measuring the throughput of the
+/// stack in a kernel built with it.
+pub const NETBENCH_STOP: usize = 0;
+pub const NETBENCH_TCP_SINK: usize = 1;
+pub const NETBENCH_TCP_SOURCE: usize = 2;
+pub const NETBENCH_UDP_SINK: usize = 3;
+pub const NETBENCH_UDP_SOURCE: usize = 4;
+
+/// `struct sockaddr_in`, with the port and the address in network order.
+#[repr(C)]
+#[derive(Copy, Clone, Default, Debug)]
+pub struct SockAddrIn {
+    pub family: u16,
+    pub port: u16,
+    pub addr: u32,
+    pub zero: [u8; 8],
+}
+
+impl SockAddrIn {
+    pub fn new(ip: [u8; 4], port: u16) -> Self {
+        Self {
+            family: AF_INET as u16,
+            port: port.to_be(),
+            addr: u32::from_be_bytes(ip).to_be(),
+            zero: [0; 8],
+        }
+    }
+    pub fn ip(&self) -> [u8; 4] {
+        u32::from_be(self.addr).to_be_bytes()
+    }
+    pub fn port(&self) -> u16 {
+        u16::from_be(self.port)
+    }
+}
+
+/// `struct sockaddr_un`, a path ending with a `\0`.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct SockAddrUn {
+    pub family: u16,
+    pub path: [u8; 108],
+}
+
+impl SockAddrUn {
+    /// The address of `path`, cut to 107 bytes.
+    pub fn new(path: &str) -> Self {
+        let mut addr = Self {
+            family: AF_UNIX as u16,
+            path: [0; 108],
+        };
+        let len = path.len().min(107);
+        addr.path[..len].copy_from_slice(&path.as_bytes()[..len]);
+        addr
+    }
+    /// The path, empty for a socket bound nowhere.
+    pub fn path(&self) -> &str {
+        let end = self.path.iter().position(|ch| *ch == 0).unwrap_or(108);
+        core::str::from_utf8(&self.path[..end]).unwrap_or("")
+    }
+}
+
+/// A piece of the data of readv and writev, or of sendmsg and recvmsg.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct IoVec {
+    pub base: *mut u8,
+    pub len: usize,
+}
+
+/// `struct msghdr`: the address of the peer, the pieces of the data and the
+/// control messages, each a `struct cmsghdr` of `len`, `level` and `type`
+/// followed by its data and starting at a multiple of 8.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct MsgHdr {
+    pub name: *mut u8,
+    pub namelen: u32,
+    pub iov: *const IoVec,
+    pub iovlen: usize,
+    pub control: *mut u8,
+    pub controllen: usize,
+    pub flags: i32,
+}
+
+/// The length of `struct cmsghdr`.
+const CMSG_HDR_LEN: usize = 16;
+
+/// The Internet checksum of `data`, which is 0 if `data` has its own.
+pub fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = 0;
+    for chunk in data.chunks(2) {
+        let word = match chunk {
+            [high, low] => u16::from_be_bytes([*high, *low]),
+            [high] => (*high as u16) << 8,
+            _ => unreachable!(),
+        };
+        sum += word as u32;
+    }
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// A new socket of AF_INET, SOCK_STREAM, SOCK_DGRAM or SOCK_RAW with
+/// SOCK_NONBLOCK and SOCK_CLOEXEC or'ed in; a raw socket takes each ICMP
+/// packet to us, IPv4 header and all.
+pub fn socket(domain: usize, kind: usize, protocol: usize) -> isize {
+    sys_socket(domain, kind, protocol)
+}
+
+/// Two AF_UNIX sockets of `kind` connected to each other, put in `sv`.
+pub fn socketpair(domain: usize, kind: usize, protocol: usize, sv: &mut [usize]) -> isize {
+    sys_socketpair(domain, kind, protocol, sv)
+}
+
+/// Give the socket `fd` the port of `addr`, a free one for 0.
+pub fn bind(fd: usize, addr: &SockAddrIn) -> isize {
+    sys_bind(fd, addr)
+}
+
+/// Bind the AF_UNIX socket `fd` to the path of `addr`, which becomes a
+/// socket file; EADDRINUSE if something is there.
+pub fn bind_un(fd: usize, addr: &SockAddrUn) -> isize {
+    sys_bind(fd, addr)
+}
+
+pub fn listen(fd: usize, backlog: usize) -> isize {
+    sys_listen(fd, backlog)
+}
+
+/// A connection of the listening socket `fd`, its peer told at `addr`.
+pub fn accept(fd: usize, addr: Option<&mut SockAddrIn>) -> isize {
+    let mut addrlen = core::mem::size_of::<SockAddrIn>() as u32;
+    sys_accept(fd, addr, &mut addrlen)
+}
+
+/// accept, with SOCK_NONBLOCK and SOCK_CLOEXEC for the connection.
+pub fn accept4(fd: usize, addr: Option<&mut SockAddrIn>, flags: usize) -> isize {
+    let mut addrlen = core::mem::size_of::<SockAddrIn>() as u32;
+    sys_accept4(fd, addr, &mut addrlen, flags)
+}
+
+/// Have the datagram socket `fd` send to `addr` and take only from it, or
+/// connect the stream socket `fd` to `addr`.
+pub fn connect(fd: usize, addr: &SockAddrIn) -> isize {
+    sys_connect(fd, addr)
+}
+
+/// connect, for the AF_UNIX socket `fd` and the path of `addr`.
+pub fn connect_un(fd: usize, addr: &SockAddrUn) -> isize {
+    sys_connect(fd, addr)
+}
+
+/// accept, for the AF_UNIX socket `fd`, its peer told at `addr`.
+pub fn accept_un(fd: usize, addr: Option<&mut SockAddrUn>) -> isize {
+    let mut addrlen = core::mem::size_of::<SockAddrUn>() as u32;
+    sys_accept(fd, addr, &mut addrlen)
+}
+
+pub fn getsockname_un(fd: usize, addr: &mut SockAddrUn) -> isize {
+    let mut addrlen = core::mem::size_of::<SockAddrUn>() as u32;
+    sys_getsockname(fd, addr, &mut addrlen)
+}
+
+pub fn getpeername_un(fd: usize, addr: &mut SockAddrUn) -> isize {
+    let mut addrlen = core::mem::size_of::<SockAddrUn>() as u32;
+    sys_getpeername(fd, addr, &mut addrlen)
+}
+
+pub fn getsockname(fd: usize, addr: &mut SockAddrIn) -> isize {
+    let mut addrlen = core::mem::size_of::<SockAddrIn>() as u32;
+    sys_getsockname(fd, addr, &mut addrlen)
+}
+
+pub fn getpeername(fd: usize, addr: &mut SockAddrIn) -> isize {
+    let mut addrlen = core::mem::size_of::<SockAddrIn>() as u32;
+    sys_getpeername(fd, addr, &mut addrlen)
+}
+
+/// Send `buf`, to `addr` if given, else to the peer.
+pub fn sendto(fd: usize, buf: &[u8], addr: Option<&SockAddrIn>) -> isize {
+    sys_sendto(fd, buf, 0, addr)
+}
+
+/// Take what arrived into `buf`, telling the sender at `addr` if given.
+pub fn recvfrom(fd: usize, buf: &mut [u8], addr: Option<&mut SockAddrIn>) -> isize {
+    let mut addrlen = core::mem::size_of::<SockAddrIn>() as u32;
+    sys_recvfrom(fd, buf, 0, addr, &mut addrlen)
+}
+
+/// sendto, for the AF_UNIX socket `fd`.
+pub fn sendto_un(fd: usize, buf: &[u8], addr: Option<&SockAddrUn>) -> isize {
+    sys_sendto(fd, buf, 0, addr)
+}
+
+/// recvfrom, for the AF_UNIX socket `fd`.
+pub fn recvfrom_un(fd: usize, buf: &mut [u8], addr: Option<&mut SockAddrUn>) -> isize {
+    let mut addrlen = core::mem::size_of::<SockAddrUn>() as u32;
+    sys_recvfrom(fd, buf, 0, addr, &mut addrlen)
+}
+
+pub fn sendmsg(fd: usize, msg: &MsgHdr, flags: usize) -> isize {
+    sys_sendmsg(fd, msg, flags)
+}
+
+pub fn recvmsg(fd: usize, msg: &mut MsgHdr, flags: usize) -> isize {
+    sys_recvmsg(fd, msg, flags)
+}
+
+/// Send `buf` on the AF_UNIX socket `fd` along with the descriptors `fds`,
+/// for the peer to have the same open files.
+pub fn send_fds(fd: usize, buf: &[u8], fds: &[usize]) -> isize {
+    let mut control = Vec::new();
+    if !fds.is_empty() {
+        control.extend_from_slice(&(CMSG_HDR_LEN + fds.len() * 4).to_ne_bytes());
+        control.extend_from_slice(&(SOL_SOCKET as i32).to_ne_bytes());
+        control.extend_from_slice(&SCM_RIGHTS.to_ne_bytes());
+        for fd in fds {
+            control.extend_from_slice(&(*fd as i32).to_ne_bytes());
+        }
+    }
+    let iov = IoVec {
+        base: buf.as_ptr() as *mut u8,
+        len: buf.len(),
+    };
+    let msg = MsgHdr {
+        name: core::ptr::null_mut(),
+        namelen: 0,
+        iov: &iov,
+        iovlen: 1,
+        control: control.as_mut_ptr(),
+        controllen: control.len(),
+        flags: 0,
+    };
+    sys_sendmsg(fd, &msg, 0)
+}
+
+/// Take what arrived on the AF_UNIX socket `fd` into `buf`, and the
+/// descriptors passed along into `fds`: how many bytes, or the errno, and
+/// how many descriptors.
+pub fn recv_fds(fd: usize, buf: &mut [u8], fds: &mut [usize]) -> (isize, usize) {
+    let mut control = vec![0u8; CMSG_HDR_LEN + fds.len() * 4];
+    let iov = IoVec {
+        base: buf.as_mut_ptr(),
+        len: buf.len(),
+    };
+    let mut msg = MsgHdr {
+        name: core::ptr::null_mut(),
+        namelen: 0,
+        iov: &iov,
+        iovlen: 1,
+        control: control.as_mut_ptr(),
+        controllen: control.len(),
+        flags: 0,
+    };
+    let len = sys_recvmsg(fd, &mut msg, 0);
+    if len < 0 || msg.controllen < CMSG_HDR_LEN {
+        return (len, 0);
+    }
+    let count = (msg.controllen - CMSG_HDR_LEN) / 4;
+    for (i, fd) in fds.iter_mut().take(count).enumerate() {
+        let at = CMSG_HDR_LEN + i * 4;
+        let bytes = [
+            control[at],
+            control[at + 1],
+            control[at + 2],
+            control[at + 3],
+        ];
+        *fd = i32::from_ne_bytes(bytes) as usize;
+    }
+    (len, count)
+}
+
+/// Set the option `optname` of `level` to `value`; the buffer sizes are
+/// taken as they are, within the bounds of the kernel.
+pub fn setsockopt(fd: usize, level: usize, optname: usize, value: i32) -> isize {
+    sys_setsockopt(fd, level, optname, &value)
+}
+
+pub fn getsockopt(fd: usize, level: usize, optname: usize, value: &mut i32) -> isize {
+    let mut len = core::mem::size_of::<i32>() as u32;
+    sys_getsockopt(fd, level, optname, value, &mut len)
+}
+
+pub fn shutdown(fd: usize, how: usize) -> isize {
+    sys_shutdown(fd, how)
+}
+
+/// Have netbench take what comes to the port of `addr` for a sink, or
+/// send to `addr` for a source, for at most `secs` seconds; it tells how
+/// it went in /proc/netbench.
+pub fn netbench(op: usize, addr: &SockAddrIn, secs: usize) -> isize {
+    sys_netbench(op, addr, secs)
+}
+
+/// Put at most `addrs.len()` addresses of the host `name` in `addrs`, and
+/// return how many it has: ENOENT for none, ETIMEDOUT if the nameserver
+/// does not answer. A name in dots is its own address.
+pub fn getaddrinfo(name: &str, addrs: &mut [[u8; 4]]) -> isize {
+    sys_getaddrinfo(name, addrs)
+}
+
+/// The address `text` names in dots, like 10.0.2.2.
+pub fn parse_ip(text: &str) -> Option<[u8; 4]> {
+    let mut ip = [0u8; 4];
+    let mut parts = text.split('.');
+    for byte in ip.iter_mut() {
+        *byte = parts.next()?.parse().ok()?;
+    }
+    parts.next().is_none().then(|| ip)
+}
+
+const IFCONFIG_GET: usize = 0;
+const IFCONFIG_SET: usize = 1;
+const IFCONFIG_DHCP: usize = 2;
+
+pub const DHCP_OFF: u8 = 0;
+/// DHCP asks for an address, the interface keeping its own meanwhile.
+pub const DHCP_ASKING: u8 = 1;
+pub const DHCP_BOUND: u8 = 2;
+
+/// The setup of an interface, with the addresses in network order; the
+/// nameserver is that of eth0, and kept when setting it to 0.
+#[repr(C)]
+#[derive(Copy, Clone, Default, Debug)]
+pub struct IfReq {
+    pub addr: u32,
+    pub netmask: u32,
+    pub gateway: u32,
+    pub nameserver: u32,
+    pub mac: [u8; 6],
+    /// DHCP_OFF, DHCP_ASKING or DHCP_BOUND.
+    pub dhcp: u8,
+    pub pad: u8,
+}
+
+impl IfReq {
+    pub fn new(ip: [u8; 4], netmask: [u8; 4], gateway: [u8; 4]) -> Self {
+        Self {
+            addr: u32::from_be_bytes(ip).to_be(),
+            netmask: u32::from_be_bytes(netmask).to_be(),
+            gateway: u32::from_be_bytes(gateway).to_be(),
+            ..Self::default()
+        }
+    }
+    pub fn ip(&self) -> [u8; 4] {
+        u32::from_be(self.addr).to_be_bytes()
+    }
+    pub fn netmask(&self) -> [u8; 4] {
+        u32::from_be(self.netmask).to_be_bytes()
+    }
+    pub fn gateway(&self) -> [u8; 4] {
+        u32::from_be(self.gateway).to_be_bytes()
+    }
+    pub fn nameserver(&self) -> [u8; 4] {
+        u32::from_be(self.nameserver).to_be_bytes()
+    }
+}
+
+/// The setup of the interface `name`, lo or eth0.
+pub fn ifconfig(name: &str, req: &mut IfReq) -> isize {
+    sys_ifconfig(name, req as *mut _, IFCONFIG_GET)
 }
 
-pub fn listen(sport: u16) -> isize {
-    sys_listen(sport)
+/// Give the interface `name` the address, netmask and gateway of `req`,
+/// stopping DHCP; for root only.
+pub fn ifconfig_set(name: &str, req: &IfReq) -> isize {
+    sys_ifconfig(name, req as *const _ as *mut _, IFCONFIG_SET)
 }
 
-pub fn accept(socket_fd: usize) -> isize {
-    sys_accept(socket_fd)
+/// Have DHCP set up the interface `name`; for root only.
+pub fn ifconfig_dhcp(name: &str) -> isize {
+    sys_ifconfig(name, core::ptr::null_mut(), IFCONFIG_DHCP)
 }
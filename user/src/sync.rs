@@ -1,4 +1,5 @@
 use super::*;
+use core::sync::atomic::{AtomicU32, Ordering};
 
 pub fn mutex_create() -> isize {
     sys_mutex_create(false)
@@ -30,3 +31,114 @@ pub fn condvar_signal(condvar_id: usize) {
 pub fn condvar_wait(condvar_id: usize, mutex_id: usize) {
     sys_condvar_wait(condvar_id, mutex_id);
 }
+
+/// The mutex of `name`, ending in `\0`, which every process opening the
+/// name shares; one is made, blocking or not, if there is none.
+pub fn mutex_open(name: &str, blocking: bool) -> isize {
+    sys_mutex_open(name, blocking)
+}
+/// The condition variable of `name`, ending in `\0`, shared like those of
+/// mutex_open.
+pub fn condvar_open(name: &str) -> isize {
+    sys_condvar_open(name)
+}
+/// Take `name` away from its mutex and condition variable; those who
+/// opened them keep them.
+pub fn sync_unlink(name: &str) -> isize {
+    sys_sync_unlink(name)
+}
+
+pub const FUTEX_WAIT: usize = 0;
+pub const FUTEX_WAKE: usize = 1;
+/// The futex is of this process alone, even in shared memory.
+pub const FUTEX_PRIVATE_FLAG: usize = 128;
+
+/// Wait while `word` is `val`, for up to `timeout_ms` if given: 0 once
+/// woken, EAGAIN if `word` is not `val`, ETIMEDOUT or EINTR.
+pub fn futex_wait(word: &AtomicU32, val: u32, timeout_ms: Option<usize>) -> isize {
+    let timeout = timeout_ms.map(TimeSpec::from_ms);
+    sys_futex(word, FUTEX_WAIT, val, timeout.as_ref())
+}
+/// Wake up to `count` of those waiting on `word`; how many were.
+pub fn futex_wake(word: &AtomicU32, count: u32) -> isize {
+    sys_futex(word, FUTEX_WAKE, count, None)
+}
+
+/// A mutex for processes to share by placing it in memory they share,
+/// which enters the kernel only when contended.
+#[repr(C)]
+pub struct SharedMutex {
+    /// 0 unlocked, 1 locked, 2 locked with someone waiting
+    state: AtomicU32,
+}
+
+impl SharedMutex {
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU32::new(0),
+        }
+    }
+    pub fn try_lock(&self) -> bool {
+        self.state
+            .compare_exchange(0, 1, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+    pub fn lock(&self) {
+        if self.try_lock() {
+            return;
+        }
+        // whoever unlocks it now wakes someone
+        while self.state.swap(2, Ordering::Acquire) != 0 {
+            futex_wait(&self.state, 2, None);
+        }
+    }
+    pub fn unlock(&self) {
+        if self.state.swap(0, Ordering::Release) == 2 {
+            futex_wake(&self.state, 1);
+        }
+    }
+}
+
+impl Default for SharedMutex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A condition variable for processes to share with a `SharedMutex`.
+#[repr(C)]
+pub struct SharedCondvar {
+    /// bumped by every signal, for a waiter to tell one came after it
+    /// unlocked the mutex
+    seq: AtomicU32,
+}
+
+impl SharedCondvar {
+    pub const fn new() -> Self {
+        Self {
+            seq: AtomicU32::new(0),
+        }
+    }
+    /// Unlock `mutex`, wait for a signal and lock it again; it may also
+    /// return without one, so the condition is for the caller to check.
+    pub fn wait(&self, mutex: &SharedMutex) {
+        let seq = self.seq.load(Ordering::Relaxed);
+        mutex.unlock();
+        futex_wait(&self.seq, seq, None);
+        mutex.lock();
+    }
+    pub fn signal(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        futex_wake(&self.seq, 1);
+    }
+    pub fn broadcast(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        futex_wake(&self.seq, i32::MAX as u32);
+    }
+}
+
+impl Default for SharedCondvar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
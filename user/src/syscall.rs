@@ -1,33 +1,162 @@
-const SYSCALL_DUP: usize = 24;
-const SYSCALL_CONNECT: usize = 29;
-const SYSCALL_LISTEN: usize = 30;
-const SYSCALL_ACCEPT: usize = 31;
+use crate::{
+    EpollEvent, ITimerSpec, ITimerVal, IfReq, IoVec, IpcMsg, MemInfo, MqAttr, MsgHdr, ObjStat,
+    PerfCounts, PollFd, RLimit, SigAction, SigEvent, SigInfo, SignalFlags, SockAddrIn, Stat,
+    TimeSpec, VmStat,
+};
+use core::mem::size_of;
+use core::sync::atomic::AtomicU32;
+
+const SYSCALL_GETCWD: usize = 17;
+const SYSCALL_EVENTFD2: usize = 19;
+const SYSCALL_EPOLL_CREATE1: usize = 20;
+const SYSCALL_EPOLL_CTL: usize = 21;
+const SYSCALL_EPOLL_PWAIT: usize = 22;
+const SYSCALL_DUP: usize = 23;
+const SYSCALL_DUP3: usize = 24;
+const SYSCALL_FCNTL: usize = 25;
+const SYSCALL_INOTIFY_INIT1: usize = 26;
+const SYSCALL_INOTIFY_ADD_WATCH: usize = 27;
+const SYSCALL_INOTIFY_RM_WATCH: usize = 28;
+const SYSCALL_IOCTL: usize = 29;
+const SYSCALL_FLOCK: usize = 32;
+const SYSCALL_MKFIFO: usize = 33;
+const SYSCALL_MKDIR: usize = 34;
+const SYSCALL_UNLINKAT: usize = 35;
+const SYSCALL_SYMLINK: usize = 36;
+const SYSCALL_LINK: usize = 37;
+const SYSCALL_RENAME: usize = 38;
+const SYSCALL_UMOUNT2: usize = 39;
+const SYSCALL_MOUNT: usize = 40;
+const SYSCALL_CHDIR: usize = 49;
+const SYSCALL_CHMOD: usize = 53;
+const SYSCALL_CHOWN: usize = 54;
 const SYSCALL_OPEN: usize = 56;
 const SYSCALL_CLOSE: usize = 57;
 const SYSCALL_PIPE: usize = 59;
+const SYSCALL_GETDENTS64: usize = 61;
+const SYSCALL_LSEEK: usize = 62;
 const SYSCALL_READ: usize = 63;
 const SYSCALL_WRITE: usize = 64;
+const SYSCALL_READV: usize = 65;
+const SYSCALL_WRITEV: usize = 66;
+const SYSCALL_PREAD64: usize = 67;
+const SYSCALL_PWRITE64: usize = 68;
+const SYSCALL_PPOLL: usize = 73;
+const SYSCALL_SIGNALFD4: usize = 74;
+const SYSCALL_SPLICE: usize = 76;
+const SYSCALL_READLINK: usize = 78;
+const SYSCALL_STAT: usize = 79;
+const SYSCALL_FSTAT: usize = 80;
+const SYSCALL_FSYNC: usize = 82;
+const SYSCALL_FDATASYNC: usize = 83;
+const SYSCALL_TIMERFD_CREATE: usize = 85;
+const SYSCALL_TIMERFD_SETTIME: usize = 86;
+const SYSCALL_TIMERFD_GETTIME: usize = 87;
 const SYSCALL_EXIT: usize = 93;
-const SYSCALL_SLEEP: usize = 101;
+const SYSCALL_WAITID: usize = 95;
+const SYSCALL_FUTEX: usize = 98;
+const SYSCALL_NANOSLEEP: usize = 101;
+const SYSCALL_GETITIMER: usize = 102;
+const SYSCALL_SETITIMER: usize = 103;
+const SYSCALL_TIMER_CREATE: usize = 107;
+const SYSCALL_TIMER_GETTIME: usize = 108;
+const SYSCALL_TIMER_GETOVERRUN: usize = 109;
+const SYSCALL_TIMER_SETTIME: usize = 110;
+const SYSCALL_TIMER_DELETE: usize = 111;
+const SYSCALL_CLOCK_GETTIME: usize = 113;
+const SYSCALL_CLOCK_GETRES: usize = 114;
+const SYSCALL_CLOCK_NANOSLEEP: usize = 115;
+const SYSCALL_SYSLOG: usize = 116;
 const SYSCALL_YIELD: usize = 124;
 const SYSCALL_KILL: usize = 129;
+const SYSCALL_TGKILL: usize = 131;
+const SYSCALL_RT_SIGSUSPEND: usize = 133;
+const SYSCALL_RT_SIGACTION: usize = 134;
+const SYSCALL_RT_SIGPROCMASK: usize = 135;
+const SYSCALL_RT_SIGPENDING: usize = 136;
+const SYSCALL_RT_SIGTIMEDWAIT: usize = 137;
+const SYSCALL_REBOOT: usize = 142;
 const SYSCALL_GET_TIME: usize = 169;
+const SYSCALL_SETGID: usize = 144;
+const SYSCALL_SETUID: usize = 146;
+const SYSCALL_SETPGID: usize = 154;
+const SYSCALL_GETPGID: usize = 155;
 const SYSCALL_GETPID: usize = 172;
+const SYSCALL_GETUID: usize = 174;
+const SYSCALL_GETGID: usize = 176;
+const SYSCALL_MQ_OPEN: usize = 180;
+const SYSCALL_MQ_UNLINK: usize = 181;
+const SYSCALL_MQ_TIMEDSEND: usize = 182;
+const SYSCALL_MQ_TIMEDRECEIVE: usize = 183;
+const SYSCALL_MQ_GETSETATTR: usize = 185;
+const SYSCALL_SHMGET: usize = 194;
+const SYSCALL_SHMCTL: usize = 195;
+const SYSCALL_SHMAT: usize = 196;
+const SYSCALL_SHMDT: usize = 197;
+const SYSCALL_SOCKET: usize = 198;
+const SYSCALL_SOCKETPAIR: usize = 199;
+const SYSCALL_BIND: usize = 200;
+const SYSCALL_LISTEN: usize = 201;
+const SYSCALL_ACCEPT: usize = 202;
+const SYSCALL_CONNECT: usize = 203;
+const SYSCALL_GETSOCKNAME: usize = 204;
+const SYSCALL_GETPEERNAME: usize = 205;
+const SYSCALL_SENDTO: usize = 206;
+const SYSCALL_RECVFROM: usize = 207;
+const SYSCALL_SETSOCKOPT: usize = 208;
+const SYSCALL_GETSOCKOPT: usize = 209;
+const SYSCALL_SHUTDOWN: usize = 210;
+const SYSCALL_SENDMSG: usize = 211;
+const SYSCALL_RECVMSG: usize = 212;
+const SYSCALL_ACCEPT4: usize = 242;
+const SYSCALL_BRK: usize = 214;
+const SYSCALL_MUNMAP: usize = 215;
 const SYSCALL_FORK: usize = 220;
 const SYSCALL_EXEC: usize = 221;
+const SYSCALL_MMAP: usize = 222;
+const SYSCALL_MPROTECT: usize = 226;
+const SYSCALL_MLOCK: usize = 228;
+const SYSCALL_MUNLOCK: usize = 229;
+const SYSCALL_MLOCKALL: usize = 230;
+const SYSCALL_MUNLOCKALL: usize = 231;
+const SYSCALL_MADVISE: usize = 233;
 const SYSCALL_WAITPID: usize = 260;
+const SYSCALL_PRLIMIT: usize = 261;
+const SYSCALL_SPAWN: usize = 400;
+const SYSCALL_MEMINFO: usize = 410;
+const SYSCALL_VMSTAT: usize = 411;
+const SYSCALL_IFCONFIG: usize = 420;
+const SYSCALL_GETADDRINFO: usize = 421;
+const SYSCALL_NETBENCH: usize = 422;
+const SYSCALL_PIDFD_SEND_SIGNAL: usize = 424;
+const SYSCALL_RING_CREATE: usize = 430;
+const SYSCALL_ENDPOINT_CREATE: usize = 431;
+const SYSCALL_IPC_CALL: usize = 432;
+const SYSCALL_IPC_REPLY_RECV: usize = 433;
+const SYSCALL_PIDFD_OPEN: usize = 434;
+const SYSCALL_TRACE_CTL: usize = 440;
+const SYSCALL_FAULT_INJECT: usize = 441;
+const SYSCALL_REPLAY: usize = 442;
+const SYSCALL_PERF_READ: usize = 443;
+const SYSCALL_OBJSTAT: usize = 444;
+const SYSCALL_SUSPEND: usize = 445;
+const SYSCALL_CAPGET: usize = 446;
+const SYSCALL_CAPSET: usize = 447;
 const SYSCALL_THREAD_CREATE: usize = 1000;
 const SYSCALL_GETTID: usize = 1001;
 const SYSCALL_WAITTID: usize = 1002;
 const SYSCALL_MUTEX_CREATE: usize = 1010;
 const SYSCALL_MUTEX_LOCK: usize = 1011;
 const SYSCALL_MUTEX_UNLOCK: usize = 1012;
+const SYSCALL_MUTEX_OPEN: usize = 1013;
 const SYSCALL_SEMAPHORE_CREATE: usize = 1020;
 const SYSCALL_SEMAPHORE_UP: usize = 1021;
 const SYSCALL_SEMAPHORE_DOWN: usize = 1022;
 const SYSCALL_CONDVAR_CREATE: usize = 1030;
 const SYSCALL_CONDVAR_SIGNAL: usize = 1031;
 const SYSCALL_CONDVAR_WAIT: usize = 1032;
+const SYSCALL_CONDVAR_OPEN: usize = 1033;
+const SYSCALL_SYNC_UNLINK: usize = 1040;
 const SYSCALL_FRAMEBUFFER: usize = 2000;
 const SYSCALL_FRAMEBUFFER_FLUSH: usize = 2001;
 const SYSCALL_EVENT_GET: usize = 3000;
@@ -47,24 +176,442 @@ fn syscall(id: usize, args: [usize; 3]) -> isize {
     ret
 }
 
+fn syscall6(id: usize, args: [usize; 6]) -> isize {
+    let mut ret: isize;
+    unsafe {
+        core::arch::asm!(
+            "ecall",
+            inlateout("x10") args[0] => ret,
+            in("x11") args[1],
+            in("x12") args[2],
+            in("x13") args[3],
+            in("x14") args[4],
+            in("x15") args[5],
+            in("x17") id
+        );
+    }
+    ret
+}
+
 pub fn sys_dup(fd: usize) -> isize {
     syscall(SYSCALL_DUP, [fd, 0, 0])
 }
 
-pub fn sys_connect(dest: u32, sport: u16, dport: u16) -> isize {
+pub fn sys_dup3(old_fd: usize, new_fd: usize, flags: u32) -> isize {
+    syscall(SYSCALL_DUP3, [old_fd, new_fd, flags as usize])
+}
+
+pub fn sys_fcntl(fd: usize, cmd: usize, arg: usize) -> isize {
+    syscall(SYSCALL_FCNTL, [fd, cmd, arg])
+}
+
+pub fn sys_inotify_init1(flags: u32) -> isize {
+    syscall(SYSCALL_INOTIFY_INIT1, [flags as usize, 0, 0])
+}
+
+pub fn sys_inotify_add_watch(fd: usize, path: &str, mask: u32) -> isize {
+    syscall(
+        SYSCALL_INOTIFY_ADD_WATCH,
+        [fd, path.as_ptr() as usize, mask as usize],
+    )
+}
+
+pub fn sys_inotify_rm_watch(fd: usize, wd: i32) -> isize {
+    syscall(SYSCALL_INOTIFY_RM_WATCH, [fd, wd as usize, 0])
+}
+
+pub fn sys_ppoll(fds: &mut [PollFd], timeout: Option<&TimeSpec>) -> isize {
+    syscall6(
+        SYSCALL_PPOLL,
+        [
+            fds.as_mut_ptr() as usize,
+            fds.len(),
+            timeout.map_or(0, |timeout| timeout as *const TimeSpec as usize),
+            0,
+            0,
+            0,
+        ],
+    )
+}
+
+pub fn sys_epoll_create1(flags: u32) -> isize {
+    syscall(SYSCALL_EPOLL_CREATE1, [flags as usize, 0, 0])
+}
+
+pub fn sys_epoll_ctl(epfd: usize, op: usize, fd: usize, event: &EpollEvent) -> isize {
+    syscall6(
+        SYSCALL_EPOLL_CTL,
+        [epfd, op, fd, event as *const EpollEvent as usize, 0, 0],
+    )
+}
+
+pub fn sys_epoll_pwait(epfd: usize, events: &mut [EpollEvent], timeout: isize) -> isize {
+    syscall6(
+        SYSCALL_EPOLL_PWAIT,
+        [
+            epfd,
+            events.as_mut_ptr() as usize,
+            events.len(),
+            timeout as usize,
+            0,
+            0,
+        ],
+    )
+}
+
+pub fn sys_eventfd2(initval: u32, flags: u32) -> isize {
+    syscall(SYSCALL_EVENTFD2, [initval as usize, flags as usize, 0])
+}
+
+pub fn sys_signalfd4(fd: isize, mask: &SignalFlags, flags: u32) -> isize {
+    syscall6(
+        SYSCALL_SIGNALFD4,
+        [
+            fd as usize,
+            mask as *const _ as usize,
+            size_of::<SignalFlags>(),
+            flags as usize,
+            0,
+            0,
+        ],
+    )
+}
+
+pub fn sys_timerfd_create(clockid: usize, flags: u32) -> isize {
+    syscall(SYSCALL_TIMERFD_CREATE, [clockid, flags as usize, 0])
+}
+
+pub fn sys_timerfd_settime(
+    fd: usize,
+    flags: u32,
+    new: &ITimerSpec,
+    old: Option<&mut ITimerSpec>,
+) -> isize {
+    syscall6(
+        SYSCALL_TIMERFD_SETTIME,
+        [
+            fd,
+            flags as usize,
+            new as *const ITimerSpec as usize,
+            old.map_or(0, |old| old as *mut ITimerSpec as usize),
+            0,
+            0,
+        ],
+    )
+}
+
+pub fn sys_timerfd_gettime(fd: usize, curr: &mut ITimerSpec) -> isize {
+    syscall(
+        SYSCALL_TIMERFD_GETTIME,
+        [fd, curr as *mut ITimerSpec as usize, 0],
+    )
+}
+
+pub fn sys_getitimer(which: usize, curr: &mut ITimerVal) -> isize {
+    syscall(
+        SYSCALL_GETITIMER,
+        [which, curr as *mut ITimerVal as usize, 0],
+    )
+}
+
+pub fn sys_setitimer(which: usize, new: &ITimerVal, old: Option<&mut ITimerVal>) -> isize {
+    syscall(
+        SYSCALL_SETITIMER,
+        [
+            which,
+            new as *const ITimerVal as usize,
+            old.map_or(0, |old| old as *mut ITimerVal as usize),
+        ],
+    )
+}
+
+pub fn sys_timer_create(clockid: usize, event: Option<&SigEvent>, timerid: &mut i32) -> isize {
+    syscall(
+        SYSCALL_TIMER_CREATE,
+        [
+            clockid,
+            event.map_or(0, |event| event as *const SigEvent as usize),
+            timerid as *mut i32 as usize,
+        ],
+    )
+}
+
+pub fn sys_timer_gettime(timerid: i32, curr: &mut ITimerSpec) -> isize {
+    syscall(
+        SYSCALL_TIMER_GETTIME,
+        [timerid as usize, curr as *mut ITimerSpec as usize, 0],
+    )
+}
+
+pub fn sys_timer_getoverrun(timerid: i32) -> isize {
+    syscall(SYSCALL_TIMER_GETOVERRUN, [timerid as usize, 0, 0])
+}
+
+pub fn sys_timer_settime(
+    timerid: i32,
+    flags: u32,
+    new: &ITimerSpec,
+    old: Option<&mut ITimerSpec>,
+) -> isize {
+    syscall6(
+        SYSCALL_TIMER_SETTIME,
+        [
+            timerid as usize,
+            flags as usize,
+            new as *const ITimerSpec as usize,
+            old.map_or(0, |old| old as *mut ITimerSpec as usize),
+            0,
+            0,
+        ],
+    )
+}
+
+pub fn sys_timer_delete(timerid: i32) -> isize {
+    syscall(SYSCALL_TIMER_DELETE, [timerid as usize, 0, 0])
+}
+
+pub fn sys_clock_gettime(clockid: usize, tp: &mut TimeSpec) -> isize {
+    syscall(SYSCALL_CLOCK_GETTIME, [clockid, tp as *mut TimeSpec as usize, 0])
+}
+
+pub fn sys_clock_getres(clockid: usize, res: Option<&mut TimeSpec>) -> isize {
+    syscall(
+        SYSCALL_CLOCK_GETRES,
+        [clockid, res.map_or(0, |res| res as *mut TimeSpec as usize), 0],
+    )
+}
+
+pub fn sys_flock(fd: usize, operation: usize) -> isize {
+    syscall(SYSCALL_FLOCK, [fd, operation, 0])
+}
+
+pub fn sys_ioctl(fd: usize, cmd: usize, arg: usize) -> isize {
+    syscall(SYSCALL_IOCTL, [fd, cmd, arg])
+}
+
+pub fn sys_socket(domain: usize, kind: usize, protocol: usize) -> isize {
+    syscall(SYSCALL_SOCKET, [domain, kind, protocol])
+}
+
+pub fn sys_socketpair(domain: usize, kind: usize, protocol: usize, sv: &mut [usize]) -> isize {
+    syscall6(
+        SYSCALL_SOCKETPAIR,
+        [domain, kind, protocol, sv.as_mut_ptr() as usize, 0, 0],
+    )
+}
+
+pub fn sys_bind<A>(fd: usize, addr: &A) -> isize {
+    syscall(
+        SYSCALL_BIND,
+        [fd, addr as *const _ as usize, size_of::<A>()],
+    )
+}
+
+pub fn sys_listen(fd: usize, backlog: usize) -> isize {
+    syscall(SYSCALL_LISTEN, [fd, backlog, 0])
+}
+
+pub fn sys_accept<A>(fd: usize, addr: Option<&mut A>, addrlen: &mut u32) -> isize {
+    syscall(
+        SYSCALL_ACCEPT,
+        [
+            fd,
+            addr.map_or(0, |addr| addr as *mut _ as usize),
+            addrlen as *mut _ as usize,
+        ],
+    )
+}
+
+pub fn sys_accept4<A>(fd: usize, addr: Option<&mut A>, addrlen: &mut u32, flags: usize) -> isize {
+    syscall6(
+        SYSCALL_ACCEPT4,
+        [
+            fd,
+            addr.map_or(0, |addr| addr as *mut _ as usize),
+            addrlen as *mut _ as usize,
+            flags,
+            0,
+            0,
+        ],
+    )
+}
+
+pub fn sys_connect<A>(fd: usize, addr: &A) -> isize {
     syscall(
         SYSCALL_CONNECT,
-        [dest as usize, sport as usize, dport as usize],
+        [fd, addr as *const _ as usize, size_of::<A>()],
     )
 }
 
-// just listen for tcp connections now
-pub fn sys_listen(sport: u16) -> isize {
-    syscall(SYSCALL_LISTEN, [sport as usize, 0, 0])
+pub fn sys_getsockname<A>(fd: usize, addr: &mut A, addrlen: &mut u32) -> isize {
+    syscall(
+        SYSCALL_GETSOCKNAME,
+        [fd, addr as *mut _ as usize, addrlen as *mut _ as usize],
+    )
+}
+
+pub fn sys_getpeername<A>(fd: usize, addr: &mut A, addrlen: &mut u32) -> isize {
+    syscall(
+        SYSCALL_GETPEERNAME,
+        [fd, addr as *mut _ as usize, addrlen as *mut _ as usize],
+    )
+}
+
+pub fn sys_sendto<A>(fd: usize, buf: &[u8], flags: usize, addr: Option<&A>) -> isize {
+    syscall6(
+        SYSCALL_SENDTO,
+        [
+            fd,
+            buf.as_ptr() as usize,
+            buf.len(),
+            flags,
+            addr.map_or(0, |addr| addr as *const _ as usize),
+            size_of::<A>(),
+        ],
+    )
+}
+
+pub fn sys_recvfrom<A>(
+    fd: usize,
+    buf: &mut [u8],
+    flags: usize,
+    addr: Option<&mut A>,
+    addrlen: &mut u32,
+) -> isize {
+    syscall6(
+        SYSCALL_RECVFROM,
+        [
+            fd,
+            buf.as_mut_ptr() as usize,
+            buf.len(),
+            flags,
+            addr.map_or(0, |addr| addr as *mut _ as usize),
+            addrlen as *mut _ as usize,
+        ],
+    )
+}
+
+pub fn sys_sendmsg(fd: usize, msg: &MsgHdr, flags: usize) -> isize {
+    syscall(SYSCALL_SENDMSG, [fd, msg as *const _ as usize, flags])
+}
+
+pub fn sys_recvmsg(fd: usize, msg: &mut MsgHdr, flags: usize) -> isize {
+    syscall(SYSCALL_RECVMSG, [fd, msg as *mut _ as usize, flags])
+}
+
+pub fn sys_setsockopt(fd: usize, level: usize, optname: usize, value: &i32) -> isize {
+    syscall6(
+        SYSCALL_SETSOCKOPT,
+        [
+            fd,
+            level,
+            optname,
+            value as *const _ as usize,
+            size_of::<i32>(),
+            0,
+        ],
+    )
+}
+
+pub fn sys_getsockopt(
+    fd: usize,
+    level: usize,
+    optname: usize,
+    value: &mut i32,
+    len: &mut u32,
+) -> isize {
+    syscall6(
+        SYSCALL_GETSOCKOPT,
+        [
+            fd,
+            level,
+            optname,
+            value as *mut _ as usize,
+            len as *mut _ as usize,
+            0,
+        ],
+    )
+}
+
+pub fn sys_shutdown(fd: usize, how: usize) -> isize {
+    syscall(SYSCALL_SHUTDOWN, [fd, how, 0])
+}
+
+pub fn sys_mkfifo(path: &str, mode: u32) -> isize {
+    syscall(SYSCALL_MKFIFO, [path.as_ptr() as usize, mode as usize, 0])
 }
 
-pub fn sys_accept(socket_fd: usize) -> isize {
-    syscall(SYSCALL_ACCEPT, [socket_fd, 0, 0])
+pub fn sys_mkdir(path: &str) -> isize {
+    syscall(SYSCALL_MKDIR, [path.as_ptr() as usize, 0, 0])
+}
+
+pub fn sys_unlinkat(path: &str, flags: u32) -> isize {
+    syscall(
+        SYSCALL_UNLINKAT,
+        [path.as_ptr() as usize, flags as usize, 0],
+    )
+}
+
+pub fn sys_getcwd(buf: &mut [u8]) -> isize {
+    syscall(SYSCALL_GETCWD, [buf.as_mut_ptr() as usize, buf.len(), 0])
+}
+
+pub fn sys_chdir(path: &str) -> isize {
+    syscall(SYSCALL_CHDIR, [path.as_ptr() as usize, 0, 0])
+}
+
+pub fn sys_chmod(path: &str, mode: u32) -> isize {
+    syscall(SYSCALL_CHMOD, [path.as_ptr() as usize, mode as usize, 0])
+}
+
+pub fn sys_chown(path: &str, uid: u32, gid: u32) -> isize {
+    syscall(
+        SYSCALL_CHOWN,
+        [path.as_ptr() as usize, uid as usize, gid as usize],
+    )
+}
+
+pub fn sys_symlink(target: &str, link_path: &str) -> isize {
+    syscall(
+        SYSCALL_SYMLINK,
+        [target.as_ptr() as usize, link_path.as_ptr() as usize, 0],
+    )
+}
+
+pub fn sys_readlink(path: &str, buf: &mut [u8]) -> isize {
+    syscall(
+        SYSCALL_READLINK,
+        [path.as_ptr() as usize, buf.as_mut_ptr() as usize, buf.len()],
+    )
+}
+
+pub fn sys_link(old_path: &str, new_path: &str) -> isize {
+    syscall(
+        SYSCALL_LINK,
+        [old_path.as_ptr() as usize, new_path.as_ptr() as usize, 0],
+    )
+}
+
+pub fn sys_rename(old_path: &str, new_path: &str) -> isize {
+    syscall(
+        SYSCALL_RENAME,
+        [old_path.as_ptr() as usize, new_path.as_ptr() as usize, 0],
+    )
+}
+
+pub fn sys_umount(target: &str) -> isize {
+    syscall(SYSCALL_UMOUNT2, [target.as_ptr() as usize, 0, 0])
+}
+
+pub fn sys_mount(source: &str, target: &str, fstype: &str) -> isize {
+    syscall(
+        SYSCALL_MOUNT,
+        [
+            source.as_ptr() as usize,
+            target.as_ptr() as usize,
+            fstype.as_ptr() as usize,
+        ],
+    )
 }
 
 pub fn sys_open(path: &str, flags: u32) -> isize {
@@ -75,8 +622,8 @@ pub fn sys_close(fd: usize) -> isize {
     syscall(SYSCALL_CLOSE, [fd, 0, 0])
 }
 
-pub fn sys_pipe(pipe: &mut [usize]) -> isize {
-    syscall(SYSCALL_PIPE, [pipe.as_mut_ptr() as usize, 0, 0])
+pub fn sys_pipe(pipe: &mut [usize], flags: u32) -> isize {
+    syscall(SYSCALL_PIPE, [pipe.as_mut_ptr() as usize, flags as usize, 0])
 }
 
 pub fn sys_read(fd: usize, buffer: &mut [u8]) -> isize {
@@ -90,21 +637,181 @@ pub fn sys_write(fd: usize, buffer: &[u8]) -> isize {
     syscall(SYSCALL_WRITE, [fd, buffer.as_ptr() as usize, buffer.len()])
 }
 
+pub fn sys_readv(fd: usize, iov: &[IoVec]) -> isize {
+    syscall(SYSCALL_READV, [fd, iov.as_ptr() as usize, iov.len()])
+}
+
+pub fn sys_writev(fd: usize, iov: &[IoVec]) -> isize {
+    syscall(SYSCALL_WRITEV, [fd, iov.as_ptr() as usize, iov.len()])
+}
+
+pub fn sys_splice(
+    fd_in: usize,
+    fd_out: usize,
+    off_out: *mut i64,
+    len: usize,
+    flags: usize,
+) -> isize {
+    syscall6(SYSCALL_SPLICE, [fd_in, 0, fd_out, off_out as usize, len, flags])
+}
+
+pub fn sys_getdents64(fd: usize, buffer: &mut [u8]) -> isize {
+    syscall(
+        SYSCALL_GETDENTS64,
+        [fd, buffer.as_mut_ptr() as usize, buffer.len()],
+    )
+}
+
+pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    syscall(SYSCALL_LSEEK, [fd, offset as usize, whence])
+}
+
+pub fn sys_pread64(fd: usize, buffer: &mut [u8], offset: usize) -> isize {
+    syscall6(
+        SYSCALL_PREAD64,
+        [fd, buffer.as_mut_ptr() as usize, buffer.len(), offset, 0, 0],
+    )
+}
+
+pub fn sys_pwrite64(fd: usize, buffer: &[u8], offset: usize) -> isize {
+    syscall6(
+        SYSCALL_PWRITE64,
+        [fd, buffer.as_ptr() as usize, buffer.len(), offset, 0, 0],
+    )
+}
+
+pub fn sys_stat(path: &str, stat: *mut Stat, flags: u32) -> isize {
+    syscall(
+        SYSCALL_STAT,
+        [path.as_ptr() as usize, stat as usize, flags as usize],
+    )
+}
+
+pub fn sys_fstat(fd: usize, stat: *mut Stat) -> isize {
+    syscall(SYSCALL_FSTAT, [fd, stat as usize, 0])
+}
+
+pub fn sys_fsync(fd: usize) -> isize {
+    syscall(SYSCALL_FSYNC, [fd, 0, 0])
+}
+
+pub fn sys_fdatasync(fd: usize) -> isize {
+    syscall(SYSCALL_FDATASYNC, [fd, 0, 0])
+}
+
 pub fn sys_exit(exit_code: i32) -> ! {
     syscall(SYSCALL_EXIT, [exit_code as usize, 0, 0]);
     panic!("sys_exit never returns!");
 }
 
-pub fn sys_sleep(sleep_ms: usize) -> isize {
-    syscall(SYSCALL_SLEEP, [sleep_ms, 0, 0])
+pub fn sys_nanosleep(req: &TimeSpec, rem: Option<&mut TimeSpec>) -> isize {
+    syscall(
+        SYSCALL_NANOSLEEP,
+        [
+            req as *const TimeSpec as usize,
+            rem.map_or(0, |rem| rem as *mut TimeSpec as usize),
+            0,
+        ],
+    )
+}
+
+pub fn sys_clock_nanosleep(
+    clockid: usize,
+    flags: u32,
+    req: &TimeSpec,
+    rem: Option<&mut TimeSpec>,
+) -> isize {
+    syscall6(
+        SYSCALL_CLOCK_NANOSLEEP,
+        [
+            clockid,
+            flags as usize,
+            req as *const TimeSpec as usize,
+            rem.map_or(0, |rem| rem as *mut TimeSpec as usize),
+            0,
+            0,
+        ],
+    )
+}
+
+pub fn sys_syslog(action: usize, buf: *mut u8, len: usize) -> isize {
+    syscall(SYSCALL_SYSLOG, [action, buf as usize, len])
+}
+
+pub fn sys_reboot(magic1: usize, magic2: usize, cmd: usize) -> isize {
+    syscall(SYSCALL_REBOOT, [magic1, magic2, cmd])
 }
 
 pub fn sys_yield() -> isize {
     syscall(SYSCALL_YIELD, [0, 0, 0])
 }
 
-pub fn sys_kill(pid: usize, signal: i32) -> isize {
-    syscall(SYSCALL_KILL, [pid, signal as usize, 0])
+pub fn sys_kill(pid: isize, signo: usize) -> isize {
+    syscall(SYSCALL_KILL, [pid as usize, signo, 0])
+}
+
+pub fn sys_tgkill(tgid: usize, tid: usize, signo: usize) -> isize {
+    syscall(SYSCALL_TGKILL, [tgid, tid, signo])
+}
+
+pub fn sys_rt_sigaction(signo: usize, act: *const SigAction, oldact: *mut SigAction) -> isize {
+    syscall6(
+        SYSCALL_RT_SIGACTION,
+        [
+            signo,
+            act as usize,
+            oldact as usize,
+            size_of::<SignalFlags>(),
+            0,
+            0,
+        ],
+    )
+}
+
+pub fn sys_rt_sigprocmask(how: usize, set: *const SignalFlags, oldset: *mut SignalFlags) -> isize {
+    syscall6(
+        SYSCALL_RT_SIGPROCMASK,
+        [
+            how,
+            set as usize,
+            oldset as usize,
+            size_of::<SignalFlags>(),
+            0,
+            0,
+        ],
+    )
+}
+
+pub fn sys_rt_sigpending(set: &mut SignalFlags) -> isize {
+    syscall(
+        SYSCALL_RT_SIGPENDING,
+        [set as *mut _ as usize, size_of::<SignalFlags>(), 0],
+    )
+}
+
+pub fn sys_rt_sigsuspend(mask: &SignalFlags) -> isize {
+    syscall(
+        SYSCALL_RT_SIGSUSPEND,
+        [mask as *const _ as usize, size_of::<SignalFlags>(), 0],
+    )
+}
+
+pub fn sys_rt_sigtimedwait(
+    set: &SignalFlags,
+    info: *mut SigInfo,
+    timeout: Option<&TimeSpec>,
+) -> isize {
+    syscall6(
+        SYSCALL_RT_SIGTIMEDWAIT,
+        [
+            set as *const _ as usize,
+            info as usize,
+            timeout.map_or(0, |timeout| timeout as *const TimeSpec as usize),
+            size_of::<SignalFlags>(),
+            0,
+            0,
+        ],
+    )
 }
 
 pub fn sys_get_time() -> isize {
@@ -115,19 +822,316 @@ pub fn sys_getpid() -> isize {
     syscall(SYSCALL_GETPID, [0, 0, 0])
 }
 
+pub fn sys_getuid() -> isize {
+    syscall(SYSCALL_GETUID, [0, 0, 0])
+}
+
+pub fn sys_getgid() -> isize {
+    syscall(SYSCALL_GETGID, [0, 0, 0])
+}
+
+pub fn sys_setuid(uid: u32) -> isize {
+    syscall(SYSCALL_SETUID, [uid as usize, 0, 0])
+}
+
+pub fn sys_setgid(gid: u32) -> isize {
+    syscall(SYSCALL_SETGID, [gid as usize, 0, 0])
+}
+
+pub fn sys_setpgid(pid: usize, pgid: usize) -> isize {
+    syscall(SYSCALL_SETPGID, [pid, pgid, 0])
+}
+
+pub fn sys_getpgid(pid: usize) -> isize {
+    syscall(SYSCALL_GETPGID, [pid, 0, 0])
+}
+
 pub fn sys_fork() -> isize {
     syscall(SYSCALL_FORK, [0, 0, 0])
 }
 
-pub fn sys_exec(path: &str, args: &[*const u8]) -> isize {
+pub fn sys_exec(path: &str, args: &[*const u8], envp: &[*const u8]) -> isize {
     syscall(
         SYSCALL_EXEC,
-        [path.as_ptr() as usize, args.as_ptr() as usize, 0],
+        [
+            path.as_ptr() as usize,
+            args.as_ptr() as usize,
+            envp.as_ptr() as usize,
+        ],
+    )
+}
+
+pub fn sys_spawn(path: &str, args: &[*const u8], envp: &[*const u8]) -> isize {
+    syscall(
+        SYSCALL_SPAWN,
+        [
+            path.as_ptr() as usize,
+            args.as_ptr() as usize,
+            envp.as_ptr() as usize,
+        ],
+    )
+}
+
+pub fn sys_mq_open(name: &str, flags: u32, mode: u32, attr: Option<&MqAttr>) -> isize {
+    syscall6(
+        SYSCALL_MQ_OPEN,
+        [
+            name.as_ptr() as usize,
+            flags as usize,
+            mode as usize,
+            attr.map_or(0, |attr| attr as *const MqAttr as usize),
+            0,
+            0,
+        ],
+    )
+}
+
+pub fn sys_mq_unlink(name: &str) -> isize {
+    syscall(SYSCALL_MQ_UNLINK, [name.as_ptr() as usize, 0, 0])
+}
+
+pub fn sys_mq_timedsend(
+    mqdes: usize,
+    msg: &[u8],
+    prio: u32,
+    abs_timeout: Option<&TimeSpec>,
+) -> isize {
+    syscall6(
+        SYSCALL_MQ_TIMEDSEND,
+        [
+            mqdes,
+            msg.as_ptr() as usize,
+            msg.len(),
+            prio as usize,
+            abs_timeout.map_or(0, |timeout| timeout as *const TimeSpec as usize),
+            0,
+        ],
+    )
+}
+
+pub fn sys_mq_timedreceive(
+    mqdes: usize,
+    buf: &mut [u8],
+    prio: Option<&mut u32>,
+    abs_timeout: Option<&TimeSpec>,
+) -> isize {
+    syscall6(
+        SYSCALL_MQ_TIMEDRECEIVE,
+        [
+            mqdes,
+            buf.as_mut_ptr() as usize,
+            buf.len(),
+            prio.map_or(0, |prio| prio as *mut u32 as usize),
+            abs_timeout.map_or(0, |timeout| timeout as *const TimeSpec as usize),
+            0,
+        ],
+    )
+}
+
+pub fn sys_mq_getsetattr(mqdes: usize, new: Option<&MqAttr>, old: Option<&mut MqAttr>) -> isize {
+    syscall(
+        SYSCALL_MQ_GETSETATTR,
+        [
+            mqdes,
+            new.map_or(0, |new| new as *const MqAttr as usize),
+            old.map_or(0, |old| old as *mut MqAttr as usize),
+        ],
+    )
+}
+
+pub fn sys_ring_create(
+    entries: usize,
+    entry_size: usize,
+    flags: u32,
+    fds: &mut [usize; 2],
+) -> isize {
+    syscall6(
+        SYSCALL_RING_CREATE,
+        [entries, entry_size, flags as usize, fds.as_mut_ptr() as usize, 0, 0],
     )
 }
 
-pub fn sys_waitpid(pid: isize, exit_code: *mut i32) -> isize {
-    syscall(SYSCALL_WAITPID, [pid as usize, exit_code as usize, 0])
+pub fn sys_endpoint_create(flags: u32) -> isize {
+    syscall(SYSCALL_ENDPOINT_CREATE, [flags as usize, 0, 0])
+}
+
+pub fn sys_ipc_call(ep: usize, msg: &IpcMsg, reply: &mut IpcMsg) -> isize {
+    syscall(
+        SYSCALL_IPC_CALL,
+        [ep, msg as *const IpcMsg as usize, reply as *mut IpcMsg as usize],
+    )
+}
+
+pub fn sys_ipc_reply_recv(ep: usize, reply: Option<&IpcMsg>, msg: Option<&mut IpcMsg>) -> isize {
+    syscall(
+        SYSCALL_IPC_REPLY_RECV,
+        [
+            ep,
+            reply.map_or(0, |reply| reply as *const IpcMsg as usize),
+            msg.map_or(0, |msg| msg as *mut IpcMsg as usize),
+        ],
+    )
+}
+
+pub fn sys_shmget(key: usize, size: usize, shmflg: usize) -> isize {
+    syscall(SYSCALL_SHMGET, [key, size, shmflg])
+}
+
+pub fn sys_shmctl(shmid: usize, cmd: usize, buf: usize) -> isize {
+    syscall(SYSCALL_SHMCTL, [shmid, cmd, buf])
+}
+
+pub fn sys_shmat(shmid: usize, shmaddr: usize, shmflg: usize) -> isize {
+    syscall(SYSCALL_SHMAT, [shmid, shmaddr, shmflg])
+}
+
+pub fn sys_shmdt(shmaddr: usize) -> isize {
+    syscall(SYSCALL_SHMDT, [shmaddr, 0, 0])
+}
+
+pub fn sys_brk(addr: usize) -> isize {
+    syscall(SYSCALL_BRK, [addr, 0, 0])
+}
+
+pub fn sys_mmap(
+    addr: usize,
+    len: usize,
+    prot: usize,
+    flags: usize,
+    fd: usize,
+    offset: usize,
+) -> isize {
+    syscall6(SYSCALL_MMAP, [addr, len, prot, flags, fd, offset])
+}
+
+pub fn sys_munmap(addr: usize, len: usize) -> isize {
+    syscall(SYSCALL_MUNMAP, [addr, len, 0])
+}
+
+pub fn sys_mprotect(addr: usize, len: usize, prot: usize) -> isize {
+    syscall(SYSCALL_MPROTECT, [addr, len, prot])
+}
+
+pub fn sys_mlock(addr: usize, len: usize) -> isize {
+    syscall(SYSCALL_MLOCK, [addr, len, 0])
+}
+
+pub fn sys_munlock(addr: usize, len: usize) -> isize {
+    syscall(SYSCALL_MUNLOCK, [addr, len, 0])
+}
+
+pub fn sys_mlockall(flags: usize) -> isize {
+    syscall(SYSCALL_MLOCKALL, [flags, 0, 0])
+}
+
+pub fn sys_munlockall() -> isize {
+    syscall(SYSCALL_MUNLOCKALL, [0; 3])
+}
+
+pub fn sys_madvise(addr: usize, len: usize, advice: usize) -> isize {
+    syscall(SYSCALL_MADVISE, [addr, len, advice])
+}
+
+pub fn sys_meminfo(info: *mut MemInfo) -> isize {
+    syscall(SYSCALL_MEMINFO, [info as usize, 0, 0])
+}
+
+pub fn sys_vmstat(pid: usize, stat: *mut VmStat) -> isize {
+    syscall(SYSCALL_VMSTAT, [pid, stat as usize, 0])
+}
+
+pub fn sys_ifconfig(name: &str, req: *mut IfReq, op: usize) -> isize {
+    syscall(SYSCALL_IFCONFIG, [name.as_ptr() as usize, req as usize, op])
+}
+
+pub fn sys_netbench(op: usize, addr: &SockAddrIn, secs: usize) -> isize {
+    syscall(SYSCALL_NETBENCH, [op, addr as *const _ as usize, secs])
+}
+
+pub fn sys_getaddrinfo(name: &str, addrs: &mut [[u8; 4]]) -> isize {
+    syscall(
+        SYSCALL_GETADDRINFO,
+        [
+            name.as_ptr() as usize,
+            addrs.as_mut_ptr() as usize,
+            addrs.len(),
+        ],
+    )
+}
+
+pub fn sys_waitpid(pid: isize, exit_code: *mut i32, options: usize) -> isize {
+    syscall(SYSCALL_WAITPID, [pid as usize, exit_code as usize, options])
+}
+
+pub fn sys_waitid(idtype: usize, id: usize, info: Option<&mut SigInfo>, options: usize) -> isize {
+    syscall6(
+        SYSCALL_WAITID,
+        [
+            idtype,
+            id,
+            info.map_or(0, |info| info as *mut SigInfo as usize),
+            options,
+            0,
+            0,
+        ],
+    )
+}
+
+pub fn sys_pidfd_open(pid: usize, flags: u32) -> isize {
+    syscall(SYSCALL_PIDFD_OPEN, [pid, flags as usize, 0])
+}
+
+pub fn sys_trace_ctl(op: usize) -> isize {
+    syscall(SYSCALL_TRACE_CTL, [op, 0, 0])
+}
+
+pub fn sys_fault_inject(point: usize, interval: usize, delay_ms: usize) -> isize {
+    syscall(SYSCALL_FAULT_INJECT, [point, interval, delay_ms])
+}
+
+pub fn sys_replay(op: usize, buf: *mut u8, len: usize, from: usize) -> isize {
+    syscall6(SYSCALL_REPLAY, [op, buf as usize, len, from, 0, 0])
+}
+
+pub fn sys_perf_read(target: usize, counts: *mut PerfCounts) -> isize {
+    syscall(SYSCALL_PERF_READ, [target, counts as usize, 0])
+}
+
+pub fn sys_objstat(stats: *mut ObjStat, count: usize, mark: usize) -> isize {
+    syscall(SYSCALL_OBJSTAT, [stats as usize, count, mark])
+}
+
+pub fn sys_suspend(timeout_ms: usize) -> isize {
+    syscall(SYSCALL_SUSPEND, [timeout_ms, 0, 0])
+}
+
+pub fn sys_capget() -> isize {
+    syscall(SYSCALL_CAPGET, [0, 0, 0])
+}
+
+pub fn sys_capset(caps: u32) -> isize {
+    syscall(SYSCALL_CAPSET, [caps as usize, 0, 0])
+}
+
+/// Syscall `id` with `args` as they are, for fuzzers.
+pub fn sys_raw(id: usize, args: [usize; 6]) -> isize {
+    syscall6(id, args)
+}
+
+pub fn sys_pidfd_send_signal(pidfd: usize, signo: usize) -> isize {
+    syscall6(SYSCALL_PIDFD_SEND_SIGNAL, [pidfd, signo, 0, 0, 0, 0])
+}
+
+pub fn sys_prlimit(
+    pid: usize,
+    resource: usize,
+    new_limit: *const RLimit,
+    old_limit: *mut RLimit,
+) -> isize {
+    syscall6(
+        SYSCALL_PRLIMIT,
+        [pid, resource, new_limit as usize, old_limit as usize, 0, 0],
+    )
 }
 
 pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
@@ -154,6 +1158,10 @@ pub fn sys_mutex_unlock(id: usize) -> isize {
     syscall(SYSCALL_MUTEX_UNLOCK, [id, 0, 0])
 }
 
+pub fn sys_mutex_open(name: &str, blocking: bool) -> isize {
+    syscall(SYSCALL_MUTEX_OPEN, [name.as_ptr() as usize, blocking as usize, 0])
+}
+
 pub fn sys_semaphore_create(res_count: usize) -> isize {
     syscall(SYSCALL_SEMAPHORE_CREATE, [res_count, 0, 0])
 }
@@ -178,6 +1186,28 @@ pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
     syscall(SYSCALL_CONDVAR_WAIT, [condvar_id, mutex_id, 0])
 }
 
+pub fn sys_condvar_open(name: &str) -> isize {
+    syscall(SYSCALL_CONDVAR_OPEN, [name.as_ptr() as usize, 0, 0])
+}
+
+pub fn sys_sync_unlink(name: &str) -> isize {
+    syscall(SYSCALL_SYNC_UNLINK, [name.as_ptr() as usize, 0, 0])
+}
+
+pub fn sys_futex(uaddr: &AtomicU32, op: usize, val: u32, timeout: Option<&TimeSpec>) -> isize {
+    syscall6(
+        SYSCALL_FUTEX,
+        [
+            uaddr as *const AtomicU32 as usize,
+            op,
+            val as usize,
+            timeout.map_or(0, |timeout| timeout as *const TimeSpec as usize),
+            0,
+            0,
+        ],
+    )
+}
+
 pub fn sys_framebuffer() -> isize {
     syscall(SYSCALL_FRAMEBUFFER, [0, 0, 0])
 }
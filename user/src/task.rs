@@ -1,5 +1,20 @@
 use super::*;
 
+pub const RLIMIT_STACK: usize = 3;
+/// The size a core dump may have, 0 for none.
+pub const RLIMIT_CORE: usize = 4;
+pub const RLIMIT_NOFILE: usize = 7;
+pub const RLIMIT_MEMLOCK: usize = 8;
+pub const RLIMIT_AS: usize = 9;
+pub const RLIM_INFINITY: usize = usize::MAX;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RLimit {
+    pub rlim_cur: usize,
+    pub rlim_max: usize,
+}
+
 pub fn exit(exit_code: i32) -> ! {
     sys_exit(exit_code);
 }
@@ -12,16 +27,46 @@ pub fn get_time() -> isize {
 pub fn getpid() -> isize {
     sys_getpid()
 }
+pub fn getuid() -> isize {
+    sys_getuid()
+}
+pub fn getgid() -> isize {
+    sys_getgid()
+}
+/// Root may become any user, others only themselves.
+pub fn setuid(uid: u32) -> isize {
+    sys_setuid(uid)
+}
+pub fn setgid(gid: u32) -> isize {
+    sys_setgid(gid)
+}
+/// Put the process `pid`, the caller if 0, in the group `pgid`, a new one
+/// of its own if 0.
+pub fn setpgid(pid: usize, pgid: usize) -> isize {
+    sys_setpgid(pid, pgid)
+}
+pub fn getpgid(pid: usize) -> isize {
+    sys_getpgid(pid)
+}
 pub fn fork() -> isize {
     sys_fork()
 }
 pub fn exec(path: &str, args: &[*const u8]) -> isize {
-    sys_exec(path, args)
+    sys_exec(path, args, &[core::ptr::null::<u8>()])
+}
+pub fn execve(path: &str, args: &[*const u8], envp: &[*const u8]) -> isize {
+    sys_exec(path, args, envp)
+}
+pub fn spawn(path: &str, args: &[*const u8]) -> isize {
+    sys_spawn(path, args, &[core::ptr::null::<u8>()])
+}
+pub fn spawnve(path: &str, args: &[*const u8], envp: &[*const u8]) -> isize {
+    sys_spawn(path, args, envp)
 }
 
 pub fn wait(exit_code: &mut i32) -> isize {
     loop {
-        match sys_waitpid(-1, exit_code as *mut _) {
+        match sys_waitpid(-1, exit_code as *mut _, 0) {
             -2 => {
                 yield_();
             }
@@ -33,7 +78,7 @@ pub fn wait(exit_code: &mut i32) -> isize {
 
 pub fn waitpid(pid: usize, exit_code: &mut i32) -> isize {
     loop {
-        match sys_waitpid(pid as isize, exit_code as *mut _) {
+        match sys_waitpid(pid as isize, exit_code as *mut _, 0) {
             -2 => {
                 yield_();
             }
@@ -44,25 +89,623 @@ pub fn waitpid(pid: usize, exit_code: &mut i32) -> isize {
 }
 
 pub fn waitpid_nb(pid: usize, exit_code: &mut i32) -> isize {
-    sys_waitpid(pid as isize, exit_code as *mut _)
+    sys_waitpid(pid as isize, exit_code as *mut _, 0)
+}
+
+/// waitpid also returns for a child stopped, once for each stop.
+pub const WUNTRACED: usize = 2;
+
+/// Wait for the child `pid` to exit or stop; `status` is its exit code,
+/// or tells the stop signal if `wifstopped`.
+pub fn waitpid_untraced(pid: usize, status: &mut i32) -> isize {
+    loop {
+        match sys_waitpid(pid as isize, status as *mut _, WUNTRACED) {
+            -2 => {
+                yield_();
+            }
+            exit_pid => return exit_pid,
+        }
+    }
+}
+/// waitid: which children to wait for.
+pub const P_ALL: usize = 0;
+pub const P_PID: usize = 1;
+pub const P_PIDFD: usize = 3;
+/// waitid: return at once if no child has exited.
+pub const WNOHANG: usize = 1;
+/// waitid: wait for children to exit.
+pub const WEXITED: usize = 4;
+/// The `code` of the SigInfo of a child which exited.
+pub const CLD_EXITED: i32 = 1;
+
+/// Wait for a child to exit, and reap it: any of them for P_ALL, the one
+/// of pid `id` for P_PID, or the one the pidfd `id` refers to for P_PIDFD.
+/// `info` tells its pid and exit code, or is zeroed if WNOHANG is in
+/// `options` and none has exited.
+pub fn waitid(idtype: usize, id: usize, info: Option<&mut SigInfo>, options: usize) -> isize {
+    sys_waitid(idtype, id, info, options)
+}
+
+/// A descriptor of the process `pid`, with FD_CLOEXEC, which is readable
+/// for poll once the process has exited.
+pub fn pidfd_open(pid: usize, flags: OpenFlags) -> isize {
+    sys_pidfd_open(pid, flags.bits())
+}
+
+/// Send `signo` to the process `pidfd` refers to, or fail with ESRCH if
+/// it has exited.
+pub fn pidfd_send_signal(pidfd: usize, signo: usize) -> isize {
+    sys_pidfd_send_signal(pidfd, signo)
+}
+
+pub fn wifstopped(status: i32) -> bool {
+    status & 0xff == 0x7f
 }
+pub fn wstopsig(status: i32) -> usize {
+    (status >> 8 & 0xff) as usize
+}
+
+pub const SIGHUP: usize = 1;
+pub const SIGINT: usize = 2;
+pub const SIGQUIT: usize = 3;
+pub const SIGILL: usize = 4;
+pub const SIGTRAP: usize = 5;
+pub const SIGABRT: usize = 6;
+pub const SIGBUS: usize = 7;
+pub const SIGFPE: usize = 8;
+pub const SIGKILL: usize = 9;
+pub const SIGUSR1: usize = 10;
+pub const SIGSEGV: usize = 11;
+pub const SIGUSR2: usize = 12;
+pub const SIGPIPE: usize = 13;
+pub const SIGALRM: usize = 14;
+pub const SIGTERM: usize = 15;
+pub const SIGCHLD: usize = 17;
+pub const SIGCONT: usize = 18;
+pub const SIGSTOP: usize = 19;
+pub const SIGTSTP: usize = 20;
+/// The first real-time signal, which queue up rather than merge.
+pub const SIGRTMIN: usize = 32;
+pub const SIGRTMAX: usize = 64;
 
 bitflags! {
-    pub struct SignalFlags: i32 {
-        const SIGINT    = 1 << 2;
-        const SIGILL    = 1 << 4;
-        const SIGABRT   = 1 << 6;
-        const SIGFPE    = 1 << 8;
-        const SIGSEGV   = 1 << 11;
+    /// A set of signals, signal n being bit n - 1.
+    #[derive(Default)]
+    pub struct SignalFlags: u64 {
+        const SIGHUP    = 1 << 0;
+        const SIGINT    = 1 << 1;
+        const SIGQUIT   = 1 << 2;
+        const SIGILL    = 1 << 3;
+        const SIGTRAP   = 1 << 4;
+        const SIGABRT   = 1 << 5;
+        const SIGBUS    = 1 << 6;
+        const SIGFPE    = 1 << 7;
+        const SIGKILL   = 1 << 8;
+        const SIGUSR1   = 1 << 9;
+        const SIGSEGV   = 1 << 10;
+        const SIGUSR2   = 1 << 11;
+        const SIGPIPE   = 1 << 12;
+        const SIGALRM   = 1 << 13;
+        const SIGTERM   = 1 << 14;
+        const SIGCHLD   = 1 << 16;
+        const SIGCONT   = 1 << 17;
+        const SIGSTOP   = 1 << 18;
+        const SIGTSTP   = 1 << 19;
+        /// SIGRTMIN to SIGRTMAX
+        const SIGRT     = !0 << (SIGRTMIN - 1);
     }
 }
 
-pub fn kill(pid: usize, signal: i32) -> isize {
-    sys_kill(pid, signal)
+impl SignalFlags {
+    pub fn from_signum(signo: usize) -> Self {
+        Self::from_bits_truncate(1 << (signo - 1))
+    }
+}
+
+pub const SIG_DFL: usize = 0;
+pub const SIG_IGN: usize = 1;
+
+/// Syscalls the signal interrupts go on after the handler, rather than
+/// fail with EINTR.
+pub const SA_RESTART: usize = 0x1000_0000;
+/// The signal is not blocked while its handler runs.
+pub const SA_NODEFER: usize = 0x4000_0000;
+/// The action goes back to SIG_DFL once the handler is entered.
+pub const SA_RESETHAND: usize = 0x8000_0000;
+
+pub const SIG_BLOCK: usize = 0;
+pub const SIG_UNBLOCK: usize = 1;
+pub const SIG_SETMASK: usize = 2;
+
+/// si_code of signals sent by kill and tgkill.
+pub const SI_USER: i32 = 0;
+pub const SI_TKILL: i32 = -6;
+/// si_code of signals sent by the kernel, as ITIMER_REAL's SIGALRM.
+pub const SI_KERNEL: i32 = 0x80;
+/// si_code of signals sent by timer_create's timers.
+pub const SI_TIMER: i32 = -2;
+
+/// `handler` is SIG_DFL, SIG_IGN, or the address of a
+/// `extern "C" fn(signo: usize, info: &SigInfo, ucontext: usize)`,
+/// which runs with `mask` blocked besides the signal itself.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SigAction {
+    pub handler: usize,
+    pub flags: usize,
+    pub mask: SignalFlags,
+}
+
+impl SigAction {
+    pub fn new(handler: usize, flags: usize) -> Self {
+        Self {
+            handler,
+            flags,
+            mask: SignalFlags::empty(),
+        }
+    }
+}
+
+/// Who sent a signal to a handler, which timer expired, or where a fault
+/// was.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SigInfo {
+    pub signo: i32,
+    pub errno: i32,
+    pub code: i32,
+    pub pid: i32,
+    pub uid: u32,
+    pub timerid: i32,
+    pub overrun: i32,
+    /// the exit code of a child, as waitid tells
+    pub status: i32,
+    pub addr: usize,
+    /// sigev_value of the timer
+    pub value: usize,
+}
+
+/// The timer of setitimer counting real time, the only one there is.
+pub const ITIMER_REAL: usize = 0;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TimeVal {
+    pub tv_sec: i64,
+    pub tv_usec: i64,
+}
+
+impl TimeVal {
+    pub fn from_ms(ms: usize) -> Self {
+        Self {
+            tv_sec: (ms / 1000) as i64,
+            tv_usec: (ms % 1000 * 1000) as i64,
+        }
+    }
+    pub fn as_ms(&self) -> usize {
+        self.tv_sec as usize * 1000 + self.tv_usec as usize / 1000
+    }
+}
+
+/// The time between expirations of ITIMER_REAL and to the next.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ITimerVal {
+    pub it_interval: TimeVal,
+    pub it_value: TimeVal,
+}
+
+impl ITimerVal {
+    pub fn from_ms(value_ms: usize, interval_ms: usize) -> Self {
+        Self {
+            it_interval: TimeVal::from_ms(interval_ms),
+            it_value: TimeVal::from_ms(value_ms),
+        }
+    }
+}
+
+pub const SIGEV_SIGNAL: i32 = 0;
+pub const SIGEV_NONE: i32 = 1;
+/// The time set is when to expire, not how long from now.
+pub const TIMER_ABSTIME: u32 = 1;
+
+/// How a timer of timer_create tells of expirations: with the signal
+/// `signo` carrying `value` for SIGEV_SIGNAL, or not at all for SIGEV_NONE.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct SigEvent {
+    pub value: usize,
+    pub signo: i32,
+    pub notify: i32,
+    _pad: [i32; 12],
+}
+
+impl SigEvent {
+    pub fn new(notify: i32, signo: usize, value: usize) -> Self {
+        Self {
+            value,
+            signo: signo as i32,
+            notify,
+            _pad: [0; 12],
+        }
+    }
+}
+
+/// Send `signo` to the process `pid`, or to the caller's process group
+/// if it is 0.
+pub fn kill(pid: usize, signo: usize) -> isize {
+    sys_kill(pid as isize, signo)
+}
+/// Send `signo` to the processes of the group `pgid`.
+pub fn killpg(pgid: usize, signo: usize) -> isize {
+    sys_kill(-(pgid as isize), signo)
+}
+/// Send `signo` to the thread `tid` of the process `pid` alone.
+pub fn tgkill(pid: usize, tid: usize, signo: usize) -> isize {
+    sys_tgkill(pid, tid, signo)
+}
+pub fn sigaction(signo: usize, act: Option<&SigAction>, oldact: Option<&mut SigAction>) -> isize {
+    sys_rt_sigaction(
+        signo,
+        act.map_or(core::ptr::null(), |act| act as *const _),
+        oldact.map_or(core::ptr::null_mut(), |act| act as *mut _),
+    )
+}
+/// Change the mask of the calling thread as `how`, SIG_BLOCK,
+/// SIG_UNBLOCK or SIG_SETMASK, says.
+pub fn sigprocmask(
+    how: usize,
+    set: Option<&SignalFlags>,
+    oldset: Option<&mut SignalFlags>,
+) -> isize {
+    sys_rt_sigprocmask(
+        how,
+        set.map_or(core::ptr::null(), |set| set as *const _),
+        oldset.map_or(core::ptr::null_mut(), |set| set as *mut _),
+    )
+}
+/// The signals sent which the calling thread blocks.
+pub fn sigpending(set: &mut SignalFlags) -> isize {
+    sys_rt_sigpending(set)
+}
+/// Wait for a signal with `mask` blocked; always EINTR.
+pub fn sigsuspend(mask: &SignalFlags) -> isize {
+    sys_rt_sigsuspend(mask)
+}
+
+/// Take a signal of `set`, which ought to be blocked, waiting for one for
+/// `timeout_ms` or for ever if None, and return its number; EAGAIN if
+/// none came in time.
+pub fn sigtimedwait(
+    set: &SignalFlags,
+    info: Option<&mut SigInfo>,
+    timeout_ms: Option<usize>,
+) -> isize {
+    let timeout = timeout_ms.map(TimeSpec::from_ms);
+    sys_rt_sigtimedwait(
+        set,
+        info.map_or(core::ptr::null_mut(), |info| info as *mut _),
+        timeout.as_ref(),
+    )
+}
+pub fn sigwaitinfo(set: &SignalFlags, info: Option<&mut SigInfo>) -> isize {
+    sigtimedwait(set, info, None)
+}
+
+/// Arm ITIMER_REAL to send SIGALRM after `new`'s value, then each
+/// interval, or disarm it with a zero value.
+pub fn setitimer(which: usize, new: &ITimerVal, old: Option<&mut ITimerVal>) -> isize {
+    sys_setitimer(which, new, old)
+}
+pub fn getitimer(which: usize, curr: &mut ITimerVal) -> isize {
+    sys_getitimer(which, curr)
+}
+/// SIGALRM in `secs` seconds, or none if 0; the seconds left to the one
+/// set before, if any.
+pub fn alarm(secs: usize) -> usize {
+    let mut old = ITimerVal::default();
+    setitimer(
+        ITIMER_REAL,
+        &ITimerVal::from_ms(secs * 1000, 0),
+        Some(&mut old),
+    );
+    (old.it_value.as_ms() + 999) / 1000
+}
+/// A new timer, disarmed, telling of expirations as `event` says, or with
+/// SIGALRM if None; its id is put in `timerid`.
+pub fn timer_create(clockid: usize, event: Option<&SigEvent>, timerid: &mut i32) -> isize {
+    sys_timer_create(clockid, event, timerid)
+}
+pub fn timer_settime(
+    timerid: i32,
+    flags: u32,
+    new: &ITimerSpec,
+    old: Option<&mut ITimerSpec>,
+) -> isize {
+    sys_timer_settime(timerid, flags, new, old)
+}
+pub fn timer_gettime(timerid: i32, curr: &mut ITimerSpec) -> isize {
+    sys_timer_gettime(timerid, curr)
+}
+/// Expirations which found the timer's signal still queued.
+pub fn timer_getoverrun(timerid: i32) -> isize {
+    sys_timer_getoverrun(timerid)
+}
+pub fn timer_delete(timerid: i32) -> isize {
+    sys_timer_delete(timerid)
+}
+/// The ns since boot, of CLOCK_MONOTONIC or CLOCK_REALTIME alike.
+pub fn clock_gettime(clockid: usize, tp: &mut TimeSpec) -> isize {
+    sys_clock_gettime(clockid, tp)
+}
+/// The resolution of `clockid`, a tick of the timer of the hart.
+pub fn clock_getres(clockid: usize, res: Option<&mut TimeSpec>) -> isize {
+    sys_clock_getres(clockid, res)
 }
 
+/// Sleep `sleep_ms`, the whole of it even if signals are handled meanwhile.
 pub fn sleep(sleep_ms: usize) {
-    sys_sleep(sleep_ms);
+    let mut req = TimeSpec::from_ms(sleep_ms);
+    let mut rem = TimeSpec::default();
+    while nanosleep(&req, Some(&mut rem)) == EINTR {
+        req = rem;
+    }
+}
+/// Sleep for `req`; interrupted by a signal, EINTR with the time left in
+/// `rem`.
+pub fn nanosleep(req: &TimeSpec, rem: Option<&mut TimeSpec>) -> isize {
+    sys_nanosleep(req, rem)
+}
+/// Sleep for `req`, or until it of `clockid` with TIMER_ABSTIME.
+pub fn clock_nanosleep(
+    clockid: usize,
+    flags: u32,
+    req: &TimeSpec,
+    rem: Option<&mut TimeSpec>,
+) -> isize {
+    sys_clock_nanosleep(clockid, flags, req, rem)
+}
+
+pub const SYSLOG_ACTION_READ: usize = 2;
+pub const SYSLOG_ACTION_READ_ALL: usize = 3;
+pub const SYSLOG_ACTION_READ_CLEAR: usize = 4;
+pub const SYSLOG_ACTION_CLEAR: usize = 5;
+pub const SYSLOG_ACTION_CONSOLE_OFF: usize = 6;
+pub const SYSLOG_ACTION_CONSOLE_ON: usize = 7;
+pub const SYSLOG_ACTION_CONSOLE_LEVEL: usize = 8;
+pub const SYSLOG_ACTION_SIZE_UNREAD: usize = 9;
+pub const SYSLOG_ACTION_SIZE_BUFFER: usize = 10;
+/// Set the kernel log filter, like `info,net=debug`.
+pub const SYSLOG_ACTION_SET_FILTER: usize = 100;
+
+pub fn syslog(action: usize, buf: &mut [u8]) -> isize {
+    sys_syslog(action, buf.as_mut_ptr(), buf.len())
+}
+/// The last `buf.len()` bytes of the kernel log.
+pub fn dmesg(buf: &mut [u8]) -> isize {
+    sys_syslog(SYSLOG_ACTION_READ_ALL, buf.as_mut_ptr(), buf.len())
+}
+pub fn set_log_filter(spec: &str) -> isize {
+    sys_syslog(SYSLOG_ACTION_SET_FILTER, spec.as_ptr() as *mut u8, spec.len())
+}
+pub fn set_console_loglevel(level: usize) -> isize {
+    sys_syslog(SYSLOG_ACTION_CONSOLE_LEVEL, core::ptr::null_mut(), level)
+}
+
+pub const REBOOT_MAGIC1: usize = 0xfee1_dead;
+pub const REBOOT_MAGIC2: usize = 0x2812_1969;
+pub const REBOOT_CMD_RESTART: usize = 0x0123_4567;
+pub const REBOOT_CMD_HALT: usize = 0xcdef_0123;
+pub const REBOOT_CMD_POWER_OFF: usize = 0x4321_fedc;
+
+/// Sync the disks and restart, halt or power off as `cmd` says; returns
+/// only if it could not.
+pub fn reboot(cmd: usize) -> isize {
+    sys_reboot(REBOOT_MAGIC1, REBOOT_MAGIC2, cmd)
+}
+
+/// Suspend the system until a key is pressed, or `timeout_ms` passed
+/// unless it is 0; the milliseconds it slept.
+pub fn suspend(timeout_ms: usize) -> isize {
+    sys_suspend(timeout_ms)
+}
+
+/// The kernel log, tracing, replay, fault injection, reboot and suspend.
+pub const CAP_RAW_IO: u32 = 1 << 0;
+/// Raw sockets and configuring the interfaces.
+pub const CAP_NET: u32 = 1 << 1;
+/// Signals to the processes of other users.
+pub const CAP_KILL: u32 = 1 << 2;
+/// Mount and umount.
+pub const CAP_MOUNT: u32 = 1 << 3;
+
+/// The capabilities of the process, of CAP_*.
+pub fn capget() -> u32 {
+    sys_capget() as u32
+}
+/// Keep only the capabilities of `caps`; -1 if it has not all of them, as
+/// none dropped can be taken back.
+pub fn capset(caps: u32) -> isize {
+    sys_capset(caps)
+}
+
+/// Have a child, which has become an ordinary user and so has no
+/// capabilities, call `f` and see it refused with EPERM; for tests of
+/// what only root may do.
+pub fn assert_root_only(f: impl FnOnce() -> isize) {
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(setuid(1000), 0);
+        assert_eq!(f(), EPERM);
+        exit(0);
+    }
+    let mut exit_code = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+}
+
+pub const TRACE_STOP: usize = 0;
+pub const TRACE_START: usize = 1;
+
+/// Start tracing the kernel afresh.
+pub fn trace_start() -> isize {
+    sys_trace_ctl(TRACE_START)
+}
+/// Stop tracing, for /proc/trace to give the trace; how many records it has.
+pub fn trace_stop() -> isize {
+    sys_trace_ctl(TRACE_STOP)
+}
+
+/// Allocations of frames, which fail.
+pub const FAULT_ALLOC: usize = 0;
+/// Requests to disks, which fail, to be tried again, or are delayed.
+pub const FAULT_BLOCK: usize = 1;
+/// Packets sent or received, which are dropped.
+pub const FAULT_PACKET: usize = 2;
+/// Allocations of buffers as large as a syscall is asked for, which fail
+/// with ENOMEM.
+pub const FAULT_HEAP: usize = 3;
+
+/// Fault every `interval`th event at `point` from now on, delaying rather
+/// than failing it by `delay_ms` if not 0, or none if `interval` is 0; how
+/// many faults were injected since it was last armed, or ENOSYS if the
+/// kernel was built without `fault_inject`.
+pub fn fault_inject(point: usize, interval: usize, delay_ms: usize) -> isize {
+    sys_fault_inject(point, interval, delay_ms)
+}
+
+const REPLAY_STOP: usize = 0;
+const REPLAY_RECORD: usize = 1;
+const REPLAY_READ: usize = 2;
+const REPLAY_LOAD: usize = 3;
+/// Events a recording holds at most.
+pub const REPLAY_MAX_EVENTS: usize = 1 << 16;
+
+/// An event of a recording of the scheduling, as the kernel logs it.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct ReplayEvent {
+    pub kind: usize,
+    pub a: usize,
+    pub b: usize,
+}
+
+/// Record the scheduling of the kernel from the next syscall on.
+pub fn replay_record() -> isize {
+    sys_replay(REPLAY_RECORD, core::ptr::null_mut(), 0, 0)
+}
+/// Replay `events` from the next syscall on, for the same syscalls to be
+/// made as when they were recorded.
+pub fn replay_load(events: &[ReplayEvent]) -> isize {
+    sys_replay(
+        REPLAY_LOAD,
+        events.as_ptr() as *mut u8,
+        core::mem::size_of_val(events),
+        0,
+    )
+}
+/// Stop recording or replaying; how many events were recorded or
+/// replayed, or EIO if the replay went other than the recording.
+pub fn replay_stop() -> isize {
+    sys_replay(REPLAY_STOP, core::ptr::null_mut(), 0, 0)
+}
+/// Copy the events recorded from the `from`th on into `events`; how many.
+pub fn replay_read(events: &mut [ReplayEvent], from: usize) -> isize {
+    sys_replay(
+        REPLAY_READ,
+        events.as_mut_ptr() as *mut u8,
+        core::mem::size_of_val(events),
+        from,
+    )
+}
+
+/// The counts of the current thread.
+pub const PERF_THREAD: usize = 0;
+/// The counts of the current process, its threads which exited among them.
+pub const PERF_PROCESS: usize = 1;
+/// Hardware events counted: cache references and misses, branches and
+/// branches mispredicted.
+pub const PERF_EVENTS: usize = 4;
+
+/// What the performance counters counted while a thread or process ran.
+#[repr(C)]
+#[derive(Clone, Copy, Default, Debug)]
+pub struct PerfCounts {
+    pub cycles: u64,
+    pub instret: u64,
+    pub time_ns: u64,
+    pub switches: u64,
+    pub events: [u64; PERF_EVENTS],
+}
+
+/// Read the counts of `target`, PERF_THREAD or PERF_PROCESS, into
+/// `counts`; a mask of the hardware events counted.
+pub fn perf_read(target: usize, counts: &mut PerfCounts) -> isize {
+    sys_perf_read(target, counts as *mut _)
+}
+/// The cycle counter of the hart, whoever ran on it.
+pub fn rdcycle() -> u64 {
+    let cycles: u64;
+    unsafe { core::arch::asm!("rdcycle {}", out(reg) cycles) };
+    cycles
+}
+/// The instructions the hart retired, whoever ran on it.
+pub fn rdinstret() -> u64 {
+    let instret: u64;
+    unsafe { core::arch::asm!("rdinstret {}", out(reg) instret) };
+    instret
+}
+
+pub const OBJ_TASK: usize = 0;
+pub const OBJ_PROCESS: usize = 1;
+pub const OBJ_INODE: usize = 2;
+pub const OBJ_FILE: usize = 3;
+pub const OBJ_BLOCK_CACHE: usize = 4;
+pub const OBJ_KINDS: usize = 5;
+pub const OBJ_NAMES: [&str; OBJ_KINDS] =
+    ["tasks", "processes", "inodes", "files", "block caches"];
+
+/// The counts of a kind of kernel objects.
+#[repr(C)]
+#[derive(Clone, Copy, Default, Debug)]
+pub struct ObjStat {
+    /// alive now
+    pub live: usize,
+    /// made since boot
+    pub made: usize,
+    /// how many more are alive than at the mark
+    pub delta: isize,
+}
+
+/// The counts of the kernel objects of each kind, then the mark they are
+/// told against set to now if `mark`; how many kinds.
+pub fn objstat(stats: &mut [ObjStat; OBJ_KINDS], mark: bool) -> isize {
+    sys_objstat(stats.as_mut_ptr(), OBJ_KINDS, mark as usize)
+}
+
+/// Syscall `id` with `args` as they are, whatever they are, for fuzzers.
+pub fn raw_syscall(id: usize, args: [usize; 6]) -> isize {
+    sys_raw(id, args)
+}
+
+pub fn prlimit(
+    pid: usize,
+    resource: usize,
+    new_limit: Option<&RLimit>,
+    old_limit: Option<&mut RLimit>,
+) -> isize {
+    sys_prlimit(
+        pid,
+        resource,
+        new_limit.map_or(core::ptr::null(), |l| l as *const _),
+        old_limit.map_or(core::ptr::null_mut(), |l| l as *mut _),
+    )
+}
+pub fn getrlimit(resource: usize, rlim: &mut RLimit) -> isize {
+    sys_prlimit(0, resource, core::ptr::null(), rlim as *mut _)
+}
+pub fn setrlimit(resource: usize, rlim: &RLimit) -> isize {
+    sys_prlimit(0, resource, rlim as *const _, core::ptr::null_mut())
 }
 
 pub fn thread_create(entry: usize, arg: usize) -> isize {